@@ -0,0 +1,27 @@
+//! Generates the shell completions of wasmer, for bash, zsh and fish.
+//!
+//! They're written to `OUT_DIR`, or to `WASMER_COMPLETIONS_DIR` when it's
+//! set, which is where packages pick them up from.
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use structopt::clap::Shell;
+use structopt::StructOpt;
+
+include!("src/cli.rs");
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/cli.rs");
+    println!("cargo:rerun-if-env-changed=WASMER_COMPLETIONS_DIR");
+    let dir = match env::var_os("WASMER_COMPLETIONS_DIR").or_else(|| env::var_os("OUT_DIR")) {
+        Some(dir) => PathBuf::from(dir),
+        None => return,
+    };
+    fs::create_dir_all(&dir).expect("can't create the directory of the completions");
+    let mut app = CLIOptions::clap();
+    for &shell in &[Shell::Bash, Shell::Zsh, Shell::Fish] {
+        app.gen_completions("wasmer", shell, dir.clone());
+    }
+}
@@ -207,7 +207,8 @@ impl<'env, 'module, 'isa> FuncEnvironment for FuncEnv<'env, 'module, 'isa> {
             )
         };
 
-        match description.memory_type() {
+        let memory_config = self.env.module.info.memory_config;
+        match memory_config.memory_type(description) {
             mem_type @ MemoryType::Dynamic => {
                 let local_memory_bound = func.create_global_value(ir::GlobalValueData::Load {
                     base: local_memory_ptr,
@@ -219,7 +220,7 @@ impl<'env, 'module, 'isa> FuncEnvironment for FuncEnv<'env, 'module, 'isa> {
                 func.create_heap(ir::HeapData {
                     base: local_memory_base,
                     min_size: (description.minimum.bytes().0 as u64).into(),
-                    offset_guard_size: mem_type.guard_size().into(),
+                    offset_guard_size: memory_config.guard_size(mem_type).into(),
                     style: ir::HeapStyle::Dynamic {
                         bound_gv: local_memory_bound,
                     },
@@ -230,9 +231,9 @@ impl<'env, 'module, 'isa> FuncEnvironment for FuncEnv<'env, 'module, 'isa> {
                 .create_heap(ir::HeapData {
                     base: local_memory_base,
                     min_size: (description.minimum.bytes().0 as u64).into(),
-                    offset_guard_size: mem_type.guard_size().into(),
+                    offset_guard_size: memory_config.guard_size(mem_type).into(),
                     style: ir::HeapStyle::Static {
-                        bound: mem_type.bounds().unwrap().into(),
+                        bound: memory_config.bounds(mem_type).unwrap().into(),
                     },
                     index_type: ir::types::I32,
                 }),
@@ -581,7 +582,7 @@ impl<'env, 'module, 'isa> FuncEnvironment for FuncEnv<'env, 'module, 'isa> {
             ),
         };
 
-        let name_index = match description.memory_type() {
+        let name_index = match self.env.module.info.memory_config.memory_type(description) {
             MemoryType::Dynamic => call_names::DYNAMIC_MEM_GROW,
             MemoryType::Static => call_names::STATIC_MEM_GROW,
             MemoryType::SharedStatic => call_names::SHARED_STATIC_MEM_GROW,
@@ -644,7 +645,7 @@ impl<'env, 'module, 'isa> FuncEnvironment for FuncEnv<'env, 'module, 'isa> {
             ),
         };
 
-        let name_index = match description.memory_type() {
+        let name_index = match self.env.module.info.memory_config.memory_type(description) {
             MemoryType::Dynamic => call_names::DYNAMIC_MEM_SIZE,
             MemoryType::Static => call_names::STATIC_MEM_SIZE,
             MemoryType::SharedStatic => call_names::SHARED_STATIC_MEM_SIZE,
@@ -18,11 +18,13 @@ use target_lexicon::Triple;
 use wasmer_runtime_core::{
     backend::sys::Memory,
     cache::{Cache, Error as CacheError},
+    error::CompileError,
     module::ModuleInfo,
 };
 use wasmer_runtime_core::{
-    backend::{Compiler, Token},
-    error::{CompileError, CompileResult},
+    backend::{Compiler, CompilerConfig, StackConfig, Token},
+    error::CompileResult,
+    features::{validate, Features},
     module::ModuleInner,
 };
 #[cfg(feature = "cache")]
@@ -31,8 +33,6 @@ extern crate serde_derive;
 #[cfg(feature = "cache")]
 extern crate serde;
 
-use wasmparser::{self, WasmDecoder};
-
 pub struct CraneliftCompiler {}
 
 impl CraneliftCompiler {
@@ -43,23 +43,43 @@ impl CraneliftCompiler {
 
 impl Compiler for CraneliftCompiler {
     /// Compiles wasm binary to a wasmer module.
-    fn compile(&self, wasm: &[u8], _: Token) -> CompileResult<ModuleInner> {
-        validate(wasm)?;
+    fn compile(&self, wasm: &[u8], token: Token) -> CompileResult<ModuleInner> {
+        self.compile_with_config(wasm, CompilerConfig::default(), token)
+    }
 
-        let isa = get_isa();
+    fn compile_with_config(
+        &self,
+        wasm: &[u8],
+        config: CompilerConfig,
+        _: Token,
+    ) -> CompileResult<ModuleInner> {
+        validate(wasm, &config.features)?;
+
+        let isa = get_isa_with_stack(&config.stack);
 
         let mut module = module::Module::empty();
+        module.info.memory_config = config.memory;
         let module_env = module_env::ModuleEnv::new(&mut module, &*isa);
 
         let func_bodies = module_env.translate(wasm)?;
 
-        module.compile(&*isa, func_bodies)
+        module.compile(&*isa, func_bodies, config.code_hook.as_ref(), &config.stack)
     }
 
     /// Create a wasmer Module from an already-compiled cache.
     #[cfg(feature = "cache")]
     unsafe fn from_cache(&self, cache: Cache, _: Token) -> Result<ModuleInner, CacheError> {
-        module::Module::from_cache(cache)
+        module::Module::from_cache(cache, None)
+    }
+
+    #[cfg(feature = "cache")]
+    unsafe fn from_cache_with_config(
+        &self,
+        cache: Cache,
+        config: &CompilerConfig,
+        _: Token,
+    ) -> Result<ModuleInner, CacheError> {
+        module::Module::from_cache(cache, config.code_hook.as_ref())
     }
 
     #[cfg(feature = "cache")]
@@ -68,7 +88,7 @@ impl Compiler for CraneliftCompiler {
         wasm: &[u8],
         _: Token,
     ) -> CompileResult<(Box<ModuleInfo>, Vec<u8>, Memory)> {
-        validate(wasm)?;
+        validate(wasm, &Features::default())?;
 
         let isa = get_isa();
 
@@ -95,9 +115,19 @@ impl Compiler for CraneliftCompiler {
 }
 
 fn get_isa() -> Box<isa::TargetIsa> {
+    get_isa_with_stack(&StackConfig::default())
+}
+
+fn get_isa_with_stack(stack: &StackConfig) -> Box<isa::TargetIsa> {
     let flags = {
         let mut builder = settings::builder();
         builder.set("opt_level", "best").unwrap();
+        builder
+            .set(
+                "probestack_enabled",
+                if stack.probes { "true" } else { "false" },
+            )
+            .unwrap();
 
         if cfg!(not(test)) {
             builder.set("enable_verifier", "false").unwrap();
@@ -110,19 +140,5 @@ fn get_isa() -> Box<isa::TargetIsa> {
     isa::lookup(Triple::host()).unwrap().finish(flags)
 }
 
-fn validate(bytes: &[u8]) -> CompileResult<()> {
-    let mut parser = wasmparser::ValidatingParser::new(bytes, None);
-    loop {
-        let state = parser.read();
-        match *state {
-            wasmparser::ParserState::EndWasm => break Ok(()),
-            wasmparser::ParserState::Error(err) => Err(CompileError::ValidationError {
-                msg: err.message.to_string(),
-            })?,
-            _ => {}
-        }
-    }
-}
-
 /// The current version of this crate
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -16,8 +16,9 @@ use wasmer_runtime_core::{
     cache::{Cache, Error as CacheError},
 };
 use wasmer_runtime_core::{
-    backend::{Backend, FuncResolver, ProtectedCaller, Token, UserTrapper},
-    error::{CompileResult, RuntimeResult},
+    backend::{Backend, CodeHook, FuncResolver, ProtectedCaller, StackConfig, Token, UserTrapper},
+    error::{CompileError, CompileResult, RuntimeResult},
+    memory::MemoryConfig,
     module::{ModuleInfo, ModuleInner, StringTable},
     structures::{Map, TypedIndex},
     types::{
@@ -90,6 +91,7 @@ impl Module {
                     func_assoc: Map::new(),
                     signatures: Map::new(),
                     backend: Backend::Cranelift,
+                    memory_config: MemoryConfig::default(),
 
                     namespace_table: StringTable::new(),
                     name_table: StringTable::new(),
@@ -102,15 +104,21 @@ impl Module {
         mut self,
         isa: &isa::TargetIsa,
         functions: Map<LocalFuncIndex, ir::Function>,
+        code_hook: Option<&CodeHook>,
+        stack: &StackConfig,
     ) -> CompileResult<ModuleInner> {
         let (func_resolver_builder, handler_data) =
-            FuncResolverBuilder::new(isa, functions, &self.module.info)?;
+            FuncResolverBuilder::new(isa, functions, &self.module.info, stack)?;
 
-        self.module.func_resolver =
-            Box::new(func_resolver_builder.finalize(&self.module.info.signatures)?);
+        let func_resolver = func_resolver_builder.finalize(&self.module.info.signatures)?;
 
         let trampolines = Trampolines::new(isa, &self.module.info);
 
+        check_code(code_hook, &func_resolver, &trampolines)
+            .map_err(|msg| CompileError::InternalError { msg })?;
+
+        self.module.func_resolver = Box::new(func_resolver);
+
         self.module.protected_caller =
             Box::new(Caller::new(&self.module.info, handler_data, trampolines));
 
@@ -124,7 +132,7 @@ impl Module {
         functions: Map<LocalFuncIndex, ir::Function>,
     ) -> CompileResult<(ModuleInfo, BackendCache, Memory)> {
         let (func_resolver_builder, handler_data) =
-            FuncResolverBuilder::new(isa, functions, &self.module.info)?;
+            FuncResolverBuilder::new(isa, functions, &self.module.info, &StackConfig::default())?;
 
         let trampolines = Trampolines::new(isa, &self.module.info);
 
@@ -137,17 +145,22 @@ impl Module {
     }
 
     #[cfg(feature = "cache")]
-    pub fn from_cache(cache: Cache) -> Result<ModuleInner, CacheError> {
+    pub fn from_cache(
+        cache: Cache,
+        code_hook: Option<&CodeHook>,
+    ) -> Result<ModuleInner, CacheError> {
         let (info, compiled_code, backend_cache) = BackendCache::from_cache(cache)?;
 
         let (func_resolver_builder, trampolines, handler_data) =
             FuncResolverBuilder::new_from_backend_cache(backend_cache, compiled_code, &info)?;
 
-        let func_resolver = Box::new(
-            func_resolver_builder
-                .finalize(&info.signatures)
-                .map_err(|e| CacheError::Unknown(format!("{:?}", e)))?,
-        );
+        let func_resolver = func_resolver_builder
+            .finalize(&info.signatures)
+            .map_err(|e| CacheError::Unknown(format!("{:?}", e)))?;
+
+        check_code(code_hook, &func_resolver, &trampolines).map_err(CacheError::Unknown)?;
+
+        let func_resolver = Box::new(func_resolver);
 
         let protected_caller = Box::new(Caller::new(&info, handler_data, trampolines));
 
@@ -159,6 +172,21 @@ impl Module {
     }
 }
 
+/// Runs `code_hook` over the code of the functions and the trampolines,
+/// once they're executable.
+fn check_code(
+    code_hook: Option<&CodeHook>,
+    func_resolver: &crate::resolver::FuncResolver,
+    trampolines: &Trampolines,
+) -> Result<(), String> {
+    if let Some(code_hook) = code_hook {
+        for code in &[func_resolver.code(), trampolines.code()] {
+            code_hook.check(code)?;
+        }
+    }
+    Ok(())
+}
+
 impl Deref for Module {
     type Target = ModuleInner;
 
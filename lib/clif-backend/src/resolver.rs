@@ -26,7 +26,7 @@ use wasmer_runtime_core::{
     backend::{
         self,
         sys::{Memory, Protect},
-        SigRegistry,
+        SigRegistry, StackConfig,
     },
     error::{CompileError, CompileResult},
     module::ModuleInfo,
@@ -54,12 +54,17 @@ impl FuncResolverBuilder {
     #[cfg(feature = "cache")]
     pub fn new_from_backend_cache(
         backend_cache: BackendCache,
-        mut code: Memory,
+        cached_code: Memory,
         info: &ModuleInfo,
     ) -> Result<(Self, Trampolines, HandlerData), CacheError> {
+        // The code is copied out of the memory it was loaded into, which
+        // can't be made executable everywhere.
+        let mut code = Memory::with_size_for_code(cached_code.size())
+            .map_err(|e| CacheError::Unknown(e.to_string()))?;
         unsafe {
             code.protect(.., Protect::ReadWrite)
                 .map_err(|e| CacheError::Unknown(e.to_string()))?;
+            code.as_slice_mut()[..cached_code.size()].copy_from_slice(cached_code.as_slice());
         }
 
         let handler_data =
@@ -102,6 +107,7 @@ impl FuncResolverBuilder {
         isa: &isa::TargetIsa,
         function_bodies: Map<LocalFuncIndex, ir::Function>,
         info: &ModuleInfo,
+        stack: &StackConfig,
     ) -> CompileResult<(Self, HandlerData)> {
         let mut compiled_functions: Vec<Vec<u8>> = Vec::with_capacity(function_bodies.len());
         let mut local_relocs = Map::with_capacity(function_bodies.len());
@@ -113,13 +119,19 @@ impl FuncResolverBuilder {
         let mut ctx = Context::new();
         let mut total_size = 0;
 
-        for (_, func) in function_bodies {
+        for (index, func) in function_bodies {
             ctx.func = func;
             let mut code_buf = Vec::new();
             let mut reloc_sink = RelocSink::new();
 
             ctx.compile_and_emit(isa, &mut code_buf, &mut reloc_sink, &mut local_trap_sink)
                 .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
+            if let Some(frame_hook) = &stack.frame_hook {
+                let frame_size = ctx.func.stack_slots.frame_size.unwrap_or(0);
+                if frame_size > stack.large_frame_size {
+                    frame_hook.report(index, frame_size);
+                }
+            }
             ctx.clear();
 
             // Clear the local trap sink and consolidate all trap info
@@ -134,7 +146,7 @@ impl FuncResolverBuilder {
             external_relocs.push(reloc_sink.external_relocs.into_boxed_slice());
         }
 
-        let mut memory = Memory::with_size(total_size)
+        let mut memory = Memory::with_size_for_code(total_size)
             .map_err(|e| CompileError::InternalError { msg: e.to_string() })?;
         unsafe {
             memory
@@ -326,6 +338,11 @@ pub struct FuncResolver {
 }
 
 impl FuncResolver {
+    /// The machine code of the functions.
+    pub(crate) fn code(&self) -> &[u8] {
+        unsafe { self.memory.as_slice() }
+    }
+
     fn lookup(&self, local_func_index: LocalFuncIndex) -> Option<NonNull<vm::Func>> {
         let offset = *self.map.get(local_func_index)?;
         let ptr = unsafe { self.memory.as_ptr().add(offset) };
@@ -147,6 +147,18 @@ impl ProtectedCaller for Caller {
     fn get_early_trapper(&self) -> Box<dyn UserTrapper> {
         Box::new(Trapper)
     }
+
+    #[cfg(unix)]
+    fn save_trap_state(&self) -> Box<dyn std::any::Any> {
+        Box::new(TrapState::save())
+    }
+
+    #[cfg(unix)]
+    fn restore_trap_state(&self, state: Box<dyn std::any::Any>) {
+        if let Ok(state) = state.downcast::<TrapState>() {
+            state.restore();
+        }
+    }
 }
 
 fn get_func_from_index(
@@ -60,6 +60,27 @@ thread_local! {
     pub static CURRENT_EXECUTABLE_BUFFER: Cell<*const c_void> = Cell::new(ptr::null());
 }
 
+/// The state that catches the traps of the calls in progress on one native
+/// stack of a thread.
+pub struct TrapState {
+    jmp_buf: [c_int; SETJMP_BUFFER_LEN],
+    executable_buffer: *const c_void,
+}
+
+impl TrapState {
+    pub fn save() -> Self {
+        TrapState {
+            jmp_buf: SETJMP_BUFFER.with(|buf| unsafe { *buf.get() }),
+            executable_buffer: CURRENT_EXECUTABLE_BUFFER.with(|cell| cell.get()),
+        }
+    }
+
+    pub fn restore(self) {
+        SETJMP_BUFFER.with(|buf| unsafe { *buf.get() = self.jmp_buf });
+        CURRENT_EXECUTABLE_BUFFER.with(|cell| cell.set(self.executable_buffer));
+    }
+}
+
 pub unsafe fn trigger_trap() -> ! {
     let jmp_buf = SETJMP_BUFFER.with(|buf| buf.get());
 
@@ -245,8 +266,61 @@ unsafe fn get_faulting_addr_and_ip(
     (si_addr, rip as _)
 }
 
+#[cfg(all(target_os = "freebsd", target_arch = "x86_64"))]
+unsafe fn get_faulting_addr_and_ip(
+    siginfo: *const c_void,
+    ucontext: *const c_void,
+) -> (*const c_void, *const c_void) {
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct ucontext_t {
+        uc_sigmask: [u32; 4],
+        uc_mcontext: mcontext_t,
+        // ...
+    }
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct mcontext_t {
+        mc_onstack: i64,
+        mc_rdi: i64,
+        mc_rsi: i64,
+        mc_rdx: i64,
+        mc_rcx: i64,
+        mc_r8: i64,
+        mc_r9: i64,
+        mc_rax: i64,
+        mc_rbx: i64,
+        mc_rbp: i64,
+        mc_r10: i64,
+        mc_r11: i64,
+        mc_r12: i64,
+        mc_r13: i64,
+        mc_r14: i64,
+        mc_r15: i64,
+        mc_trapno: u32,
+        mc_fs: u16,
+        mc_gs: u16,
+        mc_addr: i64,
+        mc_flags: u32,
+        mc_es: u16,
+        mc_ds: u16,
+        mc_err: i64,
+        mc_rip: i64,
+        // ...
+    }
+
+    let siginfo = siginfo as *const siginfo_t;
+    let si_addr = (*siginfo).si_addr;
+
+    let ucontext = ucontext as *const ucontext_t;
+    let rip = (*ucontext).uc_mcontext.mc_rip;
+
+    (si_addr, rip as _)
+}
+
 #[cfg(not(any(
     all(target_os = "macos", target_arch = "x86_64"),
     all(target_os = "linux", target_arch = "x86_64"),
+    all(target_os = "freebsd", target_arch = "x86_64"),
 )))]
-compile_error!("This crate doesn't yet support compiling on operating systems other than linux and macos and architectures other than x86_64");
+compile_error!("This crate doesn't yet support compiling on operating systems other than linux, macos and freebsd and architectures other than x86_64");
@@ -14,6 +14,7 @@ use wasmer_runtime_core::{
 use wasmer_win_exception_handler::CallProtectedData;
 pub use wasmer_win_exception_handler::_call_protected;
 use winapi::shared::minwindef::DWORD;
+use winapi::um::errhandlingapi::RaiseException;
 use winapi::um::minwinbase::{
     EXCEPTION_ACCESS_VIOLATION, EXCEPTION_FLT_DENORMAL_OPERAND, EXCEPTION_FLT_DIVIDE_BY_ZERO,
     EXCEPTION_FLT_INEXACT_RESULT, EXCEPTION_FLT_INVALID_OPERATION, EXCEPTION_FLT_OVERFLOW,
@@ -21,6 +22,10 @@ use winapi::um::minwinbase::{
     EXCEPTION_INT_DIVIDE_BY_ZERO, EXCEPTION_INT_OVERFLOW, EXCEPTION_STACK_OVERFLOW,
 };
 
+/// The code of the exception `trigger_trap` raises, as defined in
+/// `exception_handling.h`.
+const EARLY_TRAP_EXCEPTION_CODE: DWORD = 0xE000_0001;
+
 thread_local! {
     pub static CURRENT_EXECUTABLE_BUFFER: Cell<*const c_void> = Cell::new(ptr::null());
 }
@@ -33,12 +38,6 @@ pub fn call_protected(
     param_vec: *const u64,
     return_vec: *mut u64,
 ) -> RuntimeResult<()> {
-    // TODO: trap early
-    // user code error
-    //    if let Some(msg) = super::TRAP_EARLY_DATA.with(|cell| cell.replace(None)) {
-    //        return Err(RuntimeError::User { msg });
-    //    }
-
     let result = _call_protected(trampoline, ctx, func, param_vec, return_vec);
 
     if let Ok(_) = result {
@@ -51,6 +50,12 @@ pub fn call_protected(
         instructionPointer: instruction_pointer,
     } = result.unwrap_err();
 
+    if signum as DWORD == EARLY_TRAP_EXCEPTION_CODE {
+        if let Some(msg) = super::TRAP_EARLY_DATA.with(|cell| cell.replace(None)) {
+            return Err(RuntimeError::User { msg });
+        }
+    }
+
     if let Some(TrapData {
         trapcode,
         srcloc: _,
@@ -111,6 +116,6 @@ pub fn call_protected(
 }
 
 pub unsafe fn trigger_trap() -> ! {
-    // TODO
-    unimplemented!();
+    RaiseException(EARLY_TRAP_EXCEPTION_CODE, 0, 0, ptr::null());
+    unreachable!("the exception handler resumes in `call_protected`");
 }
@@ -41,7 +41,7 @@ impl Trampolines {
         //     offsets: HashMap<SigIndex, usize>,
         // }
 
-        let mut memory = Memory::with_size(cache.code.len()).unwrap();
+        let mut memory = Memory::with_size_for_code(cache.code.len()).unwrap();
         unsafe {
             memory.protect(.., Protect::ReadWrite).unwrap();
 
@@ -87,6 +87,13 @@ impl Trampolines {
 
         for exported_func_index in func_index_iter {
             let sig_index = module.func_assoc[*exported_func_index];
+            // Exports that share a signature share its trampoline.
+            if compiled_functions
+                .iter()
+                .any(|&(compiled, _)| compiled == sig_index)
+            {
+                continue;
+            }
             let func_sig = &module.signatures[sig_index];
 
             let trampoline_func = generate_func(&func_sig);
@@ -108,7 +115,7 @@ impl Trampolines {
             compiled_functions.push((sig_index, code_buf));
         }
 
-        let mut memory = Memory::with_size(total_size).unwrap();
+        let mut memory = Memory::with_size_for_code(total_size).unwrap();
         unsafe {
             memory.protect(.., Protect::ReadWrite).unwrap();
         }
@@ -142,6 +149,11 @@ impl Trampolines {
         }
     }
 
+    /// The machine code of the trampolines.
+    pub(crate) fn code(&self) -> &[u8] {
+        unsafe { self.memory.as_slice() }
+    }
+
     pub fn lookup(&self, sig_index: SigIndex) -> Option<Trampoline> {
         let offset = *self.offsets.get(&sig_index)?;
         let ptr = unsafe { self.memory.as_ptr().add(offset) };
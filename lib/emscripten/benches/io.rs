@@ -0,0 +1,79 @@
+//! Throughput of the vectored IO syscalls, writing guest buffers to /dev/null.
+#[macro_use]
+extern crate criterion;
+
+use criterion::{Criterion, ParameterizedBenchmark, Throughput};
+use wabt::wat2wasm;
+use wasmer_clif_backend::CraneliftCompiler;
+use wasmer_emscripten::{generate_emscripten_env, EmscriptenGlobals};
+use wasmer_runtime_core::compile_with;
+
+static WAT: &str = r#"
+(module
+  (import "env" "memory" (memory 256))
+  (import "env" "table" (table 0 anyfunc))
+  (import "env" "___syscall146" (func $writev (param i32 i32) (result i32)))
+  (func (export "writev") (param $varargs i32) (result i32)
+    i32.const 146
+    get_local $varargs
+    call $writev))
+"#;
+
+const VARARGS: u32 = 1024;
+const IOVECS: u32 = 2048;
+const BUFFERS: u32 = 65536;
+
+/// A descriptor of the null device, as the guest sees descriptors: the
+/// host's on unix, and the C runtime's on Windows.
+#[cfg(unix)]
+fn open_null() -> i32 {
+    use std::fs::OpenOptions;
+    use std::os::unix::io::IntoRawFd;
+    let file = OpenOptions::new().write(true).open("/dev/null").unwrap();
+    file.into_raw_fd()
+}
+
+#[cfg(windows)]
+fn open_null() -> i32 {
+    let fd = unsafe { libc::open(b"NUL\0".as_ptr() as *const _, libc::O_WRONLY) };
+    assert!(fd >= 0);
+    fd
+}
+
+/// Writes `total` bytes split evenly across `chunks` iovecs.
+fn bench_writev(c: &mut Criterion) {
+    let params: Vec<(u32, u32)> = vec![(4096, 1), (65536, 1), (1 << 20, 1), (1 << 20, 16)];
+    let bench = ParameterizedBenchmark::new(
+        "writev",
+        |b, &(total, chunks)| {
+            let wasm = wat2wasm(WAT).unwrap();
+            let module = compile_with(&wasm, &CraneliftCompiler::new()).unwrap();
+            let mut globals = EmscriptenGlobals::new(&module);
+            let import_object = generate_emscripten_env(&mut globals);
+            let mut instance = module.instantiate(&import_object).unwrap();
+            let dev_null = open_null();
+
+            {
+                let view = instance.context_mut().memory(0).view::<u32>();
+                let words = |offset: u32| (offset / 4) as usize;
+                view[words(VARARGS)].set(dev_null as u32);
+                view[words(VARARGS) + 1].set(IOVECS);
+                view[words(VARARGS) + 2].set(chunks);
+                let chunk = total / chunks;
+                for i in 0..chunks {
+                    view[words(IOVECS) + 2 * i as usize].set(BUFFERS + i * chunk);
+                    view[words(IOVECS) + 2 * i as usize + 1].set(chunk);
+                }
+            }
+
+            let writev = instance.func::<i32, i32>("writev").unwrap();
+            b.iter(|| assert_eq!(writev.call(VARARGS as i32).unwrap(), total as i32));
+        },
+        params,
+    )
+    .throughput(|&(total, _)| Throughput::Bytes(total));
+    c.bench("io", bench);
+}
+
+criterion_group!(benches, bench_writev);
+criterion_main!(benches);
@@ -0,0 +1,34 @@
+//! Guest malloc/free loops, which dominate allocation heavy programs.
+#[macro_use]
+extern crate criterion;
+
+use criterion::{Criterion, ParameterizedBenchmark};
+use wasmer_clif_backend::CraneliftCompiler;
+use wasmer_emscripten::{generate_emscripten_env, EmscriptenGlobals};
+use wasmer_runtime_core::compile_with;
+
+static WASM: &[u8] = include_bytes!("../emtests/env.wasm");
+
+fn bench_malloc_free(c: &mut Criterion) {
+    let sizes: Vec<u32> = vec![16, 4096, 1 << 20];
+    let bench = ParameterizedBenchmark::new(
+        "malloc_free",
+        |b, &size| {
+            let module = compile_with(WASM, &CraneliftCompiler::new()).unwrap();
+            let mut globals = EmscriptenGlobals::new(&module);
+            let import_object = generate_emscripten_env(&mut globals);
+            let instance = module.instantiate(&import_object).unwrap();
+            let malloc = instance.func::<u32, u32>("_malloc").unwrap();
+            let free = instance.func::<u32, ()>("_free").unwrap();
+            b.iter(|| {
+                let ptr = malloc.call(size).unwrap();
+                free.call(ptr).unwrap();
+            })
+        },
+        sizes,
+    );
+    c.bench("emscripten", bench);
+}
+
+criterion_group!(benches, bench_malloc_free);
+criterion_main!(benches);
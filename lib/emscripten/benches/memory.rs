@@ -0,0 +1,59 @@
+//! Throughput of the host side memcpy/memmove/memset imports.
+#[macro_use]
+extern crate criterion;
+
+use criterion::{Criterion, ParameterizedBenchmark, Throughput};
+use wabt::wat2wasm;
+use wasmer_clif_backend::CraneliftCompiler;
+use wasmer_emscripten::{generate_emscripten_env, EmscriptenGlobals};
+use wasmer_runtime_core::compile_with;
+
+static WAT: &str = r#"
+(module
+  (import "env" "memory" (memory 256))
+  (import "env" "table" (table 0 anyfunc))
+  (import "env" "_emscripten_memcpy_big" (func $memcpy (param i32 i32 i32) (result i32)))
+  (import "env" "_memmove" (func $memmove (param i32 i32 i32) (result i32)))
+  (import "env" "_memset" (func $memset (param i32 i32 i32) (result i32)))
+  (func (export "memcpy") (param i32 i32 i32) (result i32)
+    get_local 0 get_local 1 get_local 2 call $memcpy)
+  (func (export "memmove") (param i32 i32 i32) (result i32)
+    get_local 0 get_local 1 get_local 2 call $memmove)
+  (func (export "memset") (param i32 i32 i32) (result i32)
+    get_local 0 get_local 1 get_local 2 call $memset))
+"#;
+
+const SRC: i32 = 1 << 20;
+const DEST: i32 = 4 << 20;
+
+fn bench_memory(c: &mut Criterion) {
+    let sizes: Vec<u32> = vec![4096, 65536, 1 << 20];
+    let bench = ParameterizedBenchmark::new(
+        "memcpy_big",
+        |b, &size| with_func("memcpy", |f| b.iter(|| f(DEST, SRC, size as i32))),
+        sizes,
+    )
+    .with_function("memmove", |b, &size| {
+        // Overlapping ranges take the slow path in a naive memmove.
+        with_func("memmove", |f| b.iter(|| f(SRC + 64, SRC, size as i32)))
+    })
+    .with_function("memset", |b, &size| {
+        with_func("memset", |f| b.iter(|| f(DEST, 0xAA, size as i32)))
+    })
+    .throughput(|&size| Throughput::Bytes(size));
+    c.bench("memory", bench);
+}
+
+/// Instantiates the module and hands `bench` the exported import `name`.
+fn with_func(name: &str, bench: impl FnOnce(&dyn Fn(i32, i32, i32) -> i32)) {
+    let wasm = wat2wasm(WAT).unwrap();
+    let module = compile_with(&wasm, &CraneliftCompiler::new()).unwrap();
+    let mut globals = EmscriptenGlobals::new(&module);
+    let import_object = generate_emscripten_env(&mut globals);
+    let instance = module.instantiate(&import_object).unwrap();
+    let func = instance.func::<(i32, i32, i32), i32>(name).unwrap();
+    bench(&|a, b, c| func.call(a, b, c).unwrap());
+}
+
+criterion_group!(benches, bench_memory);
+criterion_main!(benches);
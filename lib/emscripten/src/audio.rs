@@ -0,0 +1,705 @@
+//! The OpenAL and SDL audio imports, which play nothing unless the
+//! embedder connects an `AudioBackend`, so games and demos run either way.
+//!
+//! OpenAL sources play their buffers through the backend. SDL audio is
+//! pulled from the callback of the guest with `pump_sdl_audio`, which the
+//! host calls whenever its device needs more samples.
+use crate::env::{call_malloc, get_emscripten_data};
+use crate::utils::read_guest_bytes;
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+use std::sync::Arc;
+use wasmer_runtime_core::{error::CallResult, types::Value, vm::Ctx, Instance};
+
+/// The samples of a format, interleaved by channel, little endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    U8,
+    S8,
+    U16,
+    S16,
+    F32,
+}
+
+impl SampleFormat {
+    pub fn bytes(self) -> u32 {
+        match self {
+            SampleFormat::U8 | SampleFormat::S8 => 1,
+            SampleFormat::U16 | SampleFormat::S16 => 2,
+            SampleFormat::F32 => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioFormat {
+    pub sample: SampleFormat,
+    pub channels: u32,
+    /// Frames per second.
+    pub frequency: u32,
+}
+
+/// Where the sound of the guest goes.
+///
+/// Every OpenAL source of the guest is a stream of its own, named by the
+/// source. SDL audio is the stream `SDL_STREAM`.
+pub trait AudioBackend: Debug {
+    /// Play `samples` on `stream` from the start, replacing whatever it
+    /// played, over and over if `looping`.
+    fn play(&self, stream: u32, format: AudioFormat, samples: &[u8], looping: bool);
+
+    /// Append `samples` to what `stream` plays.
+    fn queue(&self, stream: u32, format: AudioFormat, samples: &[u8]);
+
+    /// Stop playing `stream`.
+    fn stop(&self, stream: u32);
+
+    /// Whether `stream` is still playing what it was given, which the
+    /// guest polls to tell when a sound is over.
+    fn is_playing(&self, stream: u32) -> bool;
+}
+
+/// The stream SDL audio plays on. OpenAL never names a source 0.
+pub const SDL_STREAM: u32 = 0;
+
+/// Plays nothing, and is done playing at once.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Silence;
+
+impl AudioBackend for Silence {
+    fn play(&self, _stream: u32, _format: AudioFormat, _samples: &[u8], _looping: bool) {}
+
+    fn queue(&self, _stream: u32, _format: AudioFormat, _samples: &[u8]) {}
+
+    fn stop(&self, _stream: u32) {}
+
+    fn is_playing(&self, _stream: u32) -> bool {
+        false
+    }
+}
+
+const AL_NO_ERROR: i32 = 0;
+const AL_INVALID_NAME: i32 = 0xa001;
+const AL_INVALID_ENUM: i32 = 0xa002;
+const AL_INVALID_VALUE: i32 = 0xa003;
+
+const AL_LOOPING: u32 = 0x1007;
+const AL_BUFFER: u32 = 0x1009;
+const AL_SOURCE_STATE: u32 = 0x1010;
+const AL_INITIAL: i32 = 0x1011;
+const AL_PLAYING: i32 = 0x1012;
+const AL_PAUSED: i32 = 0x1013;
+const AL_STOPPED: i32 = 0x1014;
+const AL_BUFFERS_QUEUED: u32 = 0x1015;
+const AL_BUFFERS_PROCESSED: u32 = 0x1016;
+
+/// The handle of the only device and context, which can't be null.
+const ALC_HANDLE: u32 = 1;
+
+struct Buffer {
+    format: AudioFormat,
+    samples: Vec<u8>,
+}
+
+struct Source {
+    buffers: VecDeque<u32>,
+    looping: bool,
+    state: i32,
+}
+
+/// The audio callback of the guest, and what it fills.
+struct SdlAudio {
+    format: AudioFormat,
+    callback: u32,
+    userdata: u32,
+    stream: u32,
+    size: u32,
+    silence: u8,
+    paused: bool,
+}
+
+/// The audio state of an instance.
+pub(crate) struct Audio {
+    backend: Arc<dyn AudioBackend>,
+    buffers: HashMap<u32, Buffer>,
+    sources: HashMap<u32, Source>,
+    next_name: u32,
+    error: i32,
+    sdl: Option<SdlAudio>,
+}
+
+impl Audio {
+    pub(crate) fn new(backend: Option<Arc<dyn AudioBackend>>) -> Self {
+        Audio {
+            backend: backend.unwrap_or_else(|| Arc::new(Silence)),
+            buffers: HashMap::new(),
+            sources: HashMap::new(),
+            next_name: 1,
+            error: AL_NO_ERROR,
+            sdl: None,
+        }
+    }
+
+    fn fail(&mut self, error: i32) {
+        // OpenAL reports the first error since the last `alGetError`.
+        if self.error == AL_NO_ERROR {
+            self.error = error;
+        }
+    }
+
+    fn names(&mut self, n: u32) -> Vec<u32> {
+        let names = (self.next_name..self.next_name + n).collect();
+        self.next_name += n;
+        names
+    }
+
+    /// The state of `source`, which stops once the backend is done with it.
+    fn state(&mut self, name: u32) -> Option<i32> {
+        let backend = self.backend.clone();
+        let source = self.sources.get_mut(&name)?;
+        if source.state == AL_PLAYING && !source.looping && !backend.is_playing(name) {
+            source.state = AL_STOPPED;
+        }
+        Some(source.state)
+    }
+
+    fn play(&mut self, name: u32) {
+        let (format, samples, looping) = match self.sources.get_mut(&name) {
+            Some(source) => {
+                source.state = AL_PLAYING;
+                let all_buffers = &self.buffers;
+                let buffers: Vec<&Buffer> = source
+                    .buffers
+                    .iter()
+                    .filter_map(|buffer| all_buffers.get(buffer))
+                    .collect();
+                let format = match buffers.first() {
+                    Some(buffer) => buffer.format,
+                    None => return,
+                };
+                let samples: Vec<u8> = buffers
+                    .iter()
+                    .flat_map(|buffer| buffer.samples.iter().cloned())
+                    .collect();
+                (format, samples, source.looping)
+            }
+            None => return self.fail(AL_INVALID_NAME),
+        };
+        self.backend.play(name, format, &samples, looping);
+    }
+
+    /// Take the `n` buffers `source` is done with off its queue, or fail
+    /// with the OpenAL error.
+    fn unqueue(&mut self, name: u32, n: i32) -> Result<Vec<u32>, i32> {
+        let state = self.state(name).ok_or(AL_INVALID_NAME)?;
+        let source = self.sources.get_mut(&name).ok_or(AL_INVALID_NAME)?;
+        match (state, n) {
+            (_, 0) => Ok(Vec::new()),
+            (AL_STOPPED, n) if n > 0 && n as usize <= source.buffers.len() => {
+                Ok(source.buffers.drain(..n as usize).collect())
+            }
+            _ => Err(AL_INVALID_VALUE),
+        }
+    }
+
+    fn stop(&mut self, name: u32, state: i32) {
+        match self.sources.get_mut(&name) {
+            Some(source) => source.state = state,
+            None => return self.fail(AL_INVALID_NAME),
+        }
+        self.backend.stop(name);
+    }
+}
+
+fn audio_state(ctx: &mut Ctx) -> &mut Audio {
+    &mut get_emscripten_data(ctx).audio
+}
+
+fn read_u32s(ctx: &Ctx, ptr: u32, n: u32) -> Option<Vec<u32>> {
+    let view = ctx.memory(0).view::<u32>();
+    let start = (ptr / 4) as usize;
+    let cells = view.get(start..start.checked_add(n as usize)?)?;
+    Some(cells.iter().map(Cell::get).collect())
+}
+
+fn write_u32s(ctx: &Ctx, ptr: u32, values: &[u32]) -> bool {
+    let view = ctx.memory(0).view::<u32>();
+    let start = (ptr / 4) as usize;
+    match view.get(start..start + values.len()) {
+        Some(cells) => {
+            for (cell, &value) in cells.iter().zip(values) {
+                cell.set(value);
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// Generate `n` names into the array at `ptr`.
+fn gen_names(ctx: &mut Ctx, n: i32, ptr: u32, source: bool) {
+    if ctx.data.is_null() {
+        return;
+    }
+    if n < 0 {
+        return audio_state(ctx).fail(AL_INVALID_VALUE);
+    }
+    let names = audio_state(ctx).names(n as u32);
+    if !write_u32s(ctx, ptr, &names) {
+        return audio_state(ctx).fail(AL_INVALID_VALUE);
+    }
+    let audio = audio_state(ctx);
+    for name in names {
+        if source {
+            audio.sources.insert(
+                name,
+                Source {
+                    buffers: VecDeque::new(),
+                    looping: false,
+                    state: AL_INITIAL,
+                },
+            );
+        } else {
+            audio.buffers.insert(
+                name,
+                Buffer {
+                    format: AudioFormat {
+                        sample: SampleFormat::U8,
+                        channels: 1,
+                        frequency: 0,
+                    },
+                    samples: Vec::new(),
+                },
+            );
+        }
+    }
+}
+
+/// Delete the `n` names in the array at `ptr`.
+fn delete_names(ctx: &mut Ctx, n: i32, ptr: u32, source: bool) {
+    if ctx.data.is_null() {
+        return;
+    }
+    let names = match read_u32s(ctx, ptr, n.max(0) as u32) {
+        Some(names) if n >= 0 => names,
+        _ => return audio_state(ctx).fail(AL_INVALID_VALUE),
+    };
+    let audio = audio_state(ctx);
+    for name in names {
+        if source {
+            if audio.sources.remove(&name).is_some() {
+                audio.backend.stop(name);
+            }
+        } else {
+            audio.buffers.remove(&name);
+        }
+    }
+}
+
+pub fn _alc_open_device(_ctx: &mut Ctx, _name: u32) -> u32 {
+    debug!("emscripten::_alcOpenDevice");
+    ALC_HANDLE
+}
+
+pub fn _alc_close_device(_ctx: &mut Ctx, _device: u32) -> i32 {
+    debug!("emscripten::_alcCloseDevice");
+    1
+}
+
+pub fn _alc_create_context(_ctx: &mut Ctx, _device: u32, _attributes: u32) -> u32 {
+    debug!("emscripten::_alcCreateContext");
+    ALC_HANDLE
+}
+
+pub fn _alc_make_context_current(_ctx: &mut Ctx, _context: u32) -> i32 {
+    debug!("emscripten::_alcMakeContextCurrent");
+    1
+}
+
+pub fn _alc_destroy_context(_ctx: &mut Ctx, _context: u32) {
+    debug!("emscripten::_alcDestroyContext");
+}
+
+pub fn _alc_get_error(_ctx: &mut Ctx, _device: u32) -> i32 {
+    debug!("emscripten::_alcGetError");
+    0
+}
+
+pub fn _al_get_error(ctx: &mut Ctx) -> i32 {
+    debug!("emscripten::_alGetError");
+    if ctx.data.is_null() {
+        return AL_NO_ERROR;
+    }
+    let audio = audio_state(ctx);
+    let error = audio.error;
+    audio.error = AL_NO_ERROR;
+    error
+}
+
+pub fn _al_gen_buffers(ctx: &mut Ctx, n: i32, buffers: u32) {
+    debug!("emscripten::_alGenBuffers {}", n);
+    gen_names(ctx, n, buffers, false);
+}
+
+pub fn _al_delete_buffers(ctx: &mut Ctx, n: i32, buffers: u32) {
+    debug!("emscripten::_alDeleteBuffers {}", n);
+    delete_names(ctx, n, buffers, false);
+}
+
+pub fn _al_gen_sources(ctx: &mut Ctx, n: i32, sources: u32) {
+    debug!("emscripten::_alGenSources {}", n);
+    gen_names(ctx, n, sources, true);
+}
+
+pub fn _al_delete_sources(ctx: &mut Ctx, n: i32, sources: u32) {
+    debug!("emscripten::_alDeleteSources {}", n);
+    delete_names(ctx, n, sources, true);
+}
+
+pub fn _al_buffer_data(ctx: &mut Ctx, buffer: u32, format: u32, data: u32, size: u32, freq: u32) {
+    debug!(
+        "emscripten::_alBufferData {} {:#x} {}",
+        buffer, format, size
+    );
+    if ctx.data.is_null() {
+        return;
+    }
+    let (sample, channels) = match format {
+        // AL_FORMAT_MONO8, AL_FORMAT_MONO16, AL_FORMAT_STEREO8, AL_FORMAT_STEREO16
+        0x1100 => (SampleFormat::U8, 1),
+        0x1101 => (SampleFormat::S16, 1),
+        0x1102 => (SampleFormat::U8, 2),
+        0x1103 => (SampleFormat::S16, 2),
+        _ => return audio_state(ctx).fail(AL_INVALID_ENUM),
+    };
+    let samples = match read_guest_bytes(ctx, data, size) {
+        Some(samples) => samples,
+        None => return audio_state(ctx).fail(AL_INVALID_VALUE),
+    };
+    let audio = audio_state(ctx);
+    match audio.buffers.get_mut(&buffer) {
+        Some(buffer) => {
+            buffer.format = AudioFormat {
+                sample,
+                channels,
+                frequency: freq,
+            };
+            buffer.samples = samples;
+        }
+        None => audio.fail(AL_INVALID_NAME),
+    }
+}
+
+pub fn _al_sourcei(ctx: &mut Ctx, source: u32, param: u32, value: i32) {
+    debug!("emscripten::_alSourcei {} {:#x} {}", source, param, value);
+    if ctx.data.is_null() {
+        return;
+    }
+    let audio = audio_state(ctx);
+    if param == AL_BUFFER && value != 0 && !audio.buffers.contains_key(&(value as u32)) {
+        return audio.fail(AL_INVALID_VALUE);
+    }
+    let source = match audio.sources.get_mut(&source) {
+        Some(source) => source,
+        None => return audio.fail(AL_INVALID_NAME),
+    };
+    match param {
+        AL_BUFFER => {
+            source.buffers.clear();
+            if value != 0 {
+                source.buffers.push_back(value as u32);
+            }
+        }
+        AL_LOOPING => source.looping = value != 0,
+        // Where the sound comes from, its pitch and its gain are up to the
+        // backend, which isn't told yet.
+        _ => {}
+    }
+}
+
+pub fn _al_sourcef(_ctx: &mut Ctx, source: u32, param: u32, value: f32) {
+    debug!("emscripten::_alSourcef {} {:#x} {}", source, param, value);
+}
+
+pub fn _al_source3f(_ctx: &mut Ctx, source: u32, param: u32, _x: f32, _y: f32, _z: f32) {
+    debug!("emscripten::_alSource3f {} {:#x}", source, param);
+}
+
+pub fn _al_listenerf(_ctx: &mut Ctx, param: u32, value: f32) {
+    debug!("emscripten::_alListenerf {:#x} {}", param, value);
+}
+
+pub fn _al_listener3f(_ctx: &mut Ctx, param: u32, _x: f32, _y: f32, _z: f32) {
+    debug!("emscripten::_alListener3f {:#x}", param);
+}
+
+pub fn _al_get_sourcei(ctx: &mut Ctx, source: u32, param: u32, value: u32) {
+    debug!("emscripten::_alGetSourcei {} {:#x}", source, param);
+    if ctx.data.is_null() {
+        return;
+    }
+    let audio = audio_state(ctx);
+    let state = match audio.state(source) {
+        Some(state) => state,
+        None => return audio.fail(AL_INVALID_NAME),
+    };
+    let source = match audio.sources.get(&source) {
+        Some(source) => source,
+        None => return audio.fail(AL_INVALID_NAME),
+    };
+    let result = match param {
+        AL_SOURCE_STATE => state,
+        AL_LOOPING => source.looping as i32,
+        AL_BUFFER => source.buffers.front().cloned().unwrap_or(0) as i32,
+        AL_BUFFERS_QUEUED => source.buffers.len() as i32,
+        AL_BUFFERS_PROCESSED if state == AL_STOPPED => source.buffers.len() as i32,
+        AL_BUFFERS_PROCESSED => 0,
+        _ => return audio.fail(AL_INVALID_ENUM),
+    };
+    if !write_u32s(ctx, value, &[result as u32]) {
+        audio_state(ctx).fail(AL_INVALID_VALUE);
+    }
+}
+
+pub fn _al_source_queue_buffers(ctx: &mut Ctx, source: u32, n: i32, buffers: u32) {
+    debug!("emscripten::_alSourceQueueBuffers {} {}", source, n);
+    if ctx.data.is_null() {
+        return;
+    }
+    let names = match read_u32s(ctx, buffers, n.max(0) as u32) {
+        Some(names) if n >= 0 => names,
+        _ => return audio_state(ctx).fail(AL_INVALID_VALUE),
+    };
+    let audio = audio_state(ctx);
+    if names.iter().any(|name| !audio.buffers.contains_key(name)) {
+        return audio.fail(AL_INVALID_NAME);
+    }
+    match audio.sources.get_mut(&source) {
+        Some(source) => source.buffers.extend(names),
+        None => audio.fail(AL_INVALID_NAME),
+    }
+}
+
+pub fn _al_source_unqueue_buffers(ctx: &mut Ctx, source: u32, n: i32, buffers: u32) {
+    debug!("emscripten::_alSourceUnqueueBuffers {} {}", source, n);
+    if ctx.data.is_null() {
+        return;
+    }
+    let audio = audio_state(ctx);
+    let names = match audio.unqueue(source, n) {
+        Ok(names) => names,
+        Err(error) => return audio.fail(error),
+    };
+    if !write_u32s(ctx, buffers, &names) {
+        audio_state(ctx).fail(AL_INVALID_VALUE);
+    }
+}
+
+pub fn _al_source_play(ctx: &mut Ctx, source: u32) {
+    debug!("emscripten::_alSourcePlay {}", source);
+    if ctx.data.is_null() {
+        return;
+    }
+    audio_state(ctx).play(source);
+}
+
+pub fn _al_source_stop(ctx: &mut Ctx, source: u32) {
+    debug!("emscripten::_alSourceStop {}", source);
+    if ctx.data.is_null() {
+        return;
+    }
+    audio_state(ctx).stop(source, AL_STOPPED);
+}
+
+/// Pausing stops the source, so playing it again starts over.
+pub fn _al_source_pause(ctx: &mut Ctx, source: u32) {
+    debug!("emscripten::_alSourcePause {}", source);
+    if ctx.data.is_null() {
+        return;
+    }
+    audio_state(ctx).stop(source, AL_PAUSED);
+}
+
+/// Open the audio device with the `SDL_AudioSpec` at `desired`, which is
+/// what the guest gets. What it gets is written to `obtained`, or to
+/// `desired` if `obtained` is null.
+pub fn _sdl_open_audio(ctx: &mut Ctx, desired: u32, obtained: u32) -> i32 {
+    debug!("emscripten::_SDL_OpenAudio");
+    if ctx.data.is_null() {
+        return -1;
+    }
+    // freq: i32, format: u16, channels: u8, silence: u8, samples: u16,
+    // padding: u16, size: u32, callback: u32, userdata: u32
+    let spec = match read_guest_bytes(ctx, desired, 24) {
+        Some(spec) => spec,
+        None => return -1,
+    };
+    let u16_at = |offset: usize| u32::from(spec[offset]) | u32::from(spec[offset + 1]) << 8;
+    let u32_at = |offset: usize| u16_at(offset) | u16_at(offset + 2) << 16;
+    let (sample, silence) = match u16_at(4) {
+        0x0008 => (SampleFormat::U8, 0x80),
+        0x8008 => (SampleFormat::S8, 0),
+        0x0010 => (SampleFormat::U16, 0),
+        0x8010 => (SampleFormat::S16, 0),
+        0x8120 => (SampleFormat::F32, 0),
+        _ => return -1,
+    };
+    let format = AudioFormat {
+        sample,
+        channels: u32::from(spec[6]),
+        frequency: u32_at(0),
+    };
+    let frames = u16_at(8);
+    let size = frames * format.channels * sample.bytes();
+    if format.channels == 0 || size == 0 || audio_state(ctx).sdl.is_some() {
+        return -1;
+    }
+
+    let mut spec = spec;
+    spec[7] = silence;
+    for (i, byte) in spec[12..16].iter_mut().enumerate() {
+        *byte = (size >> (i * 8)) as u8;
+    }
+    let target = if obtained == 0 { desired } else { obtained };
+    {
+        let view = ctx.memory(0).view::<u8>();
+        match view.get(target as usize..target as usize + spec.len()) {
+            Some(cells) => cells
+                .iter()
+                .zip(&spec)
+                .for_each(|(cell, &byte)| cell.set(byte)),
+            None => return -1,
+        }
+    }
+
+    let stream = call_malloc(ctx, size);
+    audio_state(ctx).sdl = Some(SdlAudio {
+        format,
+        callback: u32_at(16),
+        userdata: u32_at(20),
+        stream,
+        size,
+        silence,
+        paused: true,
+    });
+    0
+}
+
+pub fn _sdl_pause_audio(ctx: &mut Ctx, pause_on: i32) {
+    debug!("emscripten::_SDL_PauseAudio {}", pause_on);
+    if ctx.data.is_null() {
+        return;
+    }
+    let audio = audio_state(ctx);
+    if let Some(sdl) = &mut audio.sdl {
+        sdl.paused = pause_on != 0;
+        if sdl.paused {
+            audio.backend.stop(SDL_STREAM);
+        }
+    }
+}
+
+pub fn _sdl_close_audio(ctx: &mut Ctx) {
+    debug!("emscripten::_SDL_CloseAudio");
+    if ctx.data.is_null() {
+        return;
+    }
+    let data = get_emscripten_data(ctx);
+    if let Some(sdl) = data.audio.sdl.take() {
+        data.audio.backend.stop(SDL_STREAM);
+        let _ = data.free.call(sdl.stream);
+    }
+}
+
+/// The callback runs in `pump_sdl_audio`, between calls into the guest, so
+/// there's nothing to lock it out of.
+pub fn _sdl_lock_audio(_ctx: &mut Ctx) {
+    debug!("emscripten::_SDL_LockAudio");
+}
+
+pub fn _sdl_unlock_audio(_ctx: &mut Ctx) {
+    debug!("emscripten::_SDL_UnlockAudio");
+}
+
+/// Have the SDL audio callback of the guest fill one buffer, which goes to
+/// the backend. Does nothing while SDL audio is closed or paused.
+///
+/// The instance must be one whose runtime outlived `main`, and the guest
+/// must export `dynCall_viii` for the callback to be called.
+pub fn pump_sdl_audio(instance: &mut Instance) -> CallResult<()> {
+    if instance.context().data.is_null() {
+        return Ok(());
+    }
+    let (callback, userdata, stream, size, silence) = match &audio_state(instance.context_mut()).sdl
+    {
+        Some(sdl) if !sdl.paused => (
+            sdl.callback,
+            sdl.userdata,
+            sdl.stream,
+            sdl.size,
+            sdl.silence,
+        ),
+        _ => return Ok(()),
+    };
+    {
+        let view = instance.context().memory(0).view::<u8>();
+        if let Some(cells) = view.get(stream as usize..(stream + size) as usize) {
+            cells.iter().for_each(|cell| cell.set(silence));
+        }
+    }
+    instance.call(
+        "dynCall_viii",
+        &[
+            Value::I32(callback as i32),
+            Value::I32(userdata as i32),
+            Value::I32(stream as i32),
+            Value::I32(size as i32),
+        ],
+    )?;
+
+    // The callback may have closed the device.
+    let samples = read_guest_bytes(instance.context(), stream, size);
+    let audio = audio_state(instance.context_mut());
+    if let (Some(sdl), Some(samples)) = (&audio.sdl, samples) {
+        audio.backend.queue(SDL_STREAM, sdl.format, &samples);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn audio_with_source(state: i32, buffers: &[u32]) -> Audio {
+        let mut audio = Audio::new(None);
+        audio.sources.insert(
+            1,
+            Source {
+                buffers: buffers.iter().cloned().collect(),
+                looping: false,
+                state,
+            },
+        );
+        audio
+    }
+
+    #[test]
+    fn unqueues_the_buffers_of_stopped_sources() {
+        let mut audio = audio_with_source(AL_STOPPED, &[7, 8, 9]);
+        assert_eq!(audio.unqueue(1, 2), Ok(vec![7, 8]));
+        assert_eq!(audio.unqueue(1, 2), Err(AL_INVALID_VALUE));
+        assert_eq!(audio.unqueue(1, -1), Err(AL_INVALID_VALUE));
+        assert_eq!(audio.unqueue(1, 1), Ok(vec![9]));
+    }
+
+    #[test]
+    fn unqueueing_fails_for_unknown_or_looping_sources() {
+        let mut audio = audio_with_source(AL_PLAYING, &[7]);
+        audio.sources.get_mut(&1).unwrap().looping = true;
+        assert_eq!(audio.unqueue(1, 1), Err(AL_INVALID_VALUE));
+        assert_eq!(audio.unqueue(1, 0), Ok(Vec::new()));
+        assert_eq!(audio.unqueue(2, 1), Err(AL_INVALID_NAME));
+        assert_eq!(audio.unqueue(2, 0), Err(AL_INVALID_NAME));
+    }
+}
@@ -0,0 +1,203 @@
+//! The capabilities a module declares it needs, which the host grants
+//! before instantiating it.
+//!
+//! They are declared in a custom section named `wasmer.capabilities`,
+//! which holds UTF-8 text with one capability per line:
+//!
+//! - `fs:<path>`: access to the files under the absolute guest path `path`;
+//! - `net`: access to the network;
+//! - `env:<name>`: the environment variable `name`.
+//!
+//! Empty lines and lines starting with `#` are ignored. A module without
+//! the section declares that it needs nothing.
+//!
+//! The host runs the module with only what it granted, through
+//! `EmscriptenConfig::capabilities`, whether the module declared anything
+//! or not.
+use crate::linking::Reader;
+use crate::vfs::normalize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+const SECTION_NAME: &[u8] = b"wasmer.capabilities";
+
+/// A set of capabilities, requested by a module or granted by the host.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    pub fs: Vec<PathBuf>,
+    pub net: bool,
+    pub env: Vec<String>,
+}
+
+impl Capabilities {
+    /// Returns `Ok(None)` if `wasm` doesn't declare its capabilities.
+    pub fn parse(wasm: &[u8]) -> Result<Option<Self>, CapabilityError> {
+        let section = match find_custom_section(wasm, SECTION_NAME) {
+            Some(section) => section,
+            None => return Ok(None),
+        };
+        let text = std::str::from_utf8(section)
+            .map_err(|_| CapabilityError::Malformed("the section isn't UTF-8".to_string()))?;
+        let mut capabilities = Capabilities::default();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line == "net" {
+                capabilities.net = true;
+            } else if line.starts_with("fs:") {
+                let path = Path::new(&line["fs:".len()..]);
+                if !path.has_root() {
+                    return Err(CapabilityError::Malformed(format!(
+                        "`{}` isn't an absolute path",
+                        path.display()
+                    )));
+                }
+                capabilities.fs.push(normalize(path));
+            } else if line.starts_with("env:") {
+                capabilities.env.push(line["env:".len()..].to_string());
+            } else {
+                return Err(CapabilityError::Malformed(format!(
+                    "unknown capability `{}`",
+                    line
+                )));
+            }
+        }
+        Ok(Some(capabilities))
+    }
+
+    /// Whether `path` is one of the granted paths or under one, once `.`
+    /// and `..` are resolved the way the guest resolves them.
+    pub fn allows_path(&self, path: &Path) -> bool {
+        let path = normalize(path);
+        self.fs
+            .iter()
+            .any(|granted| path.starts_with(normalize(granted)))
+    }
+
+    /// The capabilities of `self` that `granted` doesn't cover.
+    pub fn missing_from(&self, granted: &Capabilities) -> Vec<String> {
+        let mut missing = Vec::new();
+        for path in &self.fs {
+            if !granted.allows_path(path) {
+                missing.push(format!("fs:{}", path.display()));
+            }
+        }
+        if self.net && !granted.net {
+            missing.push("net".to_string());
+        }
+        for name in &self.env {
+            if !granted.env.contains(name) {
+                missing.push(format!("env:{}", name));
+            }
+        }
+        missing
+    }
+
+    /// Check that `granted` covers every capability of `self`.
+    pub fn check(&self, granted: &Capabilities) -> Result<(), CapabilityError> {
+        let missing = self.missing_from(granted);
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(CapabilityError::NotGranted(missing))
+        }
+    }
+}
+
+/// Why a module can't be instantiated with the capabilities it declares.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CapabilityError {
+    /// The `wasmer.capabilities` section can't be read.
+    Malformed(String),
+    /// The host didn't grant these capabilities.
+    NotGranted(Vec<String>),
+}
+
+impl fmt::Display for CapabilityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CapabilityError::Malformed(reason) => {
+                write!(f, "malformed capabilities section: {}", reason)
+            }
+            CapabilityError::NotGranted(missing) => write!(
+                f,
+                "the module needs capabilities that weren't granted: {}",
+                missing.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CapabilityError {}
+
+/// The contents of the first custom section named `name`.
+pub(crate) fn find_custom_section<'a>(wasm: &'a [u8], name: &[u8]) -> Option<&'a [u8]> {
+    let mut reader = Reader {
+        bytes: wasm.get(8..)?,
+    };
+    while !reader.bytes.is_empty() {
+        let id = reader.byte()?;
+        let size = reader.varuint32()? as usize;
+        let mut section = Reader {
+            bytes: reader.bytes.get(..size)?,
+        };
+        reader.bytes = &reader.bytes[size..];
+        if id != 0 {
+            continue;
+        }
+        let name_len = section.varuint32()? as usize;
+        if section.bytes.get(..name_len)? == name {
+            return Some(&section.bytes[name_len..]);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module_with_section(contents: &[u8]) -> Vec<u8> {
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        wasm.push(0);
+        wasm.push((1 + SECTION_NAME.len() + contents.len()) as u8);
+        wasm.push(SECTION_NAME.len() as u8);
+        wasm.extend_from_slice(SECTION_NAME);
+        wasm.extend_from_slice(contents);
+        wasm
+    }
+
+    #[test]
+    fn checks_the_declared_capabilities() {
+        let wasm = module_with_section(b"# needs\nfs:/data/in\nnet\nenv:HOME\n");
+        let requested = Capabilities::parse(&wasm).unwrap().unwrap();
+        let granted = Capabilities {
+            fs: vec![PathBuf::from("/data")],
+            net: false,
+            env: vec!["HOME".to_string()],
+        };
+        assert_eq!(requested.missing_from(&granted), vec!["net".to_string()]);
+        assert_eq!(Capabilities::parse(b"\0asm\x01\0\0\0"), Ok(None));
+    }
+
+    #[test]
+    fn granted_paths_are_compared_once_normalized() {
+        let granted = Capabilities {
+            fs: vec![PathBuf::from("/data/./in/")],
+            ..Capabilities::default()
+        };
+        assert!(granted.allows_path(Path::new("/data/in/a/../b")));
+        assert!(!granted.allows_path(Path::new("/data/in/../secret")));
+        assert!(!granted.allows_path(Path::new("/data/input")));
+
+        let wasm = module_with_section(b"fs:/data/in/../../etc\n");
+        let requested = Capabilities::parse(&wasm).unwrap().unwrap();
+        assert_eq!(requested.fs, vec![PathBuf::from("/etc")]);
+        assert_eq!(
+            requested.missing_from(&granted),
+            vec!["fs:/etc".to_string()]
+        );
+        assert!(Capabilities::parse(&module_with_section(b"fs:data\n")).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! Calls to the C functions a module exports, the way `ccall` and
+//! `cwrap` make them from JavaScript.
+//!
+//! Emscripten exports the C function `foo` as `_foo`. Its wasm signature
+//! only has numbers, since strings and arrays are passed as pointers, so
+//! they have to be declared like the `argTypes` and `returnType` of
+//! `ccall`. Until then, every argument and the result are numbers.
+use crate::env::get_emscripten_data;
+use crate::utils::{allocate_cstr_on_stack, allocate_on_stack, read_string_from_wasm};
+use crate::EmscriptenData;
+use std::{ffi::c_void, ptr, sync::Arc};
+use wasmer_runtime_core::{
+    error::{CallResult, ResolveError, ResolveResult},
+    module::ExportIndex,
+    types::{FuncSig, Type, Value},
+    Instance, Module,
+};
+
+/// The types a C function takes and returns, as `ccall` names them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CType {
+    Number,
+    /// A NUL-terminated UTF-8 string.
+    String,
+    /// An array of bytes.
+    Array,
+}
+
+/// An argument of a C function.
+#[derive(Debug, Clone, Copy)]
+pub enum CArg<'a> {
+    Number(f64),
+    /// Copied onto the stack for the duration of the call, with a terminator.
+    String(&'a str),
+    /// Copied onto the stack for the duration of the call.
+    Array(&'a [u8]),
+}
+
+/// The result of a C function.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CValue {
+    Number(f64),
+    String(String),
+}
+
+/// A C function exported by a module.
+#[derive(Debug, Clone)]
+pub struct CFunction {
+    name: String,
+    signature: Arc<FuncSig>,
+    arg_types: Vec<CType>,
+    return_type: Option<CType>,
+}
+
+/// The C functions exported by `module`, by name.
+pub fn c_functions(module: &Module) -> Vec<CFunction> {
+    let mut functions: Vec<_> = module
+        .0
+        .info
+        .exports
+        .keys()
+        .filter(|export| export.starts_with('_'))
+        .filter_map(|export| c_function(module, &export[1..]).ok())
+        .collect();
+    functions.sort_by(|a, b| a.name.cmp(&b.name));
+    functions
+}
+
+/// Look up the C function `name`, which the module exports as `_name`.
+pub fn c_function(module: &Module, name: &str) -> ResolveResult<CFunction> {
+    let info = &module.0.info;
+    let export = format!("_{}", name);
+    let signature = match info.exports.get(&export) {
+        Some(ExportIndex::Func(func_index)) => {
+            info.signatures[info.func_assoc[*func_index]].clone()
+        }
+        Some(_) => return Err(ResolveError::ExportWrongType { name: export }),
+        None => return Err(ResolveError::ExportNotFound { name: export }),
+    };
+    Ok(CFunction {
+        name: name.to_string(),
+        arg_types: vec![CType::Number; signature.params().len()],
+        return_type: signature.returns().first().map(|_| CType::Number),
+        signature,
+    })
+}
+
+impl CFunction {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn signature(&self) -> &FuncSig {
+        &self.signature
+    }
+
+    pub fn arg_types(&self) -> &[CType] {
+        &self.arg_types
+    }
+
+    /// The type of the result, or `None` if the function returns nothing.
+    pub fn return_type(&self) -> Option<CType> {
+        self.return_type
+    }
+
+    /// Declare the types of the arguments and of the result.
+    ///
+    /// Strings and arrays are pointers, so they are only accepted where
+    /// the signature has an `i32`, and only strings can be returned.
+    pub fn with_types(
+        mut self,
+        arg_types: &[CType],
+        return_type: Option<CType>,
+    ) -> ResolveResult<Self> {
+        let fits = |c_type: CType, ty: &Type| c_type == CType::Number || *ty == Type::I32;
+        let args_fit = arg_types.len() == self.signature.params().len()
+            && arg_types
+                .iter()
+                .zip(self.signature.params())
+                .all(|(c_type, ty)| fits(*c_type, ty));
+        let return_fits = match (return_type, self.signature.returns().first()) {
+            // Like `ccall`, arrays can't be returned since their length is unknown.
+            (Some(CType::Array), _) => false,
+            (Some(c_type), Some(ty)) => fits(c_type, ty),
+            (None, None) => true,
+            _ => false,
+        };
+        if !args_fit || !return_fits {
+            return Err(self.mismatch());
+        }
+        self.arg_types = arg_types.to_vec();
+        self.return_type = return_type;
+        Ok(self)
+    }
+
+    fn mismatch(&self) -> ResolveError {
+        ResolveError::Signature {
+            expected: self.signature.clone(),
+            found: self.signature.params().to_vec(),
+        }
+    }
+
+    /// Call the function with `args`, which must have the declared types.
+    pub fn call(&self, instance: &mut Instance, args: &[CArg]) -> CallResult<Option<CValue>> {
+        let args_match = args.len() == self.arg_types.len()
+            && args
+                .iter()
+                .zip(&self.arg_types)
+                .all(|(arg, c_type)| match (arg, c_type) {
+                    (CArg::Number(_), CType::Number)
+                    | (CArg::String(_), CType::String)
+                    | (CArg::Array(_), CType::Array) => true,
+                    _ => false,
+                });
+        if !args_match {
+            return Err(self.mismatch().into());
+        }
+
+        // An instance whose runtime outlived `main` already has its data.
+        if !instance.context().data.is_null() {
+            return self.call_with_data(instance, args);
+        }
+        let mut data = EmscriptenData::try_new(instance)?;
+        let data_ptr = &mut data as *mut _ as *mut c_void;
+        instance.context_mut().data = data_ptr;
+
+        let result = self.call_with_data(instance, args);
+        instance.context_mut().data = ptr::null_mut();
+        result
+    }
+
+    fn call_with_data(&self, instance: &mut Instance, args: &[CArg]) -> CallResult<Option<CValue>> {
+        let ctx = instance.context_mut();
+        // Like `ccall`, pop the strings and arrays once the call returns.
+        let stack_top = match &get_emscripten_data(ctx).stack_save {
+            Some(stack_save) => Some(stack_save.call()?),
+            None => None,
+        };
+
+        let params: Vec<Value> = args
+            .iter()
+            .zip(self.signature.params())
+            .map(|(arg, ty)| match *arg {
+                CArg::Number(n) => match ty {
+                    Type::I32 => Value::I32(n as i32),
+                    Type::I64 => Value::I64(n as i64),
+                    Type::F32 => Value::F32(n as f32),
+                    Type::F64 => Value::F64(n),
+                },
+                CArg::String(s) => Value::I32(unsafe { allocate_cstr_on_stack(ctx, s).0 } as i32),
+                CArg::Array(bytes) => {
+                    let (offset, slice) =
+                        unsafe { allocate_on_stack::<u8>(ctx, bytes.len() as u32) };
+                    slice.copy_from_slice(bytes);
+                    Value::I32(offset as i32)
+                }
+            })
+            .collect();
+
+        let results = instance.call(&format!("_{}", self.name), &params)?;
+        let ctx = instance.context_mut();
+        let result = match (self.return_type, results.first()) {
+            (Some(CType::String), Some(Value::I32(ptr))) => Some(CValue::String(
+                read_string_from_wasm(ctx.memory(0), *ptr as u32),
+            )),
+            (Some(_), Some(value)) => Some(CValue::Number(match *value {
+                Value::I32(n) => f64::from(n),
+                Value::I64(n) => n as f64,
+                Value::F32(n) => f64::from(n),
+                Value::F64(n) => n,
+            })),
+            _ => None,
+        };
+
+        if let (Some(stack_top), Some(stack_restore)) =
+            (stack_top, &get_emscripten_data(ctx).stack_restore)
+        {
+            stack_restore.call(stack_top)?;
+        }
+        Ok(result)
+    }
+}
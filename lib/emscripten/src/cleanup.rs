@@ -0,0 +1,170 @@
+//! Cleanup of the host resources held by imports across a `longjmp`.
+//!
+//! `longjmp` is the host's, so when the guest jumps out of a wasm call
+//! made by an import, the host frames in between are skipped without
+//! their destructors running. An import that holds a resource, e.g. a
+//! lock, a buffer or a temporary file, while it calls into the guest must
+//! release it through `defer`, never through `Drop` alone: the cleanup
+//! runs when the guard is dropped, or when the guest jumps past it. The
+//! same goes for the imports an embedder adds to the emscripten ones.
+//!
+//! A resource the import hands over to the guest, e.g. a new descriptor
+//! once its number is written to guest memory, is released on the paths
+//! that fail before that, and `dismiss`ed once it's handed over.
+use crate::env::get_emscripten_data;
+use wasmer_runtime_core::vm::Ctx;
+
+/// The pending cleanups of an instance, innermost last.
+#[derive(Default)]
+pub(crate) struct Cleanups {
+    pending: Vec<Box<dyn FnOnce()>>,
+    /// The number of pending cleanups when each jump buffer was set.
+    marks: Vec<usize>,
+}
+
+impl Cleanups {
+    /// Register `cleanup`, returning its index.
+    fn push(&mut self, cleanup: Box<dyn FnOnce()>) -> usize {
+        self.pending.push(cleanup);
+        self.pending.len() - 1
+    }
+
+    fn run_from(&mut self, len: usize) {
+        while self.pending.len() > len {
+            let cleanup = self.pending.pop().unwrap();
+            cleanup();
+        }
+    }
+}
+
+/// Runs a cleanup registered by `defer` when dropped.
+#[must_use = "the cleanup runs as soon as the guard is dropped"]
+pub struct Deferred {
+    registered: Option<(*mut Cleanups, usize)>,
+    cleanup: Option<Box<dyn FnOnce()>>,
+}
+
+impl Deferred {
+    /// Drop the cleanup without running it, once what it releases has been
+    /// handed over to the guest.
+    pub fn dismiss(mut self) {
+        match self.registered.take() {
+            Some((cleanups, index)) => {
+                let cleanups = unsafe { &mut *cleanups };
+                debug_assert_eq!(
+                    cleanups.pending.len(),
+                    index + 1,
+                    "deferred cleanups must be dismissed in reverse order"
+                );
+                cleanups.pending.truncate(index);
+            }
+            None => self.cleanup = None,
+        }
+    }
+}
+
+impl Drop for Deferred {
+    fn drop(&mut self) {
+        match self.registered {
+            Some((cleanups, index)) => {
+                let cleanups = unsafe { &mut *cleanups };
+                debug_assert_eq!(
+                    cleanups.pending.len(),
+                    index + 1,
+                    "deferred cleanups must be dropped in reverse order"
+                );
+                cleanups.run_from(index);
+            }
+            None => {
+                if let Some(cleanup) = self.cleanup.take() {
+                    cleanup();
+                }
+            }
+        }
+    }
+}
+
+/// Run `cleanup` once the returned guard is dropped, or once the guest
+/// longjmps past the current frame, whichever comes first.
+pub fn defer<F: FnOnce() + 'static>(ctx: &mut Ctx, cleanup: F) -> Deferred {
+    // Without instance data there's no jump buffer to skip past this frame.
+    if ctx.data.is_null() {
+        return Deferred {
+            registered: None,
+            cleanup: Some(Box::new(cleanup)),
+        };
+    }
+    let cleanups = &mut get_emscripten_data(ctx).cleanups;
+    let index = cleanups.push(Box::new(cleanup));
+    Deferred {
+        registered: Some((cleanups as *mut Cleanups, index)),
+        cleanup: None,
+    }
+}
+
+/// Remember the pending cleanups when the jump buffer `jump_index` is set.
+pub(crate) fn mark(ctx: &mut Ctx, jump_index: usize) {
+    let cleanups = &mut get_emscripten_data(ctx).cleanups;
+    cleanups.marks.resize(jump_index + 1, 0);
+    cleanups.marks[jump_index] = cleanups.pending.len();
+}
+
+/// The number of pending cleanups, for `run_from` to go back to.
+pub(crate) fn pending(ctx: &mut Ctx) -> usize {
+    get_emscripten_data(ctx).cleanups.pending.len()
+}
+
+/// Run the cleanups registered since there were `len` pending, whose
+/// frames a jump is about to skip, or has skipped.
+pub(crate) fn run_from(ctx: &mut Ctx, len: usize) {
+    get_emscripten_data(ctx).cleanups.run_from(len);
+}
+
+/// Run the cleanups registered since the jump buffer `jump_index` was
+/// set, whose frames a jump to it is about to skip.
+pub(crate) fn unwind_to(ctx: &mut Ctx, jump_index: usize) {
+    let cleanups = &mut get_emscripten_data(ctx).cleanups;
+    if let Some(&len) = cleanups.marks.get(jump_index) {
+        cleanups.run_from(len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::mem;
+    use std::rc::Rc;
+
+    fn deferred(cleanups: &mut Cleanups, log: &Rc<RefCell<Vec<u32>>>, id: u32) -> Deferred {
+        let log = log.clone();
+        let index = cleanups.push(Box::new(move || log.borrow_mut().push(id)));
+        Deferred {
+            registered: Some((cleanups as *mut Cleanups, index)),
+            cleanup: None,
+        }
+    }
+
+    #[test]
+    fn a_jump_runs_the_skipped_cleanups_innermost_first() {
+        let mut cleanups = Cleanups::default();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let guards: Vec<_> = (0..3).map(|id| deferred(&mut cleanups, &log, id)).collect();
+        cleanups.run_from(1);
+        assert_eq!(*log.borrow(), vec![2, 1]);
+        // The guards of the skipped frames are never dropped.
+        guards.into_iter().skip(1).for_each(mem::forget);
+    }
+
+    #[test]
+    fn a_dismissed_cleanup_never_runs() {
+        let mut cleanups = Cleanups::default();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let outer = deferred(&mut cleanups, &log, 0);
+        deferred(&mut cleanups, &log, 1).dismiss();
+        assert!(log.borrow().is_empty());
+        drop(outer);
+        assert_eq!(*log.borrow(), vec![0]);
+        assert!(cleanups.pending.is_empty());
+    }
+}
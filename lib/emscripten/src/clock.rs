@@ -0,0 +1,183 @@
+use crate::env::get_emscripten_data;
+use std::fmt::Debug;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use wasmer_runtime_core::vm::Ctx;
+
+/// The clocks a guest can query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockId {
+    /// Wall-clock time since the unix epoch.
+    Realtime,
+    /// Time since an arbitrary point, which never goes backwards.
+    Monotonic,
+    /// CPU time consumed by the process.
+    ProcessCpuTime,
+    /// CPU time consumed by the calling thread.
+    ThreadCpuTime,
+}
+
+impl ClockId {
+    /// Decode a `clockid_t` as passed by emscripten, which uses the
+    /// Linux numbering whatever the host is.
+    pub fn from_emscripten(clk_id: i32) -> Option<Self> {
+        match clk_id {
+            // CLOCK_REALTIME, CLOCK_REALTIME_COARSE
+            0 | 5 => Some(ClockId::Realtime),
+            // CLOCK_MONOTONIC, CLOCK_MONOTONIC_RAW, CLOCK_MONOTONIC_COARSE, CLOCK_BOOTTIME
+            1 | 4 | 6 | 7 => Some(ClockId::Monotonic),
+            2 => Some(ClockId::ProcessCpuTime),
+            3 => Some(ClockId::ThreadCpuTime),
+            _ => None,
+        }
+    }
+
+    /// Decode a WASI `clockid`.
+    pub fn from_wasi(clock_id: u32) -> Option<Self> {
+        match clock_id {
+            0 => Some(ClockId::Realtime),
+            1 => Some(ClockId::Monotonic),
+            2 => Some(ClockId::ProcessCpuTime),
+            3 => Some(ClockId::ThreadCpuTime),
+            _ => None,
+        }
+    }
+}
+
+/// A source of time for the guest.
+///
+/// Every time query made by the guest goes through a `Clock`, so an
+/// embedder can substitute its own, e.g. to make runs reproducible.
+pub trait Clock: Debug {
+    /// The current time of `id`, or `None` if this clock can't tell.
+    fn time(&self, id: ClockId) -> Option<Duration>;
+
+    /// The resolution of `id`, or `None` if this clock can't tell.
+    fn resolution(&self, id: ClockId) -> Option<Duration>;
+}
+
+/// The clocks of the host operating system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HostClock;
+
+#[cfg(unix)]
+impl HostClock {
+    fn clock_id(id: ClockId) -> libc::clockid_t {
+        match id {
+            ClockId::Realtime => libc::CLOCK_REALTIME,
+            ClockId::Monotonic => libc::CLOCK_MONOTONIC,
+            ClockId::ProcessCpuTime => libc::CLOCK_PROCESS_CPUTIME_ID,
+            ClockId::ThreadCpuTime => libc::CLOCK_THREAD_CPUTIME_ID,
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Clock for HostClock {
+    fn time(&self, id: ClockId) -> Option<Duration> {
+        let mut timespec: libc::timespec = unsafe { std::mem::zeroed() };
+        if unsafe { libc::clock_gettime(Self::clock_id(id), &mut timespec) } != 0 {
+            return None;
+        }
+        Some(Duration::new(
+            timespec.tv_sec as u64,
+            timespec.tv_nsec as u32,
+        ))
+    }
+
+    fn resolution(&self, id: ClockId) -> Option<Duration> {
+        let mut timespec: libc::timespec = unsafe { std::mem::zeroed() };
+        if unsafe { libc::clock_getres(Self::clock_id(id), &mut timespec) } != 0 {
+            return None;
+        }
+        Some(Duration::new(
+            timespec.tv_sec as u64,
+            timespec.tv_nsec as u32,
+        ))
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::os::raw::{c_int, c_void};
+
+    extern "system" {
+        pub fn GetCurrentProcess() -> *mut c_void;
+        pub fn GetCurrentThread() -> *mut c_void;
+        // The times are FILETIMEs, counting 100ns intervals.
+        pub fn GetProcessTimes(
+            process: *mut c_void,
+            creation: *mut u64,
+            exit: *mut u64,
+            kernel: *mut u64,
+            user: *mut u64,
+        ) -> c_int;
+        pub fn GetThreadTimes(
+            thread: *mut c_void,
+            creation: *mut u64,
+            exit: *mut u64,
+            kernel: *mut u64,
+            user: *mut u64,
+        ) -> c_int;
+    }
+}
+
+#[cfg(windows)]
+impl Clock for HostClock {
+    fn time(&self, id: ClockId) -> Option<Duration> {
+        let (mut creation, mut exit, mut kernel, mut user) = (0, 0, 0, 0);
+        let ok = unsafe {
+            match id {
+                ClockId::Realtime => return SystemTime::now().duration_since(UNIX_EPOCH).ok(),
+                ClockId::Monotonic => return Some(Duration::from_nanos(time::precise_time_ns())),
+                ClockId::ProcessCpuTime => windows::GetProcessTimes(
+                    windows::GetCurrentProcess(),
+                    &mut creation,
+                    &mut exit,
+                    &mut kernel,
+                    &mut user,
+                ),
+                ClockId::ThreadCpuTime => windows::GetThreadTimes(
+                    windows::GetCurrentThread(),
+                    &mut creation,
+                    &mut exit,
+                    &mut kernel,
+                    &mut user,
+                ),
+            }
+        };
+        if ok == 0 {
+            return None;
+        }
+        Some(Duration::from_nanos((kernel + user) * 100))
+    }
+
+    fn resolution(&self, id: ClockId) -> Option<Duration> {
+        match id {
+            ClockId::Realtime | ClockId::ProcessCpuTime | ClockId::ThreadCpuTime => {
+                Some(Duration::from_nanos(100))
+            }
+            ClockId::Monotonic => Some(Duration::from_nanos(1)),
+        }
+    }
+}
+
+/// Returns the clock configured for the instance, or the host clock.
+pub(crate) fn get_clock(ctx: &mut Ctx) -> &dyn Clock {
+    static HOST_CLOCK: HostClock = HostClock;
+    // Time can be queried before `run_emscripten_instance` sets up the data.
+    if ctx.data.is_null() {
+        return &HOST_CLOCK;
+    }
+    match &get_emscripten_data(ctx).config.clock {
+        Some(clock) => &**clock,
+        None => &HOST_CLOCK,
+    }
+}
+
+/// The wall-clock time, for the functions that only deal in it.
+pub(crate) fn realtime(ctx: &mut Ctx) -> Duration {
+    get_clock(ctx)
+        .time(ClockId::Realtime)
+        .or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default()
+}
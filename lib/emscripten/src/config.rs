@@ -0,0 +1,169 @@
+use crate::audio::AudioBackend;
+use crate::capabilities::Capabilities;
+use crate::clock::Clock;
+use crate::hooks::{SyscallHandler, SyscallHandlers};
+use crate::http::NetPolicy;
+use crate::kv::KvStore;
+use crate::process::AbortHook;
+use crate::stats::SyscallStats;
+use crate::stdio::{Buffering, Input, Output, Tee};
+use crate::tty::Terminal;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Options controlling how an emscripten instance interacts with the host.
+///
+/// Most options default to the behavior the runtime had before the option
+/// existed. The exceptions keep the host out of sight: the guest sees the
+/// virtual IDs of `GuestIds::default()`, pid 1, ppid 0 and uid and gid 0,
+/// instead of the host's, and can't open sockets unless `networking` is
+/// set. `EmscriptenConfig::default()` is always a safe choice.
+#[derive(Debug, Clone)]
+pub struct EmscriptenConfig {
+    /// Cache the results of `stat64` calls by path, and the listings of
+    /// `getdents64`, dropping entries whenever the guest mutates the
+    /// filesystem. Interpreters such as Python and Ruby stat the same
+    /// handful of paths hundreds of times during startup.
+    pub stat_cache: bool,
+    /// The clock the guest reads the time from, instead of the host's.
+    pub clock: Option<Arc<dyn Clock>>,
+    /// The time zone `localtime` and `mktime` convert with, given the way
+    /// `TZ` would name it. Defaults to the zone of the host.
+    pub timezone: Option<String>,
+    /// The process and user IDs the guest sees, which never reveal the
+    /// host's.
+    pub ids: GuestIds,
+    /// Where the environment of the guest comes from.
+    pub env: EnvPolicy,
+    /// The handlers that intercept the syscalls of the guest.
+    pub syscall_handlers: SyscallHandlers,
+    /// Called with the reason of an abort of the guest, before the process
+    /// aborts, instead of logging the message.
+    pub abort_hook: Option<AbortHook>,
+    /// Where to count and time the syscalls of the guest.
+    pub syscall_stats: Option<SyscallStats>,
+    /// Where the guest reads stdin from. Defaults to the host's stdin.
+    pub stdin: Option<Input>,
+    /// Where the output of the guest to stdout goes. Defaults to the host's
+    /// stdout.
+    pub stdout: Option<Output>,
+    /// Where the output of the guest to stderr goes. Defaults to the host's
+    /// stderr.
+    pub stderr: Option<Output>,
+    /// How the output of the guest to stdout is buffered.
+    pub stdout_buffering: Buffering,
+    /// Where to copy the output of the guest to stdout, besides stdout.
+    pub stdout_tee: Option<Tee>,
+    /// The pseudo-terminal the standard streams of the guest are attached
+    /// to. Without one, they are as much of a terminal as the host's.
+    pub terminal: Option<Terminal>,
+    /// Where to write a core dump of the guest when it aborts.
+    pub core_dump: Option<PathBuf>,
+    /// Tear the runtime down once `main` returns, like emscripten's
+    /// `EXIT_RUNTIME=1`. Without it, the instance keeps its state for the
+    /// host to call into it afterwards, like it would in a browser.
+    pub exit_runtime: bool,
+    /// Where the sound the guest plays through OpenAL and SDL goes. Without
+    /// one, the guest plays silence.
+    pub audio: Option<Arc<dyn AudioBackend>>,
+    /// The store behind the `wasmer_ext.kv` imports. Without one, the
+    /// guest can't keep anything there.
+    pub kv_store: Option<Arc<dyn KvStore>>,
+    /// The hosts the `wasmer_ext.http` imports can make requests to.
+    pub net_policy: NetPolicy,
+    /// Let the guest open sockets of the host through `socketcall`.
+    /// Without it, every socket call fails with `EACCES`.
+    pub networking: bool,
+    /// The host directories mounted in the guest, each under a guest path
+    /// like `/data`. With any, the guest starts in `/` and can only reach
+    /// the paths under them. Without any, the guest sees the filesystem of
+    /// the host as it is.
+    pub mapped_dirs: Vec<(String, PathBuf)>,
+    /// What the guest is confined to: the files under the granted guest
+    /// paths, the network if it's granted, and the granted variables of
+    /// the host environment. Everything else is denied. Without them, the
+    /// guest is only confined by the other options.
+    pub capabilities: Option<Capabilities>,
+}
+
+impl Default for EmscriptenConfig {
+    fn default() -> Self {
+        EmscriptenConfig {
+            stat_cache: false,
+            clock: None,
+            timezone: None,
+            ids: GuestIds::default(),
+            env: EnvPolicy::Inherit,
+            syscall_handlers: SyscallHandlers::default(),
+            abort_hook: None,
+            syscall_stats: None,
+            stdin: None,
+            stdout: None,
+            stderr: None,
+            stdout_buffering: Buffering::Unbuffered,
+            stdout_tee: None,
+            terminal: None,
+            core_dump: None,
+            exit_runtime: true,
+            audio: None,
+            kv_store: None,
+            net_policy: NetPolicy::default(),
+            networking: false,
+            mapped_dirs: Vec::new(),
+            capabilities: None,
+        }
+    }
+}
+
+impl EmscriptenConfig {
+    /// Handle the syscall `which` with `handler` instead of the emscripten
+    /// implementation, which the handler can still call.
+    pub fn override_syscall<H: SyscallHandler + 'static>(
+        &mut self,
+        which: i32,
+        handler: H,
+    ) -> &mut Self {
+        self.syscall_handlers.insert(which, handler);
+        self
+    }
+}
+
+/// How the environment of the guest relates to the one of the host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvPolicy {
+    /// The guest reads and changes the environment of the host process.
+    Inherit,
+    /// The guest has an environment of its own, which starts with the host
+    /// variables matching one of the `inherit` rules, followed by `vars`.
+    ///
+    /// A rule is either the name of a variable, like `LANG`, or a prefix
+    /// followed by `*`, like `MYAPP_*`. Without any rule, the environment
+    /// is exactly `vars`.
+    Private {
+        inherit: Vec<String>,
+        vars: Vec<(String, String)>,
+    },
+}
+
+/// The identity of the guest process.
+///
+/// The defaults make the guest look like the first process of a
+/// container, run by root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GuestIds {
+    pub pid: u32,
+    pub ppid: u32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl Default for GuestIds {
+    fn default() -> Self {
+        GuestIds {
+            pid: 1,
+            ppid: 0,
+            uid: 0,
+            gid: 0,
+        }
+    }
+}
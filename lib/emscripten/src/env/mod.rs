@@ -10,7 +10,9 @@ pub use self::unix::*;
 #[cfg(windows)]
 pub use self::windows::*;
 
+use crate::utils::{copy_cstr_into_wasm, read_string_from_wasm};
 use crate::{allocate_on_stack, EmscriptenData};
+use std::ffi::CString;
 use std::os::raw::c_int;
 use wasmer_runtime_core::vm::Ctx;
 
@@ -42,14 +44,167 @@ pub(crate) fn get_emscripten_data(ctx: &mut Ctx) -> &mut EmscriptenData {
     unsafe { &mut *(ctx.data as *mut EmscriptenData) }
 }
 
+/// getTempRet0
+pub fn get_temp_ret_0(ctx: &mut Ctx) -> i32 {
+    debug!("emscripten::getTempRet0");
+    if ctx.data.is_null() {
+        return 0;
+    }
+    get_emscripten_data(ctx).temp_ret_0
+}
+
+/// setTempRet0
+pub fn set_temp_ret_0(ctx: &mut Ctx, value: i32) {
+    debug!("emscripten::setTempRet0");
+    if ctx.data.is_null() {
+        return;
+    }
+    get_emscripten_data(ctx).temp_ret_0 = value;
+}
+
 pub fn _getpagesize(_ctx: &mut Ctx) -> u32 {
     debug!("emscripten::_getpagesize");
     16384
 }
 
+/// The environment of a guest that doesn't share the one of the host
+/// process, in the order the variables were defined.
+#[derive(Debug, Default)]
+pub(crate) struct GuestEnv {
+    vars: Vec<(String, String)>,
+}
+
+impl GuestEnv {
+    /// The host variables matching one of the `inherit` rules, followed by `vars`.
+    pub(crate) fn new(inherit: &[String], vars: &[(String, String)]) -> Self {
+        let mut env = GuestEnv::default();
+        for (name, value) in std::env::vars_os() {
+            if let (Some(name), Some(value)) = (name.to_str(), value.to_str()) {
+                if inherit.iter().any(|rule| rule_matches(rule, name)) {
+                    env.set(name, value, true);
+                }
+            }
+        }
+        for (name, value) in vars {
+            env.set(name, value, true);
+        }
+        env
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.vars
+            .iter()
+            .find(|(var, _)| var == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn set(&mut self, name: &str, value: &str, overwrite: bool) {
+        match self.vars.iter_mut().find(|(var, _)| var == name) {
+            Some((_, old)) if overwrite => *old = value.to_string(),
+            Some(_) => {}
+            None => self.vars.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    fn unset(&mut self, name: &str) {
+        self.vars.retain(|(var, _)| var != name);
+    }
+}
+
+/// Whether `name` matches `rule`, which is either a name or a prefix
+/// followed by `*`.
+/// The variables of `granted` that one of the `inherit` rules matches.
+pub(crate) fn granted_rules(inherit: &[String], granted: &[String]) -> Vec<String> {
+    granted
+        .iter()
+        .filter(|name| inherit.iter().any(|rule| rule_matches(rule, name)))
+        .cloned()
+        .collect()
+}
+
+fn rule_matches(rule: &str, name: &str) -> bool {
+    if rule.ends_with('*') {
+        name.starts_with(&rule[..rule.len() - 1])
+    } else {
+        rule == name
+    }
+}
+
+fn get_private_env(ctx: &mut Ctx) -> Option<&mut GuestEnv> {
+    if ctx.data.is_null() {
+        return None;
+    }
+    get_emscripten_data(ctx).environment.as_mut()
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains('=')
+}
+
+/// `getenv` on the private environment of the guest, or `None` if the
+/// guest shares the environment of the host.
+pub(crate) fn private_getenv(ctx: &mut Ctx, name: u32) -> Option<u32> {
+    let name = read_string_from_wasm(ctx.memory(0), name);
+    let value = get_private_env(ctx)?.get(&name).map(str::to_string);
+    Some(match value.and_then(|value| CString::new(value).ok()) {
+        Some(value) => unsafe { copy_cstr_into_wasm(ctx, value.as_ptr()) },
+        None => 0,
+    })
+}
+
+/// `setenv` on the private environment of the guest.
+pub(crate) fn private_setenv(
+    ctx: &mut Ctx,
+    name: u32,
+    value: u32,
+    overwrite: c_int,
+) -> Option<c_int> {
+    let name = read_string_from_wasm(ctx.memory(0), name);
+    let value = read_string_from_wasm(ctx.memory(0), value);
+    let env = get_private_env(ctx)?;
+    if !is_valid_name(&name) {
+        return Some(-1);
+    }
+    env.set(&name, &value, overwrite != 0);
+    Some(0)
+}
+
+/// `putenv` on the private environment of the guest.
+pub(crate) fn private_putenv(ctx: &mut Ctx, string: u32) -> Option<c_int> {
+    let string = read_string_from_wasm(ctx.memory(0), string);
+    let env = get_private_env(ctx)?;
+    // Like glibc, a string without a value removes the variable.
+    match string.find('=') {
+        Some(0) => return Some(-1),
+        Some(eq) => env.set(&string[..eq], &string[eq + 1..], true),
+        None => env.unset(&string),
+    }
+    Some(0)
+}
+
+/// `unsetenv` on the private environment of the guest.
+pub(crate) fn private_unsetenv(ctx: &mut Ctx, name: u32) -> Option<c_int> {
+    let name = read_string_from_wasm(ctx.memory(0), name);
+    let env = get_private_env(ctx)?;
+    if !is_valid_name(&name) {
+        return Some(-1);
+    }
+    env.unset(&name);
+    Some(0)
+}
+
 #[allow(clippy::cast_ptr_alignment)]
 pub fn ___build_environment(ctx: &mut Ctx, environ: c_int) {
     debug!("emscripten::___build_environment {}", environ);
+    if let Some(env) = get_private_env(ctx) {
+        let vars: Vec<String> = env
+            .vars
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        build_private_environment(ctx, environ, &vars);
+        return;
+    }
     const MAX_ENV_VALUES: u32 = 64;
     const TOTAL_ENV_SIZE: u32 = 1024;
     let environment = emscripten_memory_pointer!(ctx.memory(0), environ) as *mut c_int;
@@ -70,6 +225,28 @@ pub fn ___build_environment(ctx: &mut Ctx, environ: c_int) {
     // };
 }
 
+/// Point the guest `environ` at copies of `vars` on the stack.
+#[allow(clippy::cast_ptr_alignment)]
+fn build_private_environment(ctx: &mut Ctx, environ: c_int, vars: &[String]) {
+    let pool_size: usize = vars.iter().map(|var| var.len() + 1).sum();
+    unsafe {
+        let (pool_offset, _) = allocate_on_stack::<u8>(ctx, pool_size as u32);
+        let (env_offset, _) = allocate_on_stack::<u32>(ctx, vars.len() as u32 + 1);
+        let memory = ctx.memory(0);
+        let env_ptr = emscripten_memory_pointer!(memory, env_offset) as *mut u32;
+        let mut offset = pool_offset;
+        for (i, var) in vars.iter().enumerate() {
+            let ptr = emscripten_memory_pointer!(memory, offset);
+            ptr.copy_from_nonoverlapping(var.as_ptr(), var.len());
+            *ptr.add(var.len()) = 0;
+            *env_ptr.add(i) = offset;
+            offset += var.len() as u32 + 1;
+        }
+        *env_ptr.add(vars.len()) = 0;
+        *(emscripten_memory_pointer!(memory, environ) as *mut u32) = env_offset;
+    }
+}
+
 pub fn ___assert_fail(_ctx: &mut Ctx, a: c_int, b: c_int, c: c_int, d: c_int) {
     debug!("emscripten::___assert_fail {} {} {} {}", a, b, c, d);
     // TODO: Implement like emscripten expects regarding memory/page size
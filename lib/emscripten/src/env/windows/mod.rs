@@ -5,7 +5,7 @@ use std::ffi::CString;
 use std::mem;
 use std::os::raw::c_char;
 
-use crate::env::call_malloc;
+use crate::env::{call_malloc, private_getenv, private_putenv, private_setenv, private_unsetenv};
 use crate::utils::{copy_cstr_into_wasm, read_string_from_wasm};
 use wasmer_runtime_core::vm::Ctx;
 
@@ -18,6 +18,9 @@ extern "C" {
 /// emscripten: _getenv // (name: *const char) -> *const c_char;
 pub fn _getenv(ctx: &mut Ctx, name: u32) -> u32 {
     debug!("emscripten::_getenv");
+    if let Some(value) = private_getenv(ctx, name) {
+        return value;
+    }
     let name_string = read_string_from_wasm(ctx.memory(0), name);
     debug!("=> name({:?})", name_string);
     let c_str = unsafe { getenv(name_string.as_ptr() as *const libc::c_char) };
@@ -30,6 +33,9 @@ pub fn _getenv(ctx: &mut Ctx, name: u32) -> u32 {
 /// emscripten: _setenv // (name: *const char, name: *const value, overwrite: int);
 pub fn _setenv(ctx: &mut Ctx, name: u32, value: u32, overwrite: u32) -> c_int {
     debug!("emscripten::_setenv");
+    if let Some(ret) = private_setenv(ctx, name, value, overwrite as c_int) {
+        return ret;
+    }
     let name_addr = emscripten_memory_pointer!(ctx.memory(0), name);
     let value_addr = emscripten_memory_pointer!(ctx.memory(0), value);
     // setenv does not exist on windows, so we hack it with _putenv
@@ -46,6 +52,9 @@ pub fn _setenv(ctx: &mut Ctx, name: u32, value: u32, overwrite: u32) -> c_int {
 /// emscripten: _putenv // (name: *const char);
 pub fn _putenv(ctx: &mut Ctx, name: c_int) -> c_int {
     debug!("emscripten::_putenv");
+    if let Some(ret) = private_putenv(ctx, name as u32) {
+        return ret;
+    }
 
     let name_addr = emscripten_memory_pointer!(ctx.memory(0), name) as *const c_char;
 
@@ -56,6 +65,9 @@ pub fn _putenv(ctx: &mut Ctx, name: c_int) -> c_int {
 /// emscripten: _unsetenv // (name: *const char);
 pub fn _unsetenv(ctx: &mut Ctx, name: u32) -> c_int {
     debug!("emscripten::_unsetenv");
+    if let Some(ret) = private_unsetenv(ctx, name) {
+        return ret;
+    }
     let name_addr = emscripten_memory_pointer!(ctx.memory(0), name);
     let name = read_string_from_wasm(ctx.memory(0), name);
     // no unsetenv on windows, so use putenv with an empty value
@@ -1,266 +1,523 @@
-// use std::collections::HashMap;
+use crate::env::get_emscripten_data;
+use libc::c_int;
+use std::io;
 use wasmer_runtime_core::vm::Ctx;
 
-pub fn ___seterrno(_ctx: &mut Ctx, value: i32) {
+pub fn ___seterrno(ctx: &mut Ctx, value: i32) {
     debug!("emscripten::___seterrno {}", value);
-    // TODO: Incomplete impl
-    eprintln!("failed to set errno!");
-    // value
+    write_errno(ctx, value);
 }
 
-// pub enum ErrnoCodes {
-//     EPERM = 1,
-//     ENOENT = 2,
-//     ESRCH = 3,
-//     EINTR = 4,
-//     EIO = 5,
-//     ENXIO = 6,
-//     E2BIG = 7,
-//     ENOEXEC = 8,
-//     EBADF = 9,
-//     ECHILD = 10,
-//     EAGAIN = 11,
-//     EWOULDBLOCK = 11,
-//     ENOMEM = 12,
-//     EACCES = 13,
-//     EFAULT = 14,
-//     ENOTBLK = 15,
-//     EBUSY = 16,
-//     EEXIST = 17,
-//     EXDEV = 18,
-//     ENODEV = 19,
-//     ENOTDIR = 20,
-//     EISDIR = 21,
-//     EINVAL = 22,
-//     ENFILE = 23,
-//     EMFILE = 24,
-//     ENOTTY = 25,
-//     ETXTBSY = 26,
-//     EFBIG = 27,
-//     ENOSPC = 28,
-//     ESPIPE = 29,
-//     EROFS = 30,
-//     EMLINK = 31,
-//     EPIPE = 32,
-//     EDOM = 33,
-//     ERANGE = 34,
-//     ENOMSG = 42,
-//     EIDRM = 43,
-//     ECHRNG = 44,
-//     EL2NSYNC = 45,
-//     EL3HLT = 46,
-//     EL3RST = 47,
-//     ELNRNG = 48,
-//     EUNATCH = 49,
-//     ENOCSI = 50,
-//     EL2HLT = 51,
-//     EDEADLK = 35,
-//     ENOLCK = 37,
-//     EBADE = 52,
-//     EBADR = 53,
-//     EXFULL = 54,
-//     ENOANO = 55,
-//     EBADRQC = 56,
-//     EBADSLT = 57,
-//     EDEADLOCK = 35,
-//     EBFONT = 59,
-//     ENOSTR = 60,
-//     ENODATA = 61,
-//     ETIME = 62,
-//     ENOSR = 63,
-//     ENONET = 64,
-//     ENOPKG = 65,
-//     EREMOTE = 66,
-//     ENOLINK = 67,
-//     EADV = 68,
-//     ESRMNT = 69,
-//     ECOMM = 70,
-//     EPROTO = 71,
-//     EMULTIHOP = 72,
-//     EDOTDOT = 73,
-//     EBADMSG = 74,
-//     ENOTUNIQ = 76,
-//     EBADFD = 77,
-//     EREMCHG = 78,
-//     ELIBACC = 79,
-//     ELIBBAD = 80,
-//     ELIBSCN = 81,
-//     ELIBMAX = 82,
-//     ELIBEXEC = 83,
-//     ENOSYS = 38,
-//     ENOTEMPTY = 39,
-//     ENAMETOOLONG = 36,
-//     ELOOP = 40,
-//     EOPNOTSUPP = 95,
-//     EPFNOSUPPORT = 96,
-//     ECONNRESET = 104,
-//     ENOBUFS = 105,
-//     EAFNOSUPPORT = 97,
-//     EPROTOTYPE = 91,
-//     ENOTSOCK = 88,
-//     ENOPROTOOPT = 92,
-//     ESHUTDOWN = 108,
-//     ECONNREFUSED = 111,
-//     EADDRINUSE = 98,
-//     ECONNABORTED = 103,
-//     ENETUNREACH = 101,
-//     ENETDOWN = 100,
-//     ETIMEDOUT = 110,
-//     EHOSTDOWN = 112,
-//     EHOSTUNREACH = 113,
-//     EINPROGRESS = 115,
-//     EALREADY = 114,
-//     EDESTADDRREQ = 89,
-//     EMSGSIZE = 90,
-//     EPROTONOSUPPORT = 93,
-//     ESOCKTNOSUPPORT = 94,
-//     EADDRNOTAVAIL = 99,
-//     ENETRESET = 102,
-//     EISCONN = 106,
-//     ENOTCONN = 107,
-//     ETOOMANYREFS = 109,
-//     EUSERS = 87,
-//     EDQUOT = 122,
-//     ESTALE = 116,
-//     ENOTSUP = 95,
-//     ENOMEDIUM = 123,
-//     EILSEQ = 84,
-//     EOVERFLOW = 75,
-//     ECANCELED = 125,
-//     ENOTRECOVERABLE = 131,
-//     EOWNERDEAD = 130,
-//     ESTRPIPE = 86,
-// }
+/// Set the `errno` of the guest, for the imports that fail the way libc
+/// functions do, by returning -1.
+pub(crate) fn set_errno(ctx: &mut Ctx, errno: Errno) {
+    write_errno(ctx, errno.to_emscripten());
+}
+
+fn write_errno(ctx: &mut Ctx, value: i32) {
+    // Modules that never read errno don't export where it is.
+    if ctx.data.is_null() {
+        return;
+    }
+    let location = match &get_emscripten_data(ctx).errno_location {
+        Some(errno_location) => match errno_location.call() {
+            Ok(location) => location,
+            Err(_) => return,
+        },
+        None => return,
+    };
+    unsafe {
+        (emscripten_memory_pointer!(ctx.memory(0), location) as *mut i32).write_unaligned(value);
+    }
+}
+
+/// An error number that can be handed back to the guest.
+///
+/// Hosts number their errors differently, so syscalls translate host
+/// errors into an `Errno` and then into the numbering of the guest ABI.
+/// The discriminants are the numbers emscripten uses, which are Linux's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum Errno {
+    E2BIG = 7,
+    EACCES = 13,
+    EADDRINUSE = 98,
+    EADDRNOTAVAIL = 99,
+    EAFNOSUPPORT = 97,
+    EAGAIN = 11,
+    EALREADY = 114,
+    EBADF = 9,
+    EBADMSG = 74,
+    EBUSY = 16,
+    ECANCELED = 125,
+    ECHILD = 10,
+    ECONNABORTED = 103,
+    ECONNREFUSED = 111,
+    ECONNRESET = 104,
+    EDEADLK = 35,
+    EDESTADDRREQ = 89,
+    EDOM = 33,
+    EDQUOT = 122,
+    EEXIST = 17,
+    EFAULT = 14,
+    EFBIG = 27,
+    EHOSTUNREACH = 113,
+    EIDRM = 43,
+    EILSEQ = 84,
+    EINPROGRESS = 115,
+    EINTR = 4,
+    EINVAL = 22,
+    EIO = 5,
+    EISCONN = 106,
+    EISDIR = 21,
+    ELOOP = 40,
+    EMFILE = 24,
+    EMLINK = 31,
+    EMSGSIZE = 90,
+    EMULTIHOP = 72,
+    ENAMETOOLONG = 36,
+    ENETDOWN = 100,
+    ENETRESET = 102,
+    ENETUNREACH = 101,
+    ENFILE = 23,
+    ENOBUFS = 105,
+    ENODEV = 19,
+    ENOENT = 2,
+    ENOEXEC = 8,
+    ENOLCK = 37,
+    ENOLINK = 67,
+    ENOMEM = 12,
+    ENOMSG = 42,
+    ENOPROTOOPT = 92,
+    ENOSPC = 28,
+    ENOSYS = 38,
+    ENOTCONN = 107,
+    ENOTDIR = 20,
+    ENOTEMPTY = 39,
+    ENOTRECOVERABLE = 131,
+    ENOTSOCK = 88,
+    ENOTSUP = 95,
+    ENOTTY = 25,
+    ENXIO = 6,
+    EOVERFLOW = 75,
+    EOWNERDEAD = 130,
+    EPERM = 1,
+    EPIPE = 32,
+    EPROTO = 71,
+    EPROTONOSUPPORT = 93,
+    EPROTOTYPE = 91,
+    ERANGE = 34,
+    EROFS = 30,
+    ESPIPE = 29,
+    ESRCH = 3,
+    ESTALE = 116,
+    ETIMEDOUT = 110,
+    ETXTBSY = 26,
+    EXDEV = 18,
+}
+
+impl Errno {
+    /// The errno of the last failed host call on this thread.
+    ///
+    /// On Windows the host calls are the ones of the CRT, which sets its
+    /// own `errno` and leaves `GetLastError` to whatever it last called.
+    pub fn last() -> Self {
+        #[cfg(unix)]
+        {
+            Errno::from_io_error(&io::Error::last_os_error())
+        }
+        #[cfg(windows)]
+        {
+            Errno::from_windows_crt(unsafe { *_errno() })
+        }
+    }
+
+    /// The errno of an error returned by the standard library.
+    pub fn from_io_error(err: &io::Error) -> Self {
+        let raw = err.raw_os_error().unwrap_or(0);
+        #[cfg(unix)]
+        {
+            Errno::from_unix(raw)
+        }
+        #[cfg(windows)]
+        {
+            Errno::from_windows_error(raw as u32)
+        }
+    }
+
+    /// Translate a host `errno` value.
+    #[cfg(unix)]
+    pub fn from_unix(errno: c_int) -> Self {
+        lookup(&UNIX_ERRNOS, errno)
+    }
+
+    /// Translate an `errno` value set by the Microsoft CRT.
+    pub fn from_windows_crt(errno: c_int) -> Self {
+        lookup(&WINDOWS_CRT_ERRNOS, errno)
+    }
+
+    /// Translate a Windows system or Winsock error code, as returned
+    /// by `GetLastError` or `WSAGetLastError`.
+    pub fn from_windows_error(code: u32) -> Self {
+        lookup(&WINDOWS_ERRORS, code)
+    }
+
+    /// The number emscripten's libc expects for this error.
+    pub fn to_emscripten(self) -> i32 {
+        self as i32
+    }
+
+    /// The number WASI's `errno` type uses for this error.
+    pub fn to_wasi(self) -> u16 {
+        match self {
+            Errno::E2BIG => 1,
+            Errno::EACCES => 2,
+            Errno::EADDRINUSE => 3,
+            Errno::EADDRNOTAVAIL => 4,
+            Errno::EAFNOSUPPORT => 5,
+            Errno::EAGAIN => 6,
+            Errno::EALREADY => 7,
+            Errno::EBADF => 8,
+            Errno::EBADMSG => 9,
+            Errno::EBUSY => 10,
+            Errno::ECANCELED => 11,
+            Errno::ECHILD => 12,
+            Errno::ECONNABORTED => 13,
+            Errno::ECONNREFUSED => 14,
+            Errno::ECONNRESET => 15,
+            Errno::EDEADLK => 16,
+            Errno::EDESTADDRREQ => 17,
+            Errno::EDOM => 18,
+            Errno::EDQUOT => 19,
+            Errno::EEXIST => 20,
+            Errno::EFAULT => 21,
+            Errno::EFBIG => 22,
+            Errno::EHOSTUNREACH => 23,
+            Errno::EIDRM => 24,
+            Errno::EILSEQ => 25,
+            Errno::EINPROGRESS => 26,
+            Errno::EINTR => 27,
+            Errno::EINVAL => 28,
+            Errno::EIO => 29,
+            Errno::EISCONN => 30,
+            Errno::EISDIR => 31,
+            Errno::ELOOP => 32,
+            Errno::EMFILE => 33,
+            Errno::EMLINK => 34,
+            Errno::EMSGSIZE => 35,
+            Errno::EMULTIHOP => 36,
+            Errno::ENAMETOOLONG => 37,
+            Errno::ENETDOWN => 38,
+            Errno::ENETRESET => 39,
+            Errno::ENETUNREACH => 40,
+            Errno::ENFILE => 41,
+            Errno::ENOBUFS => 42,
+            Errno::ENODEV => 43,
+            Errno::ENOENT => 44,
+            Errno::ENOEXEC => 45,
+            Errno::ENOLCK => 46,
+            Errno::ENOLINK => 47,
+            Errno::ENOMEM => 48,
+            Errno::ENOMSG => 49,
+            Errno::ENOPROTOOPT => 50,
+            Errno::ENOSPC => 51,
+            Errno::ENOSYS => 52,
+            Errno::ENOTCONN => 53,
+            Errno::ENOTDIR => 54,
+            Errno::ENOTEMPTY => 55,
+            Errno::ENOTRECOVERABLE => 56,
+            Errno::ENOTSOCK => 57,
+            Errno::ENOTSUP => 58,
+            Errno::ENOTTY => 59,
+            Errno::ENXIO => 60,
+            Errno::EOVERFLOW => 61,
+            Errno::EOWNERDEAD => 62,
+            Errno::EPERM => 63,
+            Errno::EPIPE => 64,
+            Errno::EPROTO => 65,
+            Errno::EPROTONOSUPPORT => 66,
+            Errno::EPROTOTYPE => 67,
+            Errno::ERANGE => 68,
+            Errno::EROFS => 69,
+            Errno::ESPIPE => 70,
+            Errno::ESRCH => 71,
+            Errno::ESTALE => 72,
+            Errno::ETIMEDOUT => 73,
+            Errno::ETXTBSY => 74,
+            Errno::EXDEV => 75,
+        }
+    }
+}
+
+#[cfg(windows)]
+extern "C" {
+    /// The address of the CRT's `errno` for this thread.
+    fn _errno() -> *mut c_int;
+}
+
+/// Converts the return value of a host call that returns -1 and sets
+/// `errno` on failure into the `-errno` that emscripten syscalls return.
+pub fn translate_ret(ret: c_int) -> c_int {
+    if ret == -1 {
+        -Errno::last().to_emscripten()
+    } else {
+        ret
+    }
+}
+
+// Errors without an equivalent are reported as an I/O error.
+fn lookup<T: PartialEq>(table: &[(T, Errno)], value: T) -> Errno {
+    table
+        .iter()
+        .find(|(host, _)| *host == value)
+        .map(|&(_, errno)| errno)
+        .unwrap_or(Errno::EIO)
+}
+
+#[cfg(unix)]
+static UNIX_ERRNOS: [(c_int, Errno); 76] = [
+    (libc::E2BIG, Errno::E2BIG),
+    (libc::EACCES, Errno::EACCES),
+    (libc::EADDRINUSE, Errno::EADDRINUSE),
+    (libc::EADDRNOTAVAIL, Errno::EADDRNOTAVAIL),
+    (libc::EAFNOSUPPORT, Errno::EAFNOSUPPORT),
+    (libc::EAGAIN, Errno::EAGAIN),
+    (libc::EALREADY, Errno::EALREADY),
+    (libc::EBADF, Errno::EBADF),
+    (libc::EBADMSG, Errno::EBADMSG),
+    (libc::EBUSY, Errno::EBUSY),
+    (libc::ECANCELED, Errno::ECANCELED),
+    (libc::ECHILD, Errno::ECHILD),
+    (libc::ECONNABORTED, Errno::ECONNABORTED),
+    (libc::ECONNREFUSED, Errno::ECONNREFUSED),
+    (libc::ECONNRESET, Errno::ECONNRESET),
+    (libc::EDEADLK, Errno::EDEADLK),
+    (libc::EDESTADDRREQ, Errno::EDESTADDRREQ),
+    (libc::EDOM, Errno::EDOM),
+    (libc::EDQUOT, Errno::EDQUOT),
+    (libc::EEXIST, Errno::EEXIST),
+    (libc::EFAULT, Errno::EFAULT),
+    (libc::EFBIG, Errno::EFBIG),
+    (libc::EHOSTUNREACH, Errno::EHOSTUNREACH),
+    (libc::EIDRM, Errno::EIDRM),
+    (libc::EILSEQ, Errno::EILSEQ),
+    (libc::EINPROGRESS, Errno::EINPROGRESS),
+    (libc::EINTR, Errno::EINTR),
+    (libc::EINVAL, Errno::EINVAL),
+    (libc::EIO, Errno::EIO),
+    (libc::EISCONN, Errno::EISCONN),
+    (libc::EISDIR, Errno::EISDIR),
+    (libc::ELOOP, Errno::ELOOP),
+    (libc::EMFILE, Errno::EMFILE),
+    (libc::EMLINK, Errno::EMLINK),
+    (libc::EMSGSIZE, Errno::EMSGSIZE),
+    (libc::EMULTIHOP, Errno::EMULTIHOP),
+    (libc::ENAMETOOLONG, Errno::ENAMETOOLONG),
+    (libc::ENETDOWN, Errno::ENETDOWN),
+    (libc::ENETRESET, Errno::ENETRESET),
+    (libc::ENETUNREACH, Errno::ENETUNREACH),
+    (libc::ENFILE, Errno::ENFILE),
+    (libc::ENOBUFS, Errno::ENOBUFS),
+    (libc::ENODEV, Errno::ENODEV),
+    (libc::ENOENT, Errno::ENOENT),
+    (libc::ENOEXEC, Errno::ENOEXEC),
+    (libc::ENOLCK, Errno::ENOLCK),
+    (libc::ENOLINK, Errno::ENOLINK),
+    (libc::ENOMEM, Errno::ENOMEM),
+    (libc::ENOMSG, Errno::ENOMSG),
+    (libc::ENOPROTOOPT, Errno::ENOPROTOOPT),
+    (libc::ENOSPC, Errno::ENOSPC),
+    (libc::ENOSYS, Errno::ENOSYS),
+    (libc::ENOTCONN, Errno::ENOTCONN),
+    (libc::ENOTDIR, Errno::ENOTDIR),
+    (libc::ENOTEMPTY, Errno::ENOTEMPTY),
+    (libc::ENOTRECOVERABLE, Errno::ENOTRECOVERABLE),
+    (libc::ENOTSOCK, Errno::ENOTSOCK),
+    (libc::ENOTSUP, Errno::ENOTSUP),
+    (libc::ENOTTY, Errno::ENOTTY),
+    (libc::ENXIO, Errno::ENXIO),
+    (libc::EOVERFLOW, Errno::EOVERFLOW),
+    (libc::EOWNERDEAD, Errno::EOWNERDEAD),
+    (libc::EPERM, Errno::EPERM),
+    (libc::EPIPE, Errno::EPIPE),
+    (libc::EPROTO, Errno::EPROTO),
+    (libc::EPROTONOSUPPORT, Errno::EPROTONOSUPPORT),
+    (libc::EPROTOTYPE, Errno::EPROTOTYPE),
+    (libc::ERANGE, Errno::ERANGE),
+    (libc::EROFS, Errno::EROFS),
+    (libc::ESPIPE, Errno::ESPIPE),
+    (libc::ESRCH, Errno::ESRCH),
+    (libc::ESTALE, Errno::ESTALE),
+    (libc::ETIMEDOUT, Errno::ETIMEDOUT),
+    (libc::ETXTBSY, Errno::ETXTBSY),
+    (libc::EXDEV, Errno::EXDEV),
+    (libc::EOPNOTSUPP, Errno::ENOTSUP),
+];
+
+// From the Microsoft CRT's errno.h, including the POSIX supplement.
+static WINDOWS_CRT_ERRNOS: [(c_int, Errno); 74] = [
+    (1, Errno::EPERM),
+    (2, Errno::ENOENT),
+    (3, Errno::ESRCH),
+    (4, Errno::EINTR),
+    (5, Errno::EIO),
+    (6, Errno::ENXIO),
+    (7, Errno::E2BIG),
+    (8, Errno::ENOEXEC),
+    (9, Errno::EBADF),
+    (10, Errno::ECHILD),
+    (11, Errno::EAGAIN),
+    (12, Errno::ENOMEM),
+    (13, Errno::EACCES),
+    (14, Errno::EFAULT),
+    (16, Errno::EBUSY),
+    (17, Errno::EEXIST),
+    (18, Errno::EXDEV),
+    (19, Errno::ENODEV),
+    (20, Errno::ENOTDIR),
+    (21, Errno::EISDIR),
+    (22, Errno::EINVAL),
+    (23, Errno::ENFILE),
+    (24, Errno::EMFILE),
+    (25, Errno::ENOTTY),
+    (27, Errno::EFBIG),
+    (28, Errno::ENOSPC),
+    (29, Errno::ESPIPE),
+    (30, Errno::EROFS),
+    (31, Errno::EMLINK),
+    (32, Errno::EPIPE),
+    (33, Errno::EDOM),
+    (34, Errno::ERANGE),
+    (36, Errno::EDEADLK),
+    (38, Errno::ENAMETOOLONG),
+    (39, Errno::ENOLCK),
+    (40, Errno::ENOSYS),
+    (41, Errno::ENOTEMPTY),
+    (42, Errno::EILSEQ),
+    (100, Errno::EADDRINUSE),
+    (101, Errno::EADDRNOTAVAIL),
+    (102, Errno::EAFNOSUPPORT),
+    (103, Errno::EALREADY),
+    (104, Errno::EBADMSG),
+    (105, Errno::ECANCELED),
+    (106, Errno::ECONNABORTED),
+    (107, Errno::ECONNREFUSED),
+    (108, Errno::ECONNRESET),
+    (109, Errno::EDESTADDRREQ),
+    (110, Errno::EHOSTUNREACH),
+    (111, Errno::EIDRM),
+    (112, Errno::EINPROGRESS),
+    (113, Errno::EISCONN),
+    (114, Errno::ELOOP),
+    (115, Errno::EMSGSIZE),
+    (116, Errno::ENETDOWN),
+    (117, Errno::ENETRESET),
+    (118, Errno::ENETUNREACH),
+    (119, Errno::ENOBUFS),
+    (121, Errno::ENOLINK),
+    (122, Errno::ENOMSG),
+    (123, Errno::ENOPROTOOPT),
+    (126, Errno::ENOTCONN),
+    (127, Errno::ENOTRECOVERABLE),
+    (128, Errno::ENOTSOCK),
+    (129, Errno::ENOTSUP),
+    (130, Errno::ENOTSUP),
+    (132, Errno::EOVERFLOW),
+    (133, Errno::EOWNERDEAD),
+    (134, Errno::EPROTO),
+    (135, Errno::EPROTONOSUPPORT),
+    (136, Errno::EPROTOTYPE),
+    (138, Errno::ETIMEDOUT),
+    (139, Errno::ETXTBSY),
+    (140, Errno::EAGAIN),
+];
 
-// pub struct ErrnoMessages<'a> {
-//     message_map: HashMap<u32, &'a str>
-// }
+static WINDOWS_ERRORS: [(u32, Errno); 53] = [
+    (1, Errno::ENOSYS),            // ERROR_INVALID_FUNCTION
+    (2, Errno::ENOENT),            // ERROR_FILE_NOT_FOUND
+    (3, Errno::ENOENT),            // ERROR_PATH_NOT_FOUND
+    (4, Errno::EMFILE),            // ERROR_TOO_MANY_OPEN_FILES
+    (5, Errno::EACCES),            // ERROR_ACCESS_DENIED
+    (6, Errno::EBADF),             // ERROR_INVALID_HANDLE
+    (8, Errno::ENOMEM),            // ERROR_NOT_ENOUGH_MEMORY
+    (14, Errno::ENOMEM),           // ERROR_OUTOFMEMORY
+    (15, Errno::ENOENT),           // ERROR_INVALID_DRIVE
+    (17, Errno::EXDEV),            // ERROR_NOT_SAME_DEVICE
+    (18, Errno::ENOENT),           // ERROR_NO_MORE_FILES
+    (19, Errno::EROFS),            // ERROR_WRITE_PROTECT
+    (32, Errno::EBUSY),            // ERROR_SHARING_VIOLATION
+    (33, Errno::EBUSY),            // ERROR_LOCK_VIOLATION
+    (39, Errno::ENOSPC),           // ERROR_HANDLE_DISK_FULL
+    (50, Errno::ENOTSUP),          // ERROR_NOT_SUPPORTED
+    (53, Errno::ENOENT),           // ERROR_BAD_NETPATH
+    (80, Errno::EEXIST),           // ERROR_FILE_EXISTS
+    (87, Errno::EINVAL),           // ERROR_INVALID_PARAMETER
+    (109, Errno::EPIPE),           // ERROR_BROKEN_PIPE
+    (112, Errno::ENOSPC),          // ERROR_DISK_FULL
+    (120, Errno::ENOSYS),          // ERROR_CALL_NOT_IMPLEMENTED
+    (123, Errno::ENOENT),          // ERROR_INVALID_NAME
+    (145, Errno::ENOTEMPTY),       // ERROR_DIR_NOT_EMPTY
+    (183, Errno::EEXIST),          // ERROR_ALREADY_EXISTS
+    (206, Errno::ENAMETOOLONG),    // ERROR_FILENAME_EXCED_RANGE
+    (267, Errno::ENOTDIR),         // ERROR_DIRECTORY
+    (995, Errno::EINTR),           // ERROR_OPERATION_ABORTED
+    (1460, Errno::ETIMEDOUT),      // ERROR_TIMEOUT
+    (10004, Errno::EINTR),         // WSAEINTR
+    (10009, Errno::EBADF),         // WSAEBADF
+    (10013, Errno::EACCES),        // WSAEACCES
+    (10014, Errno::EFAULT),        // WSAEFAULT
+    (10022, Errno::EINVAL),        // WSAEINVAL
+    (10024, Errno::EMFILE),        // WSAEMFILE
+    (10035, Errno::EAGAIN),        // WSAEWOULDBLOCK
+    (10036, Errno::EINPROGRESS),   // WSAEINPROGRESS
+    (10037, Errno::EALREADY),      // WSAEALREADY
+    (10038, Errno::ENOTSOCK),      // WSAENOTSOCK
+    (10040, Errno::EMSGSIZE),      // WSAEMSGSIZE
+    (10047, Errno::EAFNOSUPPORT),  // WSAEAFNOSUPPORT
+    (10048, Errno::EADDRINUSE),    // WSAEADDRINUSE
+    (10049, Errno::EADDRNOTAVAIL), // WSAEADDRNOTAVAIL
+    (10050, Errno::ENETDOWN),      // WSAENETDOWN
+    (10051, Errno::ENETUNREACH),   // WSAENETUNREACH
+    (10053, Errno::ECONNABORTED),  // WSAECONNABORTED
+    (10054, Errno::ECONNRESET),    // WSAECONNRESET
+    (10055, Errno::ENOBUFS),       // WSAENOBUFS
+    (10056, Errno::EISCONN),       // WSAEISCONN
+    (10057, Errno::ENOTCONN),      // WSAENOTCONN
+    (10060, Errno::ETIMEDOUT),     // WSAETIMEDOUT
+    (10061, Errno::ECONNREFUSED),  // WSAECONNREFUSED
+    (10065, Errno::EHOSTUNREACH),  // WSAEHOSTUNREACH
+];
 
-// impl<'a> ErrnoMessages<'a> {
-//     fn new() -> Self {
-//         let mut message_map = HashMap::new();
-//         message_map.insert(0, "Success");
-//         message_map.insert(1, "Not super-user");
-//         message_map.insert(2, "No such file or directory");
-//         message_map.insert(3, "No such process");
-//         message_map.insert(4, "Interrupted system call");
-//         message_map.insert(5, "I/O error");
-//         message_map.insert(6, "No such device or address");
-//         message_map.insert(7, "Arg list too long");
-//         message_map.insert(8, "Exec format error");
-//         message_map.insert(9, "Bad file number");
-//         message_map.insert(10, "No children");
-//         message_map.insert(11, "No more processes");
-//         message_map.insert(12, "Not enough core");
-//         message_map.insert(13, "Permission denied");
-//         message_map.insert(14, "Bad address");
-//         message_map.insert(15, "Block device required");
-//         message_map.insert(16, "Mount device busy");
-//         message_map.insert(17, "File exists");
-//         message_map.insert(18, "Cross-device link");
-//         message_map.insert(19, "No such device");
-//         message_map.insert(20, "Not a directory");
-//         message_map.insert(21, "Is a directory");
-//         message_map.insert(22, "Invalid argument");
-//         message_map.insert(23, "Too many open files in system");
-//         message_map.insert(24, "Too many open files");
-//         message_map.insert(25, "Not a typewriter");
-//         message_map.insert(26, "Text file busy");
-//         message_map.insert(27, "File too large");
-//         message_map.insert(28, "No space left on device");
-//         message_map.insert(29, "Illegal seek");
-//         message_map.insert(30, "Read only file system");
-//         message_map.insert(31, "Too many links");
-//         message_map.insert(32, "Broken pipe");
-//         message_map.insert(33, "Math arg out of domain of func");
-//         message_map.insert(34, "Math result not representable");
-//         message_map.insert(35, "File locking deadlock error");
-//         message_map.insert(36, "File or path name too long");
-//         message_map.insert(37, "No record locks available");
-//         message_map.insert(38, "Function not implemented");
-//         message_map.insert(39, "Directory not empty");
-//         message_map.insert(40, "Too many symbolic links");
-//         message_map.insert(42, "No message of desired type");
-//         message_map.insert(43, "Identifier removed");
-//         message_map.insert(44, "Channel number out of range");
-//         message_map.insert(45, "Level 2 not synchronized");
-//         message_map.insert(46, "Level 3 halted");
-//         message_map.insert(47, "Level 3 reset");
-//         message_map.insert(48, "Link number out of range");
-//         message_map.insert(49, "Protocol driver not attached");
-//         message_map.insert(50, "No CSI structure available");
-//         message_map.insert(51, "Level 2 halted");
-//         message_map.insert(52, "Invalid exchange");
-//         message_map.insert(53, "Invalid request descriptor");
-//         message_map.insert(54, "Exchange full");
-//         message_map.insert(55, "No anode");
-//         message_map.insert(56, "Invalid request code");
-//         message_map.insert(57, "Invalid slot");
-//         message_map.insert(59, "Bad font file fmt");
-//         message_map.insert(60, "Device not a stream");
-//         message_map.insert(61, "No data (for no delay io)");
-//         message_map.insert(62, "Timer expired");
-//         message_map.insert(63, "Out of streams resources");
-//         message_map.insert(64, "Machine is not on the network");
-//         message_map.insert(65, "Package not installed");
-//         message_map.insert(66, "The object is remote");
-//         message_map.insert(67, "The link has been severed");
-//         message_map.insert(68, "Advertise error");
-//         message_map.insert(69, "Srmount error");
-//         message_map.insert(70, "Communication error on send");
-//         message_map.insert(71, "Protocol error");
-//         message_map.insert(72, "Multihop attempted");
-//         message_map.insert(73, "Cross mount point (not really error)");
-//         message_map.insert(74, "Trying to read unreadable message");
-//         message_map.insert(75, "Value too large for defined data type");
-//         message_map.insert(76, "Given log. name not unique");
-//         message_map.insert(77, "f.d. invalid for this operation");
-//         message_map.insert(78, "Remote address changed");
-//         message_map.insert(79, "Can   access a needed shared lib");
-//         message_map.insert(80, "Accessing a corrupted shared lib");
-//         message_map.insert(81, ".lib section in a.out corrupted");
-//         message_map.insert(82, "Attempting to link in too many libs");
-//         message_map.insert(83, "Attempting to exec a shared library");
-//         message_map.insert(84, "Illegal byte sequence");
-//         message_map.insert(86, "Streams pipe error");
-//         message_map.insert(87, "Too many users");
-//         message_map.insert(88, "Socket operation on non-socket");
-//         message_map.insert(89, "Destination address required");
-//         message_map.insert(90, "Message too long");
-//         message_map.insert(91, "Protocol wrong type for socket");
-//         message_map.insert(92, "Protocol not available");
-//         message_map.insert(93, "Unknown protocol");
-//         message_map.insert(94, "Socket type not supported");
-//         message_map.insert(95, "Not supported");
-//         message_map.insert(96, "Protocol family not supported");
-//         message_map.insert(97, "Address family not supported by protocol family");
-//         message_map.insert(98, "Address already in use");
-//         message_map.insert(99, "Address not available");
-//         message_map.insert(100, "Network interface is not configured");
-//         message_map.insert(101, "Network is unreachable");
-//         message_map.insert(102, "Connection reset by network");
-//         message_map.insert(103, "Connection aborted");
-//         message_map.insert(104, "Connection reset by peer");
-//         message_map.insert(105, "No buffer space available");
-//         message_map.insert(106, "Socket is already connected");
-//         message_map.insert(107, "Socket is not connected");
-//         message_map.insert(108, "Can't send after socket shutdown");
-//         message_map.insert(109, "Too many references");
-//         message_map.insert(110, "Connection timed out");
-//         message_map.insert(111, "Connection refused");
-//         message_map.insert(112, "Host is down");
-//         message_map.insert(113, "Host is unreachable");
-//         message_map.insert(114, "Socket already connected");
-//         message_map.insert(115, "Connection already in progress");
-//         message_map.insert(116, "Stale file handle");
-//         message_map.insert(122, "Quota exceeded");
-//         message_map.insert(123, "No medium (in tape drive)");
-//         message_map.insert(125, "Operation canceled");
-//         message_map.insert(130, "Previous owner died");
-//         message_map.insert(131, "State not recoverable");
+#[cfg(test)]
+mod tests {
+    use super::Errno;
 
-//         ErrnoMessages {
-//             message_map,
-//         }
-//     }
-// }
+    #[test]
+    fn should_map_windows_errors() {
+        assert_eq!(Errno::from_windows_error(2), Errno::ENOENT);
+        assert_eq!(Errno::from_windows_error(5), Errno::EACCES);
+        assert_eq!(Errno::from_windows_error(6), Errno::EBADF);
+        assert_eq!(Errno::from_windows_error(183), Errno::EEXIST);
+        assert_eq!(Errno::from_windows_error(10035), Errno::EAGAIN);
+        assert_eq!(Errno::from_windows_error(10054), Errno::ECONNRESET);
+        assert_eq!(Errno::from_windows_error(0xdead), Errno::EIO);
+        assert_eq!(Errno::from_windows_crt(2), Errno::ENOENT);
+        assert_eq!(Errno::from_windows_crt(140), Errno::EAGAIN);
+    }
+
+    #[test]
+    fn should_number_errors_per_abi() {
+        assert_eq!(Errno::EAGAIN.to_emscripten(), 11);
+        assert_eq!(Errno::EAGAIN.to_wasi(), 6);
+        assert_eq!(Errno::ENOTSUP.to_emscripten(), 95);
+        assert_eq!(Errno::EXDEV.to_wasi(), 75);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_map_host_errnos() {
+        assert_eq!(Errno::from_unix(libc::EAGAIN), Errno::EAGAIN);
+        assert_eq!(Errno::from_unix(libc::ENOTEMPTY), Errno::ENOTEMPTY);
+        assert_eq!(Errno::from_unix(libc::EOPNOTSUPP), Errno::ENOTSUP);
+    }
+}
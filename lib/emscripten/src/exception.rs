@@ -1,6 +1,112 @@
-use super::env;
-use super::process::_abort;
-use wasmer_runtime_core::vm::Ctx;
+//! C++ exceptions, the way emscripten's JavaScript runtime does them.
+//!
+//! A function that catches makes its calls through `invoke_*` imports.
+//! `___cxa_throw` records the exception and traps out to the innermost of
+//! those, which tells the caller through `setThrew` to find the handler
+//! with `___cxa_find_matching_catch_*`. The exceptions stay alive while a
+//! handler holds them, and their destructor runs once the last one ends.
+use super::env::{self, get_emscripten_data};
+use super::process::abort_with_message;
+use std::collections::HashMap;
+use wasmer_runtime_core::{error::RuntimeError, vm::Ctx};
+
+/// The live exceptions of an instance.
+#[derive(Debug, Default)]
+pub(crate) struct Exceptions {
+    /// By the address of the exception object.
+    infos: HashMap<u32, ExceptionInfo>,
+    /// The exceptions whose handler is running, innermost last.
+    caught: Vec<u32>,
+    /// The exception being thrown, or 0.
+    last: u32,
+    /// The exceptions thrown and not caught yet.
+    uncaught: u32,
+    /// Where `___cxa_find_matching_catch_*` passes `___cxa_can_catch` the
+    /// address of the exception, which it adjusts to the type caught.
+    can_catch_buffer: u32,
+    /// Set while `___cxa_throw` traps out to the `invoke_*` that catches
+    /// it, so the trap isn't taken for an error.
+    throwing: bool,
+}
+
+#[derive(Debug)]
+struct ExceptionInfo {
+    ty: u32,
+    destructor: u32,
+    /// The handlers that hold the exception.
+    refcount: u32,
+    caught: bool,
+    rethrown: bool,
+    /// The address of the exception as the type that caught it.
+    adjusted: u32,
+}
+
+impl Exceptions {
+    /// Whether a throw is what trapped out of the guest, which is forgotten.
+    pub(crate) fn take_throwing(&mut self) -> bool {
+        let throwing = self.throwing;
+        self.throwing = false;
+        throwing
+    }
+
+    /// The exception a handler was given the adjusted address of.
+    fn de_adjust(&self, adjusted: u32) -> u32 {
+        if self.infos.contains_key(&adjusted) {
+            return adjusted;
+        }
+        self.infos
+            .iter()
+            .find(|(_, info)| info.adjusted == adjusted)
+            .map_or(adjusted, |(&ptr, _)| ptr)
+    }
+}
+
+/// How `___cxa_throw` traps out of the guest.
+pub struct Throw;
+
+// What the error shows when no handler catches the exception.
+impl std::fmt::Debug for Throw {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("uncaught C++ exception")
+    }
+}
+
+fn throw(ctx: &mut Ctx) -> Result<(), Throw> {
+    if !ctx.data.is_null() {
+        get_emscripten_data(ctx).exceptions.throwing = true;
+    }
+    Err(Throw)
+}
+
+/// Release a hold on the exception at `ptr`, destroying and freeing it
+/// once no handler holds it.
+fn release(ctx: &mut Ctx, ptr: u32) -> Result<(), RuntimeError> {
+    let exceptions = &mut get_emscripten_data(ctx).exceptions;
+    let destructor = match exceptions.infos.get_mut(&ptr) {
+        Some(info) => {
+            info.refcount = info.refcount.saturating_sub(1);
+            if info.refcount > 0 || info.rethrown {
+                return Ok(());
+            }
+            info.destructor
+        }
+        None => return Ok(()),
+    };
+    exceptions.infos.remove(&ptr);
+    if destructor != 0 {
+        match &get_emscripten_data(ctx).dyn_call_vi {
+            Some(dyn_call_vi) => dyn_call_vi.call(destructor as i32, ptr as i32)?,
+            None => {
+                abort_with_message(
+                    ctx,
+                    "can't destroy an exception in a module that doesn't export dynCall_vi",
+                );
+                return Ok(());
+            }
+        }
+    }
+    ___cxa_free_exception(ctx, ptr)
+}
 
 /// emscripten: ___cxa_allocate_exception
 pub fn ___cxa_allocate_exception(ctx: &mut Ctx, size: u32) -> u32 {
@@ -8,9 +114,270 @@ pub fn ___cxa_allocate_exception(ctx: &mut Ctx, size: u32) -> u32 {
     env::call_malloc(ctx, size as _)
 }
 
+/// emscripten: ___cxa_free_exception
+pub fn ___cxa_free_exception(ctx: &mut Ctx, ptr: u32) -> Result<(), RuntimeError> {
+    debug!("emscripten::___cxa_free_exception");
+    if ctx.data.is_null() {
+        return Ok(());
+    }
+    get_emscripten_data(ctx).free.call(ptr)
+}
+
 /// emscripten: ___cxa_throw
-/// TODO: We don't have support for exceptions yet
-pub fn ___cxa_throw(ctx: &mut Ctx, _ptr: u32, _ty: u32, _destructor: u32) {
+pub fn ___cxa_throw(ctx: &mut Ctx, ptr: u32, ty: u32, destructor: u32) -> Result<(), Throw> {
     debug!("emscripten::___cxa_throw");
-    _abort(ctx);
+    if !ctx.data.is_null() {
+        let exceptions = &mut get_emscripten_data(ctx).exceptions;
+        exceptions.infos.insert(
+            ptr,
+            ExceptionInfo {
+                ty,
+                destructor,
+                refcount: 0,
+                caught: false,
+                rethrown: false,
+                adjusted: ptr,
+            },
+        );
+        exceptions.last = ptr;
+        exceptions.uncaught += 1;
+    }
+    throw(ctx)
+}
+
+/// emscripten: ___cxa_rethrow
+pub fn ___cxa_rethrow(ctx: &mut Ctx) -> Result<(), Throw> {
+    debug!("emscripten::___cxa_rethrow");
+    if !ctx.data.is_null() {
+        let exceptions = &mut get_emscripten_data(ctx).exceptions;
+        if let Some(adjusted) = exceptions.caught.pop() {
+            let ptr = exceptions.de_adjust(adjusted);
+            if let Some(info) = exceptions.infos.get_mut(&ptr) {
+                if !info.rethrown {
+                    // The handler that rethrows still ends its catch.
+                    info.rethrown = true;
+                    exceptions.caught.push(ptr);
+                }
+            }
+            exceptions.last = ptr;
+        }
+    }
+    throw(ctx)
+}
+
+/// emscripten: ___resumeException
+pub fn ___resume_exception(ctx: &mut Ctx, ptr: u32) -> Result<(), Throw> {
+    debug!("emscripten::___resumeException");
+    if !ctx.data.is_null() {
+        let exceptions = &mut get_emscripten_data(ctx).exceptions;
+        if exceptions.last == 0 {
+            exceptions.last = ptr;
+        }
+    }
+    throw(ctx)
+}
+
+/// The exception being thrown, adjusted to the first of `types` that
+/// catches it, with the type that does in `tempRet0`. If none of them
+/// does, the type of the exception is.
+fn find_matching_catch(ctx: &mut Ctx, types: &[u32]) -> Result<u32, RuntimeError> {
+    if ctx.data.is_null() {
+        return Ok(0);
+    }
+    let data = get_emscripten_data(ctx);
+    let thrown = data.exceptions.last;
+    let thrown_type = match data.exceptions.infos.get(&thrown) {
+        Some(info) if thrown != 0 => info.ty,
+        _ => {
+            data.temp_ret_0 = 0;
+            return Ok(thrown);
+        }
+    };
+    if thrown_type == 0 {
+        data.temp_ret_0 = 0;
+        return Ok(thrown);
+    }
+
+    let has_rtti = data.can_catch.is_some();
+    let mut buffer = data.exceptions.can_catch_buffer;
+    if buffer == 0 && has_rtti {
+        buffer = env::call_malloc(ctx, 4);
+        if buffer == 0 {
+            abort_with_message(ctx, "out of memory to match an exception");
+            return Ok(0);
+        }
+        get_emscripten_data(ctx).exceptions.can_catch_buffer = buffer;
+    }
+    for &ty in types.iter().filter(|&&ty| ty != 0) {
+        let adjusted = if has_rtti {
+            // `___cxa_can_catch` takes the address of the address of the
+            // exception, and adjusts the address to the type it checks.
+            ctx.memory(0).view::<u32>()[(buffer / 4) as usize].set(thrown);
+            let caught = match &get_emscripten_data(ctx).can_catch {
+                Some(can_catch) => can_catch.call(ty, thrown_type, buffer)?,
+                None => 0,
+            };
+            if caught == 0 {
+                continue;
+            }
+            ctx.memory(0).view::<u32>()[(buffer / 4) as usize].get()
+        } else if ty == thrown_type {
+            // Without RTTI to go by, only the type thrown catches it.
+            thrown
+        } else {
+            continue;
+        };
+        let data = get_emscripten_data(ctx);
+        if let Some(info) = data.exceptions.infos.get_mut(&thrown) {
+            info.adjusted = adjusted;
+        }
+        data.temp_ret_0 = ty as i32;
+        return Ok(adjusted);
+    }
+    get_emscripten_data(ctx).temp_ret_0 = thrown_type as i32;
+    Ok(thrown)
+}
+
+/// emscripten: ___cxa_find_matching_catch_2
+pub fn ___cxa_find_matching_catch_2(ctx: &mut Ctx) -> Result<u32, RuntimeError> {
+    debug!("emscripten::___cxa_find_matching_catch_2");
+    find_matching_catch(ctx, &[])
+}
+
+/// emscripten: ___cxa_find_matching_catch_3
+pub fn ___cxa_find_matching_catch_3(ctx: &mut Ctx, a: u32) -> Result<u32, RuntimeError> {
+    debug!("emscripten::___cxa_find_matching_catch_3");
+    find_matching_catch(ctx, &[a])
+}
+
+/// emscripten: ___cxa_find_matching_catch_4
+pub fn ___cxa_find_matching_catch_4(ctx: &mut Ctx, a: u32, b: u32) -> Result<u32, RuntimeError> {
+    debug!("emscripten::___cxa_find_matching_catch_4");
+    find_matching_catch(ctx, &[a, b])
+}
+
+/// emscripten: ___cxa_find_matching_catch_5
+pub fn ___cxa_find_matching_catch_5(
+    ctx: &mut Ctx,
+    a: u32,
+    b: u32,
+    c: u32,
+) -> Result<u32, RuntimeError> {
+    debug!("emscripten::___cxa_find_matching_catch_5");
+    find_matching_catch(ctx, &[a, b, c])
+}
+
+/// emscripten: ___cxa_find_matching_catch_6
+pub fn ___cxa_find_matching_catch_6(
+    ctx: &mut Ctx,
+    a: u32,
+    b: u32,
+    c: u32,
+    d: u32,
+) -> Result<u32, RuntimeError> {
+    debug!("emscripten::___cxa_find_matching_catch_6");
+    find_matching_catch(ctx, &[a, b, c, d])
+}
+
+/// emscripten: ___cxa_begin_catch
+pub fn ___cxa_begin_catch(ctx: &mut Ctx, adjusted: u32) -> u32 {
+    debug!("emscripten::___cxa_begin_catch");
+    if ctx.data.is_null() {
+        return adjusted;
+    }
+    let exceptions = &mut get_emscripten_data(ctx).exceptions;
+    let ptr = exceptions.de_adjust(adjusted);
+    if let Some(info) = exceptions.infos.get_mut(&ptr) {
+        if !info.caught {
+            info.caught = true;
+            exceptions.uncaught = exceptions.uncaught.saturating_sub(1);
+        }
+        info.rethrown = false;
+        info.refcount += 1;
+    }
+    exceptions.caught.push(adjusted);
+    adjusted
+}
+
+/// emscripten: ___cxa_end_catch
+pub fn ___cxa_end_catch(ctx: &mut Ctx) -> Result<(), RuntimeError> {
+    debug!("emscripten::___cxa_end_catch");
+    if ctx.data.is_null() {
+        return Ok(());
+    }
+    let data = get_emscripten_data(ctx);
+    if let Some(set_threw) = &data.set_threw {
+        set_threw.call(0, 0)?;
+    }
+    match data.exceptions.caught.pop() {
+        Some(adjusted) => {
+            let ptr = data.exceptions.de_adjust(adjusted);
+            data.exceptions.last = 0;
+            release(ctx, ptr)
+        }
+        None => Ok(()),
+    }
+}
+
+/// emscripten: ___cxa_current_primary_exception
+pub fn ___cxa_current_primary_exception(ctx: &mut Ctx) -> u32 {
+    debug!("emscripten::___cxa_current_primary_exception");
+    if ctx.data.is_null() {
+        return 0;
+    }
+    let exceptions = &mut get_emscripten_data(ctx).exceptions;
+    match exceptions.caught.last() {
+        Some(&adjusted) => {
+            let ptr = exceptions.de_adjust(adjusted);
+            if let Some(info) = exceptions.infos.get_mut(&ptr) {
+                info.refcount += 1;
+            }
+            ptr
+        }
+        None => 0,
+    }
+}
+
+/// emscripten: ___cxa_decrement_exception_refcount
+pub fn ___cxa_decrement_exception_refcount(ctx: &mut Ctx, ptr: u32) -> Result<(), RuntimeError> {
+    debug!("emscripten::___cxa_decrement_exception_refcount");
+    if ptr == 0 || ctx.data.is_null() {
+        return Ok(());
+    }
+    let ptr = get_emscripten_data(ctx).exceptions.de_adjust(ptr);
+    release(ctx, ptr)
+}
+
+/// emscripten: ___cxa_increment_exception_refcount
+pub fn ___cxa_increment_exception_refcount(ctx: &mut Ctx, ptr: u32) {
+    debug!("emscripten::___cxa_increment_exception_refcount");
+    if ctx.data.is_null() {
+        return;
+    }
+    let exceptions = &mut get_emscripten_data(ctx).exceptions;
+    let ptr = exceptions.de_adjust(ptr);
+    if let Some(info) = exceptions.infos.get_mut(&ptr) {
+        info.refcount += 1;
+    }
+}
+
+/// emscripten: ___cxa_uncaught_exceptions
+pub fn ___cxa_uncaught_exceptions(ctx: &mut Ctx) -> u32 {
+    debug!("emscripten::___cxa_uncaught_exceptions");
+    if ctx.data.is_null() {
+        return 0;
+    }
+    get_emscripten_data(ctx).exceptions.uncaught
+}
+
+/// emscripten: std::uncaught_exception()
+pub fn _std_uncaught_exception(ctx: &mut Ctx) -> u32 {
+    debug!("emscripten::__ZSt18uncaught_exceptionv");
+    (___cxa_uncaught_exceptions(ctx) > 0) as u32
+}
+
+/// emscripten: ___gxx_personality_v0
+pub fn ___gxx_personality_v0(_ctx: &mut Ctx) {
+    debug!("emscripten::___gxx_personality_v0");
+    // The invoke_* imports find the handlers, so nothing calls it.
 }
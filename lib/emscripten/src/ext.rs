@@ -0,0 +1,36 @@
+//! The `wasmer_ext` namespaces, of imports that extend what a guest can
+//! ask of the host. Unlike the imports of `env`, a guest only gets them
+//! when the host registers them.
+use wasmer_runtime_core::{
+    func,
+    import::{ImportObject, Namespace},
+};
+
+/// The name of the namespace.
+pub const EXT_NAMESPACE: &str = "wasmer_ext";
+/// The name of the namespace of the key-value store.
+pub const EXT_KV_NAMESPACE: &str = "wasmer_ext.kv";
+/// The name of the namespace of the HTTP client.
+pub const EXT_HTTP_NAMESPACE: &str = "wasmer_ext.http";
+
+/// Register the `wasmer_ext` namespaces in `import_object`.
+pub fn register_ext_namespace(import_object: &mut ImportObject) {
+    let mut namespace = Namespace::new();
+    namespace.insert("log", func!(crate::guest_log::log));
+    import_object.register(EXT_NAMESPACE, namespace);
+
+    let mut kv = Namespace::new();
+    kv.insert("get", func!(crate::kv::get));
+    kv.insert("set", func!(crate::kv::set));
+    kv.insert("delete", func!(crate::kv::delete));
+    kv.insert("next_key", func!(crate::kv::next_key));
+    import_object.register(EXT_KV_NAMESPACE, kv);
+
+    let mut http = Namespace::new();
+    http.insert("request", func!(crate::http::request));
+    http.insert("status", func!(crate::http::status));
+    http.insert("headers", func!(crate::http::headers));
+    http.insert("read", func!(crate::http::read));
+    http.insert("close", func!(crate::http::close));
+    import_object.register(EXT_HTTP_NAMESPACE, http);
+}
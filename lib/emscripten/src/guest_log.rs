@@ -0,0 +1,58 @@
+//! The log lines of the guest, which go to the `log` facade of the host
+//! with their level, rather than to stdout.
+//!
+//! Guests log through `emscripten_console_log`, `emscripten_console_warn`
+//! and `emscripten_console_error`, or through `log` of the `wasmer_ext`
+//! namespace, which has every level. Lines are logged with the target
+//! `wasmer::guest`, so the host can filter them apart from its own.
+use crate::utils::read_string_from_wasm;
+use log::Level;
+use wasmer_runtime_core::vm::Ctx;
+
+/// The target the lines of the guest are logged with.
+pub const GUEST_LOG_TARGET: &str = "wasmer::guest";
+
+fn emit(level: Level, line: &str) {
+    log::log!(target: GUEST_LOG_TARGET, level, "{}", line.trim_end_matches('\n'));
+}
+
+/// The level of `wasmer_ext.log`, numbered like `log::Level`: 1 is an
+/// error and 5 is a trace. Levels past either end are clamped to it.
+fn level_of(level: i32) -> Level {
+    match level {
+        i32::MIN..=1 => Level::Error,
+        2 => Level::Warn,
+        3 => Level::Info,
+        4 => Level::Debug,
+        _ => Level::Trace,
+    }
+}
+
+/// `wasmer_ext.log`: log the `len` bytes of UTF-8 at `ptr` at `level`.
+pub fn log(ctx: &mut Ctx, level: i32, ptr: u32, len: u32) {
+    debug!("emscripten::log {} {} {}", level, ptr, len);
+    let line = {
+        let view = ctx.memory(0).view::<u8>();
+        let end = (ptr as usize).saturating_add(len as usize);
+        match view.get(ptr as usize..end) {
+            Some(cells) => cells.iter().map(|cell| cell.get()).collect::<Vec<u8>>(),
+            None => return emit(Level::Error, "wasmer_ext.log: line out of bounds"),
+        }
+    };
+    emit(level_of(level), &String::from_utf8_lossy(&line));
+}
+
+pub fn _emscripten_console_log(ctx: &mut Ctx, str_ptr: u32) {
+    debug!("emscripten::_emscripten_console_log");
+    emit(Level::Info, &read_string_from_wasm(ctx.memory(0), str_ptr));
+}
+
+pub fn _emscripten_console_warn(ctx: &mut Ctx, str_ptr: u32) {
+    debug!("emscripten::_emscripten_console_warn");
+    emit(Level::Warn, &read_string_from_wasm(ctx.memory(0), str_ptr));
+}
+
+pub fn _emscripten_console_error(ctx: &mut Ctx, str_ptr: u32) {
+    debug!("emscripten::_emscripten_console_error");
+    emit(Level::Error, &read_string_from_wasm(ctx.memory(0), str_ptr));
+}
@@ -0,0 +1,124 @@
+//! Syscall handlers an embedder can install to intercept any syscall of
+//! the guest, e.g. to virtualize the reads of a given file.
+use crate::env::get_emscripten_data;
+use crate::itimer;
+use crate::scratch;
+use crate::signal::{self, SignalError};
+use crate::varargs::VarArgs;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+use wasmer_runtime_core::vm::Ctx;
+
+/// Handles a syscall in place of the emscripten implementation.
+///
+/// `which` is the number of the syscall and `args` points to its
+/// arguments. `default` runs the emscripten implementation, so a handler
+/// can act before or after it, change its result, or replace it.
+pub trait SyscallHandler: Send + Sync {
+    fn handle(
+        &self,
+        ctx: &mut Ctx,
+        which: i32,
+        args: VarArgs,
+        default: &dyn Fn(&mut Ctx, VarArgs) -> i32,
+    ) -> i32;
+}
+
+impl<F> SyscallHandler for F
+where
+    F: Fn(&mut Ctx, i32, VarArgs, &dyn Fn(&mut Ctx, VarArgs) -> i32) -> i32 + Send + Sync,
+{
+    fn handle(
+        &self,
+        ctx: &mut Ctx,
+        which: i32,
+        args: VarArgs,
+        default: &dyn Fn(&mut Ctx, VarArgs) -> i32,
+    ) -> i32 {
+        self(ctx, which, args, default)
+    }
+}
+
+/// The syscall handlers of an instance, by syscall number.
+#[derive(Clone, Default)]
+pub struct SyscallHandlers {
+    handlers: HashMap<i32, Arc<dyn SyscallHandler>>,
+}
+
+impl SyscallHandlers {
+    /// Handle the syscall `which` with `handler`, replacing the previous
+    /// handler if there's one.
+    pub fn insert<H: SyscallHandler + 'static>(&mut self, which: i32, handler: H) {
+        self.handlers.insert(which, Arc::new(handler));
+    }
+
+    pub fn remove(&mut self, which: i32) {
+        self.handlers.remove(&which);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+}
+
+impl fmt::Debug for SyscallHandlers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut numbers: Vec<_> = self.handlers.keys().collect();
+        numbers.sort();
+        f.debug_set().entries(numbers).finish()
+    }
+}
+
+/// The second parameter of the syscall imports, which the stubs take as
+/// a plain `i32`.
+pub(crate) trait FromVarArgs {
+    fn from_varargs(varargs: VarArgs) -> Self;
+}
+
+impl FromVarArgs for VarArgs {
+    fn from_varargs(varargs: VarArgs) -> Self {
+        varargs
+    }
+}
+
+impl FromVarArgs for i32 {
+    fn from_varargs(varargs: VarArgs) -> Self {
+        varargs.pointer as i32
+    }
+}
+
+/// Run the syscall `which` through its handler, if there's one, or else
+/// through `default`. Delivers the signals that came in meanwhile, which
+/// can stop the guest.
+pub(crate) fn dispatch<F>(
+    ctx: &mut Ctx,
+    which: i32,
+    varargs: VarArgs,
+    default: F,
+) -> Result<i32, SignalError>
+where
+    F: Fn(&mut Ctx, VarArgs) -> i32,
+{
+    // Syscalls can happen before `run_emscripten_instance` sets up the data.
+    let (handler, stats) = if ctx.data.is_null() {
+        (None, None)
+    } else {
+        let config = &get_emscripten_data(ctx).config;
+        let handler = config.syscall_handlers.handlers.get(&which).cloned();
+        (handler, config.syscall_stats.clone())
+    };
+    let start = stats.as_ref().map(|_| Instant::now());
+    let ret = match handler {
+        Some(handler) => handler.handle(ctx, which, varargs, &default),
+        None => default(ctx, varargs),
+    };
+    if let (Some(stats), Some(start)) = (stats, start) {
+        stats.record(which, start.elapsed());
+    }
+    scratch::reset(ctx);
+    signal::deliver_pending(ctx)?;
+    itimer::deliver_expired(ctx)?;
+    Ok(ret)
+}
@@ -0,0 +1,386 @@
+//! The `wasmer_ext.http` namespace, for guests to make HTTP requests
+//! without sockets of their own.
+//!
+//! Only the hosts `EmscriptenConfig::net_policy` allows can be reached,
+//! and none when `EmscriptenConfig::capabilities` doesn't grant `net`.
+//! Requests are plain HTTP/1.0 over TCP, so the server sends the body as
+//! is, ending with the connection; there's no TLS. A request returns a
+//! handle the guest reads the status, the headers and the body from, a
+//! buffer at a time, before it closes it.
+use crate::env::get_emscripten_data;
+use crate::kv::write_bytes;
+use crate::utils::read_guest_bytes;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use wasmer_runtime_core::vm::Ctx;
+
+/// What the imports return when the policy doesn't allow the host.
+pub const HTTP_DENIED: i32 = -1;
+/// What the imports return for a malformed request, or an unknown handle.
+pub const HTTP_INVALID: i32 = -2;
+/// What the imports return when the connection fails.
+pub const HTTP_FAILED: i32 = -3;
+
+/// How long connecting to an address of the server can block the guest.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a read from or a write to the server can block the guest.
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+/// The longest status or header line the server can send.
+const MAX_LINE: usize = 8 << 10;
+/// The most header bytes the server can send.
+const MAX_HEADERS: usize = 64 << 10;
+
+/// The hosts the guest can make HTTP requests to.
+///
+/// A rule is either a host name, like `example.com`, or `*.` followed by
+/// a domain, like `*.example.com`, which matches the hosts under it. A
+/// rule followed by `:` and a port only matches that port. The default
+/// policy allows nothing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetPolicy {
+    pub allow: Vec<String>,
+}
+
+impl NetPolicy {
+    /// Whether the guest can connect to `port` of `host`.
+    pub fn allows(&self, host: &str, port: u16) -> bool {
+        let host = host.to_ascii_lowercase();
+        self.allow.iter().any(|rule| {
+            let rule = rule.to_ascii_lowercase();
+            let (pattern, rule_port) = match rule.rfind(':') {
+                Some(colon) => (&rule[..colon], rule[colon + 1..].parse().ok()),
+                None => (&rule[..], None),
+            };
+            if rule_port.map_or(false, |rule_port: u16| rule_port != port) {
+                return false;
+            }
+            if pattern.starts_with("*.") {
+                host.ends_with(&pattern[1..])
+            } else {
+                host == pattern
+            }
+        })
+    }
+}
+
+struct Response {
+    status: i32,
+    /// The header lines, as the server sent them.
+    headers: Vec<u8>,
+    body: BufReader<TcpStream>,
+}
+
+/// The responses the guest hasn't closed yet, by handle.
+#[derive(Default)]
+pub(crate) struct HttpResponses {
+    responses: HashMap<i32, Response>,
+    next_handle: i32,
+}
+
+struct Url<'a> {
+    host: &'a str,
+    port: u16,
+    path: &'a str,
+}
+
+fn parse_url(url: &str) -> Option<Url> {
+    let scheme = url.get(..7)?;
+    if !scheme.eq_ignore_ascii_case("http://") {
+        return None;
+    }
+    let rest = &url[7..];
+    let (authority, path) = match rest.find('/') {
+        Some(slash) => (&rest[..slash], &rest[slash..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rfind(':') {
+        Some(colon) => (&authority[..colon], authority[colon + 1..].parse().ok()?),
+        None => (authority, 80),
+    };
+    let unsafe_char = |c: char| c.is_control() || c.is_whitespace();
+    if host.is_empty() || host.contains(unsafe_char) || path.contains(unsafe_char) {
+        return None;
+    }
+    Some(Url { host, port, path })
+}
+
+/// The head of the request, with the header lines of the guest, or `None`
+/// if they would change its meaning: lines with a bare carriage return
+/// or without a name, and the `Host` and `Content-Length` headers, which
+/// are the host's to send.
+fn request_head(method: &str, url: &Url, headers: &str, body_len: usize) -> Option<String> {
+    let mut head = format!("{} {} HTTP/1.0\r\nHost: {}", method, url.path, url.host);
+    if url.port != 80 {
+        head.push_str(&format!(":{}", url.port));
+    }
+    head.push_str("\r\n");
+    for line in headers.lines().map(str::trim_end) {
+        if line.is_empty() {
+            continue;
+        }
+        let name = line.split(':').next().filter(|_| line.contains(':'))?;
+        if line.contains('\r')
+            || name.is_empty()
+            || name.contains(char::is_whitespace)
+            || name.eq_ignore_ascii_case("host")
+            || name.eq_ignore_ascii_case("content-length")
+        {
+            return None;
+        }
+        head.push_str(line);
+        head.push_str("\r\n");
+    }
+    if body_len > 0 {
+        head.push_str(&format!("Content-Length: {}\r\n", body_len));
+    }
+    head.push_str("\r\n");
+    Some(head)
+}
+
+fn connect(url: &Url) -> io::Result<TcpStream> {
+    let mut last_err = io::Error::new(io::ErrorKind::NotFound, "the host has no address");
+    for addr in (url.host, url.port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = err,
+        }
+    }
+    Err(last_err)
+}
+
+fn send(url: &Url, head: &str, body: &[u8]) -> io::Result<Response> {
+    let mut stream = connect(url)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body)?;
+
+    let mut body = BufReader::new(stream);
+    let (status, headers) = read_head(&mut body)?;
+    Ok(Response {
+        status,
+        headers,
+        body,
+    })
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Read a line of at most `MAX_LINE` bytes, with its line feed.
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut line = Vec::new();
+    reader
+        .take(MAX_LINE as u64 + 1)
+        .read_until(b'\n', &mut line)?;
+    if line.len() > MAX_LINE {
+        return Err(invalid_data("line too long"));
+    }
+    Ok(line)
+}
+
+/// Read the status code and the header lines of a response.
+fn read_head<R: BufRead>(reader: &mut R) -> io::Result<(i32, Vec<u8>)> {
+    let status_line = read_line(reader)?;
+    let status = std::str::from_utf8(&status_line)
+        .ok()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| invalid_data("bad status line"))?;
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line(reader)?;
+        if line.is_empty() || line == b"\r\n" || line == b"\n" {
+            break;
+        }
+        if headers.len() + line.len() > MAX_HEADERS {
+            return Err(invalid_data("headers too long"));
+        }
+        headers.extend_from_slice(&line);
+    }
+    Ok((status, headers))
+}
+
+fn read_str(ctx: &Ctx, ptr: u32, len: u32) -> Option<String> {
+    read_guest_bytes(ctx, ptr, len).and_then(|bytes| String::from_utf8(bytes).ok())
+}
+
+/// `wasmer_ext.http.request`: send a request, with the header lines at
+/// `headers_ptr`, and return the handle of its response.
+#[allow(clippy::too_many_arguments)]
+pub fn request(
+    ctx: &mut Ctx,
+    method_ptr: u32,
+    method_len: u32,
+    url_ptr: u32,
+    url_len: u32,
+    headers_ptr: u32,
+    headers_len: u32,
+    body_ptr: u32,
+    body_len: u32,
+) -> i32 {
+    debug!("emscripten::http::request");
+    if ctx.data.is_null() {
+        return HTTP_DENIED;
+    }
+    let (method, url, headers, body) = match (
+        read_str(ctx, method_ptr, method_len),
+        read_str(ctx, url_ptr, url_len),
+        read_str(ctx, headers_ptr, headers_len),
+        read_guest_bytes(ctx, body_ptr, body_len),
+    ) {
+        (Some(method), Some(url), Some(headers), Some(body)) => (method, url, headers, body),
+        _ => return HTTP_INVALID,
+    };
+    if method.is_empty() || method.contains(char::is_whitespace) {
+        return HTTP_INVALID;
+    }
+    let url = match parse_url(&url) {
+        Some(url) => url,
+        None => return HTTP_INVALID,
+    };
+    let head = match request_head(&method, &url, &headers, body.len()) {
+        Some(head) => head,
+        None => return HTTP_INVALID,
+    };
+    let config = &get_emscripten_data(ctx).config;
+    let granted = config
+        .capabilities
+        .as_ref()
+        .map_or(true, |granted| granted.net);
+    if !granted || !config.net_policy.allows(url.host, url.port) {
+        return HTTP_DENIED;
+    }
+    let response = match send(&url, &head, &body) {
+        Ok(response) => response,
+        Err(_) => return HTTP_FAILED,
+    };
+    let http = &mut get_emscripten_data(ctx).http;
+    http.next_handle += 1;
+    let handle = http.next_handle;
+    http.responses.insert(handle, response);
+    handle
+}
+
+fn response(ctx: &mut Ctx, handle: i32) -> Option<&mut Response> {
+    if ctx.data.is_null() {
+        return None;
+    }
+    get_emscripten_data(ctx).http.responses.get_mut(&handle)
+}
+
+/// `wasmer_ext.http.status`: the status code of the response.
+pub fn status(ctx: &mut Ctx, handle: i32) -> i32 {
+    debug!("emscripten::http::status {}", handle);
+    response(ctx, handle).map_or(HTTP_INVALID, |response| response.status)
+}
+
+/// `wasmer_ext.http.headers`: write the header lines of the response to
+/// the buffer at `buf_ptr`, returning their length like `kv.get`.
+pub fn headers(ctx: &mut Ctx, handle: i32, buf_ptr: u32, buf_len: u32) -> i32 {
+    debug!("emscripten::http::headers {}", handle);
+    let headers = match response(ctx, handle) {
+        Some(response) => response.headers.clone(),
+        None => return HTTP_INVALID,
+    };
+    write_bytes(ctx, buf_ptr, buf_len, &headers)
+}
+
+/// `wasmer_ext.http.read`: read the next part of the body into the buffer
+/// at `buf_ptr`, returning its length, which is 0 at the end.
+pub fn read(ctx: &mut Ctx, handle: i32, buf_ptr: u32, buf_len: u32) -> i32 {
+    debug!("emscripten::http::read {}", handle);
+    let mut buf = vec![0; buf_len.min(1 << 16) as usize];
+    let read = match response(ctx, handle) {
+        Some(response) => match response.body.read(&mut buf) {
+            Ok(read) => read,
+            Err(_) => return HTTP_FAILED,
+        },
+        None => return HTTP_INVALID,
+    };
+    write_bytes(ctx, buf_ptr, buf_len, &buf[..read])
+}
+
+/// `wasmer_ext.http.close`: close the connection of the response.
+pub fn close(ctx: &mut Ctx, handle: i32) -> i32 {
+    debug!("emscripten::http::close {}", handle);
+    if ctx.data.is_null() {
+        return HTTP_INVALID;
+    }
+    match get_emscripten_data(ctx).http.responses.remove(&handle) {
+        Some(_) => 0,
+        None => HTTP_INVALID,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn policy(rules: &[&str]) -> NetPolicy {
+        NetPolicy {
+            allow: rules.iter().map(|rule| rule.to_string()).collect(),
+        }
+    }
+
+    fn head(url: &str, headers: &str) -> Option<String> {
+        request_head("GET", &parse_url(url)?, headers, 0)
+    }
+
+    #[test]
+    fn rules_match_hosts_domains_and_ports() {
+        assert!(!NetPolicy::default().allows("example.com", 80));
+
+        let policy = policy(&["Example.com", "*.example.org", "api.example.net:8080"]);
+        assert!(policy.allows("example.com", 80));
+        assert!(policy.allows("EXAMPLE.COM", 443));
+        assert!(!policy.allows("www.example.com", 80));
+        assert!(policy.allows("www.example.org", 80));
+        assert!(!policy.allows("example.org", 80));
+        assert!(!policy.allows("badexample.org", 80));
+        assert!(policy.allows("api.example.net", 8080));
+        assert!(!policy.allows("api.example.net", 80));
+    }
+
+    #[test]
+    fn requests_carry_the_guests_headers() {
+        assert_eq!(
+            head("http://example.com:8080/a?b", "Accept: */*\r\n\nX-A: 1\n").unwrap(),
+            "GET /a?b HTTP/1.0\r\nHost: example.com:8080\r\nAccept: */*\r\nX-A: 1\r\n\r\n"
+        );
+        let url = parse_url("http://example.com").unwrap();
+        assert_eq!(
+            request_head("POST", &url, "", 3).unwrap(),
+            "POST / HTTP/1.0\r\nHost: example.com\r\nContent-Length: 3\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn requests_cant_be_split_or_retargeted() {
+        assert!(head("http://example.com/a\r\nX-A: 1", "").is_none());
+        assert!(head("http://example.com/a b", "").is_none());
+        assert!(head("http://exa mple.com/", "").is_none());
+        assert!(head("http://example.com/", "X-A: 1\rX-B: 2").is_none());
+        assert!(head("http://example.com/", "no colon").is_none());
+        assert!(head("http://example.com/", "host: evil.com").is_none());
+        assert!(head("http://example.com/", "Content-Length: 0").is_none());
+    }
+
+    #[test]
+    fn response_heads_are_bounded() {
+        let mut response = Cursor::new(&b"HTTP/1.0 404 Not Found\r\nA: 1\r\n\r\nbody"[..]);
+        let (status, headers) = read_head(&mut response).unwrap();
+        assert_eq!(status, 404);
+        assert_eq!(headers, b"A: 1\r\n");
+
+        let long_line = format!("HTTP/1.0 200 {}\r\n\r\n", "x".repeat(MAX_LINE));
+        assert!(read_head(&mut Cursor::new(long_line.into_bytes())).is_err());
+
+        let many_headers = format!("HTTP/1.0 200 OK\r\n{}\r\n", "A: 1\r\n".repeat(MAX_HEADERS));
+        assert!(read_head(&mut Cursor::new(many_headers.into_bytes())).is_err());
+    }
+}
@@ -0,0 +1,362 @@
+//! inotify for the guest, with events generated from the changes the
+//! guest itself makes to the filesystem through its syscalls.
+//!
+//! An inotify instance is the read end of a host pipe, so `read`, `poll`
+//! and `close` work on it like on any other fd. Events are written to the
+//! other end as the guest changes files. Changes made by the host, or by
+//! other processes, aren't seen.
+use crate::env::get_emscripten_data;
+use crate::errno::Errno;
+use crate::vfs::to_path;
+use libc::{c_int, c_void};
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use wasmer_runtime_core::vm::Ctx;
+
+const IN_MODIFY: u32 = 0x2;
+const IN_ATTRIB: u32 = 0x4;
+const IN_CLOSE_WRITE: u32 = 0x8;
+const IN_CLOSE_NOWRITE: u32 = 0x10;
+const IN_OPEN: u32 = 0x20;
+const IN_CREATE: u32 = 0x100;
+const IN_DELETE: u32 = 0x200;
+const IN_DELETE_SELF: u32 = 0x400;
+const IN_ALL_EVENTS: u32 = 0xfff;
+const IN_IGNORED: u32 = 0x8000;
+const IN_ONLYDIR: u32 = 0x0100_0000;
+const IN_MASK_ADD: u32 = 0x2000_0000;
+const IN_ISDIR: u32 = 0x4000_0000;
+const IN_ONESHOT: u32 = 0x8000_0000;
+
+/// The size of `struct inotify_event` without its name, which the length
+/// of names is rounded up to.
+const EVENT_SIZE: usize = 16;
+
+struct Watch {
+    path: PathBuf,
+    mask: u32,
+}
+
+/// An inotify instance of the guest.
+struct Queue {
+    /// The end of the pipe events are written to.
+    writer: c_int,
+    watches: HashMap<c_int, Watch>,
+}
+
+/// The inotify instances of the guest, and the files it has open, whose
+/// writes and closes are events.
+#[derive(Default)]
+pub(crate) struct Inotify {
+    queues: HashMap<c_int, Queue>,
+    /// The path of each open fd, and whether it's open for writing.
+    open_files: HashMap<c_int, (PathBuf, bool)>,
+    next_wd: c_int,
+}
+
+impl Inotify {
+    /// Watch `path` for the events of `mask` on the instance `fd`, and
+    /// return the watch descriptor.
+    fn add_watch(&mut self, fd: c_int, path: PathBuf, mask: u32) -> Result<c_int, Errno> {
+        let queue = self.queues.get_mut(&fd).ok_or(Errno::EINVAL)?;
+        if mask & IN_ALL_EVENTS == 0 {
+            return Err(Errno::EINVAL);
+        }
+        // Watching a path again changes its watch.
+        if let Some((&wd, watch)) = queue.watches.iter_mut().find(|(_, w)| w.path == path) {
+            if mask & IN_MASK_ADD != 0 {
+                watch.mask |= mask;
+            } else {
+                watch.mask = mask;
+            }
+            return Ok(wd);
+        }
+        self.next_wd += 1;
+        queue.watches.insert(self.next_wd, Watch { path, mask });
+        Ok(self.next_wd)
+    }
+}
+
+fn get_inotify(ctx: &mut Ctx) -> Option<&mut Inotify> {
+    // Syscalls can happen before `run_emscripten_instance` sets up the data.
+    if ctx.data.is_null() {
+        return None;
+    }
+    Some(&mut get_emscripten_data(ctx).inotify)
+}
+
+/// Make the pipe of `reader` and `writer` an inotify instance.
+pub(crate) fn init(ctx: &mut Ctx, reader: c_int, writer: c_int) -> c_int {
+    match get_inotify(ctx) {
+        Some(inotify) => {
+            inotify.queues.insert(
+                reader,
+                Queue {
+                    writer,
+                    watches: HashMap::new(),
+                },
+            );
+            reader
+        }
+        None => {
+            unsafe {
+                libc::close(reader);
+                libc::close(writer);
+            }
+            -Errno::ENOSYS.to_emscripten()
+        }
+    }
+}
+
+pub(crate) fn add_watch(ctx: &mut Ctx, fd: c_int, path: &CStr, mask: u32) -> c_int {
+    let path = to_path(path);
+    let metadata = match fs::metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(err) => return -Errno::from_io_error(&err).to_emscripten(),
+    };
+    if mask & IN_ONLYDIR != 0 && !metadata.is_dir() {
+        return -Errno::ENOTDIR.to_emscripten();
+    }
+    let watched = match get_inotify(ctx) {
+        Some(inotify) => inotify.add_watch(fd, path, mask),
+        None => Err(Errno::EBADF),
+    };
+    watched.unwrap_or_else(|errno| -errno.to_emscripten())
+}
+
+pub(crate) fn rm_watch(ctx: &mut Ctx, fd: c_int, wd: c_int) -> c_int {
+    let queue = match get_inotify(ctx).and_then(|inotify| inotify.queues.get_mut(&fd)) {
+        Some(queue) => queue,
+        None => return -Errno::EINVAL.to_emscripten(),
+    };
+    match queue.watches.remove(&wd) {
+        Some(_) => {
+            queue.write(wd, IN_IGNORED, None);
+            0
+        }
+        None => -Errno::EINVAL.to_emscripten(),
+    }
+}
+
+impl Queue {
+    fn write(&self, wd: c_int, mask: u32, name: Option<&str>) {
+        let name = name.unwrap_or("").as_bytes();
+        let len = if name.is_empty() {
+            0
+        } else {
+            (name.len() / EVENT_SIZE + 1) * EVENT_SIZE
+        };
+        let mut event = Vec::with_capacity(EVENT_SIZE + len);
+        for field in &[wd as u32, mask, 0, len as u32] {
+            event.extend_from_slice(&[
+                *field as u8,
+                (*field >> 8) as u8,
+                (*field >> 16) as u8,
+                (*field >> 24) as u8,
+            ]);
+        }
+        event.extend_from_slice(name);
+        event.resize(EVENT_SIZE + len, 0);
+        // The writer doesn't block, so events past what the pipe holds are
+        // lost rather than hanging the guest.
+        unsafe {
+            libc::write(
+                self.writer,
+                event.as_ptr() as *const c_void,
+                event.len() as _,
+            );
+        }
+    }
+
+    /// Report `mask` happening to `path`, to the watch of the path and to
+    /// the one of its directory.
+    fn notify(&mut self, path: &Path, mask: u32) {
+        let event = mask & !IN_ISDIR;
+        let mut ended = Vec::new();
+        for (&wd, watch) in &self.watches {
+            let (mask, name) = if watch.path == path {
+                match event {
+                    IN_CREATE => continue,
+                    IN_DELETE => (IN_DELETE_SELF, None),
+                    _ => (mask, None),
+                }
+            } else if Some(watch.path.as_path()) == path.parent() {
+                (mask, path.file_name().map(|name| name.to_string_lossy()))
+            } else {
+                continue;
+            };
+            if watch.mask & mask & IN_ALL_EVENTS == 0 {
+                continue;
+            }
+            self.write(wd, mask, name.as_ref().map(|name| &**name));
+            if mask == IN_DELETE_SELF || watch.mask & IN_ONESHOT != 0 {
+                ended.push(wd);
+            }
+        }
+        for wd in ended {
+            self.watches.remove(&wd);
+            self.write(wd, IN_IGNORED, None);
+        }
+    }
+}
+
+fn notify(ctx: &mut Ctx, path: &Path, mask: u32) {
+    if let Some(inotify) = get_inotify(ctx) {
+        for queue in inotify.queues.values_mut() {
+            queue.notify(path, mask);
+        }
+    }
+}
+
+fn dir_flag(is_dir: bool) -> u32 {
+    if is_dir {
+        IN_ISDIR
+    } else {
+        0
+    }
+}
+
+pub(crate) fn opened(ctx: &mut Ctx, fd: c_int, path: &CStr, writable: bool, created: bool) {
+    let path = to_path(path);
+    let is_dir = dir_flag(path.is_dir());
+    if created {
+        notify(ctx, &path, IN_CREATE | is_dir);
+    }
+    notify(ctx, &path, IN_OPEN | is_dir);
+    if let Some(inotify) = get_inotify(ctx) {
+        inotify.open_files.insert(fd, (path, writable));
+    }
+}
+
+pub(crate) fn written(ctx: &mut Ctx, fd: c_int) {
+    let path = match get_inotify(ctx).and_then(|inotify| inotify.open_files.get(&fd)) {
+        Some((path, _)) => path.clone(),
+        None => return,
+    };
+    notify(ctx, &path, IN_MODIFY);
+}
+
+pub(crate) fn closed(ctx: &mut Ctx, fd: c_int) {
+    let inotify = match get_inotify(ctx) {
+        Some(inotify) => inotify,
+        None => return,
+    };
+    if let Some(queue) = inotify.queues.remove(&fd) {
+        unsafe {
+            libc::close(queue.writer);
+        }
+    }
+    if let Some((path, writable)) = inotify.open_files.remove(&fd) {
+        let mask = if writable {
+            IN_CLOSE_WRITE
+        } else {
+            IN_CLOSE_NOWRITE
+        };
+        notify(ctx, &path, mask | dir_flag(path.is_dir()));
+    }
+}
+
+pub(crate) fn created(ctx: &mut Ctx, path: &CStr, is_dir: bool) {
+    notify(ctx, &to_path(path), IN_CREATE | dir_flag(is_dir));
+}
+
+pub(crate) fn deleted(ctx: &mut Ctx, path: &CStr, is_dir: bool) {
+    notify(ctx, &to_path(path), IN_DELETE | dir_flag(is_dir));
+}
+
+pub(crate) fn attributes_changed(ctx: &mut Ctx, path: &CStr) {
+    let path = to_path(path);
+    let is_dir = dir_flag(path.is_dir());
+    notify(ctx, &path, IN_ATTRIB | is_dir);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An inotify instance on a pipe, with the fd to read its events from.
+    fn inotify() -> (Inotify, c_int) {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        unsafe { libc::fcntl(fds[0], libc::F_SETFL, libc::O_NONBLOCK) };
+        let mut inotify = Inotify::default();
+        inotify.queues.insert(
+            fds[0],
+            Queue {
+                writer: fds[1],
+                watches: HashMap::new(),
+            },
+        );
+        (inotify, fds[0])
+    }
+
+    /// The watch descriptors and masks of the events waiting on `reader`.
+    fn events(reader: c_int) -> Vec<(c_int, u32)> {
+        let mut buf = [0u8; 1024];
+        let len = unsafe { libc::read(reader, buf.as_mut_ptr() as *mut c_void, buf.len()) };
+        let mut events = Vec::new();
+        let mut at = 0;
+        while at + EVENT_SIZE <= len.max(0) as usize {
+            let field = |i: usize| {
+                let bytes = &buf[at + i * 4..at + i * 4 + 4];
+                u32::from(bytes[0])
+                    | u32::from(bytes[1]) << 8
+                    | u32::from(bytes[2]) << 16
+                    | u32::from(bytes[3]) << 24
+            };
+            events.push((field(0) as c_int, field(1)));
+            at += EVENT_SIZE + field(3) as usize;
+        }
+        events
+    }
+
+    #[test]
+    fn watching_needs_an_instance_and_events() {
+        let (mut inotify, reader) = inotify();
+        let path = PathBuf::from("/data");
+        assert_eq!(
+            inotify.add_watch(reader + 100, path.clone(), IN_MODIFY),
+            Err(Errno::EINVAL)
+        );
+        assert_eq!(
+            inotify.add_watch(reader, path.clone(), IN_ONLYDIR),
+            Err(Errno::EINVAL)
+        );
+        let wd = inotify.add_watch(reader, path.clone(), IN_MODIFY).unwrap();
+        assert_eq!(
+            inotify.add_watch(reader, path, IN_CREATE | IN_MASK_ADD),
+            Ok(wd)
+        );
+        assert_eq!(
+            inotify.queues[&reader].watches[&wd].mask,
+            IN_MODIFY | IN_CREATE | IN_MASK_ADD
+        );
+    }
+
+    #[test]
+    fn notifies_the_watches_of_the_path_and_its_directory() {
+        let (mut inotify, reader) = inotify();
+        let dir = inotify
+            .add_watch(reader, PathBuf::from("/data"), IN_CREATE | IN_DELETE)
+            .unwrap();
+        let file = inotify
+            .add_watch(reader, PathBuf::from("/data/a"), IN_ALL_EVENTS)
+            .unwrap();
+        let queue = inotify.queues.get_mut(&reader).unwrap();
+
+        queue.notify(Path::new("/data/b"), IN_CREATE);
+        assert_eq!(events(reader), vec![(dir, IN_CREATE)]);
+
+        // Deleting a watched file ends its watch.
+        queue.notify(Path::new("/data/a"), IN_DELETE);
+        let mut deleted = events(reader);
+        deleted.sort();
+        assert_eq!(
+            deleted,
+            vec![(dir, IN_DELETE), (file, IN_DELETE_SELF), (file, IN_IGNORED)]
+        );
+        assert!(!queue.watches.contains_key(&file));
+    }
+}
@@ -0,0 +1,142 @@
+//! The interval timers of `setitimer`, which fire at the first syscall
+//! after they expire, like the signals the host forwards.
+//!
+//! The runtime doesn't meter the guest, so the execution time of the
+//! guest that `ITIMER_VIRTUAL` and `ITIMER_PROF` count is the CPU time of
+//! the process, which includes the time spent in imports.
+use crate::clock::{get_clock, ClockId};
+use crate::env::get_emscripten_data;
+use crate::signal::{self, SignalError};
+use std::time::Duration;
+use wasmer_runtime_core::vm::Ctx;
+
+const ITIMER_REAL: i32 = 0;
+const ITIMER_VIRTUAL: i32 = 1;
+const ITIMER_PROF: i32 = 2;
+
+const SIGALRM: u32 = 14;
+const SIGVTALRM: u32 = 26;
+const SIGPROF: u32 = 27;
+
+#[derive(Debug, Clone, Copy)]
+struct Timer {
+    /// When the timer expires, on the clock of its kind.
+    deadline: Duration,
+    /// The period it's rearmed with once it expires, if it repeats.
+    interval: Duration,
+}
+
+/// The timers of the guest, by kind.
+#[derive(Debug, Default)]
+pub(crate) struct Timers {
+    timers: [Option<Timer>; 3],
+}
+
+fn clock_of(which: i32) -> ClockId {
+    if which == ITIMER_REAL {
+        ClockId::Monotonic
+    } else {
+        ClockId::ProcessCpuTime
+    }
+}
+
+fn signal_of(which: i32) -> u32 {
+    match which {
+        ITIMER_REAL => SIGALRM,
+        ITIMER_VIRTUAL => SIGVTALRM,
+        _ => SIGPROF,
+    }
+}
+
+fn now(ctx: &mut Ctx, which: i32) -> Duration {
+    get_clock(ctx)
+        .time(clock_of(which))
+        .unwrap_or_else(|| Duration::from_secs(0))
+}
+
+/// Read the `struct timeval` at `ptr`, which is `{ i32 tv_sec; i32 tv_usec }`.
+fn read_timeval(ctx: &Ctx, ptr: u32) -> Option<Duration> {
+    let view = ctx.memory(0).view::<u32>();
+    let index = (ptr / 4) as usize;
+    let secs = view.get(index)?.get();
+    let usecs = view.get(index + 1)?.get();
+    if usecs >= 1_000_000 {
+        return None;
+    }
+    Some(Duration::new(u64::from(secs), usecs * 1000))
+}
+
+fn write_timeval(ctx: &Ctx, ptr: u32, time: Duration) {
+    let view = ctx.memory(0).view::<u32>();
+    let index = (ptr / 4) as usize;
+    if let Some(cells) = view.get(index..index + 2) {
+        cells[0].set(time.as_secs() as u32);
+        cells[1].set(time.subsec_micros());
+    }
+}
+
+/// Arm or disarm the timer `which` with the `struct itimerval` at
+/// `new_value`, and write how it was set to `old_value`.
+pub fn _setitimer(ctx: &mut Ctx, which: i32, new_value: u32, old_value: u32) -> i32 {
+    debug!("emscripten::_setitimer {}", which);
+    if ctx.data.is_null() || which < ITIMER_REAL || which > ITIMER_PROF {
+        return -1;
+    }
+    // it_interval comes first, then it_value.
+    let (interval, value) = match (
+        read_timeval(ctx, new_value),
+        read_timeval(ctx, new_value + 8),
+    ) {
+        (Some(interval), Some(value)) => (interval, value),
+        _ => return -1,
+    };
+    let now = now(ctx, which);
+    let timer = &mut get_emscripten_data(ctx).timers.timers[which as usize];
+    let old = timer.take();
+    if value != Duration::from_secs(0) {
+        *timer = Some(Timer {
+            deadline: now + value,
+            interval,
+        });
+    }
+
+    if old_value != 0 {
+        let (interval, left) = match old {
+            Some(old) if old.deadline > now => (old.interval, old.deadline - now),
+            Some(old) => (old.interval, Duration::from_micros(1)),
+            None => (Duration::from_secs(0), Duration::from_secs(0)),
+        };
+        write_timeval(ctx, old_value, interval);
+        write_timeval(ctx, old_value + 8, left);
+    }
+    0
+}
+
+/// Send the guest the signal of each timer that expired, and rearm the
+/// ones that repeat.
+pub(crate) fn deliver_expired(ctx: &mut Ctx) -> Result<(), SignalError> {
+    if ctx.data.is_null() {
+        return Ok(());
+    }
+    for which in ITIMER_REAL..=ITIMER_PROF {
+        if get_emscripten_data(ctx).timers.timers[which as usize].is_none() {
+            continue;
+        }
+        let now = now(ctx, which);
+        let timer = &mut get_emscripten_data(ctx).timers.timers[which as usize];
+        match *timer {
+            Some(Timer { deadline, .. }) if deadline > now => continue,
+            Some(Timer { interval, .. }) if interval != Duration::from_secs(0) => {
+                // A timer that expired several times over fires once.
+                *timer = Some(Timer {
+                    deadline: now + interval,
+                    interval,
+                });
+            }
+            _ => *timer = None,
+        }
+        debug!("emscripten::deliver_expired {}", which);
+        signal::deliver(ctx, signal_of(which))?;
+    }
+    Ok(())
+}
@@ -1,40 +1,353 @@
-use super::env::get_emscripten_data;
+//! `setjmp` and `longjmp`.
+//!
+//! Current emscripten output doesn't call `setjmp` at all: a function that
+//! sets a jump buffer records it in a table of its own with `saveSetjmp`,
+//! and makes every call that could jump back through an `invoke_*` import.
+//! `longjmp` traps out to the innermost of those, which tells the caller
+//! through `setThrew` that it has to find its jump buffer with
+//! `testSetjmp`. The tables are in guest memory and grow as needed.
+//!
+//! Older output imports `__setjmp` and `__longjmp`, which are the host's,
+//! with their buffers outside the guest memory. Those are only safe to
+//! jump to while the import that set them hasn't returned into a frame
+//! the jump skips, which is as much as the host can do for such code.
+use super::cleanup;
+use super::env::{call_malloc, get_emscripten_data};
+use super::process::abort_with_message;
 use libc::{c_int, c_void};
-use std::cell::UnsafeCell;
-use wasmer_runtime_core::vm::Ctx;
+use std::collections::HashMap;
+use std::ptr;
+use wasmer_runtime_core::{
+    error::{CallError, RuntimeError},
+    instance::DynFunc,
+    types::Value,
+    vm::Ctx,
+    Instance,
+};
+
+/// Larger than the `jmp_buf` of any host libc, which is a few hundred
+/// bytes at most.
+const JMP_BUF_WORDS: usize = 64;
+
+/// The signatures of the `invoke_*` imports, each of which calls through
+/// the `dynCall_*` export of the same signature. A `j`, an `i64`, is two
+/// `i32`s on both sides, low word first, and a returned one leaves its
+/// high word in `tempRet0`.
+const INVOKE_SIGNATURES: &[&str] = &[
+    "v", "vi", "vii", "viii", "viiii", "viiiii", "i", "ii", "iii", "iiii", "iiiii", "iiiiii",
+    "vij", "iij", "ji", "jii", "vif", "vid", "viid", "iid", "fi", "di", "dii",
+];
+
+/// The jump buffers of an instance.
+#[derive(Default)]
+pub(crate) struct Jumps {
+    /// The buffers of `__setjmp`, by the number it keeps in the guest
+    /// `jmp_buf`. Boxed, so growing the list doesn't move them.
+    host: Vec<Box<[u64; JMP_BUF_WORDS]>>,
+    /// The last id `saveSetjmp` gave a jump buffer.
+    last_id: u32,
+    /// Set while `longjmp` traps out to the `invoke_*` that catches it, so
+    /// the trap isn't taken for an error.
+    longjmping: bool,
+}
+
+impl Jumps {
+    /// Forget the buffers of `__setjmp` once the call into the guest that
+    /// set them has returned, since none of their frames is left.
+    pub(crate) fn release(&mut self) {
+        self.host = Vec::new();
+        self.longjmping = false;
+    }
+}
+
+/// The `dynCall_*` exports the `invoke_*` imports call through, by
+/// signature.
+pub(crate) fn dyn_calls(instance: &Instance) -> HashMap<&'static str, DynFunc> {
+    INVOKE_SIGNATURES
+        .iter()
+        .filter_map(|&sig| Some((sig, instance.dyn_func(&format!("dynCall_{}", sig)).ok()?)))
+        .collect()
+}
+
+/// How `longjmp` traps out of the guest, or why it couldn't.
+pub enum Longjmp {
+    Jump,
+    /// `setThrew` trapped.
+    SetThrew(RuntimeError),
+}
+
+// What the error shows when no `invoke_*` catches the jump.
+impl std::fmt::Debug for Longjmp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Longjmp::Jump => f.write_str("longjmp with no setjmp to return to"),
+            Longjmp::SetThrew(err) => write!(f, "longjmp failed: {:?}", err),
+        }
+    }
+}
+
+fn read_u32(ctx: &mut Ctx, addr: u32) -> u32 {
+    unsafe { (emscripten_memory_pointer!(ctx.memory(0), addr) as *const u32).read_unaligned() }
+}
+
+fn write_u32(ctx: &mut Ctx, addr: u32, value: u32) {
+    unsafe { (emscripten_memory_pointer!(ctx.memory(0), addr) as *mut u32).write_unaligned(value) }
+}
 
 /// setjmp
 pub fn __setjmp(ctx: &mut Ctx, env_addr: u32) -> c_int {
     debug!("emscripten::__setjmp (setjmp)");
-    unsafe {
-        // Rather than using the env as the holder of the jump buffer pointer,
-        // we use the environment address to store the index relative to jumps
-        // so the address of the jump it's outside the wasm memory itself.
-        let jump_index = emscripten_memory_pointer!(ctx.memory(0), env_addr) as *mut i8;
-        // We create the jump buffer outside of the wasm memory
-        let jump_buf: UnsafeCell<[u32; 27]> = UnsafeCell::new([0; 27]);
-        let jumps = &mut get_emscripten_data(ctx).jumps;
-        let result = setjmp(jump_buf.get() as _);
-        // We set the jump index to be the last 3value of jumps
-        *jump_index = jumps.len() as _;
-        // We hold the reference of the jump buffer
-        jumps.push(jump_buf);
-        result
-    }
+    let index = get_emscripten_data(ctx).jumps.host.len();
+    cleanup::mark(ctx, index);
+    // The guest `jmp_buf` only holds the index of the host one.
+    write_u32(ctx, env_addr, index as u32);
+    let jumps = &mut get_emscripten_data(ctx).jumps;
+    jumps.host.push(Box::new([0; JMP_BUF_WORDS]));
+    unsafe { setjmp(jumps.host[index].as_mut_ptr() as _) }
 }
 
 /// longjmp
-#[allow(unreachable_code)]
 pub fn __longjmp(ctx: &mut Ctx, env_addr: u32, val: c_int) {
     debug!("emscripten::__longjmp (longmp)");
-    unsafe {
-        // We retrieve the jump index from the env address
-        let jump_index = emscripten_memory_pointer!(ctx.memory(0), env_addr) as *mut i8;
-        let jumps = &mut get_emscripten_data(ctx).jumps;
-        // We get the real jump buffer from the jumps vector, using the retrieved index
-        let jump_buf = &jumps[*jump_index as usize];
-        longjmp(jump_buf.get() as _, val)
+    let index = read_u32(ctx, env_addr) as usize;
+    if index >= get_emscripten_data(ctx).jumps.host.len() {
+        return abort_with_message(ctx, "longjmp to a jmp_buf that isn't set");
+    }
+    // The host frames the jump skips can't release their resources.
+    cleanup::unwind_to(ctx, index);
+    let jumps = &mut get_emscripten_data(ctx).jumps;
+    // The buffers set since are in the frames the jump skips.
+    jumps.host.truncate(index + 1);
+    let jump_buf = jumps.host[index].as_mut_ptr();
+    unsafe { longjmp(jump_buf as _, val) }
+}
+
+/// sigsetjmp
+pub fn _sigsetjmp(ctx: &mut Ctx, env_addr: u32, _savemask: c_int) -> c_int {
+    debug!("emscripten::_sigsetjmp");
+    // The guest has no signal mask to save, so this is plain setjmp.
+    __setjmp(ctx, env_addr)
+}
+
+/// siglongjmp
+pub fn _siglongjmp(ctx: &mut Ctx, env_addr: u32, val: c_int) {
+    debug!("emscripten::_siglongjmp");
+    __longjmp(ctx, env_addr, val)
+}
+
+/// saveSetjmp: give the jump buffer at `env_addr` a new id, and record
+/// that it was set at `label` in the setjmp table of the caller, which
+/// has room for `size` entries and ends with a zero id. Returns the
+/// table, moved if it had to grow, with its new size in `tempRet0`.
+pub fn _save_setjmp(
+    ctx: &mut Ctx,
+    env_addr: u32,
+    label: u32,
+    table: u32,
+    size: u32,
+) -> Result<u32, RuntimeError> {
+    debug!("emscripten::_saveSetjmp");
+    let jumps = &mut get_emscripten_data(ctx).jumps;
+    jumps.last_id += 1;
+    let id = jumps.last_id;
+    write_u32(ctx, env_addr, id);
+
+    let (table, size, slot) = match (0..size).find(|slot| read_u32(ctx, table + slot * 8) == 0) {
+        Some(slot) => (table, size, slot),
+        None => {
+            let grown_size = size.max(1) * 2;
+            let grown = call_malloc(ctx, (grown_size + 1) * 8);
+            if grown == 0 {
+                abort_with_message(ctx, "out of memory for a setjmp table");
+                return Ok(0);
+            }
+            let memory = ctx.memory(0);
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    emscripten_memory_pointer!(memory, table) as *const u8,
+                    emscripten_memory_pointer!(memory, grown) as *mut u8,
+                    size as usize * 8,
+                );
+            }
+            get_emscripten_data(ctx).free.call(table)?;
+            (grown, grown_size, size)
+        }
     };
+    write_u32(ctx, table + slot * 8, id);
+    write_u32(ctx, table + slot * 8 + 4, label);
+    write_u32(ctx, table + slot * 8 + 8, 0);
+    get_emscripten_data(ctx).temp_ret_0 = size as i32;
+    Ok(table)
+}
+
+/// testSetjmp: the label the jump buffer `id` was set at, if the setjmp
+/// table of the caller has it, or 0.
+pub fn _test_setjmp(ctx: &mut Ctx, id: u32, table: u32, size: u32) -> u32 {
+    debug!("emscripten::_testSetjmp");
+    for slot in 0..=size {
+        match read_u32(ctx, table + slot * 8) {
+            0 => break,
+            curr if curr == id => return read_u32(ctx, table + slot * 8 + 4),
+            _ => {}
+        }
+    }
+    0
+}
+
+/// emscripten_longjmp
+pub fn _emscripten_longjmp(ctx: &mut Ctx, env_addr: u32, value: c_int) -> Result<(), Longjmp> {
+    debug!("emscripten::_emscripten_longjmp");
+    let data = get_emscripten_data(ctx);
+    let set_threw = match &data.set_threw {
+        Some(set_threw) => set_threw,
+        None => {
+            abort_with_message(ctx, "longjmp in a module that doesn't export setThrew");
+            return Ok(());
+        }
+    };
+    // The caller finds its jump buffer by the address of the `jmp_buf`.
+    let value = if value == 0 { 1 } else { value };
+    set_threw
+        .call(env_addr as i32, value)
+        .map_err(Longjmp::SetThrew)?;
+    data.jumps.longjmping = true;
+    Err(Longjmp::Jump)
+}
+
+/// longjmp
+pub fn _longjmp(ctx: &mut Ctx, env_addr: u32, value: c_int) -> Result<(), Longjmp> {
+    debug!("emscripten::_longjmp");
+    _emscripten_longjmp(ctx, env_addr, value)
+}
+
+/// Call the function at `index` in the table through the `dynCall_*`
+/// export of signature `sig`, and if it longjmps or throws, tell the
+/// caller with `setThrew` instead of letting the trap through. Returns
+/// what the function returned, if it did.
+fn invoke(
+    ctx: &mut Ctx,
+    sig: &str,
+    index: i32,
+    args: &[Value],
+) -> Result<Option<Value>, CallError> {
+    if ctx.data.is_null() {
+        abort_with_message(ctx, "invoke before the runtime was set up");
+        return Ok(None);
+    }
+    let stack_top = match &get_emscripten_data(ctx).stack_save {
+        Some(save) => Some(save.call()?),
+        None => None,
+    };
+    let pending = cleanup::pending(ctx);
+
+    let mut params = vec![Value::I32(index)];
+    params.extend_from_slice(args);
+    let dyn_call = match get_emscripten_data(ctx).dyn_calls.get_mut(sig) {
+        Some(dyn_call) => dyn_call,
+        None => {
+            let message = format!(
+                "invoke_{} in a module that doesn't export dynCall_{}",
+                sig, sig
+            );
+            abort_with_message(ctx, &message);
+            return Ok(None);
+        }
+    };
+    match dyn_call.call(&params) {
+        Ok(results) => Ok(results.into_iter().next()),
+        Err(err) => {
+            let data = get_emscripten_data(ctx);
+            let longjmping = data.jumps.longjmping;
+            data.jumps.longjmping = false;
+            if !(longjmping || data.exceptions.take_throwing()) {
+                return Err(err);
+            }
+            // The host frames the jump skipped can't release their resources.
+            cleanup::run_from(ctx, pending);
+            let data = get_emscripten_data(ctx);
+            if let (Some(restore), Some(stack_top)) = (&data.stack_restore, stack_top) {
+                restore.call(stack_top)?;
+            }
+            if let Some(set_threw) = &data.set_threw {
+                set_threw.call(1, 0)?;
+            }
+            Ok(None)
+        }
+    }
+}
+
+macro_rules! invoke_funcs {
+    ($($name:ident($sig:expr; $($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+        $(
+            pub fn $name(ctx: &mut Ctx, index: i32 $(, $arg: $ty)*) -> Result<$ret, CallError> {
+                debug!(concat!("emscripten::", stringify!($name)));
+                invoke(ctx, $sig, index, &[$(Value::from($arg)),*]).map(Invoked::from_value)
+            }
+        )*
+    };
+}
+
+/// What an `invoke_*` returns, from what the call returned: zero when it
+/// didn't return.
+trait Invoked {
+    fn from_value(ret: Option<Value>) -> Self;
+}
+
+impl Invoked for () {
+    fn from_value(_ret: Option<Value>) -> Self {}
+}
+
+impl Invoked for i32 {
+    fn from_value(ret: Option<Value>) -> Self {
+        match ret {
+            Some(Value::I32(ret)) => ret,
+            _ => 0,
+        }
+    }
+}
+
+impl Invoked for f32 {
+    fn from_value(ret: Option<Value>) -> Self {
+        match ret {
+            Some(Value::F32(ret)) => ret,
+            _ => 0.0,
+        }
+    }
+}
+
+impl Invoked for f64 {
+    fn from_value(ret: Option<Value>) -> Self {
+        match ret {
+            Some(Value::F64(ret)) => ret,
+            _ => 0.0,
+        }
+    }
+}
+
+invoke_funcs! {
+    invoke_v("v";) -> ();
+    invoke_vi("vi"; a: i32) -> ();
+    invoke_vii("vii"; a: i32, b: i32) -> ();
+    invoke_viii("viii"; a: i32, b: i32, c: i32) -> ();
+    invoke_viiii("viiii"; a: i32, b: i32, c: i32, d: i32) -> ();
+    invoke_viiiii("viiiii"; a: i32, b: i32, c: i32, d: i32, e: i32) -> ();
+    invoke_i("i";) -> i32;
+    invoke_ii("ii"; a: i32) -> i32;
+    invoke_iii("iii"; a: i32, b: i32) -> i32;
+    invoke_iiii("iiii"; a: i32, b: i32, c: i32) -> i32;
+    invoke_iiiii("iiiii"; a: i32, b: i32, c: i32, d: i32) -> i32;
+    invoke_iiiiii("iiiiii"; a: i32, b: i32, c: i32, d: i32, e: i32) -> i32;
+    invoke_vij("vij"; a: i32, b_lo: i32, b_hi: i32) -> ();
+    invoke_iij("iij"; a: i32, b_lo: i32, b_hi: i32) -> i32;
+    invoke_ji("ji"; a: i32) -> i32;
+    invoke_jii("jii"; a: i32, b: i32) -> i32;
+    invoke_vif("vif"; a: i32, b: f32) -> ();
+    invoke_vid("vid"; a: i32, b: f64) -> ();
+    invoke_viid("viid"; a: i32, b: i32, c: f64) -> ();
+    invoke_iid("iid"; a: i32, b: f64) -> i32;
+    invoke_fi("fi"; a: i32) -> f32;
+    invoke_di("di"; a: i32) -> f64;
+    invoke_dii("dii"; a: i32, b: i32) -> f64;
 }
 
 extern "C" {
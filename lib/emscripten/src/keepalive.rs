@@ -0,0 +1,104 @@
+//! Whether the runtime outlives `main`, so event-driven guests can leave
+//! `main` early and have the host call them back, as in a browser.
+use crate::env::get_emscripten_data;
+use crate::{EmscriptenConfig, EmscriptenData, EmscriptenError};
+use std::ffi::c_void;
+use std::{mem, ptr};
+use wasmer_runtime_core::{vm::Ctx, Instance};
+
+/// The references of the guest to its runtime.
+#[derive(Debug, Default)]
+pub(crate) struct Keepalive {
+    counter: u32,
+    /// Set while `emscripten_exit_with_live_runtime` unwinds the guest, so
+    /// the trap it unwinds with isn't taken for an error.
+    unwinding: bool,
+}
+
+/// How `emscripten_exit_with_live_runtime` traps out of the guest.
+#[derive(Debug)]
+pub struct Unwind;
+
+impl<'a> EmscriptenData<'a> {
+    /// Whether the runtime stays up once the call into the guest returns.
+    pub(crate) fn keeps_runtime_alive(&self) -> bool {
+        !self.config.exit_runtime || self.keepalive.counter > 0
+    }
+
+    /// Whether the guest trapped to leave with its runtime alive, which is
+    /// forgotten.
+    pub(crate) fn take_unwinding(&mut self) -> bool {
+        let unwinding = self.keepalive.unwinding;
+        self.keepalive.unwinding = false;
+        unwinding
+    }
+}
+
+/// The data an instance keeps in its `Ctx` while its runtime is up.
+///
+/// It holds exports of the instance, so it can't outlive it: the `Ctx`
+/// frees it when it's dropped, if `release` didn't before.
+pub(crate) type LiveData = EmscriptenData<'static>;
+
+/// Set the runtime of `instance` up, freeing the data of an earlier run
+/// it still holds.
+pub(crate) fn keep(
+    instance: &mut Instance,
+    config: EmscriptenConfig,
+) -> Result<(), EmscriptenError> {
+    release(instance.context_mut());
+    let data = EmscriptenData::try_with_config(instance, config)?;
+    let data: LiveData = unsafe { mem::transmute(data) };
+    let ctx = instance.context_mut();
+    ctx.data = Box::into_raw(Box::new(data)) as *mut c_void;
+    ctx.data_finalizer = Some(drop_emscripten_data);
+    Ok(())
+}
+
+/// Tear the runtime of the instance of `ctx` down, if `keep` set it up.
+pub(crate) fn release(ctx: &mut Ctx) {
+    if let Some(finalizer) = ctx.data_finalizer.take() {
+        finalizer(ctx.data);
+        ctx.data = ptr::null_mut();
+    }
+}
+
+extern "C" fn drop_emscripten_data(data: *mut c_void) {
+    unsafe { drop(Box::from_raw(data as *mut LiveData)) }
+}
+
+pub fn _emscripten_runtime_keepalive_push(ctx: &mut Ctx) {
+    debug!("emscripten::_emscripten_runtime_keepalive_push");
+    if ctx.data.is_null() {
+        return;
+    }
+    get_emscripten_data(ctx).keepalive.counter += 1;
+}
+
+pub fn _emscripten_runtime_keepalive_pop(ctx: &mut Ctx) {
+    debug!("emscripten::_emscripten_runtime_keepalive_pop");
+    if ctx.data.is_null() {
+        return;
+    }
+    let keepalive = &mut get_emscripten_data(ctx).keepalive;
+    keepalive.counter = keepalive.counter.saturating_sub(1);
+}
+
+pub fn _emscripten_runtime_keepalive_check(ctx: &mut Ctx) -> i32 {
+    debug!("emscripten::_emscripten_runtime_keepalive_check");
+    if ctx.data.is_null() {
+        return 0;
+    }
+    get_emscripten_data(ctx).keeps_runtime_alive() as i32
+}
+
+/// Leave the guest without tearing its runtime down, by trapping out of it.
+pub fn _emscripten_exit_with_live_runtime(ctx: &mut Ctx) -> Result<(), Unwind> {
+    debug!("emscripten::_emscripten_exit_with_live_runtime");
+    if !ctx.data.is_null() {
+        let keepalive = &mut get_emscripten_data(ctx).keepalive;
+        keepalive.counter += 1;
+        keepalive.unwinding = true;
+    }
+    Err(Unwind)
+}
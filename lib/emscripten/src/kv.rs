@@ -0,0 +1,162 @@
+//! The `wasmer_ext.kv` namespace, a key-value store of byte strings the
+//! guest can keep state in without access to the filesystem.
+//!
+//! The host picks the store with `EmscriptenConfig::kv_store`. Without
+//! one, every call of the guest fails with `KV_FAILED`.
+use crate::env::get_emscripten_data;
+use crate::utils::{read_guest_bytes, write_guest_bytes};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::io;
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
+use wasmer_runtime_core::vm::Ctx;
+
+/// What `get` and `next_key` return when there's no such key.
+pub const KV_NOT_FOUND: i32 = -1;
+/// What the imports return when the store fails, or there's none.
+pub const KV_FAILED: i32 = -2;
+
+/// A store of values by key, both byte strings.
+pub trait KvStore: Debug {
+    fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>>;
+
+    fn set(&self, key: &[u8], value: &[u8]) -> io::Result<()>;
+
+    /// Remove `key`, and tell whether it was there.
+    fn delete(&self, key: &[u8]) -> io::Result<bool>;
+
+    /// The first key after `after` in byte order, or the first key of all
+    /// without one.
+    fn next_key(&self, after: Option<&[u8]>) -> io::Result<Option<Vec<u8>>>;
+}
+
+/// A store that lives as long as the host process, shared by every
+/// instance it's given to.
+#[derive(Debug, Default)]
+pub struct MemoryKvStore {
+    entries: Mutex<BTreeMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl MemoryKvStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KvStore for MemoryKvStore {
+    fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.entries.lock().unwrap().get(key).cloned())
+    }
+
+    fn set(&self, key: &[u8], value: &[u8]) -> io::Result<()> {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn delete(&self, key: &[u8]) -> io::Result<bool> {
+        Ok(self.entries.lock().unwrap().remove(key).is_some())
+    }
+
+    fn next_key(&self, after: Option<&[u8]>) -> io::Result<Option<Vec<u8>>> {
+        let entries = self.entries.lock().unwrap();
+        let next = match after {
+            Some(after) => entries
+                .range::<[u8], _>((Bound::Excluded(after), Bound::Unbounded))
+                .next(),
+            None => entries.iter().next(),
+        };
+        Ok(next.map(|(key, _)| key.clone()))
+    }
+}
+
+fn store(ctx: &mut Ctx) -> Option<Arc<dyn KvStore>> {
+    if ctx.data.is_null() {
+        return None;
+    }
+    get_emscripten_data(ctx).config.kv_store.clone()
+}
+
+/// Copy as much of `bytes` as fits to the `len` bytes at `ptr`, and
+/// return the length of all of them, so the guest can retry with a larger
+/// buffer when it was too small.
+pub(crate) fn write_bytes(ctx: &Ctx, ptr: u32, len: u32, bytes: &[u8]) -> i32 {
+    let copied = bytes.len().min(len as usize);
+    match write_guest_bytes(ctx, ptr, &bytes[..copied]) {
+        Some(()) => bytes.len() as i32,
+        None => KV_FAILED,
+    }
+}
+
+/// `wasmer_ext.kv.get`: write the value of the key at `key_ptr` to the
+/// buffer at `buf_ptr`.
+pub fn get(ctx: &mut Ctx, key_ptr: u32, key_len: u32, buf_ptr: u32, buf_len: u32) -> i32 {
+    debug!("emscripten::kv::get");
+    let (store, key) = match (store(ctx), read_guest_bytes(ctx, key_ptr, key_len)) {
+        (Some(store), Some(key)) => (store, key),
+        _ => return KV_FAILED,
+    };
+    match store.get(&key) {
+        Ok(Some(value)) => write_bytes(ctx, buf_ptr, buf_len, &value),
+        Ok(None) => KV_NOT_FOUND,
+        Err(_) => KV_FAILED,
+    }
+}
+
+/// `wasmer_ext.kv.set`: set the key at `key_ptr` to the value at
+/// `value_ptr`.
+pub fn set(ctx: &mut Ctx, key_ptr: u32, key_len: u32, value_ptr: u32, value_len: u32) -> i32 {
+    debug!("emscripten::kv::set");
+    let store = match store(ctx) {
+        Some(store) => store,
+        None => return KV_FAILED,
+    };
+    match (
+        read_guest_bytes(ctx, key_ptr, key_len),
+        read_guest_bytes(ctx, value_ptr, value_len),
+    ) {
+        (Some(key), Some(value)) if store.set(&key, &value).is_ok() => 0,
+        _ => KV_FAILED,
+    }
+}
+
+/// `wasmer_ext.kv.delete`: remove the key at `key_ptr`, returning 1 if it
+/// was there and 0 if it wasn't.
+pub fn delete(ctx: &mut Ctx, key_ptr: u32, key_len: u32) -> i32 {
+    debug!("emscripten::kv::delete");
+    let (store, key) = match (store(ctx), read_guest_bytes(ctx, key_ptr, key_len)) {
+        (Some(store), Some(key)) => (store, key),
+        _ => return KV_FAILED,
+    };
+    match store.delete(&key) {
+        Ok(existed) => existed as i32,
+        Err(_) => KV_FAILED,
+    }
+}
+
+/// `wasmer_ext.kv.next_key`: write the key after the one at `after_ptr`
+/// to the buffer at `buf_ptr`. A negative `after_len` starts from the
+/// first key, so the guest iterates by passing back each key it gets.
+pub fn next_key(ctx: &mut Ctx, after_ptr: u32, after_len: i32, buf_ptr: u32, buf_len: u32) -> i32 {
+    debug!("emscripten::kv::next_key");
+    let store = match store(ctx) {
+        Some(store) => store,
+        None => return KV_FAILED,
+    };
+    let after = if after_len < 0 {
+        None
+    } else {
+        match read_guest_bytes(ctx, after_ptr, after_len as u32) {
+            Some(after) => Some(after),
+            None => return KV_FAILED,
+        }
+    };
+    match store.next_key(after.as_ref().map(|after| after.as_slice())) {
+        Ok(Some(key)) => write_bytes(ctx, buf_ptr, buf_len, &key),
+        Ok(None) => KV_NOT_FOUND,
+        Err(_) => KV_FAILED,
+    }
+}
@@ -1,18 +1,42 @@
 #[macro_use]
 extern crate wasmer_runtime_core;
 
-use std::cell::UnsafeCell;
-use std::{f64, ffi::c_void};
+use crate::audio::Audio;
+use crate::cleanup::Cleanups;
+use crate::env::{get_emscripten_data, granted_rules, GuestEnv};
+use crate::exception::Exceptions;
+use crate::hooks::FromVarArgs;
+use crate::http::HttpResponses;
+use crate::inotify::Inotify;
+use crate::itimer::Timers;
+use crate::jmp::Jumps;
+use crate::keepalive::Keepalive;
+use crate::nullfunc::NullFuncs;
+use crate::omp::OpenMp;
+use crate::pthread::ThreadSpecific;
+use crate::rust_panic::PanicWatcher;
+use crate::scratch::Scratch;
+use crate::signal::{PendingSignals, SignalError};
+use crate::stat_cache::StatCache;
+use crate::stdio::StdoutBuffer;
+use crate::timezone::TimeZone;
+use crate::tty::AnsiStripper;
+use crate::ucontext::Contexts;
+use crate::vfs::Vfs;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::{f64, ffi::c_void, fmt, ptr};
 use wasmer_runtime_core::{
-    error::CallResult,
+    error::{CallError, CallResult, ResolveError, RuntimeError},
     export::Export,
     func,
     global::Global,
-    import::ImportObject,
+    import::{ImportObject, Namespace},
     imports,
+    instance::DynFunc,
     memory::Memory,
     table::Table,
-    types::{ElementType, MemoryDescriptor, TableDescriptor, Value},
+    types::{ElementType, FuncSig, Initializer, MemoryDescriptor, TableDescriptor, Type, Value},
     units::Pages,
     vm::Ctx,
     Func, Instance, Module,
@@ -20,40 +44,86 @@ use wasmer_runtime_core::{
 
 #[macro_use]
 mod macros;
+mod audio;
+mod capabilities;
+mod ccall;
+mod cleanup;
+mod clock;
+mod config;
 //#[cfg(test)]
 mod file_descriptor;
+mod stat_cache;
 pub mod stdio;
 
 // EMSCRIPTEN APIS
 mod env;
 mod errno;
 mod exception;
+mod ext;
+mod guest_log;
+mod hooks;
+mod http;
+mod inotify;
 mod io;
+mod itimer;
 mod jmp;
+mod keepalive;
+mod kv;
 mod linking;
 mod lock;
 mod math;
 mod memory;
+mod metadata;
 mod nullfunc;
+mod omp;
 mod process;
+mod pthread;
+mod rust_panic;
+mod scratch;
 mod signal;
+mod stats;
 mod storage;
 mod syscalls;
 mod time;
+mod timezone;
+mod tty;
+mod ucontext;
 mod utils;
 mod varargs;
+mod vfs;
 
+pub use self::audio::{
+    pump_sdl_audio, AudioBackend, AudioFormat, SampleFormat, Silence, SDL_STREAM,
+};
+pub use self::capabilities::{Capabilities, CapabilityError};
+pub use self::ccall::{c_function, c_functions, CArg, CFunction, CType, CValue};
+pub use self::cleanup::{defer, Deferred};
+pub use self::clock::{Clock, ClockId, HostClock};
+pub use self::config::{EmscriptenConfig, EnvPolicy, GuestIds};
+pub use self::ext::{register_ext_namespace, EXT_HTTP_NAMESPACE, EXT_KV_NAMESPACE, EXT_NAMESPACE};
+pub use self::guest_log::GUEST_LOG_TARGET;
+pub use self::hooks::{SyscallHandler, SyscallHandlers};
+pub use self::http::{NetPolicy, HTTP_DENIED, HTTP_FAILED, HTTP_INVALID};
+pub use self::kv::{KvStore, MemoryKvStore, KV_FAILED, KV_NOT_FOUND};
+pub use self::linking::{generate_side_module_env, DylinkInfo, SideModuleBases, TableFullError};
+pub use self::metadata::EmscriptenMetadata;
+pub use self::process::{core_dump, freeze, thaw, AbortHook, AbortInfo, AbortKind};
+pub use self::signal::forward_host_signals;
+pub use self::stats::{SlowSyscallHook, SyscallStat, SyscallStats};
+pub use self::stdio::{Buffering, Input, Output, Tee};
 pub use self::storage::{align_memory, static_alloc};
+pub use self::tty::{Ansi, Terminal};
 pub use self::utils::{
     allocate_cstr_on_stack, allocate_on_stack, get_emscripten_memory_size,
     get_emscripten_table_size, is_emscripten_module,
 };
+pub use self::varargs::VarArgs;
 
-// TODO: Magic number - how is this calculated?
+/// The default size of the stack.
 const TOTAL_STACK: u32 = 5_242_880;
 // TODO: Magic number - how is this calculated?
 const DYNAMICTOP_PTR_DIFF: u32 = 1088;
-// TODO: make this variable
+/// The default size of the static data.
 const STATIC_BUMP: u32 = 215_536;
 
 // The address globals begin at. Very low in memory, for code size and optimization opportunities.
@@ -85,52 +155,262 @@ pub struct EmscriptenData<'a> {
     pub memalign: Option<Func<'a, (u32, u32), u32>>,
     pub memset: Func<'a, (u32, u32, u32), u32>,
     pub stack_alloc: Func<'a, u32, u32>,
+    pub stack_save: Option<Func<'a, (), u32>>,
+    pub stack_restore: Option<Func<'a, u32>>,
+    pub dyn_call_v: Option<Func<'a, i32>>,
+    pub dyn_call_vi: Option<Func<'a, (i32, i32)>>,
+    pub dyn_call_vii: Option<Func<'a, (i32, i32, i32)>>,
+    /// The `dynCall_*` exports the `invoke_*` imports call through.
+    pub(crate) dyn_calls: HashMap<&'static str, DynFunc<'a>>,
+    pub(crate) set_threw: Option<Func<'a, (i32, i32)>>,
+    /// Whether a C++ type catches an exception of another, adjusting the
+    /// address of the exception to it.
+    pub(crate) can_catch: Option<Func<'a, (u32, u32, u32), u32>>,
+    /// The address of the `errno` of the guest.
+    pub(crate) errno_location: Option<Func<'a, (), u32>>,
+    /// The high half of the `i64` an import returns, or the other value it
+    /// returns, which the guest reads with `getTempRet0`.
+    pub(crate) temp_ret_0: i32,
 
-    pub jumps: Vec<UnsafeCell<[u32; 27]>>,
+    pub(crate) jumps: Jumps,
+    pub(crate) exceptions: Exceptions,
+    pub(crate) cleanups: Cleanups,
+
+    pub config: EmscriptenConfig,
+    pub(crate) stat_cache: Option<StatCache>,
+    pub(crate) environment: Option<GuestEnv>,
+    pub(crate) timezone: TimeZone,
+    pub(crate) ucontexts: Contexts,
+    pub(crate) vfs: Vfs,
+    pub(crate) stdout: StdoutBuffer,
+    /// The strippers of the escape sequences written to stdout and stderr.
+    pub(crate) ansi_strippers: Option<[AnsiStripper; 2]>,
+    /// The handlers the guest installed, by signal number.
+    pub(crate) signal_handlers: HashMap<u32, u32>,
+    pub(crate) pending_signals: PendingSignals,
+    /// Finds the panics of Rust guests in what they write to stderr.
+    pub(crate) panic_watcher: PanicWatcher,
+    pub(crate) keepalive: Keepalive,
+    pub(crate) audio: Audio,
+    pub(crate) inotify: Inotify,
+    pub(crate) timers: Timers,
+    pub(crate) thread_specific: ThreadSpecific,
+    pub(crate) openmp: OpenMp,
+    pub(crate) http: HttpResponses,
+    pub(crate) scratch: Scratch,
+}
+
+/// Why an instance can't be run as an emscripten module.
+#[derive(Debug, Clone)]
+pub enum EmscriptenError {
+    /// The instance doesn't export a function the runtime calls, or not
+    /// with the signature the runtime calls it with.
+    MissingExport {
+        name: &'static str,
+        reason: ResolveError,
+    },
+    /// `_main` takes neither `()` nor `(argc, argv)`.
+    UnexpectedMainSignature { params: Vec<Type> },
+}
+
+impl fmt::Display for EmscriptenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmscriptenError::MissingExport { name, reason } => {
+                write!(f, "the module can't run without `{}`: {}", name, reason)
+            }
+            EmscriptenError::UnexpectedMainSignature { params } => write!(
+                f,
+                "`_main` takes {:?}, instead of nothing or `argc` and `argv`",
+                params
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EmscriptenError {}
+
+impl From<EmscriptenError> for CallError {
+    fn from(err: EmscriptenError) -> Self {
+        match err {
+            EmscriptenError::MissingExport { reason, .. } => CallError::Resolve(reason),
+            EmscriptenError::UnexpectedMainSignature { params } => {
+                CallError::Resolve(ResolveError::Signature {
+                    expected: Arc::new(FuncSig::new(vec![Type::I32, Type::I32], vec![Type::I32])),
+                    found: params,
+                })
+            }
+        }
+    }
 }
 
 impl<'a> EmscriptenData<'a> {
+    /// # Panics
+    ///
+    /// If the instance doesn't export the functions the runtime calls,
+    /// which `try_new` reports instead.
     pub fn new(instance: &'a mut Instance) -> EmscriptenData<'a> {
-        let malloc = instance.func("_malloc").unwrap();
-        let free = instance.func("_free").unwrap();
+        Self::with_config(instance, EmscriptenConfig::default())
+    }
+
+    /// # Panics
+    ///
+    /// Like `new`, which `try_with_config` doesn't.
+    pub fn with_config(instance: &'a mut Instance, config: EmscriptenConfig) -> EmscriptenData<'a> {
+        Self::try_with_config(instance, config).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_new(instance: &'a mut Instance) -> Result<EmscriptenData<'a>, EmscriptenError> {
+        Self::try_with_config(instance, EmscriptenConfig::default())
+    }
+
+    pub fn try_with_config(
+        instance: &'a mut Instance,
+        config: EmscriptenConfig,
+    ) -> Result<EmscriptenData<'a>, EmscriptenError> {
+        let missing = |name| move |reason| EmscriptenError::MissingExport { name, reason };
+        let malloc = instance.func("_malloc").map_err(missing("_malloc"))?;
+        let free = instance.func("_free").map_err(missing("_free"))?;
         let memalign = if let Ok(func) = instance.func("_memalign") {
             Some(func)
         } else {
             None
         };
-        let memset = instance.func("_memset").unwrap();
-        let stack_alloc = instance.func("stackAlloc").unwrap();
+        let memset = instance.func("_memset").map_err(missing("_memset"))?;
+        let stack_alloc = instance.func("stackAlloc").map_err(missing("stackAlloc"))?;
+        let stack_save = instance.func("stackSave").ok();
+        let stack_restore = instance.func("stackRestore").ok();
+        let dyn_call_v = instance.func("dynCall_v").ok();
+        let dyn_call_vi = instance.func("dynCall_vi").ok();
+        let dyn_call_vii = instance.func("dynCall_vii").ok();
+        let dyn_calls = jmp::dyn_calls(instance);
+        let set_threw = instance
+            .func("setThrew")
+            .or_else(|_| instance.func("_setThrew"))
+            .ok();
+        let can_catch = instance.func("___cxa_can_catch").ok();
+        let errno_location = instance.func("___errno_location").ok();
+        let stat_cache = if config.stat_cache {
+            Some(StatCache::default())
+        } else {
+            None
+        };
+        let environment = match (&config.env, &config.capabilities) {
+            (EnvPolicy::Inherit, None) => None,
+            (EnvPolicy::Inherit, Some(granted)) => Some(GuestEnv::new(&granted.env, &[])),
+            (EnvPolicy::Private { inherit, vars }, None) => Some(GuestEnv::new(inherit, vars)),
+            (EnvPolicy::Private { inherit, vars }, Some(granted)) => {
+                Some(GuestEnv::new(&granted_rules(inherit, &granted.env), vars))
+            }
+        };
+        let timezone = match &config.timezone {
+            Some(name) => TimeZone::from_name(name).unwrap_or_else(TimeZone::utc),
+            None => TimeZone::local(),
+        };
+        let ansi_strippers = tty::strippers(config.terminal.as_ref());
+        let audio = Audio::new(config.audio.clone());
+        let stdout = StdoutBuffer::new(config.stdout.clone());
+        let vfs = Vfs::new(&config.mapped_dirs, config.capabilities.as_ref());
 
-        EmscriptenData {
+        Ok(EmscriptenData {
             malloc,
             free,
             memalign,
             memset,
             stack_alloc,
-            jumps: Vec::new(),
-        }
+            stack_save,
+            stack_restore,
+            dyn_call_v,
+            dyn_call_vi,
+            dyn_call_vii,
+            dyn_calls,
+            set_threw,
+            temp_ret_0: 0,
+            can_catch,
+            errno_location,
+            jumps: Jumps::default(),
+            exceptions: Exceptions::default(),
+            cleanups: Cleanups::default(),
+            config,
+            stat_cache,
+            environment,
+            timezone,
+            ucontexts: Contexts::default(),
+            vfs,
+            stdout,
+            ansi_strippers,
+            signal_handlers: HashMap::new(),
+            pending_signals: PendingSignals::new(),
+            panic_watcher: PanicWatcher::default(),
+            keepalive: Keepalive::default(),
+            audio,
+            inotify: Inotify::default(),
+            timers: Timers::default(),
+            thread_specific: ThreadSpecific::default(),
+            openmp: OpenMp::default(),
+            http: HttpResponses::default(),
+            scratch: Scratch::default(),
+        })
     }
 }
 
 pub fn run_emscripten_instance(
+    module: &Module,
+    instance: &mut Instance,
+    path: &str,
+    args: Vec<&str>,
+) -> CallResult<()> {
+    run_emscripten_instance_with_config(module, instance, path, args, EmscriptenConfig::default())
+}
+
+/// Runs `main`. Unless the runtime exits once `main` returns, the instance
+/// keeps its emscripten data for `call_emscripten_func` to call into it
+/// afterwards.
+pub fn run_emscripten_instance_with_config(
     _module: &Module,
     instance: &mut Instance,
     path: &str,
     args: Vec<&str>,
+    config: EmscriptenConfig,
 ) -> CallResult<()> {
-    let mut data = EmscriptenData::new(instance);
-    let data_ptr = &mut data as *mut _ as *mut c_void;
-    instance.context_mut().data = data_ptr;
+    let core_dump_path = config.core_dump.clone();
+    keepalive::keep(instance, config)?;
+
+    let result = call_main(instance, path, args);
+    // A Rust guest writes where it panicked to stderr before it aborts
+    // or traps.
+    let result = result.map_err(|err| match rust_panic::take(instance.context_mut()) {
+        Some(panic) => CallError::Runtime(RuntimeError::User {
+            msg: format!("{}, then the guest trapped: {}", panic, err),
+        }),
+        None => err,
+    });
+    let data = get_emscripten_data(instance.context_mut());
+    let result = match result {
+        Err(_) if data.take_unwinding() => Ok(()),
+        result => result,
+    };
+    let keep_alive = result.is_ok() && data.keeps_runtime_alive();
+    if let (Err(err), Some(core_dump_path)) = (&result, &core_dump_path) {
+        let reason = format!("{:?}", err);
+        process::write_core_dump(instance.context_mut(), &reason, core_dump_path);
+    }
 
+    // TODO atinit and atexit for emscripten
+    if !keep_alive {
+        keepalive::release(instance.context_mut());
+    }
+    result
+}
+
+fn call_main(instance: &mut Instance, path: &str, args: Vec<&str>) -> CallResult<()> {
     if let Ok(_func) = instance.dyn_func("___emscripten_environ_constructor") {
         instance.call("___emscripten_environ_constructor", &[])?;
     }
 
-    // println!("running emscripten instance");
-
     let main_func = instance.dyn_func("_main")?;
-    let num_params = main_func.signature().params().len();
-    let _result = match num_params {
+    let params = main_func.signature().params().to_vec();
+    match params.len() {
         2 => {
             let (argc, argv) = store_module_arguments(instance.context_mut(), path, args);
             instance.call("_main", &[Value::I32(argc as i32), Value::I32(argv as i32)])?;
@@ -138,17 +418,52 @@ pub fn run_emscripten_instance(
         0 => {
             instance.call("_main", &[])?;
         }
-        _ => panic!(
-            "The emscripten main function has received an incorrect number of params {}",
-            num_params
-        ),
+        _ => return Err(EmscriptenError::UnexpectedMainSignature { params }.into()),
     };
-
-    // TODO atinit and atexit for emscripten
-    // println!("{:?}", data);
     Ok(())
 }
 
+/// Calls an export of an emscripten instance outside of `main`, with
+/// the per-instance data that the emscripten imports rely on.
+///
+/// An instance whose runtime outlived `main` keeps its data across calls,
+/// until no call leaves the runtime alive. Any other instance gets fresh
+/// data for the call.
+pub fn call_emscripten_func(
+    instance: &mut Instance,
+    name: &str,
+    params: &[Value],
+) -> CallResult<Vec<Value>> {
+    if instance.context().data.is_null() {
+        let mut data = EmscriptenData::try_new(instance)?;
+        let data_ptr = &mut data as *mut _ as *mut c_void;
+        instance.context_mut().data = data_ptr;
+
+        let result = instance.call(name, params);
+        instance.context_mut().data = ptr::null_mut();
+        return result;
+    }
+
+    let result = instance.call(name, params);
+    let data = get_emscripten_data(instance.context_mut());
+    data.jumps.release();
+    data.exceptions.take_throwing();
+    let result = match result {
+        Err(_) if data.take_unwinding() => Ok(Vec::new()),
+        result => result,
+    };
+    if !data.keeps_runtime_alive() {
+        keepalive::release(instance.context_mut());
+    }
+    result
+}
+
+/// Whether the runtime of an instance outlived the last call into it, so
+/// the host can keep calling it back.
+pub fn emscripten_runtime_alive(instance: &Instance) -> bool {
+    !instance.context().data.is_null()
+}
+
 fn store_module_arguments(ctx: &mut Ctx, path: &str, args: Vec<&str>) -> (u32, u32) {
     let argc = args.len() + 1;
 
@@ -193,6 +508,94 @@ pub struct EmscriptenGlobalsData {
     nan: f64,
 }
 
+/// Where an emscripten module keeps its static data and its stack: the
+/// static data from `GLOBAL_BASE` up, then the stack, then the heap that
+/// `sbrk` grows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmscriptenMemoryConfig {
+    /// The size of the stack, emscripten's `TOTAL_STACK`.
+    pub total_stack: u32,
+    /// The size of the static data, emscripten's `STATIC_BUMP`.
+    pub static_bump: u32,
+    /// The initial size of the memory, emscripten's `TOTAL_MEMORY`, when
+    /// it's more than the module declares.
+    pub total_memory: Option<Pages>,
+    /// The initial size of the table, when it's more than the module
+    /// declares.
+    pub table_size: Option<u32>,
+    /// The most the memory can grow to, when it's less than the module
+    /// declares. It's never less than the initial size the module declares.
+    pub max_memory: Option<Pages>,
+}
+
+impl Default for EmscriptenMemoryConfig {
+    fn default() -> Self {
+        EmscriptenMemoryConfig {
+            total_stack: TOTAL_STACK,
+            static_bump: STATIC_BUMP,
+            total_memory: None,
+            table_size: None,
+            max_memory: None,
+        }
+    }
+}
+
+impl EmscriptenMemoryConfig {
+    /// The default layout, with the static data grown to fit the data
+    /// segments of `module` at constant offsets, so the stack doesn't
+    /// start in the middle of them.
+    pub fn for_module(module: &Module) -> Self {
+        let data_end = module
+            .0
+            .info
+            .data_initializers
+            .iter()
+            .filter_map(|init| match init.base {
+                Initializer::Const(Value::I32(base)) => {
+                    Some((base as u32).saturating_add(init.data.len() as u32))
+                }
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        let default = Self::default();
+        EmscriptenMemoryConfig {
+            static_bump: default
+                .static_bump
+                .max(align_memory(data_end.saturating_sub(STATIC_BASE))),
+            ..default
+        }
+    }
+
+    /// The layout `wasm` was linked for, as its `emscripten_metadata`
+    /// section records it, or its `dylink` section for the modules linked
+    /// for dynamic linking. Without either, it's the one `for_module`
+    /// guesses for `module`, which is compiled from `wasm`.
+    pub fn for_binary(wasm: &[u8], module: &Module) -> Self {
+        if let Some(metadata) = EmscriptenMetadata::parse(wasm) {
+            let stacktop = align_memory(metadata.dynamictop_ptr.saturating_add(4));
+            EmscriptenMemoryConfig {
+                total_stack: match metadata.dynamic_base.checked_sub(stacktop) {
+                    Some(total_stack) if total_stack > 0 => total_stack,
+                    _ => TOTAL_STACK,
+                },
+                static_bump: metadata.temp_double_ptr.saturating_sub(STATIC_BASE),
+                total_memory: Some(Pages(metadata.memory_pages)),
+                table_size: Some(metadata.table_size),
+                max_memory: None,
+            }
+        } else if let Some(dylink) = DylinkInfo::parse(wasm) {
+            EmscriptenMemoryConfig {
+                static_bump: align_memory(dylink.memory_size),
+                table_size: Some(dylink.table_size),
+                ..Self::default()
+            }
+        } else {
+            Self::for_module(module)
+        }
+    }
+}
+
 pub struct EmscriptenGlobals {
     // The emscripten data
     pub data: EmscriptenGlobalsData,
@@ -204,17 +607,25 @@ pub struct EmscriptenGlobals {
 }
 
 impl EmscriptenGlobals {
-    pub fn new(module: &Module /*, static_bump: u32 */) -> Self {
-        let (table_min, table_max) = get_emscripten_table_size(&module);
-        let (memory_min, memory_max) = get_emscripten_memory_size(&module);
+    pub fn new(module: &Module) -> Self {
+        Self::with_config(module, EmscriptenMemoryConfig::for_module(module))
+    }
 
-        // Memory initialization
-        let memory_type = MemoryDescriptor {
-            minimum: memory_min,
-            maximum: memory_max,
-            shared: false,
-        };
-        let memory = Memory::new(memory_type).unwrap();
+    /// Lay the memory of `module` out as `config` says. The memory starts
+    /// out large enough for the stack, as far as its maximum allows.
+    pub fn with_config(module: &Module, config: EmscriptenMemoryConfig) -> Self {
+        let (mut table_min, table_max) = get_emscripten_table_size(&module);
+        let (mut memory_min, mut memory_max) = get_emscripten_memory_size(&module);
+        if let Some(limit) = config.max_memory {
+            memory_max = Some(
+                memory_max
+                    .map_or(limit, |max| max.min(limit))
+                    .max(memory_min),
+            );
+        }
+        if let Some(table_size) = config.table_size {
+            table_min = table_min.max(table_max.map_or(table_size, |max| table_size.min(max)));
+        }
 
         let table_type = TableDescriptor {
             element: ElementType::Anyfunc,
@@ -224,7 +635,7 @@ impl EmscriptenGlobals {
         let mut table = Table::new(table_type).unwrap();
 
         let data = {
-            let static_bump = STATIC_BUMP;
+            let static_bump = config.static_bump;
 
             let mut STATIC_TOP = STATIC_BASE + static_bump;
 
@@ -237,7 +648,7 @@ impl EmscriptenGlobals {
             let dynamictop_ptr = static_alloc(&mut STATIC_TOP, 4);
 
             let stacktop = align_memory(STATIC_TOP);
-            let stack_max = stacktop + TOTAL_STACK;
+            let stack_max = stacktop.saturating_add(config.total_stack);
 
             EmscriptenGlobalsData {
                 abort: 0,
@@ -253,6 +664,24 @@ impl EmscriptenGlobals {
             }
         };
 
+        // Memory initialization
+        let mut needed = Pages(((u64::from(data.stack_max) + 0xffff) / 0x10000) as u32);
+        if let Some(total_memory) = config.total_memory {
+            needed = needed.max(total_memory);
+        }
+        if needed > memory_min {
+            memory_min = match memory_max {
+                Some(max) if needed > max => max,
+                _ => needed,
+            };
+        }
+        let memory_type = MemoryDescriptor {
+            minimum: memory_min,
+            maximum: memory_max,
+            shared: false,
+        };
+        let memory = Memory::new(memory_type).unwrap();
+
         emscripten_set_up_memory(&memory, &data);
 
         Self {
@@ -265,8 +694,21 @@ impl EmscriptenGlobals {
     }
 }
 
+/// The import of a syscall, which goes through the handler configured for
+/// it, if there's one.
+macro_rules! syscall {
+    ($name:ident) => {{
+        fn hooked(ctx: &mut Ctx, which: i32, varargs: VarArgs) -> Result<i32, SignalError> {
+            crate::hooks::dispatch(ctx, which, varargs, |ctx, varargs| {
+                crate::syscalls::$name(ctx, which, FromVarArgs::from_varargs(varargs))
+            })
+        }
+        func!(hooked)
+    }};
+}
+
 pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject {
-    imports! {
+    let mut import_object = imports! {
         "env" => {
             "memory" => Export::Memory(globals.memory.clone()),
             "table" => Export::Table(globals.table.clone()),
@@ -303,74 +745,81 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
             "_sysconf" => func!(crate::env::_sysconf),
             "_getaddrinfo" => func!(crate::env::_getaddrinfo),
 
-            // Null func
-            "nullFunc_i" => func!(crate::nullfunc::nullfunc_i),
-            "nullFunc_ii" => func!(crate::nullfunc::nullfunc_ii),
-            "nullFunc_iii" => func!(crate::nullfunc::nullfunc_iii),
-            "nullFunc_iiii" => func!(crate::nullfunc::nullfunc_iiii),
-            "nullFunc_iiiii" => func!(crate::nullfunc::nullfunc_iiiii),
-            "nullFunc_iiiiii" => func!(crate::nullfunc::nullfunc_iiiiii),
-            "nullFunc_v" => func!(crate::nullfunc::nullfunc_v),
-            "nullFunc_vi" => func!(crate::nullfunc::nullfunc_vi),
-            "nullFunc_vii" => func!(crate::nullfunc::nullfunc_vii),
-            "nullFunc_viii" => func!(crate::nullfunc::nullfunc_viii),
-            "nullFunc_viiii" => func!(crate::nullfunc::nullfunc_viiii),
-            "nullFunc_viiiii" => func!(crate::nullfunc::nullfunc_viiiii),
-            "nullFunc_viiiiii" => func!(crate::nullfunc::nullfunc_viiiiii),
 
             // Syscalls
             "___syscall1" => func!(crate::syscalls::___syscall1),
-            "___syscall3" => func!(crate::syscalls::___syscall3),
-            "___syscall4" => func!(crate::syscalls::___syscall4),
-            "___syscall5" => func!(crate::syscalls::___syscall5),
-            "___syscall6" => func!(crate::syscalls::___syscall6),
-            "___syscall10" => func!(crate::syscalls::___syscall10),
-            "___syscall12" => func!(crate::syscalls::___syscall12),
-            "___syscall15" => func!(crate::syscalls::___syscall15),
-            "___syscall20" => func!(crate::syscalls::___syscall20),
-            "___syscall39" => func!(crate::syscalls::___syscall39),
-            "___syscall38" => func!(crate::syscalls::___syscall38),
-            "___syscall40" => func!(crate::syscalls::___syscall40),
-            "___syscall54" => func!(crate::syscalls::___syscall54),
-            "___syscall57" => func!(crate::syscalls::___syscall57),
-            "___syscall60" => func!(crate::syscalls::___syscall60),
-            "___syscall63" => func!(crate::syscalls::___syscall63),
-            "___syscall64" => func!(crate::syscalls::___syscall64),
-            "___syscall66" => func!(crate::syscalls::___syscall66),
-            "___syscall75" => func!(crate::syscalls::___syscall75),
-            "___syscall85" => func!(crate::syscalls::___syscall85),
-            "___syscall91" => func!(crate::syscalls::___syscall191),
-            "___syscall97" => func!(crate::syscalls::___syscall97),
-            "___syscall102" => func!(crate::syscalls::___syscall102),
-            "___syscall110" => func!(crate::syscalls::___syscall110),
-            "___syscall114" => func!(crate::syscalls::___syscall114),
-            "___syscall122" => func!(crate::syscalls::___syscall122),
-            "___syscall140" => func!(crate::syscalls::___syscall140),
-            "___syscall142" => func!(crate::syscalls::___syscall142),
-            "___syscall145" => func!(crate::syscalls::___syscall145),
-            "___syscall146" => func!(crate::syscalls::___syscall146),
-            "___syscall168" => func!(crate::syscalls::___syscall168),
-            "___syscall180" => func!(crate::syscalls::___syscall180),
-            "___syscall181" => func!(crate::syscalls::___syscall181),
-            "___syscall191" => func!(crate::syscalls::___syscall191),
-            "___syscall192" => func!(crate::syscalls::___syscall192),
-            "___syscall194" => func!(crate::syscalls::___syscall194),
-            "___syscall195" => func!(crate::syscalls::___syscall195),
-            "___syscall196" => func!(crate::syscalls::___syscall196),
-            "___syscall197" => func!(crate::syscalls::___syscall197),
-            "___syscall199" => func!(crate::syscalls::___syscall199),
-            "___syscall201" => func!(crate::syscalls::___syscall201),
-            "___syscall202" => func!(crate::syscalls::___syscall202),
-            "___syscall212" => func!(crate::syscalls::___syscall212),
-            "___syscall220" => func!(crate::syscalls::___syscall220),
-            "___syscall221" => func!(crate::syscalls::___syscall221),
-            "___syscall268" => func!(crate::syscalls::___syscall268),
-            "___syscall272" => func!(crate::syscalls::___syscall272),
-            "___syscall295" => func!(crate::syscalls::___syscall295),
-            "___syscall300" => func!(crate::syscalls::___syscall300),
-            "___syscall330" => func!(crate::syscalls::___syscall330),
-            "___syscall334" => func!(crate::syscalls::___syscall334),
-            "___syscall340" => func!(crate::syscalls::___syscall340),
+            "___syscall3" => syscall!(___syscall3),
+            "___syscall4" => syscall!(___syscall4),
+            "___syscall5" => syscall!(___syscall5),
+            "___syscall6" => syscall!(___syscall6),
+            "___syscall10" => syscall!(___syscall10),
+            "___syscall12" => syscall!(___syscall12),
+            "___syscall15" => syscall!(___syscall15),
+            "___syscall20" => syscall!(___syscall20),
+            "___syscall24" => syscall!(___syscall24),
+            "___syscall33" => syscall!(___syscall33),
+            "___syscall39" => syscall!(___syscall39),
+            "___syscall38" => syscall!(___syscall38),
+            "___syscall40" => syscall!(___syscall40),
+            "___syscall43" => syscall!(___syscall43),
+            "___syscall47" => syscall!(___syscall47),
+            "___syscall49" => syscall!(___syscall49),
+            "___syscall50" => syscall!(___syscall50),
+            "___syscall54" => syscall!(___syscall54),
+            "___syscall57" => syscall!(___syscall57),
+            "___syscall60" => syscall!(___syscall60),
+            "___syscall63" => syscall!(___syscall63),
+            "___syscall64" => syscall!(___syscall64),
+            "___syscall66" => syscall!(___syscall66),
+            "___syscall75" => syscall!(___syscall75),
+            "___syscall85" => syscall!(___syscall85),
+            "___syscall91" => syscall!(___syscall191),
+            "___syscall97" => syscall!(___syscall97),
+            "___syscall102" => syscall!(___syscall102),
+            "___syscall110" => syscall!(___syscall110),
+            "___syscall114" => syscall!(___syscall114),
+            "___syscall122" => syscall!(___syscall122),
+            "___syscall140" => syscall!(___syscall140),
+            "___syscall142" => syscall!(___syscall142),
+            "___syscall145" => syscall!(___syscall145),
+            "___syscall146" => syscall!(___syscall146),
+            "___syscall168" => syscall!(___syscall168),
+            "___syscall180" => syscall!(___syscall180),
+            "___syscall181" => syscall!(___syscall181),
+            "___syscall183" => syscall!(___syscall183),
+            "___syscall187" => syscall!(___syscall187),
+            "___syscall191" => syscall!(___syscall191),
+            "___syscall192" => syscall!(___syscall192),
+            "___syscall194" => syscall!(___syscall194),
+            "___syscall195" => syscall!(___syscall195),
+            "___syscall196" => syscall!(___syscall196),
+            "___syscall197" => syscall!(___syscall197),
+            "___syscall199" => syscall!(___syscall199),
+            "___syscall200" => syscall!(___syscall200),
+            "___syscall201" => syscall!(___syscall201),
+            "___syscall202" => syscall!(___syscall202),
+            "___syscall212" => syscall!(___syscall212),
+            "___syscall220" => syscall!(___syscall220),
+            "___syscall221" => syscall!(___syscall221),
+            "___syscall224" => syscall!(___syscall224),
+            "___syscall239" => syscall!(___syscall239),
+            "___syscall268" => syscall!(___syscall268),
+            "___syscall272" => syscall!(___syscall272),
+            "___syscall291" => syscall!(___syscall291),
+            "___syscall292" => syscall!(___syscall292),
+            "___syscall293" => syscall!(___syscall293),
+            "___syscall295" => syscall!(___syscall295),
+            "___syscall296" => syscall!(___syscall296),
+            "___syscall298" => syscall!(___syscall298),
+            "___syscall300" => syscall!(___syscall300),
+            "___syscall301" => syscall!(___syscall301),
+            "___syscall305" => syscall!(___syscall305),
+            "___syscall330" => syscall!(___syscall330),
+            "___syscall307" => syscall!(___syscall307),
+            "___syscall332" => syscall!(___syscall332),
+            "___syscall334" => syscall!(___syscall334),
+            "___syscall340" => syscall!(___syscall340),
+            "___syscall377" => syscall!(___syscall377),
 
             // Process
             "abort" => func!(crate::process::em_abort),
@@ -379,6 +828,10 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
             "_llvm_trap" => func!(crate::process::_llvm_trap),
             "_fork" => func!(crate::process::_fork),
             "_exit" => func!(crate::process::_exit),
+            "_emscripten_exit_with_live_runtime" => func!(crate::keepalive::_emscripten_exit_with_live_runtime),
+            "_emscripten_runtime_keepalive_push" => func!(crate::keepalive::_emscripten_runtime_keepalive_push),
+            "_emscripten_runtime_keepalive_pop" => func!(crate::keepalive::_emscripten_runtime_keepalive_pop),
+            "_emscripten_runtime_keepalive_check" => func!(crate::keepalive::_emscripten_runtime_keepalive_check),
             "_system" => func!(crate::process::_system),
             "_popen" => func!(crate::process::_popen),
             "_endgrent" => func!(crate::process::_endgrent),
@@ -394,11 +847,81 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
             "_sched_yield" => func!(crate::process::_sched_yield),
             "_setgrent" => func!(crate::process::_setgrent),
             "_setgroups" => func!(crate::process::_setgroups),
-            "_setitimer" => func!(crate::process::_setitimer),
+            "_setitimer" => func!(crate::itimer::_setitimer),
             "_usleep" => func!(crate::process::_usleep),
             "_utimes" => func!(crate::process::_utimes),
             "_waitpid" => func!(crate::process::_waitpid),
 
+            // Audio
+            "_alcOpenDevice" => func!(crate::audio::_alc_open_device),
+            "_alcCloseDevice" => func!(crate::audio::_alc_close_device),
+            "_alcCreateContext" => func!(crate::audio::_alc_create_context),
+            "_alcMakeContextCurrent" => func!(crate::audio::_alc_make_context_current),
+            "_alcDestroyContext" => func!(crate::audio::_alc_destroy_context),
+            "_alcGetError" => func!(crate::audio::_alc_get_error),
+            "_alGetError" => func!(crate::audio::_al_get_error),
+            "_alGenBuffers" => func!(crate::audio::_al_gen_buffers),
+            "_alDeleteBuffers" => func!(crate::audio::_al_delete_buffers),
+            "_alGenSources" => func!(crate::audio::_al_gen_sources),
+            "_alDeleteSources" => func!(crate::audio::_al_delete_sources),
+            "_alBufferData" => func!(crate::audio::_al_buffer_data),
+            "_alSourcei" => func!(crate::audio::_al_sourcei),
+            "_alSourcef" => func!(crate::audio::_al_sourcef),
+            "_alSource3f" => func!(crate::audio::_al_source3f),
+            "_alListenerf" => func!(crate::audio::_al_listenerf),
+            "_alListener3f" => func!(crate::audio::_al_listener3f),
+            "_alGetSourcei" => func!(crate::audio::_al_get_sourcei),
+            "_alSourceQueueBuffers" => func!(crate::audio::_al_source_queue_buffers),
+            "_alSourceUnqueueBuffers" => func!(crate::audio::_al_source_unqueue_buffers),
+            "_alSourcePlay" => func!(crate::audio::_al_source_play),
+            "_alSourceStop" => func!(crate::audio::_al_source_stop),
+            "_alSourcePause" => func!(crate::audio::_al_source_pause),
+            "_SDL_OpenAudio" => func!(crate::audio::_sdl_open_audio),
+            "_SDL_PauseAudio" => func!(crate::audio::_sdl_pause_audio),
+            "_SDL_CloseAudio" => func!(crate::audio::_sdl_close_audio),
+            "_SDL_LockAudio" => func!(crate::audio::_sdl_lock_audio),
+            "_SDL_UnlockAudio" => func!(crate::audio::_sdl_unlock_audio),
+
+            // Console
+            "_emscripten_console_log" => func!(crate::guest_log::_emscripten_console_log),
+            "_emscripten_console_warn" => func!(crate::guest_log::_emscripten_console_warn),
+            "_emscripten_console_error" => func!(crate::guest_log::_emscripten_console_error),
+
+            // Threads
+            "_pthread_self" => func!(crate::pthread::_pthread_self),
+            "_pthread_equal" => func!(crate::pthread::_pthread_equal),
+            "_pthread_key_create" => func!(crate::pthread::_pthread_key_create),
+            "_pthread_key_delete" => func!(crate::pthread::_pthread_key_delete),
+            "_pthread_getspecific" => func!(crate::pthread::_pthread_getspecific),
+            "_pthread_setspecific" => func!(crate::pthread::_pthread_setspecific),
+            "_pthread_once" => func!(crate::pthread::_pthread_once),
+
+            // OpenMP
+            "_omp_get_num_threads" => func!(crate::omp::_omp_get_num_threads),
+            "_omp_get_max_threads" => func!(crate::omp::_omp_get_max_threads),
+            "_omp_get_num_procs" => func!(crate::omp::_omp_get_num_procs),
+            "_omp_get_thread_num" => func!(crate::omp::_omp_get_thread_num),
+            "_omp_set_num_threads" => func!(crate::omp::_omp_set_num_threads),
+            "_omp_in_parallel" => func!(crate::omp::_omp_in_parallel),
+            "_omp_get_wtime" => func!(crate::omp::_omp_get_wtime),
+            "_omp_get_wtick" => func!(crate::omp::_omp_get_wtick),
+            "___kmpc_global_thread_num" => func!(crate::omp::___kmpc_global_thread_num),
+            "___kmpc_fork_call" => func!(crate::omp::___kmpc_fork_call),
+            "___kmpc_for_static_init_4" => func!(crate::omp::___kmpc_for_static_init_4),
+            "___kmpc_for_static_init_4u" => func!(crate::omp::___kmpc_for_static_init_4u),
+            "___kmpc_for_static_fini" => func!(crate::omp::___kmpc_for_static_fini),
+            "___kmpc_push_num_threads" => func!(crate::omp::___kmpc_push_num_threads),
+            "___kmpc_barrier" => func!(crate::omp::___kmpc_barrier),
+            "___kmpc_critical" => func!(crate::omp::___kmpc_critical),
+            "___kmpc_end_critical" => func!(crate::omp::___kmpc_end_critical),
+            "___kmpc_master" => func!(crate::omp::___kmpc_master),
+            "___kmpc_end_master" => func!(crate::omp::___kmpc_end_master),
+            "___kmpc_single" => func!(crate::omp::___kmpc_single),
+            "___kmpc_end_single" => func!(crate::omp::___kmpc_end_single),
+            "___kmpc_reduce_nowait" => func!(crate::omp::___kmpc_reduce_nowait),
+            "___kmpc_end_reduce_nowait" => func!(crate::omp::___kmpc_end_reduce_nowait),
+            "___kmpc_serialized_parallel" => func!(crate::omp::___kmpc_serialized_parallel),
+            "___kmpc_end_serialized_parallel" => func!(crate::omp::___kmpc_end_serialized_parallel),
 
             // Signal
             "_sigemptyset" => func!(crate::signal::_sigemptyset),
@@ -411,13 +934,31 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
             // Memory
             "abortOnCannotGrowMemory" => func!(crate::memory::abort_on_cannot_grow_memory),
             "_emscripten_memcpy_big" => func!(crate::memory::_emscripten_memcpy_big),
+            "_memmove" => func!(crate::memory::_memmove),
+            "_memset" => func!(crate::memory::_memset),
             "enlargeMemory" => func!(crate::memory::enlarge_memory),
             "getTotalMemory" => func!(crate::memory::get_total_memory),
             "___map_file" => func!(crate::memory::___map_file),
 
             // Exception
             "___cxa_allocate_exception" => func!(crate::exception::___cxa_allocate_exception),
+            "___cxa_free_exception" => func!(crate::exception::___cxa_free_exception),
             "___cxa_throw" => func!(crate::exception::___cxa_throw),
+            "___cxa_rethrow" => func!(crate::exception::___cxa_rethrow),
+            "___resumeException" => func!(crate::exception::___resume_exception),
+            "___cxa_find_matching_catch_2" => func!(crate::exception::___cxa_find_matching_catch_2),
+            "___cxa_find_matching_catch_3" => func!(crate::exception::___cxa_find_matching_catch_3),
+            "___cxa_find_matching_catch_4" => func!(crate::exception::___cxa_find_matching_catch_4),
+            "___cxa_find_matching_catch_5" => func!(crate::exception::___cxa_find_matching_catch_5),
+            "___cxa_find_matching_catch_6" => func!(crate::exception::___cxa_find_matching_catch_6),
+            "___cxa_begin_catch" => func!(crate::exception::___cxa_begin_catch),
+            "___cxa_end_catch" => func!(crate::exception::___cxa_end_catch),
+            "___cxa_current_primary_exception" => func!(crate::exception::___cxa_current_primary_exception),
+            "___cxa_decrement_exception_refcount" => func!(crate::exception::___cxa_decrement_exception_refcount),
+            "___cxa_increment_exception_refcount" => func!(crate::exception::___cxa_increment_exception_refcount),
+            "___cxa_uncaught_exceptions" => func!(crate::exception::___cxa_uncaught_exceptions),
+            "__ZSt18uncaught_exceptionv" => func!(crate::exception::_std_uncaught_exception),
+            "___gxx_personality_v0" => func!(crate::exception::___gxx_personality_v0),
 
             // Time
             "_gettimeofday" => func!(crate::time::_gettimeofday),
@@ -446,6 +987,43 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
             // Jump
             "__setjmp" => func!(crate::jmp::__setjmp),
             "__longjmp" => func!(crate::jmp::__longjmp),
+            "_sigsetjmp" => func!(crate::jmp::_sigsetjmp),
+            "_siglongjmp" => func!(crate::jmp::_siglongjmp),
+            "_saveSetjmp" => func!(crate::jmp::_save_setjmp),
+            "_testSetjmp" => func!(crate::jmp::_test_setjmp),
+            "_longjmp" => func!(crate::jmp::_longjmp),
+            "_emscripten_longjmp" => func!(crate::jmp::_emscripten_longjmp),
+            "getTempRet0" => func!(crate::env::get_temp_ret_0),
+            "setTempRet0" => func!(crate::env::set_temp_ret_0),
+            "invoke_v" => func!(crate::jmp::invoke_v),
+            "invoke_vi" => func!(crate::jmp::invoke_vi),
+            "invoke_vii" => func!(crate::jmp::invoke_vii),
+            "invoke_viii" => func!(crate::jmp::invoke_viii),
+            "invoke_viiii" => func!(crate::jmp::invoke_viiii),
+            "invoke_viiiii" => func!(crate::jmp::invoke_viiiii),
+            "invoke_i" => func!(crate::jmp::invoke_i),
+            "invoke_ii" => func!(crate::jmp::invoke_ii),
+            "invoke_iii" => func!(crate::jmp::invoke_iii),
+            "invoke_iiii" => func!(crate::jmp::invoke_iiii),
+            "invoke_iiiii" => func!(crate::jmp::invoke_iiiii),
+            "invoke_iiiiii" => func!(crate::jmp::invoke_iiiiii),
+            "invoke_vij" => func!(crate::jmp::invoke_vij),
+            "invoke_iij" => func!(crate::jmp::invoke_iij),
+            "invoke_ji" => func!(crate::jmp::invoke_ji),
+            "invoke_jii" => func!(crate::jmp::invoke_jii),
+            "invoke_vif" => func!(crate::jmp::invoke_vif),
+            "invoke_vid" => func!(crate::jmp::invoke_vid),
+            "invoke_viid" => func!(crate::jmp::invoke_viid),
+            "invoke_iid" => func!(crate::jmp::invoke_iid),
+            "invoke_fi" => func!(crate::jmp::invoke_fi),
+            "invoke_di" => func!(crate::jmp::invoke_di),
+            "invoke_dii" => func!(crate::jmp::invoke_dii),
+
+            // ucontext
+            "_getcontext" => func!(crate::ucontext::_getcontext),
+            "_setcontext" => func!(crate::ucontext::_setcontext),
+            "_makecontext" => func!(crate::ucontext::_makecontext),
+            "_swapcontext" => func!(crate::ucontext::_swapcontext),
 
             // Linking
             "_dlclose" => func!(crate::linking::_dlclose),
@@ -464,7 +1042,13 @@ pub fn generate_emscripten_env(globals: &mut EmscriptenGlobals) -> ImportObject
         "asm2wasm" => {
             "f64-rem" => func!(crate::math::f64_rem),
         },
+    };
+
+    // The null functions depend on the signatures the module imports them for.
+    if let Some(env) = import_object.register("env", Namespace::new()) {
+        import_object.register("env", NullFuncs::new(env));
     }
+    import_object
 }
 
 /// The current version of this crate
@@ -1,4 +1,6 @@
-use wasmer_runtime_core::vm::Ctx;
+use crate::{align_memory, generate_emscripten_env, EmscriptenGlobals};
+use std::fmt;
+use wasmer_runtime_core::{import::ImportObject, vm::Ctx};
 
 // TODO: Need to implement.
 
@@ -25,3 +27,230 @@ pub fn _dlerror(_ctx: &mut Ctx) -> i32 {
     debug!("emscripten::_dlerror");
     -1
 }
+
+/// The memory and table requirements of an emscripten side module,
+/// read from its `dylink` custom section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DylinkInfo {
+    pub memory_size: u32,
+    /// The log2 of the alignment of the module's data.
+    pub memory_align: u32,
+    pub table_size: u32,
+    /// The log2 of the alignment of the module's table slice.
+    pub table_align: u32,
+}
+
+impl DylinkInfo {
+    /// Returns `None` if `wasm` isn't a side module.
+    pub fn parse(wasm: &[u8]) -> Option<Self> {
+        // The `dylink` section has to be the first section of the module.
+        let mut reader = Reader {
+            bytes: wasm.get(8..)?,
+        };
+        if reader.byte()? != 0 {
+            return None;
+        }
+        let size = reader.varuint32()? as usize;
+        let mut section = Reader {
+            bytes: reader.bytes.get(..size)?,
+        };
+        let name_len = section.varuint32()? as usize;
+        if section.bytes.get(..name_len)? != b"dylink" {
+            return None;
+        }
+        section.bytes = &section.bytes[name_len..];
+
+        Some(DylinkInfo {
+            memory_size: section.varuint32()?,
+            memory_align: section.varuint32()?,
+            table_size: section.varuint32()?,
+            table_align: section.varuint32()?,
+        })
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    pub(crate) bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn byte(&mut self) -> Option<u8> {
+        let (&byte, rest) = self.bytes.split_first()?;
+        self.bytes = rest;
+        Some(byte)
+    }
+
+    pub(crate) fn varuint32(&mut self) -> Option<u32> {
+        let mut result = 0u32;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            // The fifth byte only has room for the top 4 bits.
+            if shift == 28 && byte & 0x70 != 0 {
+                return None;
+            }
+            result |= u32::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Some(result);
+            }
+        }
+        None
+    }
+}
+
+/// Where a side module was placed in the memory and table it shares
+/// with the main module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SideModuleBases {
+    pub memory_base: u32,
+    pub table_base: u32,
+}
+
+impl EmscriptenGlobals {
+    /// Reserves a region of the shared memory and a slice of the shared
+    /// table for a side module, and returns their bases.
+    ///
+    /// Memory is taken from the start of the sbrk heap, so side modules
+    /// have to be allocated before the main module starts running.
+    /// Returns `None` if the memory or the table can't fit the module.
+    pub fn allocate_side_module(&mut self, info: DylinkInfo) -> Option<SideModuleBases> {
+        let align_up = |value: u32, log2: u32| -> Option<u32> {
+            let mask = 1u32.checked_shl(log2)? - 1;
+            value.checked_add(mask).map(|value| value & !mask)
+        };
+
+        let view = self.memory.view::<u32>();
+        let dynamictop = &view[(self.data.dynamictop_ptr / 4) as usize];
+        let memory_base = align_up(dynamictop.get(), info.memory_align)?;
+        let memory_top = align_memory(memory_base.checked_add(info.memory_size)?);
+        if memory_top as usize > self.memory.size().bytes().0 {
+            return None;
+        }
+
+        let table_base = align_up(self.table.size(), info.table_align)?;
+        let table_top = table_base.checked_add(info.table_size)?;
+        self.reserve_table(table_top).ok()?;
+
+        dynamictop.set(memory_top);
+        Some(SideModuleBases {
+            memory_base,
+            table_base,
+        })
+    }
+}
+
+/// The shared table can't grow to the size that was asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableFullError {
+    pub size: u32,
+    pub requested: u32,
+    pub maximum: Option<u32>,
+}
+
+impl fmt::Display for TableFullError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Can't grow the table from {} to {} slots",
+            self.size, self.requested
+        )?;
+        match self.maximum {
+            Some(maximum) => write!(f, ", its maximum is {}", maximum),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for TableFullError {}
+
+impl EmscriptenGlobals {
+    /// Grows the shared table, if needed, so that it has at least `size`
+    /// slots.
+    ///
+    /// The new slots are null, and calling one traps as an indirect call
+    /// to null. They aren't filled with the `nullFunc_*` imports: an
+    /// indirect call checks the signature of the element, so a slot could
+    /// only serve calls of one signature, and the null function would be
+    /// called with the arguments of the call instead of the table index.
+    pub fn reserve_table(&mut self, size: u32) -> Result<(), TableFullError> {
+        let current = self.table.size();
+        if size <= current {
+            return Ok(());
+        }
+        match self.table.grow(size - current) {
+            Some(_) => Ok(()),
+            None => Err(TableFullError {
+                size: current,
+                requested: size,
+                maximum: self.table.descriptor().maximum,
+            }),
+        }
+    }
+
+    /// Adds `count` slots at the end of the shared table, e.g. for the
+    /// callbacks of an embedder, and returns the index of the first one.
+    pub fn add_table_slots(&mut self, count: u32) -> Result<u32, TableFullError> {
+        let first = self.table.size();
+        let size = first.checked_add(count).ok_or(TableFullError {
+            size: first,
+            requested: u32::max_value(),
+            maximum: self.table.descriptor().maximum,
+        })?;
+        self.reserve_table(size)?;
+        Ok(first)
+    }
+}
+
+/// Generates the imports of a side module that was laid out with
+/// [`EmscriptenGlobals::allocate_side_module`]. The module shares the
+/// memory and the table of the main module.
+pub fn generate_side_module_env(
+    globals: &mut EmscriptenGlobals,
+    bases: SideModuleBases,
+) -> ImportObject {
+    let main_bases = (globals.data.memory_base, globals.data.table_base);
+    globals.data.memory_base = bases.memory_base;
+    globals.data.table_base = bases.table_base;
+    let import_object = generate_emscripten_env(globals);
+    globals.data.memory_base = main_bases.0;
+    globals.data.table_base = main_bases.1;
+    import_object
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DylinkInfo, Reader};
+
+    fn varuint32(bytes: &[u8]) -> Option<u32> {
+        Reader { bytes }.varuint32()
+    }
+
+    #[test]
+    fn should_reject_overflowing_varuint32() {
+        assert_eq!(
+            varuint32(&[0xff, 0xff, 0xff, 0xff, 0x0f]),
+            Some(u32::max_value())
+        );
+        assert_eq!(varuint32(&[0x80, 0x80, 0x80, 0x80, 0x10]), None);
+        assert_eq!(varuint32(&[0xff, 0xff, 0xff, 0xff, 0x7f]), None);
+        assert_eq!(varuint32(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x00]), None);
+    }
+
+    #[test]
+    fn should_parse_dylink_section() {
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        // memory size 1024 (a two byte LEB), alignment 4, table size 3, alignment 0
+        wasm.extend_from_slice(&[0, 12, 6]);
+        wasm.extend_from_slice(b"dylink");
+        wasm.extend_from_slice(&[0x80, 0x08, 4, 3, 0]);
+        assert_eq!(
+            DylinkInfo::parse(&wasm),
+            Some(DylinkInfo {
+                memory_size: 1024,
+                memory_align: 4,
+                table_size: 3,
+                table_align: 0,
+            })
+        );
+        assert_eq!(DylinkInfo::parse(b"\0asm\x01\0\0\0"), None);
+    }
+}
@@ -1,5 +1,7 @@
-use super::process::abort_with_message;
-use libc::{c_int, c_void, memcpy, size_t};
+use super::process::{abort_with_info, abort_with_message, AbortInfo, AbortKind};
+use super::utils::guest_memory_range;
+use libc::c_int;
+use std::ptr;
 use wasmer_runtime_core::vm::Ctx;
 
 /// emscripten: _emscripten_memcpy_big
@@ -8,10 +10,47 @@ pub fn _emscripten_memcpy_big(ctx: &mut Ctx, dest: u32, src: u32, len: u32) -> u
         "emscripten::_emscripten_memcpy_big {}, {}, {}",
         dest, src, len
     );
-    let dest_addr = emscripten_memory_pointer!(ctx.memory(0), dest) as *mut c_void;
-    let src_addr = emscripten_memory_pointer!(ctx.memory(0), src) as *mut c_void;
-    unsafe {
-        memcpy(dest_addr, src_addr, len as size_t);
+    match (
+        guest_memory_range(ctx, dest, len),
+        guest_memory_range(ctx, src, len),
+    ) {
+        (Some(dest_addr), Some(src_addr)) => unsafe {
+            // Overlapping ranges are undefined behavior for the guest's memcpy,
+            // but must not become undefined behavior on the host.
+            if dest.max(src) - dest.min(src) >= len {
+                ptr::copy_nonoverlapping(src_addr, dest_addr, len as usize);
+            } else {
+                ptr::copy(src_addr, dest_addr, len as usize);
+            }
+        },
+        _ => abort_with_message(ctx, "memcpy out of bounds"),
+    }
+    dest
+}
+
+/// emscripten: _memmove
+pub fn _memmove(ctx: &mut Ctx, dest: u32, src: u32, len: u32) -> u32 {
+    debug!("emscripten::_memmove {}, {}, {}", dest, src, len);
+    match (
+        guest_memory_range(ctx, dest, len),
+        guest_memory_range(ctx, src, len),
+    ) {
+        (Some(dest_addr), Some(src_addr)) => unsafe {
+            ptr::copy(src_addr, dest_addr, len as usize);
+        },
+        _ => abort_with_message(ctx, "memmove out of bounds"),
+    }
+    dest
+}
+
+/// emscripten: _memset
+pub fn _memset(ctx: &mut Ctx, dest: u32, value: u32, len: u32) -> u32 {
+    debug!("emscripten::_memset {}, {}, {}", dest, value, len);
+    match guest_memory_range(ctx, dest, len) {
+        Some(dest_addr) => unsafe {
+            ptr::write_bytes(dest_addr, value as u8, len as usize);
+        },
+        None => abort_with_message(ctx, "memset out of bounds"),
     }
     dest
 }
@@ -35,7 +74,14 @@ pub fn enlarge_memory(_ctx: &mut Ctx) -> u32 {
 /// emscripten: abortOnCannotGrowMemory
 pub fn abort_on_cannot_grow_memory(ctx: &mut Ctx) -> u32 {
     debug!("emscripten::abort_on_cannot_grow_memory");
-    abort_with_message(ctx, "Cannot enlarge memory arrays!");
+    abort_with_info(
+        ctx,
+        AbortInfo {
+            kind: AbortKind::CannotGrowMemory,
+            message: Some("Cannot enlarge memory arrays!".to_string()),
+            requested_size: None,
+        },
+    );
     0
 }
 
@@ -0,0 +1,96 @@
+//! The layout an emscripten module was linked for, which emscripten
+//! records in an `emscripten_metadata` custom section: `TOTAL_MEMORY`,
+//! the size of the table, and where the static data ends.
+//!
+//! The section holds a sequence of LEB128 numbers, starting with the
+//! version of the metadata. Minor versions only append numbers, so the
+//! ones this doesn't know about are ignored.
+use crate::capabilities::find_custom_section;
+use crate::linking::Reader;
+
+const SECTION_NAME: &[u8] = b"emscripten_metadata";
+
+/// The major version of the metadata this reads.
+const METADATA_MAJOR: u32 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmscriptenMetadata {
+    /// `TOTAL_MEMORY`, in wasm pages.
+    pub memory_pages: u32,
+    pub table_size: u32,
+    pub global_base: u32,
+    /// Where the `sbrk` heap starts, after the stack.
+    pub dynamic_base: u32,
+    pub dynamictop_ptr: u32,
+    /// Where the static data ends.
+    pub temp_double_ptr: u32,
+}
+
+impl EmscriptenMetadata {
+    /// Returns `None` if `wasm` has no metadata, or metadata of a major
+    /// version this can't read.
+    pub fn parse(wasm: &[u8]) -> Option<Self> {
+        let mut reader = Reader {
+            bytes: find_custom_section(wasm, SECTION_NAME)?,
+        };
+        if reader.varuint32()? != METADATA_MAJOR {
+            return None;
+        }
+        let minor = reader.varuint32()?;
+        let _abi_major = reader.varuint32()?;
+        let _abi_minor = reader.varuint32()?;
+        // Whether the module was built by the LLVM backend, since 0.1.
+        if minor >= 1 {
+            reader.varuint32()?;
+        }
+        Some(EmscriptenMetadata {
+            memory_pages: reader.varuint32()?,
+            table_size: reader.varuint32()?,
+            global_base: reader.varuint32()?,
+            dynamic_base: reader.varuint32()?,
+            dynamictop_ptr: reader.varuint32()?,
+            temp_double_ptr: reader.varuint32()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module_with_metadata(fields: &[u8]) -> Vec<u8> {
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        wasm.push(0);
+        wasm.push((1 + SECTION_NAME.len() + fields.len()) as u8);
+        wasm.push(SECTION_NAME.len() as u8);
+        wasm.extend_from_slice(SECTION_NAME);
+        wasm.extend_from_slice(fields);
+        wasm
+    }
+
+    #[test]
+    fn parses_metadata() {
+        // Version 0.1, ABI 0.0, fastcomp, 256 pages, a table of 10, and
+        // the addresses 1024, 5_246_880, 3008 and 2992.
+        let wasm = module_with_metadata(&[
+            0, 1, 0, 0, 0, 0x80, 0x02, 10, 0x80, 0x08, 0xa0, 0x9f, 0xc0, 0x02, 0xc0, 0x17, 0xb0,
+            0x17, 1,
+        ]);
+        assert_eq!(
+            EmscriptenMetadata::parse(&wasm),
+            Some(EmscriptenMetadata {
+                memory_pages: 256,
+                table_size: 10,
+                global_base: 1024,
+                dynamic_base: 5_246_880,
+                dynamictop_ptr: 3008,
+                temp_double_ptr: 2992,
+            })
+        );
+        assert_eq!(
+            EmscriptenMetadata::parse(&module_with_metadata(&[1, 0])),
+            None
+        );
+        assert_eq!(EmscriptenMetadata::parse(b"\0asm\x01\0\0\0"), None);
+    }
+}
@@ -1,67 +1,147 @@
+//! The `nullFunc_<signature>` imports, which the guest calls with the
+//! table index when it calls a function pointer that doesn't point to a
+//! function of the signature, e.g. `vii` for `void (*)(int, int)`.
+//!
+//! Every signature emscripten can emit is resolved. Each one is given a
+//! slot of [`NullFunc`], which reads the signature back from the name the
+//! calling instance imports it as.
 use super::process::abort_with_message;
-use wasmer_runtime_core::vm::Ctx;
+use std::cell::RefCell;
+use wasmer_runtime_core::{
+    export::Export,
+    import::{self, IsExport, LikeNamespace, Slot, SlotFunc, SLOTS},
+    types::{FuncSig, Type},
+    vm::Ctx,
+    Func,
+};
 
-pub fn nullfunc_i(ctx: &mut Ctx, x: u32) {
-    debug!("emscripten::nullfunc_i {}", x);
-    abort_with_message(ctx, "Invalid function pointer called with signature 'i'. Perhaps this is an invalid value (e.g. caused by calling a virtual method on a NULL pointer)? Or calling a function with an incorrect type, which will fail? (it is worth building your source files with -Werror (warnings are errors), as warnings can indicate undefined behavior which can cause this)");
-}
-
-pub fn nullfunc_ii(ctx: &mut Ctx, x: u32) {
-    debug!("emscripten::nullfunc_ii {}", x);
-    abort_with_message(ctx, "Invalid function pointer called with signature 'ii'. Perhaps this is an invalid value (e.g. caused by calling a virtual method on a NULL pointer)? Or calling a function with an incorrect type, which will fail? (it is worth building your source files with -Werror (warnings are errors), as warnings can indicate undefined behavior which can cause this)");
-}
+/// The `nullFunc_*` imports.
+struct NullFunc;
 
-pub fn nullfunc_iii(ctx: &mut Ctx, x: u32) {
-    debug!("emscripten::nullfunc_iii {}", x);
-    abort_with_message(ctx, "Invalid function pointer called with signature 'iii'. Perhaps this is an invalid value (e.g. caused by calling a virtual method on a NULL pointer)? Or calling a function with an incorrect type, which will fail? (it is worth building your source files with -Werror (warnings are errors), as warnings can indicate undefined behavior which can cause this)");
+impl SlotFunc for NullFunc {
+    fn export<S: Slot>() -> Export {
+        func!(null_func::<S>).to_export()
+    }
 }
 
-pub fn nullfunc_iiii(ctx: &mut Ctx, x: u32) {
-    debug!("emscripten::nullfunc_iiii {}", x);
-    abort_with_message(ctx, "Invalid function pointer called with signature 'iiii'. Perhaps this is an invalid value (e.g. caused by calling a virtual method on a NULL pointer)? Or calling a function with an incorrect type, which will fail? (it is worth building your source files with -Werror (warnings are errors), as warnings can indicate undefined behavior which can cause this)");
-}
+/// How many elements on each side of the called index the diagnostic shows.
+const TABLE_CONTEXT: u32 = 4;
 
-pub fn nullfunc_iiiii(ctx: &mut Ctx, x: u32) {
-    debug!("emscripten::nullfunc_iiiii {}", x);
-    abort_with_message(ctx, "Invalid function pointer called with signature 'iiiii'. Perhaps this is an invalid value (e.g. caused by calling a virtual method on a NULL pointer)? Or calling a function with an incorrect type, which will fail? (it is worth building your source files with -Werror (warnings are errors), as warnings can indicate undefined behavior which can cause this)");
+/// `signature` in the letters emscripten names signatures with.
+fn signature_letters(signature: &FuncSig) -> String {
+    let letter = |ty: &Type| match ty {
+        Type::I32 => 'i',
+        Type::I64 => 'j',
+        Type::F32 => 'f',
+        Type::F64 => 'd',
+    };
+    let mut letters = String::new();
+    match signature.returns().first() {
+        Some(ty) => letters.push(letter(ty)),
+        None => letters.push('v'),
+    }
+    letters.extend(signature.params().iter().map(letter));
+    letters
 }
 
-pub fn nullfunc_iiiiii(ctx: &mut Ctx, x: u32) {
-    debug!("emscripten::nullfunc_iiiiii {}", x);
-    abort_with_message(ctx, "Invalid function pointer called with signature 'iiiiii'. Perhaps this is an invalid value (e.g. caused by calling a virtual method on a NULL pointer)? Or calling a function with an incorrect type, which will fail? (it is worth building your source files with -Werror (warnings are errors), as warnings can indicate undefined behavior which can cause this)");
+/// What the table holds at and around `index`.
+fn describe_table(ctx: &Ctx, index: u32) -> String {
+    let table = match ctx.get_table(0) {
+        Some(table) => table,
+        None => return "There is no table.".to_string(),
+    };
+    let size = table.size();
+    let describe = |index: u32| match table.signature(index) {
+        Some(signature) => format!("'{}'", signature_letters(&signature)),
+        None => "null".to_string(),
+    };
+    if index >= size {
+        return format!(
+            "The index is past the end of the table of {} elements.",
+            size
+        );
+    }
+    let start = index.saturating_sub(TABLE_CONTEXT);
+    let end = size.min(index.saturating_add(TABLE_CONTEXT + 1));
+    let elements: Vec<_> = (start..end)
+        .map(|i| {
+            format!(
+                "{}{}: {}",
+                if i == index { "*" } else { "" },
+                i,
+                describe(i)
+            )
+        })
+        .collect();
+    format!(
+        "The element at {} has signature {}. Table[{}..{}] of {}: {}",
+        index,
+        describe(index),
+        start,
+        end,
+        size,
+        elements.join(", ")
+    )
 }
 
-pub fn nullfunc_v(ctx: &mut Ctx, x: u32) {
-    debug!("emscripten::nullfunc_v {}", x);
-    abort_with_message(ctx, "Invalid function pointer called with signature 'v'. Perhaps this is an invalid value (e.g. caused by calling a virtual method on a NULL pointer)? Or calling a function with an incorrect type, which will fail? (it is worth building your source files with -Werror (warnings are errors), as warnings can indicate undefined behavior which can cause this)");
+fn abort_null_func(ctx: &mut Ctx, signature: &str, index: u32) {
+    let table = describe_table(ctx, index);
+    abort_with_message(ctx, &format!("Invalid function pointer {} called with signature '{}'. Perhaps this is an invalid value (e.g. caused by calling a virtual method on a NULL pointer)? Or calling a function with an incorrect type, which will fail? (it is worth building your source files with -Werror (warnings are errors), as warnings can indicate undefined behavior which can cause this)\n{}", index, signature, table));
 }
 
-pub fn nullfunc_vi(ctx: &mut Ctx, x: u32) {
-    debug!("emscripten::nullfunc_vi {}", x);
-    abort_with_message(ctx, "Invalid function pointer called with signature 'vi'. Perhaps this is an invalid value (e.g. caused by calling a virtual method on a NULL pointer)? Or calling a function with an incorrect type, which will fail? (it is worth building your source files with -Werror (warnings are errors), as warnings can indicate undefined behavior which can cause this)");
+fn null_func<S: Slot>(ctx: &mut Ctx, index: u32) {
+    let signature = match import::called_as::<NullFunc, S>(ctx) {
+        Some((_, name)) => name.trim_start_matches("nullFunc_").to_string(),
+        None => "unknown".to_string(),
+    };
+    debug!("emscripten::nullfunc_{} {}", signature, index);
+    abort_null_func(ctx, &signature, index);
 }
 
-pub fn nullfunc_vii(ctx: &mut Ctx, x: u32) {
-    debug!("emscripten::nullfunc_vii {}", x);
-    abort_with_message(ctx, "Invalid function pointer called with signature 'vii'. Perhaps this is an invalid value (e.g. caused by calling a virtual method on a NULL pointer)? Or calling a function with an incorrect type, which will fail? (it is worth building your source files with -Werror (warnings are errors), as warnings can indicate undefined behavior which can cause this)");
+/// The null function of the signatures that didn't get a slot.
+fn null_func_unknown(ctx: &mut Ctx, index: u32) {
+    debug!("emscripten::nullfunc {}", index);
+    abort_null_func(ctx, "unknown", index);
 }
 
-pub fn nullfunc_viii(ctx: &mut Ctx, x: u32) {
-    debug!("emscripten::nullfunc_viii {}", x);
-    abort_with_message(ctx, "Invalid function pointer called with signature 'viii'. Perhaps this is an invalid value (e.g. caused by calling a virtual method on a NULL pointer)? Or calling a function with an incorrect type, which will fail? (it is worth building your source files with -Werror (warnings are errors), as warnings can indicate undefined behavior which can cause this)");
+/// The null function of `signature`, which is given the next free slot
+/// of `signatures`, the signatures that were given a slot in order.
+fn null_func_export(signatures: &mut Vec<String>, signature: &str) -> Export {
+    let index = match signatures.iter().position(|known| known == signature) {
+        Some(index) => index,
+        None if signatures.len() < SLOTS => {
+            signatures.push(signature.to_string());
+            signatures.len() - 1
+        }
+        None => return func!(null_func_unknown).to_export(),
+    };
+    import::slot_export::<NullFunc>(index).unwrap()
 }
 
-pub fn nullfunc_viiii(ctx: &mut Ctx, x: u32) {
-    debug!("emscripten::nullfunc_viiii {}", x);
-    abort_with_message(ctx, "Invalid function pointer called with signature 'viiii'. Perhaps this is an invalid value (e.g. caused by calling a virtual method on a NULL pointer)? Or calling a function with an incorrect type, which will fail? (it is worth building your source files with -Werror (warnings are errors), as warnings can indicate undefined behavior which can cause this)");
+/// Wraps the `env` namespace to resolve every `nullFunc_*` import.
+pub(crate) struct NullFuncs {
+    env: Box<dyn LikeNamespace>,
+    signatures: RefCell<Vec<String>>,
 }
 
-pub fn nullfunc_viiiii(ctx: &mut Ctx, _x: u32) {
-    debug!("emscripten::nullfunc_viiiii");
-    abort_with_message(ctx, "Invalid function pointer called with signature 'viiiii'. Perhaps this is an invalid value (e.g. caused by calling a virtual method on a NULL pointer)? Or calling a function with an incorrect type, which will fail? (it is worth building your source files with -Werror (warnings are errors), as warnings can indicate undefined behavior which can cause this)");
+impl NullFuncs {
+    pub(crate) fn new(env: Box<dyn LikeNamespace>) -> Self {
+        NullFuncs {
+            env,
+            signatures: RefCell::new(Vec::new()),
+        }
+    }
 }
 
-pub fn nullfunc_viiiiii(ctx: &mut Ctx, _x: u32) {
-    debug!("emscripten::nullfunc_viiiiii");
-    abort_with_message(ctx, "Invalid function pointer called with signature 'viiiiii'. Perhaps this is an invalid value (e.g. caused by calling a virtual method on a NULL pointer)? Or calling a function with an incorrect type, which will fail? (it is worth building your source files with -Werror (warnings are errors), as warnings can indicate undefined behavior which can cause this)");
+impl LikeNamespace for NullFuncs {
+    fn get_export(&self, name: &str) -> Option<Export> {
+        if name.starts_with("nullFunc_") {
+            let mut signatures = self.signatures.borrow_mut();
+            return Some(null_func_export(
+                &mut signatures,
+                &name["nullFunc_".len()..],
+            ));
+        }
+        self.env.get_export(name)
+    }
 }
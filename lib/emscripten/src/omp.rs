@@ -0,0 +1,295 @@
+//! The OpenMP runtime, for guests built with `-fopenmp`.
+//!
+//! The runtime has no threads to give the guest, so every parallel region
+//! runs on a team of one: the outlined body of a region is called once,
+//! by the main thread, and each worksharing loop gives it the whole
+//! iteration space. The results are those of `OMP_NUM_THREADS=1`.
+use crate::cleanup;
+use crate::clock::{get_clock, ClockId};
+use crate::env::{call_malloc, get_emscripten_data};
+use crate::process::abort_with_message;
+use std::mem;
+use wasmer_runtime_core::vm::{Anyfunc, Ctx};
+
+/// The most arguments `__kmpc_fork_call` passes on to a region.
+const MAX_SHARED: usize = 8;
+
+#[derive(Debug, Default)]
+pub(crate) struct OpenMp {
+    /// The guest memory of the thread IDs outlined regions are passed
+    /// pointers to, both 0.
+    thread_ids: u32,
+    /// How deeply parallel regions are nested.
+    depth: u32,
+}
+
+fn state(ctx: &mut Ctx) -> Option<&mut OpenMp> {
+    if ctx.data.is_null() {
+        return None;
+    }
+    Some(&mut get_emscripten_data(ctx).openmp)
+}
+
+pub fn _omp_get_num_threads(_ctx: &mut Ctx) -> i32 {
+    debug!("emscripten::_omp_get_num_threads");
+    1
+}
+
+pub fn _omp_get_max_threads(_ctx: &mut Ctx) -> i32 {
+    debug!("emscripten::_omp_get_max_threads");
+    1
+}
+
+pub fn _omp_get_num_procs(_ctx: &mut Ctx) -> i32 {
+    debug!("emscripten::_omp_get_num_procs");
+    1
+}
+
+pub fn _omp_get_thread_num(_ctx: &mut Ctx) -> i32 {
+    debug!("emscripten::_omp_get_thread_num");
+    0
+}
+
+/// The team of later regions stays a team of one.
+pub fn _omp_set_num_threads(_ctx: &mut Ctx, num_threads: i32) {
+    debug!("emscripten::_omp_set_num_threads {}", num_threads);
+}
+
+pub fn _omp_in_parallel(ctx: &mut Ctx) -> i32 {
+    debug!("emscripten::_omp_in_parallel");
+    state(ctx).map_or(0, |omp| (omp.depth > 0) as i32)
+}
+
+pub fn _omp_get_wtime(ctx: &mut Ctx) -> f64 {
+    debug!("emscripten::_omp_get_wtime");
+    get_clock(ctx).time(ClockId::Monotonic).map_or(0.0, |time| {
+        time.as_secs() as f64 + f64::from(time.subsec_nanos()) / 1e9
+    })
+}
+
+pub fn _omp_get_wtick(_ctx: &mut Ctx) -> f64 {
+    debug!("emscripten::_omp_get_wtick");
+    1e-9
+}
+
+pub fn ___kmpc_global_thread_num(_ctx: &mut Ctx, _loc: u32) -> i32 {
+    debug!("emscripten::___kmpc_global_thread_num");
+    0
+}
+
+/// Run the outlined region `microtask` with the `argc` arguments in the
+/// varargs at `args`, as `microtask(&gtid, &btid, args...)`.
+pub fn ___kmpc_fork_call(ctx: &mut Ctx, _loc: u32, argc: i32, microtask: u32, args: u32) {
+    debug!("emscripten::___kmpc_fork_call {} {}", argc, microtask);
+    if argc < 0 || argc as usize > MAX_SHARED {
+        return abort_with_message(ctx, "too many variables shared by an OpenMP region");
+    }
+    let shared: Option<Vec<i32>> = {
+        let view = ctx.memory(0).view::<u32>();
+        let start = (args / 4) as usize;
+        view.get(start..start + argc as usize)
+            .map(|cells| cells.iter().map(|cell| cell.get() as i32).collect())
+    };
+    let shared = match shared {
+        Some(shared) => shared,
+        None => {
+            return abort_with_message(ctx, "the arguments of an OpenMP region are out of bounds")
+        }
+    };
+    let thread_ids = match thread_ids(ctx) {
+        Ok(thread_ids) => thread_ids,
+        Err(message) => return abort_with_message(ctx, message),
+    };
+
+    let mut params = vec![thread_ids as i32, thread_ids as i32 + 4];
+    params.extend(shared);
+    let omp: *mut OpenMp = match state(ctx) {
+        Some(omp) => omp,
+        None => return abort_with_message(ctx, "OpenMP used before the runtime was set up"),
+    };
+    unsafe { (*omp).depth += 1 };
+    // The region is left even if the microtask longjmps out of it.
+    let _leave = cleanup::defer(ctx, move || unsafe { (*omp).depth -= 1 });
+    call_table(ctx, microtask, &params);
+}
+
+/// The guest memory of the thread IDs, allocated on first use.
+fn thread_ids(ctx: &mut Ctx) -> Result<u32, &'static str> {
+    let omp = state(ctx).ok_or("OpenMP used before the runtime was set up")?;
+    if omp.thread_ids != 0 {
+        return Ok(omp.thread_ids);
+    }
+    let thread_ids = call_malloc(ctx, 8);
+    {
+        let view = ctx.memory(0).view::<u32>();
+        let start = (thread_ids / 4) as usize;
+        let cells = view
+            .get(start..start + 2)
+            .filter(|_| thread_ids != 0)
+            .ok_or("out of memory for the OpenMP thread IDs")?;
+        for cell in cells {
+            cell.set(0);
+        }
+    }
+    if let Some(omp) = state(ctx) {
+        omp.thread_ids = thread_ids;
+    }
+    Ok(thread_ids)
+}
+
+/// Call the function at `index` in the table with `params`, all `i32`.
+fn call_table(ctx: &mut Ctx, index: u32, params: &[i32]) {
+    let entry = ctx
+        .vm_table(0)
+        .filter(|table| (index as usize) < table.count)
+        .map(|table| unsafe { &*(table.base as *const Anyfunc).add(index as usize) })
+        .map(|anyfunc| (anyfunc.func as *const (), anyfunc.ctx));
+    let (f, vmctx) = match entry {
+        Some((f, vmctx)) if !f.is_null() => (f, vmctx),
+        _ => return abort_with_message(ctx, "an OpenMP region isn't in the table"),
+    };
+    macro_rules! call {
+        ($($param:ident),*) => {{
+            let f: extern "C" fn(*mut Ctx $(, call!(@i32 $param))*) = unsafe { mem::transmute(f) };
+            f(vmctx $(, $param)*)
+        }};
+        (@i32 $param:ident) => { i32 };
+    }
+    match *params {
+        [a, b] => call!(a, b),
+        [a, b, c] => call!(a, b, c),
+        [a, b, c, d] => call!(a, b, c, d),
+        [a, b, c, d, e] => call!(a, b, c, d, e),
+        [a, b, c, d, e, g] => call!(a, b, c, d, e, g),
+        [a, b, c, d, e, g, h] => call!(a, b, c, d, e, g, h),
+        [a, b, c, d, e, g, h, i] => call!(a, b, c, d, e, g, h, i),
+        [a, b, c, d, e, g, h, i, j] => call!(a, b, c, d, e, g, h, i, j),
+        [a, b, c, d, e, g, h, i, j, k] => call!(a, b, c, d, e, g, h, i, j, k),
+        _ => unreachable!(),
+    }
+}
+
+/// The team is of one, so the one thread gets the whole iteration space
+/// of a statically scheduled loop: its bounds stay as they are.
+#[allow(clippy::too_many_arguments)]
+pub fn ___kmpc_for_static_init_4(
+    ctx: &mut Ctx,
+    _loc: u32,
+    _gtid: i32,
+    _schedule: i32,
+    last_iteration: u32,
+    lower: u32,
+    upper: u32,
+    stride: u32,
+    _increment: i32,
+    _chunk: i32,
+) {
+    debug!("emscripten::___kmpc_for_static_init_4");
+    let view = ctx.memory(0).view::<u32>();
+    let (lower, upper) = (
+        view[(lower / 4) as usize].get(),
+        view[(upper / 4) as usize].get(),
+    );
+    view[(last_iteration / 4) as usize].set(1);
+    view[(stride / 4) as usize].set(upper.wrapping_sub(lower).wrapping_add(1));
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn ___kmpc_for_static_init_4u(
+    ctx: &mut Ctx,
+    loc: u32,
+    gtid: i32,
+    schedule: i32,
+    last_iteration: u32,
+    lower: u32,
+    upper: u32,
+    stride: u32,
+    increment: i32,
+    chunk: i32,
+) {
+    ___kmpc_for_static_init_4(
+        ctx,
+        loc,
+        gtid,
+        schedule,
+        last_iteration,
+        lower,
+        upper,
+        stride,
+        increment,
+        chunk,
+    )
+}
+
+pub fn ___kmpc_for_static_fini(_ctx: &mut Ctx, _loc: u32, _gtid: i32) {
+    debug!("emscripten::___kmpc_for_static_fini");
+}
+
+pub fn ___kmpc_push_num_threads(_ctx: &mut Ctx, _loc: u32, _gtid: i32, num_threads: i32) {
+    debug!("emscripten::___kmpc_push_num_threads {}", num_threads);
+}
+
+pub fn ___kmpc_barrier(_ctx: &mut Ctx, _loc: u32, _gtid: i32) {
+    debug!("emscripten::___kmpc_barrier");
+}
+
+pub fn ___kmpc_critical(_ctx: &mut Ctx, _loc: u32, _gtid: i32, _lock: u32) {
+    debug!("emscripten::___kmpc_critical");
+}
+
+pub fn ___kmpc_end_critical(_ctx: &mut Ctx, _loc: u32, _gtid: i32, _lock: u32) {
+    debug!("emscripten::___kmpc_end_critical");
+}
+
+/// The one thread is both the master and the one to run single blocks.
+pub fn ___kmpc_master(_ctx: &mut Ctx, _loc: u32, _gtid: i32) -> i32 {
+    debug!("emscripten::___kmpc_master");
+    1
+}
+
+pub fn ___kmpc_end_master(_ctx: &mut Ctx, _loc: u32, _gtid: i32) {
+    debug!("emscripten::___kmpc_end_master");
+}
+
+pub fn ___kmpc_single(_ctx: &mut Ctx, _loc: u32, _gtid: i32) -> i32 {
+    debug!("emscripten::___kmpc_single");
+    1
+}
+
+pub fn ___kmpc_end_single(_ctx: &mut Ctx, _loc: u32, _gtid: i32) {
+    debug!("emscripten::___kmpc_end_single");
+}
+
+/// 1 tells the thread to combine its partial result itself, which is the
+/// final one as there's no other thread.
+pub fn ___kmpc_reduce_nowait(
+    _ctx: &mut Ctx,
+    _loc: u32,
+    _gtid: i32,
+    _num_vars: i32,
+    _size: i32,
+    _data: u32,
+    _reduce: u32,
+    _lock: u32,
+) -> i32 {
+    debug!("emscripten::___kmpc_reduce_nowait");
+    1
+}
+
+pub fn ___kmpc_end_reduce_nowait(_ctx: &mut Ctx, _loc: u32, _gtid: i32, _lock: u32) {
+    debug!("emscripten::___kmpc_end_reduce_nowait");
+}
+
+pub fn ___kmpc_serialized_parallel(ctx: &mut Ctx, _loc: u32, _gtid: i32) {
+    debug!("emscripten::___kmpc_serialized_parallel");
+    if let Some(omp) = state(ctx) {
+        omp.depth += 1;
+    }
+}
+
+pub fn ___kmpc_end_serialized_parallel(ctx: &mut Ctx, _loc: u32, _gtid: i32) {
+    debug!("emscripten::___kmpc_end_serialized_parallel");
+    if let Some(omp) = state(ctx) {
+        omp.depth = omp.depth.saturating_sub(1);
+    }
+}
@@ -1,4 +1,5 @@
-use libc::{abort, c_char, c_int, exit, EAGAIN};
+use crate::errno::Errno;
+use libc::{abort, c_char, c_int, exit};
 
 #[cfg(not(target_os = "windows"))]
 use libc::pid_t;
@@ -6,17 +7,127 @@ use libc::pid_t;
 #[cfg(target_os = "windows")]
 type pid_t = c_int;
 
+use crate::env::get_emscripten_data;
+use crate::rust_panic;
+use crate::stdio::flush_stdout;
+use crate::vfs::{self, describe_open_fds};
 use std::ffi::CStr;
-use wasmer_runtime_core::vm::Ctx;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use wasmer_runtime_core::{coredump::CoreDump, vm::Ctx};
+
+/// Why the guest aborted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbortKind {
+    /// `abort` was called, or the runtime found an unrecoverable error.
+    Abort,
+    /// The guest needed more memory than the memory can grow to.
+    CannotGrowMemory,
+    /// The guest ran out of stack.
+    StackOverflow,
+}
+
+/// What the abort hook of `EmscriptenConfig` is told about an abort.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbortInfo {
+    pub kind: AbortKind,
+    /// The message of the guest or of the runtime, if there's one.
+    pub message: Option<String>,
+    /// The number of bytes whose allocation failed, if it's known.
+    pub requested_size: Option<u32>,
+}
+
+/// A function called with the reason of an abort, before the process aborts.
+#[derive(Clone)]
+pub struct AbortHook(Arc<dyn Fn(&AbortInfo) + Send + Sync>);
+
+impl AbortHook {
+    pub fn new<F: Fn(&AbortInfo) + Send + Sync + 'static>(hook: F) -> Self {
+        AbortHook(Arc::new(hook))
+    }
+}
+
+impl fmt::Debug for AbortHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("AbortHook")
+    }
+}
 
 pub fn abort_with_message(ctx: &mut Ctx, message: &str) {
     debug!("emscripten::abort_with_message");
-    println!("{}", message);
+    abort_with_info(
+        ctx,
+        AbortInfo {
+            kind: AbortKind::Abort,
+            message: Some(message.to_string()),
+            requested_size: None,
+        },
+    );
+}
+
+pub fn abort_with_info(ctx: &mut Ctx, info: AbortInfo) {
+    debug!("emscripten::abort_with_info {:?}", info);
+    flush_stdout(ctx);
+    // The runtime can abort before `run_emscripten_instance` sets up the data.
+    let (hook, core_dump_path) = if ctx.data.is_null() {
+        (None, None)
+    } else {
+        let config = &get_emscripten_data(ctx).config;
+        (config.abort_hook.clone(), config.core_dump.clone())
+    };
+    if let Some(path) = core_dump_path {
+        let reason = info.message.as_ref().map_or("abort", String::as_str);
+        write_core_dump(ctx, reason, &path);
+    }
+    match hook {
+        Some(AbortHook(hook)) => hook(&info),
+        None => {
+            if let Some(message) = &info.message {
+                log::error!("{}", message);
+            }
+        }
+    }
     _abort(ctx);
 }
 
-pub fn _abort(_ctx: &mut Ctx) {
+/// A core dump of the guest, with the files it has open as notes.
+pub fn core_dump(ctx: &mut Ctx, reason: &str) -> CoreDump {
+    let mut dump = CoreDump::capture(ctx, reason);
+    dump.notes = describe_open_fds(ctx);
+    dump
+}
+
+/// A snapshot of the guest, paused between calls, which `thaw` restores
+/// into a new instance of the same module, possibly on another machine.
+///
+/// The state of the filesystem of the guest is carried in the notes.
+pub fn freeze(ctx: &mut Ctx) -> CoreDump {
+    let mut dump = CoreDump::capture(ctx, "frozen");
+    dump.notes = vfs::freeze(ctx);
+    dump
+}
+
+/// Restore a snapshot taken by `freeze`.
+pub fn thaw(ctx: &mut Ctx, dump: &CoreDump) -> io::Result<()> {
+    dump.restore(ctx)?;
+    vfs::thaw(ctx, &dump.notes);
+    Ok(())
+}
+
+pub(crate) fn write_core_dump(ctx: &mut Ctx, reason: &str, path: &Path) {
+    if let Err(err) = core_dump(ctx, reason).save(path) {
+        log::warn!("Can't write the core dump {}: {}", path.display(), err);
+    }
+}
+
+pub fn _abort(ctx: &mut Ctx) {
     debug!("emscripten::_abort");
+    // Rust guests abort once std has written where they panicked.
+    if let Some(panic) = rust_panic::take(ctx) {
+        abort_with_message(ctx, &panic.to_string());
+    }
     unsafe {
         abort();
     }
@@ -40,9 +151,10 @@ pub fn _execve(_ctx: &mut Ctx, _one: i32, _two: i32, _three: i32) -> i32 {
 }
 
 #[allow(unreachable_code)]
-pub fn _exit(_ctx: &mut Ctx, status: c_int) {
+pub fn _exit(ctx: &mut Ctx, status: c_int) {
     // -> !
     debug!("emscripten::_exit {}", status);
+    flush_stdout(ctx);
     unsafe { exit(status) }
 }
 
@@ -112,11 +224,6 @@ pub fn _setgroups(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
     -1
 }
 
-pub fn _setitimer(_ctx: &mut Ctx, _one: i32, _two: i32, _three: i32) -> i32 {
-    debug!("emscripten::_setitimer");
-    -1
-}
-
 pub fn _usleep(_ctx: &mut Ctx, _one: i32) -> i32 {
     debug!("emscripten::_usleep");
     -1
@@ -132,12 +239,18 @@ pub fn _waitpid(_ctx: &mut Ctx, _one: i32, _two: i32, _three: i32) -> i32 {
     -1
 }
 
-pub fn abort_stack_overflow(ctx: &mut Ctx, _what: c_int) {
-    debug!("emscripten::abort_stack_overflow");
-    // TODO: Message incomplete. Need to finish em runtime data first
-    abort_with_message(
+pub fn abort_stack_overflow(ctx: &mut Ctx, allocation: c_int) {
+    debug!("emscripten::abort_stack_overflow {}", allocation);
+    abort_with_info(
         ctx,
-        "Stack overflow! Attempted to allocate some bytes on the stack",
+        AbortInfo {
+            kind: AbortKind::StackOverflow,
+            message: Some(format!(
+                "Stack overflow! Attempted to allocate {} bytes on the stack",
+                allocation
+            )),
+            requested_size: Some(allocation as u32),
+        },
     );
 }
 
@@ -150,7 +263,7 @@ pub fn _system(_ctx: &mut Ctx, _one: i32) -> c_int {
     debug!("emscripten::_system");
     // TODO: May need to change this Em impl to a working version
     eprintln!("Can't call external programs");
-    return EAGAIN;
+    return Errno::EAGAIN.to_emscripten();
 }
 
 pub fn _popen(_ctx: &mut Ctx, _one: i32, _two: i32) -> c_int {
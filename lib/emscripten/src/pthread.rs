@@ -0,0 +1,113 @@
+//! Thread-specific data, for a guest that only ever has its main thread.
+//!
+//! The runtime has no threads to give the guest, so its thread-local
+//! variables are plain statics and the keys of `pthread_key_create` hold
+//! the values of the main thread alone. Destructors are recorded but never
+//! run, as the main thread never exits through `pthread_exit`.
+use crate::env::get_emscripten_data;
+use crate::errno::Errno;
+use std::collections::HashMap;
+use wasmer_runtime_core::vm::Ctx;
+
+/// `PTHREAD_KEYS_MAX` of musl.
+const KEYS_MAX: usize = 128;
+
+/// The ID of the main thread, which `pthread_self` returns.
+const MAIN_THREAD: u32 = 1;
+
+#[derive(Debug, Default)]
+pub(crate) struct ThreadSpecific {
+    /// The value and destructor of each key.
+    keys: HashMap<u32, (u32, u32)>,
+    next_key: u32,
+}
+
+fn errno(errno: Errno) -> i32 {
+    errno.to_emscripten()
+}
+
+pub fn _pthread_self(_ctx: &mut Ctx) -> u32 {
+    debug!("emscripten::_pthread_self");
+    MAIN_THREAD
+}
+
+pub fn _pthread_equal(_ctx: &mut Ctx, a: u32, b: u32) -> i32 {
+    debug!("emscripten::_pthread_equal");
+    (a == b) as i32
+}
+
+/// Create a key and write it to `key_ptr`.
+pub fn _pthread_key_create(ctx: &mut Ctx, key_ptr: u32, destructor: u32) -> i32 {
+    debug!("emscripten::_pthread_key_create");
+    if ctx.data.is_null() {
+        return errno(Errno::EAGAIN);
+    }
+    let tsd = &mut get_emscripten_data(ctx).thread_specific;
+    if tsd.keys.len() >= KEYS_MAX {
+        return errno(Errno::EAGAIN);
+    }
+    // Keys start at 1, so a zeroed key is never a valid one.
+    tsd.next_key += 1;
+    let key = tsd.next_key;
+    tsd.keys.insert(key, (0, destructor));
+    match ctx.memory(0).view::<u32>().get((key_ptr / 4) as usize) {
+        Some(cell) => {
+            cell.set(key);
+            0
+        }
+        None => errno(Errno::EFAULT),
+    }
+}
+
+pub fn _pthread_key_delete(ctx: &mut Ctx, key: u32) -> i32 {
+    debug!("emscripten::_pthread_key_delete {}", key);
+    if ctx.data.is_null() {
+        return errno(Errno::EINVAL);
+    }
+    match get_emscripten_data(ctx).thread_specific.keys.remove(&key) {
+        Some(_) => 0,
+        None => errno(Errno::EINVAL),
+    }
+}
+
+pub fn _pthread_getspecific(ctx: &mut Ctx, key: u32) -> u32 {
+    debug!("emscripten::_pthread_getspecific {}", key);
+    if ctx.data.is_null() {
+        return 0;
+    }
+    let tsd = &get_emscripten_data(ctx).thread_specific;
+    tsd.keys.get(&key).map_or(0, |&(value, _)| value)
+}
+
+pub fn _pthread_setspecific(ctx: &mut Ctx, key: u32, value: u32) -> i32 {
+    debug!("emscripten::_pthread_setspecific {} {}", key, value);
+    if ctx.data.is_null() {
+        return errno(Errno::EINVAL);
+    }
+    match get_emscripten_data(ctx).thread_specific.keys.get_mut(&key) {
+        Some(entry) => {
+            entry.0 = value;
+            0
+        }
+        None => errno(Errno::EINVAL),
+    }
+}
+
+/// Call `func` unless the `pthread_once_t` at `once_ptr` says it was.
+pub fn _pthread_once(ctx: &mut Ctx, once_ptr: u32, func: u32) -> i32 {
+    debug!("emscripten::_pthread_once");
+    let done = match ctx.memory(0).view::<u32>().get((once_ptr / 4) as usize) {
+        Some(cell) => cell.replace(1) != 0,
+        None => return errno(Errno::EINVAL),
+    };
+    if done || ctx.data.is_null() {
+        return 0;
+    }
+    match &get_emscripten_data(ctx).dyn_call_v {
+        Some(dyn_call_v) => {
+            dyn_call_v.call(func as i32).unwrap();
+            0
+        }
+        None => errno(Errno::ENOSYS),
+    }
+}
@@ -0,0 +1,121 @@
+//! The panics of guests compiled from Rust, whose message std writes to
+//! stderr before the guest aborts, so the error the host gets says where
+//! the guest panicked rather than only that it aborted or trapped.
+use crate::env::get_emscripten_data;
+use std::fmt;
+use wasmer_runtime_core::vm::Ctx;
+
+/// Longer lines of stderr are cut, they can't be panic messages anyway.
+const MAX_LINE: usize = 4096;
+
+/// A panic of a Rust guest, as std reported it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RustPanic {
+    thread: String,
+    message: String,
+    /// Where the panic happened, as `file:line:column`.
+    location: String,
+}
+
+impl fmt::Display for RustPanic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "thread '{}' panicked at {}: {}",
+            self.thread, self.location, self.message
+        )
+    }
+}
+
+/// Reads the stderr of the guest for panic messages.
+#[derive(Debug, Default)]
+pub(crate) struct PanicWatcher {
+    line: Vec<u8>,
+    /// The thread and location of a panic whose message is on the next
+    /// line, as newer versions of std write it.
+    header: Option<(String, String)>,
+    panic: Option<RustPanic>,
+}
+
+impl PanicWatcher {
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if byte == b'\n' {
+                let line = String::from_utf8_lossy(&self.line).into_owned();
+                self.line.clear();
+                self.end_line(&line);
+            } else if self.line.len() < MAX_LINE {
+                self.line.push(byte);
+            }
+        }
+    }
+
+    /// std writes either of:
+    ///
+    /// ```text
+    /// thread 'main' panicked at 'the message', src/main.rs:10:5
+    /// thread 'main' panicked at src/main.rs:10:5:
+    /// the message
+    /// ```
+    fn end_line(&mut self, line: &str) {
+        if let Some((thread, location)) = self.header.take() {
+            self.panic = Some(RustPanic {
+                thread,
+                message: line.to_string(),
+                location,
+            });
+            return;
+        }
+
+        const PREFIX: &str = "thread '";
+        const PANICKED_AT: &str = "' panicked at ";
+        if !line.starts_with(PREFIX) {
+            return;
+        }
+        let (thread, rest) = match line.find(PANICKED_AT) {
+            Some(end) => (&line[PREFIX.len()..end], &line[end + PANICKED_AT.len()..]),
+            None => return,
+        };
+        if rest.starts_with('\'') {
+            if let Some(end) = rest.rfind("', ") {
+                self.panic = Some(RustPanic {
+                    thread: thread.to_string(),
+                    message: rest[1..end].to_string(),
+                    location: rest[end + 3..].to_string(),
+                });
+            }
+        } else if rest.ends_with(':') {
+            self.header = Some((thread.to_string(), rest[..rest.len() - 1].to_string()));
+        }
+    }
+}
+
+/// The last panic of the guest, which is forgotten.
+pub(crate) fn take(ctx: &mut Ctx) -> Option<RustPanic> {
+    if ctx.data.is_null() {
+        return None;
+    }
+    get_emscripten_data(ctx).panic_watcher.panic.take()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PanicWatcher;
+
+    #[test]
+    fn reads_both_formats() {
+        let mut watcher = PanicWatcher::default();
+        watcher.write(b"thread 'main' panicked at 'index out of bounds: the len is 1 but ");
+        watcher.write(b"the index is 2', src/main.rs:10:5\nnote: run with `RUST_BACKTRACE=1`\n");
+        assert_eq!(
+            watcher.panic.take().unwrap().to_string(),
+            "thread 'main' panicked at src/main.rs:10:5: index out of bounds: the len is 1 but the index is 2"
+        );
+
+        watcher.write(b"thread 'main' panicked at src/lib.rs:3:9:\nboom\n");
+        assert_eq!(
+            watcher.panic.take().unwrap().to_string(),
+            "thread 'main' panicked at src/lib.rs:3:9: boom"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Scratch buffers for the syscalls, which the instance keeps from one
+//! syscall to the next, so the hot ones stop allocating once their
+//! buffers have grown to the size the guest needs.
+//!
+//! A syscall takes a buffer, empty, and puts it back when it's done with
+//! it. After each syscall the buffers that grew past `MAX_RETAINED` are
+//! dropped, so one large call doesn't pin its memory for the life of the
+//! instance.
+// Only the unix syscalls have buffers to reuse so far.
+#![cfg_attr(not(unix), allow(dead_code))]
+use crate::env::get_emscripten_data;
+#[cfg(unix)]
+use libc::iovec;
+use std::mem;
+use wasmer_runtime_core::vm::Ctx;
+
+/// The most bytes a buffer keeps between syscalls.
+const MAX_RETAINED: usize = 1 << 20;
+
+#[derive(Default)]
+pub(crate) struct Scratch {
+    /// The host iovecs of `readv` and `writev`.
+    #[cfg(unix)]
+    iovecs: Vec<iovec>,
+    /// The copies of guest memory the syscalls make.
+    bytes: Vec<u8>,
+}
+
+impl Scratch {
+    #[cfg(unix)]
+    fn take_iovecs(&mut self) -> Vec<iovec> {
+        let mut iovecs = mem::replace(&mut self.iovecs, Vec::new());
+        iovecs.clear();
+        iovecs
+    }
+
+    fn take_bytes(&mut self) -> Vec<u8> {
+        let mut bytes = mem::replace(&mut self.bytes, Vec::new());
+        bytes.clear();
+        bytes
+    }
+
+    /// Drop the buffers that grew too large to keep.
+    fn reset(&mut self) {
+        #[cfg(unix)]
+        {
+            if self.iovecs.capacity() * mem::size_of::<iovec>() > MAX_RETAINED {
+                self.iovecs = Vec::new();
+            }
+        }
+        if self.bytes.capacity() > MAX_RETAINED {
+            self.bytes = Vec::new();
+        }
+    }
+}
+
+fn get_scratch(ctx: &mut Ctx) -> Option<&mut Scratch> {
+    // Syscalls can happen before `run_emscripten_instance` sets up the data.
+    if ctx.data.is_null() {
+        return None;
+    }
+    Some(&mut get_emscripten_data(ctx).scratch)
+}
+
+/// An empty iovec array, to give back with `put_iovecs`.
+#[cfg(unix)]
+pub(crate) fn take_iovecs(ctx: &mut Ctx) -> Vec<iovec> {
+    get_scratch(ctx).map_or_else(Vec::new, Scratch::take_iovecs)
+}
+
+#[cfg(unix)]
+pub(crate) fn put_iovecs(ctx: &mut Ctx, iovecs: Vec<iovec>) {
+    if let Some(scratch) = get_scratch(ctx) {
+        scratch.iovecs = iovecs;
+    }
+}
+
+/// An empty byte buffer, to give back with `put_bytes`.
+pub(crate) fn take_bytes(ctx: &mut Ctx) -> Vec<u8> {
+    get_scratch(ctx).map_or_else(Vec::new, Scratch::take_bytes)
+}
+
+pub(crate) fn put_bytes(ctx: &mut Ctx, bytes: Vec<u8>) {
+    if let Some(scratch) = get_scratch(ctx) {
+        scratch.bytes = bytes;
+    }
+}
+
+/// Called after each syscall.
+pub(crate) fn reset(ctx: &mut Ctx) {
+    if let Some(scratch) = get_scratch(ctx) {
+        scratch.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What a syscall that copies `len` bytes of guest memory does.
+    fn copy(scratch: &mut Scratch, len: usize) {
+        let mut bytes = scratch.take_bytes();
+        bytes.resize(len, 0);
+        scratch.bytes = bytes;
+        scratch.reset();
+    }
+
+    #[test]
+    fn reuses_its_buffers() {
+        let mut scratch = Scratch::default();
+        copy(&mut scratch, 4096);
+        let (ptr, capacity) = (scratch.bytes.as_ptr(), scratch.bytes.capacity());
+        for len in (0..4096).step_by(64) {
+            copy(&mut scratch, len);
+        }
+        assert_eq!(scratch.bytes.as_ptr(), ptr);
+        assert_eq!(scratch.bytes.capacity(), capacity);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reuses_its_iovecs() {
+        let mut scratch = Scratch::default();
+        let entry = iovec {
+            iov_base: std::ptr::null_mut(),
+            iov_len: 0,
+        };
+        let mut iovecs = scratch.take_iovecs();
+        iovecs.resize(16, entry);
+        scratch.iovecs = iovecs;
+        let (ptr, capacity) = (scratch.iovecs.as_ptr(), scratch.iovecs.capacity());
+        for _ in 0..100 {
+            let mut iovecs = scratch.take_iovecs();
+            iovecs.extend((0..16).map(|_| entry));
+            scratch.iovecs = iovecs;
+            scratch.reset();
+        }
+        assert_eq!(scratch.iovecs.as_ptr(), ptr);
+        assert_eq!(scratch.iovecs.capacity(), capacity);
+    }
+
+    #[test]
+    fn drops_large_buffers() {
+        let mut scratch = Scratch::default();
+        copy(&mut scratch, MAX_RETAINED + 1);
+        assert_eq!(scratch.bytes.capacity(), 0);
+        copy(&mut scratch, MAX_RETAINED);
+        assert!(scratch.bytes.capacity() >= MAX_RETAINED);
+    }
+}
@@ -1,5 +1,138 @@
 // use super::varargs::VarArgs;
-use wasmer_runtime_core::vm::Ctx;
+use crate::env::get_emscripten_data;
+use crate::stdio::flush_stdout;
+use libc::{c_int, sighandler_t, SIGINT, SIGTERM};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use wasmer_runtime_core::{error::RuntimeError, vm::Ctx};
+
+const SIG_DFL: u32 = 0;
+const SIG_IGN: u32 = 1;
+
+/// The signals the host forwards to the guests.
+const FORWARDED: [c_int; 2] = [SIGINT, SIGTERM];
+
+/// How many of each forwarded signal the host received. Each instance
+/// counts the ones it was delivered, so every instance gets each signal.
+static RECEIVED: [AtomicUsize; 2] = [AtomicUsize::new(0), AtomicUsize::new(0)];
+
+/// Whether the host received a signal that no guest got yet.
+static UNDELIVERED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn host_handler(signum: c_int) {
+    if UNDELIVERED.swap(true, Ordering::SeqCst) {
+        // No guest even got to the previous signal, so stop waiting.
+        unsafe { libc::_exit(128 + signum) }
+    }
+    if let Some(index) = FORWARDED.iter().position(|&forwarded| forwarded == signum) {
+        RECEIVED[index].fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Forward the `SIGINT` and `SIGTERM` the host process receives to the
+/// guest, instead of terminating the host right away.
+///
+/// The guest gets the signal at its next syscall, through the handler it
+/// installed with `signal` or `sigaction`. Without a handler the syscall
+/// traps, which the call into the guest returns as an error. A second
+/// signal before a guest got the first one terminates the host.
+pub fn forward_host_signals() {
+    unsafe {
+        libc::signal(SIGINT, host_handler as sighandler_t);
+        libc::signal(SIGTERM, host_handler as sighandler_t);
+    }
+}
+
+/// The forwarded signals of an instance.
+#[derive(Debug)]
+pub(crate) struct PendingSignals {
+    /// How many of each forwarded signal the host had received when the
+    /// instance last got one, or was set up.
+    seen: [usize; 2],
+}
+
+impl PendingSignals {
+    /// The signals the host receives from now on.
+    pub(crate) fn new() -> Self {
+        PendingSignals {
+            seen: [
+                RECEIVED[0].load(Ordering::SeqCst),
+                RECEIVED[1].load(Ordering::SeqCst),
+            ],
+        }
+    }
+
+    /// The next signal the instance didn't get yet, which it now got.
+    fn take(&mut self) -> Option<u32> {
+        for (index, seen) in self.seen.iter_mut().enumerate() {
+            let received = RECEIVED[index].load(Ordering::SeqCst);
+            if received > *seen {
+                *seen = received;
+                UNDELIVERED.store(false, Ordering::SeqCst);
+                return Some(FORWARDED[index] as u32);
+            }
+        }
+        None
+    }
+}
+
+/// How a signal stops the guest, by trapping out of the syscall it was
+/// delivered at.
+#[derive(Debug)]
+pub enum SignalError {
+    /// The guest has no handler for the signal, whose default action
+    /// terminates it.
+    Killed { signal: u32 },
+    /// The handler of the guest trapped.
+    Handler(RuntimeError),
+}
+
+/// Run the handlers of the guest for the signals the host forwarded to it
+/// and it didn't get yet.
+pub(crate) fn deliver_pending(ctx: &mut Ctx) -> Result<(), SignalError> {
+    if ctx.data.is_null() {
+        return Ok(());
+    }
+    while let Some(signum) = get_emscripten_data(ctx).pending_signals.take() {
+        debug!("emscripten::deliver_pending {}", signum);
+        deliver(ctx, signum)?;
+    }
+    Ok(())
+}
+
+/// Run the handler of the guest for `signum`, or stop it like the default
+/// action of the signal would.
+pub(crate) fn deliver(ctx: &mut Ctx, signum: u32) -> Result<(), SignalError> {
+    let data = get_emscripten_data(ctx);
+    let handler = data
+        .signal_handlers
+        .get(&signum)
+        .cloned()
+        .unwrap_or(SIG_DFL);
+    let handled = match (handler, &data.dyn_call_vi) {
+        (SIG_IGN, _) => true,
+        (SIG_DFL, _) | (_, None) => false,
+        (handler, Some(dyn_call_vi)) => {
+            dyn_call_vi
+                .call(handler as i32, signum as i32)
+                .map_err(SignalError::Handler)?;
+            true
+        }
+    };
+    if !handled {
+        flush_stdout(ctx);
+        return Err(SignalError::Killed { signal: signum });
+    }
+    Ok(())
+}
+
+/// Install `handler` for `signum` and return the previous one.
+fn set_handler(ctx: &mut Ctx, signum: u32, handler: u32) -> u32 {
+    if ctx.data.is_null() {
+        return SIG_DFL;
+    }
+    let handlers = &mut get_emscripten_data(ctx).signal_handlers;
+    handlers.insert(signum, handler).unwrap_or(SIG_DFL)
+}
 
 #[allow(clippy::cast_ptr_alignment)]
 pub fn _sigemptyset(ctx: &mut Ctx, set: u32) -> i32 {
@@ -11,8 +144,27 @@ pub fn _sigemptyset(ctx: &mut Ctx, set: u32) -> i32 {
     0
 }
 
-pub fn _sigaction(_ctx: &mut Ctx, signum: u32, act: u32, oldact: u32) -> i32 {
+/// Only `sa_handler`, the first field of `struct sigaction`, is used.
+#[allow(clippy::cast_ptr_alignment)]
+pub fn _sigaction(ctx: &mut Ctx, signum: u32, act: u32, oldact: u32) -> i32 {
     debug!("emscripten::_sigaction {}, {}, {}", signum, act, oldact);
+    let old_handler = if act == 0 {
+        if ctx.data.is_null() {
+            SIG_DFL
+        } else {
+            let handlers = &get_emscripten_data(ctx).signal_handlers;
+            handlers.get(&signum).cloned().unwrap_or(SIG_DFL)
+        }
+    } else {
+        let act_addr = emscripten_memory_pointer!(ctx.memory(0), act) as *const u32;
+        set_handler(ctx, signum, unsafe { *act_addr })
+    };
+    if oldact != 0 {
+        let oldact_addr = emscripten_memory_pointer!(ctx.memory(0), oldact) as *mut u32;
+        unsafe {
+            *oldact_addr = old_handler;
+        }
+    }
     0
 }
 
@@ -36,7 +188,23 @@ pub fn _sigprocmask(_ctx: &mut Ctx, _one: i32, _two: i32, _three: i32) -> i32 {
     0
 }
 
-pub fn _signal(_ctx: &mut Ctx, sig: u32, _two: i32) -> i32 {
+pub fn _signal(ctx: &mut Ctx, sig: u32, handler: u32) -> u32 {
     debug!("emscripten::_signal ({})", sig);
-    0
+    set_handler(ctx, sig, handler)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_instance_gets_each_forwarded_signal_once() {
+        let mut first = PendingSignals::new();
+        let mut second = PendingSignals::new();
+        RECEIVED[1].fetch_add(1, Ordering::SeqCst);
+        assert_eq!(first.take(), Some(SIGTERM as u32));
+        assert_eq!(first.take(), None);
+        assert_eq!(second.take(), Some(SIGTERM as u32));
+        assert_eq!(PendingSignals::new().take(), None);
+    }
 }
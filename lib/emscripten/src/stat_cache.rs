@@ -0,0 +1,169 @@
+use crate::env::get_emscripten_data;
+use crate::vfs::Dirent;
+use libc::{c_int, stat};
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::rc::Rc;
+use wasmer_runtime_core::vm::Ctx;
+
+/// A path keyed cache of host `stat` results and directory listings.
+///
+/// Keys are paths resolved against the guest's working directory, so they
+/// stay valid when it changes. Entries are only invalidated by syscalls that
+/// go through the emscripten layer; changes made by other host processes are
+/// not observed.
+///
+/// The cached `stat`s are the ones the guest sees, with the emulated
+/// permission bits applied, so changing those must invalidate them too.
+#[derive(Default)]
+pub struct StatCache {
+    entries: HashMap<Vec<u8>, stat>,
+    // The host paths of the absolute guest paths that were looked up, so
+    // that a hit doesn't have to resolve, or allocate, anything.
+    guest_paths: HashMap<Vec<u8>, Vec<u8>>,
+    dirents: HashMap<Vec<u8>, Rc<[Dirent]>>,
+    // Paths of the fds that were opened for writing, so that a write through
+    // the fd can drop the matching entry.
+    writable_fds: HashMap<c_int, Vec<u8>>,
+}
+
+impl StatCache {
+    pub fn get(&self, path: &CStr) -> Option<&stat> {
+        self.entries.get(path.to_bytes())
+    }
+
+    /// The entry for the guest path `path`, as `plain_guest_path` reads it.
+    pub fn get_guest(&self, path: &[u8]) -> Option<&stat> {
+        self.entries.get(self.guest_paths.get(path)?)
+    }
+
+    pub fn insert(&mut self, path: &CStr, stat: stat) {
+        self.entries.insert(path.to_bytes().to_vec(), stat);
+    }
+
+    /// Remember that the guest path `guest` is the host path `path`.
+    pub fn insert_guest(&mut self, guest: &[u8], path: &CStr) {
+        self.guest_paths
+            .insert(guest.to_vec(), path.to_bytes().to_vec());
+    }
+
+    pub fn dirents(&self, path: &CStr) -> Option<Rc<[Dirent]>> {
+        self.dirents.get(path.to_bytes()).cloned()
+    }
+
+    pub fn insert_dirents(&mut self, path: &CStr, dirents: Rc<[Dirent]>) {
+        self.dirents.insert(path.to_bytes().to_vec(), dirents);
+    }
+
+    /// Drops the entries for `path`, after its attributes changed.
+    pub fn invalidate(&mut self, path: &CStr) {
+        self.entries.remove(path.to_bytes());
+        self.dirents.remove(path.to_bytes());
+    }
+
+    /// Drops the entries for `path` and everything below it, and for its
+    /// parent directory, after `path` was created, removed or renamed.
+    pub fn entry_changed(&mut self, path: &CStr) {
+        let path = path.to_bytes();
+        self.entries.retain(|key, _| !is_within(key, path));
+        self.dirents.retain(|key, _| !is_within(key, path));
+        if let Some(parent) = parent(path) {
+            self.entries.remove(parent);
+            self.dirents.remove(parent);
+        }
+    }
+
+    pub fn track_writable_fd(&mut self, fd: c_int, path: &CStr) {
+        self.invalidate(path);
+        self.writable_fds.insert(fd, path.to_bytes().to_vec());
+    }
+
+    /// Called after the guest writes through `fd`.
+    pub fn written(&mut self, fd: c_int) {
+        if let Some(path) = self.writable_fds.get(&fd) {
+            self.entries.remove(path);
+        }
+    }
+
+    pub fn closed(&mut self, fd: c_int) {
+        if let Some(path) = self.writable_fds.remove(&fd) {
+            self.entries.remove(&path);
+        }
+    }
+}
+
+/// Whether `path` is `dir` or a path below it.
+pub(crate) fn is_within(path: &[u8], dir: &[u8]) -> bool {
+    path.starts_with(dir) && (path.len() == dir.len() || path[dir.len()] == b'/')
+}
+
+fn parent(path: &[u8]) -> Option<&[u8]> {
+    match path.iter().rposition(|&byte| byte == b'/')? {
+        0 if path.len() > 1 => Some(&b"/"[..]),
+        0 => None,
+        slash => Some(&path[..slash]),
+    }
+}
+
+/// Returns the instance's stat cache, if it was enabled in the config.
+pub(crate) fn get_stat_cache(ctx: &mut Ctx) -> Option<&mut StatCache> {
+    // Syscalls can be called before `run_emscripten_instance` sets up the data.
+    if ctx.data.is_null() {
+        return None;
+    }
+    get_emscripten_data(ctx).stat_cache.as_mut()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use std::mem;
+
+    fn path(path: &str) -> CString {
+        CString::new(path).unwrap()
+    }
+
+    fn cache_with(paths: &[&str]) -> StatCache {
+        let mut cache = StatCache::default();
+        for &p in paths {
+            cache.insert(&path(p), unsafe { mem::zeroed() });
+            cache.insert_dirents(&path(p), Vec::<Dirent>::new().into());
+        }
+        cache
+    }
+
+    #[test]
+    fn write_invalidates_the_written_file() {
+        let mut cache = cache_with(&["/data", "/data/a"]);
+        cache.track_writable_fd(3, &path("/data/a"));
+        cache.insert(&path("/data/a"), unsafe { mem::zeroed() });
+        cache.written(3);
+        assert!(cache.get(&path("/data/a")).is_none());
+        assert!(cache.get(&path("/data")).is_some());
+        assert!(cache.dirents(&path("/data")).is_some());
+    }
+
+    #[test]
+    fn unlink_invalidates_the_file_and_its_directory() {
+        let mut cache = cache_with(&["/data", "/data/a", "/data/ab"]);
+        cache.insert_guest(b"/data/a", &path("/data/a"));
+        cache.entry_changed(&path("/data/a"));
+        assert!(cache.get_guest(b"/data/a").is_none());
+        assert!(cache.get(&path("/data")).is_none());
+        assert!(cache.dirents(&path("/data")).is_none());
+        assert!(cache.get(&path("/data/ab")).is_some());
+    }
+
+    #[test]
+    fn rename_invalidates_both_paths_and_what_is_below_them() {
+        let mut cache = cache_with(&["/", "/a", "/a/x", "/b", "/b/y", "/c"]);
+        cache.entry_changed(&path("/a"));
+        cache.entry_changed(&path("/b"));
+        for &gone in &["/", "/a", "/a/x", "/b", "/b/y"] {
+            assert!(cache.get(&path(gone)).is_none(), "{}", gone);
+            assert!(cache.dirents(&path(gone)).is_none(), "{}", gone);
+        }
+        assert!(cache.get(&path("/c")).is_some());
+    }
+}
@@ -0,0 +1,94 @@
+//! Statistics of the syscalls of the guest, to find the ones that stall it.
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The calls the guest made to a syscall.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyscallStat {
+    pub calls: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+/// Called with the number of a syscall and its duration, when a call
+/// takes longer than the threshold.
+#[derive(Clone)]
+pub struct SlowSyscallHook(Arc<dyn Fn(i32, Duration) + Send + Sync>);
+
+impl SlowSyscallHook {
+    pub fn new<F: Fn(i32, Duration) + Send + Sync + 'static>(hook: F) -> Self {
+        SlowSyscallHook(Arc::new(hook))
+    }
+}
+
+impl fmt::Debug for SlowSyscallHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SlowSyscallHook")
+    }
+}
+
+/// Collects the statistics of the syscalls by number.
+///
+/// Clones share the statistics, so an embedder can keep one to read them
+/// while or after the guest runs with another.
+#[derive(Debug, Clone, Default)]
+pub struct SyscallStats {
+    stats: Arc<Mutex<HashMap<i32, SyscallStat>>>,
+    slow_threshold: Option<Duration>,
+    slow_hook: Option<SlowSyscallHook>,
+}
+
+impl SyscallStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report the calls that take longer than `threshold` to `hook`, or
+    /// log them as warnings without a hook.
+    pub fn with_slow_threshold(
+        mut self,
+        threshold: Duration,
+        hook: Option<SlowSyscallHook>,
+    ) -> Self {
+        self.slow_threshold = Some(threshold);
+        self.slow_hook = hook;
+        self
+    }
+
+    /// The statistics of the syscall `which`, if the guest called it.
+    pub fn get(&self, which: i32) -> Option<SyscallStat> {
+        self.stats.lock().unwrap().get(&which).cloned()
+    }
+
+    /// The statistics of every syscall the guest called, by number.
+    pub fn all(&self) -> Vec<(i32, SyscallStat)> {
+        let mut all: Vec<_> = self
+            .stats
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(which, stat)| (*which, *stat))
+            .collect();
+        all.sort_by_key(|(which, _)| *which);
+        all
+    }
+
+    pub(crate) fn record(&self, which: i32, elapsed: Duration) {
+        {
+            let mut stats = self.stats.lock().unwrap();
+            let stat = stats.entry(which).or_default();
+            stat.calls += 1;
+            stat.total += elapsed;
+            stat.max = stat.max.max(elapsed);
+        }
+        match self.slow_threshold {
+            Some(threshold) if elapsed > threshold => match &self.slow_hook {
+                Some(SlowSyscallHook(hook)) => hook(which, elapsed),
+                None => log::warn!("syscall {} took {:?}", which, elapsed),
+            },
+            _ => {}
+        }
+    }
+}
@@ -1,7 +1,13 @@
+use super::env::get_emscripten_data;
+use super::errno::Errno;
 use super::file_descriptor::FileDescriptor;
-use libc;
-use std::io::BufReader;
-use std::io::Read;
+use libc::{self, c_int};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use wasmer_runtime_core::vm::Ctx;
 
 // A struct to hold the references to the base stdout and the captured one
 pub struct StdioCapturer {
@@ -83,3 +89,236 @@ impl StdioCapturer {
         Ok((stdout_read, stderr_read))
     }
 }
+
+/// How the writes of the guest to stdout are buffered before they reach
+/// the host's stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Buffering {
+    /// Every write goes through right away.
+    Unbuffered,
+    /// Writes go through once they end a line.
+    Line,
+    /// Writes go through once the buffer is full.
+    Full,
+}
+
+impl Default for Buffering {
+    fn default() -> Self {
+        Buffering::Unbuffered
+    }
+}
+
+/// How much output `Buffering::Full` holds on to.
+const FULL_BUFFER_SIZE: usize = 8192;
+
+/// A copy of everything the guest writes to stdout, e.g. a capture
+/// buffer or a log file.
+#[derive(Clone)]
+pub struct Tee(Arc<Mutex<dyn Write + Send>>);
+
+impl Tee {
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        Tee(Arc::new(Mutex::new(writer)))
+    }
+
+    /// Copy the output to the file at `path`, which is truncated.
+    pub fn file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(File::create(path)?))
+    }
+}
+
+impl fmt::Debug for Tee {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Tee")
+    }
+}
+
+/// Where the guest reads stdin from, instead of the host's stdin, e.g. a
+/// buffer of input prepared by the embedder.
+#[derive(Clone)]
+pub struct Input(Arc<Mutex<dyn Read + Send>>);
+
+impl Input {
+    pub fn new<R: Read + Send + 'static>(reader: R) -> Self {
+        Input(Arc::new(Mutex::new(reader)))
+    }
+}
+
+impl fmt::Debug for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Input")
+    }
+}
+
+/// Where the output of the guest to stdout or stderr goes, instead of the
+/// host's, e.g. a capture buffer.
+#[derive(Clone)]
+pub struct Output(Arc<Mutex<dyn Write + Send>>);
+
+impl Output {
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        Output(Arc::new(Mutex::new(writer)))
+    }
+}
+
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Output")
+    }
+}
+
+/// The output of the guest to stdout that hasn't reached the host yet.
+#[derive(Default)]
+pub(crate) struct StdoutBuffer {
+    pending: Vec<u8>,
+    /// Where the output goes, when it isn't the host's stdout.
+    output: Option<Output>,
+}
+
+impl StdoutBuffer {
+    pub(crate) fn new(output: Option<Output>) -> Self {
+        StdoutBuffer {
+            pending: Vec::new(),
+            output,
+        }
+    }
+
+    fn flush_to(&mut self, len: usize) {
+        // The guest has no way to learn about a failure of a buffered write.
+        match &self.output {
+            Some(Output(output)) => {
+                let mut output = output.lock().unwrap();
+                let _ = output.write_all(&self.pending[..len]);
+                let _ = output.flush();
+            }
+            None => {
+                let mut stdout = io::stdout();
+                let _ = stdout.write_all(&self.pending[..len]);
+                let _ = stdout.flush();
+            }
+        }
+        self.pending.drain(..len);
+    }
+}
+
+impl Drop for StdoutBuffer {
+    fn drop(&mut self) {
+        let len = self.pending.len();
+        self.flush_to(len);
+    }
+}
+
+/// Write `bytes` to the stdout or stderr of the guest as configured, or
+/// return `None` for the caller to write them to the host's `fd` directly.
+pub(crate) fn write_output(ctx: &mut Ctx, fd: c_int, bytes: &[u8]) -> Option<c_int> {
+    if (fd != 1 && fd != 2) || ctx.data.is_null() {
+        return None;
+    }
+    let data = get_emscripten_data(ctx);
+    if fd == 2 {
+        data.panic_watcher.write(bytes);
+    }
+    let stripped;
+    let output = match &mut data.ansi_strippers {
+        Some(strippers) => {
+            stripped = strippers[fd as usize - 1].strip(bytes);
+            &stripped[..]
+        }
+        None => bytes,
+    };
+    if fd == 2 {
+        if let Some(Output(stderr)) = &data.config.stderr {
+            return Some(match stderr.lock().unwrap().write_all(output) {
+                Ok(()) => bytes.len() as c_int,
+                Err(err) => -Errno::from_io_error(&err).to_emscripten(),
+            });
+        }
+        if data.ansi_strippers.is_none() {
+            return None;
+        }
+        let _ = io::stderr().write_all(output);
+        return Some(bytes.len() as c_int);
+    }
+    let buffering = data.config.stdout_buffering;
+    if buffering == Buffering::Unbuffered
+        && data.config.stdout.is_none()
+        && data.config.stdout_tee.is_none()
+        && data.ansi_strippers.is_none()
+    {
+        return None;
+    }
+
+    if let Some(Tee(tee)) = &data.config.stdout_tee {
+        let _ = tee.lock().unwrap().write_all(output);
+    }
+    let buffer = &mut data.stdout;
+    buffer.pending.extend_from_slice(output);
+    let ready = match buffering {
+        Buffering::Unbuffered => buffer.pending.len(),
+        Buffering::Line => buffer
+            .pending
+            .iter()
+            .rposition(|&byte| byte == b'\n')
+            .map_or(0, |newline| newline + 1),
+        Buffering::Full if buffer.pending.len() >= FULL_BUFFER_SIZE => buffer.pending.len(),
+        Buffering::Full => 0,
+    };
+    if ready > 0 {
+        buffer.flush_to(ready);
+    }
+    Some(bytes.len() as c_int)
+}
+
+fn input(ctx: &mut Ctx, fd: c_int) -> Option<Input> {
+    if fd != 0 || ctx.data.is_null() {
+        return None;
+    }
+    get_emscripten_data(ctx).config.stdin.clone()
+}
+
+fn read_from(input: &Input, buf: &mut [u8]) -> c_int {
+    match input.0.lock().unwrap().read(buf) {
+        Ok(read) => read as c_int,
+        Err(err) => -Errno::from_io_error(&err).to_emscripten(),
+    }
+}
+
+/// Read from the stdin of the guest into `buf` as configured, or return
+/// `None` for the caller to read from the host's `fd` directly.
+pub(crate) fn read_input(ctx: &mut Ctx, fd: c_int, buf: &mut [u8]) -> Option<c_int> {
+    input(ctx, fd).map(|input| read_from(&input, buf))
+}
+
+/// Like `read_input`, filling `bufs` in turn until a read comes up short.
+#[cfg(unix)]
+pub(crate) fn read_input_vectored<'b, I>(ctx: &mut Ctx, fd: c_int, bufs: I) -> Option<c_int>
+where
+    I: IntoIterator<Item = &'b mut [u8]>,
+{
+    let input = input(ctx, fd)?;
+    let mut total = 0;
+    for buf in bufs {
+        let read = read_from(&input, buf);
+        if read < 0 {
+            return Some(if total > 0 { total } else { read });
+        }
+        total += read;
+        if (read as usize) < buf.len() {
+            break;
+        }
+    }
+    Some(total)
+}
+
+/// Write out what the guest has buffered, before the process exits.
+pub(crate) fn flush_stdout(ctx: &mut Ctx) {
+    if ctx.data.is_null() {
+        return;
+    }
+    let data = get_emscripten_data(ctx);
+    let len = data.stdout.pending.len();
+    data.stdout.flush_to(len);
+    if let Some(Tee(tee)) = &data.config.stdout_tee {
+        let _ = tee.lock().unwrap().flush();
+    }
+}
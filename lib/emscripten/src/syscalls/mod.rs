@@ -1,4 +1,6 @@
 #[cfg(unix)]
+mod socket;
+#[cfg(unix)]
 mod unix;
 
 #[cfg(windows)]
@@ -10,7 +12,8 @@ pub use self::unix::*;
 #[cfg(windows)]
 pub use self::windows::*;
 
-use super::utils::copy_stat_into_wasm;
+use super::stat_cache::get_stat_cache;
+use super::utils::{copy_stat_into_wasm, guest_memory_range};
 use super::varargs::VarArgs;
 use byteorder::{ByteOrder, LittleEndian};
 /// NOTE: TODO: These syscalls only support wasm_32 for now because they assume offsets are u32
@@ -19,13 +22,11 @@ use libc::{
     // ENOTTY,
     c_int,
     c_void,
-    chdir,
     // fcntl, setsockopt, getppid
     close,
     dup2,
     exit,
     fstat,
-    getpid,
     // iovec,
     lseek,
     open,
@@ -36,11 +37,29 @@ use libc::{
     stat,
     write,
     // sockaddr_in,
+    O_APPEND,
+    O_CREAT,
+    O_RDWR,
+    O_TRUNC,
+    O_WRONLY,
+    SEEK_CUR,
+    SEEK_SET,
 };
 use wasmer_runtime_core::vm::Ctx;
 
+use super::clock::{get_clock, ClockId};
+use super::config::GuestIds;
 use super::env;
+use super::errno::{translate_ret, Errno};
+use super::inotify;
+use super::stdio;
+use super::vfs;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::io;
+use std::rc::Rc;
 use std::slice;
+use std::time::Duration;
 // use std::sys::fd::FileDesc;
 
 // Another conditional constant for name resolution: Macos et iOS use
@@ -55,6 +74,7 @@ const SO_NOSIGPIPE: c_int = 0;
 pub fn ___syscall1(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) {
     debug!("emscripten::___syscall1 (exit) {}", which);
     let status: i32 = varargs.get(ctx);
+    stdio::flush_stdout(ctx);
     unsafe {
         exit(status);
     }
@@ -65,24 +85,47 @@ pub fn ___syscall3(ctx: &mut Ctx, which: i32, mut varargs: VarArgs) -> i32 {
     // -> ssize_t
     debug!("emscripten::___syscall3 (read) {}", which);
     let fd: i32 = varargs.get(ctx);
+    let fd = vfs::host_fd(ctx, fd);
     let buf: u32 = varargs.get(ctx);
-    let count = varargs.get(ctx);
+    let count: u32 = varargs.get(ctx);
     debug!("=> fd: {}, buf_offset: {}, count: {}", fd, buf, count);
-    let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as *mut c_void;
-    let ret = unsafe { read(fd, buf_addr, count) };
+    let buf_addr = match guest_memory_range(ctx, buf, count) {
+        Some(addr) => addr as *mut c_void,
+        None => return -Errno::EFAULT.to_emscripten(),
+    };
+    let buf = unsafe { slice::from_raw_parts_mut(buf_addr as *mut u8, count as usize) };
+    if let Some(ret) = stdio::read_input(ctx, fd, buf) {
+        return ret;
+    }
+    let ret = translate_ret(unsafe { read(fd, buf_addr, count as _) as i32 });
     debug!("=> ret: {}", ret);
-    ret as _
+    ret
 }
 
 /// write
 pub fn ___syscall4(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall4 (write) {}", which);
     let fd: i32 = varargs.get(ctx);
+    let fd = vfs::host_fd(ctx, fd);
     let buf: u32 = varargs.get(ctx);
-    let count = varargs.get(ctx);
+    let count: u32 = varargs.get(ctx);
     debug!("=> fd: {}, buf: {}, count: {}", fd, buf, count);
-    let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as *const c_void;
-    unsafe { write(fd, buf_addr, count) as i32 }
+    let buf_addr = match guest_memory_range(ctx, buf, count) {
+        Some(addr) => addr as *const c_void,
+        None => return -Errno::EFAULT.to_emscripten(),
+    };
+    let bytes = unsafe { slice::from_raw_parts(buf_addr as *const u8, count as usize) };
+    if let Some(ret) = stdio::write_output(ctx, fd, bytes) {
+        return ret;
+    }
+    let ret = translate_ret(unsafe { write(fd, buf_addr, count as _) as i32 });
+    if let Some(cache) = get_stat_cache(ctx) {
+        cache.written(fd);
+    }
+    if ret >= 0 {
+        inotify::written(ctx, fd);
+    }
+    ret
 }
 
 /// open
@@ -91,69 +134,291 @@ pub fn ___syscall5(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     let pathname: u32 = varargs.get(ctx);
     let flags: i32 = varargs.get(ctx);
     let mode: u32 = varargs.get(ctx);
-    let pathname_addr = emscripten_memory_pointer!(ctx.memory(0), pathname) as *const i8;
-    let path_str = unsafe { std::ffi::CStr::from_ptr(pathname_addr).to_str().unwrap() };
-    let fd = unsafe { open(pathname_addr, flags, mode) };
+    let path = match vfs::resolve(ctx, pathname) {
+        Ok(path) => path,
+        Err(errno) => return -errno.to_emscripten(),
+    };
+    open_path(ctx, path, flags, mode)
+}
+
+fn open_path(ctx: &mut Ctx, path: CString, flags: c_int, mode: u32) -> c_int {
+    let denied = vfs::check_open(ctx, &path, flags);
+    if denied != 0 {
+        return denied;
+    }
+    let created = flags & O_CREAT != 0 && fs::metadata(vfs::to_path(&path)).is_err();
+    let mode = vfs::creation_mode(ctx, mode);
+    let fd = unsafe { open(path.as_ptr(), flags, mode) };
     debug!(
-        "=> pathname: {}, flags: {}, mode: {} = fd: {}\npath: {}",
-        pathname, flags, mode, fd, path_str
+        "=> path: {:?}, flags: {}, mode: {} = fd: {}",
+        path, flags, mode, fd
     );
-    fd
+    let mutating = O_WRONLY | O_RDWR | O_CREAT | O_TRUNC | O_APPEND;
+    if fd >= 0 && flags & mutating != 0 {
+        if let Some(cache) = get_stat_cache(ctx) {
+            if created {
+                cache.entry_changed(&path);
+            }
+            cache.track_writable_fd(fd, &path);
+        }
+    }
+    if fd >= 0 && created {
+        vfs::set_mode(ctx, &path, mode);
+        // The host applied its own umask on top of the guest's.
+        #[cfg(unix)]
+        unsafe {
+            libc::fchmod(fd, mode as _);
+        }
+    }
+    if fd >= 0 {
+        vfs::opened(ctx, fd, &path);
+        let writable = flags & (O_WRONLY | O_RDWR) != 0;
+        inotify::opened(ctx, fd, &path, writable, created);
+    }
+    translate_ret(fd)
 }
 
 /// close
 pub fn ___syscall6(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall6 (close) {}", which);
-    let fd: i32 = varargs.get(ctx);
+    let guest_fd: i32 = varargs.get(ctx);
+    let fd = vfs::host_fd(ctx, guest_fd);
     debug!("fd: {}", fd);
-    unsafe { close(fd) }
+    if let Some(cache) = get_stat_cache(ctx) {
+        cache.closed(fd);
+    }
+    inotify::closed(ctx, fd);
+    let ret = translate_ret(unsafe { close(fd) });
+    vfs::closed(ctx, guest_fd);
+    ret
 }
 
 // chdir
 pub fn ___syscall12(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall12 (chdir) {}", which);
-    let path_addr: i32 = varargs.get(ctx);
-    unsafe {
-        let path_ptr = emscripten_memory_pointer!(ctx.memory(0), path_addr) as *const i8;
-        let path = std::ffi::CStr::from_ptr(path_ptr);
-        let ret = chdir(path_ptr);
-        debug!("=> path: {:?}, ret: {}", path, ret);
-        ret
+    let path_addr: u32 = varargs.get(ctx);
+    let path = vfs::resolve_guest(ctx, path_addr);
+    debug!("=> path: {:?}", path);
+    let host_path = match vfs::host_path(ctx, &path) {
+        Ok(host_path) => host_path,
+        Err(errno) => return -errno.to_emscripten(),
+    };
+    // Only the guest's working directory changes, never the host's.
+    match fs::metadata(vfs::to_path(&host_path)) {
+        Ok(ref metadata) if metadata.is_dir() => {
+            vfs::set_cwd(ctx, path);
+            0
+        }
+        Ok(_) => -Errno::ENOTDIR.to_emscripten(),
+        Err(err) => -Errno::from_io_error(&err).to_emscripten(),
+    }
+}
+
+// unlink
+pub fn ___syscall10(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall10 (unlink) {}", which);
+    let pathname: u32 = varargs.get(ctx);
+    let path = match vfs::resolve(ctx, pathname) {
+        Ok(path) => path,
+        Err(errno) => return -errno.to_emscripten(),
+    };
+    unlink_path(ctx, path)
+}
+
+fn unlink_path(ctx: &mut Ctx, path: CString) -> c_int {
+    if let Some(cache) = get_stat_cache(ctx) {
+        cache.entry_changed(&path);
+    }
+    match fs::remove_file(vfs::to_path(&path)) {
+        Ok(()) => {
+            vfs::remove(ctx, &path);
+            inotify::deleted(ctx, &path, false);
+            0
+        }
+        Err(err) => -Errno::from_io_error(&err).to_emscripten(),
     }
 }
 
-pub fn ___syscall10(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::___syscall10");
-    -1
+// chmod
+pub fn ___syscall15(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall15 (chmod) {}", which);
+    let pathname: u32 = varargs.get(ctx);
+    let mode: u32 = varargs.get(ctx);
+    let path = match vfs::resolve(ctx, pathname) {
+        Ok(path) => path,
+        Err(errno) => return -errno.to_emscripten(),
+    };
+    if let Err(err) = fs::metadata(vfs::to_path(&path)) {
+        return -Errno::from_io_error(&err).to_emscripten();
+    }
+    // The guest sees the emulated mode either way, but keep the host in
+    // sync where it has permission bits.
+    #[cfg(unix)]
+    {
+        if unsafe { libc::chmod(path.as_ptr(), mode as _) } == -1 {
+            return -Errno::last().to_emscripten();
+        }
+    }
+    vfs::set_mode(ctx, &path, mode);
+    if let Some(cache) = get_stat_cache(ctx) {
+        cache.invalidate(&path);
+    }
+    inotify::attributes_changed(ctx, &path);
+    0
 }
 
-pub fn ___syscall15(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::___syscall15");
-    -1
+/// The IDs the guest sees, which are configured rather than the host's.
+fn guest_ids(ctx: &mut Ctx) -> GuestIds {
+    // IDs can be queried before `run_emscripten_instance` sets up the data.
+    if ctx.data.is_null() {
+        return GuestIds::default();
+    }
+    env::get_emscripten_data(ctx).config.ids
 }
 
 // getpid
-pub fn ___syscall20(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
+pub fn ___syscall20(ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall20 (getpid)");
-    unsafe { getpid() }
+    guest_ids(ctx).pid as i32
+}
+
+// getuid
+pub fn ___syscall24(ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
+    debug!("emscripten::___syscall24 (getuid)");
+    guest_ids(ctx).uid as i32
 }
 
-pub fn ___syscall38(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::___syscall38");
-    -1
+// rename
+pub fn ___syscall38(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall38 (rename) {}", which);
+    let old_pathname: u32 = varargs.get(ctx);
+    let new_pathname: u32 = varargs.get(ctx);
+    let old_path = match vfs::resolve(ctx, old_pathname) {
+        Ok(path) => path,
+        Err(errno) => return -errno.to_emscripten(),
+    };
+    let new_path = match vfs::resolve(ctx, new_pathname) {
+        Ok(path) => path,
+        Err(errno) => return -errno.to_emscripten(),
+    };
+    rename_path(ctx, old_path, new_path)
+}
+
+fn rename_path(ctx: &mut Ctx, old_path: CString, new_path: CString) -> c_int {
+    if let Some(cache) = get_stat_cache(ctx) {
+        cache.entry_changed(&old_path);
+        cache.entry_changed(&new_path);
+    }
+    let is_dir = fs::metadata(vfs::to_path(&old_path)).map_or(false, |m| m.is_dir());
+    match fs::rename(vfs::to_path(&old_path), vfs::to_path(&new_path)) {
+        Ok(()) => {
+            vfs::renamed(ctx, &old_path, &new_path);
+            inotify::deleted(ctx, &old_path, is_dir);
+            inotify::created(ctx, &new_path, is_dir);
+            0
+        }
+        Err(err) => -Errno::from_io_error(&err).to_emscripten(),
+    }
 }
 
 // rmdir
 pub fn ___syscall40(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall40 (rmdir)");
     let pathname: u32 = varargs.get(ctx);
-    let pathname_addr = emscripten_memory_pointer!(ctx.memory(0), pathname) as *const i8;
-    unsafe { rmdir(pathname_addr) }
+    let path = match vfs::resolve(ctx, pathname) {
+        Ok(path) => path,
+        Err(errno) => return -errno.to_emscripten(),
+    };
+    rmdir_path(ctx, path)
+}
+
+fn rmdir_path(ctx: &mut Ctx, path: CString) -> c_int {
+    if let Some(cache) = get_stat_cache(ctx) {
+        cache.entry_changed(&path);
+    }
+    let ret = unsafe { rmdir(path.as_ptr()) };
+    if ret == 0 {
+        vfs::remove(ctx, &path);
+        inotify::deleted(ctx, &path, true);
+    }
+    translate_ret(ret)
 }
 
-pub fn ___syscall60(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::___syscall60");
-    -1
+// access
+pub fn ___syscall33(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall33 (access) {}", which);
+    let pathname: u32 = varargs.get(ctx);
+    let amode: c_int = varargs.get(ctx);
+    let path = match vfs::resolve(ctx, pathname) {
+        Ok(path) => path,
+        Err(errno) => return -errno.to_emscripten(),
+    };
+    vfs::access(ctx, &path, amode)
+}
+
+// times
+#[allow(clippy::cast_ptr_alignment)]
+pub fn ___syscall43(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall43 (times)");
+    // emscripten reports sysconf(_SC_CLK_TCK) as 100.
+    const CLK_TCK: u64 = 100;
+    #[repr(C)]
+    struct GuestTms {
+        tms_utime: i32,
+        tms_stime: i32,
+        tms_cutime: i32,
+        tms_cstime: i32,
+    }
+
+    let buf: u32 = varargs.get(ctx);
+    let to_ticks = |time: Duration| {
+        (time.as_secs() * CLK_TCK + u64::from(time.subsec_nanos()) * CLK_TCK / 1_000_000_000) as i32
+    };
+    let clock = get_clock(ctx);
+    let (cpu_time, elapsed) = match (
+        clock.time(ClockId::ProcessCpuTime),
+        clock.time(ClockId::Monotonic),
+    ) {
+        (Some(cpu_time), Some(elapsed)) => (to_ticks(cpu_time), to_ticks(elapsed)),
+        _ => return -Errno::ENOSYS.to_emscripten(),
+    };
+
+    if buf != 0 {
+        let tms = emscripten_memory_pointer!(ctx.memory(0), buf) as *mut GuestTms;
+        unsafe {
+            // The host can't tell user and system time apart portably.
+            (*tms).tms_utime = cpu_time;
+            (*tms).tms_stime = 0;
+            (*tms).tms_cutime = 0;
+            (*tms).tms_cstime = 0;
+        }
+    }
+    elapsed
+}
+
+// getgid
+pub fn ___syscall47(ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
+    debug!("emscripten::___syscall47 (getgid)");
+    guest_ids(ctx).gid as i32
+}
+
+// geteuid
+pub fn ___syscall49(ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
+    debug!("emscripten::___syscall49 (geteuid)");
+    guest_ids(ctx).uid as i32
+}
+
+// getegid
+pub fn ___syscall50(ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
+    debug!("emscripten::___syscall50 (getegid)");
+    guest_ids(ctx).gid as i32
+}
+
+// umask
+pub fn ___syscall60(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall60 (umask) {}", which);
+    let mask: u32 = varargs.get(ctx);
+    vfs::set_umask(ctx, mask) as c_int
 }
 
 // dup2
@@ -161,45 +426,78 @@ pub fn ___syscall63(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int
     debug!("emscripten::___syscall63 (dup2) {}", which);
 
     let src: i32 = varargs.get(ctx);
-    let dst: i32 = varargs.get(ctx);
+    let src = vfs::host_fd(ctx, src);
+    let guest_dst: i32 = varargs.get(ctx);
+    let dst = vfs::host_fd(ctx, guest_dst);
 
-    unsafe { dup2(src, dst) }
+    if let Some(cache) = get_stat_cache(ctx) {
+        cache.closed(dst);
+    }
+    let ret = unsafe { dup2(src, dst) };
+    if ret < 0 {
+        return translate_ret(ret);
+    }
+    vfs::duplicated(ctx, src, dst);
+    guest_dst
 }
 
 // getppid
-pub fn ___syscall64(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
+pub fn ___syscall64(ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall64 (getppid)");
-    unsafe { getpid() }
+    guest_ids(ctx).ppid as i32
 }
 
 pub fn ___syscall66(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall66");
-    -1
+    -Errno::ENOSYS.to_emscripten()
 }
 
 pub fn ___syscall75(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall75");
-    -1
+    -Errno::ENOSYS.to_emscripten()
+}
+
+// readlink
+pub fn ___syscall85(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall85 (readlink) {}", which);
+    let pathname: u32 = varargs.get(ctx);
+    let buf: u32 = varargs.get(ctx);
+    let buf_size: u32 = varargs.get(ctx);
+    let path = match vfs::resolve(ctx, pathname) {
+        Ok(path) => path,
+        Err(errno) => return -errno.to_emscripten(),
+    };
+    readlink_path(ctx, path, buf, buf_size)
 }
 
-pub fn ___syscall85(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::___syscall85");
-    -1
+fn readlink_path(ctx: &mut Ctx, path: CString, buf: u32, buf_size: u32) -> c_int {
+    let target = match fs::read_link(vfs::to_path(&path)) {
+        Ok(target) => vfs::name_bytes(target.as_os_str()),
+        Err(err) => return -Errno::from_io_error(&err).to_emscripten(),
+    };
+    // Like readlink, truncate silently and don't append a terminator.
+    let len = target.len().min(buf_size as usize);
+    let buf = match guest_memory_range(ctx, buf, len as u32) {
+        Some(buf) => buf,
+        None => return -Errno::EFAULT.to_emscripten(),
+    };
+    unsafe { slice::from_raw_parts_mut(buf as *mut u8, len) }.copy_from_slice(&target[..len]);
+    len as c_int
 }
 
 pub fn ___syscall91(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall91");
-    -1
+    -Errno::ENOSYS.to_emscripten()
 }
 
 pub fn ___syscall97(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall97");
-    -1
+    -Errno::ENOSYS.to_emscripten()
 }
 
 pub fn ___syscall110(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall110");
-    -1
+    -Errno::ENOSYS.to_emscripten()
 }
 
 // mmap2
@@ -219,12 +517,13 @@ pub fn ___syscall192(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int
     if fd == -1 {
         let ptr = env::call_memalign(ctx, 16384, len);
         if ptr == 0 {
-            return -1;
+            return -Errno::ENOMEM.to_emscripten();
         }
         env::call_memset(ctx, ptr, 0, len);
         ptr as _
     } else {
-        -1
+        // Mapping files isn't supported.
+        -Errno::ENOSYS.to_emscripten()
     }
 }
 
@@ -233,114 +532,286 @@ pub fn ___syscall140(ctx: &mut Ctx, which: i32, mut varargs: VarArgs) -> i32 {
     // -> c_int
     debug!("emscripten::___syscall140 (lseek) {}", which);
     let fd: i32 = varargs.get(ctx);
+    let fd = vfs::host_fd(ctx, fd);
     let offset = varargs.get(ctx);
     let whence: i32 = varargs.get(ctx);
     debug!("=> fd: {}, offset: {}, whence = {}", fd, offset, whence);
-    unsafe { lseek(fd, offset, whence) as _ }
+    if let Some(ret) = vfs::seek_dir(ctx, fd, offset as i64, whence) {
+        return ret;
+    }
+    translate_ret(unsafe { lseek(fd, offset, whence) as _ })
 }
 
-/// readv
-#[allow(clippy::cast_ptr_alignment)]
-pub fn ___syscall145(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> i32 {
-    // -> ssize_t
-    debug!("emscripten::___syscall145 (readv) {}", which);
-    // let fd: i32 = varargs.get(ctx);
-    // let iov: u32 = varargs.get(ctx);
-    // let iovcnt: i32 = varargs.get(ctx);
-    // debug!("=> fd: {}, iov: {}, iovcnt = {}", fd, iov, iovcnt);
-    // let iov_addr = emscripten_memory_pointer!(ctx.memory(0), iov) as *mut iovec;
-    // unsafe { readv(fd, iov_addr, iovcnt) }
+pub fn ___syscall168(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
+    debug!("emscripten::___syscall168");
+    -Errno::ENOSYS.to_emscripten()
+}
 
-    let fd: i32 = varargs.get(ctx);
-    let iov: i32 = varargs.get(ctx);
-    let iovcnt: i32 = varargs.get(ctx);
+// getcwd
+pub fn ___syscall183(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall183 (getcwd) {}", which);
+    let buf: u32 = varargs.get(ctx);
+    let size: u32 = varargs.get(ctx);
+    let cwd = vfs::name_bytes(vfs::cwd(ctx).as_os_str());
+    debug!("=> cwd: {}", String::from_utf8_lossy(&cwd));
+    let len = cwd.len() as u32 + 1;
+    if len > size {
+        return -Errno::ERANGE.to_emscripten();
+    }
+    let buf = match guest_memory_range(ctx, buf, len) {
+        Some(buf) => unsafe { slice::from_raw_parts_mut(buf, len as usize) },
+        None => return -Errno::EFAULT.to_emscripten(),
+    };
+    buf[..cwd.len()].copy_from_slice(&cwd);
+    buf[cwd.len()] = 0;
+    // Like the kernel, return the length including the terminator.
+    len as c_int
+}
 
-    #[repr(C)]
-    struct GuestIovec {
-        iov_base: i32,
-        iov_len: i32,
+// sendfile
+pub fn ___syscall187(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall187 (sendfile) {}", which);
+    let out_fd: c_int = varargs.get(ctx);
+    let in_fd: c_int = varargs.get(ctx);
+    let offset: u32 = varargs.get(ctx);
+    let count: u32 = varargs.get(ctx);
+    debug!(
+        "=> out_fd: {}, in_fd: {}, offset: {}, count: {}",
+        out_fd, in_fd, offset, count
+    );
+    sendfile(ctx, out_fd, in_fd, GuestOffset::Off32(offset), count)
+}
+
+/// The pointer to a file offset the guest passes to the copying syscalls,
+/// whose width depends on the syscall.
+#[derive(Clone, Copy)]
+enum GuestOffset {
+    Off32(u32),
+    Off64(u32),
+}
+
+impl GuestOffset {
+    fn read(self, ctx: &Ctx) -> Result<Option<i64>, Errno> {
+        let (addr, size) = match self {
+            GuestOffset::Off32(0) | GuestOffset::Off64(0) => return Ok(None),
+            GuestOffset::Off32(addr) => (addr, 4),
+            GuestOffset::Off64(addr) => (addr, 8),
+        };
+        let ptr = guest_memory_range(ctx, addr, size).ok_or(Errno::EFAULT)?;
+        let offset = unsafe {
+            match self {
+                GuestOffset::Off32(_) => i64::from((ptr as *const i32).read_unaligned()),
+                GuestOffset::Off64(_) => (ptr as *const i64).read_unaligned(),
+            }
+        };
+        if offset < 0 {
+            return Err(Errno::EINVAL);
+        }
+        Ok(Some(offset))
     }
 
-    debug!("=> fd: {}, iov: {}, iovcnt = {}", fd, iov, iovcnt);
-    let mut ret = 0;
-    unsafe {
-        for i in 0..iovcnt {
-            let guest_iov_addr =
-                emscripten_memory_pointer!(ctx.memory(0), (iov + i * 8)) as *mut GuestIovec;
-            let iov_base = emscripten_memory_pointer!(ctx.memory(0), (*guest_iov_addr).iov_base)
-                as *mut c_void;
-            let iov_len = (*guest_iov_addr).iov_len as _;
-            // debug!("=> iov_addr: {:?}, {:?}", iov_base, iov_len);
-            let curr = read(fd, iov_base, iov_len);
-            if curr < 0 {
-                return -1;
+    fn write(self, ctx: &Ctx, offset: i64) {
+        let memory = ctx.memory(0);
+        unsafe {
+            match self {
+                GuestOffset::Off32(0) | GuestOffset::Off64(0) => {}
+                GuestOffset::Off32(addr) => {
+                    let ptr = emscripten_memory_pointer!(memory, addr) as *mut i32;
+                    ptr.write_unaligned(offset as i32)
+                }
+                GuestOffset::Off64(addr) => {
+                    let ptr = emscripten_memory_pointer!(memory, addr) as *mut i64;
+                    ptr.write_unaligned(offset)
+                }
             }
-            ret += curr;
         }
-        // debug!(" => ret: {}", ret);
-        ret as _
     }
 }
 
-// writev
-#[allow(clippy::cast_ptr_alignment)]
-pub fn ___syscall146(ctx: &mut Ctx, which: i32, mut varargs: VarArgs) -> i32 {
-    // -> ssize_t
-    debug!("emscripten::___syscall146 (writev) {}", which);
-    let fd: i32 = varargs.get(ctx);
-    let iov: i32 = varargs.get(ctx);
-    let iovcnt: i32 = varargs.get(ctx);
+fn sendfile(ctx: &mut Ctx, out_fd: c_int, in_fd: c_int, offset: GuestOffset, count: u32) -> c_int {
+    let (out_fd, in_fd) = (vfs::host_fd(ctx, out_fd), vfs::host_fd(ctx, in_fd));
+    let mut in_offset = match offset.read(ctx) {
+        Ok(in_offset) => in_offset,
+        Err(errno) => return -errno.to_emscripten(),
+    };
+    let ret = match copy_range(in_fd, in_offset.as_mut(), out_fd, None, count as usize) {
+        Ok(copied) => copied as c_int,
+        Err(err) => return -Errno::from_io_error(&err).to_emscripten(),
+    };
+    if let Some(in_offset) = in_offset {
+        offset.write(ctx, in_offset);
+    }
+    if let Some(cache) = get_stat_cache(ctx) {
+        cache.written(out_fd);
+    }
+    ret
+}
 
-    #[repr(C)]
-    struct GuestIovec {
-        iov_base: i32,
-        iov_len: i32,
+/// The size of the host buffer that data is copied through when the
+/// host can't copy between the descriptors by itself.
+const COPY_BUFFER_SIZE: usize = 64 * 1024;
+
+/// The most a copy transfers at once, so that the count it returns is a
+/// positive `c_int`. The kernel caps transfers at the same size.
+const MAX_COPY: usize = 0x7fff_f000;
+
+/// Copy up to `count` bytes from `in_fd` to `out_fd` on the host, so that
+/// the data never goes through guest memory.
+///
+/// A descriptor with an offset is accessed at that offset, which is then
+/// advanced, and its own file offset is left alone; the file offset of a
+/// descriptor without one is used and advanced instead.
+fn copy_range(
+    in_fd: c_int,
+    in_offset: Option<&mut i64>,
+    out_fd: c_int,
+    out_offset: Option<&mut i64>,
+    count: usize,
+) -> io::Result<usize> {
+    let count = count.min(MAX_COPY);
+    #[cfg(target_os = "linux")]
+    {
+        if out_offset.is_none() {
+            return match host_sendfile(in_fd, in_offset, out_fd, count) {
+                Ok(copied) => Ok(copied),
+                // The input can't be mapped, e.g. it's a pipe.
+                Err((in_offset, err)) => match err.raw_os_error() {
+                    Some(libc::EINVAL) => {
+                        copy_through_buffer(in_fd, in_offset, out_fd, None, count)
+                    }
+                    _ => Err(err),
+                },
+            };
+        }
     }
+    copy_through_buffer(in_fd, in_offset, out_fd, out_offset, count)
+}
 
-    debug!("=> fd: {}, iov: {}, iovcnt = {}", fd, iov, iovcnt);
-    let mut ret = 0;
-    unsafe {
-        for i in 0..iovcnt {
-            let guest_iov_addr =
-                emscripten_memory_pointer!(ctx.memory(0), (iov + i * 8)) as *mut GuestIovec;
-            let iov_base = emscripten_memory_pointer!(ctx.memory(0), (*guest_iov_addr).iov_base)
-                as *const c_void;
-            let iov_len = (*guest_iov_addr).iov_len as _;
-            // debug!("=> iov_addr: {:?}, {:?}", iov_base, iov_len);
-            let curr = write(fd, iov_base, iov_len);
-            if curr < 0 {
-                return -1;
+#[cfg(target_os = "linux")]
+fn host_sendfile(
+    in_fd: c_int,
+    in_offset: Option<&mut i64>,
+    out_fd: c_int,
+    count: usize,
+) -> Result<usize, (Option<&mut i64>, io::Error)> {
+    let mut host_offset = in_offset.as_ref().map(|offset| **offset as libc::off_t);
+    let offset_ptr = host_offset
+        .as_mut()
+        .map_or(std::ptr::null_mut(), |offset| offset as *mut libc::off_t);
+    let ret = unsafe { libc::sendfile(out_fd, in_fd, offset_ptr, count) };
+    if ret < 0 {
+        return Err((in_offset, io::Error::last_os_error()));
+    }
+    if let (Some(in_offset), Some(host_offset)) = (in_offset, host_offset) {
+        *in_offset = host_offset as i64;
+    }
+    Ok(ret as usize)
+}
+
+fn copy_through_buffer(
+    in_fd: c_int,
+    in_offset: Option<&mut i64>,
+    out_fd: c_int,
+    out_offset: Option<&mut i64>,
+    count: usize,
+) -> io::Result<usize> {
+    let in_restore = match &in_offset {
+        Some(offset) => Some(seek_to(in_fd, **offset)?),
+        None => None,
+    };
+    let out_restore = match &out_offset {
+        Some(offset) => Some(seek_to(out_fd, **offset)?),
+        None => None,
+    };
+
+    let mut buf = vec![0u8; count.min(COPY_BUFFER_SIZE)];
+    let mut copied = 0;
+    let mut result = Ok(());
+    'copy: while copied < count {
+        let len = buf.len().min(count - copied);
+        let read_len = unsafe { read(in_fd, buf.as_mut_ptr() as *mut c_void, len as _) };
+        if read_len <= 0 {
+            if read_len < 0 {
+                result = Err(io::Error::last_os_error());
+            }
+            break;
+        }
+        let mut written = 0;
+        while written < read_len as usize {
+            let chunk = &buf[written..read_len as usize];
+            let write_len =
+                unsafe { write(out_fd, chunk.as_ptr() as *const c_void, chunk.len() as _) };
+            if write_len < 0 {
+                result = Err(io::Error::last_os_error());
+                copied += written;
+                break 'copy;
             }
-            ret += curr;
+            written += write_len as usize;
         }
-        // debug!(" => ret: {}", ret);
-        ret as _
+        copied += written;
+    }
+
+    if let (Some(offset), Some(restore)) = (in_offset, in_restore) {
+        *offset += copied as i64;
+        seek_to(in_fd, restore)?;
+    }
+    if let (Some(offset), Some(restore)) = (out_offset, out_restore) {
+        *offset += copied as i64;
+        seek_to(out_fd, restore)?;
+    }
+    // Like the kernel, only report an error if nothing was copied.
+    match result {
+        Err(err) if copied == 0 => Err(err),
+        _ => Ok(copied),
     }
 }
 
-pub fn ___syscall168(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::___syscall168");
-    -1
+/// Move the file offset of `fd` to `offset`, returning the previous one.
+fn seek_to(fd: c_int, offset: i64) -> io::Result<i64> {
+    let previous = unsafe { lseek(fd, 0, SEEK_CUR) };
+    if previous < 0 || unsafe { lseek(fd, offset as _, SEEK_SET) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(previous as i64)
 }
 
 pub fn ___syscall191(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall191 - stub");
-    -1
+    -Errno::ENOSYS.to_emscripten()
 }
 
 pub fn ___syscall194(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall194 - stub");
-    -1
+    -Errno::ENOSYS.to_emscripten()
 }
 
 pub fn ___syscall196(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall194 - stub");
-    -1
+    -Errno::ENOSYS.to_emscripten()
+}
+
+// getuid32
+pub fn ___syscall199(ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
+    debug!("emscripten::___syscall199 (getuid32)");
+    guest_ids(ctx).uid as i32
 }
 
-pub fn ___syscall199(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::___syscall199 - stub");
-    -1
+// getgid32
+pub fn ___syscall200(ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
+    debug!("emscripten::___syscall200 (getgid32)");
+    guest_ids(ctx).gid as i32
+}
+
+// geteuid32
+pub fn ___syscall201(ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
+    debug!("emscripten::___syscall201 (geteuid32)");
+    guest_ids(ctx).uid as i32
+}
+
+// getegid32
+pub fn ___syscall202(ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
+    debug!("emscripten::___syscall202 (getegid32)");
+    guest_ids(ctx).gid as i32
 }
 
 // stat64
@@ -348,15 +819,47 @@ pub fn ___syscall195(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int
     debug!("emscripten::___syscall195 (stat64) {}", which);
     let pathname: u32 = varargs.get(ctx);
     let buf: u32 = varargs.get(ctx);
+    // The fast path, which doesn't allocate.
+    let guest_path = if get_stat_cache(ctx).is_some() {
+        vfs::plain_guest_path(ctx, pathname)
+    } else {
+        None
+    };
+    if let Some(guest_path) = guest_path {
+        let cached = get_stat_cache(ctx).and_then(|cache| cache.get_guest(guest_path).cloned());
+        if let Some(cached) = cached {
+            debug!("=> cached");
+            unsafe { copy_stat_into_wasm(ctx, buf, &cached) };
+            return 0;
+        }
+    }
+    let path = match vfs::resolve(ctx, pathname) {
+        Ok(path) => path,
+        Err(errno) => return -errno.to_emscripten(),
+    };
+    if let (Some(guest_path), Some(cache)) = (guest_path, get_stat_cache(ctx)) {
+        cache.insert_guest(guest_path, &path);
+    }
+    stat_path(ctx, path, buf)
+}
 
-    let pathname_addr = emscripten_memory_pointer!(ctx.memory(0), pathname) as *const i8;
-
+fn stat_path(ctx: &mut Ctx, path: CString, buf: u32) -> c_int {
     unsafe {
+        if let Some(cached) = get_stat_cache(ctx).and_then(|cache| cache.get(&path).cloned()) {
+            debug!("=> cached");
+            copy_stat_into_wasm(ctx, buf, &cached);
+            return 0;
+        }
+
         let mut _stat: stat = std::mem::zeroed();
-        let ret = stat(pathname_addr, &mut _stat);
+        let ret = stat(path.as_ptr(), &mut _stat);
         debug!("ret: {}", ret);
         if ret != 0 {
-            return ret;
+            return translate_ret(ret);
+        }
+        _stat.st_mode = vfs::apply_mode(ctx, &path, _stat.st_mode as u32) as _;
+        if let Some(cache) = get_stat_cache(ctx) {
+            cache.insert(&path, _stat);
         }
         copy_stat_into_wasm(ctx, buf, &_stat);
     }
@@ -367,6 +870,7 @@ pub fn ___syscall195(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int
 pub fn ___syscall197(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall197 (fstat64) {}", which);
     let fd: c_int = varargs.get(ctx);
+    let fd = vfs::host_fd(ctx, fd);
     let buf: u32 = varargs.get(ctx);
 
     unsafe {
@@ -374,7 +878,7 @@ pub fn ___syscall197(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int
         let ret = fstat(fd, &mut stat);
         debug!("ret: {}", ret);
         if ret != 0 {
-            return ret;
+            return translate_ret(ret);
         }
         copy_stat_into_wasm(ctx, buf, &stat);
     }
@@ -382,46 +886,213 @@ pub fn ___syscall197(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int
     0
 }
 
-pub fn ___syscall220(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::___syscall220");
-    -1
+// getdents64
+pub fn ___syscall220(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall220 (getdents64) {}", which);
+    let fd: c_int = varargs.get(ctx);
+    let fd = vfs::host_fd(ctx, fd);
+    let dirp: u32 = varargs.get(ctx);
+    let count: u32 = varargs.get(ctx);
+    let (path, position) = match vfs::read_position(ctx, fd) {
+        Ok(dir) => dir,
+        Err(errno) => return -errno.to_emscripten(),
+    };
+    let dirents = match dir_entries(ctx, &path) {
+        Ok(dirents) => dirents,
+        Err(err) => return -Errno::from_io_error(&err).to_emscripten(),
+    };
+    let buf = match guest_memory_range(ctx, dirp, count) {
+        Some(buf) => unsafe { slice::from_raw_parts_mut(buf as *mut u8, count as usize) },
+        None => return -Errno::EFAULT.to_emscripten(),
+    };
+    // The directory may have lost entries since the last read.
+    let position = position.min(dirents.len());
+    let read = (dirents.len() - position).min(buf.len() / vfs::DIRENT_SIZE);
+    if read == 0 && position < dirents.len() {
+        return -Errno::EINVAL.to_emscripten();
+    }
+    for (i, dirent) in dirents[position..position + read].iter().enumerate() {
+        let offset = (position + i + 1) * vfs::DIRENT_SIZE;
+        dirent.write(&mut buf[i * vfs::DIRENT_SIZE..], offset);
+    }
+    vfs::set_read_position(ctx, fd, position + read);
+    (read * vfs::DIRENT_SIZE) as c_int
+}
+
+/// The entries of the directory `path`, from the stat cache if it has them.
+fn dir_entries(ctx: &mut Ctx, path: &CStr) -> io::Result<Rc<[vfs::Dirent]>> {
+    if let Some(dirents) = get_stat_cache(ctx).and_then(|cache| cache.dirents(path)) {
+        return Ok(dirents);
+    }
+    let dirents: Rc<[vfs::Dirent]> = vfs::read_dir(path)?.into();
+    if let Some(cache) = get_stat_cache(ctx) {
+        cache.insert_dirents(path, dirents.clone());
+    }
+    Ok(dirents)
 }
 
 // fcntl64
 pub fn ___syscall221(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall221 (fcntl64) {}", which);
-    // fcntl64
-    let _fd: i32 = varargs.get(ctx);
+    // The commands, as the guest defines them.
+    const F_DUPFD: u32 = 0;
+    const F_SETFD: u32 = 2;
+    const F_DUPFD_CLOEXEC: u32 = 1030;
+    let fd: i32 = varargs.get(ctx);
+    let fd = vfs::host_fd(ctx, fd);
     let cmd: u32 = varargs.get(ctx);
     match cmd {
-        2 => 0,
-        _ => -1,
+        F_DUPFD | F_DUPFD_CLOEXEC => {
+            let min: c_int = varargs.get(ctx);
+            duplicate_from(ctx, fd, min, cmd == F_DUPFD_CLOEXEC)
+        }
+        F_SETFD => 0,
+        _ => -Errno::EINVAL.to_emscripten(),
+    }
+}
+
+/// Duplicate `fd` as the lowest free descriptor from `min` up.
+#[cfg(unix)]
+fn duplicate_from(ctx: &mut Ctx, fd: c_int, min: c_int, cloexec: bool) -> c_int {
+    let cmd = if cloexec {
+        libc::F_DUPFD_CLOEXEC
+    } else {
+        libc::F_DUPFD
+    };
+    let new = unsafe { libc::fcntl(fd, cmd, min) };
+    if new >= 0 {
+        vfs::duplicated(ctx, fd, new);
     }
+    translate_ret(new)
+}
+
+#[cfg(windows)]
+fn duplicate_from(_ctx: &mut Ctx, _fd: c_int, _min: c_int, _cloexec: bool) -> c_int {
+    -Errno::EINVAL.to_emscripten()
+}
+
+// sendfile64
+pub fn ___syscall239(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall239 (sendfile64) {}", which);
+    let out_fd: c_int = varargs.get(ctx);
+    let in_fd: c_int = varargs.get(ctx);
+    let offset: u32 = varargs.get(ctx);
+    let count: u32 = varargs.get(ctx);
+    sendfile(ctx, out_fd, in_fd, GuestOffset::Off64(offset), count)
+}
+
+// gettid
+pub fn ___syscall224(ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
+    debug!("emscripten::___syscall224 (gettid)");
+    // The guest is single-threaded, so its only thread is the main one.
+    guest_ids(ctx).pid as i32
 }
 
 pub fn ___syscall268(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall268");
-    -1
+    -Errno::ENOSYS.to_emscripten()
 }
 
 pub fn ___syscall272(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall272");
-    -1
+    -Errno::ENOSYS.to_emscripten()
 }
 
-pub fn ___syscall295(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::___syscall295");
-    -1
+// openat
+pub fn ___syscall295(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall295 (openat) {}", which);
+    let dirfd: c_int = varargs.get(ctx);
+    let pathname: u32 = varargs.get(ctx);
+    let flags: c_int = varargs.get(ctx);
+    let mode: u32 = varargs.get(ctx);
+    match vfs::resolve_at(ctx, dirfd, pathname) {
+        Ok(path) => open_path(ctx, path, flags, mode),
+        Err(errno) => -errno.to_emscripten(),
+    }
 }
 
-pub fn ___syscall300(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::___syscall300");
-    -1
+// mkdirat
+pub fn ___syscall296(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall296 (mkdirat) {}", which);
+    let dirfd: c_int = varargs.get(ctx);
+    let pathname: u32 = varargs.get(ctx);
+    let mode: u32 = varargs.get(ctx);
+    match vfs::resolve_at(ctx, dirfd, pathname) {
+        Ok(path) => mkdir_path(ctx, path, mode),
+        Err(errno) => -errno.to_emscripten(),
+    }
+}
+
+// fchownat
+pub fn ___syscall298(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall298 (fchownat) {}", which);
+    let dirfd: c_int = varargs.get(ctx);
+    let pathname: u32 = varargs.get(ctx);
+    let owner: u32 = varargs.get(ctx);
+    let group: u32 = varargs.get(ctx);
+    match vfs::resolve_at(ctx, dirfd, pathname) {
+        Ok(path) => chown_path(ctx, path, owner, group),
+        Err(errno) => -errno.to_emscripten(),
+    }
+}
+
+// fstatat64
+pub fn ___syscall300(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall300 (fstatat64) {}", which);
+    let dirfd: c_int = varargs.get(ctx);
+    let pathname: u32 = varargs.get(ctx);
+    let buf: u32 = varargs.get(ctx);
+    // AT_SYMLINK_NOFOLLOW isn't honored: links are always followed.
+    let _flags: c_int = varargs.get(ctx);
+    match vfs::resolve_at(ctx, dirfd, pathname) {
+        Ok(path) => stat_path(ctx, path, buf),
+        Err(errno) => -errno.to_emscripten(),
+    }
+}
+
+// unlinkat
+pub fn ___syscall301(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall301 (unlinkat) {}", which);
+    const AT_REMOVEDIR: c_int = 0x200;
+    let dirfd: c_int = varargs.get(ctx);
+    let pathname: u32 = varargs.get(ctx);
+    let flags: c_int = varargs.get(ctx);
+    match vfs::resolve_at(ctx, dirfd, pathname) {
+        Ok(path) if flags & AT_REMOVEDIR != 0 => rmdir_path(ctx, path),
+        Ok(path) => unlink_path(ctx, path),
+        Err(errno) => -errno.to_emscripten(),
+    }
+}
+
+// readlinkat
+pub fn ___syscall305(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall305 (readlinkat) {}", which);
+    let dirfd: c_int = varargs.get(ctx);
+    let pathname: u32 = varargs.get(ctx);
+    let buf: u32 = varargs.get(ctx);
+    let buf_size: u32 = varargs.get(ctx);
+    match vfs::resolve_at(ctx, dirfd, pathname) {
+        Ok(path) => readlink_path(ctx, path, buf, buf_size),
+        Err(errno) => -errno.to_emscripten(),
+    }
+}
+
+// faccessat
+pub fn ___syscall307(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall307 (faccessat) {}", which);
+    let dirfd: c_int = varargs.get(ctx);
+    let pathname: u32 = varargs.get(ctx);
+    let amode: c_int = varargs.get(ctx);
+    let _flags: c_int = varargs.get(ctx);
+    match vfs::resolve_at(ctx, dirfd, pathname) {
+        Ok(path) => vfs::access(ctx, &path, amode),
+        Err(errno) => -errno.to_emscripten(),
+    }
 }
 
 pub fn ___syscall334(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
     debug!("emscripten::___syscall334");
-    -1
+    -Errno::ENOSYS.to_emscripten()
 }
 
 // prlimit64
@@ -446,3 +1117,95 @@ pub fn ___syscall340(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int
 
     0
 }
+
+// copy_file_range
+pub fn ___syscall377(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall377 (copy_file_range) {}", which);
+    let in_fd: c_int = varargs.get(ctx);
+    let in_fd = vfs::host_fd(ctx, in_fd);
+    let in_offset_addr: u32 = varargs.get(ctx);
+    let out_fd: c_int = varargs.get(ctx);
+    let out_fd = vfs::host_fd(ctx, out_fd);
+    let out_offset_addr: u32 = varargs.get(ctx);
+    let len: u32 = varargs.get(ctx);
+    let flags: u32 = varargs.get(ctx);
+    debug!(
+        "=> in_fd: {}, out_fd: {}, len: {}, flags: {}",
+        in_fd, out_fd, len, flags
+    );
+    if flags != 0 {
+        return -Errno::EINVAL.to_emscripten();
+    }
+
+    let (in_offset_ptr, out_offset_ptr) = (
+        GuestOffset::Off64(in_offset_addr),
+        GuestOffset::Off64(out_offset_addr),
+    );
+    let offsets = in_offset_ptr
+        .read(ctx)
+        .and_then(|in_offset| Ok((in_offset, out_offset_ptr.read(ctx)?)));
+    let (mut in_offset, mut out_offset) = match offsets {
+        Ok(offsets) => offsets,
+        Err(errno) => return -errno.to_emscripten(),
+    };
+    let ret = match copy_range(
+        in_fd,
+        in_offset.as_mut(),
+        out_fd,
+        out_offset.as_mut(),
+        len as usize,
+    ) {
+        Ok(copied) => copied as c_int,
+        Err(err) => return -Errno::from_io_error(&err).to_emscripten(),
+    };
+    if let Some(in_offset) = in_offset {
+        in_offset_ptr.write(ctx, in_offset);
+    }
+    if let Some(out_offset) = out_offset {
+        out_offset_ptr.write(ctx, out_offset);
+    }
+    if let Some(cache) = get_stat_cache(ctx) {
+        cache.written(out_fd);
+    }
+    ret
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::os::unix::io::AsRawFd;
+
+    #[test]
+    fn copies_report_what_was_copied_whatever_the_count() {
+        assert!(MAX_COPY <= c_int::max_value() as usize);
+
+        let dir = std::env::temp_dir();
+        let (in_path, out_path) = (dir.join("copy_range.in"), dir.join("copy_range.out"));
+        fs::File::create(&in_path)
+            .unwrap()
+            .write_all(b"hello")
+            .unwrap();
+        let input = fs::File::open(&in_path).unwrap();
+        let output = fs::File::create(&out_path).unwrap();
+        let (mut in_offset, mut out_offset) = (1, 0);
+        let copied = copy_range(
+            input.as_raw_fd(),
+            Some(&mut in_offset),
+            output.as_raw_fd(),
+            Some(&mut out_offset),
+            u32::max_value() as usize,
+        )
+        .unwrap();
+        assert_eq!((copied, in_offset, out_offset), (4, 5, 4));
+
+        let mut copy = String::new();
+        fs::File::open(&out_path)
+            .unwrap()
+            .read_to_string(&mut copy)
+            .unwrap();
+        assert_eq!(copy, "ello");
+        fs::remove_file(in_path).unwrap();
+        fs::remove_file(out_path).unwrap();
+    }
+}
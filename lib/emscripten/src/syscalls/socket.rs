@@ -0,0 +1,727 @@
+//! The calls of `socketcall`, over the sockets of the host.
+//!
+//! The guest numbers its address families, options and message flags the
+//! way Linux does, and lays its structures out for wasm32, so each call
+//! translates them on their way to the host and back. Every call fails
+//! with `EACCES` unless `EmscriptenConfig::networking` is set, and
+//! `EmscriptenConfig::capabilities`, if there are any, grant `net`.
+use super::unix::host_iovecs;
+use crate::cleanup;
+use crate::env::get_emscripten_data;
+use crate::errno::Errno;
+use crate::utils::{guest_memory_range, read_guest_bytes, write_guest_bytes};
+use crate::varargs::VarArgs;
+use crate::vfs;
+use byteorder::{ByteOrder, LittleEndian, NativeEndian};
+use libc::{
+    c_int, c_void, msghdr, sockaddr, sockaddr_in, sockaddr_in6, sockaddr_storage, sockaddr_un,
+    socklen_t,
+};
+use std::mem;
+use wasmer_runtime_core::vm::Ctx;
+
+// The address families of the guest.
+const AF_UNIX: c_int = 1;
+const AF_INET: c_int = 2;
+const AF_INET6: c_int = 10;
+
+// The flags the guest can add to the type of a socket.
+const SOCK_TYPE_MASK: c_int = 0xf;
+const SOCK_NONBLOCK: c_int = 0o4000;
+
+const SOL_SOCKET: c_int = 1;
+// The protocol numbers are the same everywhere.
+const IPPROTO_TCP: c_int = 6;
+const IPPROTO_IPV6: c_int = 41;
+
+/// The message flags of the guest, with the ones of the host.
+const MSG_FLAGS: &[(c_int, c_int)] = &[
+    (0x1, libc::MSG_OOB),
+    (0x2, libc::MSG_PEEK),
+    (0x4, libc::MSG_DONTROUTE),
+    (0x8, libc::MSG_CTRUNC),
+    (0x20, libc::MSG_TRUNC),
+    (0x40, libc::MSG_DONTWAIT),
+    (0x80, libc::MSG_EOR),
+    (0x100, libc::MSG_WAITALL),
+];
+
+/// Keeps a send to a closed connection from raising `SIGPIPE` in the host,
+/// where it would kill the process rather than the guest.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const SEND_FLAGS: c_int = libc::MSG_NOSIGNAL;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+const SEND_FLAGS: c_int = 0;
+
+/// The size of the guest's `struct msghdr`, which is seven words.
+const MSGHDR_SIZE: u32 = 28;
+
+/// How the value of a socket option is laid out.
+#[derive(Clone, Copy)]
+enum OptionValue {
+    Int,
+    /// An `int` holding an error number.
+    Error,
+    /// A `struct linger`, which is two `int`s.
+    Linger,
+    /// A `struct timeval`, which is `{ i32 tv_sec; i32 tv_usec }`.
+    Timeval,
+}
+
+/// The level and the name of the host option for the guest's.
+fn host_option(level: c_int, name: c_int) -> Option<(c_int, c_int, OptionValue)> {
+    use self::OptionValue::*;
+    let option = match (level, name) {
+        (SOL_SOCKET, 2) => (libc::SOL_SOCKET, libc::SO_REUSEADDR, Int),
+        (SOL_SOCKET, 3) => (libc::SOL_SOCKET, libc::SO_TYPE, Int),
+        (SOL_SOCKET, 4) => (libc::SOL_SOCKET, libc::SO_ERROR, Error),
+        (SOL_SOCKET, 6) => (libc::SOL_SOCKET, libc::SO_BROADCAST, Int),
+        (SOL_SOCKET, 7) => (libc::SOL_SOCKET, libc::SO_SNDBUF, Int),
+        (SOL_SOCKET, 8) => (libc::SOL_SOCKET, libc::SO_RCVBUF, Int),
+        (SOL_SOCKET, 9) => (libc::SOL_SOCKET, libc::SO_KEEPALIVE, Int),
+        (SOL_SOCKET, 13) => (libc::SOL_SOCKET, libc::SO_LINGER, Linger),
+        (SOL_SOCKET, 15) => (libc::SOL_SOCKET, libc::SO_REUSEPORT, Int),
+        (SOL_SOCKET, 20) => (libc::SOL_SOCKET, libc::SO_RCVTIMEO, Timeval),
+        (SOL_SOCKET, 21) => (libc::SOL_SOCKET, libc::SO_SNDTIMEO, Timeval),
+        (IPPROTO_TCP, 1) => (libc::IPPROTO_TCP, libc::TCP_NODELAY, Int),
+        (IPPROTO_IPV6, 26) => (libc::IPPROTO_IPV6, libc::IPV6_V6ONLY, Int),
+        _ => return None,
+    };
+    Some(option)
+}
+
+fn host_msg_flags(flags: c_int) -> c_int {
+    MSG_FLAGS
+        .iter()
+        .filter(|&&(guest, _)| flags & guest != 0)
+        .fold(0, |host_flags, &(_, host)| host_flags | host)
+}
+
+fn guest_msg_flags(flags: c_int) -> c_int {
+    MSG_FLAGS
+        .iter()
+        .filter(|&&(_, host)| flags & host != 0)
+        .fold(0, |guest_flags, &(guest, _)| guest_flags | guest)
+}
+
+fn cvt(ret: c_int) -> Result<c_int, Errno> {
+    if ret < 0 {
+        Err(Errno::last())
+    } else {
+        Ok(ret)
+    }
+}
+
+fn cvt_size(ret: isize) -> Result<c_int, Errno> {
+    if ret < 0 {
+        Err(Errno::last())
+    } else {
+        Ok(ret as c_int)
+    }
+}
+
+fn guest_bytes(ctx: &Ctx, ptr: u32, len: u32) -> Result<Vec<u8>, Errno> {
+    read_guest_bytes(ctx, ptr, len).ok_or(Errno::EFAULT)
+}
+
+fn write_guest(ctx: &Ctx, ptr: u32, bytes: &[u8]) -> Result<(), Errno> {
+    write_guest_bytes(ctx, ptr, bytes).ok_or(Errno::EFAULT)
+}
+
+fn read_u32(ctx: &Ctx, ptr: u32) -> Result<u32, Errno> {
+    Ok(LittleEndian::read_u32(&guest_bytes(ctx, ptr, 4)?))
+}
+
+fn write_u32(ctx: &Ctx, ptr: u32, value: u32) -> Result<(), Errno> {
+    let mut bytes = [0; 4];
+    LittleEndian::write_u32(&mut bytes, value);
+    write_guest(ctx, ptr, &bytes)
+}
+
+/// Read the guest address at `addr` as an address of the host.
+fn read_sockaddr(ctx: &Ctx, addr: u32, len: u32) -> Result<(sockaddr_storage, socklen_t), Errno> {
+    let bytes = guest_bytes(ctx, addr, len)?;
+    if bytes.len() < 2 {
+        return Err(Errno::EINVAL);
+    }
+    let mut storage: sockaddr_storage = unsafe { mem::zeroed() };
+    let host_len = match c_int::from(LittleEndian::read_u16(&bytes)) {
+        AF_INET => {
+            if bytes.len() < 16 {
+                return Err(Errno::EINVAL);
+            }
+            let host = unsafe { &mut *(&mut storage as *mut _ as *mut sockaddr_in) };
+            host.sin_family = libc::AF_INET as _;
+            host.sin_port = NativeEndian::read_u16(&bytes[2..4]);
+            host.sin_addr.s_addr = NativeEndian::read_u32(&bytes[4..8]);
+            mem::size_of::<sockaddr_in>()
+        }
+        AF_INET6 => {
+            if bytes.len() < 28 {
+                return Err(Errno::EINVAL);
+            }
+            let host = unsafe { &mut *(&mut storage as *mut _ as *mut sockaddr_in6) };
+            host.sin6_family = libc::AF_INET6 as _;
+            host.sin6_port = NativeEndian::read_u16(&bytes[2..4]);
+            host.sin6_flowinfo = NativeEndian::read_u32(&bytes[4..8]);
+            host.sin6_addr.s6_addr.copy_from_slice(&bytes[8..24]);
+            host.sin6_scope_id = LittleEndian::read_u32(&bytes[24..28]);
+            mem::size_of::<sockaddr_in6>()
+        }
+        AF_UNIX => {
+            let host = unsafe { &mut *(&mut storage as *mut _ as *mut sockaddr_un) };
+            host.sun_family = libc::AF_UNIX as _;
+            let path = &bytes[2..];
+            let path = &path[..path.iter().position(|&b| b == 0).unwrap_or(path.len())];
+            if path.len() >= host.sun_path.len() {
+                return Err(Errno::ENAMETOOLONG);
+            }
+            for (dst, &src) in host.sun_path.iter_mut().zip(path) {
+                *dst = src as _;
+            }
+            mem::size_of::<sockaddr_un>() - host.sun_path.len() + path.len() + 1
+        }
+        _ => return Err(Errno::EAFNOSUPPORT),
+    };
+    Ok((storage, host_len as socklen_t))
+}
+
+/// The host address `storage` as the guest lays it out.
+fn guest_sockaddr(storage: &sockaddr_storage, len: socklen_t) -> Vec<u8> {
+    let mut bytes = vec![0; 2];
+    match c_int::from(storage.ss_family) {
+        libc::AF_INET => {
+            let host = unsafe { &*(storage as *const _ as *const sockaddr_in) };
+            LittleEndian::write_u16(&mut bytes, AF_INET as u16);
+            bytes.resize(16, 0);
+            NativeEndian::write_u16(&mut bytes[2..4], host.sin_port);
+            NativeEndian::write_u32(&mut bytes[4..8], host.sin_addr.s_addr);
+        }
+        libc::AF_INET6 => {
+            let host = unsafe { &*(storage as *const _ as *const sockaddr_in6) };
+            LittleEndian::write_u16(&mut bytes, AF_INET6 as u16);
+            bytes.resize(28, 0);
+            NativeEndian::write_u16(&mut bytes[2..4], host.sin6_port);
+            NativeEndian::write_u32(&mut bytes[4..8], host.sin6_flowinfo);
+            bytes[8..24].copy_from_slice(&host.sin6_addr.s6_addr);
+            LittleEndian::write_u32(&mut bytes[24..28], host.sin6_scope_id);
+        }
+        libc::AF_UNIX => {
+            let host = unsafe { &*(storage as *const _ as *const sockaddr_un) };
+            LittleEndian::write_u16(&mut bytes, AF_UNIX as u16);
+            let path_offset = mem::size_of::<sockaddr_un>() - host.sun_path.len();
+            let path_len = (len as usize)
+                .saturating_sub(path_offset)
+                .min(host.sun_path.len());
+            bytes.extend(host.sun_path[..path_len].iter().map(|&c| c as u8));
+        }
+        family => LittleEndian::write_u16(&mut bytes, family as u16),
+    }
+    bytes
+}
+
+/// Write the host address `storage` to the guest buffer at `addr`, as much
+/// as `room` bytes of it, and return its whole length.
+fn put_sockaddr(
+    ctx: &Ctx,
+    storage: &sockaddr_storage,
+    len: socklen_t,
+    addr: u32,
+    room: u32,
+) -> Result<u32, Errno> {
+    let bytes = guest_sockaddr(storage, len);
+    let copied = bytes.len().min(room as usize);
+    write_guest(ctx, addr, &bytes[..copied])?;
+    Ok(bytes.len() as u32)
+}
+
+/// Write the host address `storage` to the guest buffer at `addr`, whose
+/// size is at `len_ptr`, which is then set to the length of the address.
+fn write_sockaddr(
+    ctx: &Ctx,
+    storage: &sockaddr_storage,
+    len: socklen_t,
+    addr: u32,
+    len_ptr: u32,
+) -> Result<(), Errno> {
+    if addr == 0 {
+        return Ok(());
+    }
+    let room = read_u32(ctx, len_ptr)?;
+    let full_len = put_sockaddr(ctx, storage, len, addr, room)?;
+    write_u32(ctx, len_ptr, full_len)
+}
+
+/// Set a new socket up: close-on-exec, like every fd the guest opens,
+/// non-blocking if `ty` asks, and, where sends can't say so, without
+/// `SIGPIPE`.
+fn set_up(fd: c_int, ty: c_int) -> Result<(), Errno> {
+    unsafe {
+        cvt(libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC))?;
+        if ty & SOCK_NONBLOCK != 0 {
+            let status = cvt(libc::fcntl(fd, libc::F_GETFL))?;
+            cvt(libc::fcntl(fd, libc::F_SETFL, status | libc::O_NONBLOCK))?;
+        }
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        {
+            let on: c_int = 1;
+            cvt(libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_NOSIGPIPE,
+                &on as *const _ as *const c_void,
+                mem::size_of::<c_int>() as socklen_t,
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+/// Set `fd` up, closing it if that fails.
+fn new_fd(fd: c_int, ty: c_int) -> Result<c_int, Errno> {
+    if let Err(errno) = set_up(fd, ty) {
+        unsafe { libc::close(fd) };
+        return Err(errno);
+    }
+    Ok(fd)
+}
+
+/// The next argument, or `EINVAL` if the guest passed too few.
+fn arg<T>(ctx: &mut Ctx, args: &mut VarArgs) -> Result<T, Errno> {
+    args.try_get(ctx).ok_or(Errno::EINVAL)
+}
+
+/// The next argument, a descriptor, as the host knows it.
+fn fd_arg(ctx: &mut Ctx, args: &mut VarArgs) -> Result<c_int, Errno> {
+    let fd: c_int = arg(ctx, args)?;
+    if fd < 0 {
+        return Err(Errno::EBADF);
+    }
+    Ok(vfs::host_fd(ctx, fd))
+}
+
+fn host_family(domain: c_int) -> Result<c_int, Errno> {
+    match domain {
+        AF_UNIX => Ok(libc::AF_UNIX),
+        AF_INET => Ok(libc::AF_INET),
+        AF_INET6 => Ok(libc::AF_INET6),
+        _ => Err(Errno::EAFNOSUPPORT),
+    }
+}
+
+fn socket(ctx: &mut Ctx, mut args: VarArgs) -> Result<c_int, Errno> {
+    let domain: c_int = arg(ctx, &mut args)?;
+    let ty: c_int = arg(ctx, &mut args)?;
+    let protocol: c_int = arg(ctx, &mut args)?;
+    let family = host_family(domain)?;
+    let fd = cvt(unsafe { libc::socket(family, ty & SOCK_TYPE_MASK, protocol) })?;
+    new_fd(fd, ty)
+}
+
+fn socketpair(ctx: &mut Ctx, mut args: VarArgs) -> Result<c_int, Errno> {
+    let domain: c_int = arg(ctx, &mut args)?;
+    let ty: c_int = arg(ctx, &mut args)?;
+    let protocol: c_int = arg(ctx, &mut args)?;
+    let sv: u32 = arg(ctx, &mut args)?;
+    let family = host_family(domain)?;
+    guest_memory_range(ctx, sv, 8).ok_or(Errno::EFAULT)?;
+    let mut fds = [0; 2];
+    cvt(unsafe { libc::socketpair(family, ty & SOCK_TYPE_MASK, protocol, fds.as_mut_ptr()) })?;
+    let close = cleanup::defer(ctx, move || unsafe {
+        libc::close(fds[0]);
+        libc::close(fds[1]);
+    });
+    set_up(fds[0], ty)?;
+    set_up(fds[1], ty)?;
+    write_u32(ctx, sv, fds[0] as u32)?;
+    write_u32(ctx, sv + 4, fds[1] as u32)?;
+    close.dismiss();
+    Ok(0)
+}
+
+/// `bind` and `connect`, which take the same arguments.
+fn with_address(
+    ctx: &mut Ctx,
+    mut args: VarArgs,
+    call: unsafe extern "C" fn(c_int, *const sockaddr, socklen_t) -> c_int,
+) -> Result<c_int, Errno> {
+    let fd = fd_arg(ctx, &mut args)?;
+    let addr: u32 = arg(ctx, &mut args)?;
+    let len: u32 = arg(ctx, &mut args)?;
+    let (storage, host_len) = read_sockaddr(ctx, addr, len)?;
+    cvt(unsafe { call(fd, &storage as *const _ as *const sockaddr, host_len) })
+}
+
+fn listen(ctx: &mut Ctx, mut args: VarArgs) -> Result<c_int, Errno> {
+    let fd = fd_arg(ctx, &mut args)?;
+    let backlog: c_int = arg(ctx, &mut args)?;
+    cvt(unsafe { libc::listen(fd, backlog) })
+}
+
+fn accept(ctx: &mut Ctx, mut args: VarArgs, with_flags: bool) -> Result<c_int, Errno> {
+    let fd = fd_arg(ctx, &mut args)?;
+    let addr: u32 = arg(ctx, &mut args)?;
+    let len_ptr: u32 = arg(ctx, &mut args)?;
+    let flags: c_int = if with_flags { arg(ctx, &mut args)? } else { 0 };
+    let mut storage: sockaddr_storage = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<sockaddr_storage>() as socklen_t;
+    let new = cvt(unsafe { libc::accept(fd, &mut storage as *mut _ as *mut sockaddr, &mut len) })?;
+    let close = cleanup::defer(ctx, move || unsafe {
+        libc::close(new);
+    });
+    set_up(new, flags)?;
+    write_sockaddr(ctx, &storage, len, addr, len_ptr)?;
+    close.dismiss();
+    Ok(new)
+}
+
+/// `getsockname` and `getpeername`, which take the same arguments.
+fn name_of(
+    ctx: &mut Ctx,
+    mut args: VarArgs,
+    call: unsafe extern "C" fn(c_int, *mut sockaddr, *mut socklen_t) -> c_int,
+) -> Result<c_int, Errno> {
+    let fd = fd_arg(ctx, &mut args)?;
+    let addr: u32 = arg(ctx, &mut args)?;
+    let len_ptr: u32 = arg(ctx, &mut args)?;
+    let mut storage: sockaddr_storage = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<sockaddr_storage>() as socklen_t;
+    cvt(unsafe { call(fd, &mut storage as *mut _ as *mut sockaddr, &mut len) })?;
+    write_sockaddr(ctx, &storage, len, addr, len_ptr)?;
+    Ok(0)
+}
+
+/// `send` and `sendto`, which is `send` with an address.
+fn send(ctx: &mut Ctx, mut args: VarArgs, to: bool) -> Result<c_int, Errno> {
+    let fd = fd_arg(ctx, &mut args)?;
+    let buf: u32 = arg(ctx, &mut args)?;
+    let len: u32 = arg(ctx, &mut args)?;
+    let flags: c_int = arg(ctx, &mut args)?;
+    let (addr, addr_len): (u32, u32) = if to {
+        (arg(ctx, &mut args)?, arg(ctx, &mut args)?)
+    } else {
+        (0, 0)
+    };
+    let buf = guest_bytes(ctx, buf, len)?;
+    let flags = host_msg_flags(flags) | SEND_FLAGS;
+    let ret = if addr == 0 {
+        unsafe { libc::send(fd, buf.as_ptr() as *const c_void, buf.len(), flags) }
+    } else {
+        let (storage, host_len) = read_sockaddr(ctx, addr, addr_len)?;
+        unsafe {
+            libc::sendto(
+                fd,
+                buf.as_ptr() as *const c_void,
+                buf.len(),
+                flags,
+                &storage as *const _ as *const sockaddr,
+                host_len,
+            )
+        }
+    };
+    cvt_size(ret)
+}
+
+/// `recv` and `recvfrom`, which is `recv` telling where from.
+fn recv(ctx: &mut Ctx, mut args: VarArgs, from: bool) -> Result<c_int, Errno> {
+    let fd = fd_arg(ctx, &mut args)?;
+    let buf: u32 = arg(ctx, &mut args)?;
+    let len: u32 = arg(ctx, &mut args)?;
+    let flags: c_int = arg(ctx, &mut args)?;
+    let (addr, len_ptr): (u32, u32) = if from {
+        (arg(ctx, &mut args)?, arg(ctx, &mut args)?)
+    } else {
+        (0, 0)
+    };
+    let buf = guest_memory_range(ctx, buf, len).ok_or(Errno::EFAULT)?;
+    let mut storage: sockaddr_storage = unsafe { mem::zeroed() };
+    let mut host_len = mem::size_of::<sockaddr_storage>() as socklen_t;
+    let received = cvt_size(unsafe {
+        libc::recvfrom(
+            fd,
+            buf as *mut c_void,
+            len as usize,
+            host_msg_flags(flags),
+            &mut storage as *mut _ as *mut sockaddr,
+            &mut host_len,
+        )
+    })?;
+    write_sockaddr(ctx, &storage, host_len, addr, len_ptr)?;
+    Ok(received)
+}
+
+fn shutdown(ctx: &mut Ctx, mut args: VarArgs) -> Result<c_int, Errno> {
+    let fd = fd_arg(ctx, &mut args)?;
+    let how: c_int = arg(ctx, &mut args)?;
+    cvt(unsafe { libc::shutdown(fd, how) })
+}
+
+fn setsockopt(ctx: &mut Ctx, mut args: VarArgs) -> Result<c_int, Errno> {
+    let fd = fd_arg(ctx, &mut args)?;
+    let level: c_int = arg(ctx, &mut args)?;
+    let name: c_int = arg(ctx, &mut args)?;
+    let value: u32 = arg(ctx, &mut args)?;
+    let len: u32 = arg(ctx, &mut args)?;
+    let (host_level, host_name, kind) = host_option(level, name).ok_or(Errno::ENOPROTOOPT)?;
+    let bytes = guest_bytes(ctx, value, len)?;
+    let set = |value: *const c_void, size: usize| {
+        cvt(unsafe { libc::setsockopt(fd, host_level, host_name, value, size as socklen_t) })
+    };
+    match kind {
+        OptionValue::Int | OptionValue::Error => {
+            if bytes.len() < 4 {
+                return Err(Errno::EINVAL);
+            }
+            let value = LittleEndian::read_i32(&bytes) as c_int;
+            set(&value as *const _ as *const c_void, mem::size_of::<c_int>())
+        }
+        OptionValue::Linger => {
+            if bytes.len() < 8 {
+                return Err(Errno::EINVAL);
+            }
+            let value = libc::linger {
+                l_onoff: LittleEndian::read_i32(&bytes[0..4]),
+                l_linger: LittleEndian::read_i32(&bytes[4..8]),
+            };
+            set(
+                &value as *const _ as *const c_void,
+                mem::size_of::<libc::linger>(),
+            )
+        }
+        OptionValue::Timeval => {
+            if bytes.len() < 8 {
+                return Err(Errno::EINVAL);
+            }
+            let value = libc::timeval {
+                tv_sec: LittleEndian::read_i32(&bytes[0..4]) as _,
+                tv_usec: LittleEndian::read_i32(&bytes[4..8]) as _,
+            };
+            set(
+                &value as *const _ as *const c_void,
+                mem::size_of::<libc::timeval>(),
+            )
+        }
+    }
+}
+
+fn getsockopt(ctx: &mut Ctx, mut args: VarArgs) -> Result<c_int, Errno> {
+    let fd = fd_arg(ctx, &mut args)?;
+    let level: c_int = arg(ctx, &mut args)?;
+    let name: c_int = arg(ctx, &mut args)?;
+    let value: u32 = arg(ctx, &mut args)?;
+    let len_ptr: u32 = arg(ctx, &mut args)?;
+    let (host_level, host_name, kind) = host_option(level, name).ok_or(Errno::ENOPROTOOPT)?;
+    let room = read_u32(ctx, len_ptr)?;
+    let get = |value: *mut c_void, size: usize| {
+        let mut size = size as socklen_t;
+        cvt(unsafe { libc::getsockopt(fd, host_level, host_name, value, &mut size) })
+    };
+    let mut guest = [0; 8];
+    let guest_len = match kind {
+        OptionValue::Int | OptionValue::Error => {
+            let mut value: c_int = 0;
+            get(&mut value as *mut _ as *mut c_void, mem::size_of::<c_int>())?;
+            if let OptionValue::Error = kind {
+                if value != 0 {
+                    value = Errno::from_unix(value).to_emscripten();
+                }
+            }
+            LittleEndian::write_i32(&mut guest, value);
+            4
+        }
+        OptionValue::Linger => {
+            let mut value: libc::linger = unsafe { mem::zeroed() };
+            get(
+                &mut value as *mut _ as *mut c_void,
+                mem::size_of::<libc::linger>(),
+            )?;
+            LittleEndian::write_i32(&mut guest[0..4], value.l_onoff);
+            LittleEndian::write_i32(&mut guest[4..8], value.l_linger);
+            8
+        }
+        OptionValue::Timeval => {
+            let mut value: libc::timeval = unsafe { mem::zeroed() };
+            get(
+                &mut value as *mut _ as *mut c_void,
+                mem::size_of::<libc::timeval>(),
+            )?;
+            LittleEndian::write_i32(&mut guest[0..4], value.tv_sec as i32);
+            LittleEndian::write_i32(&mut guest[4..8], value.tv_usec as i32);
+            8
+        }
+    };
+    let copied = guest_len.min(room as usize);
+    write_guest(ctx, value, &guest[..copied])?;
+    write_u32(ctx, len_ptr, guest_len as u32)?;
+    Ok(0)
+}
+
+/// The fields of the guest's `struct msghdr` at `msg`.
+struct GuestMsghdr {
+    name: u32,
+    namelen: u32,
+    iov: u32,
+    iovlen: u32,
+    controllen: u32,
+}
+
+fn read_msghdr(ctx: &Ctx, msg: u32) -> Result<GuestMsghdr, Errno> {
+    let bytes = guest_bytes(ctx, msg, MSGHDR_SIZE)?;
+    let word = |index: usize| LittleEndian::read_u32(&bytes[index * 4..index * 4 + 4]);
+    Ok(GuestMsghdr {
+        name: word(0),
+        namelen: word(1),
+        iov: word(2),
+        iovlen: word(3),
+        controllen: word(5),
+    })
+}
+
+fn sendmsg(ctx: &mut Ctx, mut args: VarArgs) -> Result<c_int, Errno> {
+    let fd = fd_arg(ctx, &mut args)?;
+    let msg: u32 = arg(ctx, &mut args)?;
+    let flags: c_int = arg(ctx, &mut args)?;
+    let guest = read_msghdr(ctx, msg)?;
+    // There's no translation of ancillary data.
+    if guest.controllen != 0 {
+        return Err(Errno::ENOTSUP);
+    }
+    let mut iovecs = Vec::new();
+    if !host_iovecs(ctx, guest.iov, guest.iovlen as i32, &mut iovecs) {
+        return Err(Errno::EFAULT);
+    }
+    let mut hdr: msghdr = unsafe { mem::zeroed() };
+    let mut storage: sockaddr_storage = unsafe { mem::zeroed() };
+    if guest.name != 0 {
+        let (address, host_len) = read_sockaddr(ctx, guest.name, guest.namelen)?;
+        storage = address;
+        hdr.msg_name = &mut storage as *mut _ as *mut c_void;
+        hdr.msg_namelen = host_len;
+    }
+    hdr.msg_iov = iovecs.as_mut_ptr();
+    hdr.msg_iovlen = iovecs.len() as _;
+    cvt_size(unsafe { libc::sendmsg(fd, &hdr, host_msg_flags(flags) | SEND_FLAGS) })
+}
+
+fn recvmsg(ctx: &mut Ctx, mut args: VarArgs) -> Result<c_int, Errno> {
+    let fd = fd_arg(ctx, &mut args)?;
+    let msg: u32 = arg(ctx, &mut args)?;
+    let flags: c_int = arg(ctx, &mut args)?;
+    let guest = read_msghdr(ctx, msg)?;
+    let mut iovecs = Vec::new();
+    if !host_iovecs(ctx, guest.iov, guest.iovlen as i32, &mut iovecs) {
+        return Err(Errno::EFAULT);
+    }
+    let mut hdr: msghdr = unsafe { mem::zeroed() };
+    let mut storage: sockaddr_storage = unsafe { mem::zeroed() };
+    if guest.name != 0 {
+        hdr.msg_name = &mut storage as *mut _ as *mut c_void;
+        hdr.msg_namelen = mem::size_of::<sockaddr_storage>() as socklen_t;
+    }
+    hdr.msg_iov = iovecs.as_mut_ptr();
+    hdr.msg_iovlen = iovecs.len() as _;
+    let received = cvt_size(unsafe { libc::recvmsg(fd, &mut hdr, host_msg_flags(flags)) })?;
+
+    let namelen = if guest.name != 0 {
+        put_sockaddr(ctx, &storage, hdr.msg_namelen, guest.name, guest.namelen)?
+    } else {
+        0
+    };
+    write_u32(ctx, msg + 4, namelen)?;
+    // No ancillary data is passed on.
+    write_u32(ctx, msg + 20, 0)?;
+    write_u32(ctx, msg + 24, guest_msg_flags(hdr.msg_flags) as u32)?;
+    Ok(received)
+}
+
+fn networking(ctx: &mut Ctx) -> bool {
+    if ctx.data.is_null() {
+        return false;
+    }
+    let config = &get_emscripten_data(ctx).config;
+    config.networking
+        && config
+            .capabilities
+            .as_ref()
+            .map_or(true, |granted| granted.net)
+}
+
+/// Make the socket call `call`, with the arguments in `args`.
+pub fn socketcall(ctx: &mut Ctx, call: u32, args: VarArgs) -> c_int {
+    if !networking(ctx) {
+        return -Errno::EACCES.to_emscripten();
+    }
+    let result = match call {
+        1 => socket(ctx, args),
+        2 => with_address(ctx, args, libc::bind),
+        3 => with_address(ctx, args, libc::connect),
+        4 => listen(ctx, args),
+        5 => accept(ctx, args, false),
+        6 => name_of(ctx, args, libc::getsockname),
+        7 => name_of(ctx, args, libc::getpeername),
+        8 => socketpair(ctx, args),
+        9 => send(ctx, args, false),
+        10 => recv(ctx, args, false),
+        11 => send(ctx, args, true),
+        12 => recv(ctx, args, true),
+        13 => shutdown(ctx, args),
+        14 => setsockopt(ctx, args),
+        15 => getsockopt(ctx, args),
+        16 => sendmsg(ctx, args),
+        17 => recvmsg(ctx, args),
+        18 => accept(ctx, args, true),
+        _ => Err(Errno::ENOSYS),
+    };
+    debug!("=> call: {}, result: {:?}", call, result);
+    match result {
+        Ok(ret) => ret,
+        Err(errno) => -errno.to_emscripten(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_message_flags() {
+        let guest = 0x2 | 0x40 | 0x100;
+        assert_eq!(
+            host_msg_flags(guest),
+            libc::MSG_PEEK | libc::MSG_DONTWAIT | libc::MSG_WAITALL
+        );
+        assert_eq!(guest_msg_flags(host_msg_flags(guest)), guest);
+        assert_eq!(host_msg_flags(0x10000), 0);
+    }
+
+    #[test]
+    fn round_trips_addresses() {
+        let mut storage: sockaddr_storage = unsafe { mem::zeroed() };
+        {
+            let host = unsafe { &mut *(&mut storage as *mut _ as *mut sockaddr_in6) };
+            host.sin6_family = libc::AF_INET6 as _;
+            host.sin6_port = 8080u16.to_be();
+            host.sin6_addr.s6_addr[15] = 1;
+            host.sin6_scope_id = 3;
+        }
+        let guest = guest_sockaddr(&storage, mem::size_of::<sockaddr_in6>() as socklen_t);
+        assert_eq!(guest.len(), 28);
+        assert_eq!(&guest[0..4], &[10, 0, 0x1f, 0x90]);
+        assert_eq!(guest[23], 1);
+        assert_eq!(guest[24], 3);
+
+        let mut unix: sockaddr_storage = unsafe { mem::zeroed() };
+        let path = b"/tmp/socket";
+        let len = {
+            let host = unsafe { &mut *(&mut unix as *mut _ as *mut sockaddr_un) };
+            host.sun_family = libc::AF_UNIX as _;
+            for (dst, &src) in host.sun_path.iter_mut().zip(path.iter()) {
+                *dst = src as _;
+            }
+            mem::size_of::<sockaddr_un>() - host.sun_path.len() + path.len()
+        };
+        let guest = guest_sockaddr(&unix, len as socklen_t);
+        assert_eq!(&guest[0..2], &[1, 0]);
+        assert_eq!(&guest[2..], &path[..]);
+    }
+}
@@ -1,62 +1,47 @@
+use super::socket;
+use crate::errno::{translate_ret, Errno};
+use crate::inotify;
+use crate::scratch;
+use crate::stat_cache::get_stat_cache;
+use crate::stdio;
+use crate::tty;
+use crate::utils::{guest_memory_range, read_guest_iovec};
 use crate::varargs::VarArgs;
+use crate::vfs;
 /// NOTE: TODO: These syscalls only support wasm_32 for now because they assume offsets are u32
 /// Syscall list: https://www.cs.utexas.edu/~bismith/test/syscalls/syscalls32.html
 use libc::{
-    accept,
-    bind,
     // ENOTTY,
-    c_char,
     c_int,
     c_void,
     chown,
     // fcntl, setsockopt, getppid
-    connect,
     dup2,
     fcntl,
-    getgid,
-    getpeername,
-    getsockname,
-    getsockopt,
-    in_addr_t,
-    in_port_t,
     ioctl,
-    // iovec,
-    listen,
+    iovec,
     mkdir,
-    msghdr,
     pid_t,
     pread,
     pwrite,
-    // readv,
-    recvfrom,
-    recvmsg,
+    readv,
     // ENOTTY,
     rusage,
-    sa_family_t,
-    // writev,
     select,
-    sendmsg,
-    sendto,
     setpgid,
-    setsockopt,
-    sockaddr,
-    socket,
-    socklen_t,
     uname,
     utsname,
-    EINVAL,
+    writev,
     // sockaddr_in,
-    FIOCLEX,
     FIONBIO,
     F_GETFD,
     F_SETFD,
-    SOL_SOCKET,
-    SO_REUSEADDR,
     TIOCGWINSZ,
 };
 use wasmer_runtime_core::vm::Ctx;
 
-use std::mem;
+use std::ffi::CString;
+use std::slice;
 
 // Linking to functions that are not provided by rust libc
 #[cfg(target_os = "macos")]
@@ -68,14 +53,6 @@ extern "C" {
 #[cfg(not(target_os = "macos"))]
 use libc::wait4;
 
-// Another conditional constant for name resolution: Macos et iOS use
-// SO_NOSIGPIPE as a setsockopt flag to disable SIGPIPE emission on socket.
-// Other platforms do otherwise.
-#[cfg(target_os = "darwin")]
-use libc::SO_NOSIGPIPE;
-#[cfg(not(target_os = "darwin"))]
-const SO_NOSIGPIPE: c_int = 0;
-
 // chown
 pub fn ___syscall212(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall212 (chown) {}", which);
@@ -83,10 +60,19 @@ pub fn ___syscall212(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int
     let pathname: u32 = varargs.get(ctx);
     let owner: u32 = varargs.get(ctx);
     let group: u32 = varargs.get(ctx);
+    let path = match vfs::resolve(ctx, pathname) {
+        Ok(path) => path,
+        Err(errno) => return -errno.to_emscripten(),
+    };
+    chown_path(ctx, path, owner, group)
+}
 
-    let pathname_addr = emscripten_memory_pointer!(ctx.memory(0), pathname) as *const i8;
+pub(crate) fn chown_path(ctx: &mut Ctx, path: CString, owner: u32, group: u32) -> c_int {
+    if let Some(cache) = get_stat_cache(ctx) {
+        cache.invalidate(&path);
+    }
 
-    unsafe { chown(pathname_addr, owner, group) }
+    translate_ret(unsafe { chown(path.as_ptr(), owner, group) })
 }
 
 // mkdir
@@ -94,27 +80,26 @@ pub fn ___syscall39(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int
     debug!("emscripten::___syscall39 (mkdir) {}", which);
     let pathname: u32 = varargs.get(ctx);
     let mode: u32 = varargs.get(ctx);
-    let pathname_addr = emscripten_memory_pointer!(ctx.memory(0), pathname) as *const i8;
-    unsafe { mkdir(pathname_addr, mode as _) }
+    let path = match vfs::resolve(ctx, pathname) {
+        Ok(path) => path,
+        Err(errno) => return -errno.to_emscripten(),
+    };
+    mkdir_path(ctx, path, mode)
 }
 
-// getgid
-pub fn ___syscall201(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::___syscall201 (getgid)");
-    unsafe {
-        // Maybe fix: Emscripten returns 0 always
-        getgid() as i32
+pub(crate) fn mkdir_path(ctx: &mut Ctx, path: CString, mode: u32) -> c_int {
+    if let Some(cache) = get_stat_cache(ctx) {
+        cache.entry_changed(&path);
     }
-}
-
-// getgid32
-pub fn ___syscall202(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
-    // gid_t
-    debug!("emscripten::___syscall202 (getgid32)");
-    unsafe {
-        // Maybe fix: Emscripten returns 0 always
-        getgid() as _
+    let mode = vfs::creation_mode(ctx, mode);
+    let ret = unsafe { mkdir(path.as_ptr(), mode as _) };
+    if ret == 0 {
+        // The host applied its own umask on top of the guest's.
+        unsafe { libc::chmod(path.as_ptr(), mode as _) };
+        vfs::set_mode(ctx, &path, mode);
+        inotify::created(ctx, &path, true);
     }
+    translate_ret(ret)
 }
 
 /// dup3
@@ -122,14 +107,22 @@ pub fn ___syscall330(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> pid_
     // Implementation based on description at https://linux.die.net/man/2/dup3
     debug!("emscripten::___syscall330 (dup3)");
     let oldfd: c_int = varargs.get(ctx);
-    let newfd: c_int = varargs.get(ctx);
+    let oldfd = vfs::host_fd(ctx, oldfd);
+    let guest_newfd: c_int = varargs.get(ctx);
+    let newfd = vfs::host_fd(ctx, guest_newfd);
     let flags: c_int = varargs.get(ctx);
 
     if oldfd == newfd {
-        return EINVAL;
+        return -Errno::EINVAL.to_emscripten();
     }
 
-    let res = unsafe { dup2(oldfd, newfd) };
+    if let Some(cache) = get_stat_cache(ctx) {
+        cache.closed(newfd);
+    }
+    let res = translate_ret(unsafe { dup2(oldfd, newfd) });
+    if res >= 0 {
+        vfs::duplicated(ctx, oldfd, newfd);
+    }
 
     // Set flags on newfd (https://www.gnu.org/software/libc/manual/html_node/Descriptor-Flags.html)
     let mut old_flags = unsafe { fcntl(newfd, F_GETFD, 0) };
@@ -148,22 +141,30 @@ pub fn ___syscall330(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> pid_
         "=> oldfd: {}, newfd: {}, flags: {} = pid: {}",
         oldfd, newfd, flags, res
     );
-    res
+    if res >= 0 {
+        guest_newfd
+    } else {
+        res
+    }
 }
 
 /// ioctl
 pub fn ___syscall54(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall54 (ioctl) {}", which);
     let fd: i32 = varargs.get(ctx);
+    let fd = vfs::host_fd(ctx, fd);
     let request: u32 = varargs.get(ctx);
     debug!("fd: {}, op: {}", fd, request);
+    if let Some(ret) = tty::ioctl(ctx, fd, request, &mut varargs) {
+        return ret;
+    }
     // Got the equivalents here: https://code.woboq.org/linux/linux/include/uapi/asm-generic/ioctls.h.html
     match request as _ {
         21537 => {
             // FIONBIO
             let argp: u32 = varargs.get(ctx);
             let argp_ptr = emscripten_memory_pointer!(ctx.memory(0), argp) as *mut c_void;
-            let ret = unsafe { ioctl(fd, FIONBIO, argp_ptr) };
+            let ret = translate_ret(unsafe { ioctl(fd, FIONBIO, argp_ptr) });
             debug!("ret(FIONBIO): {}", ret);
             ret
             // 0
@@ -194,262 +195,110 @@ pub fn ___syscall54(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int
 }
 
 // socketcall
-#[allow(clippy::cast_ptr_alignment)]
 pub fn ___syscall102(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall102 (socketcall) {}", which);
-    let call: u32 = varargs.get(ctx);
-    let mut socket_varargs: VarArgs = varargs.get(ctx);
-
-    #[repr(C)]
-    pub struct GuestSockaddrIn {
-        pub sin_family: sa_family_t, // u16
-        pub sin_port: in_port_t,     // u16
-        pub sin_addr: GuestInAddr,   // u32
-        pub sin_zero: [u8; 8],       // u8 * 8
-                                     // 2 + 2 + 4 + 8 = 16
-    }
-
-    #[repr(C)]
-    pub struct GuestInAddr {
-        pub s_addr: in_addr_t, // u32
+    let call: Option<u32> = varargs.try_get(ctx);
+    let socket_varargs: Option<VarArgs> = varargs.try_get(ctx);
+    match (call, socket_varargs) {
+        (Some(call), Some(socket_varargs)) => socket::socketcall(ctx, call, socket_varargs),
+        _ => -Errno::EINVAL.to_emscripten(),
     }
+}
 
-    // debug!("GuestSockaddrIn = {}", size_of::<GuestSockaddrIn>());
-
-    pub struct LinuxSockAddr {
-        pub sa_family: u16,
-        pub sa_data: [c_char; 14],
+/// Translates the guest iovec array at `iov` into host iovecs that point
+/// straight into guest memory, so that readv/writev need no bounce buffers.
+/// Returns `false` if the array or any buffer it describes is out of bounds.
+pub(super) fn host_iovecs(ctx: &Ctx, iov: u32, iovcnt: i32, iovecs: &mut Vec<iovec>) -> bool {
+    if iovcnt < 0 {
+        return false;
     }
-
-    match call {
-        1 => {
-            debug!("socket: socket");
-            // socket (domain: c_int, ty: c_int, protocol: c_int) -> c_int
-            let domain: i32 = socket_varargs.get(ctx);
-            let ty: i32 = socket_varargs.get(ctx);
-            let protocol: i32 = socket_varargs.get(ctx);
-            let fd = unsafe { socket(domain, ty, protocol) };
-            // set_cloexec
-            unsafe {
-                ioctl(fd, FIOCLEX);
-            };
-
-            type T = u32;
-            let payload = 1 as *const T as _;
-            unsafe {
-                setsockopt(
-                    fd,
-                    SOL_SOCKET,
-                    SO_NOSIGPIPE,
-                    payload,
-                    mem::size_of::<T>() as socklen_t,
-                );
-            };
-
-            debug!(
-                "=> domain: {} (AF_INET/2), type: {} (SOCK_STREAM/1), protocol: {} = fd: {}",
-                domain, ty, protocol, fd
-            );
-            fd as _
+    for i in 0..iovcnt as u32 {
+        let guest_iov = match read_guest_iovec(ctx, iov, i) {
+            Some(guest_iov) => guest_iov,
+            None => return false,
+        };
+        match guest_memory_range(ctx, guest_iov.iov_base, guest_iov.iov_len) {
+            Some(base) => iovecs.push(iovec {
+                iov_base: base as *mut c_void,
+                iov_len: guest_iov.iov_len as usize,
+            }),
+            None => return false,
         }
-        2 => {
-            debug!("socket: bind");
-            // bind (socket: c_int, address: *const sockaddr, address_len: socklen_t) -> c_int
-            // TODO: Emscripten has a different signature.
-            let socket = socket_varargs.get(ctx);
-            let address: u32 = socket_varargs.get(ctx);
-            let address_len = socket_varargs.get(ctx);
-            let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
-
-            // Debug received address
-            unsafe {
-                let proper_address = address as *const GuestSockaddrIn;
-                debug!(
-                    "=> address.sin_family: {:?}, address.sin_port: {:?}, address.sin_addr.s_addr: {:?}",
-                    (*proper_address).sin_family, (*proper_address).sin_port, (*proper_address).sin_addr.s_addr
-                );
-            }
+    }
+    true
+}
 
-            let status = unsafe { bind(socket, address, address_len) };
-            // debug!("=> status: {}", status);
-            debug!(
-                "=> socketfd: {}, address: {:?}, address_len: {} = status: {}",
-                socket, address, address_len, status
-            );
-            status
-            // -1
-        }
-        3 => {
-            debug!("socket: connect");
-            // connect (socket: c_int, address: *const sockaddr, len: socklen_t) -> c_int
-            // TODO: Emscripten has a different signature.
-            let socket = socket_varargs.get(ctx);
-            let address: u32 = socket_varargs.get(ctx);
-            let address_len = socket_varargs.get(ctx);
-            let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
-            unsafe { connect(socket, address, address_len) }
-        }
-        4 => {
-            debug!("socket: listen");
-            // listen (socket: c_int, backlog: c_int) -> c_int
-            let socket = socket_varargs.get(ctx);
-            let backlog: i32 = socket_varargs.get(ctx);
-            let status = unsafe { listen(socket, backlog) };
-            debug!(
-                "=> socketfd: {}, backlog: {} = status: {}",
-                socket, backlog, status
-            );
-            status
+/// readv
+pub fn ___syscall145(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> i32 {
+    // -> ssize_t
+    debug!("emscripten::___syscall145 (readv) {}", which);
+    let fd: i32 = varargs.get(ctx);
+    let fd = vfs::host_fd(ctx, fd);
+    let iov: u32 = varargs.get(ctx);
+    let iovcnt: i32 = varargs.get(ctx);
+    debug!("=> fd: {}, iov: {}, iovcnt = {}", fd, iov, iovcnt);
+
+    let mut iovecs = scratch::take_iovecs(ctx);
+    let ret = if host_iovecs(ctx, iov, iovcnt, &mut iovecs) {
+        let bufs = iovecs.iter().map(|iovec| unsafe {
+            slice::from_raw_parts_mut(iovec.iov_base as *mut u8, iovec.iov_len)
+        });
+        match stdio::read_input_vectored(ctx, fd, bufs) {
+            Some(ret) => ret,
+            None => translate_ret(unsafe { readv(fd, iovecs.as_ptr(), iovecs.len() as _) as _ }),
         }
-        5 => {
-            debug!("socket: accept");
-            // accept (socket: c_int, address: *mut sockaddr, address_len: *mut socklen_t) -> c_int
-            let socket = socket_varargs.get(ctx);
-            let address_addr: u32 = socket_varargs.get(ctx);
-            let address_len: u32 = socket_varargs.get(ctx);
-            let address = emscripten_memory_pointer!(ctx.memory(0), address_addr) as *mut sockaddr;
-
-            debug!(
-                "=> socket: {}, address: {:?}, address_len: {}",
-                socket, address, address_len
-            );
-            let address_len_addr =
-                emscripten_memory_pointer!(ctx.memory(0), address_len) as *mut socklen_t;
-            // let mut address_len_addr: socklen_t = 0;
-
-            let fd = unsafe { accept(socket, address, address_len_addr) };
-
-            unsafe {
-                let address_linux =
-                    emscripten_memory_pointer!(ctx.memory(0), address_addr) as *mut LinuxSockAddr;
-                (*address_linux).sa_family = (*address).sa_family as u16;
-                (*address_linux).sa_data = (*address).sa_data;
-            };
-
-            // set_cloexec
-            unsafe {
-                ioctl(fd, FIOCLEX);
-            };
-
-            debug!("fd: {}", fd);
+    } else {
+        -Errno::EFAULT.to_emscripten()
+    };
+    scratch::put_iovecs(ctx, iovecs);
+    ret
+}
 
-            fd as _
-        }
-        6 => {
-            debug!("socket: getsockname");
-            // getsockname (socket: c_int, address: *mut sockaddr, address_len: *mut socklen_t) -> c_int
-            let socket = socket_varargs.get(ctx);
-            let address: u32 = socket_varargs.get(ctx);
-            let address_len: u32 = socket_varargs.get(ctx);
-            let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
-            let address_len_addr =
-                emscripten_memory_pointer!(ctx.memory(0), address_len) as *mut socklen_t;
-            unsafe { getsockname(socket, address, address_len_addr) }
-        }
-        7 => {
-            debug!("socket: getpeername");
-            // getpeername (socket: c_int, address: *mut sockaddr, address_len: *mut socklen_t) -> c_int
-            let socket = socket_varargs.get(ctx);
-            let address: u32 = socket_varargs.get(ctx);
-            let address_len: u32 = socket_varargs.get(ctx);
-            let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
-            let address_len_addr =
-                emscripten_memory_pointer!(ctx.memory(0), address_len) as *mut socklen_t;
-            unsafe { getpeername(socket, address, address_len_addr) }
-        }
-        11 => {
-            debug!("socket: sendto");
-            // sendto (socket: c_int, buf: *const c_void, len: size_t, flags: c_int, addr: *const sockaddr, addrlen: socklen_t) -> ssize_t
-            let socket = socket_varargs.get(ctx);
-            let buf: u32 = socket_varargs.get(ctx);
-            let flags = socket_varargs.get(ctx);
-            let len: i32 = socket_varargs.get(ctx);
-            let address: u32 = socket_varargs.get(ctx);
-            let address_len = socket_varargs.get(ctx);
-            let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as _;
-            let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
-            unsafe { sendto(socket, buf_addr, flags, len, address, address_len) as i32 }
-        }
-        12 => {
-            debug!("socket: recvfrom");
-            // recvfrom (socket: c_int, buf: *const c_void, len: size_t, flags: c_int, addr: *const sockaddr, addrlen: socklen_t) -> ssize_t
-            let socket = socket_varargs.get(ctx);
-            let buf: u32 = socket_varargs.get(ctx);
-            let flags = socket_varargs.get(ctx);
-            let len: i32 = socket_varargs.get(ctx);
-            let address: u32 = socket_varargs.get(ctx);
-            let address_len: u32 = socket_varargs.get(ctx);
-            let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as _;
-            let address = emscripten_memory_pointer!(ctx.memory(0), address) as *mut sockaddr;
-            let address_len_addr =
-                emscripten_memory_pointer!(ctx.memory(0), address_len) as *mut socklen_t;
-            unsafe { recvfrom(socket, buf_addr, flags, len, address, address_len_addr) as i32 }
-        }
-        14 => {
-            debug!("socket: setsockopt");
-            // NOTE: Emscripten seems to be passing the wrong values to this syscall
-            //      level: Em passes 1 as SOL_SOCKET; SOL_SOCKET is 0xffff in BSD
-            //      name: Em passes SO_ACCEPTCONN, but Nginx complains about REUSEADDR
-            //      https://github.com/openbsd/src/blob/master/sys/sys/socket.h#L156
-            // setsockopt (socket: c_int, level: c_int, name: c_int, value: *const c_void, option_len: socklen_t) -> c_int
-
-            let socket = socket_varargs.get(ctx);
-            // SOL_SOCKET = 0xffff (BSD, Linux)
-            let level: i32 = SOL_SOCKET;
-            let _: u32 = socket_varargs.get(ctx);
-            // SO_REUSEADDR = 0x4 (BSD, Linux)
-            let name: i32 = SO_REUSEADDR;
-            let _: u32 = socket_varargs.get(ctx);
-            let value: u32 = socket_varargs.get(ctx);
-            let option_len = socket_varargs.get(ctx);
-            let value_addr = emscripten_memory_pointer!(ctx.memory(0), value) as _; // Endian problem
-            let ret = unsafe { setsockopt(socket, level, name, value_addr, option_len) };
-
-            debug!("=> socketfd: {}, level: {} (SOL_SOCKET/0xffff), name: {} (SO_REUSEADDR/4), value_addr: {:?}, option_len: {} = status: {}", socket, level, name, value_addr, option_len, ret);
-            ret
-        }
-        15 => {
-            debug!("socket: getsockopt");
-            // getsockopt (sockfd: c_int, level: c_int, optname: c_int, optval: *mut c_void, optlen: *mut socklen_t) -> c_int
-            let socket = socket_varargs.get(ctx);
-            let level: i32 = socket_varargs.get(ctx);
-            let name: i32 = socket_varargs.get(ctx);
-            let value: u32 = socket_varargs.get(ctx);
-            let option_len: u32 = socket_varargs.get(ctx);
-            let value_addr = emscripten_memory_pointer!(ctx.memory(0), value) as _;
-            let option_len_addr =
-                emscripten_memory_pointer!(ctx.memory(0), option_len) as *mut socklen_t;
-            unsafe { getsockopt(socket, level, name, value_addr, option_len_addr) }
-        }
-        16 => {
-            debug!("socket: sendmsg");
-            // sendmsg (fd: c_int, msg: *const msghdr, flags: c_int) -> ssize_t
-            let socket: i32 = socket_varargs.get(ctx);
-            let msg: u32 = socket_varargs.get(ctx);
-            let flags: i32 = socket_varargs.get(ctx);
-            let msg_addr = emscripten_memory_pointer!(ctx.memory(0), msg) as *const msghdr;
-            unsafe { sendmsg(socket, msg_addr, flags) as i32 }
-        }
-        17 => {
-            debug!("socket: recvmsg");
-            // recvmsg (fd: c_int, msg: *mut msghdr, flags: c_int) -> ssize_t
-            let socket: i32 = socket_varargs.get(ctx);
-            let msg: u32 = socket_varargs.get(ctx);
-            let flags: i32 = socket_varargs.get(ctx);
-            let msg_addr = emscripten_memory_pointer!(ctx.memory(0), msg) as *mut msghdr;
-            unsafe { recvmsg(socket, msg_addr, flags) as i32 }
+// writev
+pub fn ___syscall146(ctx: &mut Ctx, which: i32, mut varargs: VarArgs) -> i32 {
+    // -> ssize_t
+    debug!("emscripten::___syscall146 (writev) {}", which);
+    let fd: i32 = varargs.get(ctx);
+    let fd = vfs::host_fd(ctx, fd);
+    let iov: u32 = varargs.get(ctx);
+    let iovcnt: i32 = varargs.get(ctx);
+    debug!("=> fd: {}, iov: {}, iovcnt = {}", fd, iov, iovcnt);
+
+    let mut iovecs = scratch::take_iovecs(ctx);
+    if !host_iovecs(ctx, iov, iovcnt, &mut iovecs) {
+        scratch::put_iovecs(ctx, iovecs);
+        return -Errno::EFAULT.to_emscripten();
+    }
+    if fd == 1 || fd == 2 {
+        let mut bytes = scratch::take_bytes(ctx);
+        for iovec in &iovecs {
+            bytes.extend_from_slice(unsafe {
+                slice::from_raw_parts(iovec.iov_base as *const u8, iovec.iov_len)
+            });
         }
-        _ => {
-            // others
-            -1
+        let ret = stdio::write_output(ctx, fd, &bytes);
+        scratch::put_bytes(ctx, bytes);
+        if let Some(ret) = ret {
+            scratch::put_iovecs(ctx, iovecs);
+            return ret;
         }
     }
+    let ret = translate_ret(unsafe { writev(fd, iovecs.as_ptr(), iovecs.len() as _) as _ });
+    scratch::put_iovecs(ctx, iovecs);
+    if let Some(cache) = get_stat_cache(ctx) {
+        cache.written(fd);
+    }
+    if ret >= 0 {
+        inotify::written(ctx, fd);
+    }
+    ret
 }
 
 // pread
 pub fn ___syscall180(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall180 (pread) {}", which);
     let fd: i32 = varargs.get(ctx);
+    let fd = vfs::host_fd(ctx, fd);
     let buf: u32 = varargs.get(ctx);
     let count: u32 = varargs.get(ctx);
     {
@@ -460,13 +309,14 @@ pub fn ___syscall180(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int
 
     let buf_ptr = emscripten_memory_pointer!(ctx.memory(0), buf) as _;
 
-    unsafe { pread(fd, buf_ptr, count as _, offset) as _ }
+    translate_ret(unsafe { pread(fd, buf_ptr, count as _, offset) as _ })
 }
 
 // pwrite
 pub fn ___syscall181(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall181 (pwrite) {}", which);
     let fd: i32 = varargs.get(ctx);
+    let fd = vfs::host_fd(ctx, fd);
     let buf: u32 = varargs.get(ctx);
     let count: u32 = varargs.get(ctx);
     {
@@ -476,7 +326,13 @@ pub fn ___syscall181(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int
     let offset: i64 = varargs.get(ctx);
 
     let buf_ptr = emscripten_memory_pointer!(ctx.memory(0), buf) as _;
-    let status = unsafe { pwrite(fd, buf_ptr, count as _, offset) as _ };
+    let status = translate_ret(unsafe { pwrite(fd, buf_ptr, count as _, offset) as _ });
+    if let Some(cache) = get_stat_cache(ctx) {
+        cache.written(fd);
+    }
+    if status >= 0 {
+        inotify::written(ctx, fd);
+    }
     debug!(
         "=> fd: {}, buf: {}, count: {}, offset: {} = status:{}",
         fd, buf, count, offset, status
@@ -494,7 +350,7 @@ pub fn ___syscall114(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> pid_
     let rusage: u32 = varargs.get(ctx);
     let status_addr = emscripten_memory_pointer!(ctx.memory(0), status) as *mut c_int;
     let rusage_addr = emscripten_memory_pointer!(ctx.memory(0), rusage) as *mut rusage;
-    let res = unsafe { wait4(pid, status_addr, options, rusage_addr) };
+    let res = translate_ret(unsafe { wait4(pid, status_addr, options, rusage_addr) });
     debug!(
         "=> pid: {}, status: {:?}, options: {}, rusage: {:?} = pid: {}",
         pid, status_addr, options, rusage_addr, res
@@ -519,7 +375,7 @@ pub fn ___syscall142(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int
     let readfds_ptr = emscripten_memory_pointer!(ctx.memory(0), readfds) as _;
     let writefds_ptr = emscripten_memory_pointer!(ctx.memory(0), writefds) as _;
 
-    unsafe { select(nfds, readfds_ptr, writefds_ptr, 0 as _, 0 as _) }
+    translate_ret(unsafe { select(nfds, readfds_ptr, writefds_ptr, 0 as _, 0 as _) })
 }
 
 // setpgid
@@ -527,7 +383,7 @@ pub fn ___syscall57(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int
     debug!("emscripten::___syscall57 (setpgid) {}", which);
     let pid: i32 = varargs.get(ctx);
     let pgid: i32 = varargs.get(ctx);
-    unsafe { setpgid(pid, pgid) }
+    translate_ret(unsafe { setpgid(pid, pgid) })
 }
 
 /// uname
@@ -537,5 +393,67 @@ pub fn ___syscall122(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int
     let buf: u32 = varargs.get(ctx);
     debug!("=> buf: {}", buf);
     let buf_addr = emscripten_memory_pointer!(ctx.memory(0), buf) as *mut utsname;
-    unsafe { uname(buf_addr) }
+    translate_ret(unsafe { uname(buf_addr) })
+}
+
+// inotify_init
+pub fn ___syscall291(ctx: &mut Ctx, which: c_int, _varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall291 (inotify_init) {}", which);
+    inotify_init(ctx, 0)
+}
+
+// inotify_add_watch
+pub fn ___syscall292(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall292 (inotify_add_watch) {}", which);
+    let fd: c_int = varargs.get(ctx);
+    let pathname: u32 = varargs.get(ctx);
+    let mask: u32 = varargs.get(ctx);
+    let path = match vfs::resolve(ctx, pathname) {
+        Ok(path) => path,
+        Err(errno) => return -errno.to_emscripten(),
+    };
+    debug!("=> fd: {}, path: {:?}, mask: {:#x}", fd, path, mask);
+    let fd = vfs::host_fd(ctx, fd);
+    inotify::add_watch(ctx, fd, &path, mask)
+}
+
+// inotify_rm_watch
+pub fn ___syscall293(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall293 (inotify_rm_watch) {}", which);
+    let fd: c_int = varargs.get(ctx);
+    let wd: c_int = varargs.get(ctx);
+    let fd = vfs::host_fd(ctx, fd);
+    inotify::rm_watch(ctx, fd, wd)
+}
+
+// inotify_init1
+pub fn ___syscall332(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall332 (inotify_init1) {}", which);
+    let flags: c_int = varargs.get(ctx);
+    inotify_init(ctx, flags)
+}
+
+fn inotify_init(ctx: &mut Ctx, flags: c_int) -> c_int {
+    // IN_NONBLOCK and IN_CLOEXEC, as the guest defines them.
+    const IN_NONBLOCK: c_int = 0o4000;
+    const IN_CLOEXEC: c_int = 0o2_000_000;
+    if flags & !(IN_NONBLOCK | IN_CLOEXEC) != 0 {
+        return -Errno::EINVAL.to_emscripten();
+    }
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return translate_ret(-1);
+    }
+    let [reader, writer] = fds;
+    unsafe {
+        fcntl(writer, libc::F_SETFL, libc::O_NONBLOCK);
+        fcntl(writer, F_SETFD, libc::FD_CLOEXEC);
+        if flags & IN_NONBLOCK != 0 {
+            fcntl(reader, libc::F_SETFL, libc::O_NONBLOCK);
+        }
+        if flags & IN_CLOEXEC != 0 {
+            fcntl(reader, F_SETFD, libc::FD_CLOEXEC);
+        }
+    }
+    inotify::init(ctx, reader, writer)
 }
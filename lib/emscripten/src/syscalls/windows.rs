@@ -1,6 +1,15 @@
+use crate::errno::{translate_ret, Errno};
+use crate::inotify;
+use crate::stat_cache::get_stat_cache;
+use crate::stdio;
+use crate::tty;
+use crate::utils::{guest_memory_range, read_guest_iovec};
 use crate::varargs::VarArgs;
-use libc::mkdir;
+use crate::vfs;
+use libc::{c_void, mkdir, read, write};
+use std::ffi::CString;
 use std::os::raw::c_int;
+use std::slice;
 use wasmer_runtime_core::vm::Ctx;
 
 type pid_t = c_int;
@@ -8,7 +17,11 @@ type pid_t = c_int;
 // chown
 pub fn ___syscall212(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall212 (chown) {}", which);
-    -1
+    -Errno::ENOSYS.to_emscripten()
+}
+
+pub(crate) fn chown_path(_ctx: &mut Ctx, _path: CString, _owner: u32, _group: u32) -> c_int {
+    -Errno::ENOSYS.to_emscripten()
 }
 
 // mkdir
@@ -16,77 +29,201 @@ pub fn ___syscall39(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int
     debug!("emscripten::___syscall39 (mkdir) {}", which);
     let pathname: u32 = varargs.get(ctx);
     let mode: u32 = varargs.get(ctx);
-    let pathname_addr = emscripten_memory_pointer!(ctx.memory(0), pathname) as *const i8;
-    unsafe { mkdir(pathname_addr) }
+    let path = match vfs::resolve(ctx, pathname) {
+        Ok(path) => path,
+        Err(errno) => return -errno.to_emscripten(),
+    };
+    mkdir_path(ctx, path, mode)
 }
 
-// getgid
-pub fn ___syscall201(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
-    debug!("emscripten::___syscall201 (getgid)");
-    -1
-}
-
-// getgid32
-pub fn ___syscall202(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
-    // gid_t
-    debug!("emscripten::___syscall202 (getgid32)");
-    -1
+pub(crate) fn mkdir_path(ctx: &mut Ctx, path: CString, mode: u32) -> c_int {
+    if let Some(cache) = get_stat_cache(ctx) {
+        cache.entry_changed(&path);
+    }
+    let ret = unsafe { mkdir(path.as_ptr()) };
+    if ret == 0 {
+        // Windows has no permission bits, so they only exist for the guest.
+        let mode = vfs::creation_mode(ctx, mode);
+        vfs::set_mode(ctx, &path, mode);
+        inotify::created(ctx, &path, true);
+    }
+    translate_ret(ret)
 }
 
 /// dup3
 pub fn ___syscall330(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> pid_t {
     debug!("emscripten::___syscall330 (dup3)");
-    -1
+    -Errno::ENOSYS.to_emscripten()
 }
 
 /// ioctl
 pub fn ___syscall54(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall54 (ioctl) {}", which);
-    -1
+    let fd: i32 = varargs.get(ctx);
+    let fd = vfs::host_fd(ctx, fd);
+    let request: u32 = varargs.get(ctx);
+    if let Some(ret) = tty::ioctl(ctx, fd, request, &mut varargs) {
+        return ret;
+    }
+    -Errno::ENOSYS.to_emscripten()
 }
 
 // socketcall
 #[allow(clippy::cast_ptr_alignment)]
 pub fn ___syscall102(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall102 (socketcall) {}", which);
-    -1
+    -Errno::ENOSYS.to_emscripten()
+}
+
+/// readv
+pub fn ___syscall145(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> i32 {
+    // -> ssize_t
+    debug!("emscripten::___syscall145 (readv) {}", which);
+    let fd: i32 = varargs.get(ctx);
+    let fd = vfs::host_fd(ctx, fd);
+    let iov: u32 = varargs.get(ctx);
+    let iovcnt: i32 = varargs.get(ctx);
+    debug!("=> fd: {}, iov: {}, iovcnt = {}", fd, iov, iovcnt);
+
+    // There is no readv on windows, so read each guest buffer in place.
+    let mut ret = 0;
+    for i in 0..iovcnt.max(0) as u32 {
+        let guest_iov = match read_guest_iovec(ctx, iov, i) {
+            Some(guest_iov) => guest_iov,
+            None => return -Errno::EFAULT.to_emscripten(),
+        };
+        let base = match guest_memory_range(ctx, guest_iov.iov_base, guest_iov.iov_len) {
+            Some(base) => base as *mut c_void,
+            None => return -Errno::EFAULT.to_emscripten(),
+        };
+        let buf = unsafe { slice::from_raw_parts_mut(base as *mut u8, guest_iov.iov_len as usize) };
+        if let Some(curr) = stdio::read_input(ctx, fd, buf) {
+            if curr < 0 {
+                return if ret > 0 { ret } else { curr };
+            }
+            ret += curr;
+            if (curr as u32) < guest_iov.iov_len {
+                break;
+            }
+            continue;
+        }
+        let curr = unsafe { read(fd, base, guest_iov.iov_len as _) };
+        if curr < 0 {
+            return if ret > 0 { ret } else { translate_ret(curr) };
+        }
+        ret += curr;
+        // Like readv, stop at a short read rather than leave a gap.
+        if (curr as u32) < guest_iov.iov_len {
+            break;
+        }
+    }
+    ret as _
+}
+
+// writev
+pub fn ___syscall146(ctx: &mut Ctx, which: i32, mut varargs: VarArgs) -> i32 {
+    // -> ssize_t
+    debug!("emscripten::___syscall146 (writev) {}", which);
+    let fd: i32 = varargs.get(ctx);
+    let fd = vfs::host_fd(ctx, fd);
+    let iov: u32 = varargs.get(ctx);
+    let iovcnt: i32 = varargs.get(ctx);
+    debug!("=> fd: {}, iov: {}, iovcnt = {}", fd, iov, iovcnt);
+
+    // There is no writev on windows, so write each guest buffer in place.
+    let mut ret = 0;
+    for i in 0..iovcnt.max(0) as u32 {
+        let guest_iov = match read_guest_iovec(ctx, iov, i) {
+            Some(guest_iov) => guest_iov,
+            None => return -Errno::EFAULT.to_emscripten(),
+        };
+        let base = match guest_memory_range(ctx, guest_iov.iov_base, guest_iov.iov_len) {
+            Some(base) => base as *const c_void,
+            None => return -Errno::EFAULT.to_emscripten(),
+        };
+        let bytes = unsafe { slice::from_raw_parts(base as *const u8, guest_iov.iov_len as usize) };
+        let curr = match stdio::write_output(ctx, fd, bytes) {
+            Some(curr) if curr < 0 => return if ret > 0 { ret } else { curr },
+            Some(curr) => curr,
+            None => {
+                let curr = unsafe { write(fd, base, guest_iov.iov_len as _) };
+                if curr < 0 {
+                    return if ret > 0 { ret } else { translate_ret(curr) };
+                }
+                curr
+            }
+        };
+        ret += curr;
+        // Like writev, stop at a short write rather than leave a gap.
+        if (curr as u32) < guest_iov.iov_len {
+            break;
+        }
+    }
+    if let Some(cache) = get_stat_cache(ctx) {
+        cache.written(fd);
+    }
+    ret as _
 }
 
 // pread
 pub fn ___syscall180(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall180 (pread) {}", which);
-    -1
+    -Errno::ENOSYS.to_emscripten()
 }
 
 // pwrite
 pub fn ___syscall181(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall181 (pwrite) {}", which);
-    -1
+    -Errno::ENOSYS.to_emscripten()
 }
 
 /// wait4
 #[allow(clippy::cast_ptr_alignment)]
 pub fn ___syscall114(ctx: &mut Ctx, _which: c_int, mut varargs: VarArgs) -> pid_t {
     debug!("emscripten::___syscall114 (wait4)");
-    -1
+    -Errno::ENOSYS.to_emscripten()
 }
 
 // select
 #[allow(clippy::cast_ptr_alignment)]
 pub fn ___syscall142(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall142 (newselect) {}", which);
-    -1
+    -Errno::ENOSYS.to_emscripten()
 }
 
 // setpgid
 pub fn ___syscall57(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall57 (setpgid) {}", which);
-    -1
+    -Errno::ENOSYS.to_emscripten()
 }
 
 /// uname
 // NOTE: Wondering if we should return custom utsname, like Emscripten.
 pub fn ___syscall122(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
     debug!("emscripten::___syscall122 (uname) {}", which);
-    -1
+    -Errno::ENOSYS.to_emscripten()
+}
+
+// inotify_init
+pub fn ___syscall291(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall291 (inotify_init) {}", which);
+    -Errno::ENOSYS.to_emscripten()
+}
+
+// inotify_add_watch
+pub fn ___syscall292(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall292 (inotify_add_watch) {}", which);
+    -Errno::ENOSYS.to_emscripten()
+}
+
+// inotify_rm_watch
+pub fn ___syscall293(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall293 (inotify_rm_watch) {}", which);
+    -Errno::ENOSYS.to_emscripten()
+}
+
+// inotify_init1
+pub fn ___syscall332(ctx: &mut Ctx, which: c_int, mut varargs: VarArgs) -> c_int {
+    debug!("emscripten::___syscall332 (inotify_init1) {}", which);
+    -Errno::ENOSYS.to_emscripten()
 }
@@ -1,44 +1,25 @@
 use super::utils::{copy_cstr_into_wasm, write_to_buf};
 use libc::{c_char, c_int};
 use std::mem;
-use std::time::SystemTime;
 
 #[cfg(not(target_os = "windows"))]
-use libc::{clockid_t, time as libc_time};
+use libc::time as libc_time;
 
 #[cfg(target_os = "windows")]
 use libc::time_t;
 
-#[cfg(target_os = "windows")]
-type clockid_t = c_int;
-
 #[cfg(target_os = "windows")]
 extern "C" {
     #[link_name = "time"]
     pub fn libc_time(s: *const time_t) -> time_t;
 }
 
-use time;
-
+use super::clock::{get_clock, realtime, ClockId};
 use super::env;
+use super::errno::{set_errno, Errno};
+use super::timezone::{TimeZone, Tm};
 use wasmer_runtime_core::vm::Ctx;
 
-#[cfg(target_os = "linux")]
-use libc::{CLOCK_MONOTONIC, CLOCK_MONOTONIC_COARSE, CLOCK_REALTIME};
-
-#[cfg(target_os = "macos")]
-use libc::{CLOCK_MONOTONIC, CLOCK_REALTIME};
-#[cfg(target_os = "macos")]
-const CLOCK_MONOTONIC_COARSE: clockid_t = 6;
-
-// some assumptions about the constants when targeting windows
-#[cfg(target_os = "windows")]
-const CLOCK_REALTIME: clockid_t = 0;
-#[cfg(target_os = "windows")]
-const CLOCK_MONOTONIC: clockid_t = 1;
-#[cfg(target_os = "windows")]
-const CLOCK_MONOTONIC_COARSE: clockid_t = 6;
-
 /// emscripten: _gettimeofday
 #[allow(clippy::cast_ptr_alignment)]
 pub fn _gettimeofday(ctx: &mut Ctx, tp: c_int, tz: c_int) -> c_int {
@@ -53,9 +34,8 @@ pub fn _gettimeofday(ctx: &mut Ctx, tp: c_int, tz: c_int) -> c_int {
         tz == 0,
         "the timezone argument of `_gettimeofday` must be null"
     );
+    let since_epoch = realtime(ctx);
     unsafe {
-        let now = SystemTime::now();
-        let since_epoch = now.duration_since(SystemTime::UNIX_EPOCH).unwrap();
         let timeval_struct_ptr = emscripten_memory_pointer!(ctx.memory(0), tp) as *mut GuestTimeVal;
 
         (*timeval_struct_ptr).tv_sec = since_epoch.as_secs() as _;
@@ -65,8 +45,10 @@ pub fn _gettimeofday(ctx: &mut Ctx, tp: c_int, tz: c_int) -> c_int {
 }
 
 /// emscripten: _clock_gettime
+///
+/// The `clockid_t` of the guest is an `int`, whatever the host's is.
 #[allow(clippy::cast_ptr_alignment)]
-pub fn _clock_gettime(ctx: &mut Ctx, clk_id: clockid_t, tp: c_int) -> c_int {
+pub fn _clock_gettime(ctx: &mut Ctx, clk_id: c_int, tp: c_int) -> c_int {
     debug!("emscripten::_clock_gettime {} {}", clk_id, tp);
     // debug!("Memory {:?}", ctx.memory(0)[..]);
     #[repr(C)]
@@ -75,37 +57,37 @@ pub fn _clock_gettime(ctx: &mut Ctx, clk_id: clockid_t, tp: c_int) -> c_int {
         tv_nsec: i32,
     }
 
-    let timespec = match clk_id {
-        CLOCK_REALTIME => time::get_time(),
-        CLOCK_MONOTONIC | CLOCK_MONOTONIC_COARSE => {
-            let precise_ns = time::precise_time_ns();
-            time::Timespec::new(
-                (precise_ns / 1000000000) as i64,
-                (precise_ns % 1000000000) as i32,
-            )
+    let timespec = match ClockId::from_emscripten(clk_id).and_then(|id| get_clock(ctx).time(id)) {
+        Some(timespec) => timespec,
+        None => {
+            set_errno(ctx, Errno::EINVAL);
+            return -1;
         }
-        _ => panic!("Clock with id \"{}\" is not supported.", clk_id),
     };
 
     unsafe {
         let timespec_struct_ptr =
             emscripten_memory_pointer!(ctx.memory(0), tp) as *mut GuestTimeSpec;
-        (*timespec_struct_ptr).tv_sec = timespec.sec as _;
-        (*timespec_struct_ptr).tv_nsec = timespec.nsec as _;
+        (*timespec_struct_ptr).tv_sec = timespec.as_secs() as _;
+        (*timespec_struct_ptr).tv_nsec = timespec.subsec_nanos() as _;
     }
     0
 }
 
 /// emscripten: ___clock_gettime
-pub fn ___clock_gettime(ctx: &mut Ctx, clk_id: clockid_t, tp: c_int) -> c_int {
+pub fn ___clock_gettime(ctx: &mut Ctx, clk_id: c_int, tp: c_int) -> c_int {
     debug!("emscripten::___clock_gettime {} {}", clk_id, tp);
     _clock_gettime(ctx, clk_id, tp)
 }
 
 /// emscripten: _clock
-pub fn _clock(_ctx: &mut Ctx) -> c_int {
+pub fn _clock(ctx: &mut Ctx) -> c_int {
     debug!("emscripten::_clock");
-    0 // TODO: unimplemented
+    // emscripten defines CLOCKS_PER_SEC as 1000000.
+    match get_clock(ctx).time(ClockId::ProcessCpuTime) {
+        Some(time) => (time.as_secs() * 1_000_000 + u64::from(time.subsec_micros())) as c_int,
+        None => -1,
+    }
 }
 
 /// emscripten: _difftime
@@ -119,9 +101,17 @@ pub fn _gmtime_r(_ctx: &mut Ctx, _one: i32, _two: i32) -> i32 {
     -1
 }
 
-pub fn _mktime(_ctx: &mut Ctx, _one: i32) -> i32 {
-    debug!("emscripten::_mktime");
-    -1
+/// emscripten: _mktime
+#[allow(clippy::cast_ptr_alignment)]
+pub fn _mktime(ctx: &mut Ctx, tm_p: u32) -> i32 {
+    debug!("emscripten::_mktime {}", tm_p);
+
+    let guest_tm =
+        unsafe { &mut *(emscripten_memory_pointer!(ctx.memory(0), tm_p) as *mut guest_tm) };
+    let mut tm = guest_tm.to_tm();
+    let time = with_timezone(ctx, |zone| zone.from_tm(&mut tm));
+    guest_tm.set(&tm);
+    time as i32
 }
 
 pub fn _gmtime(_ctx: &mut Ctx, _one: i32) -> i32 {
@@ -144,6 +134,47 @@ struct guest_tm {
     pub tm_zone: c_int,   // 40
 }
 
+impl guest_tm {
+    fn to_tm(&self) -> Tm {
+        Tm {
+            tm_sec: self.tm_sec,
+            tm_min: self.tm_min,
+            tm_hour: self.tm_hour,
+            tm_mday: self.tm_mday,
+            tm_mon: self.tm_mon,
+            tm_year: self.tm_year,
+            tm_wday: self.tm_wday,
+            tm_yday: self.tm_yday,
+            tm_isdst: self.tm_isdst,
+            tm_gmtoff: self.tm_gmtoff,
+        }
+    }
+
+    fn set(&mut self, tm: &Tm) {
+        self.tm_sec = tm.tm_sec;
+        self.tm_min = tm.tm_min;
+        self.tm_hour = tm.tm_hour;
+        self.tm_mday = tm.tm_mday;
+        self.tm_mon = tm.tm_mon;
+        self.tm_year = tm.tm_year;
+        self.tm_wday = tm.tm_wday;
+        self.tm_yday = tm.tm_yday;
+        self.tm_isdst = tm.tm_isdst;
+        self.tm_gmtoff = tm.tm_gmtoff;
+        self.tm_zone = 0;
+    }
+}
+
+/// Runs `f` with the time zone of the instance.
+fn with_timezone<T>(ctx: &mut Ctx, f: impl FnOnce(&TimeZone) -> T) -> T {
+    // Conversions can happen before `run_emscripten_instance` sets up the data.
+    if ctx.data.is_null() {
+        f(&TimeZone::local())
+    } else {
+        f(&env::get_emscripten_data(ctx).timezone)
+    }
+}
+
 /// emscripten: _tvset
 pub fn _tvset(_ctx: &mut Ctx) {
     debug!("emscripten::_tvset UNIMPLEMENTED");
@@ -208,78 +239,27 @@ pub fn _asctime_r(ctx: &mut Ctx, time: u32, buf: u32) -> u32 {
 }
 
 /// emscripten: _localtime
-#[allow(clippy::cast_ptr_alignment)]
 pub fn _localtime(ctx: &mut Ctx, time_p: u32) -> c_int {
     debug!("emscripten::_localtime {}", time_p);
-    // NOTE: emscripten seems to want tzset() called in this function
-    //      https://stackoverflow.com/questions/19170721/real-time-awareness-of-timezone-change-in-localtime-vs-localtime-r
 
-    let timespec = unsafe {
-        let time_p_addr = emscripten_memory_pointer!(ctx.memory(0), time_p) as *mut i64;
-        let seconds = *time_p_addr.clone();
-        time::Timespec::new(seconds, 0)
-    };
-    let result_tm = time::at(timespec);
-
-    unsafe {
-        let tm_struct_offset = env::call_malloc(ctx, mem::size_of::<guest_tm>() as _);
-        let tm_struct_ptr =
-            emscripten_memory_pointer!(ctx.memory(0), tm_struct_offset) as *mut guest_tm;
-        // debug!(
-        //     ">>>>>>> time = {}, {}, {}, {}, {}, {}, {}, {}",
-        //     result_tm.tm_sec, result_tm.tm_min, result_tm.tm_hour, result_tm.tm_mday,
-        //     result_tm.tm_mon, result_tm.tm_year, result_tm.tm_wday, result_tm.tm_yday,
-        // );
-        (*tm_struct_ptr).tm_sec = result_tm.tm_sec;
-        (*tm_struct_ptr).tm_min = result_tm.tm_min;
-        (*tm_struct_ptr).tm_hour = result_tm.tm_hour;
-        (*tm_struct_ptr).tm_mday = result_tm.tm_mday;
-        (*tm_struct_ptr).tm_mon = result_tm.tm_mon;
-        (*tm_struct_ptr).tm_year = result_tm.tm_year;
-        (*tm_struct_ptr).tm_wday = result_tm.tm_wday;
-        (*tm_struct_ptr).tm_yday = result_tm.tm_yday;
-        (*tm_struct_ptr).tm_isdst = result_tm.tm_isdst;
-        (*tm_struct_ptr).tm_gmtoff = 0;
-        (*tm_struct_ptr).tm_zone = 0;
-
-        tm_struct_offset as _
-    }
+    let tm_struct_offset = env::call_malloc(ctx, mem::size_of::<guest_tm>() as _);
+    _localtime_r(ctx, time_p, tm_struct_offset)
 }
+
 /// emscripten: _localtime_r
 #[allow(clippy::cast_ptr_alignment)]
 pub fn _localtime_r(ctx: &mut Ctx, time_p: u32, result: u32) -> c_int {
     debug!("emscripten::_localtime_r {}", time_p);
 
-    // NOTE: emscripten seems to want tzset() called in this function
-    //      https://stackoverflow.com/questions/19170721/real-time-awareness-of-timezone-change-in-localtime-vs-localtime-r
+    // time_t is 32 bits wide on wasm32.
+    let time = unsafe { *(emscripten_memory_pointer!(ctx.memory(0), time_p) as *const i32) };
+    let tm = with_timezone(ctx, |zone| zone.to_tm(i64::from(time)));
 
     unsafe {
-        let seconds = emscripten_memory_pointer!(ctx.memory(0), time_p) as *const i32;
-        let timespec = time::Timespec::new(*seconds as _, 0);
-        let result_tm = time::at(timespec);
-
-        // debug!(
-        //     ">>>>>>> time = {}, {}, {}, {}, {}, {}, {}, {}",
-        //     result_tm.tm_sec, result_tm.tm_min, result_tm.tm_hour, result_tm.tm_mday,
-        //     result_tm.tm_mon, result_tm.tm_year, result_tm.tm_wday, result_tm.tm_yday,
-        // );
-
         let result_addr = emscripten_memory_pointer!(ctx.memory(0), result) as *mut guest_tm;
-
-        (*result_addr).tm_sec = result_tm.tm_sec;
-        (*result_addr).tm_min = result_tm.tm_min;
-        (*result_addr).tm_hour = result_tm.tm_hour;
-        (*result_addr).tm_mday = result_tm.tm_mday;
-        (*result_addr).tm_mon = result_tm.tm_mon;
-        (*result_addr).tm_year = result_tm.tm_year;
-        (*result_addr).tm_wday = result_tm.tm_wday;
-        (*result_addr).tm_yday = result_tm.tm_yday;
-        (*result_addr).tm_isdst = result_tm.tm_isdst;
-        (*result_addr).tm_gmtoff = 0;
-        (*result_addr).tm_zone = 0;
-
-        result as _
+        (*result_addr).set(&tm);
     }
+    result as _
 }
 
 /// emscripten: _time
@@ -0,0 +1,658 @@
+//! Time zone conversions for `localtime` and `mktime`.
+//!
+//! Zones are read from the host's zoneinfo database (TZif files), or
+//! given directly as a POSIX `TZ` string such as `EST5EDT,M3.2.0,M11.1.0`.
+use std::{env, fs, path::Path};
+
+const SECS_PER_DAY: i64 = 86_400;
+
+/// The offset and name of the local time in effect at some instant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalTimeType {
+    /// Seconds east of UTC.
+    pub utc_offset: i32,
+    pub is_dst: bool,
+    pub abbreviation: String,
+}
+
+/// A broken-down time, laid out like the fields of `struct tm`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Tm {
+    pub tm_sec: i32,
+    pub tm_min: i32,
+    pub tm_hour: i32,
+    pub tm_mday: i32,
+    /// Months since January.
+    pub tm_mon: i32,
+    /// Years since 1900.
+    pub tm_year: i32,
+    /// Days since Sunday.
+    pub tm_wday: i32,
+    /// Days since January 1st.
+    pub tm_yday: i32,
+    pub tm_isdst: i32,
+    pub tm_gmtoff: i32,
+}
+
+/// A time zone: the history of its offsets from UTC, followed by the
+/// rule that applies after the last recorded transition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeZone {
+    transitions: Vec<i64>,
+    /// The index in `types` of the type that starts at each transition.
+    indices: Vec<usize>,
+    types: Vec<LocalTimeType>,
+    rule: Option<PosixTz>,
+}
+
+impl TimeZone {
+    pub fn utc() -> Self {
+        TimeZone::from_rule(PosixTz {
+            std: LocalTimeType {
+                utc_offset: 0,
+                is_dst: false,
+                abbreviation: "UTC".to_string(),
+            },
+            dst: None,
+        })
+    }
+
+    fn from_rule(rule: PosixTz) -> Self {
+        TimeZone {
+            transitions: Vec::new(),
+            indices: Vec::new(),
+            types: vec![rule.std.clone()],
+            rule: Some(rule),
+        }
+    }
+
+    /// The zone of the host: the one named by `TZ`, or else the one of
+    /// the system. Falls back to UTC, like libc does for invalid zones.
+    pub fn local() -> Self {
+        if let Ok(name) = env::var("TZ") {
+            return TimeZone::from_name(&name).unwrap_or_else(TimeZone::utc);
+        }
+        if let Some(zone) = fs::read("/etc/localtime")
+            .ok()
+            .and_then(|data| TimeZone::from_tzif(&data))
+        {
+            return zone;
+        }
+        // Without a zoneinfo database, settle for the current offset.
+        let utc_offset = time::now().tm_utcoff;
+        TimeZone::from_rule(PosixTz {
+            std: LocalTimeType {
+                utc_offset,
+                is_dst: false,
+                abbreviation: String::new(),
+            },
+            dst: None,
+        })
+    }
+
+    /// Look up a zone by the value `TZ` would have: a name from the
+    /// zoneinfo database such as `Europe/Paris`, the path of a TZif file,
+    /// or a POSIX time zone string.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim_start_matches(':');
+        if name.is_empty() || name == "UTC" || name == "GMT" {
+            return Some(TimeZone::utc());
+        }
+
+        let path = if name.starts_with('/') {
+            Path::new(name).to_path_buf()
+        } else if !name.split('/').any(|component| component == "..") {
+            let dir = env::var("TZDIR").unwrap_or_else(|_| "/usr/share/zoneinfo".to_string());
+            Path::new(&dir).join(name)
+        } else {
+            return None;
+        };
+        if let Ok(data) = fs::read(path) {
+            return TimeZone::from_tzif(&data);
+        }
+
+        PosixTz::parse(name).map(TimeZone::from_rule)
+    }
+
+    /// Parse the contents of a TZif file, as described in RFC 8536.
+    pub fn from_tzif(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader { data };
+        let (version, counts) = reader.header()?;
+        if version < b'2' {
+            return reader.block(&counts, 4);
+        }
+
+        // Skip the 32-bit data, which version 2 files repeat with 64-bit times.
+        let [isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt] = counts;
+        reader.take(timecnt * 5 + typecnt * 6 + charcnt + leapcnt * 8 + isstdcnt + isutcnt)?;
+        let (_, counts) = reader.header()?;
+        let mut zone = reader.block(&counts, 8)?;
+
+        // The footer holds the rule for the times after the last transition.
+        if reader.take(1)? == b"\n" {
+            let end = reader.data.iter().position(|&b| b == b'\n')?;
+            let footer = std::str::from_utf8(&reader.data[..end]).ok()?;
+            if !footer.is_empty() {
+                zone.rule = Some(PosixTz::parse(footer)?);
+            }
+        }
+        Some(zone)
+    }
+
+    /// The local time type in effect at `time`, in seconds since the epoch.
+    pub fn find(&self, time: i64) -> &LocalTimeType {
+        match (self.transitions.last(), &self.rule) {
+            (None, Some(rule)) => return rule.find(time),
+            (Some(&last), Some(rule)) if time >= last => return rule.find(time),
+            _ => {}
+        }
+        match self.transitions.binary_search(&time) {
+            Ok(i) => &self.types[self.indices[i]],
+            Err(0) => &self.types[0],
+            Err(i) => &self.types[self.indices[i - 1]],
+        }
+    }
+
+    /// Convert `time` to local time, like `localtime_r`.
+    pub fn to_tm(&self, time: i64) -> Tm {
+        let local_time_type = self.find(time);
+        let mut tm = utc_to_tm(time + i64::from(local_time_type.utc_offset));
+        tm.tm_isdst = local_time_type.is_dst as i32;
+        tm.tm_gmtoff = local_time_type.utc_offset;
+        tm
+    }
+
+    /// Convert a local time to seconds since the epoch, like `mktime`.
+    ///
+    /// The fields of `tm` may be out of range, and are normalized. When
+    /// the local time happens twice, `tm_isdst` picks the occurrence, and
+    /// when it is skipped, it is read with the offset in effect before.
+    pub fn from_tm(&self, tm: &mut Tm) -> i64 {
+        let local = tm_to_utc(tm);
+
+        let mut candidates = self
+            .types
+            .iter()
+            .chain(
+                self.rule
+                    .iter()
+                    .flat_map(|rule| rule.dst.as_ref().map(|dst| &dst.dst)),
+            )
+            .map(|local_time_type| local - i64::from(local_time_type.utc_offset))
+            .filter(|&time| i64::from(self.find(time).utc_offset) == local - time)
+            .collect::<Vec<_>>();
+        candidates.sort();
+        candidates.dedup();
+
+        let time = candidates
+            .iter()
+            .cloned()
+            .find(|&time| tm.tm_isdst < 0 || self.find(time).is_dst == (tm.tm_isdst > 0))
+            .or_else(|| candidates.first().cloned())
+            .unwrap_or_else(|| {
+                let before = self.find(local - SECS_PER_DAY);
+                local - i64::from(before.utc_offset)
+            });
+
+        *tm = self.to_tm(time);
+        time
+    }
+}
+
+/// Convert seconds since the epoch to a broken-down UTC time.
+pub fn utc_to_tm(time: i64) -> Tm {
+    let days = floor_div(time, SECS_PER_DAY);
+    let secs = time - days * SECS_PER_DAY;
+    let (year, month, day) = civil_from_days(days);
+
+    Tm {
+        tm_sec: (secs % 60) as i32,
+        tm_min: (secs / 60 % 60) as i32,
+        tm_hour: (secs / 3600) as i32,
+        tm_mday: day as i32,
+        tm_mon: month as i32 - 1,
+        tm_year: (year - 1900) as i32,
+        // The epoch was a Thursday.
+        tm_wday: (days + 4 - floor_div(days + 4, 7) * 7) as i32,
+        tm_yday: (days - days_from_civil(year, 1, 1)) as i32,
+        tm_isdst: 0,
+        tm_gmtoff: 0,
+    }
+}
+
+/// Convert a broken-down UTC time to seconds since the epoch, like `timegm`.
+/// The fields may be out of range.
+pub fn tm_to_utc(tm: &Tm) -> i64 {
+    let month = i64::from(tm.tm_mon);
+    let year = i64::from(tm.tm_year) + 1900 + floor_div(month, 12);
+    let month = month - floor_div(month, 12) * 12 + 1;
+    let days = days_from_civil(year, month, 1) + i64::from(tm.tm_mday) - 1;
+
+    days * SECS_PER_DAY
+        + i64::from(tm.tm_hour) * 3600
+        + i64::from(tm.tm_min) * 60
+        + i64::from(tm.tm_sec)
+}
+
+fn floor_div(a: i64, b: i64) -> i64 {
+    let q = a / b;
+    if (a % b != 0) && ((a < 0) != (b < 0)) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since the epoch of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = floor_div(year, 400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The inverse of `days_from_civil`.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = floor_div(days, 146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// A day of the year on which daylight saving time starts or ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RuleDay {
+    /// `Jn`: the day of the year from 1 to 365, never counting February 29th.
+    Julian1(i64),
+    /// `n`: the day of the year from 0 to 365.
+    Julian0(i64),
+    /// `Mm.w.d`: day `d` (0 is Sunday) of week `w` (5 is the last) of month `m`.
+    MonthWeekDay { month: i64, week: i64, weekday: i64 },
+}
+
+impl RuleDay {
+    /// Days since the epoch of this day in `year`.
+    fn days(self, year: i64) -> i64 {
+        let jan_1 = days_from_civil(year, 1, 1);
+        match self {
+            RuleDay::Julian1(day) if is_leap_year(year) && day >= 60 => jan_1 + day,
+            RuleDay::Julian1(day) => jan_1 + day - 1,
+            RuleDay::Julian0(day) => jan_1 + day,
+            RuleDay::MonthWeekDay {
+                month,
+                week,
+                weekday,
+            } => {
+                let first = days_from_civil(year, month, 1);
+                let first_weekday = (first + 4) - floor_div(first + 4, 7) * 7;
+                let mut day = (weekday - first_weekday + 7) % 7 + (week - 1) * 7;
+                while day >= days_in_month(year, month) {
+                    day -= 7;
+                }
+                first + day
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct DstRule {
+    dst: LocalTimeType,
+    start: RuleDay,
+    /// Local standard time of the switch, in seconds after midnight.
+    start_time: i64,
+    end: RuleDay,
+    /// Local daylight saving time of the switch, in seconds after midnight.
+    end_time: i64,
+}
+
+/// A POSIX time zone string, e.g. `CET-1CEST,M3.5.0,M10.5.0/3`.
+#[derive(Debug, Clone, PartialEq)]
+struct PosixTz {
+    std: LocalTimeType,
+    dst: Option<DstRule>,
+}
+
+impl PosixTz {
+    fn parse(s: &str) -> Option<Self> {
+        let mut parser = Parser { s: s.as_bytes() };
+
+        let std_name = parser.name()?;
+        let std_offset = -parser.time()?;
+        let std = LocalTimeType {
+            utc_offset: std_offset as i32,
+            is_dst: false,
+            abbreviation: std_name,
+        };
+        if parser.s.is_empty() {
+            return Some(PosixTz { std, dst: None });
+        }
+
+        let dst_name = parser.name()?;
+        let dst_offset = match parser.peek() {
+            Some(b',') | None => std_offset + 3600,
+            _ => -parser.time()?,
+        };
+        // Without rules, use the ones of the United States.
+        let (start, start_time, end, end_time) = if parser.s.is_empty() {
+            let march = RuleDay::MonthWeekDay {
+                month: 3,
+                week: 2,
+                weekday: 0,
+            };
+            let november = RuleDay::MonthWeekDay {
+                month: 11,
+                week: 1,
+                weekday: 0,
+            };
+            (march, 7200, november, 7200)
+        } else {
+            parser.expect(b',')?;
+            let (start, start_time) = parser.rule()?;
+            parser.expect(b',')?;
+            let (end, end_time) = parser.rule()?;
+            (start, start_time, end, end_time)
+        };
+        if !parser.s.is_empty() {
+            return None;
+        }
+
+        Some(PosixTz {
+            std,
+            dst: Some(DstRule {
+                dst: LocalTimeType {
+                    utc_offset: dst_offset as i32,
+                    is_dst: true,
+                    abbreviation: dst_name,
+                },
+                start,
+                start_time,
+                end,
+                end_time,
+            }),
+        })
+    }
+
+    fn find(&self, time: i64) -> &LocalTimeType {
+        let rule = match &self.dst {
+            Some(rule) => rule,
+            None => return &self.std,
+        };
+
+        let year = civil_from_days(floor_div(
+            time + i64::from(self.std.utc_offset),
+            SECS_PER_DAY,
+        ))
+        .0;
+        let start =
+            rule.start.days(year) * SECS_PER_DAY + rule.start_time - i64::from(self.std.utc_offset);
+        let end =
+            rule.end.days(year) * SECS_PER_DAY + rule.end_time - i64::from(rule.dst.utc_offset);
+
+        let in_dst = if start <= end {
+            start <= time && time < end
+        } else {
+            // Southern hemisphere: daylight saving time spans the new year.
+            !(end <= time && time < start)
+        };
+        if in_dst {
+            &rule.dst
+        } else {
+            &self.std
+        }
+    }
+}
+
+struct Parser<'a> {
+    s: &'a [u8],
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.s.first().cloned()
+    }
+
+    fn expect(&mut self, c: u8) -> Option<()> {
+        if self.peek()? != c {
+            return None;
+        }
+        self.s = &self.s[1..];
+        Some(())
+    }
+
+    /// A zone abbreviation, either alphabetic or quoted in `<>`.
+    fn name(&mut self) -> Option<String> {
+        let (name, rest) = if self.peek()? == b'<' {
+            let end = self.s.iter().position(|&c| c == b'>')?;
+            (&self.s[1..end], &self.s[end + 1..])
+        } else {
+            let end = self
+                .s
+                .iter()
+                .position(|c| !c.is_ascii_alphabetic())
+                .unwrap_or_else(|| self.s.len());
+            (&self.s[..end], &self.s[end..])
+        };
+        if name.len() < 3 {
+            return None;
+        }
+        self.s = rest;
+        Some(String::from_utf8_lossy(name).into_owned())
+    }
+
+    fn number(&mut self) -> Option<i64> {
+        let end = self
+            .s
+            .iter()
+            .position(|c| !c.is_ascii_digit())
+            .unwrap_or_else(|| self.s.len());
+        if end == 0 {
+            return None;
+        }
+        let n = std::str::from_utf8(&self.s[..end]).ok()?.parse().ok()?;
+        self.s = &self.s[end..];
+        Some(n)
+    }
+
+    /// A signed `hh[:mm[:ss]]`, in seconds.
+    fn time(&mut self) -> Option<i64> {
+        let sign = match self.peek()? {
+            b'-' => -1,
+            b'+' => 1,
+            _ => 0,
+        };
+        if sign != 0 {
+            self.s = &self.s[1..];
+        }
+        let mut secs = self.number()? * 3600;
+        for &unit in &[60, 1] {
+            if self.peek() != Some(b':') {
+                break;
+            }
+            self.s = &self.s[1..];
+            secs += self.number()? * unit;
+        }
+        Some(if sign < 0 { -secs } else { secs })
+    }
+
+    /// A `date[/time]` at which daylight saving time starts or ends.
+    fn rule(&mut self) -> Option<(RuleDay, i64)> {
+        let day = match self.peek()? {
+            b'J' => {
+                self.s = &self.s[1..];
+                RuleDay::Julian1(self.number()?)
+            }
+            b'M' => {
+                self.s = &self.s[1..];
+                let month = self.number()?;
+                self.expect(b'.')?;
+                let week = self.number()?;
+                self.expect(b'.')?;
+                let weekday = self.number()?;
+                if month < 1 || month > 12 || week < 1 || week > 5 || weekday > 6 {
+                    return None;
+                }
+                RuleDay::MonthWeekDay {
+                    month,
+                    week,
+                    weekday,
+                }
+            }
+            _ => RuleDay::Julian0(self.number()?),
+        };
+        let time = if self.peek() == Some(b'/') {
+            self.s = &self.s[1..];
+            self.time()?
+        } else {
+            7200
+        };
+        Some((day, time))
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(bytes)
+    }
+
+    fn int(&mut self, len: usize) -> Option<i64> {
+        let bytes = self.take(len)?;
+        let unsigned = bytes.iter().fold(0u64, |n, &b| n << 8 | u64::from(b));
+        // Sign-extend from `len` bytes.
+        let shift = 64 - 8 * len as u32;
+        Some((unsigned << shift) as i64 >> shift)
+    }
+
+    /// The version and the counts of the header:
+    /// isutcnt, isstdcnt, leapcnt, timecnt, typecnt and charcnt.
+    fn header(&mut self) -> Option<(u8, [usize; 6])> {
+        if self.take(4)? != b"TZif" {
+            return None;
+        }
+        let version = self.take(16)?[0];
+        let mut counts = [0; 6];
+        for count in counts.iter_mut() {
+            *count = self.int(4)? as u32 as usize;
+        }
+        Some((version, counts))
+    }
+
+    fn block(&mut self, counts: &[usize; 6], time_len: usize) -> Option<TimeZone> {
+        let [isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt] = *counts;
+        if typecnt == 0 {
+            return None;
+        }
+
+        let transitions = (0..timecnt)
+            .map(|_| self.int(time_len))
+            .collect::<Option<Vec<_>>>()?;
+        let indices = self
+            .take(timecnt)?
+            .iter()
+            .map(|&i| i as usize)
+            .collect::<Vec<_>>();
+        if indices.iter().any(|&i| i >= typecnt) {
+            return None;
+        }
+        let infos = (0..typecnt)
+            .map(|_| Some((self.int(4)? as i32, self.take(1)?[0] != 0, self.take(1)?[0])))
+            .collect::<Option<Vec<_>>>()?;
+        let chars = self.take(charcnt)?;
+        self.take(leapcnt * (time_len + 4) + isstdcnt + isutcnt)?;
+
+        let types = infos
+            .into_iter()
+            .map(|(utc_offset, is_dst, index)| {
+                let name = chars.get(index as usize..).unwrap_or(&[]);
+                let end = name
+                    .iter()
+                    .position(|&c| c == 0)
+                    .unwrap_or_else(|| name.len());
+                LocalTimeType {
+                    utc_offset,
+                    is_dst,
+                    abbreviation: String::from_utf8_lossy(&name[..end]).into_owned(),
+                }
+            })
+            .collect();
+
+        Some(TimeZone {
+            transitions,
+            indices,
+            types,
+            rule: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{utc_to_tm, TimeZone, Tm};
+
+    #[test]
+    fn should_convert_with_posix_rules() {
+        let zone = TimeZone::from_name("EST5EDT,M3.2.0,M11.1.0").unwrap();
+
+        // 2019-07-01 12:00:00 UTC
+        let summer = zone.to_tm(1_561_982_400);
+        assert_eq!((summer.tm_hour, summer.tm_isdst), (8, 1));
+        assert_eq!(summer.tm_gmtoff, -4 * 3600);
+        // 2019-01-01 00:00:00 UTC
+        let winter = zone.to_tm(1_546_300_800);
+        assert_eq!(
+            (winter.tm_year, winter.tm_mon, winter.tm_mday),
+            (118, 11, 31)
+        );
+        assert_eq!((winter.tm_hour, winter.tm_isdst), (19, 0));
+
+        let mut tm = summer;
+        assert_eq!(zone.from_tm(&mut tm), 1_561_982_400);
+    }
+
+    #[test]
+    fn should_normalize_in_mktime() {
+        let zone = TimeZone::utc();
+        let mut tm = Tm {
+            tm_mday: 32,
+            tm_mon: 11,
+            tm_year: 118,
+            tm_isdst: -1,
+            ..Tm::default()
+        };
+        assert_eq!(zone.from_tm(&mut tm), 1_546_300_800);
+        assert_eq!((tm.tm_year, tm.tm_mon, tm.tm_mday), (119, 0, 1));
+        assert_eq!((tm.tm_wday, tm.tm_yday), (2, 0));
+        assert_eq!(utc_to_tm(-1).tm_year, 69);
+    }
+}
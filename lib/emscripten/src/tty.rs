@@ -0,0 +1,212 @@
+//! A pseudo-terminal for the guest, so ported CLI tools that check for a
+//! terminal before they draw colors or progress bars render as they would
+//! natively.
+//!
+//! musl's `isatty` is a `TIOCGWINSZ` ioctl, so answering it makes the
+//! standard streams terminals of the configured size.
+use crate::env::get_emscripten_data;
+use crate::errno::Errno;
+use crate::varargs::VarArgs;
+use libc::c_int;
+use wasmer_runtime_core::vm::Ctx;
+
+const TCGETS: u32 = 21505;
+const TIOCGWINSZ: u32 = 21523;
+
+/// The terminal the standard streams of the guest are attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Terminal {
+    /// The size the guest sees, as `(columns, rows)`. Defaults to the size
+    /// of the host terminal, or 80x24 without one.
+    pub size: Option<(u16, u16)>,
+    /// What happens to the ANSI escape sequences the guest writes.
+    pub ansi: Ansi,
+}
+
+impl Default for Terminal {
+    fn default() -> Self {
+        Terminal {
+            size: None,
+            ansi: Ansi::Auto,
+        }
+    }
+}
+
+impl Terminal {
+    fn size_or_host(&self) -> (u16, u16) {
+        self.size.or_else(host_size).unwrap_or((80, 24))
+    }
+
+    fn strips_ansi(&self) -> bool {
+        match self.ansi {
+            Ansi::Keep => false,
+            Ansi::Strip => true,
+            Ansi::Auto => !host_renders_ansi(),
+        }
+    }
+}
+
+/// What happens to the ANSI escape sequences the guest writes to stdout
+/// and stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ansi {
+    /// They reach the host untouched.
+    Keep,
+    /// They are removed, e.g. when the output goes to a log file.
+    Strip,
+    /// They are kept when the host's stdout is a terminal that renders
+    /// them, and removed otherwise.
+    Auto,
+}
+
+#[cfg(not(target_os = "windows"))]
+fn host_size() -> Option<(u16, u16)> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == -1
+        || size.ws_col == 0
+    {
+        return None;
+    }
+    Some((size.ws_col, size.ws_row))
+}
+
+#[cfg(target_os = "windows")]
+fn host_size() -> Option<(u16, u16)> {
+    None
+}
+
+#[cfg(not(target_os = "windows"))]
+fn host_renders_ansi() -> bool {
+    unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
+/// The console only renders escape sequences once virtual terminal
+/// processing is turned on, which can't be told from here.
+#[cfg(target_os = "windows")]
+fn host_renders_ansi() -> bool {
+    false
+}
+
+/// `struct winsize` of the guest.
+#[repr(C)]
+struct GuestWinsize {
+    ws_row: u16,
+    ws_col: u16,
+    ws_xpixel: u16,
+    ws_ypixel: u16,
+}
+
+/// Answer the terminal ioctl `request` on `fd`, or return `None` for the
+/// caller to handle it as usual.
+pub(crate) fn ioctl(
+    ctx: &mut Ctx,
+    fd: c_int,
+    request: u32,
+    varargs: &mut VarArgs,
+) -> Option<c_int> {
+    if ctx.data.is_null() {
+        return None;
+    }
+    let terminal = get_emscripten_data(ctx).config.terminal?;
+    match request {
+        TCGETS | TIOCGWINSZ if fd < 0 || fd > 2 => Some(-Errno::ENOTTY.to_emscripten()),
+        TCGETS => Some(0),
+        TIOCGWINSZ => {
+            let (columns, rows) = terminal.size_or_host();
+            let argp: u32 = varargs.get(ctx);
+            let size = emscripten_memory_pointer!(ctx.memory(0), argp) as *mut GuestWinsize;
+            unsafe {
+                *size = GuestWinsize {
+                    ws_row: rows,
+                    ws_col: columns,
+                    ws_xpixel: 0,
+                    ws_ypixel: 0,
+                };
+            }
+            Some(0)
+        }
+        _ => None,
+    }
+}
+
+/// Where an `AnsiStripper` is in an escape sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Text,
+    /// After `ESC`.
+    Escape,
+    /// In a control sequence, `ESC [`, which ends with a byte in `@..=~`.
+    Csi,
+    /// In an operating system command, `ESC ]`, which ends with `BEL` or
+    /// `ESC \`.
+    Osc,
+    /// After `ESC` in an operating system command.
+    OscEscape,
+}
+
+/// Removes the escape sequences from a stream, which may split them
+/// across writes.
+#[derive(Debug)]
+pub(crate) struct AnsiStripper {
+    state: State,
+}
+
+impl Default for AnsiStripper {
+    fn default() -> Self {
+        AnsiStripper { state: State::Text }
+    }
+}
+
+impl AnsiStripper {
+    pub(crate) fn strip(&mut self, bytes: &[u8]) -> Vec<u8> {
+        const ESC: u8 = 0x1b;
+        const BEL: u8 = 0x07;
+        let mut text = Vec::with_capacity(bytes.len());
+        for &byte in bytes {
+            self.state = match (self.state, byte) {
+                (State::Text, ESC) => State::Escape,
+                (State::Text, _) => {
+                    text.push(byte);
+                    State::Text
+                }
+                (State::Escape, b'[') => State::Csi,
+                (State::Escape, b']') => State::Osc,
+                // An intermediate byte, as in `ESC ( B`, continues the escape.
+                (State::Escape, 0x20..=0x2f) => State::Escape,
+                (State::Escape, _) => State::Text,
+                (State::Csi, 0x40..=0x7e) => State::Text,
+                (State::Csi, _) => State::Csi,
+                (State::Osc, BEL) => State::Text,
+                (State::Osc, ESC) => State::OscEscape,
+                (State::Osc, _) => State::Osc,
+                (State::OscEscape, b'\\') => State::Text,
+                (State::OscEscape, _) => State::Osc,
+            };
+        }
+        text
+    }
+}
+
+/// The strippers of stdout and stderr, if the configured terminal strips
+/// the escape sequences.
+pub(crate) fn strippers(terminal: Option<&Terminal>) -> Option<[AnsiStripper; 2]> {
+    if terminal?.strips_ansi() {
+        Some(Default::default())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnsiStripper;
+
+    #[test]
+    fn strips_sequences_split_across_writes() {
+        let mut stripper = AnsiStripper::default();
+        let mut text = stripper.strip(b"\x1b[1;3");
+        text.extend(stripper.strip(b"1mred\x1b[0m 50%\r\x1b]0;title\x07"));
+        text.extend(stripper.strip(b"\x1b(Bdone\n"));
+        assert_eq!(text, b"red 50%\rdone\n");
+    }
+}
@@ -0,0 +1,483 @@
+//! `getcontext`, `makecontext`, `swapcontext` and `setcontext`, which
+//! interpreters with green threads use as coroutines.
+//!
+//! Wasm code can't switch native stacks, so every context made by
+//! `makecontext` gets a native stack of its own, and switching contexts
+//! switches the thread that runs the instance over to the stack of the
+//! target with the host's `swapcontext`. The state the backend catches
+//! traps with is kept apart for each stack, so a trap is caught by the call
+//! that started the context it happens in. A context that traps, or returns
+//! without a `uc_link` to go on with, ends the instance with an error.
+//!
+//! Hosts without `swapcontext`, such as Windows and musl, can't make
+//! contexts.
+use super::env::get_emscripten_data;
+use super::varargs::VarArgs;
+use libc::c_int;
+use std::cell::Cell;
+use std::ptr;
+use wasmer_runtime_core::{
+    error::{CallError, RuntimeError},
+    types::Value,
+    vm::Ctx,
+};
+
+use self::host::{HostContext, Stack};
+
+// Offsets in the guest `ucontext_t`.
+const UC_LINK: u32 = 4;
+const UC_STACK_SP: u32 = 8;
+const UC_STACK_SIZE: u32 = 16;
+// `uc_mcontext` is opaque to the guest, so it holds the index of the context.
+const UC_MCONTEXT: u32 = 20;
+
+/// The native stack of a context, which wasm frames live on.
+const STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// The `dynCall_*` a context starts with, by the number of arguments.
+const START_SIGNATURES: &[&str] = &["v", "vi", "vii", "viii", "viiii", "viiiii"];
+
+thread_local! {
+    /// The instance a context starts in, since `start_context` can't take
+    /// any arguments.
+    static STARTING: Cell<*mut Ctx> = Cell::new(ptr::null_mut());
+}
+
+/// Why the contexts of an instance stopped it.
+#[derive(Debug)]
+pub enum ContextError {
+    /// `makecontext` was given more arguments than a `dynCall_*` takes.
+    TooManyArguments(c_int),
+    /// The module doesn't export the `dynCall_*` to start a context with.
+    MissingDynCall(&'static str),
+    /// The host couldn't make a native stack for a context.
+    NoStack,
+    /// The host couldn't switch to another context.
+    Switch,
+    /// A context returned without a `uc_link`, which ends the program.
+    Returned,
+    /// A context trapped.
+    Call(CallError),
+}
+
+impl From<CallError> for ContextError {
+    fn from(err: CallError) -> Self {
+        ContextError::Call(err)
+    }
+}
+
+impl From<RuntimeError> for ContextError {
+    fn from(err: RuntimeError) -> Self {
+        ContextError::Call(err.into())
+    }
+}
+
+/// The contexts of an instance. The first one runs on the native stack of
+/// the thread that runs the instance.
+pub(crate) struct Contexts {
+    contexts: Vec<Context>,
+    current: usize,
+    /// Why a context stopped, for the first one to report when it resumes.
+    stopped: Option<ContextError>,
+}
+
+impl Default for Contexts {
+    fn default() -> Self {
+        Contexts {
+            contexts: vec![Context {
+                host: HostContext::new(),
+                _stack: None,
+                stack_top: 0,
+                start: None,
+                finished: false,
+            }],
+            current: 0,
+            stopped: None,
+        }
+    }
+}
+
+struct Context {
+    /// The registers of the context while it doesn't run. Boxed, so that
+    /// it doesn't move when the list grows.
+    host: Box<HostContext>,
+    /// Kept for the context to run on, `None` for the first one.
+    _stack: Option<Stack>,
+    /// The guest stack pointer to restore when the context resumes.
+    stack_top: u32,
+    /// The entry point, until the context first runs.
+    start: Option<Start>,
+    /// Whether the entry point returned, after which the context can't be
+    /// switched to.
+    finished: bool,
+}
+
+struct Start {
+    func: u32,
+    args: Vec<i32>,
+    link: u32,
+}
+
+fn read_u32(ctx: &mut Ctx, addr: u32) -> u32 {
+    unsafe { (emscripten_memory_pointer!(ctx.memory(0), addr) as *const u32).read_unaligned() }
+}
+
+fn write_u32(ctx: &mut Ctx, addr: u32, value: u32) {
+    unsafe { (emscripten_memory_pointer!(ctx.memory(0), addr) as *mut u32).write_unaligned(value) }
+}
+
+/// The context `ucp` was made or saved as, if it can be switched to.
+fn context_index(ctx: &mut Ctx, ucp: u32) -> Option<usize> {
+    let index = (read_u32(ctx, ucp + UC_MCONTEXT) as usize).checked_sub(1)?;
+    match get_emscripten_data(ctx).ucontexts.contexts.get(index) {
+        Some(context) if !context.finished => Some(index),
+        _ => None,
+    }
+}
+
+/// Switch to `target`, and return once the current context is resumed.
+fn switch_to(ctx: &mut Ctx, target: usize) -> Result<(), ContextError> {
+    let data = get_emscripten_data(ctx);
+    let current = data.ucontexts.current;
+    if current == target {
+        return Ok(());
+    }
+    if let Some(stack_save) = &data.stack_save {
+        data.ucontexts.contexts[current].stack_top = stack_save.call()?;
+    }
+    if let Some(stack_restore) = &data.stack_restore {
+        stack_restore.call(data.ucontexts.contexts[target].stack_top)?;
+    }
+    data.ucontexts.current = target;
+    let from: *mut HostContext = &mut *data.ucontexts.contexts[current].host;
+    let to: *mut HostContext = &mut *data.ucontexts.contexts[target].host;
+
+    STARTING.with(|starting| starting.set(ctx as *mut Ctx));
+    if !ctx.switch_stacks(|| unsafe { host::swap(from, to) }) {
+        return Err(ContextError::Switch);
+    }
+    // Resumed, by the context that stopped if the first one was.
+    match get_emscripten_data(ctx).ucontexts.stopped.take() {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Where the native stack of a context starts.
+extern "C" fn start_context() {
+    let ctx = unsafe { &mut *STARTING.with(Cell::get) };
+    let err = match run(ctx) {
+        Ok(Some(link)) => match switch_to(ctx, link) {
+            // Nothing switches back to a finished context.
+            Ok(()) => ContextError::Returned,
+            Err(err) => err,
+        },
+        Ok(None) => ContextError::Returned,
+        Err(err) => err,
+    };
+    // Returning goes on with the first context, through the host
+    // `uc_link`, which reports the error. This stack is never resumed, so
+    // nothing may be left on it to drop.
+    let contexts = &mut get_emscripten_data(ctx).ucontexts;
+    contexts.stopped = Some(err);
+    contexts.current = 0;
+}
+
+/// Run the entry point of the current context, and return the context to
+/// go on with.
+fn run(ctx: &mut Ctx) -> Result<Option<usize>, ContextError> {
+    let data = get_emscripten_data(ctx);
+    let current = data.ucontexts.current;
+    let start = match data.ucontexts.contexts[current].start.take() {
+        Some(start) => start,
+        None => return Ok(None),
+    };
+    let sig = START_SIGNATURES[start.args.len()];
+    let dyn_call = data
+        .dyn_calls
+        .get_mut(sig)
+        .ok_or(ContextError::MissingDynCall(sig))?;
+    let mut params = vec![Value::I32(start.func as i32)];
+    params.extend(start.args.iter().map(|&arg| Value::I32(arg)));
+    let called = dyn_call.call(&params);
+    get_emscripten_data(ctx).ucontexts.contexts[current].finished = true;
+    called?;
+
+    Ok(match start.link {
+        0 => None,
+        link => context_index(ctx, link),
+    })
+}
+
+/// emscripten: _getcontext
+pub fn _getcontext(ctx: &mut Ctx, ucp: u32) -> c_int {
+    debug!("emscripten::_getcontext {}", ucp);
+    let current = get_emscripten_data(ctx).ucontexts.current;
+    write_u32(ctx, ucp + UC_MCONTEXT, current as u32 + 1);
+    0
+}
+
+/// emscripten: _makecontext
+pub fn _makecontext(
+    ctx: &mut Ctx,
+    ucp: u32,
+    func: u32,
+    argc: c_int,
+    mut varargs: VarArgs,
+) -> Result<(), ContextError> {
+    debug!("emscripten::_makecontext {} {} {}", ucp, func, argc);
+    let sig = match START_SIGNATURES.get(argc as usize) {
+        Some(&sig) if argc >= 0 => sig,
+        _ => return Err(ContextError::TooManyArguments(argc)),
+    };
+    if !get_emscripten_data(ctx).dyn_calls.contains_key(sig) {
+        return Err(ContextError::MissingDynCall(sig));
+    }
+    let args = (0..argc).map(|_| varargs.get(ctx)).collect();
+    let link = read_u32(ctx, ucp + UC_LINK);
+    let stack_top = read_u32(ctx, ucp + UC_STACK_SP) + read_u32(ctx, ucp + UC_STACK_SIZE);
+    // The slot of a finished context can be reused, since nothing can
+    // switch to it anymore.
+    let reused = (read_u32(ctx, ucp + UC_MCONTEXT) as usize).checked_sub(1);
+
+    let contexts = &mut get_emscripten_data(ctx).ucontexts.contexts;
+    let stack = Stack::new(STACK_SIZE).ok_or(ContextError::NoStack)?;
+    let mut host = HostContext::new();
+    if !host::make(&mut host, &stack, &mut *contexts[0].host, start_context) {
+        return Err(ContextError::NoStack);
+    }
+    let context = Context {
+        host,
+        _stack: Some(stack),
+        stack_top,
+        start: Some(Start { func, args, link }),
+        finished: false,
+    };
+    let index = match reused {
+        Some(index)
+            if contexts
+                .get(index)
+                .map_or(false, |context| context.finished) =>
+        {
+            contexts[index] = context;
+            index
+        }
+        _ => {
+            contexts.push(context);
+            contexts.len() - 1
+        }
+    };
+    write_u32(ctx, ucp + UC_MCONTEXT, index as u32 + 1);
+    Ok(())
+}
+
+/// emscripten: _swapcontext
+pub fn _swapcontext(ctx: &mut Ctx, oucp: u32, ucp: u32) -> Result<c_int, ContextError> {
+    debug!("emscripten::_swapcontext {} {}", oucp, ucp);
+    let target = match context_index(ctx, ucp) {
+        Some(target) => target,
+        None => return Ok(-1),
+    };
+    _getcontext(ctx, oucp);
+    switch_to(ctx, target)?;
+    Ok(0)
+}
+
+/// emscripten: _setcontext
+pub fn _setcontext(ctx: &mut Ctx, ucp: u32) -> Result<c_int, ContextError> {
+    debug!("emscripten::_setcontext {}", ucp);
+    let target = match context_index(ctx, ucp) {
+        Some(target) => target,
+        None => return Ok(-1),
+    };
+    // The current context can only be resumed if it was saved before.
+    switch_to(ctx, target)?;
+    Ok(0)
+}
+
+#[cfg(all(unix, not(target_env = "musl")))]
+mod host {
+    use libc::{c_int, c_void, ucontext_t};
+    use std::ptr;
+
+    /// Room for the host `ucontext_t`, which some libcs make larger than
+    /// the `libc` crate has it.
+    #[repr(C, align(16))]
+    pub struct HostContext([u8; 4096]);
+
+    impl HostContext {
+        pub fn new() -> Box<Self> {
+            Box::new(HostContext([0; 4096]))
+        }
+
+        fn as_mut_ptr(&mut self) -> *mut ucontext_t {
+            self as *mut Self as *mut ucontext_t
+        }
+    }
+
+    /// A native stack, above a guard page.
+    pub struct Stack {
+        base: *mut c_void,
+        len: usize,
+        guard: usize,
+    }
+
+    impl Stack {
+        pub fn new(size: usize) -> Option<Stack> {
+            unsafe {
+                let guard = libc::sysconf(libc::_SC_PAGESIZE) as usize;
+                let base = libc::mmap(
+                    ptr::null_mut(),
+                    size + guard,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_PRIVATE | libc::MAP_ANON,
+                    -1,
+                    0,
+                );
+                if base == libc::MAP_FAILED {
+                    return None;
+                }
+                let stack = Stack {
+                    base,
+                    len: size + guard,
+                    guard,
+                };
+                if libc::mprotect(base, guard, libc::PROT_NONE) != 0 {
+                    return None;
+                }
+                Some(stack)
+            }
+        }
+    }
+
+    impl Drop for Stack {
+        fn drop(&mut self) {
+            unsafe {
+                libc::munmap(self.base, self.len);
+            }
+        }
+    }
+
+    /// Make `context` run `start` on `stack` when it's switched to, and
+    /// resume `link` when `start` returns.
+    pub fn make(
+        context: &mut HostContext,
+        stack: &Stack,
+        link: *mut HostContext,
+        start: extern "C" fn(),
+    ) -> bool {
+        unsafe {
+            let ucp = context.as_mut_ptr();
+            if getcontext(ucp) != 0 {
+                return false;
+            }
+            (*ucp).uc_stack.ss_sp = (stack.base as *mut u8).add(stack.guard) as _;
+            (*ucp).uc_stack.ss_size = stack.len - stack.guard;
+            (*ucp).uc_link = link as *mut ucontext_t;
+            makecontext(ucp, start, 0);
+        }
+        true
+    }
+
+    /// Save the running context in `from` and resume `to`. Returns once
+    /// `from` is resumed.
+    pub unsafe fn swap(from: *mut HostContext, to: *mut HostContext) -> bool {
+        swapcontext((*from).as_mut_ptr(), (*to).as_mut_ptr()) == 0
+    }
+
+    extern "C" {
+        fn getcontext(ucp: *mut ucontext_t) -> c_int;
+        fn makecontext(ucp: *mut ucontext_t, func: extern "C" fn(), argc: c_int, ...);
+        fn swapcontext(oucp: *mut ucontext_t, ucp: *const ucontext_t) -> c_int;
+    }
+}
+
+#[cfg(not(all(unix, not(target_env = "musl"))))]
+mod host {
+    pub struct HostContext;
+
+    impl HostContext {
+        pub fn new() -> Box<Self> {
+            Box::new(HostContext)
+        }
+    }
+
+    pub struct Stack;
+
+    impl Stack {
+        pub fn new(_size: usize) -> Option<Stack> {
+            Some(Stack)
+        }
+    }
+
+    /// Fails, since there's no `makecontext`.
+    pub fn make(
+        _context: &mut HostContext,
+        _stack: &Stack,
+        _link: *mut HostContext,
+        _start: extern "C" fn(),
+    ) -> bool {
+        false
+    }
+
+    /// Never called, since there's no other context to switch to.
+    pub unsafe fn swap(_from: *mut HostContext, _to: *mut HostContext) -> bool {
+        false
+    }
+}
+
+#[cfg(all(test, unix, not(target_env = "musl")))]
+mod tests {
+    use super::host::{self, HostContext, Stack};
+    use std::cell::{Cell, RefCell};
+    use std::ptr;
+    use std::thread::{self, ThreadId};
+
+    thread_local! {
+        static MAIN: Cell<*mut HostContext> = Cell::new(ptr::null_mut());
+        static OTHER: Cell<*mut HostContext> = Cell::new(ptr::null_mut());
+        static STEPS: RefCell<Vec<(&'static str, ThreadId)>> = RefCell::new(Vec::new());
+    }
+
+    fn step(name: &'static str) {
+        STEPS.with(|steps| steps.borrow_mut().push((name, thread::current().id())));
+    }
+
+    extern "C" fn bounce() {
+        step("context");
+        unsafe {
+            assert!(host::swap(OTHER.with(Cell::get), MAIN.with(Cell::get)));
+        }
+        step("context resumed");
+    }
+
+    #[test]
+    fn contexts_switch_on_the_calling_thread() {
+        let mut main = HostContext::new();
+        let mut other = HostContext::new();
+        let stack = Stack::new(64 * 1024).unwrap();
+        assert!(host::make(&mut other, &stack, &mut *main, bounce));
+        MAIN.with(|cell| cell.set(&mut *main));
+        OTHER.with(|cell| cell.set(&mut *other));
+
+        unsafe {
+            assert!(host::swap(&mut *main, &mut *other));
+            step("main");
+            // `bounce` returns into its link, which is `main`.
+            assert!(host::swap(&mut *main, &mut *other));
+        }
+        step("main resumed");
+
+        let id = thread::current().id();
+        let steps = STEPS.with(|steps| steps.borrow().clone());
+        assert_eq!(
+            steps,
+            vec![
+                ("context", id),
+                ("main", id),
+                ("context resumed", id),
+                ("main resumed", id),
+            ]
+        );
+    }
+}
@@ -108,6 +108,47 @@ pub unsafe fn copy_terminated_array_of_cstrs(_ctx: &mut Ctx, cstrs: *mut *mut c_
     0
 }
 
+/// Returns a host pointer to the `len` bytes of guest memory starting at
+/// `offset`, or `None` if any part of the range lies outside the memory.
+pub fn guest_memory_range(ctx: &Ctx, offset: u32, len: u32) -> Option<*mut u8> {
+    let view = ctx.memory(0).view::<u8>();
+    let end = (offset as usize).checked_add(len as usize)?;
+    view.get(offset as usize..end)
+        .map(|cells| cells.as_ptr() as *mut u8)
+}
+
+/// Copies the `len` bytes of guest memory at `ptr`, or returns `None` if
+/// any of them lie outside the memory.
+pub fn read_guest_bytes(ctx: &Ctx, ptr: u32, len: u32) -> Option<Vec<u8>> {
+    let base = guest_memory_range(ctx, ptr, len)?;
+    Some(unsafe { slice::from_raw_parts(base, len as usize) }.to_vec())
+}
+
+/// Copies `bytes` to guest memory at `ptr`, or returns `None`, writing
+/// nothing, if any of them would lie outside the memory.
+pub fn write_guest_bytes(ctx: &Ctx, ptr: u32, bytes: &[u8]) -> Option<()> {
+    let base = guest_memory_range(ctx, ptr, bytes.len() as u32)?;
+    unsafe { base.copy_from_nonoverlapping(bytes.as_ptr(), bytes.len()) };
+    Some(())
+}
+
+/// The layout of `struct iovec` in wasm32 guest memory.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct GuestIovec {
+    pub iov_base: u32,
+    pub iov_len: u32,
+}
+
+/// Reads the `index`th entry of the guest iovec array at `iov`, or `None`
+/// if it is out of bounds.
+pub fn read_guest_iovec(ctx: &Ctx, iov: u32, index: u32) -> Option<GuestIovec> {
+    let size = size_of::<GuestIovec>() as u32;
+    let offset = index.checked_mul(size)?.checked_add(iov)?;
+    let ptr = guest_memory_range(ctx, offset, size)? as *const GuestIovec;
+    Some(unsafe { ptr.read_unaligned() })
+}
+
 #[repr(C)]
 pub struct GuestStat {
     st_dev: u32,
@@ -1,3 +1,4 @@
+use crate::utils::guest_memory_range;
 use std::mem;
 use wasmer_runtime_core::{
     types::{Type, WasmExternType},
@@ -16,6 +17,14 @@ impl VarArgs {
         self.pointer += mem::size_of::<T>() as u32;
         unsafe { (ptr as *const T).read() }
     }
+
+    /// The next argument, or `None` if it's out of the guest memory.
+    pub fn try_get<T: Sized>(&mut self, ctx: &mut Ctx) -> Option<T> {
+        let size = mem::size_of::<T>() as u32;
+        let ptr = guest_memory_range(ctx, self.pointer, size)?;
+        self.pointer += size;
+        Some(unsafe { (ptr as *const T).read_unaligned() })
+    }
 }
 
 unsafe impl WasmExternType for VarArgs {
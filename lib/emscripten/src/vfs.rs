@@ -0,0 +1,1020 @@
+//! The guest's view of the filesystem.
+//!
+//! Each instance has a working directory of its own, so relative paths
+//! are resolved here rather than against the working directory of the
+//! host process, which the guest never changes.
+//!
+//! The embedder can mount host directories in the guest with
+//! `EmscriptenConfig::mapped_dirs`. The paths of the guest are then
+//! translated through the mounts, and the ones outside of them are
+//! rejected. Without mounts, the paths of the guest are the host's.
+//! With `EmscriptenConfig::capabilities`, the guest paths outside of the
+//! granted ones are rejected too.
+//!
+//! Files live on the host, but their permission bits are emulated: the
+//! modes the guest sets are recorded here and take precedence over the
+//! host's, so a guest sees the same permissions whatever the host
+//! filesystem supports and whichever user runs it.
+//!
+//! Paths are bytes to the guest, and are handed to the host as they are,
+//! except on Windows, whose paths must be Unicode. `..` is resolved
+//! lexically, the way the emscripten filesystem does: `dir/link/..` is
+//! `dir` even when `link` is a symbolic link to a directory elsewhere,
+//! where the host would resolve it to the parent of the link's target.
+use crate::capabilities::Capabilities;
+use crate::env::get_emscripten_data;
+use crate::errno::Errno;
+use crate::stat_cache::is_within;
+use byteorder::{ByteOrder, LittleEndian};
+use libc::{c_char, c_int};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{CStr, CString, OsStr};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use wasmer_runtime_core::vm::Ctx;
+
+// The flags of `access` and `open`, as the guest defines them.
+const R_OK: c_int = 4;
+const W_OK: c_int = 2;
+const X_OK: c_int = 1;
+const O_ACCMODE: c_int = 0o3;
+const O_RDONLY: c_int = 0o0;
+const O_WRONLY: c_int = 0o1;
+
+// The `whence` of `lseek`, as the guest defines it.
+const SEEK_SET: c_int = 0;
+const SEEK_CUR: c_int = 1;
+
+// The types of `dirent`, as the guest defines them.
+const DT_UNKNOWN: u8 = 0;
+const DT_DIR: u8 = 4;
+const DT_REG: u8 = 8;
+const DT_LNK: u8 = 10;
+
+/// The size of the guest's `struct dirent`, which `getdents64` fills.
+pub(crate) const DIRENT_SIZE: usize = 280;
+
+const DEFAULT_UMASK: u32 = 0o022;
+
+/// The `dirfd` that makes the `*at` syscalls resolve against the working directory.
+const AT_FDCWD: c_int = -100;
+
+/// The per-instance state of the filesystem.
+pub(crate) struct Vfs {
+    cwd: PathBuf,
+    umask: u32,
+    /// The permission bits set by the guest, by resolved path.
+    modes: HashMap<CString, u32>,
+    /// The host paths of the open directories, which the `*at` syscalls
+    /// resolve relative paths against.
+    dir_fds: HashMap<c_int, PathBuf>,
+    /// The index of the next entry `getdents64` reads from each open
+    /// directory that it read from.
+    dir_positions: HashMap<c_int, usize>,
+    /// The host paths of the open regular files, to carry them in a
+    /// snapshot.
+    file_paths: HashMap<c_int, PathBuf>,
+    /// The host descriptors of the guest descriptors a thaw couldn't give
+    /// their number back, because the host uses it. The other descriptors
+    /// of the guest are the host's.
+    host_fds: HashMap<c_int, c_int>,
+    /// The host directories mounted in the guest, by guest path, the
+    /// deepest first.
+    mounts: Vec<(PathBuf, PathBuf)>,
+    /// What the guest is granted, if it's confined to it.
+    granted: Option<Capabilities>,
+}
+
+impl Default for Vfs {
+    fn default() -> Self {
+        Vfs {
+            // The working directory of a new instance is the one of the host.
+            cwd: env::current_dir().unwrap_or_else(|_| PathBuf::from("/")),
+            umask: DEFAULT_UMASK,
+            modes: HashMap::new(),
+            dir_fds: HashMap::new(),
+            dir_positions: HashMap::new(),
+            file_paths: HashMap::new(),
+            host_fds: HashMap::new(),
+            mounts: Vec::new(),
+            granted: None,
+        }
+    }
+}
+
+impl Vfs {
+    /// The filesystem of an instance with `mapped_dirs` mounted, which
+    /// starts in `/` when there are any, and confined to the paths
+    /// `granted` has, if any.
+    pub(crate) fn new(mapped_dirs: &[(String, PathBuf)], granted: Option<&Capabilities>) -> Self {
+        let mut vfs = Vfs::default();
+        vfs.granted = granted.cloned();
+        if mapped_dirs.is_empty() {
+            return vfs;
+        }
+        vfs.cwd = PathBuf::from("/");
+        vfs.mounts = mapped_dirs
+            .iter()
+            .map(|(guest, host)| (normalize(&Path::new("/").join(guest)), host.clone()))
+            .collect();
+        vfs.mounts
+            .sort_by_key(|(guest, _)| Reverse(guest.components().count()));
+        vfs
+    }
+
+    /// The host path of the absolute guest path `path`.
+    fn to_host(&self, path: &Path) -> Result<PathBuf, Errno> {
+        if let Some(granted) = &self.granted {
+            if !granted.allows_path(path) {
+                return Err(Errno::EACCES);
+            }
+        }
+        if self.mounts.is_empty() {
+            return Ok(path.to_owned());
+        }
+        self.mounts
+            .iter()
+            .find_map(|(guest, host)| match path.strip_prefix(guest).ok()? {
+                rest if rest.as_os_str().is_empty() => Some(host.clone()),
+                rest => Some(host.join(rest)),
+            })
+            .ok_or(Errno::EACCES)
+    }
+
+    /// Move the modes of `old` and the paths below it to `new`, dropping
+    /// the ones of what `new` replaced.
+    fn rename_modes(&mut self, old: &[u8], new: &[u8]) {
+        if old == new {
+            return;
+        }
+        self.modes
+            .retain(|path, _| !is_within(path.to_bytes(), new));
+        let moved: Vec<_> = self
+            .modes
+            .keys()
+            .filter(|path| is_within(path.to_bytes(), old))
+            .cloned()
+            .collect();
+        for path in moved {
+            let mode = self.modes.remove(&path);
+            let mut renamed = new.to_vec();
+            renamed.extend_from_slice(&path.to_bytes()[old.len()..]);
+            if let (Some(mode), Ok(renamed)) = (mode, CString::new(renamed)) {
+                self.modes.insert(renamed, mode);
+            }
+        }
+    }
+
+    /// Make `new` refer to what `fd` does, once the host duplicated it.
+    fn duplicate(&mut self, fd: c_int, new: c_int) {
+        match self.dir_fds.get(&fd).cloned() {
+            Some(path) => self.dir_fds.insert(new, path),
+            None => self.dir_fds.remove(&new),
+        };
+        match self.dir_positions.get(&fd).cloned() {
+            Some(position) => self.dir_positions.insert(new, position),
+            None => self.dir_positions.remove(&new),
+        };
+        match self.file_paths.get(&fd).cloned() {
+            Some(path) => self.file_paths.insert(new, path),
+            None => self.file_paths.remove(&new),
+        };
+    }
+
+    /// Forget the host descriptor `fd`, once it's closed.
+    fn forget(&mut self, fd: c_int) {
+        self.dir_fds.remove(&fd);
+        self.dir_positions.remove(&fd);
+        self.file_paths.remove(&fd);
+    }
+
+    /// The host descriptor of the guest descriptor `fd`.
+    fn host_fd(&self, fd: c_int) -> c_int {
+        self.host_fds.get(&fd).cloned().unwrap_or(fd)
+    }
+
+    /// The guest descriptor of the host descriptor `fd`.
+    fn guest_fd(&self, fd: c_int) -> c_int {
+        self.host_fds
+            .iter()
+            .find(|&(_, &host)| host == fd)
+            .map_or(fd, |(&guest, _)| guest)
+    }
+
+    /// Make the host descriptor `opened` the guest descriptor `fd`: moved
+    /// to that number if the host doesn't use it, and mapped to it
+    /// otherwise. Returns the host descriptor.
+    #[cfg(not(target_os = "windows"))]
+    fn place(&mut self, opened: c_int, fd: c_int) -> c_int {
+        if opened == fd {
+            return fd;
+        }
+        let free = unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1;
+        if !free || unsafe { libc::dup2(opened, fd) } == -1 {
+            self.host_fds.insert(fd, opened);
+            return opened;
+        }
+        unsafe { libc::close(opened) };
+        self.duplicate(opened, fd);
+        self.forget(opened);
+        fd
+    }
+
+    #[cfg(target_os = "windows")]
+    fn place(&mut self, opened: c_int, fd: c_int) -> c_int {
+        if opened != fd {
+            self.host_fds.insert(fd, opened);
+        }
+        opened
+    }
+
+    /// The state of the filesystem as lines of text, to carry in a snapshot.
+    ///
+    /// The open directories and files are carried by their guest descriptors
+    /// and guest paths, so they can be opened again wherever the snapshot is
+    /// thawed.
+    fn freeze(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("vfs.cwd {}", self.cwd.display()),
+            format!("vfs.umask {:o}", self.umask),
+        ];
+        // The lines are text, so the paths that aren't are left out rather
+        // than restored as other paths.
+        for (path, mode) in &self.modes {
+            if let Ok(path) = path.to_str() {
+                lines.push(format!("vfs.mode {:o} {}", mode, path));
+            }
+        }
+        let guest_path = |path: &Path| Some(self.to_guest(path)?.to_str()?.to_string());
+        for (&fd, path) in &self.dir_fds {
+            if let Some(path) = guest_path(path) {
+                lines.push(format!("vfs.dir {} {}", self.guest_fd(fd), path));
+            }
+        }
+        for (&fd, path) in &self.file_paths {
+            if let (Some(path), Some((access, offset))) = (guest_path(path), file_state(fd)) {
+                lines.push(format!(
+                    "vfs.file {} {} {} {}",
+                    self.guest_fd(fd),
+                    access,
+                    offset,
+                    path
+                ));
+            }
+        }
+        lines
+    }
+
+    /// Restore the state of the filesystem from the lines of `freeze`,
+    /// ignoring any other line. The open directories and files are opened
+    /// again for the same guest descriptors, without taking the descriptors
+    /// the host uses.
+    fn thaw(&mut self, lines: &[String]) {
+        for line in lines {
+            let (key, rest) = match split_field(line) {
+                Some(fields) => fields,
+                None => continue,
+            };
+            match key {
+                "vfs.cwd" => self.cwd = PathBuf::from(rest),
+                "vfs.umask" => {
+                    if let Ok(umask) = u32::from_str_radix(rest, 8) {
+                        self.umask = umask;
+                    }
+                }
+                "vfs.mode" => {
+                    if let Some((mode, path)) = split_field(rest) {
+                        if let (Ok(mode), Ok(path)) =
+                            (u32::from_str_radix(mode, 8), CString::new(path))
+                        {
+                            self.modes.insert(path, mode);
+                        }
+                    }
+                }
+                "vfs.dir" => {
+                    if let Some((fd, path)) = split_field(rest) {
+                        if let (Ok(fd), Ok(path)) = (fd.parse(), self.to_host(Path::new(path))) {
+                            if let Some(opened) = reopen(self, fd, &path, "dir", 0) {
+                                self.dir_fds.insert(opened, path);
+                            }
+                        }
+                    }
+                }
+                "vfs.file" => {
+                    let fields: Vec<_> = rest.splitn(4, ' ').collect();
+                    if let [fd, access, offset, path] = fields[..] {
+                        if let (Ok(fd), Ok(offset), Ok(path)) =
+                            (fd.parse(), offset.parse(), self.to_host(Path::new(path)))
+                        {
+                            if let Some(opened) = reopen(self, fd, &path, access, offset) {
+                                self.file_paths.insert(opened, path);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The guest path of the host path `path`, the way `to_host` maps it.
+    fn to_guest(&self, path: &Path) -> Option<PathBuf> {
+        if self.mounts.is_empty() {
+            return Some(path.to_owned());
+        }
+        self.mounts
+            .iter()
+            .filter_map(|(guest, host)| Some((guest, path.strip_prefix(host).ok()?)))
+            .max_by_key(|(_, rest)| Reverse(rest.components().count()))
+            .map(|(guest, rest)| guest.join(rest))
+    }
+}
+
+fn get_vfs(ctx: &mut Ctx) -> Option<&mut Vfs> {
+    // Syscalls can happen before `run_emscripten_instance` sets up the data.
+    if ctx.data.is_null() {
+        return None;
+    }
+    Some(&mut get_emscripten_data(ctx).vfs)
+}
+
+/// The working directory of the guest.
+pub(crate) fn cwd(ctx: &mut Ctx) -> PathBuf {
+    match get_vfs(ctx) {
+        Some(vfs) => vfs.cwd.clone(),
+        None => Vfs::default().cwd,
+    }
+}
+
+/// Change the working directory of the guest to `path`, which must
+/// already be resolved.
+pub(crate) fn set_cwd(ctx: &mut Ctx, path: PathBuf) {
+    if let Some(vfs) = get_vfs(ctx) {
+        vfs.cwd = path;
+    }
+}
+
+/// Set the file mode creation mask of the guest, returning the previous one.
+pub(crate) fn set_umask(ctx: &mut Ctx, umask: u32) -> u32 {
+    match get_vfs(ctx) {
+        Some(vfs) => std::mem::replace(&mut vfs.umask, umask & 0o777),
+        None => DEFAULT_UMASK,
+    }
+}
+
+/// The permission bits of a file the guest creates with `mode`.
+pub(crate) fn creation_mode(ctx: &mut Ctx, mode: u32) -> u32 {
+    let umask = get_vfs(ctx).map_or(DEFAULT_UMASK, |vfs| vfs.umask);
+    mode & 0o7777 & !umask
+}
+
+/// Record the permission bits the guest gave to `path`.
+pub(crate) fn set_mode(ctx: &mut Ctx, path: &CStr, mode: u32) {
+    if let Some(vfs) = get_vfs(ctx) {
+        vfs.modes.insert(path.to_owned(), mode & 0o7777);
+    }
+}
+
+/// Forget about `path`, once it's removed.
+pub(crate) fn remove(ctx: &mut Ctx, path: &CStr) {
+    if let Some(vfs) = get_vfs(ctx) {
+        vfs.modes.remove(path);
+    }
+}
+
+/// Carry what's recorded about `old` over to `new`, once it's renamed.
+pub(crate) fn renamed(ctx: &mut Ctx, old: &CStr, new: &CStr) {
+    if let Some(vfs) = get_vfs(ctx) {
+        vfs.rename_modes(old.to_bytes(), new.to_bytes());
+    }
+}
+
+/// The permission bits the guest gave to `path`, if it did.
+pub(crate) fn mode(ctx: &mut Ctx, path: &CStr) -> Option<u32> {
+    get_vfs(ctx)?.modes.get(path).cloned()
+}
+
+/// Replace the permission bits of a host `st_mode` with the emulated ones.
+pub(crate) fn apply_mode(ctx: &mut Ctx, path: &CStr, st_mode: u32) -> u32 {
+    match mode(ctx, path) {
+        Some(mode) => st_mode & !0o7777 | mode,
+        None => st_mode,
+    }
+}
+
+/// Check whether the guest may access `path` as `amode` asks, returning
+/// 0 or a negated errno.
+///
+/// The guest owns every file, so the owner bits of emulated modes apply.
+pub(crate) fn access(ctx: &mut Ctx, path: &CStr, amode: c_int) -> c_int {
+    let metadata = match fs::metadata(to_path(path)) {
+        Ok(metadata) => metadata,
+        Err(err) => return -Errno::from_io_error(&err).to_emscripten(),
+    };
+    if amode & !(R_OK | W_OK | X_OK) != 0 {
+        return -Errno::EINVAL.to_emscripten();
+    }
+
+    let allowed = match mode(ctx, path) {
+        Some(mode) => (mode >> 6) as c_int & (R_OK | W_OK | X_OK),
+        None => return host_access(path, &metadata, amode),
+    };
+    if amode & !allowed != 0 {
+        return -Errno::EACCES.to_emscripten();
+    }
+    0
+}
+
+#[cfg(unix)]
+fn host_access(path: &CStr, _metadata: &fs::Metadata, amode: c_int) -> c_int {
+    // The guest's flags have the same values as the host's.
+    crate::errno::translate_ret(unsafe { libc::access(path.as_ptr(), amode) })
+}
+
+#[cfg(windows)]
+fn host_access(_path: &CStr, metadata: &fs::Metadata, amode: c_int) -> c_int {
+    // Windows only knows whether a file is read-only.
+    if amode & W_OK != 0 && metadata.permissions().readonly() {
+        return -Errno::EACCES.to_emscripten();
+    }
+    0
+}
+
+/// Check the emulated permissions for opening `path` with `flags`,
+/// returning 0 or a negated errno.
+pub(crate) fn check_open(ctx: &mut Ctx, path: &CStr, flags: c_int) -> c_int {
+    let mode = match mode(ctx, path) {
+        Some(mode) => mode,
+        None => return 0,
+    };
+    let required = match flags & O_ACCMODE {
+        O_RDONLY => R_OK,
+        O_WRONLY => W_OK,
+        _ => R_OK | W_OK,
+    };
+    if required & !((mode >> 6) as c_int) != 0 {
+        return -Errno::EACCES.to_emscripten();
+    }
+    0
+}
+
+/// Remember the path of `fd`.
+pub(crate) fn opened(ctx: &mut Ctx, fd: c_int, path: &CStr) {
+    let path = to_path(path);
+    if let Some(vfs) = get_vfs(ctx) {
+        vfs.forget(fd);
+        if path.is_dir() {
+            vfs.dir_fds.insert(fd, path);
+        } else {
+            vfs.file_paths.insert(fd, path);
+        }
+    }
+}
+
+/// The host descriptor of the guest descriptor `fd`, which the syscalls
+/// that take descriptors go through.
+pub(crate) fn host_fd(ctx: &mut Ctx, fd: c_int) -> c_int {
+    match get_vfs(ctx) {
+        Some(vfs) => vfs.host_fd(fd),
+        None => fd,
+    }
+}
+
+/// Forget the guest descriptor `fd`, once its host descriptor is closed.
+pub(crate) fn closed(ctx: &mut Ctx, fd: c_int) {
+    if let Some(vfs) = get_vfs(ctx) {
+        let host = vfs.host_fds.remove(&fd).unwrap_or(fd);
+        vfs.forget(host);
+        // The guest descriptor that couldn't have the number of the closed
+        // one can have it now. The host closing a descriptor of its own
+        // doesn't free the number that way, so the guest may then open a
+        // file under a number it knows another one by.
+        if let Some(moved) = vfs.host_fds.remove(&host) {
+            vfs.place(moved, host);
+        }
+    }
+}
+
+/// Remember that `new` is a duplicate of `fd`, by `dup2`, `dup3` or
+/// `fcntl`, which closed what `new` was before.
+pub(crate) fn duplicated(ctx: &mut Ctx, fd: c_int, new: c_int) {
+    if let Some(vfs) = get_vfs(ctx) {
+        vfs.duplicate(fd, new);
+    }
+}
+
+/// An entry of a directory, as `getdents64` reports it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dirent {
+    ino: u64,
+    kind: u8,
+    name: Vec<u8>,
+}
+
+impl Dirent {
+    /// Fill the guest `struct dirent` at the start of `buf`, which is the
+    /// entry before `offset`.
+    pub(crate) fn write(&self, buf: &mut [u8], offset: usize) {
+        let buf = &mut buf[..DIRENT_SIZE];
+        // The name is truncated to what `d_name` can hold.
+        let name = &self.name[..self.name.len().min(255)];
+        LittleEndian::write_u32(&mut buf[0..4], self.ino as u32);
+        LittleEndian::write_u32(&mut buf[4..8], offset as u32);
+        LittleEndian::write_u16(&mut buf[8..10], DIRENT_SIZE as u16);
+        buf[10] = self.kind;
+        buf[11..11 + name.len()].copy_from_slice(name);
+        buf[11 + name.len()] = 0;
+    }
+}
+
+/// The entries of the host directory `path`, `.` and `..` first, the way
+/// `getdents64` lists them.
+pub(crate) fn read_dir(path: &CStr) -> io::Result<Vec<Dirent>> {
+    let path = to_path(path);
+    let mut dirents = Vec::new();
+    for (name, dir) in &[(".", path.clone()), ("..", path.join(".."))] {
+        dirents.push(Dirent {
+            ino: metadata_ino(&fs::metadata(dir)?),
+            kind: DT_DIR,
+            name: name.as_bytes().to_vec(),
+        });
+    }
+    for entry in fs::read_dir(&path)? {
+        let entry = entry?;
+        let kind = match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => DT_DIR,
+            Ok(file_type) if file_type.is_file() => DT_REG,
+            Ok(file_type) if file_type.is_symlink() => DT_LNK,
+            _ => DT_UNKNOWN,
+        };
+        dirents.push(Dirent {
+            ino: entry.metadata().map(|metadata| metadata_ino(&metadata))?,
+            kind,
+            name: name_bytes(&entry.file_name()),
+        });
+    }
+    Ok(dirents)
+}
+
+#[cfg(unix)]
+fn metadata_ino(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.ino()
+}
+
+/// Windows has no inode numbers.
+#[cfg(windows)]
+fn metadata_ino(_metadata: &fs::Metadata) -> u64 {
+    0
+}
+
+/// The bytes of a host file name or path, which the guest sees as they
+/// are.
+#[cfg(unix)]
+pub(crate) fn name_bytes(name: &OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    name.as_bytes().to_vec()
+}
+
+#[cfg(windows)]
+pub(crate) fn name_bytes(name: &OsStr) -> Vec<u8> {
+    name.to_string_lossy().into_owned().into_bytes()
+}
+
+/// The host path of the bytes of `path`.
+#[cfg(unix)]
+pub(crate) fn to_path(path: &CStr) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(OsStr::from_bytes(path.to_bytes()))
+}
+
+#[cfg(windows)]
+pub(crate) fn to_path(path: &CStr) -> PathBuf {
+    PathBuf::from(path.to_string_lossy().into_owned())
+}
+
+/// The bytes of the host path `path`, as a C string.
+pub(crate) fn to_cstring(path: &Path) -> CString {
+    // Host paths can't have a NUL in them, and an empty path names nothing.
+    CString::new(name_bytes(path.as_os_str())).unwrap_or_default()
+}
+
+/// The host path of the directory open as `fd`, and the index of the next
+/// entry `getdents64` reads from it.
+pub(crate) fn read_position(ctx: &mut Ctx, fd: c_int) -> Result<(CString, usize), Errno> {
+    let vfs = get_vfs(ctx).ok_or(Errno::EBADF)?;
+    let path = match vfs.dir_fds.get(&fd) {
+        Some(path) => path,
+        None => return Err(not_a_directory(fd)),
+    };
+    let position = vfs.dir_positions.get(&fd).cloned().unwrap_or(0);
+    Ok((to_cstring(path), position))
+}
+
+pub(crate) fn set_read_position(ctx: &mut Ctx, fd: c_int, position: usize) {
+    if let Some(vfs) = get_vfs(ctx) {
+        vfs.dir_positions.insert(fd, position);
+    }
+}
+
+/// `lseek` in the directory open as `fd`, whose offsets are the ones of
+/// the entries `getdents64` fills, or `None` if `fd` isn't a directory.
+pub(crate) fn seek_dir(ctx: &mut Ctx, fd: c_int, offset: i64, whence: c_int) -> Option<c_int> {
+    let vfs = get_vfs(ctx)?;
+    if !vfs.dir_fds.contains_key(&fd) {
+        return None;
+    }
+    let position = vfs.dir_positions.entry(fd).or_insert(0);
+    let offset = match whence {
+        SEEK_SET => offset,
+        SEEK_CUR => (*position * DIRENT_SIZE) as i64 + offset,
+        _ => return Some(-Errno::EINVAL.to_emscripten()),
+    };
+    if offset < 0 || offset > i64::from(c_int::max_value()) {
+        return Some(-Errno::EINVAL.to_emscripten());
+    }
+    *position = offset as usize / DIRENT_SIZE;
+    Some((*position * DIRENT_SIZE) as c_int)
+}
+
+/// The error for a descriptor that should be an open directory but
+/// isn't one.
+#[cfg(unix)]
+fn not_a_directory(fd: c_int) -> Errno {
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        Errno::EBADF
+    } else {
+        Errno::ENOTDIR
+    }
+}
+
+/// Directories can't be opened as descriptors on Windows.
+#[cfg(windows)]
+fn not_a_directory(_fd: c_int) -> Errno {
+    Errno::EBADF
+}
+
+/// The state of the filesystem of the guest as lines of text, to carry
+/// in a snapshot.
+pub(crate) fn freeze(ctx: &mut Ctx) -> Vec<String> {
+    match get_vfs(ctx) {
+        Some(vfs) => vfs.freeze(),
+        None => Vec::new(),
+    }
+}
+
+/// Restore the state of the filesystem from the lines of `freeze`.
+pub(crate) fn thaw(ctx: &mut Ctx, lines: &[String]) {
+    if let Some(vfs) = get_vfs(ctx) {
+        vfs.thaw(lines);
+    }
+}
+
+/// Split `line` at its first space.
+fn split_field(line: &str) -> Option<(&str, &str)> {
+    let space = line.find(' ')?;
+    Some((&line[..space], &line[space + 1..]))
+}
+
+/// How the file open as `fd` was opened, as `r`, `w` or `rw`, followed by
+/// `a` if it appends, and its offset.
+#[cfg(not(target_os = "windows"))]
+fn file_state(fd: c_int) -> Option<(String, i64)> {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    let offset = unsafe { libc::lseek(fd, 0, libc::SEEK_CUR) };
+    if flags == -1 || offset == -1 {
+        return None;
+    }
+    let mut access = match flags & libc::O_ACCMODE {
+        libc::O_RDONLY => "r",
+        libc::O_WRONLY => "w",
+        _ => "rw",
+    }
+    .to_string();
+    if flags & libc::O_APPEND != 0 {
+        access.push('a');
+    }
+    Some((access, offset as i64))
+}
+
+/// Open `path` again for the guest descriptor `fd`, the way `file_state`
+/// describes it or as a directory, at `offset`. Returns the host
+/// descriptor.
+#[cfg(not(target_os = "windows"))]
+fn reopen(vfs: &mut Vfs, fd: c_int, path: &Path, access: &str, offset: i64) -> Option<c_int> {
+    let (access, append) = if access.ends_with('a') {
+        (&access[..access.len() - 1], libc::O_APPEND)
+    } else {
+        (access, 0)
+    };
+    let flags = match access {
+        "dir" => libc::O_RDONLY | libc::O_DIRECTORY,
+        "r" => libc::O_RDONLY,
+        "w" => libc::O_WRONLY,
+        "rw" => libc::O_RDWR,
+        _ => return None,
+    };
+    let path = to_cstring(path);
+    let opened = unsafe { libc::open(path.as_ptr(), flags | append) };
+    if opened == -1 {
+        return None;
+    }
+    if offset != 0 && unsafe { libc::lseek(opened, offset as _, libc::SEEK_SET) } == -1 {
+        unsafe { libc::close(opened) };
+        return None;
+    }
+    Some(vfs.place(opened, fd))
+}
+
+/// Snapshots don't carry the open files on Windows.
+#[cfg(target_os = "windows")]
+fn file_state(_fd: c_int) -> Option<(String, i64)> {
+    None
+}
+
+/// Directories can't be opened as descriptors on Windows.
+#[cfg(target_os = "windows")]
+fn reopen(_vfs: &mut Vfs, _fd: c_int, _path: &Path, _access: &str, _offset: i64) -> Option<c_int> {
+    None
+}
+
+/// A line for each file descriptor the guest has open, with what it
+/// refers to when that's known.
+pub(crate) fn describe_open_fds(ctx: &mut Ctx) -> Vec<String> {
+    let mut fds = host_open_fds();
+    if let Some(vfs) = get_vfs(ctx) {
+        for (fd, path) in &vfs.dir_fds {
+            if !fds.iter().any(|(open, _)| open == fd) {
+                fds.push((*fd, Some(path.clone())));
+            }
+        }
+    }
+    fds.sort();
+    fds.into_iter()
+        .map(|(fd, path)| match path {
+            Some(path) => format!("fd {}: {}", fd, path.display()),
+            None => format!("fd {}", fd),
+        })
+        .collect()
+}
+
+/// The guest's descriptors are the host's, so the host lists them.
+#[cfg(target_os = "linux")]
+fn host_open_fds() -> Vec<(c_int, Option<PathBuf>)> {
+    let entries = match fs::read_dir("/proc/self/fd") {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let fd = entry.file_name().to_str()?.parse().ok()?;
+            Some((fd, fs::read_link(entry.path()).ok()))
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn host_open_fds() -> Vec<(c_int, Option<PathBuf>)> {
+    Vec::new()
+}
+
+/// The path at `path_addr` as the guest wrote it, if it's absolute and
+/// has nothing to resolve, so that it can be looked up without resolving
+/// it.
+pub(crate) fn plain_guest_path<'a>(ctx: &mut Ctx, path_addr: u32) -> Option<&'a [u8]> {
+    let path_ptr = emscripten_memory_pointer!(ctx.memory(0), path_addr) as *const c_char;
+    let path = unsafe { CStr::from_ptr(path_ptr) }.to_bytes();
+    if path.first() != Some(&b'/') {
+        return None;
+    }
+    let plain = path[1..]
+        .split(|&byte| byte == b'/')
+        .all(|name| !name.is_empty() && name != b"." && name != b"..");
+    if plain {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+fn read_path(ctx: &mut Ctx, path_addr: u32) -> PathBuf {
+    let path_ptr = emscripten_memory_pointer!(ctx.memory(0), path_addr) as *const c_char;
+    to_path(unsafe { CStr::from_ptr(path_ptr) })
+}
+
+fn resolve_in(base: &Path, path: &Path) -> PathBuf {
+    // An empty path doesn't name anything, and the host reports ENOENT for it.
+    if path.as_os_str().is_empty() {
+        return PathBuf::new();
+    }
+    normalize(&base.join(path))
+}
+
+/// The host path of the absolute guest path `path`, failing with `EACCES`
+/// when it's outside of the mapped directories.
+pub(crate) fn host_path(ctx: &mut Ctx, path: &Path) -> Result<CString, Errno> {
+    let path = match get_vfs(ctx) {
+        Some(vfs) if !path.as_os_str().is_empty() => vfs.to_host(path)?,
+        _ => path.to_owned(),
+    };
+    Ok(to_cstring(&path))
+}
+
+/// Read the C string at `path_addr` and resolve it into an absolute path
+/// of the guest.
+pub(crate) fn resolve_guest(ctx: &mut Ctx, path_addr: u32) -> PathBuf {
+    let path = read_path(ctx, path_addr);
+    resolve_in(&cwd(ctx), &path)
+}
+
+/// Read the C string at `path_addr` and resolve it into an absolute path
+/// of the host.
+pub(crate) fn resolve(ctx: &mut Ctx, path_addr: u32) -> Result<CString, Errno> {
+    let path = resolve_guest(ctx, path_addr);
+    host_path(ctx, &path)
+}
+
+/// Like `resolve`, but relative to the directory open as `dirfd`, the
+/// way the `*at` syscalls do.
+pub(crate) fn resolve_at(ctx: &mut Ctx, dirfd: c_int, path_addr: u32) -> Result<CString, Errno> {
+    let path = read_path(ctx, path_addr);
+    if dirfd == AT_FDCWD || path.has_root() {
+        let path = resolve_in(&cwd(ctx), &path);
+        return host_path(ctx, &path);
+    }
+    let base = match get_vfs(ctx) {
+        Some(vfs) => match vfs.dir_fds.get(&vfs.host_fd(dirfd)) {
+            // The directory is outside of the mapped ones.
+            Some(dir) => vfs.to_guest(dir).ok_or(Errno::EACCES)?,
+            None => return Err(not_a_directory(dirfd)),
+        },
+        None => return Err(not_a_directory(dirfd)),
+    };
+    let path = resolve_in(&base, &path);
+    host_path(ctx, &path)
+}
+
+/// Remove the `.` and `..` components of an absolute path, the way the
+/// emscripten filesystem does, without consulting the host, so `..`
+/// after a symbolic link goes back to the directory of the link.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component.as_os_str()),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_paths_through_mounts() {
+        let vfs = Vfs::new(
+            &[
+                ("/data".to_string(), PathBuf::from("/srv/app")),
+                ("data/cache".to_string(), PathBuf::from("/var/cache/app")),
+            ],
+            None,
+        );
+        assert_eq!(vfs.cwd, PathBuf::from("/"));
+        assert_eq!(
+            vfs.to_host(Path::new("/data/in.txt")),
+            Ok(PathBuf::from("/srv/app/in.txt"))
+        );
+        assert_eq!(
+            vfs.to_host(Path::new("/data/cache/x")),
+            Ok(PathBuf::from("/var/cache/app/x"))
+        );
+        assert_eq!(
+            vfs.to_host(Path::new("/data")),
+            Ok(PathBuf::from("/srv/app"))
+        );
+        assert_eq!(vfs.to_host(Path::new("/database")), Err(Errno::EACCES));
+        assert_eq!(vfs.to_host(Path::new("/etc/passwd")), Err(Errno::EACCES));
+        assert_eq!(
+            vfs.to_guest(Path::new("/var/cache/app/x")),
+            Some(PathBuf::from("/data/cache/x"))
+        );
+        assert_eq!(vfs.to_guest(Path::new("/etc")), None);
+    }
+
+    #[test]
+    fn denies_the_paths_that_are_not_granted() {
+        let granted = Capabilities {
+            fs: vec![PathBuf::from("/srv/app")],
+            ..Capabilities::default()
+        };
+        let vfs = Vfs::new(&[], Some(&granted));
+        assert_eq!(
+            vfs.to_host(Path::new("/srv/app/in.txt")),
+            Ok(PathBuf::from("/srv/app/in.txt"))
+        );
+        assert_eq!(
+            vfs.to_host(Path::new("/srv/application")),
+            Err(Errno::EACCES)
+        );
+        assert_eq!(vfs.to_host(Path::new("/etc/passwd")), Err(Errno::EACCES));
+
+        let vfs = Vfs::new(&[], Some(&Capabilities::default()));
+        assert_eq!(vfs.to_host(Path::new("/")), Err(Errno::EACCES));
+    }
+
+    #[test]
+    fn renaming_moves_the_recorded_modes() {
+        let mut vfs = Vfs::default();
+        for &(path, mode) in &[
+            ("/a", 0o700),
+            ("/a/x", 0o600),
+            ("/ab", 0o644),
+            ("/b/y", 0o400),
+        ] {
+            vfs.modes.insert(CString::new(path).unwrap(), mode);
+        }
+        vfs.rename_modes(b"/a", b"/b");
+        let mode = |path: &str| vfs.modes.get(&CString::new(path).unwrap()).cloned();
+        assert_eq!(mode("/a"), None);
+        assert_eq!(mode("/a/x"), None);
+        assert_eq!(mode("/b"), Some(0o700));
+        assert_eq!(mode("/b/x"), Some(0o600));
+        assert_eq!(mode("/b/y"), None);
+        assert_eq!(mode("/ab"), Some(0o644));
+    }
+
+    #[test]
+    fn duplicates_carry_the_open_directory() {
+        let mut vfs = Vfs::default();
+        vfs.dir_fds.insert(3, PathBuf::from("/tmp"));
+        vfs.dir_positions.insert(3, 2);
+        vfs.dir_fds.insert(5, PathBuf::from("/var"));
+        vfs.duplicate(3, 4);
+        assert_eq!(vfs.dir_fds.get(&4), Some(&PathBuf::from("/tmp")));
+        assert_eq!(vfs.dir_positions.get(&4), Some(&2));
+        vfs.duplicate(0, 5);
+        assert_eq!(vfs.dir_fds.get(&5), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tells_files_from_closed_descriptors() {
+        use std::os::unix::io::AsRawFd;
+        let file = fs::File::open("Cargo.toml").unwrap();
+        assert_eq!(not_a_directory(file.as_raw_fd()), Errno::ENOTDIR);
+        assert_eq!(not_a_directory(-1), Errno::EBADF);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn thaws_open_files_without_taking_the_hosts_descriptors() {
+        use std::os::unix::io::AsRawFd;
+        let path = env::current_dir().unwrap().join("Cargo.toml");
+        let file = fs::File::open(&path).unwrap();
+        let fd = file.as_raw_fd();
+        unsafe { libc::lseek(fd, 5, libc::SEEK_SET) };
+        let mut vfs = Vfs::default();
+        vfs.file_paths.insert(fd, path.clone());
+        let lines = vfs.freeze();
+        assert!(lines.contains(&format!("vfs.file {} r 5 {}", fd, path.display())));
+
+        // The host still has `fd` open, so the guest's is mapped to another.
+        let mut thawed = Vfs::default();
+        thawed.thaw(&lines);
+        let host = thawed.host_fd(fd);
+        assert_ne!(host, fd);
+        assert_eq!(thawed.guest_fd(host), fd);
+        assert_eq!(thawed.file_paths.get(&host), Some(&path));
+        assert_eq!(unsafe { libc::lseek(host, 0, libc::SEEK_CUR) }, 5);
+        unsafe { libc::close(host) };
+    }
+
+    #[test]
+    fn resolves_dot_dot_lexically() {
+        assert_eq!(
+            resolve_in(Path::new("/home/user"), Path::new("../x/./y/..")),
+            PathBuf::from("/home/x")
+        );
+        assert_eq!(
+            resolve_in(Path::new("/"), Path::new("../..")),
+            PathBuf::from("/")
+        );
+        assert_eq!(resolve_in(Path::new("/"), Path::new("")), PathBuf::new());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_paths_that_arent_utf8() {
+        let path = CString::new(&b"/tmp/caf\xe9"[..]).unwrap();
+        assert_eq!(to_cstring(&to_path(&path)), path);
+    }
+}
@@ -0,0 +1,406 @@
+//! The emscripten imports, called from small modules shaped like what
+//! emscripten outputs: they import the memory and the table, and export
+//! a bump allocator and `_main`.
+use wabt::wat2wasm;
+use wasmer_clif_backend::CraneliftCompiler;
+use wasmer_emscripten::{
+    generate_emscripten_env, run_emscripten_instance_with_config, AbortHook, EmscriptenConfig,
+    EmscriptenGlobals,
+};
+use wasmer_runtime_core::{error::CallResult, Instance};
+
+/// Instantiate a module with `items`, which declare its imports, its
+/// `_main` and anything else it needs.
+fn instantiate(items: &str) -> Instance {
+    let wat = format!(
+        r#"
+        (module
+          (import "env" "memory" (memory 256 256))
+          (import "env" "table" (table 16 16 anyfunc))
+          {}
+          (global $heap (mut i32) (i32.const 65536))
+          (func (export "_malloc") (param i32) (result i32)
+            (local i32)
+            get_global $heap
+            set_local 1
+            get_global $heap
+            get_local 0
+            i32.add
+            set_global $heap
+            get_local 1)
+          (func (export "_free") (param i32))
+          (func (export "_memset") (param i32 i32 i32) (result i32)
+            get_local 0)
+          (func (export "stackAlloc") (param i32) (result i32)
+            i32.const 4096))
+        "#,
+        items
+    );
+    let wasm = wat2wasm(wat).unwrap();
+    let module = wasmer_runtime_core::compile_with(&wasm, &CraneliftCompiler::new()).unwrap();
+    let mut globals = EmscriptenGlobals::new(&module);
+    let import_object = generate_emscripten_env(&mut globals);
+    module.instantiate(&import_object).unwrap()
+}
+
+/// Run the `_main` of `instance`, with aborts trapping out of it.
+fn run(instance: &mut Instance) -> CallResult<()> {
+    run_with_config(instance, EmscriptenConfig::default())
+}
+
+fn run_with_config(instance: &mut Instance, config: EmscriptenConfig) -> CallResult<()> {
+    let config = EmscriptenConfig {
+        abort_hook: Some(AbortHook::new(|info| {
+            panic!(
+                "aborted: {}",
+                info.message.as_ref().map_or("", String::as_str)
+            )
+        })),
+        ..config
+    };
+    let module = instance.module();
+    run_emscripten_instance_with_config(&module, instance, "test", vec![], config)
+}
+
+fn read_u32(instance: &Instance, addr: u32) -> u32 {
+    instance.context().memory(0).view::<u32>()[(addr / 4) as usize].get()
+}
+
+#[test]
+fn openmp_regions_run_on_a_team_of_one() {
+    let mut instance = instantiate(
+        r#"
+        (import "env" "___kmpc_fork_call" (func $fork (param i32 i32 i32 i32)))
+        (import "env" "_omp_in_parallel" (func $in_parallel (result i32)))
+        (elem (i32.const 1) $region)
+        ;; Stores whether it's in parallel and its thread ID at `out`.
+        (func $region (param $gtid i32) (param $btid i32) (param $out i32)
+          (i32.store (get_local $out) (call $in_parallel))
+          (i32.store offset=4 (get_local $out) (i32.load (get_local $gtid))))
+        (func (export "_main") (result i32)
+          (i32.store (i32.const 8) (i32.const 2048))
+          (i32.store offset=4 (i32.const 2048) (i32.const 7))
+          (call $fork (i32.const 0) (i32.const 1) (i32.const 1) (i32.const 8))
+          (i32.store (i32.const 2056) (call $in_parallel))
+          i32.const 0)
+        "#,
+    );
+    run(&mut instance).unwrap();
+    assert_eq!(read_u32(&instance, 2048), 1);
+    assert_eq!(read_u32(&instance, 2052), 0);
+    assert_eq!(read_u32(&instance, 2056), 0);
+}
+
+#[test]
+fn openmp_regions_out_of_the_table_abort() {
+    let mut instance = instantiate(
+        r#"
+        (import "env" "___kmpc_fork_call" (func $fork (param i32 i32 i32 i32)))
+        (func (export "_main") (result i32)
+          (call $fork (i32.const 0) (i32.const 0) (i32.const 99) (i32.const 8))
+          i32.const 0)
+        "#,
+    );
+    let err = run(&mut instance).unwrap_err();
+    assert!(
+        format!("{:?}", err).contains("isn't in the table"),
+        "{:?}",
+        err
+    );
+}
+
+/// Declares `$call`, which makes the socket call `$which` with four
+/// arguments.
+const SOCKETCALL: &str = r#"
+    (import "env" "___syscall102" (func $socketcall (param i32 i32) (result i32)))
+    (func $call (param $which i32) (param i32 i32 i32 i32) (result i32)
+      (i32.store (i32.const 1024) (get_local $which))
+      (i32.store (i32.const 1028) (i32.const 1032))
+      (i32.store (i32.const 1032) (get_local 1))
+      (i32.store (i32.const 1036) (get_local 2))
+      (i32.store (i32.const 1040) (get_local 3))
+      (i32.store (i32.const 1044) (get_local 4))
+      (call $socketcall (i32.const 102) (i32.const 1024)))
+"#;
+
+#[cfg(unix)]
+#[test]
+fn sockets_are_denied_without_networking() {
+    let mut instance = instantiate(&format!(
+        r#"
+        {}
+        (func (export "_main") (result i32)
+          ;; socket(AF_INET, SOCK_STREAM, 0)
+          (i32.store (i32.const 2048)
+            (call $call (i32.const 1) (i32.const 2) (i32.const 1) (i32.const 0) (i32.const 0)))
+          i32.const 0)
+        "#,
+        SOCKETCALL
+    ));
+    run(&mut instance).unwrap();
+    // EACCES
+    assert_eq!(read_u32(&instance, 2048) as i32, -2);
+}
+
+#[cfg(unix)]
+#[test]
+fn sockets_carry_data_and_reject_bad_arguments() {
+    let mut instance = instantiate(&format!(
+        r#"
+        {}
+        (func (export "_main") (result i32)
+          ;; socketpair(AF_UNIX, SOCK_STREAM, 0, 2048)
+          (i32.store (i32.const 3000)
+            (call $call (i32.const 8) (i32.const 1) (i32.const 1) (i32.const 0) (i32.const 2048)))
+          ;; send(fds[0], "hi", 2, 0)
+          (i32.store (i32.const 2056) (i32.const 0x6968))
+          (i32.store (i32.const 3004)
+            (call $call (i32.const 9) (i32.load (i32.const 2048)) (i32.const 2056) (i32.const 2) (i32.const 0)))
+          ;; recv(fds[1], 2060, 16, 0)
+          (i32.store (i32.const 3008)
+            (call $call (i32.const 10) (i32.load (i32.const 2052)) (i32.const 2060) (i32.const 16) (i32.const 0)))
+          ;; send(-1, "hi", 2, 0)
+          (i32.store (i32.const 3012)
+            (call $call (i32.const 9) (i32.const -1) (i32.const 2056) (i32.const 2) (i32.const 0)))
+          ;; Arguments past the end of the memory.
+          (i32.store (i32.const 3016) (call $socketcall (i32.const 102) (i32.const -4)))
+          (i32.store (i32.const 1024) (i32.const 9))
+          (i32.store (i32.const 1028) (i32.const -8))
+          (i32.store (i32.const 3020) (call $socketcall (i32.const 102) (i32.const 1024)))
+          ;; An unknown call.
+          (i32.store (i32.const 3024)
+            (call $call (i32.const 99) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0)))
+          i32.const 0)
+        "#,
+        SOCKETCALL
+    ));
+    let config = EmscriptenConfig {
+        networking: true,
+        ..EmscriptenConfig::default()
+    };
+    run_with_config(&mut instance, config).unwrap();
+    assert_eq!(read_u32(&instance, 3000), 0);
+    assert_eq!(read_u32(&instance, 3004), 2);
+    assert_eq!(read_u32(&instance, 3008), 2);
+    assert_eq!(read_u32(&instance, 2060), 0x6968);
+    // EBADF, EINVAL, EINVAL and ENOSYS
+    assert_eq!(read_u32(&instance, 3012) as i32, -8);
+    assert_eq!(read_u32(&instance, 3016) as i32, -28);
+    assert_eq!(read_u32(&instance, 3020) as i32, -28);
+    assert_eq!(read_u32(&instance, 3024) as i32, -52);
+}
+
+/// Declares the `setThrew` export, which keeps the first of the calls
+/// since `$threw` was reset.
+const SET_THREW: &str = r#"
+    (global $threw (mut i32) (i32.const 0))
+    (global $threw_value (mut i32) (i32.const 0))
+    (func (export "setThrew") (param i32 i32)
+      (if (i32.eqz (get_global $threw))
+        (then
+          (set_global $threw (get_local 0))
+          (set_global $threw_value (get_local 1)))))
+"#;
+
+const DYN_CALL_VI: &str = r#"
+    (type $vi (func (param i32)))
+    (func (export "dynCall_vi") (param i32 i32)
+      (call_indirect (type $vi) (get_local 1) (get_local 0)))
+"#;
+
+#[test]
+fn longjmps_return_to_the_invoke() {
+    let mut instance = instantiate(&format!(
+        r#"
+        (import "env" "_emscripten_longjmp" (func $longjmp (param i32 i32)))
+        (import "env" "invoke_vi" (func $invoke_vi (param i32 i32)))
+        {}
+        {}
+        (elem (i32.const 1) $jumper)
+        (func $jumper (param i32)
+          (call $longjmp (get_local 0) (i32.const 0))
+          (i32.store (i32.const 2052) (i32.const 1)))
+        (func (export "_main") (result i32)
+          (call $invoke_vi (i32.const 1) (i32.const 2000))
+          (i32.store (i32.const 2048) (get_global $threw))
+          (i32.store (i32.const 2056) (get_global $threw_value))
+          i32.const 0)
+        "#,
+        SET_THREW, DYN_CALL_VI
+    ));
+    run(&mut instance).unwrap();
+    // The jump buffer, with a value of 1 for the 0 passed.
+    assert_eq!(read_u32(&instance, 2048), 2000);
+    assert_eq!(read_u32(&instance, 2056), 1);
+    assert_eq!(read_u32(&instance, 2052), 0);
+}
+
+#[test]
+fn longjmps_without_an_invoke_trap() {
+    let mut instance = instantiate(&format!(
+        r#"
+        (import "env" "_emscripten_longjmp" (func $longjmp (param i32 i32)))
+        {}
+        (func (export "_main") (result i32)
+          (call $longjmp (i32.const 2000) (i32.const 1))
+          i32.const 0)
+        "#,
+        SET_THREW
+    ));
+    let err = run(&mut instance).unwrap_err();
+    assert!(
+        format!("{:?}", err).contains("no setjmp to return to"),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn setjmp_tables_grow_and_invokes_return_floats() {
+    let mut instance = instantiate(
+        r#"
+        (import "env" "_saveSetjmp" (func $save (param i32 i32 i32 i32) (result i32)))
+        (import "env" "_testSetjmp" (func $test (param i32 i32 i32) (result i32)))
+        (import "env" "getTempRet0" (func $temp_ret_0 (result i32)))
+        (import "env" "invoke_di" (func $invoke_di (param i32 i32) (result f64)))
+        (type $di (func (param i32) (result f64)))
+        (elem (i32.const 1) $half)
+        (func $half (param i32) (result f64)
+          (f64.div (f64.convert_s/i32 (get_local 0)) (f64.const 2)))
+        (func (export "dynCall_di") (param i32 i32) (result f64)
+          (call_indirect (type $di) (get_local 1) (get_local 0)))
+        (func (export "_main") (result i32)
+          (local $table i32)
+          ;; A full table of no entries grows.
+          (set_local $table (call $save (i32.const 2100) (i32.const 3) (i32.const 0) (i32.const 0)))
+          (i32.store (i32.const 2048) (call $temp_ret_0))
+          (i32.store (i32.const 2052)
+            (call $test (i32.load (i32.const 2100)) (get_local $table) (i32.const 2)))
+          (i32.store (i32.const 2056)
+            (i32.trunc_s/f64 (f64.mul (call $invoke_di (i32.const 1) (i32.const 7)) (f64.const 2))))
+          i32.const 0)
+        "#,
+    );
+    run(&mut instance).unwrap();
+    assert_eq!(read_u32(&instance, 2048), 2);
+    assert_eq!(read_u32(&instance, 2052), 3);
+    assert_eq!(read_u32(&instance, 2056), 7);
+}
+
+/// Declares the exception imports, `invoke_v` and `dynCall_v`, and in the
+/// table `$thrower` at 1, `$destructor` at 2 and `$rethrower` at 3.
+const EXCEPTIONS: &str = r#"
+    (import "env" "___cxa_allocate_exception" (func $allocate (param i32) (result i32)))
+    (import "env" "___cxa_throw" (func $throw (param i32 i32 i32)))
+    (import "env" "___cxa_rethrow" (func $rethrow))
+    (import "env" "___resumeException" (func $resume (param i32)))
+    (import "env" "___cxa_find_matching_catch_2" (func $find_any (result i32)))
+    (import "env" "___cxa_find_matching_catch_3" (func $find (param i32) (result i32)))
+    (import "env" "___cxa_begin_catch" (func $begin (param i32) (result i32)))
+    (import "env" "___cxa_end_catch" (func $end))
+    (import "env" "___cxa_uncaught_exceptions" (func $uncaught (result i32)))
+    (import "env" "getTempRet0" (func $temp_ret_0 (result i32)))
+    (import "env" "invoke_v" (func $invoke_v (param i32)))
+    (type $v (func))
+    (func (export "dynCall_v") (param i32)
+      (call_indirect (type $v) (get_local 0)))
+    (elem (i32.const 1) $thrower $destructor $rethrower $rethrow_caught)
+    ;; Throws an `int` of type 8 holding 42.
+    (func $thrower
+      (local $e i32)
+      (set_local $e (call $allocate (i32.const 4)))
+      (i32.store (get_local $e) (i32.const 42))
+      (call $throw (get_local $e) (i32.const 8) (i32.const 2)))
+    ;; Counts the exceptions destroyed at 2060.
+    (func $destructor (param i32)
+      (i32.store (i32.const 2060) (i32.add (i32.load (i32.const 2060)) (i32.const 1))))
+    ;; Catches what `$thrower` throws and rethrows it, ending its catch on
+    ;; the way out.
+    (func $rethrower
+      (local $e i32)
+      (call $invoke_v (i32.const 1))
+      (set_global $threw (i32.const 0))
+      (drop (call $begin (call $find (i32.const 8))))
+      (call $invoke_v (i32.const 4))
+      (set_global $threw (i32.const 0))
+      (set_local $e (call $find_any))
+      (call $end)
+      (call $resume (get_local $e)))
+    (func $rethrow_caught
+      (call $rethrow))
+"#;
+
+#[test]
+fn exceptions_are_caught_and_destroyed() {
+    let mut instance = instantiate(&format!(
+        r#"
+        {}
+        {}
+        {}
+        (func (export "_main") (result i32)
+          (local $e i32)
+          (call $invoke_v (i32.const 1))
+          (i32.store (i32.const 2048) (get_global $threw))
+          (set_global $threw (i32.const 0))
+          (i32.store (i32.const 2064) (call $uncaught))
+          (set_local $e (call $find (i32.const 8)))
+          (i32.store (i32.const 2052) (call $temp_ret_0))
+          (i32.store (i32.const 2056) (i32.load (call $begin (get_local $e))))
+          (i32.store (i32.const 2068) (call $uncaught))
+          (call $end)
+          i32.const 0)
+        "#,
+        SET_THREW, DYN_CALL_VI, EXCEPTIONS
+    ));
+    run(&mut instance).unwrap();
+    assert_eq!(read_u32(&instance, 2048), 1);
+    assert_eq!(read_u32(&instance, 2052), 8);
+    assert_eq!(read_u32(&instance, 2056), 42);
+    assert_eq!(read_u32(&instance, 2060), 1);
+    assert_eq!(read_u32(&instance, 2064), 1);
+    assert_eq!(read_u32(&instance, 2068), 0);
+}
+
+#[test]
+fn rethrown_exceptions_outlive_the_first_catch() {
+    let mut instance = instantiate(&format!(
+        r#"
+        {}
+        {}
+        {}
+        (func (export "_main") (result i32)
+          (call $invoke_v (i32.const 3))
+          (i32.store (i32.const 2048) (get_global $threw))
+          (set_global $threw (i32.const 0))
+          (i32.store (i32.const 2056) (i32.load (call $begin (call $find (i32.const 8)))))
+          (i32.store (i32.const 2052) (i32.load (i32.const 2060)))
+          (call $end)
+          i32.const 0)
+        "#,
+        SET_THREW, DYN_CALL_VI, EXCEPTIONS
+    ));
+    run(&mut instance).unwrap();
+    assert_eq!(read_u32(&instance, 2048), 1);
+    assert_eq!(read_u32(&instance, 2056), 42);
+    // Not destroyed until the catch that doesn't rethrow ends.
+    assert_eq!(read_u32(&instance, 2052), 0);
+    assert_eq!(read_u32(&instance, 2060), 1);
+}
+
+#[test]
+fn exceptions_without_dyn_call_vi_abort_when_destroyed() {
+    let mut instance = instantiate(&format!(
+        r#"
+        {}
+        {}
+        (func (export "_main") (result i32)
+          (call $invoke_v (i32.const 1))
+          (drop (call $begin (call $find (i32.const 8))))
+          (call $end)
+          i32.const 0)
+        "#,
+        SET_THREW, EXCEPTIONS
+    ));
+    let err = run(&mut instance).unwrap_err();
+    assert!(format!("{:?}", err).contains("dynCall_vi"), "{:?}", err);
+}
@@ -0,0 +1,193 @@
+use crate::{
+    error::{CallError, CallResult, RuntimeError},
+    instance::Instance,
+    memory::Memory,
+    structures::TypedIndex,
+    types::MemoryIndex,
+};
+
+/// The conventions a module follows to pass strings and buffers
+/// across the host boundary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Convention {
+    /// C code compiled by emscripten.
+    ///
+    /// Memory is managed with the exported `_malloc` and `_free`.
+    /// Strings are passed as `char *f(const char *)` and buffers as
+    /// `uint8_t *f(const uint8_t *, size_t, size_t *out_len)`.
+    Emscripten,
+    /// Rust code compiled to `wasm32-unknown-unknown`.
+    ///
+    /// Memory is managed with exported `alloc(len) -> ptr` and
+    /// `dealloc(ptr, len)` functions. Strings and buffers are passed
+    /// as `f(ret_ptr, ptr, len)`, where the function writes the pointer
+    /// and the length of its result at `ret_ptr`.
+    Rust,
+}
+
+/// Calls exports that take and return strings or buffers, so the
+/// host doesn't have to marshal them through linear memory by hand.
+///
+/// Arguments are copied into memory allocated by the module and freed
+/// once the call returns. Results are copied out and then freed, so the
+/// module has to hand over ownership of the memory it returns.
+///
+/// # Usage:
+/// ```
+/// # use wasmer_runtime_core::{Instance, error::CallResult};
+/// # use wasmer_runtime_core::adapter::{Adapter, Convention};
+/// fn greet(instance: &Instance) -> CallResult<String> {
+///     let adapter = Adapter::new(instance, Convention::Rust);
+///     adapter.call_str("greet", "world")
+/// }
+/// ```
+pub struct Adapter<'a> {
+    instance: &'a Instance,
+    convention: Convention,
+}
+
+impl<'a> Adapter<'a> {
+    pub fn new(instance: &'a Instance, convention: Convention) -> Self {
+        Self {
+            instance,
+            convention,
+        }
+    }
+
+    /// Call the export `name` with a string argument and a string result.
+    pub fn call_str(&self, name: &str, arg: &str) -> CallResult<String> {
+        let bytes = match self.convention {
+            Convention::Emscripten => {
+                let arg_ptr = self.alloc(arg.len() as u32 + 1)?;
+                self.write(arg_ptr, arg.as_bytes())?;
+                self.write(arg_ptr + arg.len() as u32, &[0])?;
+
+                let ret_ptr = self.instance.func::<u32, u32>(name)?.call(arg_ptr)?;
+                self.free(arg_ptr, arg.len() as u32 + 1)?;
+
+                let bytes = self.read_c_str(ret_ptr)?;
+                self.free(ret_ptr, bytes.len() as u32 + 1)?;
+                bytes
+            }
+            Convention::Rust => self.call_bytes(name, arg.as_bytes())?,
+        };
+
+        String::from_utf8(bytes).map_err(|_| {
+            RuntimeError::User {
+                msg: format!("{} returned a string that isn't valid UTF-8", name),
+            }
+            .into()
+        })
+    }
+
+    /// Call the export `name` with a buffer argument and a buffer result.
+    pub fn call_bytes(&self, name: &str, arg: &[u8]) -> CallResult<Vec<u8>> {
+        let arg_len = arg.len() as u32;
+        let arg_ptr = self.alloc(arg_len)?;
+        self.write(arg_ptr, arg)?;
+
+        let (ret_ptr, ret_len) = match self.convention {
+            Convention::Emscripten => {
+                let out_len_ptr = self.alloc(4)?;
+                let ret_ptr = self.instance.func::<(u32, u32, u32), u32>(name)?.call(
+                    arg_ptr,
+                    arg_len,
+                    out_len_ptr,
+                )?;
+                let ret_len = self.read_u32(out_len_ptr)?;
+                self.free(out_len_ptr, 4)?;
+                (ret_ptr, ret_len)
+            }
+            Convention::Rust => {
+                let out_ptr = self.alloc(8)?;
+                self.instance
+                    .func::<(u32, u32, u32), ()>(name)?
+                    .call(out_ptr, arg_ptr, arg_len)?;
+                let ret_ptr = self.read_u32(out_ptr)?;
+                let ret_len = self.read_u32(out_ptr + 4)?;
+                self.free(out_ptr, 8)?;
+                (ret_ptr, ret_len)
+            }
+        };
+        self.free(arg_ptr, arg_len)?;
+
+        let bytes = self.read(ret_ptr, ret_len)?;
+        self.free(ret_ptr, ret_len)?;
+        Ok(bytes)
+    }
+
+    fn alloc(&self, len: u32) -> CallResult<u32> {
+        let ptr = match self.convention {
+            Convention::Emscripten => self.instance.func::<u32, u32>("_malloc")?.call(len)?,
+            Convention::Rust => self.instance.func::<u32, u32>("alloc")?.call(len)?,
+        };
+        Ok(ptr)
+    }
+
+    fn free(&self, ptr: u32, len: u32) -> CallResult<()> {
+        match self.convention {
+            Convention::Emscripten => self.instance.func::<u32, ()>("_free")?.call(ptr)?,
+            Convention::Rust => self
+                .instance
+                .func::<(u32, u32), ()>("dealloc")?
+                .call(ptr, len)?,
+        }
+        Ok(())
+    }
+
+    fn memory(&self) -> CallResult<&Memory> {
+        self.instance
+            .context()
+            .get_memory(0)
+            .ok_or_else(|| out_of_bounds(None))
+    }
+
+    fn read(&self, ptr: u32, len: u32) -> CallResult<Vec<u8>> {
+        let view = self.memory()?.view::<u8>();
+        let end = ptr as usize + len as usize;
+        let cells = view
+            .get(ptr as usize..end)
+            .ok_or_else(|| out_of_bounds(Some(ptr)))?;
+        Ok(cells.iter().map(|cell| cell.get()).collect())
+    }
+
+    fn read_u32(&self, ptr: u32) -> CallResult<u32> {
+        let bytes = self.read(ptr, 4)?;
+        Ok(u32::from(bytes[0])
+            | u32::from(bytes[1]) << 8
+            | u32::from(bytes[2]) << 16
+            | u32::from(bytes[3]) << 24)
+    }
+
+    fn read_c_str(&self, ptr: u32) -> CallResult<Vec<u8>> {
+        let view = self.memory()?.view::<u8>();
+        let cells = view
+            .get(ptr as usize..)
+            .ok_or_else(|| out_of_bounds(Some(ptr)))?;
+        let len = cells
+            .iter()
+            .position(|cell| cell.get() == 0)
+            .ok_or_else(|| out_of_bounds(Some(ptr)))?;
+        Ok(cells[..len].iter().map(|cell| cell.get()).collect())
+    }
+
+    fn write(&self, ptr: u32, bytes: &[u8]) -> CallResult<()> {
+        let view = self.memory()?.view::<u8>();
+        let end = ptr as usize + bytes.len();
+        let cells = view
+            .get(ptr as usize..end)
+            .ok_or_else(|| out_of_bounds(Some(ptr)))?;
+        for (cell, &byte) in cells.iter().zip(bytes) {
+            cell.set(byte);
+        }
+        Ok(())
+    }
+}
+
+fn out_of_bounds(addr: Option<u32>) -> CallError {
+    RuntimeError::OutOfBoundsAccess {
+        memory: MemoryIndex::new(0),
+        addr,
+    }
+    .into()
+}
@@ -0,0 +1,157 @@
+//! What calls what in a module, to tell which imports a module really
+//! uses and which of its functions can never run.
+use crate::{
+    disasm::function_names,
+    error::{CompileError, CompileResult},
+};
+use hashbrown::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
+use wasmparser::{
+    ExternalKind, ImportSectionEntryType, Operator, Parser, ParserState, WasmDecoder,
+};
+
+/// The call graph of a module. Function indices count the imported
+/// functions.
+#[derive(Debug, Clone, Default)]
+pub struct Analysis {
+    /// The imported functions, as `namespace.name`, in order.
+    pub imports: Vec<String>,
+    /// The number of functions, imported ones included.
+    pub functions: u32,
+    /// The names the name section gives the functions.
+    pub names: HashMap<u32, String>,
+    /// The number of call sites of each `(caller, callee)` pair.
+    pub calls: BTreeMap<(u32, u32), u32>,
+    /// The functions that make indirect calls.
+    pub indirect_callers: BTreeSet<u32>,
+    /// The functions stored in tables, which indirect calls can reach.
+    pub table_functions: BTreeSet<u32>,
+    /// The exported functions and the start function.
+    pub roots: BTreeSet<u32>,
+    /// Whether a table is imported or exported, so the host can call
+    /// the functions in it.
+    pub table_is_shared: bool,
+}
+
+impl Analysis {
+    pub fn of(wasm: &[u8]) -> CompileResult<Self> {
+        let mut analysis = Analysis {
+            names: function_names(wasm),
+            ..Default::default()
+        };
+        let mut parser = Parser::new(wasm);
+        let mut bodies = 0;
+        let mut caller = 0;
+        loop {
+            match *parser.read() {
+                ParserState::EndWasm => break,
+                ParserState::Error(ref err) => {
+                    return Err(CompileError::ValidationError {
+                        msg: format!("{} at offset {:#x}", err.message, err.offset),
+                        location: None,
+                    })
+                }
+                ParserState::ImportSectionEntry {
+                    module,
+                    field,
+                    ref ty,
+                } => match *ty {
+                    ImportSectionEntryType::Function(_) => analysis.imports.push(format!(
+                        "{}.{}",
+                        String::from_utf8_lossy(module.as_ref()),
+                        String::from_utf8_lossy(field.as_ref())
+                    )),
+                    ImportSectionEntryType::Table(_) => analysis.table_is_shared = true,
+                    _ => {}
+                },
+                ParserState::FunctionSectionEntry(_) => analysis.functions += 1,
+                ParserState::ExportSectionEntry {
+                    ref kind, index, ..
+                } => match *kind {
+                    ExternalKind::Function => {
+                        analysis.roots.insert(index);
+                    }
+                    ExternalKind::Table => analysis.table_is_shared = true,
+                    _ => {}
+                },
+                ParserState::StartSectionEntry(index) => {
+                    analysis.roots.insert(index);
+                }
+                ParserState::ElementSectionEntryBody(ref elements) => {
+                    analysis.table_functions.extend(elements.iter().cloned());
+                }
+                ParserState::BeginFunctionBody { .. } => {
+                    caller = analysis.imports.len() as u32 + bodies;
+                    bodies += 1;
+                }
+                ParserState::CodeOperator(Operator::Call { function_index }) => {
+                    *analysis.calls.entry((caller, function_index)).or_insert(0) += 1;
+                }
+                ParserState::CodeOperator(Operator::CallIndirect { .. }) => {
+                    analysis.indirect_callers.insert(caller);
+                }
+                _ => {}
+            }
+        }
+        analysis.functions += analysis.imports.len() as u32;
+        Ok(analysis)
+    }
+
+    /// The functions that can run, starting from the exports and the
+    /// start function.
+    pub fn reachable(&self) -> BTreeSet<u32> {
+        let mut reachable = BTreeSet::new();
+        let mut pending: Vec<u32> = self.roots.iter().cloned().collect();
+        let mut tables_reached = false;
+        if self.table_is_shared {
+            pending.extend(&self.table_functions);
+            tables_reached = true;
+        }
+        while let Some(function) = pending.pop() {
+            if !reachable.insert(function) {
+                continue;
+            }
+            pending.extend(self.callees(function));
+            if !tables_reached && self.indirect_callers.contains(&function) {
+                pending.extend(&self.table_functions);
+                tables_reached = true;
+            }
+        }
+        reachable
+    }
+
+    /// The functions the module defines that can never run.
+    pub fn unreachable(&self) -> Vec<u32> {
+        let reachable = self.reachable();
+        (self.imports.len() as u32..self.functions)
+            .filter(|function| !reachable.contains(function))
+            .collect()
+    }
+
+    /// The functions `function` calls directly.
+    pub fn callees(&self, function: u32) -> impl Iterator<Item = u32> + '_ {
+        self.calls
+            .range((function, 0)..=(function, u32::max_value()))
+            .map(|(&(_, callee), _)| callee)
+    }
+
+    /// The number of call sites of the import of `index`, and of the
+    /// functions they are in.
+    pub fn import_usage(&self, index: u32) -> (u32, u32) {
+        self.calls
+            .iter()
+            .filter(|(&(_, callee), _)| callee == index)
+            .fold((0, 0), |(sites, callers), (_, &count)| {
+                (sites + count, callers + 1)
+            })
+    }
+
+    /// The name of the function of `index`, from the name section or its
+    /// import.
+    pub fn function_name(&self, index: u32) -> Option<&str> {
+        self.names
+            .get(&index)
+            .or_else(|| self.imports.get(index as usize))
+            .map(String::as_str)
+    }
+}
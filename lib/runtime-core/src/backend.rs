@@ -1,7 +1,9 @@
 use crate::{
     backing::ImportBacking,
-    error::CompileResult,
     error::RuntimeResult,
+    error::{CompileError, CompileResult},
+    features::Features,
+    memory::MemoryConfig,
     module::ModuleInner,
     types::{FuncIndex, LocalFuncIndex, Value},
     vm,
@@ -12,7 +14,7 @@ use crate::{
     module::ModuleInfo,
     sys::Memory,
 };
-use std::ptr::NonNull;
+use std::{any::Any, fmt, ptr::NonNull, sync::Arc};
 
 pub mod sys {
     pub use crate::sys::*;
@@ -37,15 +39,141 @@ impl Token {
     }
 }
 
+/// Inspects the machine code of a module once it's been emitted and
+/// mapped read-only, before anything runs it, e.g. to sign or attest it
+/// on a locked-down platform. An error fails the compilation.
+#[derive(Clone)]
+pub struct CodeHook(Arc<dyn Fn(&[u8]) -> Result<(), String> + Send + Sync>);
+
+impl CodeHook {
+    pub fn new<F>(hook: F) -> Self
+    where
+        F: Fn(&[u8]) -> Result<(), String> + Send + Sync + 'static,
+    {
+        CodeHook(Arc::new(hook))
+    }
+
+    /// Run the hook over `code`.
+    pub fn check(&self, code: &[u8]) -> Result<(), String> {
+        (self.0)(code)
+    }
+}
+
+impl fmt::Debug for CodeHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("CodeHook")
+    }
+}
+
+/// Told about each function with a stack frame larger than
+/// `StackConfig::large_frame_size`, with the size of its frame in bytes,
+/// e.g. to warn that it may overflow the stack.
+#[derive(Clone)]
+pub struct FrameHook(Arc<dyn Fn(LocalFuncIndex, u32) + Send + Sync>);
+
+impl FrameHook {
+    pub fn new<F>(hook: F) -> Self
+    where
+        F: Fn(LocalFuncIndex, u32) + Send + Sync + 'static,
+    {
+        FrameHook(Arc::new(hook))
+    }
+
+    /// Run the hook for the function `index`.
+    pub fn report(&self, index: LocalFuncIndex, frame_size: u32) {
+        (self.0)(index, frame_size)
+    }
+}
+
+impl fmt::Debug for FrameHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("FrameHook")
+    }
+}
+
+/// How the backend guards the stack of the functions it generates.
+#[derive(Debug, Clone)]
+pub struct StackConfig {
+    /// Touch each page of a frame larger than a page, in order, before the
+    /// function uses it. A frame that overflows then faults on the guard
+    /// page under the stack, rather than reaching past it into whatever
+    /// memory is mapped below.
+    pub probes: bool,
+    /// The size in bytes above which a frame is reported to `frame_hook`.
+    pub large_frame_size: u32,
+    pub frame_hook: Option<FrameHook>,
+}
+
+impl Default for StackConfig {
+    fn default() -> Self {
+        StackConfig {
+            probes: true,
+            large_frame_size: 64 * 1024,
+            frame_hook: None,
+        }
+    }
+}
+
+/// Options that affect the code a backend generates.
+#[derive(Debug, Clone, Default)]
+pub struct CompilerConfig {
+    /// The layout that memories used by the module will have.
+    pub memory: MemoryConfig,
+    /// Runs over every region of code the backend emits.
+    pub code_hook: Option<CodeHook>,
+    /// The proposals the module may use.
+    pub features: Features,
+    /// How the stack of the generated functions is guarded.
+    pub stack: StackConfig,
+}
+
 pub trait Compiler {
     /// Compiles a `Module` from WebAssembly binary format.
     /// The `CompileToken` parameter ensures that this can only
     /// be called from inside the runtime.
     fn compile(&self, wasm: &[u8], _: Token) -> CompileResult<ModuleInner>;
 
+    /// Compiles a `Module` the way `config` asks.
+    ///
+    /// Backends that don't override this compile with `compile`, which
+    /// only does what the default config asks. A config they can't honor,
+    /// because it places memories differently or has a code hook, fails
+    /// the compilation rather than be ignored.
+    fn compile_with_config(
+        &self,
+        wasm: &[u8],
+        config: CompilerConfig,
+        token: Token,
+    ) -> CompileResult<ModuleInner> {
+        if config.memory != MemoryConfig::default() || config.code_hook.is_some() {
+            return Err(CompileError::InternalError {
+                msg: "this backend can't compile with a memory config or a code hook".to_string(),
+            });
+        }
+        self.compile(wasm, token)
+    }
+
     #[cfg(feature = "cache")]
     unsafe fn from_cache(&self, cache: Cache, _: Token) -> Result<ModuleInner, CacheError>;
 
+    /// Loads a module from `cache`, running the code hook of `config` over
+    /// its code. The default fails if there's a code hook, rather than
+    /// skip it.
+    #[cfg(feature = "cache")]
+    unsafe fn from_cache_with_config(
+        &self,
+        cache: Cache,
+        config: &CompilerConfig,
+        token: Token,
+    ) -> Result<ModuleInner, CacheError> {
+        if config.code_hook.is_some() {
+            return Err(CacheError::Unknown(
+                "this backend can't load a cache with a code hook".to_string(),
+            ));
+        }
+        self.from_cache(cache, token)
+    }
+
     #[cfg(feature = "cache")]
     fn compile_to_backend_cache_data(
         &self,
@@ -84,6 +212,15 @@ pub trait ProtectedCaller: Send + Sync {
     ) -> RuntimeResult<Vec<Value>>;
 
     fn get_early_trapper(&self) -> Box<dyn UserTrapper>;
+
+    /// Saves the state this thread keeps to catch the traps of the calls in
+    /// progress, before it switches to another native stack, for
+    /// `restore_trap_state` to put back once it's switched back.
+    fn save_trap_state(&self) -> Box<dyn Any> {
+        Box::new(())
+    }
+
+    fn restore_trap_state(&self, _state: Box<dyn Any>) {}
 }
 
 pub trait UserTrapper {
@@ -99,3 +236,64 @@ pub trait FuncResolver: Send + Sync {
         local_func_index: LocalFuncIndex,
     ) -> Option<NonNull<vm::Func>>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A backend from before `compile_with_config`.
+    struct OldCompiler;
+
+    impl Compiler for OldCompiler {
+        fn compile(&self, _wasm: &[u8], _: Token) -> CompileResult<ModuleInner> {
+            Err(CompileError::InternalError {
+                msg: "compiled".to_string(),
+            })
+        }
+
+        #[cfg(feature = "cache")]
+        unsafe fn from_cache(&self, _cache: Cache, _: Token) -> Result<ModuleInner, CacheError> {
+            unimplemented!()
+        }
+
+        #[cfg(feature = "cache")]
+        fn compile_to_backend_cache_data(
+            &self,
+            _wasm: &[u8],
+            _: Token,
+        ) -> CompileResult<(Box<ModuleInfo>, Vec<u8>, Memory)> {
+            unimplemented!()
+        }
+    }
+
+    fn message(result: CompileResult<ModuleInner>) -> String {
+        match result {
+            Err(CompileError::InternalError { msg }) => msg,
+            _ => panic!("expected an internal error"),
+        }
+    }
+
+    #[test]
+    fn old_backends_compile_with_the_default_config() {
+        let result =
+            OldCompiler.compile_with_config(&[], CompilerConfig::default(), Token::generate());
+        assert_eq!(message(result), "compiled");
+    }
+
+    #[test]
+    fn old_backends_reject_what_they_cant_honor() {
+        let config = CompilerConfig {
+            memory: MemoryConfig::bounds_checked(),
+            ..CompilerConfig::default()
+        };
+        let result = OldCompiler.compile_with_config(&[], config, Token::generate());
+        assert_ne!(message(result), "compiled");
+
+        let config = CompilerConfig {
+            code_hook: Some(CodeHook::new(|_| Ok(()))),
+            ..CompilerConfig::default()
+        };
+        let result = OldCompiler.compile_with_config(&[], config, Token::generate());
+        assert_ne!(message(result), "compiled");
+    }
+}
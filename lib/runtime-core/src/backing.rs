@@ -61,7 +61,10 @@ impl LocalBacking {
     fn generate_memories(module: &ModuleInner) -> BoxedMap<LocalMemoryIndex, Memory> {
         let mut memories = Map::with_capacity(module.info.memories.len());
         for (_, &desc) in &module.info.memories {
-            memories.push(Memory::new(desc).expect("unable to create memory"));
+            memories.push(
+                Memory::with_config(desc, module.info.memory_config)
+                    .expect("unable to create memory"),
+            );
         }
 
         memories.into_boxed_map()
@@ -456,16 +459,27 @@ fn import_memories(
             .and_then(|namespace| namespace.get_export(&name));
         match memory_import {
             Some(Export::Memory(memory)) => {
-                if expected_memory_desc.fits_in_imported(memory.descriptor()) {
-                    memories.push(memory.clone());
-                    vm_memories.push(memory.vm_local_memory());
-                } else {
+                if !expected_memory_desc.fits_in_imported(memory.descriptor()) {
                     link_errors.push(LinkError::IncorrectMemoryDescriptor {
                         namespace: namespace.to_string(),
                         name: name.to_string(),
                         expected: *expected_memory_desc,
                         found: memory.descriptor(),
                     });
+                } else if !module
+                    .info
+                    .memory_config
+                    .accepts(*expected_memory_desc, memory.config())
+                {
+                    link_errors.push(LinkError::IncompatibleMemoryConfig {
+                        namespace: namespace.to_string(),
+                        name: name.to_string(),
+                        expected: module.info.memory_config,
+                        found: memory.config(),
+                    });
+                } else {
+                    memories.push(memory.clone());
+                    vm_memories.push(memory.vm_local_memory());
                 }
             }
             Some(export_type) => {
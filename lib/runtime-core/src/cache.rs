@@ -14,6 +14,8 @@ use std::{
 pub enum InvalidFileType {
     InvalidSize,
     InvalidMagic,
+    /// The cache holds machine code for another architecture.
+    WrongArchitecture,
 }
 
 #[derive(Debug)]
@@ -26,7 +28,17 @@ pub enum Error {
     InvalidatedCache,
 }
 
-const CURRENT_CACHE_VERSION: u64 = 0;
+const CURRENT_CACHE_VERSION: u64 = 1;
+
+const ARCH_LEN: usize = 16;
+
+/// The architecture the code is compiled for, NUL-padded.
+fn current_arch() -> [u8; ARCH_LEN] {
+    let mut arch = [0; ARCH_LEN];
+    let name = std::env::consts::ARCH.as_bytes();
+    arch[..name.len()].copy_from_slice(name);
+    arch
+}
 
 /// The header of a cache file.
 #[repr(C, packed)]
@@ -35,6 +47,7 @@ struct CacheHeader {
     version: u64,
     data_len: u64,
     wasm_hash: [u8; 32], // Sha256 of the wasm in binary format.
+    arch: [u8; ARCH_LEN],
 }
 
 impl CacheHeader {
@@ -44,10 +57,12 @@ impl CacheHeader {
                 let (header_slice, body_slice) = buffer.split_at(mem::size_of::<CacheHeader>());
                 let header = unsafe { &*(header_slice.as_ptr() as *const CacheHeader) };
 
-                if header.version == CURRENT_CACHE_VERSION {
-                    Ok((header, body_slice))
-                } else {
+                if header.version != CURRENT_CACHE_VERSION {
                     Err(Error::InvalidatedCache)
+                } else if header.arch != current_arch() {
+                    Err(Error::InvalidFile(InvalidFileType::WrongArchitecture))
+                } else {
+                    Ok((header, body_slice))
                 }
             } else {
                 Err(Error::InvalidFile(InvalidFileType::InvalidMagic))
@@ -165,6 +180,7 @@ impl Cache {
             version: CURRENT_CACHE_VERSION,
             data_len,
             wasm_hash,
+            arch: current_arch(),
         };
 
         file.write(cache_header.as_slice())
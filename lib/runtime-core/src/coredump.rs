@@ -0,0 +1,270 @@
+//! Core dumps of instances, for post-mortem debugging of the guests that
+//! trap or abort.
+//!
+//! A core dump is a file with every integer in little-endian order:
+//!
+//! | Field    | Encoding                                                  |
+//! |----------|-----------------------------------------------------------|
+//! | magic    | the bytes `\0wcd`                                         |
+//! | version  | `u32`, currently `1`                                      |
+//! | reason   | a string                                                  |
+//! | memories | `u32` count, then the bytes of each memory as a `u32` length and the bytes |
+//! | globals  | `u32` count, then each global as a type byte and its `u64` bits |
+//! | notes    | `u32` count, then each note as a string                   |
+//!
+//! Strings are a `u32` length followed by that many bytes of UTF-8. The
+//! type bytes are the ones of the binary format: `0x7f` for `i32`,
+//! `0x7e` for `i64`, `0x7d` for `f32` and `0x7c` for `f64`. The notes are
+//! whatever the embedder knows about the guest, e.g. its open files.
+//!
+//! The runtime can't unwind wasm frames yet, so there's no backtrace
+//! beyond what the reason says.
+//!
+//! A dump of an instance that is paused between calls is a snapshot of
+//! it: `restore` thaws it into a new instance of the same module, here
+//! or on another machine. The tables aren't part of it, since only the
+//! element segments write to them and a new instance has them already.
+//! Neither are the imported globals, which are their owner's, nor the
+//! immutable ones, which a new instance has already.
+use crate::{
+    structures::TypedIndex,
+    types::{Type, Value},
+    units::{Bytes, Pages},
+    vm::Ctx,
+};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+const MAGIC: &[u8; 4] = b"\0wcd";
+const VERSION: u32 = 1;
+
+/// The state of an instance when it crashed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreDump {
+    /// Why the instance crashed, e.g. the trap message.
+    pub reason: String,
+    /// The contents of the linear memories, imported ones first.
+    pub memories: Vec<Vec<u8>>,
+    /// The values of the globals, imported ones first.
+    pub globals: Vec<Value>,
+    /// Anything else the embedder recorded.
+    pub notes: Vec<String>,
+}
+
+impl CoreDump {
+    /// Capture the memories and globals of the instance of `ctx`.
+    pub fn capture(ctx: &Ctx, reason: &str) -> Self {
+        let memories = (0..ctx.memory_count())
+            .map(|index| ctx.memory(index).snapshot())
+            .collect();
+        let globals = ctx
+            .global_types()
+            .enumerate()
+            .map(|(index, ty)| {
+                let bits = ctx.vm_global(index as u32).unwrap().data;
+                match ty {
+                    Type::I32 => Value::I32(bits as i32),
+                    Type::I64 => Value::I64(bits as i64),
+                    Type::F32 => Value::F32(f32::from_bits(bits as u32)),
+                    Type::F64 => Value::F64(f64::from_bits(bits)),
+                }
+            })
+            .collect();
+        CoreDump {
+            reason: reason.to_string(),
+            memories,
+            globals,
+            notes: Vec::new(),
+        }
+    }
+
+    /// Write the memories and the local mutable globals of the dump back
+    /// into the instance of `ctx`, which must be an instance of the module
+    /// it was taken from. Memories are grown to the size they had.
+    pub fn restore(&self, ctx: &mut Ctx) -> io::Result<()> {
+        let types: Vec<_> = ctx.global_types().collect();
+        let dumped_types: Vec<_> = self.globals.iter().map(Value::ty).collect();
+        if self.memories.len() != ctx.memory_count() as usize || types != dumped_types {
+            return Err(invalid_input(
+                "the dump was taken from an instance of another module",
+            ));
+        }
+
+        for (index, bytes) in self.memories.iter().enumerate() {
+            let memory = ctx.memory(index as u32);
+            let dumped = Pages::from(Bytes(bytes.len()));
+            if dumped > memory.size() && memory.grow(dumped - memory.size()).is_none() {
+                return Err(invalid_input("a memory can't grow to its size in the dump"));
+            }
+            let view = memory.view::<u8>();
+            for (cell, &byte) in view.iter().zip(bytes) {
+                cell.set(byte);
+            }
+            for cell in &view[bytes.len()..] {
+                cell.set(0);
+            }
+        }
+
+        let info = unsafe { &(*ctx.module).info };
+        let local_values = &self.globals[info.imported_globals.len()..];
+        for ((index, global), value) in info.globals.iter().zip(local_values) {
+            if global.desc.mutable {
+                let local = unsafe { &mut **ctx.globals.add(index.index()) };
+                local.data = global_bits(value);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        write_u32(&mut writer, VERSION)?;
+        write_bytes(&mut writer, self.reason.as_bytes())?;
+        write_u32(&mut writer, self.memories.len() as u32)?;
+        for memory in &self.memories {
+            write_bytes(&mut writer, memory)?;
+        }
+        write_u32(&mut writer, self.globals.len() as u32)?;
+        for global in &self.globals {
+            let ty = match global.ty() {
+                Type::I32 => 0x7f,
+                Type::I64 => 0x7e,
+                Type::F32 => 0x7d,
+                Type::F64 => 0x7c,
+            };
+            writer.write_all(&[ty])?;
+            writer.write_all(&global_bits(global).to_le_bytes())?;
+        }
+        write_u32(&mut writer, self.notes.len() as u32)?;
+        for note in &self.notes {
+            write_bytes(&mut writer, note.as_bytes())?;
+        }
+        writer.flush()
+    }
+
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a core dump"));
+        }
+        let version = read_u32(&mut reader)?;
+        if version != VERSION {
+            return Err(invalid_data(&format!(
+                "unsupported core dump version {}",
+                version
+            )));
+        }
+        let reason = read_string(&mut reader)?;
+        let memories = (0..read_u32(&mut reader)?)
+            .map(|_| read_bytes(&mut reader))
+            .collect::<io::Result<_>>()?;
+        let globals = (0..read_u32(&mut reader)?)
+            .map(|_| {
+                let mut ty = [0u8; 1];
+                reader.read_exact(&mut ty)?;
+                let mut bits = [0u8; 8];
+                reader.read_exact(&mut bits)?;
+                let bits = u64::from_le_bytes(bits);
+                Ok(match ty[0] {
+                    0x7f => Value::I32(bits as i32),
+                    0x7e => Value::I64(bits as i64),
+                    0x7d => Value::F32(f32::from_bits(bits as u32)),
+                    0x7c => Value::F64(f64::from_bits(bits)),
+                    ty => return Err(invalid_data(&format!("unknown global type {:#x}", ty))),
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        let notes = (0..read_u32(&mut reader)?)
+            .map(|_| read_string(&mut reader))
+            .collect::<io::Result<_>>()?;
+        Ok(CoreDump {
+            reason,
+            memories,
+            globals,
+            notes,
+        })
+    }
+
+    /// Write the core dump to the file at `path`, which is truncated.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_to(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+}
+
+/// The bits of `value` as a `LocalGlobal` stores them.
+fn global_bits(value: &Value) -> u64 {
+    match *value {
+        Value::I32(value) => value as u32 as u64,
+        Value::I64(value) => value as u64,
+        Value::F32(value) => u64::from(value.to_bits()),
+        Value::F64(value) => value.to_bits(),
+    }
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    write_u32(writer, bytes.len() as u32)?;
+    writer.write_all(bytes)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_bytes<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let len = read_u32(reader)?;
+    let mut bytes = Vec::new();
+    reader
+        .by_ref()
+        .take(u64::from(len))
+        .read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+fn read_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    String::from_utf8(read_bytes(reader)?).map_err(|_| invalid_data("a string isn't UTF-8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CoreDump;
+    use crate::types::Value;
+
+    #[test]
+    fn round_trips() {
+        let dump = CoreDump {
+            reason: "unreachable".to_string(),
+            memories: vec![vec![1, 2, 3]],
+            globals: vec![Value::I32(-1), Value::F64(0.5)],
+            notes: vec!["fd 3: /tmp/log".to_string()],
+        };
+        let mut bytes = Vec::new();
+        dump.write_to(&mut bytes).unwrap();
+        assert_eq!(CoreDump::read_from(&bytes[..]).unwrap(), dump);
+        assert!(CoreDump::read_from(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,396 @@
+//! Turning binary modules back into text, to read what a module does
+//! without the source it was compiled from.
+//!
+//! The output is in the text format, with the index of each definition
+//! in a comment, e.g. `(func $main (;3;) (type 1)`. Data and element
+//! segments are only counted, so it doesn't assemble back into the same
+//! module.
+use crate::error::{CompileError, CompileResult};
+use hashbrown::HashMap;
+use std::fmt::Write;
+use std::ops::Range;
+use wasmparser::{
+    BinaryReader, ExternalKind, FuncType, ImportSectionEntryType, NameEntry, Operator, Parser,
+    ParserState, ResizableLimits, Type, WasmDecoder,
+};
+
+/// Disassemble `wasm`, the whole module, or only the functions of
+/// `functions` if given. Function indices count the imported functions.
+pub fn disassemble(wasm: &[u8], functions: Option<Range<u32>>) -> CompileResult<String> {
+    let names = function_names(wasm);
+    let mut parser = Parser::new(wasm);
+    let mut text = String::new();
+    let mut types = Vec::new();
+    let mut function_types = Vec::new();
+    let mut imported_functions = 0;
+    let mut function = 0;
+    let mut depth = 0;
+    let mut printing = true;
+    let mut in_global = false;
+    let mut data_segments = 0;
+    let mut element_segments = 0;
+    let whole_module = functions.is_none();
+    let shown = |index: u32| {
+        functions
+            .as_ref()
+            .map_or(true, |range| range.contains(&index))
+    };
+
+    if whole_module {
+        text.push_str("(module\n");
+    }
+    loop {
+        match *parser.read() {
+            ParserState::EndWasm => break,
+            ParserState::Error(ref err) => {
+                return Err(CompileError::ValidationError {
+                    msg: format!("{} at offset {:#x}", err.message, err.offset),
+                    location: None,
+                })
+            }
+            ParserState::TypeSectionEntry(ref func_type) => {
+                if whole_module {
+                    writeln!(
+                        text,
+                        "  (type (;{};) (func{}))",
+                        types.len(),
+                        signature_text(func_type)
+                    )
+                    .unwrap();
+                }
+                types.push(func_type.clone());
+            }
+            ParserState::ImportSectionEntry {
+                module,
+                field,
+                ref ty,
+            } => {
+                let desc = match *ty {
+                    ImportSectionEntryType::Function(type_index) => {
+                        imported_functions += 1;
+                        format!("func (;{};) (type {})", imported_functions - 1, type_index)
+                    }
+                    ImportSectionEntryType::Table(ref table) => format!(
+                        "table{} {}",
+                        limits_text(&table.limits),
+                        type_text(table.element_type)
+                    ),
+                    ImportSectionEntryType::Memory(ref memory) => {
+                        format!("memory{}", limits_text(&memory.limits))
+                    }
+                    ImportSectionEntryType::Global(ref global) if global.mutable => {
+                        format!("global (mut {})", type_text(global.content_type))
+                    }
+                    ImportSectionEntryType::Global(ref global) => {
+                        format!("global {}", type_text(global.content_type))
+                    }
+                };
+                if whole_module {
+                    writeln!(
+                        text,
+                        "  (import \"{}\" \"{}\" ({}))",
+                        String::from_utf8_lossy(module.as_ref()),
+                        String::from_utf8_lossy(field.as_ref()),
+                        desc
+                    )
+                    .unwrap();
+                }
+            }
+            ParserState::FunctionSectionEntry(type_index) => function_types.push(type_index),
+            ParserState::TableSectionEntry(ref table) if whole_module => writeln!(
+                text,
+                "  (table{} {})",
+                limits_text(&table.limits),
+                type_text(table.element_type)
+            )
+            .unwrap(),
+            ParserState::MemorySectionEntry(ref memory) if whole_module => {
+                writeln!(text, "  (memory{})", limits_text(&memory.limits)).unwrap()
+            }
+            ParserState::GlobalSectionEntry(ref global) if whole_module => {
+                in_global = true;
+                if global.mutable {
+                    write!(text, "  (global (mut {})", type_text(global.content_type)).unwrap();
+                } else {
+                    write!(text, "  (global {}", type_text(global.content_type)).unwrap();
+                }
+            }
+            ParserState::InitExpressionOperator(ref operator) if in_global => {
+                if let Operator::End = *operator {
+                    in_global = false;
+                    text.push_str(")\n");
+                } else {
+                    write!(text, " ({})", operator_text(operator)).unwrap();
+                }
+            }
+            ParserState::ExportSectionEntry {
+                field,
+                ref kind,
+                index,
+            } if whole_module => {
+                let kind = match *kind {
+                    ExternalKind::Function => "func",
+                    ExternalKind::Table => "table",
+                    ExternalKind::Memory => "memory",
+                    ExternalKind::Global => "global",
+                };
+                writeln!(
+                    text,
+                    "  (export \"{}\" ({} {}))",
+                    String::from_utf8_lossy(field.as_ref()),
+                    kind,
+                    index
+                )
+                .unwrap();
+            }
+            ParserState::StartSectionEntry(index) if whole_module => {
+                writeln!(text, "  (start {})", index).unwrap()
+            }
+            ParserState::BeginElementSectionEntry(_) => element_segments += 1,
+            ParserState::BeginDataSectionEntry(_) => data_segments += 1,
+            ParserState::BeginFunctionBody { .. } => {
+                let index = imported_functions + function;
+                let type_index = function_types.get(function as usize).cloned();
+                function += 1;
+                depth = 0;
+                printing = shown(index);
+                if !printing {
+                    continue;
+                }
+                text.push_str("  (func");
+                if let Some(name) = names.get(&index) {
+                    write!(text, " ${}", name).unwrap();
+                }
+                write!(text, " (;{};)", index).unwrap();
+                if let Some(type_index) = type_index {
+                    write!(text, " (type {})", type_index).unwrap();
+                    if let Some(func_type) = types.get(type_index as usize) {
+                        text.push_str(&signature_text(func_type));
+                    }
+                }
+                text.push('\n');
+            }
+            ParserState::FunctionBodyLocals { ref locals } if printing => {
+                if !locals.is_empty() {
+                    text.push_str("    (local");
+                    for &(count, ty) in locals.iter() {
+                        for _ in 0..count {
+                            write!(text, " {}", type_text(ty)).unwrap();
+                        }
+                    }
+                    text.push_str(")\n");
+                }
+            }
+            ParserState::CodeOperator(ref operator) if printing => {
+                match *operator {
+                    Operator::End | Operator::Else => depth -= 1,
+                    _ => {}
+                }
+                // The last `end` closes the function.
+                if depth >= 0 {
+                    for _ in 0..depth + 2 {
+                        text.push_str("  ");
+                    }
+                    text.push_str(&operator_text(operator));
+                    text.push('\n');
+                }
+                match *operator {
+                    Operator::Block { .. }
+                    | Operator::Loop { .. }
+                    | Operator::If { .. }
+                    | Operator::Else => depth += 1,
+                    _ => {}
+                }
+            }
+            ParserState::EndFunctionBody if printing => text.push_str("  )\n"),
+            _ => {}
+        }
+    }
+    if whole_module {
+        if element_segments > 0 {
+            writeln!(text, "  ;; {} element segments", element_segments).unwrap();
+        }
+        if data_segments > 0 {
+            writeln!(text, "  ;; {} data segments", data_segments).unwrap();
+        }
+        text.push_str(")\n");
+    }
+    Ok(text)
+}
+
+/// The names the name section gives the functions.
+pub(crate) fn function_names(wasm: &[u8]) -> HashMap<u32, String> {
+    let mut parser = Parser::new(wasm);
+    loop {
+        match *parser.read() {
+            ParserState::EndWasm | ParserState::Error(_) => return HashMap::new(),
+            ParserState::NameSectionEntry(NameEntry::Function(ref names)) => {
+                return names
+                    .iter()
+                    .map(|naming| {
+                        let name = String::from_utf8_lossy(naming.name.as_ref()).into_owned();
+                        (naming.index, name)
+                    })
+                    .collect();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The name the name section gives the function of `index`.
+pub(crate) fn function_name(wasm: &[u8], index: u32) -> Option<String> {
+    function_names(wasm).remove(&index)
+}
+
+/// The operator at `offset`, disassembled, or its opcode if it can't be
+/// read.
+pub(crate) fn operator_at(wasm: &[u8], offset: usize) -> Option<String> {
+    let bytes = wasm.get(offset..)?;
+    match BinaryReader::new(bytes).read_operator() {
+        Ok(operator) => Some(operator_text(&operator)),
+        Err(_) => Some(format!("<opcode {:#04x}>", bytes.first()?)),
+    }
+}
+
+fn type_text(ty: Type) -> String {
+    format!("{:?}", ty).to_lowercase()
+}
+
+fn signature_text(func_type: &FuncType) -> String {
+    let mut text = String::new();
+    if !func_type.params.is_empty() {
+        text.push_str(" (param");
+        for &ty in func_type.params.iter() {
+            write!(text, " {}", type_text(ty)).unwrap();
+        }
+        text.push(')');
+    }
+    if !func_type.returns.is_empty() {
+        text.push_str(" (result");
+        for &ty in func_type.returns.iter() {
+            write!(text, " {}", type_text(ty)).unwrap();
+        }
+        text.push(')');
+    }
+    text
+}
+
+fn limits_text(limits: &ResizableLimits) -> String {
+    match limits.maximum {
+        Some(maximum) => format!(" {} {}", limits.initial, maximum),
+        None => format!(" {}", limits.initial),
+    }
+}
+
+/// `operator` in the text format, e.g. `i32.const 1` for
+/// `I32Const { value: 1 }`.
+pub(crate) fn operator_text(operator: &Operator) -> String {
+    let debug = format!("{:?}", operator);
+    let (name, fields) = match debug.find(' ') {
+        Some(space) => (&debug[..space], &debug[space..]),
+        None => (&debug[..], ""),
+    };
+
+    let mut text = String::new();
+    let rest = match ["I32", "I64", "F32", "F64"]
+        .iter()
+        .find(|ty| name.starts_with(*ty))
+    {
+        Some(ty) if name.len() > ty.len() => {
+            text.push_str(&ty.to_lowercase());
+            text.push('.');
+            &name[ty.len()..]
+        }
+        _ => name,
+    };
+    for (index, c) in rest.char_indices() {
+        if c.is_uppercase() && index > 0 {
+            text.push('_');
+        }
+        text.extend(c.to_lowercase());
+    }
+
+    let immediates = match *operator {
+        Operator::Block { ty } | Operator::Loop { ty } | Operator::If { ty } => match ty {
+            Type::EmptyBlockType => String::new(),
+            ty => format!("(result {})", type_text(ty)),
+        },
+        Operator::BrTable { ref table } => match table.read_table() {
+            Ok((targets, default)) => {
+                let mut immediates = String::new();
+                for target in targets.iter() {
+                    write!(immediates, "{} ", target).unwrap();
+                }
+                write!(immediates, "{}", default).unwrap();
+                immediates
+            }
+            Err(_) => "<invalid>".to_string(),
+        },
+        Operator::CallIndirect { index, .. } => format!("(type {})", index),
+        Operator::F32Const { value } => format!("{}", f32::from_bits(value.bits())),
+        Operator::F64Const { value } => format!("{}", f64::from_bits(value.bits())),
+        Operator::MemorySize { .. } | Operator::MemoryGrow { .. } => String::new(),
+        _ if fields.contains("memarg") => {
+            let field = |name: &str| {
+                let start = fields.find(name)? + name.len();
+                let end = fields[start..].find(|c: char| !c.is_ascii_digit())?;
+                fields[start..start + end].parse::<u32>().ok()
+            };
+            match (field("offset: "), field("flags: ")) {
+                (Some(0), Some(flags)) => format!("align={}", 1u32 << flags),
+                (Some(offset), Some(flags)) => {
+                    format!("offset={} align={}", offset, 1u32 << flags)
+                }
+                _ => String::new(),
+            }
+        }
+        // The other immediates are plain numbers.
+        _ => fields
+            .trim()
+            .trim_start_matches('{')
+            .trim_end_matches('}')
+            .split(',')
+            .filter_map(|field| field.split(": ").nth(1))
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+    if !immediates.is_empty() {
+        text.push(' ');
+        text.push_str(&immediates);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::operator_text;
+    use wasmparser::{MemoryImmediate, Operator, Type};
+
+    #[test]
+    fn disassembles_operators() {
+        assert_eq!(
+            operator_text(&Operator::I32Const { value: -1 }),
+            "i32.const -1"
+        );
+        assert_eq!(operator_text(&Operator::F64Add), "f64.add");
+        assert_eq!(
+            operator_text(&Operator::GetLocal { local_index: 2 }),
+            "get_local 2"
+        );
+        assert_eq!(
+            operator_text(&Operator::Block { ty: Type::I32 }),
+            "block (result i32)"
+        );
+        assert_eq!(
+            operator_text(&Operator::I64Load {
+                memarg: MemoryImmediate {
+                    flags: 3,
+                    offset: 16
+                }
+            }),
+            "i64.load offset=16 align=8"
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::memory::MemoryConfig;
 use crate::types::{
     FuncSig, GlobalDescriptor, MemoryDescriptor, MemoryIndex, TableDescriptor, TableIndex, Type,
 };
@@ -18,8 +19,58 @@ pub type ResolveResult<T> = std::result::Result<T, ResolveError>;
 /// Comparing two `CompileError`s always evaluates to false.
 #[derive(Debug, Clone)]
 pub enum CompileError {
-    ValidationError { msg: String },
-    InternalError { msg: String },
+    ValidationError {
+        msg: String,
+        location: Option<Location>,
+    },
+    InternalError {
+        msg: String,
+    },
+    ReadError {
+        msg: String,
+    },
+}
+
+/// Where in the binary a module is invalid.
+#[derive(Debug, Clone)]
+pub struct Location {
+    /// The offset of the error in the binary.
+    pub offset: usize,
+    /// The section the error is in, e.g. `code`.
+    pub section: String,
+    /// The index of the function, counting the imported ones, for errors
+    /// in the code section.
+    pub function: Option<u32>,
+    /// The name the name section gives the function.
+    pub function_name: Option<String>,
+    /// The operator at `offset`, disassembled.
+    pub operator: Option<String>,
+    /// The operators before it in the function, disassembled, last one
+    /// last.
+    pub preceding: Vec<String>,
+}
+
+impl std::fmt::Display for Location {
+    /// Writes where the error is on the first line, followed by the
+    /// operators up to the one at fault, which is underlined.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "in the {} section", self.section)?;
+        if let Some(function) = self.function {
+            write!(f, ", function {}", function)?;
+            if let Some(name) = &self.function_name {
+                write!(f, " `{}`", name)?;
+            }
+        }
+        write!(f, ", at offset {:#x}", self.offset)?;
+        if let Some(operator) = &self.operator {
+            for preceding in &self.preceding {
+                write!(f, "\n    {}", preceding)?;
+            }
+            let width = operator.split(' ').next().unwrap_or("").len();
+            write!(f, "\n    {}\n    {}", operator, "^".repeat(width))?;
+        }
+        Ok(())
+    }
 }
 
 impl PartialEq for CompileError {
@@ -34,7 +85,15 @@ impl std::fmt::Display for CompileError {
             CompileError::InternalError { msg } => {
                 write!(f, "Internal compiler error: \"{}\"", msg)
             }
-            CompileError::ValidationError { msg } => write!(f, "Validation error \"{}\"", msg),
+            CompileError::ValidationError {
+                msg,
+                location: None,
+            } => write!(f, "Validation error \"{}\"", msg),
+            CompileError::ValidationError {
+                msg,
+                location: Some(location),
+            } => write!(f, "Validation error \"{}\" {}", msg, location),
+            CompileError::ReadError { msg } => write!(f, "Failed to read the module: \"{}\"", msg),
         }
     }
 }
@@ -75,6 +134,12 @@ pub enum LinkError {
         expected: TableDescriptor,
         found: TableDescriptor,
     },
+    IncompatibleMemoryConfig {
+        namespace: String,
+        name: String,
+        expected: MemoryConfig,
+        found: MemoryConfig,
+    },
     IncorrectGlobalDescriptor {
         namespace: String,
         name: String,
@@ -103,10 +168,13 @@ impl std::fmt::Display for LinkError {
                 write!(f, "Incorrect import type, namespace: {}, name: {}, expected type: {}, found type: {}", namespace, name, expected, found)
             }
             LinkError::IncorrectMemoryDescriptor{namespace, name,expected,found} => {
-                write!(f, "Incorrect memory descriptor, namespace: {}, name: {}, expected memory descriptor: {:?}, found memory descriptor: {:?}", namespace, name, expected, found)
+                write!(f, "Incorrect memory descriptor, namespace: {}, name: {}, {}; declared {}, provided {}", namespace, name, memory_mismatch(expected, found), memory_limits(expected), memory_limits(found))
             },
             LinkError::IncorrectTableDescriptor{namespace, name,expected,found} => {
-                write!(f, "Incorrect table descriptor, namespace: {}, name: {}, expected table descriptor: {:?}, found table descriptor: {:?}", namespace, name, expected, found)
+                write!(f, "Incorrect table descriptor, namespace: {}, name: {}, {}; declared {}, provided {}", namespace, name, table_mismatch(expected, found), table_limits(expected), table_limits(found))
+            },
+            LinkError::IncompatibleMemoryConfig{namespace, name,expected,found} => {
+                write!(f, "Incompatible memory config, namespace: {}, name: {}, expected memory config: {:?}, found memory config: {:?}", namespace, name, expected, found)
             },
         }
     }
@@ -114,6 +182,89 @@ impl std::fmt::Display for LinkError {
 
 impl std::error::Error for LinkError {}
 
+fn memory_limits(desc: &MemoryDescriptor) -> String {
+    let maximum = match desc.maximum {
+        Some(maximum) => format!("max {} pages", maximum.0),
+        None => "no max".to_string(),
+    };
+    let shared = if desc.shared { ", shared" } else { "" };
+    format!("min {} pages, {}{}", desc.minimum.0, maximum, shared)
+}
+
+fn table_limits(desc: &TableDescriptor) -> String {
+    match desc.maximum {
+        Some(maximum) => format!("min {} elements, max {}", desc.minimum, maximum),
+        None => format!("min {} elements, no max", desc.minimum),
+    }
+}
+
+/// Why an imported memory doesn't fit the declaration of the module, with
+/// the `TOTAL_MEMORY` emscripten would have to size it with.
+fn memory_mismatch(expected: &MemoryDescriptor, found: &MemoryDescriptor) -> String {
+    let mut problems = vec![];
+    if expected.shared != found.shared {
+        problems.push(if expected.shared {
+            "the module declares a shared memory but the import isn't shared".to_string()
+        } else {
+            "the import is shared but the module declares an unshared memory".to_string()
+        });
+    }
+    if found.minimum < expected.minimum {
+        problems.push(format!(
+            "the module needs at least {} pages but the import starts with {} \
+             (with emscripten, the memory needs TOTAL_MEMORY={} or more)",
+            expected.minimum.0,
+            found.minimum.0,
+            expected.minimum.bytes().0
+        ));
+    }
+    if let Some(expected_max) = expected.maximum {
+        match found.maximum {
+            Some(found_max) if found_max <= expected_max => {}
+            Some(found_max) => problems.push(format!(
+                "the module allows at most {} pages but the import can grow to {}",
+                expected_max.0, found_max.0
+            )),
+            None => problems.push(format!(
+                "the module allows at most {} pages but the import has no max",
+                expected_max.0
+            )),
+        }
+    }
+    problems.join(", and ")
+}
+
+/// Why an imported table doesn't fit the declaration of the module.
+fn table_mismatch(expected: &TableDescriptor, found: &TableDescriptor) -> String {
+    let mut problems = vec![];
+    if expected.element != found.element {
+        problems.push(format!(
+            "the module declares a table of {:?} but the import holds {:?}",
+            expected.element, found.element
+        ));
+    }
+    if found.minimum < expected.minimum {
+        problems.push(format!(
+            "the module needs at least {} elements but the import starts with {}",
+            expected.minimum, found.minimum
+        ));
+    }
+    if let Some(expected_max) = expected.maximum {
+        match found.maximum {
+            Some(found_max) if found_max <= expected_max => {}
+            Some(found_max) => problems.push(format!(
+                "the module allows at most {} elements but the import can grow to {}",
+                expected_max, found_max
+            )),
+            None => problems.push(format!(
+                "the module allows at most {} elements but the import has no max",
+                expected_max
+            )),
+        }
+    }
+    problems.join(", and ")
+}
+
 /// This is the error type returned when calling
 /// a webassembly function.
 ///
@@ -267,6 +418,8 @@ pub enum CreationError {
     UnableToCreateMemory,
     UnableToCreateTable,
     InvalidDescriptor(String),
+    /// Creating it would exceed a limit set by the host.
+    LimitExceeded(String),
 }
 
 impl PartialEq for CreationError {
@@ -285,6 +438,7 @@ impl std::fmt::Display for CreationError {
                 "Unable to create because the supplied descriptor is invalid: \"{}\"",
                 msg
             ),
+            CreationError::LimitExceeded(msg) => write!(f, "Limit exceeded: {}", msg),
         }
     }
 }
@@ -362,8 +516,53 @@ impl From<ResolveError> for CallError {
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self)
+        match self {
+            Error::CompileError(err) => write!(f, "{}", err),
+            Error::LinkError(errs) => {
+                write!(f, "Link errors:")?;
+                for err in errs {
+                    write!(f, "\n  {}", err)?;
+                }
+                Ok(())
+            }
+            Error::RuntimeError(err) => write!(f, "{}", err),
+            Error::ResolveError(err) => write!(f, "{}", err),
+            Error::CallError(err) => write!(f, "{}", err),
+            Error::CreationError(err) => write!(f, "{}", err),
+        }
     }
 }
 
 impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Pages;
+
+    #[test]
+    fn memory_errors_suggest_total_memory() {
+        let err = LinkError::IncorrectMemoryDescriptor {
+            namespace: "env".to_string(),
+            name: "memory".to_string(),
+            expected: MemoryDescriptor {
+                minimum: Pages(256),
+                maximum: Some(Pages(256)),
+                shared: false,
+            },
+            found: MemoryDescriptor {
+                minimum: Pages(16),
+                maximum: None,
+                shared: false,
+            },
+        };
+        assert_eq!(
+            err.to_string(),
+            "Incorrect memory descriptor, namespace: env, name: memory, \
+             the module needs at least 256 pages but the import starts with 16 \
+             (with emscripten, the memory needs TOTAL_MEMORY=16777216 or more), \
+             and the module allows at most 256 pages but the import has no max; \
+             declared min 256 pages, max 256 pages, provided min 16 pages, no max"
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Hooks into the engine, for integrations that observe it without
+//! patching it, e.g. to trace or to monitor the modules it runs.
+use crate::{
+    error::{RuntimeError, RuntimeResult},
+    module::ModuleInfo,
+    types::{FuncIndex, Value},
+    units::Pages,
+};
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Receives the events of the engine. Every method does nothing by
+/// default, so a listener only implements the ones it cares about.
+///
+/// The events are reported on the thread they happen on, while the
+/// engine waits, so listeners should return quickly.
+pub trait EventListener: Send + Sync {
+    /// A module was compiled.
+    fn on_compile(&self, _module: &ModuleInfo) {}
+
+    /// A module was instantiated, before its start function runs.
+    fn on_instantiate(&self, _module: &ModuleInfo) {}
+
+    /// The host is about to call the function `func_index` of a module.
+    fn on_call_start(&self, _module: &ModuleInfo, _func_index: FuncIndex) {}
+
+    /// A call from the host returned.
+    fn on_call_finish(&self, _module: &ModuleInfo, _func_index: FuncIndex, _returns: &[Value]) {}
+
+    /// A call from the host trapped.
+    fn on_trap(&self, _module: &ModuleInfo, _func_index: FuncIndex, _error: &RuntimeError) {}
+
+    /// A memory grew from `old` to `new` pages.
+    fn on_memory_grow(&self, _old: Pages, _new: Pages) {}
+}
+
+lazy_static! {
+    static ref LISTENERS: RwLock<Vec<Arc<dyn EventListener>>> = RwLock::new(Vec::new());
+}
+
+/// Whether there's any listener, to skip the lock when there's none.
+static LISTENING: AtomicBool = AtomicBool::new(false);
+
+/// Report the events of the engine to `listener`, from now on.
+pub fn add_listener(listener: Arc<dyn EventListener>) {
+    LISTENERS.write().push(listener);
+    LISTENING.store(true, Ordering::SeqCst);
+}
+
+/// Stop reporting events to `listener`.
+pub fn remove_listener(listener: &Arc<dyn EventListener>) {
+    let mut listeners = LISTENERS.write();
+    listeners.retain(|other| !Arc::ptr_eq(other, listener));
+    LISTENING.store(!listeners.is_empty(), Ordering::SeqCst);
+}
+
+fn notify<F: Fn(&dyn EventListener)>(event: F) {
+    if !LISTENING.load(Ordering::Relaxed) {
+        return;
+    }
+    for listener in LISTENERS.read().iter() {
+        event(&**listener);
+    }
+}
+
+pub(crate) fn compiled(module: &ModuleInfo) {
+    notify(|listener| listener.on_compile(module));
+}
+
+pub(crate) fn instantiated(module: &ModuleInfo) {
+    notify(|listener| listener.on_instantiate(module));
+}
+
+pub(crate) fn memory_grew(old: Pages, new: Pages) {
+    notify(|listener| listener.on_memory_grow(old, new));
+}
+
+/// Run the call to `func_index` made by `call`, reporting its start and
+/// how it ends.
+pub(crate) fn observe_call<F>(
+    module: &ModuleInfo,
+    func_index: FuncIndex,
+    call: F,
+) -> RuntimeResult<Vec<Value>>
+where
+    F: FnOnce() -> RuntimeResult<Vec<Value>>,
+{
+    notify(|listener| listener.on_call_start(module, func_index));
+    let result = call();
+    match &result {
+        Ok(returns) => notify(|listener| listener.on_call_finish(module, func_index, returns)),
+        Err(error) => notify(|listener| listener.on_trap(module, func_index, error)),
+    }
+    result
+}
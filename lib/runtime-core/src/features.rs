@@ -0,0 +1,336 @@
+//! The WebAssembly proposals a module may use beyond the MVP.
+//!
+//! A module that uses a feature that isn't enabled fails validation with
+//! an error that names the feature and where it's used, rather than with
+//! whatever the parser makes of the unknown opcode.
+//!
+//! Validation errors say where the module is invalid, down to the
+//! function and the operator for errors in code.
+use crate::{
+    disasm::{function_name, operator_at, operator_text},
+    error::{CompileError, CompileResult, Location},
+};
+use std::collections::VecDeque;
+use wasmparser::{
+    ImportSectionEntryType, Operator, OperatorValidatorConfig, ParserState, SectionCode,
+    ValidatingParser, ValidatingParserConfig, WasmDecoder,
+};
+
+/// The proposals a module can use, none by default.
+///
+/// The backends compile few of them so far. A module that uses a feature
+/// they can't compile fails validation either way, and enabling the
+/// feature only changes the error to say it isn't supported yet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Features {
+    /// Shared memories and atomic operators.
+    pub threads: bool,
+    /// 128-bit vector operators.
+    pub simd: bool,
+    /// `memory.copy`, `memory.fill` and the other bulk memory operators.
+    pub bulk_memory: bool,
+    /// Functions and blocks that return more than one value.
+    pub multi_value: bool,
+    /// `anyref`, `ref.null` and the table operators.
+    pub reference_types: bool,
+    /// The `i32.extend8_s` family of sign extension operators.
+    pub sign_ext: bool,
+}
+
+impl Features {
+    fn is_enabled(&self, feature: &str) -> bool {
+        match feature {
+            "threads" => self.threads,
+            "simd" => self.simd,
+            "bulk_memory" => self.bulk_memory,
+            "multi_value" => self.multi_value,
+            "reference_types" => self.reference_types,
+            "sign_ext" => self.sign_ext,
+            _ => false,
+        }
+    }
+}
+
+/// How many operators before the one at fault a `Location` shows.
+const PRECEDING_OPERATORS: usize = 3;
+
+/// Validate `wasm` as defined by the WebAssembly specification, with the
+/// proposals of `features`.
+pub fn validate(wasm: &[u8], features: &Features) -> CompileResult<()> {
+    // The parser rejects the operators of the features it isn't told
+    // about, which are the ones that can't be compiled.
+    let config = ValidatingParserConfig {
+        operator_config: OperatorValidatorConfig {
+            enable_threads: false,
+            enable_reference_types: false,
+        },
+        mutable_global_imports: true,
+    };
+    let mut parser = ValidatingParser::new(wasm, Some(config));
+    let mut section = "header";
+    let mut section_start = 0;
+    let mut imported_functions = 0;
+    let mut function = None;
+    let mut body_start = 0;
+    let mut operator = None;
+    let mut preceding = VecDeque::with_capacity(PRECEDING_OPERATORS);
+    loop {
+        let (msg, offset) = match *parser.read() {
+            ParserState::EndWasm => return Ok(()),
+            ParserState::BeginSection {
+                ref code,
+                ref range,
+            } => {
+                section = section_name(code);
+                section_start = range.start;
+                continue;
+            }
+            ParserState::ImportSectionEntry {
+                ty: ImportSectionEntryType::Function(_),
+                ..
+            } => {
+                imported_functions += 1;
+                continue;
+            }
+            ParserState::BeginFunctionBody { ref range } => {
+                function = Some(function.map_or(imported_functions, |index| index + 1));
+                body_start = range.start;
+                preceding.clear();
+                continue;
+            }
+            // The parser reads these whether or not the feature is enabled,
+            // and doesn't say where they are, so this points at the body.
+            ParserState::CodeOperator(ref code_operator)
+                if is_sign_ext(code_operator) && !features.sign_ext =>
+            {
+                operator = Some(operator_text(code_operator));
+                (disabled("sign_ext"), body_start)
+            }
+            ParserState::CodeOperator(ref code_operator) => {
+                if preceding.len() == PRECEDING_OPERATORS {
+                    preceding.pop_front();
+                }
+                preceding.push_back(code_operator.clone());
+                continue;
+            }
+            ParserState::ImportSectionEntry {
+                ty: ImportSectionEntryType::Memory(ref memory),
+                ..
+            }
+            | ParserState::MemorySectionEntry(ref memory)
+                if memory.shared =>
+            {
+                (refused(features, "threads"), section_start)
+            }
+            // The entries don't know where they are, so this points at the
+            // section.
+            ParserState::TypeSectionEntry(ref func_type)
+                if func_type.returns.len() > 1 && !features.multi_value =>
+            {
+                (disabled("multi_value"), section_start)
+            }
+            ParserState::Error(ref err) => {
+                let msg = match feature_at(wasm, err.offset) {
+                    Some(feature) => refused(features, feature),
+                    None => err.message.to_string(),
+                };
+                (msg, err.offset)
+            }
+            _ => continue,
+        };
+
+        let mut location = Location {
+            offset,
+            section: section.to_string(),
+            function: None,
+            function_name: None,
+            operator: None,
+            preceding: Vec::new(),
+        };
+        if section == "code" {
+            location.function = function;
+            location.function_name = function.and_then(|index| function_name(wasm, index));
+            location.operator = operator.or_else(|| operator_at(wasm, offset));
+            location.preceding = preceding.iter().map(operator_text).collect();
+        }
+        return Err(CompileError::ValidationError {
+            msg,
+            location: Some(location),
+        });
+    }
+}
+
+fn disabled(feature: &str) -> String {
+    format!("feature `{}` is disabled", feature)
+}
+
+/// Why a module that uses `feature` is invalid.
+fn refused(features: &Features, feature: &str) -> String {
+    if features.is_enabled(feature) {
+        format!("feature `{}` isn't supported by this runtime yet", feature)
+    } else {
+        disabled(feature)
+    }
+}
+
+fn is_sign_ext(operator: &Operator) -> bool {
+    match operator {
+        Operator::I32Extend8S
+        | Operator::I32Extend16S
+        | Operator::I64Extend8S
+        | Operator::I64Extend16S
+        | Operator::I64Extend32S => true,
+        _ => false,
+    }
+}
+
+fn section_name(code: &SectionCode) -> &'static str {
+    match code {
+        SectionCode::Custom { .. } => "custom",
+        SectionCode::Type => "type",
+        SectionCode::Import => "import",
+        SectionCode::Function => "function",
+        SectionCode::Table => "table",
+        SectionCode::Memory => "memory",
+        SectionCode::Global => "global",
+        SectionCode::Export => "export",
+        SectionCode::Start => "start",
+        SectionCode::Element => "element",
+        SectionCode::Code => "code",
+        SectionCode::Data => "data",
+    }
+}
+
+/// The feature the operator at `offset` belongs to, if any.
+fn feature_at(wasm: &[u8], offset: usize) -> Option<&'static str> {
+    Some(match wasm.get(offset)? {
+        0xfe => "threads",
+        0xfd => "simd",
+        // The first prefixed operators are the saturating conversions,
+        // which are part of no feature here.
+        0xfc if *wasm.get(offset + 1)? >= 8 => "bulk_memory",
+        0xc0..=0xc4 => "sign_ext",
+        0x25 | 0x26 | 0xd0..=0xd2 => "reference_types",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(sections: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        for &(id, contents) in sections {
+            wasm.push(id);
+            wasm.push(contents.len() as u8);
+            wasm.extend_from_slice(contents);
+        }
+        wasm
+    }
+
+    /// A module with a memory and a function of `code`.
+    fn function(code: &[u8]) -> Vec<u8> {
+        let mut body = vec![0];
+        body.extend_from_slice(code);
+        body.push(0x0b);
+        let mut bodies = vec![1, body.len() as u8];
+        bodies.extend(body);
+        module(&[
+            (1, &[1, 0x60, 0, 0]),
+            (3, &[1, 0]),
+            (5, &[1, 0, 1]),
+            (10, &bodies),
+        ])
+    }
+
+    fn error(wasm: &[u8], features: Features) -> String {
+        match validate(wasm, &features) {
+            Err(CompileError::ValidationError { msg, .. }) => msg,
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    /// The errors for `wasm` with and without `feature`, which it uses but
+    /// the runtime can't compile.
+    fn assert_unsupported(wasm: &[u8], enabled: Features, feature: &str) {
+        assert_eq!(error(wasm, Features::default()), disabled(feature));
+        assert_eq!(
+            error(wasm, enabled),
+            format!("feature `{}` isn't supported by this runtime yet", feature)
+        );
+    }
+
+    #[test]
+    fn mutable_globals_can_be_imported() {
+        let wasm = module(&[(2, &[1, 3, b'e', b'n', b'v', 1, b'g', 3, 0x7f, 1])]);
+        assert!(validate(&wasm, &Features::default()).is_ok());
+    }
+
+    #[test]
+    fn threads() {
+        let features = Features {
+            threads: true,
+            ..Features::default()
+        };
+        // i32.const 0, i32.atomic.load, drop
+        let wasm = function(&[0x41, 0, 0xfe, 0x10, 2, 0, 0x1a]);
+        assert_unsupported(&wasm, features, "threads");
+    }
+
+    #[test]
+    fn simd() {
+        let features = Features {
+            simd: true,
+            ..Features::default()
+        };
+        // i32.const 0, v128.load, drop
+        let wasm = function(&[0x41, 0, 0xfd, 0, 4, 0, 0x1a]);
+        assert_unsupported(&wasm, features, "simd");
+    }
+
+    #[test]
+    fn bulk_memory() {
+        let features = Features {
+            bulk_memory: true,
+            ..Features::default()
+        };
+        // i32.const 0, i32.const 0, i32.const 0, memory.fill
+        let wasm = function(&[0x41, 0, 0x41, 0, 0x41, 0, 0xfc, 0x0b, 0]);
+        assert_unsupported(&wasm, features, "bulk_memory");
+    }
+
+    #[test]
+    fn reference_types() {
+        let features = Features {
+            reference_types: true,
+            ..Features::default()
+        };
+        // ref.null, drop
+        let wasm = function(&[0xd0, 0x1a]);
+        assert_unsupported(&wasm, features, "reference_types");
+    }
+
+    #[test]
+    fn multi_value() {
+        let wasm = module(&[(1, &[1, 0x60, 0, 2, 0x7f, 0x7f])]);
+        assert_eq!(error(&wasm, Features::default()), disabled("multi_value"));
+        let features = Features {
+            multi_value: true,
+            ..Features::default()
+        };
+        assert!(validate(&wasm, &features).is_ok());
+    }
+
+    #[test]
+    fn sign_ext() {
+        // i32.const 0, i32.extend8_s, drop
+        let wasm = function(&[0x41, 0, 0xc0, 0x1a]);
+        assert_eq!(error(&wasm, Features::default()), disabled("sign_ext"));
+        let features = Features {
+            sign_ext: true,
+            ..Features::default()
+        };
+        assert!(validate(&wasm, &features).is_ok());
+    }
+}
@@ -1,5 +1,15 @@
-use crate::export::Export;
+use crate::{
+    export::{Context, Export},
+    global::Global,
+    memory::Memory,
+    module::{ImportName, Module},
+    table::Table,
+    typed_func::{Func, Unsafe, WasmTypeList},
+    types::{Type, Value},
+    vm::Ctx,
+};
 use hashbrown::{hash_map::Entry, HashMap};
+use std::{fmt, marker::PhantomData};
 
 pub trait LikeNamespace {
     fn get_export(&self, name: &str) -> Option<Export>;
@@ -79,8 +89,339 @@ impl ImportObject {
     pub fn get_namespace(&self, namespace: &str) -> Option<&(dyn LikeNamespace + 'static)> {
         self.map.get(namespace).map(|namespace| &**namespace)
     }
+
+    /// An `ImportObject` with a stub for every import of `module`, so any
+    /// module can be instantiated right away. The function stubs trap
+    /// with the name of the import when they are called.
+    ///
+    /// # Usage:
+    /// ```
+    /// # use wasmer_runtime_core::{Module, Instance, error::Result};
+    /// # use wasmer_runtime_core::import::ImportObject;
+    /// fn instantiate(module: &Module) -> Result<Instance> {
+    ///     module.instantiate(&ImportObject::stubbed_for(module))
+    /// }
+    /// ```
+    pub fn stubbed_for(module: &Module) -> Self {
+        let mut import_object = ImportObject::new();
+        import_object.stub_missing(module, Stub::Trap);
+        import_object
+    }
+
+    /// Add a stub for every import of `module` that this object doesn't
+    /// provide, so only the imports that matter need to be implemented.
+    ///
+    /// Imported memories and tables are created as the module declares
+    /// them and imported globals hold zero. The function stubs do what
+    /// `stub` says, and the trap stubs name the import that was called
+    /// for the first [`SLOTS`] imports of each return type.
+    ///
+    /// [`SLOTS`]: constant.SLOTS.html
+    pub fn stub_missing(&mut self, module: &Module, stub: Stub) {
+        let info = &module.0.info;
+        let mut stubs: HashMap<String, Namespace> = HashMap::new();
+        {
+            let mut add = |import_name: &ImportName, export: Export| {
+                let namespace = info.namespace_table.get(import_name.namespace_index);
+                let name = info.name_table.get(import_name.name_index);
+                let provided = self
+                    .get_namespace(namespace)
+                    .and_then(|provided| provided.get_export(name))
+                    .is_some();
+                if !provided {
+                    stubs
+                        .entry(namespace.to_string())
+                        .or_insert_with(Namespace::new)
+                        .insert(name, export);
+                }
+            };
+
+            // The trap stubs of each return type take the slots in order,
+            // so each import can be told apart.
+            let mut slots = [0; 5];
+            for (index, import_name) in info.imported_functions.iter() {
+                let sig_index = info.func_assoc[index.convert_up(&module.0)];
+                let signature = info.signatures[sig_index].clone();
+                let func = match signature.returns() {
+                    [] => function_stub::<()>(stub, &mut slots[0]),
+                    [Type::I32] => function_stub::<i32>(stub, &mut slots[1]),
+                    [Type::I64] => function_stub::<i64>(stub, &mut slots[2]),
+                    [Type::F32] => function_stub::<f32>(stub, &mut slots[3]),
+                    [Type::F64] => function_stub::<f64>(stub, &mut slots[4]),
+                    _ => continue,
+                };
+                // The stubs return what the import returns, but take no
+                // parameters other than the `Ctx`, which is passed first.
+                // The caller pops the arguments in the C calling convention,
+                // so a function that ignores them can stand in for any
+                // parameters.
+                if let Export::Function { func, .. } = func {
+                    let export = Export::Function {
+                        func,
+                        ctx: Context::Internal,
+                        signature,
+                    };
+                    add(import_name, export);
+                }
+            }
+            for (_, (import_name, desc)) in info.imported_memories.iter() {
+                if let Ok(memory) = Memory::new(*desc) {
+                    add(import_name, Export::Memory(memory));
+                }
+            }
+            for (_, (import_name, desc)) in info.imported_tables.iter() {
+                if let Ok(table) = Table::new(*desc) {
+                    add(import_name, Export::Table(table));
+                }
+            }
+            for (_, (import_name, desc)) in info.imported_globals.iter() {
+                let zero = match desc.ty {
+                    Type::I32 => Value::I32(0),
+                    Type::I64 => Value::I64(0),
+                    Type::F32 => Value::F32(0.0),
+                    Type::F64 => Value::F64(0.0),
+                };
+                let global = if desc.mutable {
+                    Global::new_mutable(zero)
+                } else {
+                    Global::new(zero)
+                };
+                add(import_name, Export::Global(global));
+            }
+        }
+
+        for (namespace, stubs) in stubs {
+            match self.map.remove(&namespace) {
+                Some(provided) => self.register(namespace, Stubbed { provided, stubs }),
+                None => self.register(namespace, stubs),
+            };
+        }
+    }
+}
+
+/// What the function stubs of `ImportObject::stub_missing` do when called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stub {
+    /// Trap with the name of the import.
+    Trap,
+    /// Return zero, or nothing if the function returns nothing.
+    Zero,
+}
+
+/// A namespace that was given stubs for the imports it lacked.
+struct Stubbed {
+    provided: Box<dyn LikeNamespace>,
+    stubs: Namespace,
+}
+
+impl LikeNamespace for Stubbed {
+    fn get_export(&self, name: &str) -> Option<Export> {
+        self.provided
+            .get_export(name)
+            .or_else(|| self.stubs.get_export(name))
+    }
+}
+
+/// What a function stub returns: nothing, or a single value.
+trait StubReturn: WasmTypeList + 'static {
+    fn zero() -> Self;
+}
+
+impl StubReturn for () {
+    fn zero() -> Self {}
+}
+
+impl StubReturn for i32 {
+    fn zero() -> Self {
+        0
+    }
+}
+
+impl StubReturn for i64 {
+    fn zero() -> Self {
+        0
+    }
+}
+
+impl StubReturn for f32 {
+    fn zero() -> Self {
+        0.0
+    }
 }
 
+impl StubReturn for f64 {
+    fn zero() -> Self {
+        0.0
+    }
+}
+
+/// The function stub returning `R`, taking the trap stub slot `slot`.
+fn function_stub<R: StubReturn>(stub: Stub, slot: &mut usize) -> Export {
+    match stub {
+        Stub::Zero => Func::<(), R, Unsafe>::new(zero::<R>).to_export(),
+        Stub::Trap => {
+            let export = slot_export::<TrapStub<R>>(*slot)
+                .unwrap_or_else(|| Func::<(), R, Unsafe>::new(trap_unnamed::<R>).to_export());
+            *slot += 1;
+            export
+        }
+    }
+}
+
+fn zero<R: StubReturn>(_ctx: &mut Ctx) -> R {
+    R::zero()
+}
+
+/// How a trap stub traps.
+struct StubCalled(Option<String>);
+
+impl fmt::Debug for StubCalled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.0 {
+            Some(import) => write!(f, "the stubbed import `{}` was called", import),
+            None => write!(f, "a stubbed import was called"),
+        }
+    }
+}
+
+struct TrapStub<R>(PhantomData<R>);
+
+impl<R: StubReturn> SlotFunc for TrapStub<R> {
+    fn export<S: Slot>() -> Export {
+        Func::<(), R, Unsafe>::new(trap::<R, S>).to_export()
+    }
+}
+
+fn trap<R: StubReturn, S: Slot>(ctx: &mut Ctx) -> Result<R, StubCalled> {
+    let import =
+        called_as::<TrapStub<R>, S>(ctx).map(|(namespace, name)| format!("{}.{}", namespace, name));
+    Err(StubCalled(import))
+}
+
+/// The trap stub of the imports past the last slot, which can't tell
+/// them apart.
+fn trap_unnamed<R: StubReturn>(_ctx: &mut Ctx) -> Result<R, StubCalled> {
+    Err(StubCalled(None))
+}
+
+/// How many slots a [`SlotFunc`] has.
+///
+/// [`SlotFunc`]: trait.SlotFunc.html
+pub const SLOTS: usize = 64;
+
+/// One of the slots a [`SlotFunc`] is generic over.
+///
+/// [`SlotFunc`]: trait.SlotFunc.html
+pub trait Slot {
+    const INDEX: usize;
+}
+
+/// A host function that stands in for imports it only knows by name.
+///
+/// Each slot is a distinct function, and [`called_as`] finds which import
+/// of the calling instance a slot was resolved to, so the imports of a
+/// module can be told apart as long as each one gets a slot of its own.
+/// Nothing is kept outside of the instance.
+///
+/// [`called_as`]: fn.called_as.html
+pub trait SlotFunc {
+    /// The function of the slot `S`.
+    fn export<S: Slot>() -> Export;
+}
+
+/// The namespace and the name the calling instance imports the slot `S`
+/// of `F` as.
+pub fn called_as<F: SlotFunc, S: Slot>(ctx: &Ctx) -> Option<(&str, &str)> {
+    ctx.import_name(&slot_export::<F>(S::INDEX)?)
+}
+
+macro_rules! slots {
+    ($($slot:ident = $index:expr),*) => {
+        $(
+            struct $slot;
+            impl Slot for $slot {
+                const INDEX: usize = $index;
+            }
+        )*
+
+        /// The function of the slot `index` of `F`, or `None` past the
+        /// last slot.
+        pub fn slot_export<F: SlotFunc>(index: usize) -> Option<Export> {
+            match index {
+                $($index => Some(F::export::<$slot>()),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+slots!(
+    S0 = 0,
+    S1 = 1,
+    S2 = 2,
+    S3 = 3,
+    S4 = 4,
+    S5 = 5,
+    S6 = 6,
+    S7 = 7,
+    S8 = 8,
+    S9 = 9,
+    S10 = 10,
+    S11 = 11,
+    S12 = 12,
+    S13 = 13,
+    S14 = 14,
+    S15 = 15,
+    S16 = 16,
+    S17 = 17,
+    S18 = 18,
+    S19 = 19,
+    S20 = 20,
+    S21 = 21,
+    S22 = 22,
+    S23 = 23,
+    S24 = 24,
+    S25 = 25,
+    S26 = 26,
+    S27 = 27,
+    S28 = 28,
+    S29 = 29,
+    S30 = 30,
+    S31 = 31,
+    S32 = 32,
+    S33 = 33,
+    S34 = 34,
+    S35 = 35,
+    S36 = 36,
+    S37 = 37,
+    S38 = 38,
+    S39 = 39,
+    S40 = 40,
+    S41 = 41,
+    S42 = 42,
+    S43 = 43,
+    S44 = 44,
+    S45 = 45,
+    S46 = 46,
+    S47 = 47,
+    S48 = 48,
+    S49 = 49,
+    S50 = 50,
+    S51 = 51,
+    S52 = 52,
+    S53 = 53,
+    S54 = 54,
+    S55 = 55,
+    S56 = 56,
+    S57 = 57,
+    S58 = 58,
+    S59 = 59,
+    S60 = 60,
+    S61 = 61,
+    S62 = 62,
+    S63 = 63
+);
+
 pub struct Namespace {
     map: HashMap<String, Box<dyn IsExport>>,
 }
@@ -2,17 +2,29 @@ use crate::{
     backend::Token,
     backing::{ImportBacking, LocalBacking},
     error::{CallError, CallResult, ResolveError, ResolveResult, Result},
+    events,
     export::{Context, Export, ExportIter, FuncPointer},
     global::Global,
     import::{ImportObject, LikeNamespace},
-    memory::Memory,
+    memory::{Memory, PageBudget},
     module::{ExportIndex, Module, ModuleInner},
     table::Table,
-    typed_func::{Func, Safe, WasmTypeList},
+    typed_func::{ensure_early_trapper, Func, Safe, WasmTypeList},
     types::{FuncIndex, FuncSig, GlobalIndex, LocalOrImport, MemoryIndex, TableIndex, Value},
+    units::{Bytes, Pages},
     vm,
 };
-use std::{mem, sync::Arc};
+use std::{
+    fs::{self, OpenOptions},
+    io, mem,
+    path::Path,
+    process, ptr,
+    sync::atomic::{AtomicUsize, Ordering},
+    sync::Arc,
+};
+
+/// Numbers the files of parked memories, to keep their names apart.
+static PARKED_FILES: AtomicUsize = AtomicUsize::new(0);
 
 pub(crate) struct InstanceInner {
     #[allow(dead_code)]
@@ -21,8 +33,25 @@ pub(crate) struct InstanceInner {
     pub(crate) vmctx: *mut vm::Ctx,
 }
 
+impl InstanceInner {
+    /// The pages of the local memories.
+    fn local_pages(&self) -> Pages {
+        Pages(
+            self.backing
+                .memories
+                .iter()
+                .map(|(_, memory)| memory.size().0)
+                .sum(),
+        )
+    }
+}
+
 impl Drop for InstanceInner {
     fn drop(&mut self) {
+        let vmctx = unsafe { &*self.vmctx };
+        if let Some(budget) = &vmctx.page_budget {
+            budget.give_back(vmctx.pages_taken);
+        }
         // Drop the vmctx.
         unsafe { Box::from_raw(self.vmctx) };
     }
@@ -45,7 +74,11 @@ impl Instance {
         // We need the backing and import_backing to create a vm::Ctx, but we need
         // a vm::Ctx to create a backing and an import_backing. The solution is to create an
         // uninitialized vm::Ctx and then initialize it in-place.
-        let mut vmctx = unsafe { Box::new(mem::uninitialized()) };
+        //
+        // It's zeroed rather than uninitialized because it's dropped if
+        // the imports don't resolve, and a zeroed one has no data or data
+        // finalizer to drop.
+        let mut vmctx = unsafe { Box::new(mem::zeroed()) };
 
         let import_backing = ImportBacking::new(&module, &imports, &mut *vmctx)?;
         let backing = LocalBacking::new(&module, &import_backing, &mut *vmctx);
@@ -58,12 +91,16 @@ impl Instance {
         });
 
         // Initialize the vm::Ctx in-place after the backing
-        // has been boxed.
+        // has been boxed, without dropping the placeholder.
         unsafe {
-            *inner.vmctx = vm::Ctx::new(&mut inner.backing, &mut inner.import_backing, &module)
+            ptr::write(
+                inner.vmctx,
+                vm::Ctx::new(&mut inner.backing, &mut inner.import_backing, &module),
+            )
         };
 
         let instance = Instance { module, inner };
+        events::instantiated(&instance.module.info);
 
         if let Some(start_index) = instance.module.info.start_func {
             instance.call_with_index(start_index, &[])?;
@@ -269,6 +306,51 @@ impl Instance {
     pub fn module(&self) -> Module {
         Module::new(Arc::clone(&self.module))
     }
+
+    /// Take the pages of the local memories of this instance from
+    /// `budget`, which `memory.grow` then takes the new pages from, and
+    /// fails once there aren't enough left. The pages are given back when
+    /// the instance is dropped. Growing a memory from the host with
+    /// [`Memory::grow`] doesn't take pages from the budget. Fails if
+    /// `budget` doesn't have the pages the instance already has, or if it
+    /// already has a budget.
+    ///
+    /// [`Memory::grow`]: memory/struct.Memory.html#method.grow
+    pub fn set_page_budget(&mut self, budget: Arc<PageBudget>) -> bool {
+        let pages = self.inner.local_pages();
+        let vmctx = unsafe { &mut *self.inner.vmctx };
+        if vmctx.page_budget.is_some() || !budget.take(pages) {
+            return false;
+        }
+        vmctx.page_budget = Some(budget);
+        vmctx.pages_taken = pages;
+        true
+    }
+
+    /// Park the memories of this instance with [`Memory::park`], in files
+    /// created in `dir` and unlinked right away, so the disk space they
+    /// take is given back once the memories are dropped. Imported memories
+    /// are left to their owner. Returns how many bytes were written.
+    ///
+    /// [`Memory::park`]: memory/struct.Memory.html#method.park
+    pub fn park(&self, dir: &Path) -> io::Result<Bytes> {
+        let mut written = Bytes(0);
+        for (_, memory) in self.inner.backing.memories.iter() {
+            let path = dir.join(format!(
+                "wasmer-{}-{}.parked",
+                process::id(),
+                PARKED_FILES.fetch_add(1, Ordering::Relaxed)
+            ));
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(&path)?;
+            fs::remove_file(&path)?;
+            written = written + memory.park(&file)?;
+        }
+        Ok(written)
+    }
 }
 
 impl Instance {
@@ -295,16 +377,19 @@ impl Instance {
             }
         };
 
+        ensure_early_trapper(&self.module);
         let token = Token::generate();
 
-        let returns = self.module.protected_caller.call(
-            &self.module,
-            func_index,
-            args,
-            &self.inner.import_backing,
-            vmctx,
-            token,
-        )?;
+        let returns = events::observe_call(&self.module.info, func_index, || {
+            self.module.protected_caller.call(
+                &self.module,
+                func_index,
+                args,
+                &self.inner.import_backing,
+                vmctx,
+                token,
+            )
+        })?;
 
         Ok(returns)
     }
@@ -469,16 +554,19 @@ impl<'a> DynFunc<'a> {
             }
         };
 
+        ensure_early_trapper(&self.module);
         let token = Token::generate();
 
-        let returns = self.module.protected_caller.call(
-            &self.module,
-            self.func_index,
-            params,
-            &self.instance_inner.import_backing,
-            vmctx,
-            token,
-        )?;
+        let returns = events::observe_call(&self.module.info, self.func_index, || {
+            self.module.protected_caller.call(
+                &self.module,
+                self.func_index,
+                params,
+                &self.instance_inner.import_backing,
+                vmctx,
+                token,
+            )
+        })?;
 
         Ok(returns)
     }
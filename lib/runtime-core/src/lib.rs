@@ -8,22 +8,31 @@ extern crate serde_derive;
 
 #[macro_use]
 mod macros;
+pub mod adapter;
+pub mod analysis;
 #[doc(hidden)]
 pub mod backend;
 mod backing;
 #[cfg(feature = "cache")]
 pub mod cache;
+pub mod coredump;
+pub mod disasm;
 pub mod error;
+pub mod events;
 pub mod export;
+pub mod features;
 pub mod global;
 pub mod import;
 pub mod instance;
+pub mod linker;
 pub mod memory;
 pub mod module;
 mod sig_registry;
+mod streaming;
 pub mod structures;
 mod sys;
 pub mod table;
+pub mod transform;
 mod typed_func;
 pub mod types;
 pub mod units;
@@ -65,27 +74,48 @@ pub mod prelude {
 pub fn compile_with(
     wasm: &[u8],
     compiler: &dyn backend::Compiler,
+) -> CompileResult<module::Module> {
+    compile_with_config(wasm, compiler, backend::CompilerConfig::default())
+}
+
+/// The same as [`compile_with`], but lets the caller
+/// configure how the module is compiled, for example
+/// the layout of its memories.
+///
+/// [`compile_with`]: fn.compile_with.html
+pub fn compile_with_config(
+    wasm: &[u8],
+    compiler: &dyn backend::Compiler,
+    config: backend::CompilerConfig,
 ) -> CompileResult<module::Module> {
     let token = backend::Token::generate();
-    compiler
-        .compile(wasm, token)
-        .map(|inner| module::Module::new(Arc::new(inner)))
+    let inner = compiler.compile_with_config(wasm, config, token)?;
+    events::compiled(&inner.info);
+    Ok(module::Module::new(Arc::new(inner)))
+}
+
+/// The same as [`compile_with_config`], but reads the binary code
+/// from `reader`, e.g. a file or a socket, as it arrives.
+///
+/// Each section is checked as soon as it has been read, so a module
+/// that isn't WebAssembly or is truncated fails before the rest of it
+/// is downloaded. Compilation starts once the last section is in.
+///
+/// [`compile_with_config`]: fn.compile_with_config.html
+pub fn compile_streaming_with<R: std::io::Read>(
+    reader: R,
+    compiler: &dyn backend::Compiler,
+    config: backend::CompilerConfig,
+) -> CompileResult<module::Module> {
+    let wasm = streaming::read_sections(reader)?;
+    compile_with_config(&wasm, compiler, config)
 }
 
 /// Perform validation as defined by the
 /// WebAssembly specification. Returns `true` if validation
 /// succeeded, `false` if validation failed.
 pub fn validate(wasm: &[u8]) -> bool {
-    use wasmparser::WasmDecoder;
-    let mut parser = wasmparser::ValidatingParser::new(wasm, None);
-    loop {
-        let state = parser.read();
-        match *state {
-            wasmparser::ParserState::EndWasm => break true,
-            wasmparser::ParserState::Error(_) => break false,
-            _ => {}
-        }
-    }
+    features::validate(wasm, &features::Features::default()).is_ok()
 }
 
 #[cfg(feature = "cache")]
@@ -111,5 +141,21 @@ pub unsafe fn load_cache_with(
         .map(|inner| module::Module::new(Arc::new(inner)))
 }
 
+/// The same as [`load_cache_with`], but runs the code hook of `config`
+/// over the code loaded from the cache.
+///
+/// [`load_cache_with`]: fn.load_cache_with.html
+#[cfg(feature = "cache")]
+pub unsafe fn load_cache_with_config(
+    cache: Cache,
+    compiler: &dyn backend::Compiler,
+    config: &backend::CompilerConfig,
+) -> std::result::Result<module::Module, CacheError> {
+    let token = backend::Token::generate();
+    compiler
+        .from_cache_with_config(cache, config, token)
+        .map(|inner| module::Module::new(Arc::new(inner)))
+}
+
 /// The current version of this crate
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
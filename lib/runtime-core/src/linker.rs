@@ -0,0 +1,131 @@
+use crate::{
+    error::Result,
+    export::Export,
+    import::{ImportObject, IsExport, Namespace},
+    instance::Instance,
+    module::Module,
+};
+use hashbrown::HashMap;
+
+/// Wires multiple modules together, so that a module can import
+/// the exports of the modules instantiated before it.
+///
+/// Definitions live in namespaces, just like in an [`ImportObject`].
+/// A later definition of the same name shadows the earlier one.
+///
+/// The linker owns the instances whose exports it defines, because
+/// the modules that import from an instance call into it directly.
+///
+/// [`ImportObject`]: ../import/struct.ImportObject.html
+///
+/// # Usage:
+/// ```
+/// # use wasmer_runtime_core::{func, Module, error::Result};
+/// # use wasmer_runtime_core::linker::Linker;
+/// # use wasmer_runtime_core::vm::Ctx;
+/// fn link(runtime: Module, plugin: Module) -> Result<()> {
+///     let mut linker = Linker::new();
+///     linker.define("env", "log", func!(log));
+///
+///     // The plugin imports the exports of the runtime as "runtime".
+///     linker.instantiate_as("runtime", &runtime)?;
+///     let plugin = linker.instantiate(&plugin)?;
+///     plugin.call("main", &[])?;
+///     Ok(())
+/// }
+///
+/// fn log(_: &mut Ctx, value: i32) {
+///     println!("{}", value);
+/// }
+/// ```
+pub struct Linker {
+    namespaces: HashMap<String, HashMap<String, Export>>,
+    instances: Vec<Instance>,
+}
+
+impl Linker {
+    /// Create an empty `Linker`.
+    pub fn new() -> Self {
+        Self {
+            namespaces: HashMap::new(),
+            instances: Vec::new(),
+        }
+    }
+
+    /// Define `name` in `namespace`, returning the definition it shadows.
+    pub fn define<S, N, E>(&mut self, namespace: S, name: N, export: E) -> Option<Export>
+    where
+        S: Into<String>,
+        N: Into<String>,
+        E: IsExport,
+    {
+        self.namespaces
+            .entry(namespace.into())
+            .or_insert_with(HashMap::new)
+            .insert(name.into(), export.to_export())
+    }
+
+    /// Define every export of `instance` in `namespace`.
+    pub fn define_instance<S>(&mut self, namespace: S, mut instance: Instance) -> &mut Instance
+    where
+        S: Into<String>,
+    {
+        let definitions = self
+            .namespaces
+            .entry(namespace.into())
+            .or_insert_with(HashMap::new);
+        definitions.extend(instance.exports());
+
+        self.instances.push(instance);
+        self.instances.last_mut().unwrap()
+    }
+
+    /// Look up the definition of `name` in `namespace`.
+    pub fn get(&self, namespace: &str, name: &str) -> Option<Export> {
+        self.namespaces.get(namespace)?.get(name).cloned()
+    }
+
+    /// Instantiate `module` with the current definitions as its imports.
+    ///
+    /// The instance is returned to the caller, so it must outlive any
+    /// module that later imports from it.
+    pub fn instantiate(&self, module: &Module) -> Result<Instance> {
+        module.instantiate(&self.import_object())
+    }
+
+    /// Instantiate `module` and define its exports in `namespace`, so
+    /// modules instantiated after it can import them.
+    pub fn instantiate_as<S>(&mut self, namespace: S, module: &Module) -> Result<&mut Instance>
+    where
+        S: Into<String>,
+    {
+        let instance = self.instantiate(module)?;
+        Ok(self.define_instance(namespace, instance))
+    }
+
+    /// The instances owned by this linker, in the order they were defined.
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    /// Build an [`ImportObject`] out of the current definitions.
+    ///
+    /// [`ImportObject`]: ../import/struct.ImportObject.html
+    pub fn import_object(&self) -> ImportObject {
+        let mut import_object = ImportObject::new();
+        for (namespace_name, definitions) in &self.namespaces {
+            let mut namespace = Namespace::new();
+            for (name, export) in definitions {
+                namespace.insert(name.clone(), export.clone());
+            }
+            import_object.register(namespace_name.clone(), namespace);
+        }
+        import_object
+    }
+}
+
+impl Default for Linker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,105 @@
+//! Comparing snapshots of linear memory, e.g. to find where two runs that
+//! should be deterministic diverged.
+//!
+//! A snapshot is the contents of a memory as bytes, like the memories of
+//! a `CoreDump` or `Memory::snapshot`. The hashes are FNV-1a, which only
+//! depends on the bytes, so they can be compared across hosts.
+use std::ops::Range;
+
+const PAGE_SIZE: usize = 65_536;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// The hash of a whole snapshot.
+pub fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET_BASIS, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// The hash of each wasm page of a snapshot, the last one possibly
+/// partial.
+pub fn page_hashes(bytes: &[u8]) -> Vec<u64> {
+    bytes.chunks(PAGE_SIZE).map(hash).collect()
+}
+
+/// The differences between two snapshots within a wasm page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageDiff {
+    pub page: u32,
+    /// The byte ranges that differ, as offsets into the memory. The bytes
+    /// only one of the snapshots has are different.
+    pub ranges: Vec<Range<usize>>,
+}
+
+/// The pages that differ between `old` and `new`, in order.
+pub fn diff(old: &[u8], new: &[u8]) -> Vec<PageDiff> {
+    let len = old.len().max(new.len());
+    let mut diffs = Vec::new();
+    for start in (0..len).step_by(PAGE_SIZE) {
+        let end = (start + PAGE_SIZE).min(len);
+        let old_page = old.get(start..end.min(old.len())).unwrap_or(&[]);
+        let new_page = new.get(start..end.min(new.len())).unwrap_or(&[]);
+        if old_page == new_page {
+            continue;
+        }
+
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for offset in start..end {
+            if old.get(offset) == new.get(offset) {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(range) if range.end == offset => range.end += 1,
+                _ => ranges.push(offset..offset + 1),
+            }
+        }
+        diffs.push(PageDiff {
+            page: (start / PAGE_SIZE) as u32,
+            ranges,
+        });
+    }
+    diffs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, page_hashes, PageDiff, PAGE_SIZE};
+
+    #[test]
+    fn reports_the_changed_ranges_of_each_page() {
+        let old = vec![0u8; PAGE_SIZE * 2];
+        let mut new = old.clone();
+        new[3] = 1;
+        new[4] = 1;
+        new[PAGE_SIZE + 7] = 1;
+        new.extend_from_slice(&[0, 0]);
+
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                PageDiff {
+                    page: 0,
+                    ranges: vec![3..5],
+                },
+                PageDiff {
+                    page: 1,
+                    ranges: vec![PAGE_SIZE + 7..PAGE_SIZE + 8],
+                },
+                PageDiff {
+                    page: 2,
+                    ranges: vec![PAGE_SIZE * 2..PAGE_SIZE * 2 + 2],
+                },
+            ]
+        );
+
+        let (old_hashes, new_hashes) = (page_hashes(&old), page_hashes(&new));
+        assert_eq!(new_hashes.len(), 3);
+        assert_ne!(old_hashes[0], new_hashes[0]);
+        assert_eq!(
+            page_hashes(&old[..PAGE_SIZE]),
+            page_hashes(&old[PAGE_SIZE..])
+        );
+    }
+}
@@ -1,10 +1,13 @@
 use crate::{
     error::CreationError,
+    events,
+    memory::HostMemory,
     sys,
     types::MemoryDescriptor,
     units::{Bytes, Pages},
     vm,
 };
+use std::{fs::File, slice};
 
 pub const DYNAMIC_GUARD_SIZE: usize = 4096;
 
@@ -21,20 +24,40 @@ pub const DYNAMIC_GUARD_SIZE: usize = 4096;
 /// While, a dynamic memory could use a vector of some sort as its
 /// backing memory, we use mmap (or the platform-equivalent) to allow
 /// us to add a guard-page at the end to help elide some bounds-checks.
+///
+/// A dynamic memory can also be backed by memory that the embedder
+/// owns, in which case there is no guard-page and growing is delegated
+/// to the embedder.
 pub struct DynamicMemory {
-    memory: sys::Memory,
+    memory: Backing,
     current: Pages,
     max: Option<Pages>,
+    guard_size: usize,
+}
+
+enum Backing {
+    Owned(sys::Memory),
+    Host(Box<dyn HostMemory>),
+}
+
+impl Backing {
+    fn as_ptr(&self) -> *mut u8 {
+        match self {
+            Backing::Owned(memory) => memory.as_ptr(),
+            Backing::Host(host) => host.as_ptr(),
+        }
+    }
 }
 
 impl DynamicMemory {
     pub(super) fn new(
         desc: MemoryDescriptor,
+        guard_size: usize,
         local: &mut vm::LocalMemory,
     ) -> Result<Box<Self>, CreationError> {
         let min_bytes: Bytes = desc.minimum.into();
         let memory = {
-            let mut memory = sys::Memory::with_size(min_bytes.0 + DYNAMIC_GUARD_SIZE)
+            let mut memory = sys::Memory::with_size(min_bytes.0 + guard_size)
                 .map_err(|_| CreationError::UnableToCreateMemory)?;
             if desc.minimum != Pages(0) {
                 unsafe {
@@ -47,18 +70,55 @@ impl DynamicMemory {
             memory
         };
 
+        Ok(Self::with_backing(
+            desc,
+            Backing::Owned(memory),
+            guard_size,
+            local,
+        ))
+    }
+
+    pub(super) fn from_host(
+        desc: MemoryDescriptor,
+        host: Box<dyn HostMemory>,
+        local: &mut vm::LocalMemory,
+    ) -> Result<Box<Self>, CreationError> {
+        let min_bytes: Bytes = desc.minimum.into();
+        if host.size() < min_bytes.0 {
+            return Err(CreationError::InvalidDescriptor(format!(
+                "host memory of {} bytes is smaller than the minimum of {} bytes",
+                host.size(),
+                min_bytes.0
+            )));
+        }
+        if host.as_ptr() as usize % 8 != 0 {
+            return Err(CreationError::InvalidDescriptor(
+                "host memory is not aligned to 8 bytes".to_string(),
+            ));
+        }
+
+        Ok(Self::with_backing(desc, Backing::Host(host), 0, local))
+    }
+
+    fn with_backing(
+        desc: MemoryDescriptor,
+        memory: Backing,
+        guard_size: usize,
+        local: &mut vm::LocalMemory,
+    ) -> Box<Self> {
         let mut storage = Box::new(DynamicMemory {
             memory,
             current: desc.minimum,
             max: desc.maximum,
+            guard_size,
         });
         let storage_ptr: *mut DynamicMemory = &mut *storage;
 
         local.base = storage.memory.as_ptr();
-        local.bound = min_bytes.0;
+        local.bound = desc.minimum.bytes().0;
         local.memory = storage_ptr as *mut ();
 
-        Ok(storage)
+        storage
     }
 
     pub fn size(&self) -> Pages {
@@ -78,33 +138,52 @@ impl DynamicMemory {
             }
         }
 
-        let mut new_memory =
-            sys::Memory::with_size(new_pages.bytes().0 + DYNAMIC_GUARD_SIZE).ok()?;
+        match &mut self.memory {
+            Backing::Owned(memory) => {
+                let mut new_memory =
+                    sys::Memory::with_size(new_pages.bytes().0 + self.guard_size).ok()?;
 
-        unsafe {
-            new_memory
-                .protect(0..new_pages.bytes().0, sys::Protect::ReadWrite)
-                .ok()?;
+                unsafe {
+                    new_memory
+                        .protect(0..new_pages.bytes().0, sys::Protect::ReadWrite)
+                        .ok()?;
 
-            new_memory.as_slice_mut()[..self.current.bytes().0]
-                .copy_from_slice(&self.memory.as_slice()[..self.current.bytes().0]);
-        }
+                    new_memory.as_slice_mut()[..self.current.bytes().0]
+                        .copy_from_slice(&memory.as_slice()[..self.current.bytes().0]);
+                }
 
-        self.memory = new_memory; //The old memory gets dropped.
+                *memory = new_memory; //The old memory gets dropped.
+            }
+            Backing::Host(host) => {
+                // The host may have handed over more than the minimum up-front.
+                let new_size = new_pages.bytes().0;
+                if host.size() < new_size && (!host.grow(new_size) || host.size() < new_size) {
+                    return None;
+                }
+            }
+        }
 
         local.base = self.memory.as_ptr();
         local.bound = new_pages.bytes().0;
 
         let old_pages = self.current;
         self.current = new_pages;
+        events::memory_grew(old_pages, new_pages);
         Some(old_pages)
     }
 
+    pub fn park(&mut self, file: &File) -> Result<usize, String> {
+        match &mut self.memory {
+            Backing::Owned(memory) => unsafe { memory.park(self.current.bytes().0, file) },
+            Backing::Host(_) => Err("the memory belongs to the host".to_string()),
+        }
+    }
+
     pub fn as_slice(&self) -> &[u8] {
-        unsafe { &self.memory.as_slice()[0..self.current.bytes().0] }
+        unsafe { slice::from_raw_parts(self.memory.as_ptr(), self.current.bytes().0) }
     }
 
     pub fn as_slice_mut(&mut self) -> &mut [u8] {
-        unsafe { &mut self.memory.as_slice_mut()[0..self.current.bytes().0] }
+        unsafe { slice::from_raw_parts_mut(self.memory.as_ptr(), self.current.bytes().0) }
     }
 }
@@ -0,0 +1,24 @@
+/// Memory owned by the embedder that backs a wasm linear memory.
+///
+/// This lets a host application share a region, such as a frame
+/// buffer or a packet ring, with the guest without copying it.
+///
+/// # Safety
+///
+/// `as_ptr` must point to at least `size` bytes that are readable and
+/// writable, aligned to at least 8 bytes, and that stay valid until
+/// the value is dropped or `grow` is called.
+pub unsafe trait HostMemory {
+    /// The start of the region.
+    fn as_ptr(&self) -> *mut u8;
+
+    /// The number of bytes available at `as_ptr`.
+    fn size(&self) -> usize;
+
+    /// Makes at least `new_size` bytes available, possibly moving the
+    /// region and preserving its contents. Returns `false` if the region
+    /// can't grow, which is the default.
+    fn grow(&mut self, _new_size: usize) -> bool {
+        false
+    }
+}
@@ -5,22 +5,28 @@ use crate::{
     memory::dynamic::DYNAMIC_GUARD_SIZE,
     memory::static_::{SAFE_STATIC_GUARD_SIZE, SAFE_STATIC_HEAP_SIZE},
     types::{MemoryDescriptor, ValueType},
-    units::Pages,
+    units::{Bytes, Pages},
     vm,
 };
 use std::{
     cell::{Cell, RefCell},
-    fmt, mem, ptr,
+    fmt,
+    fs::File,
+    io, mem, ptr,
     rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
 pub use self::atomic::Atomic;
 pub use self::dynamic::DynamicMemory;
+pub use self::host::HostMemory;
 pub use self::static_::{SharedStaticMemory, StaticMemory};
 pub use self::view::{Atomically, MemoryView};
 
 mod atomic;
+pub mod diff;
 mod dynamic;
+mod host;
 mod static_;
 mod view;
 
@@ -36,6 +42,7 @@ enum MemoryVariant {
 #[derive(Clone)]
 pub struct Memory {
     desc: MemoryDescriptor,
+    config: MemoryConfig,
     variant: MemoryVariant,
 }
 
@@ -63,6 +70,21 @@ impl Memory {
     /// # }
     /// ```
     pub fn new(desc: MemoryDescriptor) -> Result<Self, CreationError> {
+        Self::with_config(desc, MemoryConfig::default())
+    }
+
+    /// Create a new `Memory` from a [`MemoryDescriptor`], placing it
+    /// in the host address space as described by a [`MemoryConfig`].
+    ///
+    /// A memory that is imported by a module must be created with a
+    /// configuration compatible with the one the module was compiled with.
+    ///
+    /// [`MemoryDescriptor`]: struct.MemoryDescriptor.html
+    /// [`MemoryConfig`]: struct.MemoryConfig.html
+    pub fn with_config(
+        desc: MemoryDescriptor,
+        config: MemoryConfig,
+    ) -> Result<Self, CreationError> {
         if let Some(max) = desc.maximum {
             if max < desc.minimum {
                 return Err(CreationError::InvalidDescriptor(
@@ -73,12 +95,50 @@ impl Memory {
         }
 
         let variant = if !desc.shared {
-            MemoryVariant::Unshared(UnsharedMemory::new(desc)?)
+            MemoryVariant::Unshared(UnsharedMemory::new(desc, config)?)
         } else {
             MemoryVariant::Shared(SharedMemory::new(desc)?)
         };
 
-        Ok(Memory { desc, variant })
+        Ok(Memory {
+            desc,
+            config,
+            variant,
+        })
+    }
+
+    /// Create a `Memory` over memory owned by the embedder, so the
+    /// host and the guest can share it without copying.
+    ///
+    /// Nothing guards the end of host memory, so modules that import it
+    /// have to be compiled with [`MemoryConfig::bounds_checked`]. Growing
+    /// the memory is delegated to [`HostMemory::grow`].
+    ///
+    /// [`MemoryConfig::bounds_checked`]: struct.MemoryConfig.html#method.bounds_checked
+    /// [`HostMemory::grow`]: trait.HostMemory.html#method.grow
+    pub fn from_host(
+        desc: MemoryDescriptor,
+        host: Box<dyn HostMemory>,
+    ) -> Result<Self, CreationError> {
+        if desc.shared {
+            return Err(CreationError::InvalidDescriptor(
+                "Host memory can't be shared".to_string(),
+            ));
+        }
+        if let Some(max) = desc.maximum {
+            if max < desc.minimum {
+                return Err(CreationError::InvalidDescriptor(
+                    "Max number of memory pages is less than the minimum number of pages"
+                        .to_string(),
+                ));
+            }
+        }
+
+        Ok(Memory {
+            desc,
+            config: MemoryConfig::bounds_checked(),
+            variant: MemoryVariant::Unshared(UnsharedMemory::from_host(desc, host)?),
+        })
     }
 
     /// Return the [`MemoryDescriptor`] that this memory
@@ -89,6 +149,19 @@ impl Memory {
         self.desc
     }
 
+    /// Return the [`MemoryConfig`] that this memory
+    /// was created with.
+    ///
+    /// [`MemoryConfig`]: struct.MemoryConfig.html
+    pub fn config(&self) -> MemoryConfig {
+        self.config
+    }
+
+    /// Whether this memory is placed statically or dynamically.
+    pub fn memory_type(&self) -> MemoryType {
+        self.config.memory_type(self.desc)
+    }
+
     /// Grow this memory by the specfied number of pages.
     pub fn grow(&self, delta: Pages) -> Option<Pages> {
         match &self.variant {
@@ -143,7 +216,42 @@ impl Memory {
 
         let length = self.size().bytes().0 / mem::size_of::<T>();
 
-        unsafe { MemoryView::new(base as _, length as u32) }
+        // A full 4GiB memory has more bytes than fit in a u32.
+        unsafe { MemoryView::new(base as _, length) }
+    }
+
+    /// A copy of the contents of this memory, e.g. to compare it with
+    /// the functions of [`diff`].
+    ///
+    /// [`diff`]: diff/index.html
+    pub fn snapshot(&self) -> Vec<u8> {
+        self.view::<u8>().iter().map(Cell::get).collect()
+    }
+
+    /// Write the contents of this memory to `file` and map the file in
+    /// their place, so the host can reclaim the memory of an idle instance
+    /// and page it back in, from the file, as the instance touches it
+    /// again. Nothing changes for the instance, and the pages it writes
+    /// to after that are its own again.
+    ///
+    /// `file` has to stay as it was written for as long as the memory
+    /// lives, so it's best unlinked, like the ones of [`Instance::park`].
+    /// Pages of zeros are left as holes in the file. Returns how many
+    /// bytes were written.
+    ///
+    /// This is only supported on unix, and not for memories created with
+    /// [`Memory::from_host`].
+    ///
+    /// [`Instance::park`]: ../struct.Instance.html#method.park
+    /// [`Memory::from_host`]: struct.Memory.html#method.from_host
+    pub fn park(&self, file: &File) -> io::Result<Bytes> {
+        let written = match &self.variant {
+            MemoryVariant::Unshared(unshared_mem) => unshared_mem.park(file),
+            MemoryVariant::Shared(_) => Err("shared memories can't be parked".to_string()),
+        };
+        written
+            .map(Bytes)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
     }
 
     /// Convert this memory to a shared memory if the shared flag
@@ -179,6 +287,60 @@ impl fmt::Debug for Memory {
     }
 }
 
+/// Pages shared by the memories of several instances, which together
+/// can't have more than the maximum: `memory.grow` fails instead.
+///
+/// Instances take their pages from a budget with
+/// [`Instance::set_page_budget`], and give them back when they're dropped.
+///
+/// [`Instance::set_page_budget`]: ../struct.Instance.html#method.set_page_budget
+#[derive(Debug)]
+pub struct PageBudget {
+    max: Pages,
+    used: AtomicUsize,
+}
+
+impl PageBudget {
+    pub fn new(max: Pages) -> Self {
+        PageBudget {
+            max,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn max(&self) -> Pages {
+        self.max
+    }
+
+    /// The pages that were taken and not given back.
+    pub fn used(&self) -> Pages {
+        Pages(self.used.load(Ordering::SeqCst) as u32)
+    }
+
+    /// Take `pages` from the budget, unless there are fewer left.
+    pub fn take(&self, pages: Pages) -> bool {
+        let mut used = self.used.load(Ordering::SeqCst);
+        loop {
+            let taken = used + pages.0 as usize;
+            if taken > self.max.0 as usize {
+                return false;
+            }
+            match self
+                .used
+                .compare_exchange(used, taken, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => return true,
+                Err(current) => used = current,
+            }
+        }
+    }
+
+    /// Give back `pages` that were taken.
+    pub fn give_back(&self, pages: Pages) {
+        self.used.fetch_sub(pages.0 as usize, Ordering::SeqCst);
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MemoryType {
     Dynamic,
@@ -189,19 +351,106 @@ pub enum MemoryType {
 impl MemoryType {
     #[doc(hidden)]
     pub fn guard_size(self) -> u64 {
-        match self {
-            MemoryType::Dynamic => DYNAMIC_GUARD_SIZE as u64,
-            MemoryType::Static | MemoryType::SharedStatic => SAFE_STATIC_GUARD_SIZE as u64,
-        }
+        MemoryConfig::default().guard_size(self)
     }
 
     #[doc(hidden)]
     pub fn bounds(self) -> Option<u64> {
-        match self {
+        MemoryConfig::default().bounds(self)
+    }
+}
+
+/// Where unshared memories are placed in the host address space.
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryPlacement {
+    /// Memories that declare a maximum are static, the rest are dynamic.
+    Auto,
+    /// Every memory reserves its whole addressable range up-front.
+    Static,
+    /// Every memory only maps its current size and moves when it grows.
+    Dynamic,
+}
+
+/// Controls how linear memories are laid out in the host address space.
+///
+/// Backends generate code against these settings, so the memories that a
+/// module creates or imports have to follow the configuration the module
+/// was compiled with.
+///
+/// The defaults reserve 4GiB plus a 2GiB guard region for static memories,
+/// which lets every 32-bit access skip its bounds-check.
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryConfig {
+    pub placement: MemoryPlacement,
+    /// The number of bytes reserved for a static memory, which is also
+    /// the most it can ever grow to.
+    pub static_bound: u64,
+    /// The size of the inaccessible region after a static memory.
+    pub static_guard_size: u64,
+    /// The size of the inaccessible region after a dynamic memory.
+    pub dynamic_guard_size: u64,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        MemoryConfig {
+            placement: MemoryPlacement::Auto,
+            static_bound: SAFE_STATIC_HEAP_SIZE as u64,
+            static_guard_size: SAFE_STATIC_GUARD_SIZE as u64,
+            dynamic_guard_size: DYNAMIC_GUARD_SIZE as u64,
+        }
+    }
+}
+
+impl MemoryConfig {
+    /// Every memory is dynamic and has no guard region, so every
+    /// access is bounds-checked. This is the configuration of
+    /// memories created with [`Memory::from_host`].
+    ///
+    /// [`Memory::from_host`]: struct.Memory.html#method.from_host
+    pub fn bounds_checked() -> Self {
+        MemoryConfig {
+            placement: MemoryPlacement::Dynamic,
+            dynamic_guard_size: 0,
+            ..MemoryConfig::default()
+        }
+    }
+
+    pub fn memory_type(&self, desc: MemoryDescriptor) -> MemoryType {
+        if desc.shared {
+            return desc.memory_type();
+        }
+        match self.placement {
+            MemoryPlacement::Auto => desc.memory_type(),
+            MemoryPlacement::Static => MemoryType::Static,
+            MemoryPlacement::Dynamic => MemoryType::Dynamic,
+        }
+    }
+
+    pub fn guard_size(&self, memory_type: MemoryType) -> u64 {
+        match memory_type {
+            MemoryType::Dynamic => self.dynamic_guard_size,
+            MemoryType::Static | MemoryType::SharedStatic => self.static_guard_size,
+        }
+    }
+
+    pub fn bounds(&self, memory_type: MemoryType) -> Option<u64> {
+        match memory_type {
             MemoryType::Dynamic => None,
-            MemoryType::Static | MemoryType::SharedStatic => Some(SAFE_STATIC_HEAP_SIZE as u64),
+            MemoryType::Static | MemoryType::SharedStatic => Some(self.static_bound),
         }
     }
+
+    /// Whether code compiled against `self` may access a memory of
+    /// `desc` created with `found` without additional bounds-checks.
+    pub(crate) fn accepts(&self, desc: MemoryDescriptor, found: MemoryConfig) -> bool {
+        let memory_type = self.memory_type(desc);
+        memory_type == found.memory_type(desc)
+            && found.guard_size(memory_type) >= self.guard_size(memory_type)
+            && found.bounds(memory_type) >= self.bounds(memory_type)
+    }
 }
 
 enum UnsharedMemoryStorage {
@@ -219,20 +468,25 @@ struct UnsharedMemoryInternal {
 }
 
 impl UnsharedMemory {
-    pub fn new(desc: MemoryDescriptor) -> Result<Self, CreationError> {
+    pub fn new(desc: MemoryDescriptor, config: MemoryConfig) -> Result<Self, CreationError> {
         let mut local = vm::LocalMemory {
             base: ptr::null_mut(),
             bound: 0,
             memory: ptr::null_mut(),
         };
 
-        let storage = match desc.memory_type() {
-            MemoryType::Dynamic => {
-                UnsharedMemoryStorage::Dynamic(DynamicMemory::new(desc, &mut local)?)
-            }
-            MemoryType::Static => {
-                UnsharedMemoryStorage::Static(StaticMemory::new(desc, &mut local)?)
-            }
+        let storage = match config.memory_type(desc) {
+            MemoryType::Dynamic => UnsharedMemoryStorage::Dynamic(DynamicMemory::new(
+                desc,
+                config.dynamic_guard_size as usize,
+                &mut local,
+            )?),
+            MemoryType::Static => UnsharedMemoryStorage::Static(StaticMemory::new(
+                desc,
+                config.static_bound as usize,
+                config.static_guard_size as usize,
+                &mut local,
+            )?),
             MemoryType::SharedStatic => panic!("attempting to create shared unshared memory"),
         };
 
@@ -244,6 +498,24 @@ impl UnsharedMemory {
         })
     }
 
+    fn from_host(desc: MemoryDescriptor, host: Box<dyn HostMemory>) -> Result<Self, CreationError> {
+        let mut local = vm::LocalMemory {
+            base: ptr::null_mut(),
+            bound: 0,
+            memory: ptr::null_mut(),
+        };
+
+        let storage =
+            UnsharedMemoryStorage::Dynamic(DynamicMemory::from_host(desc, host, &mut local)?);
+
+        Ok(UnsharedMemory {
+            internal: Rc::new(UnsharedMemoryInternal {
+                storage: RefCell::new(storage),
+                local: Cell::new(local),
+            }),
+        })
+    }
+
     pub fn grow(&self, delta: Pages) -> Option<Pages> {
         let mut storage = self.internal.storage.borrow_mut();
 
@@ -270,6 +542,13 @@ impl UnsharedMemory {
         }
     }
 
+    pub fn park(&self, file: &File) -> Result<usize, String> {
+        match &mut *self.internal.storage.borrow_mut() {
+            UnsharedMemoryStorage::Dynamic(dynamic_memory) => dynamic_memory.park(file),
+            UnsharedMemoryStorage::Static(static_memory) => static_memory.park(file),
+        }
+    }
+
     pub(crate) fn vm_local_memory(&self) -> *mut vm::LocalMemory {
         self.internal.local.as_ptr()
     }
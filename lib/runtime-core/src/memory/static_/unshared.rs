@@ -1,16 +1,12 @@
-use crate::{
-    error::CreationError,
-    memory::static_::{SAFE_STATIC_GUARD_SIZE, SAFE_STATIC_HEAP_SIZE},
-    sys,
-    types::MemoryDescriptor,
-    units::Pages,
-    vm,
-};
+use crate::{error::CreationError, events, sys, types::MemoryDescriptor, units::Pages, vm};
+use std::fs::File;
 
 /// This is an internal-only api.
 ///
 /// A static memory allocates 6GB of *virtual* memory when created
 /// in order to allow the webassembly module to contain no bounds-checks.
+/// Both the reserved size and the guard size can be changed through a
+/// `MemoryConfig`.
 ///
 /// Additionally, static memories stay at a single virtual address, so there is no need
 /// to reload its address on each use.
@@ -22,15 +18,25 @@ pub struct StaticMemory {
     memory: sys::Memory,
     current: Pages,
     max: Option<Pages>,
+    bound: usize,
 }
 
 impl StaticMemory {
     pub(in crate::memory) fn new(
         desc: MemoryDescriptor,
+        bound: usize,
+        guard_size: usize,
         local: &mut vm::LocalMemory,
     ) -> Result<Box<Self>, CreationError> {
+        if desc.minimum.bytes().0 > bound {
+            return Err(CreationError::InvalidDescriptor(format!(
+                "The minimum size of the memory exceeds its static bound of {} bytes",
+                bound
+            )));
+        }
+
         let memory = {
-            let mut memory = sys::Memory::with_size(SAFE_STATIC_HEAP_SIZE + SAFE_STATIC_GUARD_SIZE)
+            let mut memory = sys::Memory::with_size(bound + guard_size)
                 .map_err(|_| CreationError::UnableToCreateMemory)?;
             if desc.minimum != Pages(0) {
                 unsafe {
@@ -47,6 +53,7 @@ impl StaticMemory {
             memory,
             current: desc.minimum,
             max: desc.maximum,
+            bound,
         });
         let storage_ptr: *mut StaticMemory = &mut *storage;
 
@@ -74,6 +81,10 @@ impl StaticMemory {
             }
         }
 
+        if new_pages.bytes().0 > self.bound {
+            return None;
+        }
+
         unsafe {
             self.memory
                 .protect(
@@ -89,9 +100,15 @@ impl StaticMemory {
 
         self.current = new_pages;
 
+        events::memory_grew(old_pages, new_pages);
+
         Some(old_pages)
     }
 
+    pub fn park(&mut self, file: &File) -> Result<usize, String> {
+        unsafe { self.memory.park(self.current.bytes().0, file) }
+    }
+
     pub fn as_slice(&self) -> &[u8] {
         unsafe { &self.memory.as_slice()[0..self.current.bytes().0] }
     }
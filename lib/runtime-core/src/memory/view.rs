@@ -1,7 +1,7 @@
 use super::atomic::{Atomic, IntCast};
 use crate::types::ValueType;
 
-use std::{cell::Cell, marker::PhantomData, ops::Deref, slice};
+use std::{cell::Cell, marker::PhantomData, mem, ops::Deref, slice};
 
 pub trait Atomicity {}
 pub struct Atomically;
@@ -19,15 +19,54 @@ impl<'a, T> MemoryView<'a, T, NonAtomically>
 where
     T: ValueType,
 {
-    pub(super) unsafe fn new(ptr: *mut T, length: u32) -> Self {
+    pub(super) unsafe fn new(ptr: *mut T, length: usize) -> Self {
         Self {
             ptr,
-            length: length as usize,
+            length,
             _phantom: PhantomData,
         }
     }
 }
 
+impl<'a, T> MemoryView<'a, T, NonAtomically>
+where
+    T: ValueType,
+{
+    /// Read the element at `index` as the little-endian value wasm
+    /// stores. Unlike `self[index].get()`, which reads it in the host's
+    /// byte order, this is also correct on big-endian hosts.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn load(&self, index: usize) -> T {
+        let mut buffer = [0u8; 8];
+        let cells = self.byte_cells(index);
+        for (dst, src) in buffer.iter_mut().zip(cells) {
+            *dst = src.get();
+        }
+        T::from_le(&buffer[..cells.len()]).unwrap()
+    }
+
+    /// Write `value` at `index` in little-endian byte order.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn store(&self, index: usize, value: T) {
+        let mut buffer = [0u8; 8];
+        let cells = self.byte_cells(index);
+        value.into_le(&mut buffer[..cells.len()]);
+        for (dst, src) in cells.iter().zip(&buffer) {
+            dst.set(*src);
+        }
+    }
+
+    /// The bytes of the element at `index`.
+    fn byte_cells(&self, index: usize) -> &[Cell<u8>] {
+        assert!(index < self.length, "index out of bounds");
+        unsafe {
+            slice::from_raw_parts(self.ptr.add(index) as *const Cell<u8>, mem::size_of::<T>())
+        }
+    }
+}
+
 impl<'a, T: IntCast> MemoryView<'a, T, NonAtomically> {
     pub fn atomically(&self) -> MemoryView<'a, T, Atomically> {
         MemoryView {
@@ -51,3 +90,28 @@ impl<'a, T: IntCast> Deref for MemoryView<'a, T, Atomically> {
         unsafe { slice::from_raw_parts(self.ptr as *const Atomic<T>, self.length) }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::MemoryView;
+
+    #[test]
+    fn load_and_store_are_little_endian() {
+        let mut buffer = [0x01u8, 0x02, 0x03, 0x04, 0, 0, 0, 0];
+        let view = unsafe { MemoryView::<u32>::new(buffer.as_mut_ptr() as *mut u32, 2) };
+        assert_eq!(view.load(0), 0x0403_0201);
+
+        view.store(1, 0x0807_0605);
+        view.store(0, 0);
+        assert_eq!(buffer, [0, 0, 0, 0, 0x05, 0x06, 0x07, 0x08]);
+    }
+
+    #[test]
+    fn floats_are_stored_by_their_bits() {
+        let mut buffer = [0u8; 8];
+        let view = unsafe { MemoryView::<f64>::new(buffer.as_mut_ptr() as *mut f64, 1) };
+        view.store(0, 1.5);
+        assert_eq!(buffer, 1.5f64.to_bits().to_le_bytes());
+        assert_eq!(view.load(0), 1.5);
+    }
+}
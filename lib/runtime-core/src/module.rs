@@ -2,6 +2,7 @@ use crate::{
     backend::{Backend, FuncResolver, ProtectedCaller},
     error::Result,
     import::ImportObject,
+    memory::MemoryConfig,
     structures::{Map, TypedIndex},
     typed_func::EARLY_TRAPPER,
     types::{
@@ -48,6 +49,7 @@ pub struct ModuleInfo {
     pub func_assoc: Map<FuncIndex, SigIndex>,
     pub signatures: Map<SigIndex, Arc<FuncSig>>,
     pub backend: Backend,
+    pub memory_config: MemoryConfig,
 
     pub namespace_table: StringTable<NamespaceIndex>,
     pub name_table: StringTable<NameIndex>,
@@ -62,6 +64,13 @@ pub struct ModuleInfo {
 /// [`compile_with`]: fn.compile_with.html
 pub struct Module(#[doc(hidden)] pub Arc<ModuleInner>);
 
+/// Cloning a module shares its compiled code.
+impl Clone for Module {
+    fn clone(&self) -> Self {
+        Module::new(Arc::clone(&self.0))
+    }
+}
+
 impl Module {
     pub(crate) fn new(inner: Arc<ModuleInner>) -> Self {
         unsafe {
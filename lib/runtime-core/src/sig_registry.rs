@@ -32,6 +32,13 @@ impl SigRegistry {
         Sig: Into<Arc<FuncSig>>,
     {
         let func_sig = func_sig.into();
+        // Instantiation looks up the signature of every table element, so
+        // the signatures that are already registered, nearly all of them,
+        // only take the read lock, which threads share.
+        if let Some(&sig_index) = (*GLOBAL_SIG_REGISTRY).read().func_table.get(&func_sig) {
+            return sig_index;
+        }
+
         let mut global = (*GLOBAL_SIG_REGISTRY).write();
         let global = &mut *global;
 
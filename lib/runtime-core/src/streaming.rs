@@ -0,0 +1,89 @@
+//! Reading a module from a stream, one section at a time.
+use crate::error::{CompileError, CompileResult};
+use std::io::{self, Read};
+
+const HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+/// The id of the last known section, the data section.
+const LAST_SECTION_ID: u8 = 11;
+
+fn read_error(err: io::Error) -> CompileError {
+    if err.kind() == io::ErrorKind::UnexpectedEof {
+        CompileError::ValidationError {
+            msg: "unexpected end of the module".to_string(),
+            location: None,
+        }
+    } else {
+        CompileError::ReadError {
+            msg: err.to_string(),
+        }
+    }
+}
+
+/// Read a byte, or `None` at the end of the stream.
+fn read_byte<R: Read>(reader: &mut R) -> CompileResult<Option<u8>> {
+    let mut byte = [0];
+    loop {
+        return match reader.read(&mut byte) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(byte[0])),
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => Err(read_error(err)),
+        };
+    }
+}
+
+/// Read an unsigned LEB128 `u32`, appending its bytes to `wasm`.
+fn read_var_u32<R: Read>(reader: &mut R, wasm: &mut Vec<u8>) -> CompileResult<u32> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte =
+            read_byte(reader)?.ok_or_else(|| read_error(io::ErrorKind::UnexpectedEof.into()))?;
+        wasm.push(byte);
+        if shift == 28 && byte > 0x0f {
+            break;
+        }
+        value |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(CompileError::ValidationError {
+        msg: "invalid section size".to_string(),
+        location: None,
+    })
+}
+
+/// Read a whole module from `reader`, checking the header and the
+/// framing and order of the sections as they arrive.
+pub(crate) fn read_sections<R: Read>(mut reader: R) -> CompileResult<Vec<u8>> {
+    let mut wasm = vec![0; HEADER.len()];
+    reader.read_exact(&mut wasm).map_err(read_error)?;
+    if wasm != HEADER {
+        return Err(CompileError::ValidationError {
+            msg: "not a WebAssembly module of version 1".to_string(),
+            location: None,
+        });
+    }
+
+    let mut last_id = 0;
+    while let Some(id) = read_byte(&mut reader)? {
+        // Custom sections, of id 0, can go anywhere.
+        if id > LAST_SECTION_ID || (id != 0 && id <= last_id) {
+            return Err(CompileError::ValidationError {
+                msg: format!("unexpected section of id {}", id),
+                location: None,
+            });
+        }
+        if id != 0 {
+            last_id = id;
+        }
+        wasm.push(id);
+
+        let size = read_var_u32(&mut reader, &mut wasm)? as usize;
+        let start = wasm.len();
+        wasm.resize(start + size, 0);
+        reader.read_exact(&mut wasm[start..]).map_err(read_error)?;
+    }
+    Ok(wasm)
+}
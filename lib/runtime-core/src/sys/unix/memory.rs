@@ -1,8 +1,22 @@
 use errno;
 use nix::libc;
 use page_size;
+use std::io::{Seek, SeekFrom, Write};
 use std::ops::{Bound, RangeBounds};
-use std::{fs::File, os::unix::io::IntoRawFd, path::Path, ptr, rc::Rc, slice};
+use std::os::unix::io::{AsRawFd, IntoRawFd};
+use std::{fs::File, path::Path, ptr, rc::Rc, slice};
+
+#[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+mod jit {
+    use nix::libc::{c_int, c_void};
+
+    pub const MAP_JIT: c_int = 0x800;
+
+    extern "C" {
+        pub fn pthread_jit_write_protect_np(enabled: c_int);
+        pub fn sys_icache_invalidate(start: *mut c_void, len: usize);
+    }
+}
 
 unsafe impl Send for Memory {}
 unsafe impl Sync for Memory {}
@@ -13,6 +27,9 @@ pub struct Memory {
     size: usize,
     protection: Protect,
     fd: Option<Rc<RawFd>>,
+    /// Mapped with `MAP_JIT`, so `protect` switches the writes to it on
+    /// and off for the thread instead of changing its protection.
+    jit: bool,
 }
 
 impl Memory {
@@ -45,6 +62,7 @@ impl Memory {
                 size: file_len as usize,
                 protection,
                 fd: Some(Rc::new(raw_fd)),
+                jit: false,
             })
         }
     }
@@ -56,6 +74,7 @@ impl Memory {
                 size: 0,
                 protection,
                 fd: None,
+                jit: false,
             });
         }
 
@@ -80,6 +99,7 @@ impl Memory {
                 size,
                 protection,
                 fd: None,
+                jit: false,
             })
         }
     }
@@ -91,6 +111,7 @@ impl Memory {
                 size: 0,
                 protection: Protect::None,
                 fd: None,
+                jit: false,
             });
         }
 
@@ -115,15 +136,66 @@ impl Memory {
                 size,
                 protection: Protect::None,
                 fd: None,
+                jit: false,
+            })
+        }
+    }
+
+    /// A memory of `size` bytes for a backend to emit code into, which
+    /// `protect` makes writable, and then executable, for the thread.
+    ///
+    /// Hardened processes on macOS for Apple silicon can only execute code
+    /// they emit in a `MAP_JIT` mapping, which is readable, writable and
+    /// executable but only ever writable or executable for a thread at
+    /// once. Elsewhere, this is `with_size`.
+    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+    pub fn with_size_for_code(size: usize) -> Result<Self, String> {
+        if size == 0 {
+            return Self::with_size(0);
+        }
+
+        let size = round_up_to_page_size(size, page_size::get());
+
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC,
+                libc::MAP_PRIVATE | libc::MAP_ANON | jit::MAP_JIT,
+                -1,
+                0,
+            )
+        };
+
+        if ptr == -1 as _ {
+            Err(errno::errno().to_string())
+        } else {
+            Ok(Self {
+                ptr: ptr as *mut u8,
+                size,
+                protection: Protect::ReadExec,
+                fd: None,
+                jit: true,
             })
         }
     }
 
+    /// A memory of `size` bytes for a backend to emit code into, which
+    /// `protect` makes writable, and then executable.
+    #[cfg(not(all(target_os = "macos", target_arch = "aarch64")))]
+    pub fn with_size_for_code(size: usize) -> Result<Self, String> {
+        Self::with_size(size)
+    }
+
     pub unsafe fn protect(
         &mut self,
         range: impl RangeBounds<usize>,
         protection: Protect,
     ) -> Result<(), String> {
+        if self.jit {
+            return self.protect_jit(protection);
+        }
+
         let protect = protection.to_protect_const();
 
         let range_start = match range.start_bound() {
@@ -154,6 +226,80 @@ impl Memory {
         }
     }
 
+    /// Switches the writes to a `MAP_JIT` memory on or off for this
+    /// thread, which makes it executable while they're off.
+    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+    unsafe fn protect_jit(&mut self, protection: Protect) -> Result<(), String> {
+        match protection {
+            Protect::ReadWrite => jit::pthread_jit_write_protect_np(0),
+            Protect::ReadExec => {
+                jit::pthread_jit_write_protect_np(1);
+                jit::sys_icache_invalidate(self.ptr as _, self.size);
+            }
+            _ => return Err(format!("a JIT memory can't be made {:?}", protection)),
+        }
+        self.protection = protection;
+        Ok(())
+    }
+
+    #[cfg(not(all(target_os = "macos", target_arch = "aarch64")))]
+    unsafe fn protect_jit(&mut self, _protection: Protect) -> Result<(), String> {
+        unreachable!("only macOS on Apple silicon maps memories with MAP_JIT")
+    }
+
+    /// Write the first `len` bytes of this memory to `file` and map the
+    /// file privately in their place, so the host can drop their pages
+    /// and read them back from the file when they're next touched. Runs
+    /// of zero pages are left as holes in the file. Returns how many
+    /// bytes were written.
+    ///
+    /// The first `len` bytes have to be readable and writable, and `len`
+    /// a multiple of the page size.
+    pub unsafe fn park(&mut self, len: usize, file: &File) -> Result<usize, String> {
+        let page_size = page_size::get();
+        if len > self.size || len % page_size != 0 {
+            return Err(format!("can't park {} bytes of this memory", len));
+        }
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let mut file = file;
+        file.set_len(0).map_err(|e| e.to_string())?;
+        file.set_len(len as u64).map_err(|e| e.to_string())?;
+        let bytes = &self.as_slice()[..len];
+        let mut written = 0;
+        let mut run_start = None;
+        for offset in (0..=len).step_by(page_size) {
+            let zero = offset == len || bytes[offset..offset + page_size].iter().all(|&b| b == 0);
+            match (run_start, zero) {
+                (None, false) => run_start = Some(offset),
+                (Some(start), true) => {
+                    file.seek(SeekFrom::Start(start as u64))
+                        .and_then(|_| file.write_all(&bytes[start..offset]))
+                        .map_err(|e| e.to_string())?;
+                    written += offset - start;
+                    run_start = None;
+                }
+                _ => {}
+            }
+        }
+
+        let ptr = libc::mmap(
+            self.ptr as _,
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_FIXED,
+            file.as_raw_fd(),
+            0,
+        );
+        if ptr == -1 as _ {
+            Err(errno::errno().to_string())
+        } else {
+            Ok(written)
+        }
+    }
+
     pub fn split_at(mut self, offset: usize) -> (Memory, Memory) {
         let page_size = page_size::get();
         if offset % page_size == 0 {
@@ -167,6 +313,7 @@ impl Memory {
                 size: second_size,
                 protection: self.protection,
                 fd: self.fd.clone(),
+                jit: self.jit,
             };
 
             (self, second)
@@ -270,3 +417,34 @@ fn round_up_to_page_size(size: usize, page_size: usize) -> usize {
 fn round_down_to_page_size(size: usize, page_size: usize) -> usize {
     size & !(page_size - 1)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    #[test]
+    fn parked_memory_keeps_its_contents() {
+        let page_size = page_size::get();
+        let mut memory = Memory::with_size_protect(3 * page_size, Protect::ReadWrite).unwrap();
+        unsafe { memory.as_slice_mut()[page_size + 1] = 7 };
+
+        let path = env::temp_dir().join(format!("wasmer-park-test-{}", process::id()));
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&path)
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+        let written = unsafe { memory.park(3 * page_size, &file).unwrap() };
+        assert_eq!(written, page_size);
+
+        unsafe {
+            assert_eq!(memory.as_slice()[page_size + 1], 7);
+            assert_eq!(memory.as_slice()[2 * page_size], 0);
+            memory.as_slice_mut()[2 * page_size] = 8;
+            assert_eq!(memory.as_slice()[2 * page_size], 8);
+        }
+    }
+}
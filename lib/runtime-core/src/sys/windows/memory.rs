@@ -1,6 +1,6 @@
 use page_size;
 use std::ops::{Bound, RangeBounds};
-use std::{ptr, slice};
+use std::{fs::File, ptr, slice};
 use winapi::um::memoryapi::{VirtualAlloc, VirtualFree};
 use winapi::um::winnt::{
     MEM_COMMIT, MEM_DECOMMIT, MEM_RESERVE, PAGE_EXECUTE_READ, PAGE_NOACCESS, PAGE_READONLY,
@@ -44,6 +44,12 @@ impl Memory {
         }
     }
 
+    /// A memory of `size` bytes for a backend to emit code into, which
+    /// `protect` makes writable, and then executable.
+    pub fn with_size_for_code(size: usize) -> Result<Self, String> {
+        Self::with_size(size)
+    }
+
     pub fn with_size(size: usize) -> Result<Self, String> {
         if size == 0 {
             return Ok(Self {
@@ -105,6 +111,12 @@ impl Memory {
         }
     }
 
+    /// Parking memory relies on private file mappings, which are only
+    /// implemented on unix.
+    pub unsafe fn park(&mut self, _len: usize, _file: &File) -> Result<usize, String> {
+        Err("parking memory isn't supported on Windows".to_string())
+    }
+
     pub fn split_at(mut self, offset: usize) -> (Memory, Memory) {
         let page_size = page_size::get();
         if offset % page_size == 0 {
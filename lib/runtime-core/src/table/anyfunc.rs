@@ -3,7 +3,7 @@ use crate::{
     instance::DynFunc,
     sig_registry::SigRegistry,
     structures::TypedIndex,
-    types::{FuncSig, TableDescriptor},
+    types::{FuncSig, SigIndex, TableDescriptor},
     vm,
 };
 
@@ -80,6 +80,15 @@ impl AnyfuncTable {
         &mut self.backing
     }
 
+    pub fn signature(&self, index: u32) -> Option<Arc<FuncSig>> {
+        let anyfunc = self.backing.get(index as usize)?;
+        if anyfunc.func.is_null() {
+            return None;
+        }
+        let sig_index = SigIndex::new(anyfunc.sig_id.0 as usize);
+        Some(SigRegistry.lookup_signature(sig_index))
+    }
+
     pub fn grow(&mut self, delta: u32, local: &mut vm::LocalTable) -> Option<u32> {
         let starting_len = self.backing.len() as u32;
 
@@ -2,10 +2,10 @@ use crate::{
     error::CreationError,
     export::Export,
     import::IsExport,
-    types::{ElementType, TableDescriptor},
+    types::{ElementType, FuncSig, TableDescriptor},
     vm,
 };
-use std::{cell::RefCell, fmt, ptr, rc::Rc};
+use std::{cell::RefCell, fmt, ptr, rc::Rc, sync::Arc};
 
 mod anyfunc;
 
@@ -107,6 +107,14 @@ impl Table {
         }
     }
 
+    /// The signature of the function at `index`, or `None` if the
+    /// element is null or out of bounds.
+    pub fn signature(&self, index: u32) -> Option<Arc<FuncSig>> {
+        match &*self.storage.borrow() {
+            (TableStorage::Anyfunc(ref anyfunc_table), _) => anyfunc_table.signature(index),
+        }
+    }
+
     /// Grow this table by `delta`.
     pub fn grow(&self, delta: u32) -> Option<u32> {
         if delta == 0 {
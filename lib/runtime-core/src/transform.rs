@@ -0,0 +1,274 @@
+//! Rewriting modules without their source, to adapt a module built for
+//! another runtime to the imports this one provides.
+//!
+//! Only the import section is rewritten. Every other section is copied
+//! as is, so a module that was valid stays valid.
+use crate::error::{CompileError, CompileResult};
+
+const HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+const IMPORT_SECTION: u8 = 2;
+
+/// A set of renames of imports, applied in the order they were added.
+///
+/// # Usage:
+/// ```
+/// # use wasmer_runtime_core::{error::CompileResult, transform::ImportRenames};
+/// fn adapt(wasm: &[u8]) -> CompileResult<Vec<u8>> {
+///     let mut renames = ImportRenames::new();
+///     renames.add_prefix(("env", "__syscall"), ("syscalls", "syscall_"));
+///     renames.add(("env", "abort"), ("host", "abort"));
+///     renames.apply(wasm)
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ImportRenames {
+    renames: Vec<Rename>,
+}
+
+#[derive(Debug, Clone)]
+struct Rename {
+    from: (String, String),
+    to: (String, String),
+    prefix: bool,
+}
+
+impl ImportRenames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rename the import `from`, as `(namespace, name)`, to `to`.
+    pub fn add(&mut self, from: (&str, &str), to: (&str, &str)) -> &mut Self {
+        self.push(from, to, false)
+    }
+
+    /// Move the imports of the namespace of `from` whose names start with
+    /// the name of `from` to the namespace of `to`, replacing that prefix
+    /// with the name of `to`.
+    pub fn add_prefix(&mut self, from: (&str, &str), to: (&str, &str)) -> &mut Self {
+        self.push(from, to, true)
+    }
+
+    fn push(&mut self, from: (&str, &str), to: (&str, &str), prefix: bool) -> &mut Self {
+        self.renames.push(Rename {
+            from: (from.0.to_string(), from.1.to_string()),
+            to: (to.0.to_string(), to.1.to_string()),
+            prefix,
+        });
+        self
+    }
+
+    /// The new namespace and name of the import `namespace.name`, if one
+    /// of the renames applies to it.
+    pub fn rename(&self, namespace: &str, name: &str) -> Option<(String, String)> {
+        self.renames.iter().find_map(|rename| {
+            if rename.from.0 != namespace {
+                return None;
+            }
+            if rename.prefix && name.starts_with(&rename.from.1) {
+                let rest = &name[rename.from.1.len()..];
+                Some((rename.to.0.clone(), format!("{}{}", rename.to.1, rest)))
+            } else if !rename.prefix && rename.from.1 == name {
+                Some(rename.to.clone())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// A copy of `wasm` with its imports renamed.
+    pub fn apply(&self, wasm: &[u8]) -> CompileResult<Vec<u8>> {
+        rename_imports(wasm, |namespace, name| self.rename(namespace, name))
+    }
+}
+
+/// A copy of `wasm` where every import for which `rename` returns a new
+/// namespace and name is renamed.
+pub fn rename_imports<F>(wasm: &[u8], mut rename: F) -> CompileResult<Vec<u8>>
+where
+    F: FnMut(&str, &str) -> Option<(String, String)>,
+{
+    if wasm.get(..HEADER.len()) != Some(&HEADER[..]) {
+        return Err(invalid("not a WebAssembly module of version 1"));
+    }
+    let mut reader = Reader {
+        wasm,
+        offset: HEADER.len(),
+    };
+    let mut out = HEADER.to_vec();
+    while reader.offset < wasm.len() {
+        let id = reader.byte()?;
+        let size = reader.var_u32()? as usize;
+        let payload = reader.bytes(size)?;
+        out.push(id);
+        if id == IMPORT_SECTION {
+            let payload = rename_in_section(payload, reader.offset - size, &mut rename)?;
+            write_var_u32(&mut out, payload.len() as u32);
+            out.extend_from_slice(&payload);
+        } else {
+            write_var_u32(&mut out, size as u32);
+            out.extend_from_slice(payload);
+        }
+    }
+    Ok(out)
+}
+
+fn rename_in_section<F>(section: &[u8], start: usize, rename: &mut F) -> CompileResult<Vec<u8>>
+where
+    F: FnMut(&str, &str) -> Option<(String, String)>,
+{
+    let mut reader = Reader {
+        wasm: section,
+        offset: 0,
+    };
+    let mut out = Vec::with_capacity(section.len());
+    let count = reader.var_u32()?;
+    write_var_u32(&mut out, count);
+    for _ in 0..count {
+        let namespace = reader.string()?;
+        let name = reader.string()?;
+        match rename(namespace, name) {
+            Some((namespace, name)) => {
+                write_string(&mut out, &namespace);
+                write_string(&mut out, &name);
+            }
+            None => {
+                write_string(&mut out, namespace);
+                write_string(&mut out, name);
+            }
+        }
+
+        // The description of the import is copied as is.
+        let desc_start = reader.offset;
+        match reader.byte()? {
+            // A function, by the index of its type.
+            0x00 => {
+                reader.var_u32()?;
+            }
+            // A table, by its element type and limits.
+            0x01 => {
+                reader.byte()?;
+                reader.limits()?;
+            }
+            0x02 => reader.limits()?,
+            // A global, by its type and mutability.
+            0x03 => {
+                reader.bytes(2)?;
+            }
+            _ => {
+                return Err(invalid(&format!(
+                    "unknown import kind at offset {:#x}",
+                    start + desc_start
+                )))
+            }
+        }
+        out.extend_from_slice(&section[desc_start..reader.offset]);
+    }
+    if reader.offset != section.len() {
+        return Err(invalid(&format!(
+            "the import section ends at offset {:#x}, before its size says",
+            start + reader.offset
+        )));
+    }
+    Ok(out)
+}
+
+fn invalid(msg: &str) -> CompileError {
+    CompileError::ValidationError {
+        msg: msg.to_string(),
+        location: None,
+    }
+}
+
+struct Reader<'a> {
+    wasm: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> CompileResult<&'a [u8]> {
+        let bytes = self
+            .wasm
+            .get(self.offset..self.offset + len)
+            .ok_or_else(|| invalid("unexpected end of the module"))?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> CompileResult<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn var_u32(&mut self) -> CompileResult<u32> {
+        let mut value = 0u32;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            if shift == 28 && byte > 0x0f {
+                break;
+            }
+            value |= u32::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("invalid LEB128 integer"))
+    }
+
+    fn string(&mut self) -> CompileResult<&'a str> {
+        let len = self.var_u32()? as usize;
+        std::str::from_utf8(self.bytes(len)?).map_err(|_| invalid("an import name isn't UTF-8"))
+    }
+
+    /// Skip the limits of a table or a memory.
+    fn limits(&mut self) -> CompileResult<()> {
+        let flags = self.var_u32()?;
+        self.var_u32()?;
+        if flags & 1 != 0 {
+            self.var_u32()?;
+        }
+        Ok(())
+    }
+}
+
+fn write_var_u32(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_string(out: &mut Vec<u8>, string: &str) {
+    write_var_u32(out, string.len() as u32);
+    out.extend_from_slice(string.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ImportRenames;
+
+    #[test]
+    fn renames_imports() {
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        // An import section with `env.__syscall5` as a function of type 0
+        // and `env.memory` as a memory of 1 to 2 pages.
+        wasm.extend_from_slice(b"\x02\x20\x02");
+        wasm.extend_from_slice(b"\x03env\x0a__syscall5\x00\x00");
+        wasm.extend_from_slice(b"\x03env\x06memory\x02\x01\x01\x02");
+
+        let mut renames = ImportRenames::new();
+        renames.add_prefix(("env", "__syscall"), ("syscalls", "syscall_"));
+        let renamed = renames.apply(&wasm).unwrap();
+
+        let mut expected = b"\0asm\x01\0\0\0".to_vec();
+        expected.extend_from_slice(b"\x02\x24\x02");
+        expected.extend_from_slice(b"\x08syscalls\x09syscall_5\x00\x00");
+        expected.extend_from_slice(b"\x03env\x06memory\x02\x01\x01\x02");
+        assert_eq!(renamed, expected);
+        assert!(renames.apply(&wasm[..wasm.len() - 1]).is_err());
+    }
+}
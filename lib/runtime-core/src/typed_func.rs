@@ -3,6 +3,7 @@ use crate::{
     error::RuntimeError,
     export::{Context, Export, FuncPointer},
     import::IsExport,
+    module::ModuleInner,
     types::{FuncSig, Type, WasmExternType},
     vm::Ctx,
 };
@@ -12,6 +13,27 @@ thread_local! {
     pub static EARLY_TRAPPER: UnsafeCell<Option<Box<dyn UserTrapper>>> = UnsafeCell::new(None);
 }
 
+/// Make sure the imports can trap on this thread, which may not be the
+/// one that compiled `module`, before calling into it.
+pub(crate) fn ensure_early_trapper(module: &ModuleInner) {
+    EARLY_TRAPPER.with(|ucell| unsafe {
+        let early_trapper = &mut *ucell.get();
+        if early_trapper.is_none() {
+            *early_trapper = Some(module.protected_caller.get_early_trapper());
+        }
+    });
+}
+
+/// Traps out of the host function that was called with `ctx`, with `msg`
+/// as the error.
+unsafe fn early_trap(ctx: &Ctx, msg: String) -> ! {
+    ensure_early_trapper(&*ctx.module);
+    match &*EARLY_TRAPPER.with(|ucell| ucell.get()) {
+        Some(early_trapper) => early_trapper.do_early_trap(msg),
+        None => unreachable!(),
+    }
+}
+
 pub trait Safeness {}
 pub struct Safe;
 pub struct Unsafe;
@@ -108,6 +130,19 @@ where
     }
 }
 
+impl<'a, Args, Rets> Func<'a, Args, Rets, Safe>
+where
+    Args: WasmTypeList,
+    Rets: WasmTypeList,
+{
+    /// Make sure the imports the function calls can trap on this thread.
+    fn ensure_early_trapper(&self) {
+        if let Some(ctx) = unsafe { self.ctx.as_ref() } {
+            ensure_early_trapper(unsafe { &*ctx.module });
+        }
+    }
+}
+
 impl<'a, Args, Rets, Safety> Func<'a, Args, Rets, Safety>
 where
     Args: WasmTypeList,
@@ -149,6 +184,7 @@ where
     Rets: WasmTypeList,
 {
     pub fn call(&self, a: A) -> Result<Rets, RuntimeError> {
+        self.ensure_early_trapper();
         Ok(unsafe { <A as WasmTypeList>::call(a, self.f, self.ctx) })
     }
 }
@@ -198,23 +234,16 @@ macro_rules! impl_traits {
                         Ok(Err(err)) => err,
                         Err(err) => {
                             if let Some(s) = err.downcast_ref::<&str>() {
-                                s.to_string()
+                                format!("host function panicked: {}", s)
                             } else if let Some(s) = err.downcast_ref::<String>() {
-                                s.clone()
+                                format!("host function panicked: {}", s)
                             } else {
                                 "a panic occurred, but no additional information is available".to_string()
                             }
                         },
                     };
 
-                    unsafe {
-                        if let Some(early_trapper) = &*EARLY_TRAPPER.with(|ucell| ucell.get()) {
-                            early_trapper.do_early_trap(msg)
-                        } else {
-                            eprintln!("panic handling not setup");
-                            std::process::exit(1)
-                        }
-                    }
+                    unsafe { early_trap(ctx, msg) }
                 }
 
                 wrap::<$( $x, )* Rets, Trap, Self> as *const ()
@@ -227,6 +256,7 @@ macro_rules! impl_traits {
         {
             #[allow(non_snake_case)]
             pub fn call(&self, $( $x: $x, )* ) -> Result<Rets, RuntimeError> {
+                self.ensure_early_trapper();
                 #[allow(unused_parens)]
                 Ok(unsafe { <( $( $x ),* ) as WasmTypeList>::call(( $($x),* ), self.f, self.ctx) })
             }
@@ -1,11 +1,46 @@
+//! The layout of the structures that compiled code shares with the runtime.
+//!
+//! Compiled code receives a `*mut Ctx` as its first argument and reaches
+//! the memories, tables, globals and imported functions of the instance
+//! through it, at the offsets the `offset_*` functions give. The
+//! structures are all `#[repr(C)]`:
+//!
+//! - [`Ctx`] starts with pointers to the arrays of local memories, tables
+//!   and globals, then to the arrays of imported memories, tables, globals
+//!   and functions. Each memory, table and global array holds pointers to
+//!   the [`LocalMemory`], [`LocalTable`] or [`LocalGlobal`] itself.
+//! - [`LocalMemory`] is the base and the bound in bytes of a memory.
+//! - [`LocalTable`] is the base and the element count of a table of
+//!   [`Anyfunc`]s, which are a function, its `Ctx` and its signature id.
+//! - [`LocalGlobal`] is the 64 bits of a global's value.
+//! - [`ImportedFunc`] is a function and the `Ctx` it's called with.
+//!
+//! Backends and custom trampolines should go through the offsets, and
+//! embedders through the `vm_*` accessors of [`Ctx`], rather than assume
+//! the layout, and check [`ABI_VERSION`] when they're built separately.
+//!
+//! [`Ctx`]: struct.Ctx.html
+//! [`LocalMemory`]: struct.LocalMemory.html
+//! [`LocalTable`]: struct.LocalTable.html
+//! [`LocalGlobal`]: struct.LocalGlobal.html
+//! [`Anyfunc`]: struct.Anyfunc.html
+//! [`ImportedFunc`]: struct.ImportedFunc.html
+//! [`ABI_VERSION`]: constant.ABI_VERSION.html
 pub use crate::backing::{ImportBacking, LocalBacking};
 use crate::{
-    memory::Memory,
+    export::Export,
+    memory::{Memory, PageBudget},
     module::ModuleInner,
     structures::TypedIndex,
-    types::{LocalOrImport, MemoryIndex},
+    table::Table,
+    types::{LocalOrImport, MemoryIndex, TableIndex, Type},
+    units::Pages,
 };
-use std::{ffi::c_void, mem, ptr};
+use std::{ffi::c_void, mem, ptr, sync::Arc};
+
+/// The version of the layout described in the module documentation,
+/// bumped whenever it changes.
+pub const ABI_VERSION: u32 = 1;
 
 /// The context of the currently running WebAssembly instance.
 ///
@@ -36,10 +71,16 @@ pub struct Ctx {
 
     local_backing: *mut LocalBacking,
     import_backing: *mut ImportBacking,
-    module: *const ModuleInner,
+    pub(crate) module: *const ModuleInner,
 
     pub data: *mut c_void,
     pub data_finalizer: Option<extern "C" fn(data: *mut c_void)>,
+
+    /// Where `memory.grow` takes the pages of the local memories from.
+    pub(crate) page_budget: Option<Arc<PageBudget>>,
+    /// The pages taken from `page_budget`, which are given back when the
+    /// instance is dropped.
+    pub(crate) pages_taken: Pages,
 }
 
 impl Ctx {
@@ -65,6 +106,8 @@ impl Ctx {
 
             data: ptr::null_mut(),
             data_finalizer: None,
+            page_budget: None,
+            pages_taken: Pages(0),
         }
     }
 
@@ -92,6 +135,8 @@ impl Ctx {
 
             data,
             data_finalizer: Some(data_finalizer),
+            page_budget: None,
+            pages_taken: Pages(0),
         }
     }
 
@@ -127,9 +172,159 @@ impl Ctx {
             },
         }
     }
+
+    /// The number of linear memories, imported and local, that
+    /// this instance can access.
+    pub fn memory_count(&self) -> u32 {
+        let module = unsafe { &*self.module };
+        (module.info.imported_memories.len() + module.info.memories.len()) as u32
+    }
+
+    /// Like [`memory`], but returns `None` instead of panicking
+    /// when `mem_index` is out of range.
+    ///
+    /// [`memory`]: struct.Ctx.html#method.memory
+    pub fn get_memory(&self, mem_index: u32) -> Option<&Memory> {
+        if mem_index < self.memory_count() {
+            Some(self.memory(mem_index))
+        } else {
+            None
+        }
+    }
+
+    /// The base and bound of the specified memory, imported or local, as
+    /// compiled code sees them, or `None` when `mem_index` is out of range.
+    pub fn vm_memory(&self, mem_index: u32) -> Option<&LocalMemory> {
+        let info = unsafe { &(*self.module).info };
+        unsafe {
+            vm_local_or_import(
+                mem_index,
+                self.imported_memories,
+                info.imported_memories.len(),
+                self.memories,
+                info.memories.len(),
+            )
+        }
+    }
+
+    /// The base and element count of the specified table, imported or
+    /// local, or `None` when `table_index` is out of range.
+    pub fn vm_table(&self, table_index: u32) -> Option<&LocalTable> {
+        let info = unsafe { &(*self.module).info };
+        unsafe {
+            vm_local_or_import(
+                table_index,
+                self.imported_tables,
+                info.imported_tables.len(),
+                self.tables,
+                info.tables.len(),
+            )
+        }
+    }
+
+    /// The storage of the specified global, imported or local, or `None`
+    /// when `global_index` is out of range.
+    pub fn vm_global(&self, global_index: u32) -> Option<&LocalGlobal> {
+        let info = unsafe { &(*self.module).info };
+        unsafe {
+            vm_local_or_import(
+                global_index,
+                self.imported_globals,
+                info.imported_globals.len(),
+                self.globals,
+                info.globals.len(),
+            )
+        }
+    }
+
+    /// The types of the globals, imported ones first, in the order of
+    /// `vm_global`.
+    pub fn global_types<'a>(&'a self) -> impl Iterator<Item = Type> + 'a {
+        let info = unsafe { &(*self.module).info };
+        let imported = info.imported_globals.iter().map(|(_, (_, desc))| desc.ty);
+        let local = info.globals.iter().map(|(_, global)| global.desc.ty);
+        imported.chain(local)
+    }
+
+    /// The specified table of the WebAssembly instance, imported or
+    /// local, or `None` when `table_index` is out of range.
+    pub fn get_table(&self, table_index: u32) -> Option<&Table> {
+        let module = unsafe { &*self.module };
+        if table_index as usize >= module.info.imported_tables.len() + module.info.tables.len() {
+            return None;
+        }
+        let table_index = TableIndex::new(table_index as usize);
+        match table_index.local_or_import(module) {
+            LocalOrImport::Local(local_table_index) => unsafe {
+                let local_backing = &*self.local_backing;
+                Some(&local_backing.tables[local_table_index])
+            },
+            LocalOrImport::Import(import_table_index) => unsafe {
+                let import_backing = &*self.import_backing;
+                Some(&import_backing.tables[import_table_index])
+            },
+        }
+    }
+
+    /// The namespace and the name the instance imports the host function
+    /// `func` as, or `None` if it doesn't import it.
+    pub fn import_name(&self, func: &Export) -> Option<(&str, &str)> {
+        let func = match func {
+            Export::Function { func, .. } => func.inner(),
+            _ => return None,
+        };
+        let info = unsafe { &(*self.module).info };
+        let import_backing = unsafe { &*self.import_backing };
+        let (index, _) = import_backing
+            .vm_functions
+            .iter()
+            .find(|(_, imported)| imported.func == func)?;
+        let import_name = &info.imported_functions[index];
+        Some((
+            info.namespace_table.get(import_name.namespace_index),
+            info.name_table.get(import_name.name_index),
+        ))
+    }
+
+    /// Calls `switch`, which switches this thread to another native stack
+    /// and returns once it's switched back, keeping the state the backend
+    /// catches traps with apart for each stack.
+    pub fn switch_stacks<T>(&mut self, switch: impl FnOnce() -> T) -> T {
+        let protected_caller = unsafe { &(*self.module).protected_caller };
+        let state = protected_caller.save_trap_state();
+        let ret = switch();
+        protected_caller.restore_trap_state(state);
+        ret
+    }
+}
+
+impl Drop for Ctx {
+    fn drop(&mut self) {
+        if let Some(finalizer) = self.data_finalizer {
+            finalizer(self.data);
+        }
+    }
 }
 
-#[doc(hidden)]
+/// Looks up `index` in an index space where the imports come first.
+unsafe fn vm_local_or_import<'a, T>(
+    index: u32,
+    imported: *mut *mut T,
+    imported_count: usize,
+    local: *mut *mut T,
+    local_count: usize,
+) -> Option<&'a T> {
+    let index = index as usize;
+    if index < imported_count {
+        Some(&**imported.add(index))
+    } else if index < imported_count + local_count {
+        Some(&**local.add(index - imported_count))
+    } else {
+        None
+    }
+}
+
+/// The offsets of the fields of `Ctx`, for compiled code.
 impl Ctx {
     #[allow(clippy::erasing_op)] // TODO
     pub fn offset_memories() -> u8 {
@@ -487,6 +682,87 @@ mod vm_ctx_tests {
         drop(ctx);
     }
 
+    #[test]
+    fn finds_the_import_a_host_function_was_resolved_to() {
+        use super::ImportedFunc;
+        use crate::export::Export;
+        use crate::import::{called_as, slot_export, IsExport, Slot, SlotFunc};
+        use crate::module::{ImportName, StringTableBuilder};
+        use crate::structures::TypedIndex;
+        use crate::types::ImportedFuncIndex;
+        use std::{mem, ptr};
+
+        struct Echo;
+        impl SlotFunc for Echo {
+            fn export<S: Slot>() -> Export {
+                func!(echo::<S>).to_export()
+            }
+        }
+        fn echo<S: Slot>(ctx: &mut Ctx) -> i32 {
+            match called_as::<Echo, S>(ctx) {
+                Some(("env", "second")) => 2,
+                Some(_) => 1,
+                None => 0,
+            }
+        }
+
+        let mut module = generate_module();
+        let mut namespaces = StringTableBuilder::new();
+        let mut names = StringTableBuilder::new();
+        let mut vm_functions = Map::new();
+        for (slot, name) in [(3, "first"), (5, "second")].iter() {
+            module.info.imported_functions.push(ImportName {
+                namespace_index: namespaces.register("env"),
+                name_index: names.register(*name),
+            });
+            let func = match slot_export::<Echo>(*slot).unwrap() {
+                Export::Function { func, .. } => func.inner(),
+                _ => unreachable!(),
+            };
+            vm_functions.push(ImportedFunc {
+                func,
+                vmctx: ptr::null_mut(),
+            });
+        }
+        module.info.namespace_table = namespaces.finish();
+        module.info.name_table = names.finish();
+
+        let mut local_backing = LocalBacking {
+            memories: Map::new().into_boxed_map(),
+            tables: Map::new().into_boxed_map(),
+            globals: Map::new().into_boxed_map(),
+
+            vm_memories: Map::new().into_boxed_map(),
+            vm_tables: Map::new().into_boxed_map(),
+            vm_globals: Map::new().into_boxed_map(),
+        };
+        let mut import_backing = ImportBacking {
+            memories: Map::new().into_boxed_map(),
+            tables: Map::new().into_boxed_map(),
+            globals: Map::new().into_boxed_map(),
+
+            vm_functions: vm_functions.into_boxed_map(),
+            vm_memories: Map::new().into_boxed_map(),
+            vm_tables: Map::new().into_boxed_map(),
+            vm_globals: Map::new().into_boxed_map(),
+        };
+        let mut ctx = unsafe { Ctx::new(&mut local_backing, &mut import_backing, &module) };
+
+        let name = |slot| ctx.import_name(&slot_export::<Echo>(slot).unwrap());
+        assert_eq!(name(3), Some(("env", "first")));
+        assert_eq!(name(5), Some(("env", "second")));
+        assert_eq!(name(4), None);
+
+        let calls: Vec<i32> = (0..2)
+            .map(|index| unsafe {
+                let func = import_backing.vm_functions[ImportedFuncIndex::new(index)].func;
+                let func: extern "C" fn(&mut Ctx) -> i32 = mem::transmute(func);
+                func(&mut ctx)
+            })
+            .collect();
+        assert_eq!(calls, [1, 2]);
+    }
+
     fn cast_test_data(data: *mut c_void) -> &'static mut TestData {
         let test_data: &mut TestData = unsafe { &mut *(data as *mut TestData) };
         test_data
@@ -496,6 +772,7 @@ mod vm_ctx_tests {
         use super::Func;
         use crate::backend::{Backend, FuncResolver, ProtectedCaller, Token, UserTrapper};
         use crate::error::RuntimeResult;
+        use crate::memory::MemoryConfig;
         use crate::types::{FuncIndex, LocalFuncIndex, Value};
         use hashbrown::HashMap;
         use std::ptr::NonNull;
@@ -550,6 +827,7 @@ mod vm_ctx_tests {
                 func_assoc: Map::new(),
                 signatures: Map::new(),
                 backend: Backend::Cranelift,
+                memory_config: MemoryConfig::default(),
 
                 namespace_table: StringTable::new(),
                 name_table: StringTable::new(),
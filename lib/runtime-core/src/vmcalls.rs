@@ -12,6 +12,33 @@ use crate::{
 // |       LOCAL MEMORIES        |
 // +****************************+
 
+/// Grow a local memory with `grow`, taking the `delta` pages from the
+/// page budget of the instance, if it has one.
+fn grow_within_budget<F>(ctx: &mut vm::Ctx, delta: Pages, grow: F) -> i32
+where
+    F: FnOnce() -> Option<Pages>,
+{
+    if let Some(budget) = &ctx.page_budget {
+        if !budget.take(delta) {
+            return -1;
+        }
+    }
+    match grow() {
+        Some(old) => {
+            if ctx.page_budget.is_some() {
+                ctx.pages_taken = ctx.pages_taken + delta;
+            }
+            old.0 as i32
+        }
+        None => {
+            if let Some(budget) = &ctx.page_budget {
+                budget.give_back(delta);
+            }
+            -1
+        }
+    }
+}
+
 pub unsafe extern "C" fn local_static_memory_grow(
     ctx: &mut vm::Ctx,
     memory_index: LocalMemoryIndex,
@@ -20,11 +47,7 @@ pub unsafe extern "C" fn local_static_memory_grow(
     let local_memory = *ctx.memories.add(memory_index.index());
     let memory = (*local_memory).memory as *mut StaticMemory;
 
-    if let Some(old) = (*memory).grow(delta, &mut *local_memory) {
-        old.0 as i32
-    } else {
-        -1
-    }
+    grow_within_budget(ctx, delta, || (*memory).grow(delta, &mut *local_memory))
 }
 
 pub unsafe extern "C" fn local_static_memory_size(
@@ -45,11 +68,7 @@ pub unsafe extern "C" fn local_dynamic_memory_grow(
     let local_memory = *ctx.memories.add(memory_index.index());
     let memory = (*local_memory).memory as *mut DynamicMemory;
 
-    if let Some(old) = (*memory).grow(delta, &mut *local_memory) {
-        old.0 as i32
-    } else {
-        -1
-    }
+    grow_within_budget(ctx, delta, || (*memory).grow(delta, &mut *local_memory))
 }
 
 pub unsafe extern "C" fn local_dynamic_memory_size(
@@ -0,0 +1,231 @@
+//! Compilation, instantiation and call overhead of the default backend.
+//!
+//! These give performance work a shared yardstick. To check a change for
+//! regressions, save a baseline before it and compare against it after:
+//!
+//! ```text
+//! cargo bench -p wasmer-runtime -- --save-baseline before
+//! cargo bench -p wasmer-runtime -- --baseline before
+//! ```
+#[macro_use]
+extern crate criterion;
+
+use criterion::Criterion;
+use std::thread;
+use wabt::wat2wasm;
+use wasmer_runtime::{
+    compile, func, imports, instantiate, Ctx, Func, ImportObject, Instance, Value,
+};
+
+static WAT: &str = r#"
+(module
+  (import "env" "host" (func $host (param i32) (result i32)))
+  (memory 1)
+  (func (export "add_one") (param i32) (result i32)
+    get_local 0
+    i32.const 1
+    i32.add)
+  (func (export "call_host") (param i32) (result i32)
+    get_local 0
+    call $host)
+  (func (export "grow") (result i32)
+    i32.const 1
+    grow_memory))
+"#;
+
+/// The same loop of calls, direct and through the table, so the cost of
+/// `call_indirect` over `call` can be told apart from the cost of the loop.
+static CALLS_WAT: &str = r#"
+(module
+  (type $unary (func (param i32) (result i32)))
+  (table anyfunc (elem $inc $dec))
+  (func $inc (type $unary)
+    get_local 0
+    i32.const 1
+    i32.add)
+  (func $dec (type $unary)
+    get_local 0
+    i32.const 1
+    i32.sub)
+  (func (export "direct") (param $n i32) (result i32)
+    (local $acc i32)
+    block
+      loop
+        get_local $n
+        i32.eqz
+        br_if 1
+        get_local $acc
+        call $inc
+        set_local $acc
+        get_local $n
+        i32.const 1
+        i32.sub
+        set_local $n
+        br 0
+      end
+    end
+    get_local $acc)
+  (func (export "indirect") (param $n i32) (result i32)
+    (local $acc i32)
+    block
+      loop
+        get_local $n
+        i32.eqz
+        br_if 1
+        get_local $acc
+        ;; Alternate between the slots, like a dispatch through a vtable.
+        get_local $n
+        i32.const 1
+        i32.and
+        call_indirect (type $unary)
+        set_local $acc
+        get_local $n
+        i32.const 1
+        i32.sub
+        set_local $n
+        br 0
+      end
+    end
+    get_local $acc))
+"#;
+
+fn host(_ctx: &mut Ctx, n: i32) -> i32 {
+    n + 1
+}
+
+fn import_object() -> ImportObject {
+    imports! {
+        "env" => {
+            "host" => func!(host),
+        },
+    }
+}
+
+fn new_instance(wasm: &[u8]) -> Instance {
+    instantiate(wasm, &import_object()).unwrap()
+}
+
+fn bench_compile(c: &mut Criterion) {
+    let wasm = wat2wasm(WAT).unwrap();
+    c.bench_function("compile", move |b| b.iter(|| compile(&wasm).unwrap()));
+}
+
+fn bench_instantiate(c: &mut Criterion) {
+    let wasm = wat2wasm(WAT).unwrap();
+    let module = compile(&wasm).unwrap();
+    let import_object = import_object();
+    c.bench_function("instantiate", move |b| {
+        b.iter(|| module.instantiate(&import_object).unwrap())
+    });
+}
+
+fn bench_calls(c: &mut Criterion) {
+    let wasm = wat2wasm(WAT).unwrap();
+
+    // A cold call pays for instantiation before its first call.
+    let cold_wasm = wasm.clone();
+    c.bench_function("call_cold", move |b| {
+        b.iter(|| {
+            let instance = new_instance(&cold_wasm);
+            let add_one: Func<i32, i32> = instance.func("add_one").unwrap();
+            add_one.call(42).unwrap()
+        })
+    });
+
+    let warm_wasm = wasm.clone();
+    c.bench_function("call_warm", move |b| {
+        let instance = new_instance(&warm_wasm);
+        let add_one: Func<i32, i32> = instance.func("add_one").unwrap();
+        b.iter(|| add_one.call(42).unwrap())
+    });
+
+    let dynamic_wasm = wasm.clone();
+    c.bench_function("call_dynamic", move |b| {
+        let instance = new_instance(&dynamic_wasm);
+        b.iter(|| instance.call("add_one", &[Value::I32(42)]).unwrap())
+    });
+
+    c.bench_function("call_host_roundtrip", move |b| {
+        let instance = new_instance(&wasm);
+        let call_host: Func<i32, i32> = instance.func("call_host").unwrap();
+        b.iter(|| call_host.call(42).unwrap())
+    });
+}
+
+fn bench_indirect_calls(c: &mut Criterion) {
+    const CALLS: i32 = 10_000;
+    let wasm = wat2wasm(CALLS_WAT).unwrap();
+    c.bench_function_over_inputs(
+        "call_loop",
+        move |b, &export| {
+            let instance = instantiate(&wasm, &ImportObject::new()).unwrap();
+            let call_loop: Func<i32, i32> = instance.func(export).unwrap();
+            b.iter(|| call_loop.call(CALLS).unwrap())
+        },
+        vec!["direct", "indirect"],
+    );
+}
+
+fn bench_memory_grow(c: &mut Criterion) {
+    let wasm = wat2wasm(WAT).unwrap();
+    let module = compile(&wasm).unwrap();
+    let import_object = import_object();
+    // Memories can't shrink, so every iteration gets a fresh instance.
+    c.bench_function("memory_grow", move |b| {
+        b.iter_with_setup(
+            || module.instantiate(&import_object).unwrap(),
+            |instance| {
+                {
+                    let grow: Func<(), i32> = instance.func("grow").unwrap();
+                    assert_eq!(grow.call().unwrap(), 1);
+                }
+                instance
+            },
+        )
+    });
+}
+
+/// Each thread compiles and instantiates a module of its own, so the time
+/// per iteration stays flat as threads are added unless they contend.
+fn bench_parallel(c: &mut Criterion) {
+    const MODULES_PER_THREAD: usize = 8;
+    c.bench_function_over_inputs(
+        "compile_and_instantiate_parallel",
+        |b, &threads| {
+            let modules: Vec<_> = (0..threads)
+                .map(|n| {
+                    let wat = WAT.replace("i32.const 1", &format!("i32.const {}", n));
+                    wat2wasm(wat).unwrap()
+                })
+                .collect();
+            b.iter(|| {
+                let handles: Vec<_> = modules
+                    .iter()
+                    .cloned()
+                    .map(|wasm| {
+                        thread::spawn(move || {
+                            for _ in 0..MODULES_PER_THREAD {
+                                new_instance(&wasm);
+                            }
+                        })
+                    })
+                    .collect();
+                for handle in handles {
+                    handle.join().unwrap();
+                }
+            })
+        },
+        vec![1, 2, 4, 8],
+    );
+}
+
+criterion_group!(
+    benches,
+    bench_compile,
+    bench_instantiate,
+    bench_calls,
+    bench_indirect_calls,
+    bench_memory_grow,
+    bench_parallel
+);
+criterion_main!(benches);
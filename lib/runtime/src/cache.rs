@@ -1,4 +1,4 @@
-use crate::Module;
+use crate::{CompilerConfig, Module};
 use std::path::Path;
 use wasmer_runtime_core::cache::{hash_data, Cache as CoreCache};
 
@@ -96,6 +96,16 @@ impl Cache {
         wasmer_runtime_core::load_cache_with(self.0, default_compiler)
     }
 
+    /// The same as [`into_module`], but runs the code hook of `config`
+    /// over the code before the module can run it.
+    ///
+    /// [`into_module`]: #method.into_module
+    pub unsafe fn into_module_with_config(self, config: &CompilerConfig) -> Result<Module, Error> {
+        let default_compiler = super::default_compiler();
+
+        wasmer_runtime_core::load_cache_with_config(self.0, default_compiler, config)
+    }
+
     /// Compare the Sha256 hash of the wasm this cache was build
     /// from with some other WebAssembly.
     ///
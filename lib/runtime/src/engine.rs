@@ -0,0 +1,170 @@
+//! Compiled code shared by isolated stores, for hosts that run the
+//! modules of several tenants in one process.
+use crate::{
+    error::{CompileResult, CreationError, Result},
+    CompilerConfig, ImportObject, Instance, Module,
+};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use wasmer_runtime_core::{cache::hash_data, memory::PageBudget, units::Pages};
+
+/// Compiles each module once, however many stores instantiate it.
+///
+/// Modules are cached by the hash of their binary code. An `Engine` is
+/// cheap to clone, and the clones share the cache.
+#[derive(Clone, Default)]
+pub struct Engine {
+    config: CompilerConfig,
+    modules: Arc<Mutex<HashMap<[u8; 32], Module>>>,
+}
+
+impl Engine {
+    /// An engine that compiles with `config`.
+    pub fn new(config: CompilerConfig) -> Self {
+        Engine {
+            config,
+            modules: Arc::default(),
+        }
+    }
+
+    /// Compile `wasm`, or return the module already compiled from it.
+    pub fn compile(&self, wasm: &[u8]) -> CompileResult<Module> {
+        let hash = hash_data(wasm);
+        if let Some(module) = self.modules.lock().unwrap().get(&hash) {
+            return Ok(module.clone());
+        }
+        // Compiling doesn't hold the lock, so two stores compiling the same
+        // module at once may both compile it. The first one is kept.
+        let module =
+            crate::compile_with_config(wasm, crate::default_compiler(), self.config.clone())?;
+        let mut modules = self.modules.lock().unwrap();
+        Ok(modules.entry(hash).or_insert(module).clone())
+    }
+
+    /// The number of modules in the cache.
+    pub fn cached_modules(&self) -> usize {
+        self.modules.lock().unwrap().len()
+    }
+
+    /// Drop the cached modules. The modules that are still in use stay
+    /// alive until their last instance is dropped.
+    pub fn clear_cache(&self) {
+        self.modules.lock().unwrap().clear();
+    }
+}
+
+/// The limits of a `Store`, none by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StoreLimits {
+    /// How many instances the store can hold.
+    pub max_instances: Option<usize>,
+    /// How many pages the memories of all the instances of the store can
+    /// have together, when they're instantiated and as they grow.
+    pub max_pages: Option<Pages>,
+}
+
+/// The instances of a tenant, isolated from the ones of other stores
+/// but compiled by a shared `Engine`.
+pub struct Store {
+    engine: Engine,
+    limits: StoreLimits,
+    instances: Vec<Box<Instance>>,
+    /// When each instance was last used, and whether it's parked since.
+    used: Vec<(Instant, bool)>,
+    pages: Option<Arc<PageBudget>>,
+}
+
+impl Store {
+    pub fn new(engine: &Engine, limits: StoreLimits) -> Self {
+        Store {
+            engine: engine.clone(),
+            limits,
+            instances: Vec::new(),
+            used: Vec::new(),
+            pages: limits.max_pages.map(|max| Arc::new(PageBudget::new(max))),
+        }
+    }
+
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// Compile `wasm` through the engine and instantiate it in this
+    /// store, within the limits of the store.
+    pub fn instantiate(&mut self, wasm: &[u8], imports: &ImportObject) -> Result<&mut Instance> {
+        let module = self.engine.compile(wasm)?;
+        if let Some(max_instances) = self.limits.max_instances {
+            if self.instances.len() >= max_instances {
+                return Err(CreationError::LimitExceeded(format!(
+                    "the store can't hold more than {} instances",
+                    max_instances
+                ))
+                .into());
+            }
+        }
+        let too_many_pages = |budget: &PageBudget| {
+            CreationError::LimitExceeded(format!(
+                "the memories of the store can't have more than {} pages",
+                budget.max().0
+            ))
+        };
+        if let Some(budget) = &self.pages {
+            // Don't even allocate the memories when they wouldn't fit.
+            let pages = module
+                .0
+                .info
+                .memories
+                .iter()
+                .map(|(_, desc)| desc.minimum.0)
+                .sum::<u32>();
+            if budget.used().0 + pages > budget.max().0 {
+                return Err(too_many_pages(budget).into());
+            }
+        }
+
+        let mut instance = module.instantiate(imports)?;
+        if let Some(budget) = &self.pages {
+            if !instance.set_page_budget(Arc::clone(budget)) {
+                return Err(too_many_pages(budget).into());
+            }
+        }
+        self.instances.push(Box::new(instance));
+        self.used.push((Instant::now(), false));
+        Ok(self.instances.last_mut().unwrap())
+    }
+
+    /// The instance at `index`, in the order they were instantiated,
+    /// which counts as using it.
+    pub fn instance(&mut self, index: usize) -> Option<&mut Instance> {
+        let instance = self.instances.get_mut(index)?;
+        self.used[index] = (Instant::now(), false);
+        Some(&mut **instance)
+    }
+
+    /// All the instances, which doesn't count as using them.
+    pub fn instances(&mut self) -> impl Iterator<Item = &mut Instance> {
+        self.instances.iter_mut().map(|instance| &mut **instance)
+    }
+
+    /// Park the memories of the instances that weren't used for `idle`,
+    /// with `Instance::park`, for hosts under memory pressure to keep
+    /// many idle instances alive cheaply. Their pages are read back from
+    /// `dir` as they're used again. Returns how many instances were
+    /// parked.
+    pub fn park_idle(&mut self, idle: Duration, dir: &Path) -> io::Result<usize> {
+        let now = Instant::now();
+        let mut parked = 0;
+        for (instance, used) in self.instances.iter().zip(&mut self.used) {
+            if used.1 || now.duration_since(used.0) < idle {
+                continue;
+            }
+            instance.park(dir)?;
+            used.1 = true;
+            parked += 1;
+        }
+        Ok(parked)
+    }
+}
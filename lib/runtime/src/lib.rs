@@ -74,21 +74,30 @@
 //! [`wasmer-clif-backend`]: https://crates.io/crates/wasmer-clif-backend
 //! [`compile_with`]: fn.compile_with.html
 
+pub use wasmer_runtime_core::adapter::{Adapter, Convention};
 pub use wasmer_runtime_core::global::Global;
 pub use wasmer_runtime_core::import::ImportObject;
 pub use wasmer_runtime_core::instance::{DynFunc, Instance};
+pub use wasmer_runtime_core::linker::Linker;
 pub use wasmer_runtime_core::memory::Memory;
 pub use wasmer_runtime_core::module::Module;
 pub use wasmer_runtime_core::table::Table;
 pub use wasmer_runtime_core::types::Value;
 pub use wasmer_runtime_core::vm::Ctx;
 
+pub use wasmer_runtime_core::backend::{CodeHook, CompilerConfig, FrameHook, StackConfig};
+pub use wasmer_runtime_core::features::Features;
 pub use wasmer_runtime_core::Func;
-pub use wasmer_runtime_core::{compile_with, validate};
+pub use wasmer_runtime_core::{
+    compile_streaming_with, compile_with, compile_with_config, validate,
+};
 pub use wasmer_runtime_core::{func, imports};
 
 pub mod memory {
-    pub use wasmer_runtime_core::memory::{Atomic, Atomically, Memory, MemoryView};
+    pub use wasmer_runtime_core::memory::{
+        diff, Atomic, Atomically, HostMemory, Memory, MemoryConfig, MemoryPlacement, MemoryView,
+        PageBudget,
+    };
 }
 
 pub mod wasm {
@@ -104,6 +113,11 @@ pub mod error {
     pub use wasmer_runtime_core::error::*;
 }
 
+pub mod transform {
+    //! Rewriting the imports of modules.
+    pub use wasmer_runtime_core::transform::{rename_imports, ImportRenames};
+}
+
 pub mod units {
     //! Various unit types.
     pub use wasmer_runtime_core::units::{Bytes, Pages};
@@ -111,12 +125,16 @@ pub mod units {
 
 #[cfg(feature = "cache")]
 mod cache;
+#[cfg(feature = "cache")]
+mod engine;
 
 #[cfg(feature = "default-compiler")]
 use wasmer_runtime_core::backend::Compiler;
 
 #[cfg(feature = "cache")]
 pub use self::cache::Cache;
+#[cfg(feature = "cache")]
+pub use self::engine::{Engine, Store, StoreLimits};
 
 /// Compile WebAssembly binary code into a [`Module`].
 /// This function is useful if it is necessary to
@@ -136,6 +154,23 @@ pub fn compile(wasm: &[u8]) -> error::CompileResult<Module> {
     wasmer_runtime_core::compile_with(&wasm[..], default_compiler())
 }
 
+/// Compile WebAssembly binary code read from `reader` into a [`Module`],
+/// checking each section as it arrives instead of once the whole module
+/// has been read.
+///
+/// [`Module`]: struct.Module.html
+///
+/// # Errors:
+/// If the operation fails, the function returns `Err(error::CompileError::...)`.
+#[cfg(feature = "default-compiler")]
+pub fn compile_streaming<R: std::io::Read>(reader: R) -> error::CompileResult<Module> {
+    wasmer_runtime_core::compile_streaming_with(
+        reader,
+        default_compiler(),
+        CompilerConfig::default(),
+    )
+}
+
 /// Compile and instantiate WebAssembly code without
 /// creating a [`Module`].
 ///
@@ -0,0 +1,69 @@
+//! The code hook sees the code of a module before it can run, whether it
+//! was just compiled or loaded from a cache.
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use wabt::wat2wasm;
+use wasmer_clif_backend::CraneliftCompiler;
+use wasmer_runtime::{compile_cache, compile_with_config, imports, CodeHook, CompilerConfig, Func};
+
+fn wasm() -> Vec<u8> {
+    wat2wasm(
+        r#"
+        (module
+          (func (export "get") (result i32)
+            i32.const 42))
+        "#,
+    )
+    .unwrap()
+}
+
+/// A config whose hook counts the bytes of code it's shown.
+fn counting_config() -> (CompilerConfig, Arc<AtomicUsize>) {
+    let seen = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&seen);
+    let config = CompilerConfig {
+        code_hook: Some(CodeHook::new(move |code| {
+            counter.fetch_add(code.len(), Ordering::SeqCst);
+            Ok(())
+        })),
+        ..CompilerConfig::default()
+    };
+    (config, seen)
+}
+
+fn refusing_config() -> CompilerConfig {
+    CompilerConfig {
+        code_hook: Some(CodeHook::new(|_| Err("unsigned".to_string()))),
+        ..CompilerConfig::default()
+    }
+}
+
+fn get(module: &wasmer_runtime::Module) -> i32 {
+    let instance = module.instantiate(&imports! {}).unwrap();
+    let get: Func<(), i32> = instance.func("get").unwrap();
+    get.call().unwrap()
+}
+
+#[test]
+fn the_hook_sees_compiled_code() {
+    let (config, seen) = counting_config();
+    let module = compile_with_config(&wasm(), &CraneliftCompiler::new(), config).unwrap();
+    assert!(seen.load(Ordering::SeqCst) > 0);
+    assert_eq!(get(&module), 42);
+
+    assert!(compile_with_config(&wasm(), &CraneliftCompiler::new(), refusing_config()).is_err());
+}
+
+#[test]
+fn the_hook_sees_code_loaded_from_a_cache() {
+    let (config, seen) = counting_config();
+    let cache = compile_cache(&wasm()).unwrap();
+    let module = unsafe { cache.into_module_with_config(&config) }.unwrap();
+    assert!(seen.load(Ordering::SeqCst) > 0);
+    assert_eq!(get(&module), 42);
+
+    let cache = compile_cache(&wasm()).unwrap();
+    assert!(unsafe { cache.into_module_with_config(&refusing_config()) }.is_err());
+}
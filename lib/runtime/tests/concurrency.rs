@@ -0,0 +1,46 @@
+//! Compiling and instantiating distinct modules on many threads at once.
+use std::thread;
+use wabt::wat2wasm;
+use wasmer_runtime::{compile, imports, Func};
+
+const THREADS: i32 = 8;
+
+/// A module with a table, so instantiating it registers signatures, that
+/// returns `n` from `get`.
+fn module_returning(n: i32) -> Vec<u8> {
+    wat2wasm(format!(
+        r#"
+        (module
+          (type $t (func (result i32)))
+          (table 2 anyfunc)
+          (elem (i32.const 0) $n $n)
+          (func $n (type $t)
+            i32.const {})
+          (func (export "get") (result i32)
+            i32.const 1
+            call_indirect (type $t)))
+        "#,
+        n
+    ))
+    .unwrap()
+}
+
+#[test]
+fn compiles_and_instantiates_on_many_threads() {
+    let threads: Vec<_> = (0..THREADS)
+        .map(|n| {
+            let wasm = module_returning(n);
+            thread::spawn(move || {
+                for _ in 0..16 {
+                    let module = compile(&wasm).unwrap();
+                    let instance = module.instantiate(&imports! {}).unwrap();
+                    let get: Func<(), i32> = instance.func("get").unwrap();
+                    assert_eq!(get.call().unwrap(), n);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
@@ -0,0 +1,69 @@
+//! A core dump restores the memories and the local mutable globals of the
+//! instance it was taken from into a fresh instance of the module.
+use wabt::wat2wasm;
+use wasmer_runtime::{compile, imports, Func, Global, Instance, Value};
+use wasmer_runtime_core::coredump::CoreDump;
+
+fn instance() -> Instance {
+    let wasm = wat2wasm(
+        r#"
+        (module
+          (import "env" "base" (global $base i32))
+          (global $fixed i32 (i32.const 7))
+          (global $counter (mut i32) (i32.const 0))
+          (memory 1)
+          (func (export "bump")
+            get_global $counter
+            i32.const 1
+            i32.add
+            set_global $counter
+            i32.const 16
+            i32.const 42
+            i32.store8)
+          (func (export "state") (result i32)
+            get_global $base
+            get_global $fixed
+            i32.add
+            get_global $counter
+            i32.add
+            i32.const 16
+            i32.load8_u
+            i32.add))
+        "#,
+    )
+    .unwrap();
+    let import_object = imports! {
+        "env" => {
+            "base" => Global::new(Value::I32(100)),
+        },
+    };
+    compile(&wasm).unwrap().instantiate(&import_object).unwrap()
+}
+
+fn state(instance: &Instance) -> i32 {
+    let state: Func<(), i32> = instance.func("state").unwrap();
+    state.call().unwrap()
+}
+
+#[test]
+fn only_the_local_mutable_globals_are_restored() {
+    let crashed = instance();
+    let bump: Func<(), ()> = crashed.func("bump").unwrap();
+    bump.call().unwrap();
+    bump.call().unwrap();
+    assert_eq!(state(&crashed), 100 + 7 + 2 + 42);
+
+    let mut dump = CoreDump::capture(crashed.context(), "test");
+    assert_eq!(
+        dump.globals,
+        vec![Value::I32(100), Value::I32(7), Value::I32(2)]
+    );
+    // Neither the imported nor the immutable global is the dump's to set.
+    dump.globals[0] = Value::I32(1000);
+    dump.globals[1] = Value::I32(70);
+
+    let mut restored = instance();
+    assert_eq!(state(&restored), 100 + 7);
+    dump.restore(restored.context_mut()).unwrap();
+    assert_eq!(state(&restored), 100 + 7 + 2 + 42);
+}
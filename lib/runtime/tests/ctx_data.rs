@@ -0,0 +1,30 @@
+//! The data the host keeps in the `Ctx` of an instance is finalized when
+//! the instance is dropped, and only then.
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use wabt::wat2wasm;
+use wasmer_runtime::{compile, imports};
+
+static FINALIZED: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn finalize(data: *mut c_void) {
+    unsafe { drop(Box::from_raw(data as *mut u32)) };
+    FINALIZED.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+fn dropping_the_instance_finalizes_its_data() {
+    let module = compile(&wat2wasm("(module)").unwrap()).unwrap();
+    let mut instance = module.instantiate(&imports! {}).unwrap();
+    let ctx = instance.context_mut();
+    ctx.data = Box::into_raw(Box::new(7u32)) as *mut c_void;
+    ctx.data_finalizer = Some(finalize);
+    assert_eq!(FINALIZED.load(Ordering::SeqCst), 0);
+    drop(instance);
+    assert_eq!(FINALIZED.load(Ordering::SeqCst), 1);
+
+    // The `Ctx` of an instance whose imports don't resolve has no data.
+    let module = compile(&wat2wasm(r#"(module (import "env" "f" (func)))"#).unwrap()).unwrap();
+    assert!(module.instantiate(&imports! {}).is_err());
+    assert_eq!(FINALIZED.load(Ordering::SeqCst), 1);
+}
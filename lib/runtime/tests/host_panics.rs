@@ -0,0 +1,43 @@
+//! Host functions that panic trap the guest, on whichever thread calls it.
+use std::thread;
+use wabt::wat2wasm;
+use wasmer_runtime::{
+    compile,
+    error::{CallError, RuntimeError},
+    func, imports, Ctx,
+};
+
+fn panics(_ctx: &mut Ctx) {
+    panic!("the host gave up");
+}
+
+#[test]
+fn a_panic_on_another_thread_traps() {
+    let wasm = wat2wasm(
+        r#"
+        (module
+          (import "env" "panics" (func $panics))
+          (func (export "run")
+            call $panics))
+        "#,
+    )
+    .unwrap();
+    let module = compile(&wasm).unwrap();
+
+    thread::spawn(move || {
+        let import_object = imports! {
+            "env" => {
+                "panics" => func!(panics),
+            },
+        };
+        let instance = module.instantiate(&import_object).unwrap();
+        match instance.call("run", &[]) {
+            Err(CallError::Runtime(RuntimeError::User { msg })) => {
+                assert!(msg.contains("the host gave up"), "{}", msg)
+            }
+            other => panic!("expected a trap, got {:?}", other),
+        }
+    })
+    .join()
+    .unwrap();
+}
@@ -0,0 +1,81 @@
+//! Stubbed imports let any module be instantiated, and trap with the name
+//! of the import or return zero when they are called.
+use wabt::wat2wasm;
+use wasmer_runtime::{
+    compile,
+    error::{CallError, RuntimeError},
+    func, imports, Ctx, ImportObject, Module, Value,
+};
+use wasmer_runtime_core::import::Stub;
+
+fn module() -> Module {
+    let wasm = wat2wasm(
+        r#"
+        (module
+          (import "env" "count" (func $count (param i32 i64) (result i32)))
+          (import "env" "ratio" (func $ratio (param f64) (result f32)))
+          (import "env" "total" (func $total (result i64)))
+          (import "env" "log" (func $log (param i32)))
+          (import "env" "memory" (memory 1))
+          (import "env" "base" (global i32))
+          (func (export "count") (result i32)
+            i32.const 1
+            i64.const 2
+            call $count)
+          (func (export "ratio") (result f32)
+            f64.const 0.5
+            call $ratio)
+          (func (export "total") (result i64)
+            call $total)
+          (func (export "log")
+            i32.const 3
+            call $log))
+        "#,
+    )
+    .unwrap();
+    compile(&wasm).unwrap()
+}
+
+fn trap_message(result: Result<Vec<Value>, CallError>) -> String {
+    match result {
+        Err(CallError::Runtime(RuntimeError::User { msg })) => msg,
+        other => panic!("expected a trap, got {:?}", other),
+    }
+}
+
+#[test]
+fn trap_stubs_name_the_import_that_was_called() {
+    let module = module();
+    let instance = module
+        .instantiate(&ImportObject::stubbed_for(&module))
+        .unwrap();
+    for &(export, import) in &[
+        ("count", "env.count"),
+        ("ratio", "env.ratio"),
+        ("total", "env.total"),
+        ("log", "env.log"),
+    ] {
+        let msg = trap_message(instance.call(export, &[]));
+        assert!(msg.contains(&format!("`{}`", import)), "{}", msg);
+    }
+}
+
+#[test]
+fn zero_stubs_return_zero_of_the_import_type() {
+    fn count(_ctx: &mut Ctx, a: i32, b: i64) -> i32 {
+        a + b as i32
+    }
+
+    let module = module();
+    let mut import_object = imports! {
+        "env" => {
+            "count" => func!(count),
+        },
+    };
+    import_object.stub_missing(&module, Stub::Zero);
+    let instance = module.instantiate(&import_object).unwrap();
+    assert_eq!(instance.call("count", &[]).unwrap(), vec![Value::I32(3)]);
+    assert_eq!(instance.call("ratio", &[]).unwrap(), vec![Value::F32(0.0)]);
+    assert_eq!(instance.call("total", &[]).unwrap(), vec![Value::I64(0)]);
+    assert_eq!(instance.call("log", &[]).unwrap(), vec![]);
+}
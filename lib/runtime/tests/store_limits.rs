@@ -0,0 +1,68 @@
+//! The memories of the instances of a store share its page limit, as
+//! they're instantiated and as they grow.
+use std::sync::Arc;
+use wabt::wat2wasm;
+use wasmer_runtime::{
+    compile, imports, memory::PageBudget, units::Pages, Engine, Func, Store, StoreLimits,
+};
+
+fn wasm() -> Vec<u8> {
+    wat2wasm(
+        r#"
+        (module
+          (memory 1)
+          (func (export "grow") (param i32) (result i32)
+            get_local 0
+            grow_memory))
+        "#,
+    )
+    .unwrap()
+}
+
+fn grow(store: &mut Store, index: usize, delta: i32) -> i32 {
+    let grow: Func<i32, i32> = store.instance(index).unwrap().func("grow").unwrap();
+    grow.call(delta).unwrap()
+}
+
+#[test]
+fn memory_grow_stays_within_the_store_limit() {
+    let limits = StoreLimits {
+        max_pages: Some(Pages(4)),
+        ..StoreLimits::default()
+    };
+    let mut store = Store::new(&Engine::default(), limits);
+    store.instantiate(&wasm(), &imports! {}).unwrap();
+    store.instantiate(&wasm(), &imports! {}).unwrap();
+
+    assert_eq!(grow(&mut store, 0, 1), 1);
+    assert_eq!(grow(&mut store, 1, 2), -1);
+    assert_eq!(grow(&mut store, 1, 1), 1);
+    assert_eq!(grow(&mut store, 0, 1), -1);
+    assert!(store.instantiate(&wasm(), &imports! {}).is_err());
+}
+
+#[test]
+fn stores_have_limits_of_their_own() {
+    let engine = Engine::default();
+    let limits = StoreLimits {
+        max_pages: Some(Pages(1)),
+        ..StoreLimits::default()
+    };
+    let mut first = Store::new(&engine, limits);
+    let mut second = Store::new(&engine, limits);
+    first.instantiate(&wasm(), &imports! {}).unwrap();
+    second.instantiate(&wasm(), &imports! {}).unwrap();
+    assert_eq!(grow(&mut first, 0, 1), -1);
+    assert_eq!(engine.cached_modules(), 1);
+}
+
+#[test]
+fn host_grows_are_not_given_back_to_the_budget() {
+    let budget = Arc::new(PageBudget::new(Pages(4)));
+    let mut instance = compile(&wasm()).unwrap().instantiate(&imports! {}).unwrap();
+    assert!(instance.set_page_budget(Arc::clone(&budget)));
+    assert_eq!(instance.context().memory(0).grow(Pages(2)), Some(Pages(1)));
+    assert_eq!(budget.used(), Pages(1));
+    drop(instance);
+    assert_eq!(budget.used(), Pages(0));
+}
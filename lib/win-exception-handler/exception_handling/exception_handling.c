@@ -5,6 +5,7 @@
 #define CALL_FIRST 1
 
 __declspec(thread) jmp_buf jmpBuf;
+__declspec(thread) DWORD caughtExceptionCode;
 __declspec(thread) PVOID caughtExceptionAddress;
 __declspec(thread) DWORD64 caughtInstructionPointer;
 __declspec(thread) PVOID savedStackPointer;
@@ -24,6 +25,22 @@ static LONG WINAPI
 exceptionHandler(struct _EXCEPTION_POINTERS *ExceptionInfo) {
     EXCEPTION_RECORD* pExceptionRecord = ExceptionInfo->ExceptionRecord;
     PCONTEXT pCONTEXT = ExceptionInfo->ContextRecord;
+
+    // Leave every other exception, e.g. the ones Rust panics are made of,
+    // to the handlers of the frames they're raised in.
+    switch (pExceptionRecord->ExceptionCode) {
+        case EXCEPTION_ACCESS_VIOLATION:
+        case EXCEPTION_ILLEGAL_INSTRUCTION:
+        case EXCEPTION_INT_DIVIDE_BY_ZERO:
+        case EXCEPTION_INT_OVERFLOW:
+        case EXCEPTION_STACK_OVERFLOW:
+        case EARLY_TRAP_EXCEPTION_CODE:
+            break;
+        default:
+            return EXCEPTION_CONTINUE_SEARCH;
+    }
+
+    caughtExceptionCode = pExceptionRecord->ExceptionCode;
     caughtExceptionAddress = pExceptionRecord->ExceptionAddress;
     caughtInstructionPointer = pCONTEXT->Rip;
     if (alreadyHandlingException == TRUE) {
@@ -63,12 +80,14 @@ uint8_t callProtected(trampoline_t trampoline,
         return TRUE;
     }
 
-    out_result->code = (uint64_t)signum;
+    out_result->code = (uint64_t)caughtExceptionCode;
     out_result->exceptionAddress = (uint64_t)caughtExceptionAddress;
     out_result->instructionPointer = caughtInstructionPointer;
 
+    caughtExceptionCode = 0;
     caughtExceptionAddress = 0;
     caughtInstructionPointer = 0;
+    alreadyHandlingException = FALSE;
 
     return FALSE;
 }
@@ -8,6 +8,9 @@ struct wasmer_instance_context_t;
 
 typedef void(*trampoline_t)(struct wasmer_instance_context_t*,  const struct func_t*, const uint64_t*, uint64_t*);
 
+// The code of the exception raised to trap out of a host function.
+#define EARLY_TRAP_EXCEPTION_CODE 0xE0000001
+
 struct call_protected_result_t {
     uint64_t code;
     uint64_t exceptionAddress;
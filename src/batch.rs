@@ -0,0 +1,287 @@
+//! Running a batch of modules, each with its own arguments and input, a
+//! few at a time, and collecting how each one went into a report.
+//!
+//! Every job runs in a `wasmer run` process of its own, so a job that
+//! crashes, hangs or floods its output only takes itself down, and its
+//! output can't mix with the output of the others.
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How much of the output of a job is kept when it doesn't say.
+const DEFAULT_MAX_OUTPUT: usize = 1 << 20;
+
+/// A module to run, and how.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    /// The name of the job in the report. Defaults to the module.
+    #[serde(default)]
+    pub name: Option<String>,
+    pub module: PathBuf,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// What the job reads from stdin. Without it, stdin is empty.
+    #[serde(default)]
+    pub stdin: Option<String>,
+    /// How long the job can run before it's killed.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// How many bytes of stdout and of stderr are kept, each.
+    #[serde(default)]
+    pub max_output: Option<usize>,
+}
+
+/// The jobs of a batch, as a manifest describes them:
+///
+/// ```json
+/// {
+///     "concurrency": 4,
+///     "jobs": [
+///         { "module": "hello.wasm", "args": ["world"], "timeout_secs": 10 },
+///         { "name": "echo", "module": "cat.wasm", "stdin": "some input" }
+///     ]
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Batch {
+    /// How many jobs run at once.
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    pub jobs: Vec<Job>,
+}
+
+fn default_concurrency() -> usize {
+    1
+}
+
+/// How a job went.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JobReport {
+    pub name: String,
+    /// The exit code of the job, if it exited rather than being killed.
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub duration_ms: u64,
+    pub stdout: String,
+    pub stderr: String,
+    /// Whether stdout or stderr was longer than what was kept of it.
+    pub truncated: bool,
+    /// Why the job couldn't be run at all.
+    pub error: Option<String>,
+}
+
+impl JobReport {
+    pub fn passed(&self) -> bool {
+        self.exit_code == Some(0) && !self.timed_out
+    }
+}
+
+/// How the jobs of a batch went, in the order of the manifest.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    pub passed: usize,
+    pub failed: usize,
+    pub jobs: Vec<JobReport>,
+}
+
+impl Batch {
+    /// Read the manifest at `path`. The paths of its modules are relative
+    /// to the directory of the manifest.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let mut batch: Batch = serde_json::from_str(&text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        for job in &mut batch.jobs {
+            job.module = base.join(&job.module);
+        }
+        Ok(batch)
+    }
+
+    /// Run every job with `program`, which is the wasmer binary, as
+    /// `program run <module> -- <args>`.
+    pub fn run(&self, program: &Path) -> Report {
+        let jobs = Arc::new(self.jobs.clone());
+        let next = Arc::new(AtomicUsize::new(0));
+        let reports = Arc::new(Mutex::new(vec![None; jobs.len()]));
+        let workers: Vec<_> = (0..self.concurrency.max(1).min(jobs.len()))
+            .map(|_| {
+                let (jobs, next, reports) = (jobs.clone(), next.clone(), reports.clone());
+                let program = program.to_owned();
+                thread::spawn(move || loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let job = match jobs.get(index) {
+                        Some(job) => job,
+                        None => return,
+                    };
+                    let report = run_job(&program, job);
+                    reports.lock().unwrap()[index] = Some(report);
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let jobs: Vec<JobReport> = reports
+            .lock()
+            .unwrap()
+            .drain(..)
+            .map(|report| report.expect("every job is run"))
+            .collect();
+        let passed = jobs.iter().filter(|job| job.passed()).count();
+        Report {
+            passed,
+            failed: jobs.len() - passed,
+            jobs,
+        }
+    }
+}
+
+fn run_job(program: &Path, job: &Job) -> JobReport {
+    let name = job
+        .name
+        .clone()
+        .unwrap_or_else(|| job.module.to_string_lossy().into_owned());
+    let start = Instant::now();
+    let mut report = JobReport {
+        name,
+        exit_code: None,
+        timed_out: false,
+        duration_ms: 0,
+        stdout: String::new(),
+        stderr: String::new(),
+        truncated: false,
+        error: None,
+    };
+
+    let child = Command::new(program)
+        .arg("run")
+        .arg(&job.module)
+        .arg("--")
+        .args(&job.args)
+        .stdin(if job.stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(err) => {
+            report.error = Some(format!("can't start {}: {}", program.display(), err));
+            return report;
+        }
+    };
+
+    let max_output = job.max_output.unwrap_or(DEFAULT_MAX_OUTPUT);
+    let stdin = child.stdin.take().map(|mut pipe| {
+        let input = job.stdin.clone().unwrap_or_default();
+        // A job that exits without reading its input closes the pipe.
+        thread::spawn(move || drop(pipe.write_all(input.as_bytes())))
+    });
+    let stdout = child
+        .stdout
+        .take()
+        .map(|pipe| thread::spawn(move || read_capped(pipe, max_output)));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|pipe| thread::spawn(move || read_capped(pipe, max_output)));
+
+    let timeout = job.timeout_secs.map(Duration::from_secs);
+    match wait(&mut child, timeout) {
+        Ok((status, timed_out)) => {
+            report.exit_code = status.code();
+            report.timed_out = timed_out;
+        }
+        Err(err) => report.error = Some(format!("can't wait for the job: {}", err)),
+    }
+    let elapsed = start.elapsed();
+    report.duration_ms = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
+
+    if let Some(stdin) = stdin {
+        let _ = stdin.join();
+    }
+    for (pipe, output) in vec![(stdout, &mut report.stdout), (stderr, &mut report.stderr)] {
+        if let Some((bytes, truncated)) = pipe.and_then(|pipe| pipe.join().ok()) {
+            *output = String::from_utf8_lossy(&bytes).into_owned();
+            report.truncated |= truncated;
+        }
+    }
+    report
+}
+
+/// Wait for `child` to exit, killing it once `timeout` is over, and tell
+/// whether it had to be.
+fn wait(child: &mut Child, timeout: Option<Duration>) -> io::Result<(ExitStatus, bool)> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return child.wait().map(|status| (status, false)),
+    };
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok((status, false));
+        }
+        if Instant::now() >= deadline {
+            // The job may exit on its own in between.
+            let _ = child.kill();
+            return child.wait().map(|status| (status, true));
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Read all of `pipe`, keeping its first `max` bytes, and tell whether
+/// there were more.
+fn read_capped<R: Read>(mut pipe: R, max: usize) -> (Vec<u8>, bool) {
+    let mut kept = Vec::new();
+    let mut truncated = false;
+    let mut buf = [0; 8192];
+    loop {
+        match pipe.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => {
+                let room = max - kept.len();
+                kept.extend_from_slice(&buf[..read.min(room)]);
+                truncated |= read > room;
+            }
+        }
+    }
+    (kept, truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_start_of_long_output() {
+        let output = vec![b'x'; 10_000];
+        assert_eq!(read_capped(&output[..], 10_000), (output.clone(), false));
+        let (kept, truncated) = read_capped(&output[..], 100);
+        assert_eq!(kept.len(), 100);
+        assert!(truncated);
+    }
+
+    #[test]
+    fn parses_a_manifest() {
+        let batch: Batch =
+            serde_json::from_str(r#"{ "jobs": [{ "module": "a.wasm", "stdin": "x" }] }"#).unwrap();
+        assert_eq!(batch.concurrency, 1);
+        assert_eq!(batch.jobs[0].module, PathBuf::from("a.wasm"));
+        assert_eq!(batch.jobs[0].stdin.as_ref().map(String::as_str), Some("x"));
+        assert!(serde_json::from_str::<Batch>(r#"{ "jobs": [], "memory": 1 }"#).is_err());
+    }
+}
@@ -3,41 +3,29 @@ extern crate structopt;
 use std::fs::File;
 use std::io;
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::exit;
 
 use structopt::StructOpt;
 
+use serde_json::json;
+use wasmer::batch::Batch;
+use wasmer::run_config::{RunConfig, RUN_CONFIG_FILE};
 use wasmer::webassembly::InstanceABI;
 use wasmer::*;
 use wasmer_emscripten;
+use wasmer_runtime_core::{
+    analysis::Analysis,
+    backend::{CompilerConfig, FrameHook, StackConfig},
+    coredump::CoreDump,
+    disasm::disassemble,
+    features::Features,
+    structures::TypedIndex,
+    types::Value,
+    units::Pages,
+};
 
-#[derive(Debug, StructOpt)]
-#[structopt(name = "wasmer", about = "Wasm execution runtime.")]
-/// The options for the wasmer Command Line Interface
-enum CLIOptions {
-    /// Run a WebAssembly file. Formats accepted: wasm, wast
-    #[structopt(name = "run")]
-    Run(Run),
-
-    /// Update wasmer to the latest version
-    #[structopt(name = "self-update")]
-    SelfUpdate,
-}
-
-#[derive(Debug, StructOpt)]
-struct Run {
-    #[structopt(short = "d", long = "debug")]
-    debug: bool,
-
-    /// Input file
-    #[structopt(parse(from_os_str))]
-    path: PathBuf,
-
-    /// Application arguments
-    #[structopt(name = "--", raw(multiple = "true"))]
-    args: Vec<String>,
-}
+include!("../cli.rs");
 
 /// Read the contents of a file
 fn read_file_contents(path: &PathBuf) -> Result<Vec<u8>, io::Error> {
@@ -49,9 +37,33 @@ fn read_file_contents(path: &PathBuf) -> Result<Vec<u8>, io::Error> {
     Ok(buffer)
 }
 
+/// Read the run configuration of `options`, if it has one: the one it
+/// names, or the `wasmer.toml` of the current directory when it isn't
+/// given a module.
+fn read_run_config(options: &Run) -> Result<RunConfig, String> {
+    let path = match (&options.config, &options.path) {
+        (Some(path), _) => path.clone(),
+        (None, None) if Path::new(RUN_CONFIG_FILE).is_file() => PathBuf::from(RUN_CONFIG_FILE),
+        (None, _) => return Ok(RunConfig::default()),
+    };
+    RunConfig::load(&path).map_err(|err| err.to_string())
+}
+
 /// Execute a wasm/wat file
 fn execute_wasm(options: &Run) -> Result<(), String> {
-    let wasm_path = &options.path;
+    let run_config = read_run_config(options)?;
+    let wasm_path = options
+        .path
+        .as_ref()
+        .or_else(|| run_config.module.as_ref())
+        .ok_or_else(|| format!("No module to run, and no module in {}", RUN_CONFIG_FILE))?;
+    let mut allow_fs = run_config.preopens.clone();
+    allow_fs.extend(options.allow_fs.iter().cloned());
+    let args = if options.args.is_empty() {
+        &run_config.args
+    } else {
+        &options.args
+    };
 
     let mut wasm_binary: Vec<u8> = read_file_contents(wasm_path).map_err(|err| {
         format!(
@@ -61,21 +73,113 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
         )
     })?;
 
+    if let Some(legacy) = utils::detect_legacy_module(&wasm_binary) {
+        return Err(format!(
+            "Can't run {}: {}",
+            wasm_path.as_os_str().to_string_lossy(),
+            legacy
+        ));
+    }
+
     if !utils::is_wasm_binary(&wasm_binary) {
-        wasm_binary = wabt::wat2wasm(wasm_binary)
-            .map_err(|e| format!("Can't convert from wast to wasm: {:?}", e))?;
+        wasm_binary = utils::wat2wasm(&wasm_binary)
+            .map_err(|e| format!("Can't convert from wast to wasm: {}", e))?;
+    }
+
+    let mut mapped_dirs = Vec::new();
+    for mapping in &options.mapdir {
+        let colon = mapping
+            .find(':')
+            .ok_or_else(|| format!("Can't mount {}: expected <guest-dir>:<host-dir>", mapping))?;
+        let host_dir = PathBuf::from(&mapping[colon + 1..]);
+        let host_dir = host_dir
+            .canonicalize()
+            .map_err(|err| format!("Can't mount {}: {}", host_dir.display(), err))?;
+        mapped_dirs.push((mapping[..colon].to_string(), host_dir));
     }
 
-    let module = webassembly::compile(&wasm_binary[..])
+    // The module gets what's granted and nothing else, whether it declares
+    // what it needs or not. The mounted directories are granted too.
+    let mut granted = wasmer_emscripten::Capabilities {
+        fs: Vec::new(),
+        net: options.allow_net,
+        env: options.allow_env.clone(),
+    };
+    for path in &allow_fs {
+        let path = path
+            .canonicalize()
+            .map_err(|err| format!("Can't grant {}: {}", path.display(), err))?;
+        granted.fs.push(path);
+    }
+    for (guest_dir, _) in &mapped_dirs {
+        granted.fs.push(Path::new("/").join(guest_dir));
+    }
+    if let Some(requested) = wasmer_emscripten::Capabilities::parse(&wasm_binary)
+        .map_err(|e| format!("Can't read the capabilities of the module: {}", e))?
+    {
+        requested
+            .check(&granted)
+            .map_err(|e| format!("Can't instantiate module: {}", e))?;
+    }
+
+    let features = Features {
+        threads: options.enable_threads && !options.disable_threads,
+        simd: options.enable_simd && !options.disable_simd,
+        bulk_memory: options.enable_bulk_memory && !options.disable_bulk_memory,
+        multi_value: options.enable_multi_value && !options.disable_multi_value,
+        reference_types: options.enable_reference_types && !options.disable_reference_types,
+        sign_ext: options.enable_sign_ext && !options.disable_sign_ext,
+    };
+    let mut stack = StackConfig {
+        probes: !options.no_stack_probes,
+        ..StackConfig::default()
+    };
+    if let Some(large_frame_size) = options.warn_frame_size {
+        stack.large_frame_size = large_frame_size;
+        stack.frame_hook = Some(FrameHook::new(|index, frame_size| {
+            eprintln!(
+                "Warning: local function {} has a stack frame of {} bytes",
+                index.index(),
+                frame_size
+            )
+        }));
+    }
+    let config = CompilerConfig {
+        features,
+        stack,
+        ..Default::default()
+    };
+    let module = webassembly::compile_with_config(&wasm_binary[..], config)
         .map_err(|e| format!("Can't compile module: {:?}", e))?;
 
-    let (_abi, import_object, _em_globals) = if wasmer_emscripten::is_emscripten_module(&module) {
-        let mut emscripten_globals = wasmer_emscripten::EmscriptenGlobals::new(&module);
+    let (abi, import_object, _em_globals) = if wasmer_emscripten::is_emscripten_module(&module) {
+        let memory_config = wasmer_emscripten::EmscriptenMemoryConfig {
+            max_memory: run_config.limits.memory_pages.map(Pages),
+            ..wasmer_emscripten::EmscriptenMemoryConfig::for_binary(&wasm_binary, &module)
+        };
+        let mut emscripten_globals =
+            wasmer_emscripten::EmscriptenGlobals::with_config(&module, memory_config);
+        if let Some(limit) = memory_config.max_memory {
+            if emscripten_globals.memory_min > limit {
+                return Err(format!(
+                    "Can't run the module in {} pages of memory: it needs {}",
+                    limit.0, emscripten_globals.memory_min.0
+                ));
+            }
+        }
+        wasmer_emscripten::forward_host_signals();
         (
             InstanceABI::Emscripten,
             wasmer_emscripten::generate_emscripten_env(&mut emscripten_globals),
             Some(emscripten_globals), // TODO Em Globals is here to extend, lifetime, find better solution
         )
+    } else if run_config.limits != Default::default() {
+        // Only the emscripten runtime provides the memory, so only it can
+        // limit it.
+        return Err(format!(
+            "Can't apply the limits of {} to a module that isn't an emscripten module",
+            RUN_CONFIG_FILE
+        ));
     } else {
         (
             InstanceABI::None,
@@ -86,19 +190,258 @@ fn execute_wasm(options: &Run) -> Result<(), String> {
 
     let mut instance = module
         .instantiate(&import_object)
-        .map_err(|e| format!("Can't instantiate module: {:?}", e))?;
+        .map_err(|e| format!("Can't instantiate module: {}", e))?;
 
-    webassembly::run_instance(
+    let mut config = wasmer_emscripten::EmscriptenConfig::default();
+    if let Some(tee_path) = &options.tee_stdout {
+        let tee = wasmer_emscripten::Tee::file(tee_path).map_err(|err| {
+            format!(
+                "Can't create the file {}: {}",
+                tee_path.as_os_str().to_string_lossy(),
+                err
+            )
+        })?;
+        config.stdout_tee = Some(tee);
+    }
+    config.core_dump = options.core_dump.clone();
+    config.abort_hook = Some(wasmer_emscripten::AbortHook::new(|info| {
+        if let Some(message) = &info.message {
+            eprintln!("{}", message);
+        }
+    }));
+    config.networking = options.allow_net;
+    config.mapped_dirs = mapped_dirs;
+    config.capabilities = Some(granted);
+    if !run_config.env.is_empty() {
+        config.env = wasmer_emscripten::EnvPolicy::Private {
+            inherit: vec!["*".to_string()],
+            vars: run_config.env.clone().into_iter().collect(),
+        };
+    }
+
+    webassembly::run_instance_with_config(
         &module,
         &mut instance,
-        options.path.to_str().unwrap(),
-        options.args.iter().map(|arg| arg.as_str()).collect(),
+        wasm_path.to_str().unwrap(),
+        args.iter().map(|arg| arg.as_str()).collect(),
+        config,
     )
-    .map_err(|e| format!("{:?}", e))?;
+    .map_err(|e| {
+        let reason = format!("{:?}", e);
+        // The emscripten runtime writes its own dump, with what it knows
+        // about the guest.
+        if let (InstanceABI::None, Some(path)) = (abi, &options.core_dump) {
+            let dump = CoreDump::capture(instance.context(), &reason);
+            if let Err(err) = dump.save(path) {
+                eprintln!(
+                    "Can't write the core dump {}: {}",
+                    path.as_os_str().to_string_lossy(),
+                    err
+                );
+            }
+        }
+        reason
+    })?;
 
     Ok(())
 }
 
+/// Print a core dump
+fn inspect_core(options: &InspectCore) -> Result<(), String> {
+    let dump = CoreDump::load(&options.path).map_err(|err| {
+        format!(
+            "Can't read the core dump {}: {}",
+            options.path.as_os_str().to_string_lossy(),
+            err
+        )
+    })?;
+    // The lines of 16 bytes of a memory with any that isn't zero.
+    let lines = |memory: &[u8]| -> Vec<(usize, Vec<String>)> {
+        memory
+            .chunks(16)
+            .enumerate()
+            .filter(|(_, line)| line.iter().any(|&byte| byte != 0))
+            .map(|(offset, line)| {
+                let bytes = line.iter().map(|byte| format!("{:02x}", byte)).collect();
+                (offset * 16, bytes)
+            })
+            .collect()
+    };
+
+    if options.output == OutputFormat::Json {
+        let memories: Vec<_> = dump
+            .memories
+            .iter()
+            .map(|memory| {
+                let mut json = json!({ "size": memory.len() });
+                if options.memory {
+                    json["lines"] = lines(memory)
+                        .into_iter()
+                        .map(|(offset, bytes)| json!({ "offset": offset, "bytes": bytes.join("") }))
+                        .collect();
+                }
+                json
+            })
+            .collect();
+        let globals: Vec<_> = dump
+            .globals
+            .iter()
+            .map(|global| match global {
+                Value::I32(value) => json!({ "type": "i32", "value": value }),
+                Value::I64(value) => json!({ "type": "i64", "value": value }),
+                Value::F32(value) => json!({ "type": "f32", "value": value }),
+                Value::F64(value) => json!({ "type": "f64", "value": value }),
+            })
+            .collect();
+        let json = json!({
+            "reason": dump.reason,
+            "memories": memories,
+            "globals": globals,
+            "notes": dump.notes,
+        });
+        println!("{}", json);
+        return Ok(());
+    }
+
+    println!("Reason: {}", dump.reason);
+    for (index, memory) in dump.memories.iter().enumerate() {
+        println!("Memory {}: {} bytes", index, memory.len());
+        if options.memory {
+            for (offset, bytes) in lines(memory) {
+                println!("  {:08x}: {}", offset, bytes.join(" "));
+            }
+        }
+    }
+    for (index, global) in dump.globals.iter().enumerate() {
+        println!("Global {}: {:?}", index, global);
+    }
+    for note in &dump.notes {
+        println!("{}", note);
+    }
+    Ok(())
+}
+
+/// Disassemble a wasm file
+fn print_wat(options: &Wat) -> Result<(), String> {
+    let wasm_binary = read_file_contents(&options.path).map_err(|err| {
+        format!(
+            "Can't read the file {}: {}",
+            options.path.as_os_str().to_string_lossy(),
+            err
+        )
+    })?;
+    let functions = options.function.map(|index| index..index + 1);
+    let text = disassemble(&wasm_binary, functions)
+        .map_err(|e| format!("Can't disassemble the module: {}", e))?;
+    print!("{}", text);
+    Ok(())
+}
+
+/// Print the call graph of a wasm file
+fn analyze(options: &Analyze) -> Result<(), String> {
+    let wasm_binary = read_file_contents(&options.path).map_err(|err| {
+        format!(
+            "Can't read the file {}: {}",
+            options.path.as_os_str().to_string_lossy(),
+            err
+        )
+    })?;
+    let analysis =
+        Analysis::of(&wasm_binary).map_err(|e| format!("Can't analyze the module: {}", e))?;
+    let label = |index: u32| match analysis.function_name(index) {
+        Some(name) => format!("{} ({})", index, name),
+        None => index.to_string(),
+    };
+
+    if options.output == OutputFormat::Json {
+        let function =
+            |index: u32| json!({ "index": index, "name": analysis.function_name(index) });
+        let imports: Vec<_> = analysis
+            .imports
+            .iter()
+            .enumerate()
+            .map(|(index, import)| {
+                let (sites, callers) = analysis.import_usage(index as u32);
+                json!({ "name": import, "call_sites": sites, "callers": callers })
+            })
+            .collect();
+        let calls: Vec<_> = (analysis.imports.len() as u32..analysis.functions)
+            .map(|caller| {
+                let mut json = function(caller);
+                json["callees"] = analysis.callees(caller).collect();
+                json["indirect"] = analysis.indirect_callers.contains(&caller).into();
+                json
+            })
+            .collect();
+        let unreachable: Vec<_> = analysis.unreachable().into_iter().map(function).collect();
+        let json = json!({
+            "imports": imports,
+            "calls": calls,
+            "unreachable": unreachable,
+        });
+        println!("{}", json);
+        return Ok(());
+    }
+
+    println!("Imports:");
+    for (index, import) in analysis.imports.iter().enumerate() {
+        match analysis.import_usage(index as u32) {
+            (0, _) => println!("  {}: unused", import),
+            (sites, callers) => println!(
+                "  {}: {} call sites in {} functions",
+                import, sites, callers
+            ),
+        }
+    }
+
+    println!("Calls:");
+    for caller in analysis.imports.len() as u32..analysis.functions {
+        let callees: Vec<_> = analysis.callees(caller).map(label).collect();
+        if !callees.is_empty() {
+            println!("  {} -> {}", label(caller), callees.join(", "));
+        }
+        if analysis.indirect_callers.contains(&caller) {
+            println!("  {} -> indirect", label(caller));
+        }
+    }
+
+    let unreachable = analysis.unreachable();
+    println!("Unreachable functions: {}", unreachable.len());
+    for function in unreachable {
+        println!("  {}", label(function));
+    }
+    Ok(())
+}
+
+/// Run the jobs of a manifest, and tell whether all of them passed
+fn run_batch(options: &RunBatch) -> Result<bool, String> {
+    let mut batch = Batch::load(&options.manifest).map_err(|err| {
+        format!(
+            "Can't read the manifest {}: {}",
+            options.manifest.as_os_str().to_string_lossy(),
+            err
+        )
+    })?;
+    if let Some(concurrency) = options.concurrency {
+        batch.concurrency = concurrency;
+    }
+    let program =
+        std::env::current_exe().map_err(|err| format!("Can't find the wasmer binary: {}", err))?;
+    let report = batch.run(&program);
+    let json = serde_json::to_string_pretty(&report).unwrap();
+    match &options.report {
+        Some(path) => std::fs::write(path, json).map_err(|err| {
+            format!(
+                "Can't write the report {}: {}",
+                path.as_os_str().to_string_lossy(),
+                err
+            )
+        })?,
+        None => println!("{}", json),
+    }
+    Ok(report.failed == 0)
+}
+
 fn run(options: Run) {
     match execute_wasm(&options) {
         Ok(()) => {}
@@ -113,6 +456,33 @@ fn main() {
     let options = CLIOptions::from_args();
     match options {
         CLIOptions::Run(options) => run(options),
+        CLIOptions::InspectCore(options) => {
+            if let Err(message) = inspect_core(&options) {
+                eprintln!("{:?}", message);
+                exit(1);
+            }
+        }
+        CLIOptions::Wat(options) => {
+            if let Err(message) = print_wat(&options) {
+                eprintln!("{:?}", message);
+                exit(1);
+            }
+        }
+        CLIOptions::Analyze(options) => {
+            if let Err(message) = analyze(&options) {
+                eprintln!("{:?}", message);
+                exit(1);
+            }
+        }
+        CLIOptions::Batch(options) => match run_batch(&options) {
+            Ok(true) => {}
+            // The report says which jobs failed.
+            Ok(false) => exit(1),
+            Err(message) => {
+                eprintln!("{:?}", message);
+                exit(1);
+            }
+        },
         #[cfg(not(target_os = "windows"))]
         CLIOptions::SelfUpdate => update::self_update(),
         #[cfg(target_os = "windows")]
@@ -0,0 +1,216 @@
+// The command line of wasmer, shared by the binary and by the build script
+// that generates its shell completions. Both include it where `PathBuf` and
+// `StructOpt` are in scope.
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "wasmer", about = "Wasm execution runtime.")]
+/// The options for the wasmer Command Line Interface
+enum CLIOptions {
+    /// Run a WebAssembly file. Formats accepted: wasm, wast
+    #[structopt(name = "run")]
+    Run(Run),
+
+    /// Print the contents of a core dump written by `run --core-dump`
+    #[structopt(name = "inspect-core")]
+    InspectCore(InspectCore),
+
+    /// Print a WebAssembly file in the text format
+    #[structopt(name = "wat")]
+    Wat(Wat),
+
+    /// Print the call graph of a WebAssembly file, which imports it uses and
+    /// which functions can't run
+    #[structopt(name = "analyze")]
+    Analyze(Analyze),
+
+    /// Run the jobs of a manifest a few at a time, and print a JSON report
+    /// of how each one went
+    #[structopt(name = "batch")]
+    Batch(RunBatch),
+
+    /// Update wasmer to the latest version
+    #[structopt(name = "self-update")]
+    SelfUpdate,
+}
+
+#[derive(Debug, StructOpt)]
+struct Run {
+    #[structopt(short = "d", long = "debug")]
+    debug: bool,
+
+    /// Input file. Defaults to the module of the run configuration
+    #[structopt(parse(from_os_str))]
+    path: Option<PathBuf>,
+
+    /// Read the options not given on the command line from this run
+    /// configuration, instead of the wasmer.toml of the current directory
+    #[structopt(long = "config", parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Also write the output of the guest to stdout to this file
+    #[structopt(long = "tee-stdout", parse(from_os_str))]
+    tee_stdout: Option<PathBuf>,
+
+    /// Write a core dump of the guest to this file if it traps or aborts
+    #[structopt(long = "core-dump", parse(from_os_str))]
+    core_dump: Option<PathBuf>,
+
+    /// Grant the module access to the files under this path
+    #[structopt(long = "allow-fs", parse(from_os_str))]
+    allow_fs: Vec<PathBuf>,
+
+    /// Mount a host directory in the guest, as <guest-dir>:<host-dir>. The
+    /// guest can then only reach the paths under its mounted directories
+    #[structopt(long = "mapdir")]
+    mapdir: Vec<String>,
+
+    /// Grant the module access to the network
+    #[structopt(long = "allow-net")]
+    allow_net: bool,
+
+    /// Grant the module this environment variable
+    #[structopt(long = "allow-env")]
+    allow_env: Vec<String>,
+
+    /// Enable the wasm proposal for shared memories and atomic operators
+    #[structopt(long = "enable-threads")]
+    enable_threads: bool,
+
+    /// Disable the wasm proposal for shared memories and atomic operators, even if enabled
+    #[structopt(long = "disable-threads")]
+    disable_threads: bool,
+
+    /// Enable the wasm proposal for 128-bit vector operators
+    #[structopt(long = "enable-simd")]
+    enable_simd: bool,
+
+    /// Disable the wasm proposal for 128-bit vector operators, even if enabled
+    #[structopt(long = "disable-simd")]
+    disable_simd: bool,
+
+    /// Enable the wasm proposal for bulk memory operators
+    #[structopt(long = "enable-bulk-memory")]
+    enable_bulk_memory: bool,
+
+    /// Disable the wasm proposal for bulk memory operators, even if enabled
+    #[structopt(long = "disable-bulk-memory")]
+    disable_bulk_memory: bool,
+
+    /// Enable the wasm proposal for multiple return values
+    #[structopt(long = "enable-multi-value")]
+    enable_multi_value: bool,
+
+    /// Disable the wasm proposal for multiple return values, even if enabled
+    #[structopt(long = "disable-multi-value")]
+    disable_multi_value: bool,
+
+    /// Enable the wasm proposal for reference types
+    #[structopt(long = "enable-reference-types")]
+    enable_reference_types: bool,
+
+    /// Disable the wasm proposal for reference types, even if enabled
+    #[structopt(long = "disable-reference-types")]
+    disable_reference_types: bool,
+
+    /// Enable the wasm proposal for sign extension operators
+    #[structopt(long = "enable-sign-ext")]
+    enable_sign_ext: bool,
+
+    /// Disable the wasm proposal for sign extension operators, even if enabled
+    #[structopt(long = "disable-sign-ext")]
+    disable_sign_ext: bool,
+
+    /// Don't probe the pages of large stack frames, which keeps a function
+    /// that overflows the stack from reaching past its guard page
+    #[structopt(long = "no-stack-probes")]
+    no_stack_probes: bool,
+
+    /// Warn about the functions with stack frames larger than this many bytes
+    #[structopt(long = "warn-frame-size")]
+    warn_frame_size: Option<u32>,
+
+    /// Application arguments
+    #[structopt(name = "--", raw(multiple = "true"))]
+    args: Vec<String>,
+}
+
+#[derive(Debug, StructOpt)]
+struct InspectCore {
+    /// Core dump file
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+
+    /// Also print the non-zero bytes of the memories
+    #[structopt(long = "memory")]
+    memory: bool,
+
+    /// How to print the core dump
+    #[structopt(
+        long = "output",
+        default_value = "text",
+        raw(possible_values = "OUTPUT_FORMATS")
+    )]
+    output: OutputFormat,
+}
+
+#[derive(Debug, StructOpt)]
+struct Wat {
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+
+    /// Only print the function of this index, counting the imported ones
+    #[structopt(long = "function")]
+    function: Option<u32>,
+}
+
+#[derive(Debug, StructOpt)]
+struct Analyze {
+    /// Input file
+    #[structopt(parse(from_os_str))]
+    path: PathBuf,
+
+    /// How to print the call graph
+    #[structopt(
+        long = "output",
+        default_value = "text",
+        raw(possible_values = "OUTPUT_FORMATS")
+    )]
+    output: OutputFormat,
+}
+
+#[derive(Debug, StructOpt)]
+struct RunBatch {
+    /// The manifest of the jobs, in JSON
+    #[structopt(parse(from_os_str))]
+    manifest: PathBuf,
+
+    /// How many jobs run at once, instead of what the manifest says
+    #[structopt(long = "concurrency")]
+    concurrency: Option<usize>,
+
+    /// Write the report to this file instead of stdout
+    #[structopt(long = "report", parse(from_os_str))]
+    report: Option<PathBuf>,
+}
+
+const OUTPUT_FORMATS: &[&str] = &["text", "json"];
+
+/// How a subcommand prints what it found: for people, or for scripts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, String> {
+        match format {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("unknown output format {}", format)),
+        }
+    }
+}
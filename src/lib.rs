@@ -4,5 +4,10 @@ extern crate wasmer_runtime_core;
 
 #[macro_use]
 pub mod update;
+pub mod batch;
+pub mod run_config;
+pub mod simple;
 pub mod utils;
 pub mod webassembly;
+
+pub use crate::simple::{SimpleError, SimpleInstance};
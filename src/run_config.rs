@@ -0,0 +1,275 @@
+//! The run configuration of a project, which `wasmer run` reads from a
+//! `wasmer.toml` so the options of a module can be committed with it:
+//!
+//! ```toml
+//! module = "target/app.wasm"
+//! backend = "cranelift"
+//! preopens = ["data", "${HOME}/.cache/app"]
+//! args = ["--verbose"]
+//!
+//! [env]
+//! APP_MODE = "production"
+//!
+//! [limits]
+//! memory_pages = 1024
+//! ```
+//!
+//! `$NAME` and `${NAME}` in any of the strings are replaced with the host
+//! variable `NAME`, and `$$` with `$`. Relative paths are relative to the
+//! directory of the file.
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The name of the file `wasmer run` looks for when it isn't given a
+/// module.
+pub const RUN_CONFIG_FILE: &str = "wasmer.toml";
+
+/// The backends a module can be compiled with.
+const BACKENDS: &[&str] = &["cranelift"];
+
+// The tables come last, as TOML can't have values after them.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RunConfig {
+    /// The module to run.
+    pub module: Option<PathBuf>,
+    /// The compiler the module is compiled with.
+    pub backend: Option<String>,
+    /// The paths the module is granted access to, as with `--allow-fs`.
+    pub preopens: Vec<PathBuf>,
+    /// The arguments the module is run with, unless the command line has
+    /// some.
+    pub args: Vec<String>,
+    /// The variables set in the environment of the module.
+    pub env: BTreeMap<String, String>,
+    /// What the module can use.
+    pub limits: Limits,
+}
+
+/// The resources the module can use.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// The most memory the module can grow to, in 64 KiB pages.
+    pub memory_pages: Option<u32>,
+}
+
+#[derive(Debug)]
+pub enum RunConfigError {
+    Io(PathBuf, std::io::Error),
+    Parse(toml::de::Error),
+    /// A variable that isn't set in the host environment.
+    UnsetVariable(String),
+    UnknownBackend(String),
+}
+
+impl fmt::Display for RunConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunConfigError::Io(path, err) => write!(f, "can't read {}: {}", path.display(), err),
+            RunConfigError::Parse(err) => write!(f, "can't parse the run configuration: {}", err),
+            RunConfigError::UnsetVariable(name) => {
+                write!(f, "the environment variable {} isn't set", name)
+            }
+            RunConfigError::UnknownBackend(name) => write!(
+                f,
+                "unknown backend {}, expected one of: {}",
+                name,
+                BACKENDS.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RunConfigError {}
+
+impl RunConfig {
+    /// Read the configuration at `path`, with its variables expanded from
+    /// the host environment and its paths made relative to the current
+    /// directory.
+    pub fn load(path: &Path) -> Result<Self, RunConfigError> {
+        let text =
+            fs::read_to_string(path).map_err(|err| RunConfigError::Io(path.to_owned(), err))?;
+        let config = Self::parse(&text, |name| env::var(name).ok())?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        Ok(config.relative_to(base))
+    }
+
+    /// Parse a configuration, expanding its variables with `lookup`.
+    pub fn parse<F>(text: &str, lookup: F) -> Result<Self, RunConfigError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let config: RunConfig = toml::from_str(text).map_err(RunConfigError::Parse)?;
+        let expand_path = |path: &PathBuf| -> Result<PathBuf, RunConfigError> {
+            Ok(PathBuf::from(expand(&path.to_string_lossy(), &lookup)?))
+        };
+
+        let backend = match config.backend {
+            Some(backend) => {
+                let backend = expand(&backend, &lookup)?;
+                if !BACKENDS.contains(&backend.as_str()) {
+                    return Err(RunConfigError::UnknownBackend(backend));
+                }
+                Some(backend)
+            }
+            None => None,
+        };
+        Ok(RunConfig {
+            module: match &config.module {
+                Some(module) => Some(expand_path(module)?),
+                None => None,
+            },
+            backend,
+            preopens: config
+                .preopens
+                .iter()
+                .map(expand_path)
+                .collect::<Result<_, _>>()?,
+            args: config
+                .args
+                .iter()
+                .map(|arg| expand(arg, &lookup))
+                .collect::<Result<_, _>>()?,
+            env: config
+                .env
+                .iter()
+                .map(|(name, value)| Ok((name.clone(), expand(value, &lookup)?)))
+                .collect::<Result<_, RunConfigError>>()?,
+            limits: config.limits,
+        })
+    }
+
+    fn relative_to(mut self, base: &Path) -> Self {
+        self.module = self.module.map(|module| base.join(module));
+        self.preopens = self.preopens.iter().map(|path| base.join(path)).collect();
+        self
+    }
+}
+
+/// Replace `$NAME` and `${NAME}` in `text` with the value `lookup` gives
+/// the variable `NAME`.
+fn expand<F>(text: &str, lookup: &F) -> Result<String, RunConfigError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(dollar) = rest.find('$') {
+        expanded.push_str(&rest[..dollar]);
+        rest = &rest[dollar + 1..];
+        let (name, after) = if rest.starts_with('$') {
+            expanded.push('$');
+            rest = &rest[1..];
+            continue;
+        } else if rest.starts_with('{') {
+            match rest.find('}') {
+                Some(close) => (&rest[1..close], &rest[close + 1..]),
+                None => (&rest[1..], ""),
+            }
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or_else(|| rest.len());
+            (&rest[..end], &rest[end..])
+        };
+        if name.is_empty() {
+            expanded.push('$');
+            continue;
+        }
+        match lookup(name) {
+            Some(value) => expanded.push_str(&value),
+            None => return Err(RunConfigError::UnsetVariable(name.to_owned())),
+        }
+        rest = after;
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/guest".to_owned()),
+            "MODE" => Some("debug".to_owned()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn expands_variables() {
+        assert_eq!(
+            expand("${HOME}/cache/$MODE.log", &lookup).unwrap(),
+            "/home/guest/cache/debug.log"
+        );
+        assert_eq!(expand("cost: $$5", &lookup).unwrap(), "cost: $5");
+        assert_eq!(expand("a $ b", &lookup).unwrap(), "a $ b");
+        match expand("$MISSING", &lookup) {
+            Err(RunConfigError::UnsetVariable(name)) => assert_eq!(name, "MISSING"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_a_config() {
+        let config = RunConfig::parse(
+            r#"
+                module = "app.wasm"
+                preopens = ["${HOME}/data"]
+                args = ["--mode", "$MODE"]
+
+                [env]
+                APP_MODE = "$MODE"
+            "#,
+            lookup,
+        )
+        .unwrap()
+        .relative_to(Path::new("project"));
+        assert_eq!(config.module, Some(PathBuf::from("project/app.wasm")));
+        assert_eq!(config.preopens, vec![PathBuf::from("/home/guest/data")]);
+        assert_eq!(config.args, vec!["--mode", "debug"]);
+        assert_eq!(
+            config.env.get("APP_MODE").map(String::as_str),
+            Some("debug")
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_backends_and_fields() {
+        match RunConfig::parse("backend = \"llvm\"", lookup) {
+            Err(RunConfigError::UnknownBackend(name)) => assert_eq!(name, "llvm"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(RunConfig::parse("[limits]\nmemory = 1", lookup).is_err());
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut config = RunConfig {
+            module: Some(PathBuf::from("app.wasm")),
+            backend: Some("cranelift".to_owned()),
+            preopens: vec![PathBuf::from("data")],
+            args: vec!["--verbose".to_owned()],
+            limits: Limits {
+                memory_pages: Some(1024),
+            },
+            ..RunConfig::default()
+        };
+        config
+            .env
+            .insert("APP_MODE".to_owned(), "production".to_owned());
+        let text = toml::to_string(&config).unwrap();
+        assert_eq!(RunConfig::parse(&text, lookup).unwrap(), config);
+        assert_eq!(
+            RunConfig::parse("", lookup).unwrap().limits,
+            Limits::default()
+        );
+    }
+}
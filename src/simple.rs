@@ -0,0 +1,219 @@
+//! A high-level facade for embedding wasmer, meant as the stable
+//! surface that bindings for other languages build on.
+use crate::utils::{detect_legacy_module, is_wasm_binary, wat2wasm, LegacyModule};
+use crate::webassembly::{self, InstanceABI};
+use std::{fmt, fs, io, path::Path, sync::Arc};
+use wasmer_emscripten::{
+    call_emscripten_func, generate_emscripten_env, is_emscripten_module, EmscriptenGlobals,
+    EmscriptenMemoryConfig,
+};
+use wasmer_runtime::{
+    error::{CallError, Error, ResolveError},
+    ImportObject, Instance, Module, Value,
+};
+use wasmer_runtime_core::types::{FuncSig, Type};
+
+/// The ways creating or calling a [`SimpleInstance`] can fail.
+///
+/// [`SimpleInstance`]: struct.SimpleInstance.html
+#[derive(Debug)]
+pub enum SimpleError {
+    Io(io::Error),
+    Wat(String),
+    /// What an older emscripten built instead of a wasm module.
+    Legacy(LegacyModule),
+    UnsupportedABI(&'static str),
+    Wasmer(Error),
+}
+
+impl fmt::Display for SimpleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SimpleError::Io(err) => write!(f, "Can't read the module: {}", err),
+            SimpleError::Wat(msg) => write!(f, "Can't convert from wast to wasm: {}", msg),
+            SimpleError::Legacy(legacy) => write!(f, "Can't run the module: {}", legacy),
+            SimpleError::UnsupportedABI(abi) => write!(f, "{} modules aren't supported", abi),
+            SimpleError::Wasmer(err) => write!(f, "{:?}", err),
+        }
+    }
+}
+
+impl std::error::Error for SimpleError {}
+
+impl From<io::Error> for SimpleError {
+    fn from(err: io::Error) -> Self {
+        SimpleError::Io(err)
+    }
+}
+
+impl From<Error> for SimpleError {
+    fn from(err: Error) -> Self {
+        SimpleError::Wasmer(err)
+    }
+}
+
+impl From<ResolveError> for SimpleError {
+    fn from(err: ResolveError) -> Self {
+        SimpleError::Wasmer(err.into())
+    }
+}
+
+impl From<CallError> for SimpleError {
+    fn from(err: CallError) -> Self {
+        SimpleError::Wasmer(err.into())
+    }
+}
+
+/// A module compiled and instantiated with the imports of the ABI it
+/// was built for.
+///
+/// Only emscripten has imports here. Any other module is instantiated
+/// with an empty `ImportObject`, so one that imports anything fails to
+/// link.
+///
+/// # Usage:
+///
+/// ```no_run
+/// # use wasmer::{SimpleError, SimpleInstance};
+/// # use wasmer_runtime::Value;
+/// # fn run() -> Result<(), SimpleError> {
+/// let mut instance = SimpleInstance::from_file("add.wasm")?;
+/// let result = instance.call("add", &[Value::I32(1), Value::I32(2)])?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct SimpleInstance {
+    abi: InstanceABI,
+    module: Module,
+    instance: Instance,
+    program_name: String,
+    // The emscripten memory and table have to outlive the instance.
+    _emscripten_globals: Option<EmscriptenGlobals>,
+}
+
+impl SimpleInstance {
+    /// Load a `.wasm` or `.wat` file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, SimpleError> {
+        let bytes = fs::read(path.as_ref())?;
+        let mut instance = Self::from_bytes(&bytes)?;
+        instance.program_name = path.as_ref().to_string_lossy().into_owned();
+        Ok(instance)
+    }
+
+    /// Load a module from its binary or text format.
+    ///
+    /// Modules that emscripten didn't build get no imports.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SimpleError> {
+        if let Some(legacy) = detect_legacy_module(bytes) {
+            return Err(SimpleError::Legacy(legacy));
+        }
+        let wasm = if is_wasm_binary(bytes) {
+            bytes.to_vec()
+        } else {
+            wat2wasm(bytes).map_err(SimpleError::Wat)?
+        };
+
+        let module = webassembly::compile(&wasm)?;
+        if is_wasi_module(&module) {
+            return Err(SimpleError::UnsupportedABI("WASI"));
+        }
+
+        let (abi, import_object, emscripten_globals) = if is_emscripten_module(&module) {
+            let mut globals = EmscriptenGlobals::with_config(
+                &module,
+                EmscriptenMemoryConfig::for_binary(&wasm, &module),
+            );
+            let import_object = generate_emscripten_env(&mut globals);
+            (InstanceABI::Emscripten, import_object, Some(globals))
+        } else {
+            (InstanceABI::None, ImportObject::new(), None)
+        };
+        let instance = module.instantiate(&import_object)?;
+
+        Ok(SimpleInstance {
+            abi,
+            module,
+            instance,
+            program_name: "wasm".to_string(),
+            _emscripten_globals: emscripten_globals,
+        })
+    }
+
+    /// The ABI that was detected for the module.
+    pub fn abi(&self) -> InstanceABI {
+        self.abi
+    }
+
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    pub fn instance(&mut self) -> &mut Instance {
+        &mut self.instance
+    }
+
+    /// Call the export `name`.
+    ///
+    /// Parameters are converted to the types the export expects when
+    /// that can be done without losing precision, so an `i32` can be
+    /// passed for an `i64` or an `f64` parameter.
+    pub fn call(&mut self, name: &str, params: &[Value]) -> Result<Vec<Value>, SimpleError> {
+        let params = {
+            let func = self.instance.dyn_func(name)?;
+            convert_params(func.signature(), params)?
+        };
+
+        let results = match self.abi {
+            InstanceABI::Emscripten => call_emscripten_func(&mut self.instance, name, &params)?,
+            InstanceABI::None => self.instance.call(name, &params)?,
+        };
+        Ok(results)
+    }
+
+    /// Run the module's `main` function with `args`.
+    pub fn run(&mut self, args: &[&str]) -> Result<(), SimpleError> {
+        webassembly::run_instance(
+            &self.module,
+            &mut self.instance,
+            &self.program_name,
+            args.to_vec(),
+        )?;
+        Ok(())
+    }
+}
+
+fn is_wasi_module(module: &Module) -> bool {
+    let info = &module.0.info;
+    info.imported_functions.iter().any(|(_, import_name)| {
+        info.namespace_table
+            .get(import_name.namespace_index)
+            .starts_with("wasi_")
+    })
+}
+
+fn convert_params(signature: &FuncSig, params: &[Value]) -> Result<Vec<Value>, CallError> {
+    let mismatch = || ResolveError::Signature {
+        expected: Arc::new(signature.clone()),
+        found: params.iter().map(Value::ty).collect(),
+    };
+
+    if signature.params().len() != params.len() {
+        return Err(mismatch().into());
+    }
+
+    signature
+        .params()
+        .iter()
+        .zip(params)
+        .map(|(ty, value)| {
+            let value = match (ty, value) {
+                (_, value) if value.ty() == *ty => value.clone(),
+                (Type::I64, Value::I32(n)) => Value::I64(i64::from(*n)),
+                (Type::F64, Value::I32(n)) => Value::F64(f64::from(*n)),
+                (Type::F64, Value::F32(n)) => Value::F64(f64::from(*n)),
+                _ => return Err(mismatch().into()),
+            };
+            Ok(value)
+        })
+        .collect()
+}
@@ -1,6 +1,131 @@
 //! Utility functions for the WebAssembly module
+use std::fmt;
+
+/// The version of the binary format every engine since the MVP reads.
+const WASM_VERSION: u32 = 1;
+
+/// The versions the drafts before the MVP gave their binaries.
+const DRAFT_VERSIONS: (u32, u32) = (0xa, 0xd);
 
 /// Detect if a provided binary is a Wasm file
 pub fn is_wasm_binary(binary: &[u8]) -> bool {
     binary.starts_with(&[b'\0', b'a', b's', b'm'])
 }
+
+/// Convert a module in the text format to the binary one.
+#[cfg(feature = "wat")]
+pub fn wat2wasm(text: &[u8]) -> Result<Vec<u8>, String> {
+    wabt::wat2wasm(text).map_err(|err| format!("{:?}", err))
+}
+
+#[cfg(not(feature = "wat"))]
+pub fn wat2wasm(_text: &[u8]) -> Result<Vec<u8>, String> {
+    Err("this build of wasmer only accepts modules in the binary format".to_string())
+}
+
+/// What emscripten built instead of a wasm module wasmer can run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegacyModule {
+    /// asm.js, from a build without `-s WASM=1`.
+    AsmJs,
+    /// The asm.js half of a `--separate-asm` build.
+    SeparateAsm,
+    /// The JavaScript that loads a wasm module, rather than the module.
+    EmscriptenLoader,
+    /// A binary of a draft of wasm from before the MVP, which only the
+    /// old binaryen backends wrote.
+    PreMvp { version: u32 },
+}
+
+impl fmt::Display for LegacyModule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LegacyModule::AsmJs => write!(
+                f,
+                "this is asm.js, not wasm; rebuild it with `emcc -s WASM=1`"
+            ),
+            LegacyModule::SeparateAsm => write!(
+                f,
+                "this is the asm.js of a `--separate-asm` build, not wasm; \
+                 rebuild it with `emcc -s WASM=1` and without `--separate-asm`"
+            ),
+            LegacyModule::EmscriptenLoader => write!(
+                f,
+                "this is the JavaScript emscripten generates to load a module; \
+                 run the `.wasm` file next to it instead"
+            ),
+            LegacyModule::PreMvp { version } => write!(
+                f,
+                "this is a binary of version {:#x} of a wasm draft, but only version {} \
+                 is supported; rebuild it with a newer emscripten",
+                version, WASM_VERSION
+            ),
+        }
+    }
+}
+
+/// Recognize what older emscripten toolchains output in place of a wasm
+/// module, so it can be reported with how to rebuild it rather than as a
+/// module that doesn't parse.
+pub fn detect_legacy_module(binary: &[u8]) -> Option<LegacyModule> {
+    if is_wasm_binary(binary) {
+        let version = binary.get(4..8)?;
+        let version = u32::from(version[0])
+            | u32::from(version[1]) << 8
+            | u32::from(version[2]) << 16
+            | u32::from(version[3]) << 24;
+        return if version >= DRAFT_VERSIONS.0 && version <= DRAFT_VERSIONS.1 {
+            Some(LegacyModule::PreMvp { version })
+        } else {
+            None
+        };
+    }
+
+    let text = String::from_utf8_lossy(binary);
+    // The text format always starts with a comment or a `(module`.
+    if text.trim_start().starts_with(|c| c == '(' || c == ';') {
+        return None;
+    }
+    let asm = ["\"use asm\"", "'use asm'", "\"almost asm\"", "'almost asm'"]
+        .iter()
+        .any(|directive| text.contains(directive));
+    let loader = text.contains("Module[") || text.contains("var Module");
+    match (asm, loader) {
+        (true, true) if text.contains("Module[\"asm\"]") && !text.contains("function run(") => {
+            Some(LegacyModule::SeparateAsm)
+        }
+        (true, _) => Some(LegacyModule::AsmJs),
+        (false, true) => Some(LegacyModule::EmscriptenLoader),
+        (false, false) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_legacy_modules() {
+        assert_eq!(detect_legacy_module(b"\0asm\x01\0\0\0"), None);
+        assert_eq!(
+            detect_legacy_module(b"\0asm\x0d\0\0\0"),
+            Some(LegacyModule::PreMvp { version: 0xd })
+        );
+        assert_eq!(detect_legacy_module(b"(module (func))"), None);
+        assert_eq!(detect_legacy_module(b";; \"use asm\"\n(module)"), None);
+        assert_eq!(
+            detect_legacy_module(
+                b"var Module = {};\nfunction asm(g, e, b) { \"use asm\"; }\nfunction run() {}"
+            ),
+            Some(LegacyModule::AsmJs)
+        );
+        assert_eq!(
+            detect_legacy_module(b"Module[\"asm\"] = (function(g, e, b) { \"almost asm\"; });"),
+            Some(LegacyModule::SeparateAsm)
+        );
+        assert_eq!(
+            detect_legacy_module(b"var Module = typeof Module !== 'undefined' ? Module : {};"),
+            Some(LegacyModule::EmscriptenLoader)
+        );
+    }
+}
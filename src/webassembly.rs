@@ -1,11 +1,14 @@
 use std::panic;
+use wasmer_clif_backend::CraneliftCompiler;
 use wasmer_runtime::{
     self as runtime,
     error::{CallResult, Result},
-    ImportObject, Instance, Module,
+    CompilerConfig, ImportObject, Instance, Module,
 };
 
-use wasmer_emscripten::{is_emscripten_module, run_emscripten_instance};
+use wasmer_emscripten::{
+    is_emscripten_module, run_emscripten_instance_with_config, EmscriptenConfig,
+};
 
 pub struct ResultObject {
     /// A webassembly::Module object representing the compiled WebAssembly module.
@@ -16,7 +19,7 @@ pub struct ResultObject {
     pub instance: Box<Instance>,
 }
 
-#[derive(PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum InstanceABI {
     Emscripten,
     None,
@@ -75,6 +78,13 @@ pub fn compile(buffer_source: &[u8]) -> Result<Module> {
     Ok(module)
 }
 
+/// The same as `compile`, but with `config`, e.g. to enable the wasm
+/// features the module uses.
+pub fn compile_with_config(buffer_source: &[u8], config: CompilerConfig) -> Result<Module> {
+    let module = runtime::compile_with_config(buffer_source, &CraneliftCompiler::new(), config)?;
+    Ok(module)
+}
+
 /// Performs common instance operations needed when an instance is first run
 /// including data setup, handling arguments and calling a main function
 pub fn run_instance(
@@ -82,9 +92,20 @@ pub fn run_instance(
     instance: &mut Instance,
     path: &str,
     args: Vec<&str>,
+) -> CallResult<()> {
+    run_instance_with_config(module, instance, path, args, EmscriptenConfig::default())
+}
+
+/// Like `run_instance`, with `config` for an emscripten module.
+pub fn run_instance_with_config(
+    module: &Module,
+    instance: &mut Instance,
+    path: &str,
+    args: Vec<&str>,
+    config: EmscriptenConfig,
 ) -> CallResult<()> {
     if is_emscripten_module(module) {
-        run_emscripten_instance(module, instance, path, args)?;
+        run_emscripten_instance_with_config(module, instance, path, args, config)?;
     } else {
         instance.call("main", &[])?;
     };